@@ -1,36 +1,786 @@
-use serde::Deserialize;
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use anyhow::Context;
+use log::{info, warn};
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use serde::{de::DeserializeOwned, Deserialize};
+use tokio::sync::mpsc;
+
+use crate::{
+    engine::{symbols::IconSet, trash::TrashKind},
+    jobs::JobKind,
+};
 
 #[derive(Deserialize, Debug)]
 pub struct Config {
     pub colors: color::ColorConfig,
+    /// Named color themes, that can be selected with `colors.theme` or cycled through
+    #[serde(default)]
+    pub themes: Vec<color::ThemeConfig>,
     pub general: GeneralConfig,
+    #[serde(default)]
+    pub cache: CacheConfig,
+    #[serde(default)]
+    pub statusline: StatusLineConfig,
+    #[serde(default)]
+    pub watcher: WatcherConfig,
+    #[serde(default)]
+    pub preview: PreviewConfig,
+    #[serde(default)]
+    pub notifications: NotificationConfig,
+    #[serde(default)]
+    pub tags: TagConfig,
+    /// Custom entries of the context menu
+    #[serde(default)]
+    pub actions: Vec<ActionConfig>,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, Default)]
 pub struct GeneralConfig {
     pub use_trash: bool,
+    /// Which trash is used, setting it activates the trash (see [`TrashKind::select`])
+    pub trash: Option<TrashKind>,
+    /// Ask before items are moved to the trash (deleting without the trash always asks)
+    pub confirm_trash: Option<bool>,
+    pub sort: Option<SortMode>,
+    /// Directory that rfm starts in, if no path is given on the command line
+    pub start_dir: Option<String>,
+    /// Weather or not `next`/`previous` continue at the other end of the directory
+    pub search_wrap: Option<bool>,
+    /// Weather or not `up` at the top of a directory continues at the bottom (and vice versa)
+    pub cursor_wrap: Option<bool>,
+    /// Capture the mouse, so that a right-click opens the context menu
+    pub mouse: Option<bool>,
+    /// Show tips about the keybindings in the footer, while rfm is idle
+    pub hints: Option<bool>,
+    /// Milliseconds after the last key, after which an unfinished key sequence is dropped (0 = never)
+    pub key_timeout: Option<u64>,
+    /// Milliseconds after the last key of an unfinished sequence, after which a popup lists its continuations (0 = never)
+    pub key_popup: Option<u64>,
+    /// Let the center column grow with the longest filename (taking space from the preview)
+    pub autosize: Option<bool>,
+    /// Maximum width of the auto-sized center column in percent of the terminal width
+    pub autosize_max: Option<u16>,
+    /// Columns that are shown at startup: "miller", "dual" or "single"
+    pub layout: Option<LayoutPreset>,
+    /// Show the free space of the current filesystem in the footer
+    pub disk_usage: Option<bool>,
+    /// Usage of the filesystem in percent, above which the free space is shown as a warning
+    pub disk_warning: Option<u8>,
+    /// Deleting, cutting or overwriting these paths (or items directly inside) must be confirmed
+    pub protected_paths: Option<Vec<String>>,
+    /// Also protect the mount points of all mounted filesystems
+    pub protect_mounts: Option<bool>,
+    /// Listen for commands of external tools on a unix socket (see `rfm --send`)
+    pub server: Option<bool>,
+    /// Icons in front of the names: "auto", "ascii", "unicode" or "nerd"
+    pub icons: Option<IconSet>,
+    /// Terminal emulator that is opened in the current directory (defaults to `$TERMINAL`)
+    pub terminal: Option<String>,
+    /// Exchange copied files with graphical applications (requires `wl-copy`/`wl-paste` or `xclip`)
+    pub system_clipboard: Option<bool>,
+}
+
+/// Merges the `overlay` into `base`.
+///
+/// Tables are merged recursively, all other values (including arrays) are replaced.
+fn merge(base: &mut toml::Value, overlay: toml::Value) {
+    match (base, overlay) {
+        (toml::Value::Table(base), toml::Value::Table(overlay)) => {
+            for (key, value) in overlay {
+                match base.get_mut(&key) {
+                    Some(existing) => merge(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overlay) => *base = overlay,
+    }
+}
+
+/// Config files that are read again when they change.
+///
+/// Only the colors of the `config.toml` are applied, its other settings need a restart.
+pub const RELOADED_FILES: [&str; 3] = ["config.toml", "keys.toml", "open.toml"];
+
+/// Location of the config files, so that they can be read again while rfm is running
+#[derive(Debug, Clone)]
+pub struct ConfigFiles {
+    pub dir: PathBuf,
+    /// Directory of the selected profile, whose files are applied on top (see [`read_with_profile`])
+    pub profile_dir: Option<PathBuf>,
+}
+
+impl ConfigFiles {
+    /// Reads and parses a config file (with the profile applied on top of it).
+    pub fn read<T: DeserializeOwned>(&self, name: &str) -> anyhow::Result<T> {
+        let file = self.dir.join(name);
+        let content = read_with_profile(&file, self.profile_dir.as_deref())
+            .with_context(|| format!("cannot read {}", file.display()))?;
+        toml::from_str(&content).with_context(|| format!("invalid {}", file.display()))
+    }
+
+    /// Sends a message to `tx`, whenever one of the [`RELOADED_FILES`] changes.
+    pub fn watch(&self, tx: mpsc::UnboundedSender<()>) -> notify::Result<RecommendedWatcher> {
+        let mut watcher =
+            notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+                let Ok(event) = event else {
+                    return;
+                };
+                let changed = event.paths.iter().any(|path| {
+                    path.file_name()
+                        .and_then(|name| name.to_str())
+                        .is_some_and(|name| RELOADED_FILES.contains(&name))
+                });
+                if changed && !event.kind.is_access() {
+                    let _ = tx.send(());
+                }
+            })?;
+        for dir in std::iter::once(&self.dir).chain(&self.profile_dir) {
+            watcher.watch(dir, RecursiveMode::NonRecursive)?;
+        }
+        Ok(watcher)
+    }
+}
+
+/// Reads a config file and applies the file with the same name from the profile directory on top of it.
+///
+/// Values that are not set in the profile are taken from the base config.
+/// If the profile file is missing or invalid, the base config is returned unchanged.
+pub fn read_with_profile(file: &Path, profile_dir: Option<&Path>) -> std::io::Result<String> {
+    let content = std::fs::read_to_string(file)?;
+    let Some(profile_file) = profile_dir.and_then(|dir| file.file_name().map(|f| dir.join(f)))
+    else {
+        return Ok(content);
+    };
+    let Ok(overlay) = std::fs::read_to_string(&profile_file) else {
+        return Ok(content);
+    };
+    let parsed = content
+        .parse::<toml::Value>()
+        .and_then(|base| Ok((base, overlay.parse::<toml::Value>()?)));
+    match parsed {
+        Ok((mut base, overlay)) => {
+            info!("Using profile config: {}", profile_file.display());
+            merge(&mut base, overlay);
+            toml::to_string(&base).map_err(std::io::Error::other)
+        }
+        Err(e) => {
+            warn!("Ignoring {}: {e}", profile_file.display());
+            Ok(content)
+        }
+    }
+}
+
+/// Configuration of the background crawler, that fills the caches with panels
+/// for the directories below the current one.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct CacheConfig {
+    /// Maximum directory depth that the crawler descends into
+    pub depth: usize,
+    /// Maximum number of entries that are visited per crawl
+    pub max_entries: usize,
+    /// Maximum number of crawls that run at the same time
+    pub concurrency: usize,
+    /// Approximate memory (in MiB) of all cached panels
+    pub max_memory: usize,
+    /// Seconds after which cached panels are read again (0 keeps them forever)
+    pub ttl: u64,
+}
+
+impl CacheConfig {
+    /// Time to live of the cached panels
+    pub fn ttl(&self) -> Option<Duration> {
+        (self.ttl > 0).then(|| Duration::from_secs(self.ttl))
+    }
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        CacheConfig {
+            depth: 2,
+            max_entries: 4096,
+            concurrency: 1,
+            max_memory: 512,
+            ttl: 600,
+        }
+    }
+}
+
+/// Limits of the file previews, so that huge files cannot block the preview,
+/// and the style of directory previews.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct PreviewConfig {
+    /// Files larger than this (in bytes) are not given to `bat`,
+    /// instead only their beginning is shown (without syntax highlighting)
+    pub max_file_size: u64,
+    /// Maximum number of lines of a text preview
+    pub max_lines: usize,
+    /// Show directories as a tree (two levels deep) instead of a flat listing
+    pub tree: bool,
+    /// Maximum number of entries per directory in the tree preview
+    pub tree_entries: usize,
+    /// Generate previews at all (see `--no-previews`)
+    pub enabled: bool,
+    /// Different previews for some file extensions (like the end of `.log` files)
+    pub extensions: HashMap<String, ExtensionPreview>,
+    /// Number of frames of a video, that are shown one after another (1 shows a single thumbnail)
+    pub video_frames: usize,
+}
+
+/// Preview settings for the files with one extension
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct ExtensionPreview {
+    #[serde(default)]
+    pub mode: PreviewMode,
+    /// Replaces `max_lines` of the preview config
+    pub max_lines: Option<usize>,
+}
+
+/// Which part of a text file is previewed
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PreviewMode {
+    /// The beginning of the file
+    #[default]
+    Head,
+    /// The end of the file (e.g. for log files)
+    Tail,
+}
+
+impl Default for PreviewConfig {
+    fn default() -> Self {
+        PreviewConfig {
+            max_file_size: 10 * 1024 * 1024,
+            max_lines: 128,
+            tree: false,
+            tree_entries: 32,
+            enabled: true,
+            extensions: HashMap::new(),
+            video_frames: 10,
+        }
+    }
+}
+
+/// Configuration of the file-watchers, that refresh the panels when a directory changes.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct WatcherConfig {
+    /// Filesystem types (as listed in `/proc/mounts`), on which directories are polled,
+    /// because inotify does not report their changes
+    pub poll_filesystems: Vec<String>,
+    /// Seconds between two polls
+    pub poll_interval: f64,
+    /// Seconds without further changes, before a changed directory is read again
+    /// (so that a burst of changes causes a single reload)
+    pub debounce: f64,
+    /// Refresh the panels automatically (see `--no-watchers`)
+    pub enabled: bool,
+}
+
+impl Default for WatcherConfig {
+    fn default() -> Self {
+        WatcherConfig {
+            poll_filesystems: [
+                "nfs",
+                "nfs4",
+                "cifs",
+                "smb3",
+                "smbfs",
+                "9p",
+                "ceph",
+                "fuse.sshfs",
+                "fuse.rclone",
+                "fuse.s3fs",
+                "fuse.davfs",
+            ]
+            .map(String::from)
+            .to_vec(),
+            poll_interval: 2.0,
+            debounce: 0.2,
+            enabled: true,
+        }
+    }
+}
+
+/// Announcements of finished background jobs (see `jobs::set_notifications`).
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct NotificationConfig {
+    /// Show a desktop notification (requires `notify-send`)
+    pub desktop: bool,
+    /// Ring the terminal bell
+    pub bell: bool,
+    /// Seconds that a job has to run, before its end is announced
+    pub min_duration: u64,
+    /// Kinds of jobs, that are announced
+    pub jobs: Vec<JobKind>,
+}
+
+impl Default for NotificationConfig {
+    fn default() -> Self {
+        NotificationConfig {
+            desktop: false,
+            bell: false,
+            min_duration: 10,
+            jobs: vec![JobKind::FileOperation, JobKind::Checksum, JobKind::Search],
+        }
+    }
+}
+
+/// Colors of the tags (see `engine::tags`), tags without a color get one of a fixed palette.
+#[derive(Deserialize, Debug, Default)]
+pub struct TagConfig {
+    pub colors: HashMap<String, String>,
+}
+
+/// Custom entry of the context menu (see `engine::actions`).
+#[derive(Deserialize, Debug, Clone)]
+pub struct ActionConfig {
+    /// Label in the menu
+    pub name: String,
+    /// Key that runs the action directly when the menu is open
+    pub key: Option<char>,
+    /// Shell command. The selected items are passed as arguments (`$1`, `"$@"`)
+    pub command: String,
+    /// Run the command inside of the terminal and wait for it to finish
+    #[serde(default)]
+    pub terminal: bool,
+    /// Only show the action for this mime-type (e.g. "image" or "image/png")
+    pub mime: Option<String>,
+    /// Only show the action for these file extensions
+    pub extensions: Option<Vec<String>>,
+    /// Only show the action for directories (`true`) or files (`false`)
+    pub directory: Option<bool>,
+}
+
+/// Templates for the header, footer and terminal title (see `panel::template`).
+///
+/// Unset templates keep the built-in layout.
+#[derive(Deserialize, Debug, Default)]
+pub struct StatusLineConfig {
+    pub header: Option<String>,
+    pub footer: Option<String>,
+    pub footer_right: Option<String>,
+    /// Title of the terminal window (defaults to `rfm: {short_dir}`)
+    pub title: Option<String>,
+    /// Set to `false` to leave the title of the terminal alone
+    pub set_title: Option<bool>,
+}
+
+/// Defines how the elements of a directory are sorted.
+///
+/// Directories are always listed before files.
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SortMode {
+    /// Case-insensitive sorting by name
+    #[default]
+    Lowercase,
+    /// Case-insensitive sorting by name, where numbers are compared by their value
+    ///
+    /// This sorts "file2" before "file10".
+    Natural,
+    /// Case-insensitive sorting according to the unicode collation algorithm
+    ///
+    /// This sorts accented characters next to their base character (e.g. "Ä" next to "A").
+    Locale,
+}
+
+impl SortMode {
+    /// The next sort mode, when cycling through all of them
+    pub fn next(self) -> Self {
+        match self {
+            SortMode::Lowercase => SortMode::Natural,
+            SortMode::Natural => SortMode::Locale,
+            SortMode::Locale => SortMode::Lowercase,
+        }
+    }
+}
+
+/// Defines which columns are shown and how wide they are.
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum LayoutPreset {
+    /// Parent directory, current directory and preview
+    #[default]
+    Miller,
+    /// Current directory and preview, side by side
+    Dual,
+    /// Only the current directory in full width
+    Single,
+}
+
+impl LayoutPreset {
+    /// Relative widths of the parent, center and preview column (a zero hides the column)
+    pub fn ratios(self) -> [u16; 3] {
+        match self {
+            LayoutPreset::Miller => [1, 3, 4],
+            LayoutPreset::Dual => [0, 1, 1],
+            LayoutPreset::Single => [0, 1, 0],
+        }
+    }
+
+    /// The next layout, when cycling through all of them
+    pub fn next(self) -> Self {
+        match self {
+            LayoutPreset::Miller => LayoutPreset::Dual,
+            LayoutPreset::Dual => LayoutPreset::Single,
+            LayoutPreset::Single => LayoutPreset::Miller,
+        }
+    }
+}
+
+impl std::fmt::Display for LayoutPreset {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LayoutPreset::Miller => write!(f, "miller"),
+            LayoutPreset::Dual => write!(f, "dual"),
+            LayoutPreset::Single => write!(f, "single"),
+        }
+    }
+}
+
+impl std::fmt::Display for SortMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SortMode::Lowercase => write!(f, "lowercase"),
+            SortMode::Natural => write!(f, "natural"),
+            SortMode::Locale => write!(f, "locale"),
+        }
+    }
 }
 
 pub mod color {
-    use anyhow::{anyhow, Context, Result};
-    use crossterm::style::{Color, PrintStyledContent, Stylize};
+    use std::{
+        collections::HashMap,
+        io::{Read, Write},
+        os::unix::fs::FileTypeExt,
+        path::Path,
+        sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+        time::{Duration, Instant},
+    };
+
+    use anyhow::{anyhow, bail, Context, Result};
+    use crossterm::style::{Attribute, Color, ContentStyle, PrintStyledContent, Stylize};
+    use log::{info, warn};
     use once_cell::sync::OnceCell;
+    use parking_lot::{Mutex, RwLock};
     use serde::Deserialize;
 
-    pub static COLOR_MAIN: OnceCell<Color> = OnceCell::new();
-    pub static COLOR_MARKED: OnceCell<Color> = OnceCell::new();
-    pub static COLOR_HIGHLIGHT: OnceCell<Color> = OnceCell::new();
-    pub static COLOR_DIR_PATH: OnceCell<Color> = OnceCell::new();
+    use crate::util::wait_readable;
+
+    /// Themes can be replaced, when the config is reloaded
+    static THEMES: RwLock<Vec<Theme>> = RwLock::new(Vec::new());
+    static ACTIVE_THEME: AtomicUsize = AtomicUsize::new(0);
+    /// The color depth and `$LS_COLORS` are only read once
+    static COLOR_DEPTH: OnceCell<ColorDepth> = OnceCell::new();
+    /// Indices of the light and the dark theme, if the theme depends on the terminal background
+    static AUTO_THEMES: Mutex<Option<(usize, usize)>> = Mutex::new(None);
+    /// Weather or not the terminal has a light background (see [`detect_theme`])
+    static LIGHT_BACKGROUND: AtomicBool = AtomicBool::new(false);
+    pub static LS_COLORS: OnceCell<LsColors> = OnceCell::new();
+
+    /// Name of the theme, that is defined directly in the `[colors]` section
+    const CUSTOM_THEME: &str = "custom";
+
+    /// Picks the light or the dark theme, depending on the background of the terminal
+    const AUTO_THEME: &str = "auto";
+
+    /// How long we wait for the terminal to tell us its background color
+    const BACKGROUND_TIMEOUT: Duration = Duration::from_millis(200);
 
     #[derive(Deserialize, Debug)]
     pub struct ColorConfig {
+        main: Option<String>,
+        marked: Option<String>,
+        highlight: Option<String>,
+        dir_path: Option<String>,
+        /// Name of the theme that is used at startup (or "auto")
+        theme: Option<String>,
+        /// Theme for terminals with a light background, if the theme is "auto"
+        light_theme: Option<String>,
+        /// Theme for terminals with a dark background, if the theme is "auto"
+        dark_theme: Option<String>,
+        /// Colors that the terminal supports (detected from `$COLORTERM` and `$TERM` if unset)
+        depth: Option<ColorDepth>,
+        /// Style the directory entries with `$LS_COLORS` (as set by `dircolors`)
+        #[serde(default)]
+        ls_colors: bool,
+    }
+
+    /// Named color theme of the config (`[[themes]]`)
+    #[derive(Deserialize, Debug)]
+    pub struct ThemeConfig {
+        name: String,
         main: String,
         marked: String,
         highlight: String,
         dir_path: String,
     }
 
-    fn extract_color(string: String) -> Result<Color> {
+    /// Number of colors, that the terminal can show
+    #[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum ColorDepth {
+        /// 24-bit colors
+        #[serde(rename = "truecolor")]
+        TrueColor,
+        /// The 256 colors of xterm
+        #[serde(rename = "256")]
+        Ansi256,
+        /// The 16 standard colors
+        #[serde(rename = "16")]
+        Ansi16,
+    }
+
+    impl ColorDepth {
+        /// Guesses the color depth of the terminal from the environment
+        fn detect() -> Self {
+            let colorterm = std::env::var("COLORTERM").unwrap_or_default();
+            let term = std::env::var("TERM").unwrap_or_default();
+            if colorterm == "truecolor" || colorterm == "24bit" {
+                ColorDepth::TrueColor
+            } else if term.contains("256color") {
+                ColorDepth::Ansi256
+            } else {
+                ColorDepth::Ansi16
+            }
+        }
+    }
+
+    /// Colors of the user interface
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct Theme {
+        pub name: String,
+        pub main: Color,
+        pub marked: Color,
+        pub highlight: Color,
+        pub dir_path: Color,
+    }
+
+    impl Theme {
+        fn from_config(config: ThemeConfig) -> Result<Self> {
+            let context =
+                |what: &str| format!("Failed to set '{what}' color of theme '{}'", config.name);
+            Ok(Theme {
+                main: parse_color(&config.main).with_context(|| context("main"))?,
+                marked: parse_color(&config.marked).with_context(|| context("marked"))?,
+                highlight: parse_color(&config.highlight).with_context(|| context("highlight"))?,
+                dir_path: parse_color(&config.dir_path).with_context(|| context("dir_path"))?,
+                name: config.name,
+            })
+        }
+
+        /// Converts all colors, so that the terminal can show them
+        fn degrade(self, depth: ColorDepth) -> Self {
+            Theme {
+                main: degrade(self.main, depth),
+                marked: degrade(self.marked, depth),
+                highlight: degrade(self.highlight, depth),
+                dir_path: degrade(self.dir_path, depth),
+                name: self.name,
+            }
+        }
+    }
+
+    /// Themes that are always available
+    fn builtin_themes() -> Vec<Theme> {
+        let rgb = |r, g, b| Color::Rgb { r, g, b };
+        vec![
+            Theme {
+                name: "default".into(),
+                main: Color::DarkGreen,
+                marked: Color::DarkYellow,
+                highlight: Color::Red,
+                dir_path: Color::DarkBlue,
+            },
+            Theme {
+                name: "light".into(),
+                main: rgb(0x00, 0x5f, 0x87),
+                marked: rgb(0xaf, 0x5f, 0x00),
+                highlight: rgb(0xd7, 0x00, 0x00),
+                dir_path: rgb(0x5f, 0x00, 0xaf),
+            },
+            Theme {
+                name: "gruvbox".into(),
+                main: rgb(0xb8, 0xbb, 0x26),
+                marked: rgb(0xfa, 0xbd, 0x2f),
+                highlight: rgb(0xfb, 0x49, 0x34),
+                dir_path: rgb(0x83, 0xa5, 0x98),
+            },
+            Theme {
+                name: "nord".into(),
+                main: rgb(0x88, 0xc0, 0xd0),
+                marked: rgb(0xeb, 0xcb, 0x8b),
+                highlight: rgb(0xbf, 0x61, 0x6a),
+                dir_path: rgb(0x81, 0xa1, 0xc1),
+            },
+            Theme {
+                name: "solarized".into(),
+                main: rgb(0x85, 0x99, 0x00),
+                marked: rgb(0xb5, 0x89, 0x00),
+                highlight: rgb(0xdc, 0x32, 0x2f),
+                dir_path: rgb(0x26, 0x8b, 0xd2),
+            },
+        ]
+    }
+
+    /// File styles from `$LS_COLORS`.
+    ///
+    /// Only the file types and the patterns of the form `*<suffix>` are used.
+    #[derive(Debug, Default)]
+    pub struct LsColors {
+        /// Styles of the file types (like `di` for directories or `ex` for executables)
+        types: HashMap<String, ContentStyle>,
+        /// Lowercase suffixes (like `.tar.gz`), the longest one first
+        suffixes: Vec<(String, ContentStyle)>,
+    }
+
+    impl LsColors {
+        pub fn parse(value: &str) -> Self {
+            let mut colors = LsColors::default();
+            for (key, codes) in value.split(':').filter_map(|entry| entry.split_once('=')) {
+                let style = sgr_style(codes);
+                match key.strip_prefix('*') {
+                    Some(suffix) => colors.suffixes.push((suffix.to_lowercase(), style)),
+                    None => {
+                        colors.types.insert(key.to_string(), style);
+                    }
+                }
+            }
+            colors
+                .suffixes
+                .sort_by_key(|(suffix, _)| std::cmp::Reverse(suffix.len()));
+            colors
+        }
+
+        /// Returns the style of the file, like `ls` would choose it.
+        ///
+        /// Special files use the style of their type, executables use `ex`,
+        /// and all other files the style of the longest matching suffix (or `fi`).
+        pub fn style(&self, path: &Path, is_executable: bool) -> Option<ContentStyle> {
+            let file_type = path.symlink_metadata().ok()?.file_type();
+            let key = if file_type.is_symlink() {
+                // Broken links use "or", if it is set
+                match path.exists() {
+                    false if self.types.contains_key("or") => "or",
+                    _ => "ln",
+                }
+            } else if file_type.is_dir() {
+                "di"
+            } else if file_type.is_fifo() {
+                "pi"
+            } else if file_type.is_socket() {
+                "so"
+            } else if file_type.is_block_device() {
+                "bd"
+            } else if file_type.is_char_device() {
+                "cd"
+            } else if is_executable && self.types.contains_key("ex") {
+                "ex"
+            } else {
+                let name = path.file_name()?.to_string_lossy().to_lowercase();
+                let suffix = self
+                    .suffixes
+                    .iter()
+                    .find(|(suffix, _)| name.ends_with(suffix.as_str()));
+                if let Some((_, style)) = suffix {
+                    return Some(*style);
+                }
+                "fi"
+            };
+            self.types.get(key).copied()
+        }
+    }
+
+    /// Converts the SGR codes of a terminal escape sequence (like `01;38;5;208`) into a style.
+    fn sgr_style(codes: &str) -> ContentStyle {
+        let mut style = ContentStyle::new();
+        let mut codes = codes
+            .split(';')
+            .map(|c| c.parse::<u8>().unwrap_or_default());
+        while let Some(code) = codes.next() {
+            match code {
+                1 => style = style.attribute(Attribute::Bold),
+                2 => style = style.attribute(Attribute::Dim),
+                3 => style = style.attribute(Attribute::Italic),
+                4 => style = style.attribute(Attribute::Underlined),
+                5 => style = style.attribute(Attribute::SlowBlink),
+                7 => style = style.attribute(Attribute::Reverse),
+                30..=37 => style.foreground_color = Some(ansi_color(code - 30)),
+                90..=97 => style.foreground_color = Some(ansi_color(code - 90 + 8)),
+                40..=47 => style.background_color = Some(ansi_color(code - 40)),
+                100..=107 => style.background_color = Some(ansi_color(code - 100 + 8)),
+                38 | 48 => {
+                    let color = match codes.next() {
+                        Some(5) => codes.next().map(Color::AnsiValue),
+                        Some(2) => match (codes.next(), codes.next(), codes.next()) {
+                            (Some(r), Some(g), Some(b)) => Some(Color::Rgb { r, g, b }),
+                            _ => None,
+                        },
+                        _ => None,
+                    };
+                    if code == 38 {
+                        style.foreground_color = color;
+                    } else {
+                        style.background_color = color;
+                    }
+                }
+                _ => (),
+            }
+        }
+        style
+    }
+
+    /// One of the 16 standard colors of the terminal
+    fn ansi_color(idx: u8) -> Color {
+        match idx {
+            0 => Color::Black,
+            1 => Color::DarkRed,
+            2 => Color::DarkGreen,
+            3 => Color::DarkYellow,
+            4 => Color::DarkBlue,
+            5 => Color::DarkMagenta,
+            6 => Color::DarkCyan,
+            7 => Color::Grey,
+            8 => Color::DarkGrey,
+            9 => Color::Red,
+            10 => Color::Green,
+            11 => Color::Yellow,
+            12 => Color::Blue,
+            13 => Color::Magenta,
+            14 => Color::Cyan,
+            _ => Color::White,
+        }
+    }
+
+    /// Returns the styles from `$LS_COLORS`, if they are enabled in the config.
+    pub fn ls_colors() -> Option<&'static LsColors> {
+        LS_COLORS.get()
+    }
+
+    /// Parses a color name (like "dark-green"), a hex color ("#83a598")
+    /// or the number of one of the 256 colors ("208").
+    pub fn parse_color(string: &str) -> Result<Color> {
+        if let Some(hex) = string.strip_prefix('#') {
+            let value = u32::from_str_radix(hex, 16)
+                .ok()
+                .filter(|_| hex.len() == 6)
+                .ok_or_else(|| anyhow!("'{string}' is not a valid hex color"))?;
+            let [_, r, g, b] = value.to_be_bytes();
+            return Ok(Color::Rgb { r, g, b });
+        }
+        if let Ok(value) = string.parse::<u8>() {
+            return Ok(Color::AnsiValue(value));
+        }
         let converted = string.to_ascii_lowercase().replace('-', "_");
         let color = converted
             .as_str()
@@ -39,33 +789,277 @@ pub mod color {
         Ok(color)
     }
 
-    pub fn colors_from_config(config: ColorConfig) -> Result<()> {
-        let main = extract_color(config.main).context("Failed to set 'main' color")?;
-        let marked = extract_color(config.marked).context("Failed to set 'marked' color")?;
-        let highlight =
-            extract_color(config.highlight).context("Failed to set 'highlight' color")?;
-        let dir_path = extract_color(config.dir_path).context("Failed to set 'dir_path' color")?;
-        COLOR_MAIN.set(main).expect("color must be unset");
-        COLOR_MAIN.get_or_init(|| main);
-        COLOR_MARKED.set(marked).expect("color must be unset");
-        COLOR_HIGHLIGHT.set(highlight).expect("color must be unset");
-        COLOR_DIR_PATH.set(dir_path).expect("color must be unset");
+    /// RGB values of the 16 standard colors (as used by xterm)
+    const ANSI_RGB: [(u8, u8, u8); 16] = [
+        (0, 0, 0),
+        (205, 0, 0),
+        (0, 205, 0),
+        (205, 205, 0),
+        (0, 0, 238),
+        (205, 0, 205),
+        (0, 205, 205),
+        (229, 229, 229),
+        (127, 127, 127),
+        (255, 0, 0),
+        (0, 255, 0),
+        (255, 255, 0),
+        (92, 92, 255),
+        (255, 0, 255),
+        (0, 255, 255),
+        (255, 255, 255),
+    ];
+
+    /// Levels of the 6x6x6 color cube of the 256 colors
+    const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+    /// RGB values of one of the 256 colors
+    fn ansi256_rgb(idx: u8) -> (u8, u8, u8) {
+        match idx {
+            0..=15 => ANSI_RGB[idx as usize],
+            16..=231 => {
+                let idx = idx - 16;
+                (
+                    CUBE_LEVELS[(idx / 36) as usize],
+                    CUBE_LEVELS[(idx / 6 % 6) as usize],
+                    CUBE_LEVELS[(idx % 6) as usize],
+                )
+            }
+            _ => {
+                let level = 8 + (idx - 232) * 10;
+                (level, level, level)
+            }
+        }
+    }
+
+    fn distance((r1, g1, b1): (u8, u8, u8), (r2, g2, b2): (u8, u8, u8)) -> u32 {
+        let d = |a: u8, b: u8| (a as i32 - b as i32).pow(2) as u32;
+        d(r1, r2) + d(g1, g2) + d(b1, b2)
+    }
+
+    /// Closest color of the 6x6x6 cube or the grayscale ramp
+    fn nearest_ansi256(rgb: (u8, u8, u8)) -> u8 {
+        (16..=255)
+            .min_by_key(|idx| distance(rgb, ansi256_rgb(*idx)))
+            .unwrap_or(16)
+    }
+
+    fn nearest_ansi16(rgb: (u8, u8, u8)) -> Color {
+        let idx = (0..16)
+            .min_by_key(|idx| distance(rgb, ANSI_RGB[*idx as usize]))
+            .unwrap_or(0);
+        ansi_color(idx)
+    }
+
+    /// Converts the color to one of the colors, that a terminal with the given depth can show.
+    pub fn degrade(color: Color, depth: ColorDepth) -> Color {
+        match (color, depth) {
+            (_, ColorDepth::TrueColor) => color,
+            (Color::Rgb { r, g, b }, ColorDepth::Ansi256) => {
+                Color::AnsiValue(nearest_ansi256((r, g, b)))
+            }
+            (Color::Rgb { r, g, b }, ColorDepth::Ansi16) => nearest_ansi16((r, g, b)),
+            (Color::AnsiValue(idx), ColorDepth::Ansi16) if idx < 16 => ansi_color(idx),
+            (Color::AnsiValue(idx), ColorDepth::Ansi16) => nearest_ansi16(ansi256_rgb(idx)),
+            _ => color,
+        }
+    }
+
+    /// Converts the color, so that the current terminal can show it (see [`degrade`]).
+    #[inline]
+    pub fn adapt(color: Color) -> Color {
+        match COLOR_DEPTH.get() {
+            Some(depth) => degrade(color, *depth),
+            None => color,
+        }
+    }
+
+    /// Registers the themes (replacing the previous ones) and activates the one with the given name.
+    ///
+    /// With the name "auto", the dark theme is used until [`detect_theme`] finds a light background.
+    fn set_themes(themes: Vec<Theme>, active: &str, auto: (&str, &str), depth: ColorDepth) {
+        let depth = *COLOR_DEPTH.get_or_init(|| {
+            info!("Using {depth:?} colors");
+            depth
+        });
+        let position = |name: &str| match themes.iter().position(|t| t.name == name) {
+            Some(idx) => idx,
+            None => {
+                warn!("There is no theme '{name}', using the default theme");
+                0
+            }
+        };
+        let auto = (active == AUTO_THEME).then(|| (position(auto.0), position(auto.1)));
+        let idx = match auto {
+            Some((light, _)) if LIGHT_BACKGROUND.load(Ordering::Relaxed) => light,
+            Some((_, dark)) => dark,
+            None => position(active),
+        };
+        *AUTO_THEMES.lock() = auto;
+        *THEMES.write() = themes.into_iter().map(|t| t.degrade(depth)).collect();
+        ACTIVE_THEME.store(idx, Ordering::Relaxed);
+    }
+
+    pub fn colors_from_config(config: ColorConfig, theme_configs: Vec<ThemeConfig>) -> Result<()> {
+        let mut themes = builtin_themes();
+        // The colors of the '[colors]' section replace the ones of the default theme
+        let default = themes[0].clone();
+        let color = |value: Option<String>, what: &str, fallback: Color| match value {
+            Some(value) => parse_color(&value).context(format!("Failed to set '{what}' color")),
+            None => Ok(fallback),
+        };
+        let custom = Theme {
+            name: CUSTOM_THEME.into(),
+            main: color(config.main, "main", default.main)?,
+            marked: color(config.marked, "marked", default.marked)?,
+            highlight: color(config.highlight, "highlight", default.highlight)?,
+            dir_path: color(config.dir_path, "dir_path", default.dir_path)?,
+        };
+        let has_custom = custom
+            != Theme {
+                name: CUSTOM_THEME.into(),
+                ..default
+            };
+        if has_custom {
+            themes.insert(0, custom);
+        }
+        for theme in theme_configs {
+            let theme = Theme::from_config(theme)?;
+            if themes.iter().any(|t| t.name == theme.name) {
+                bail!(
+                    "There is more than one theme with the name '{}'",
+                    theme.name
+                );
+            }
+            themes.push(theme);
+        }
+        // Without colors of its own, the theme follows the terminal background
+        let active = match config.theme {
+            Some(theme) => theme,
+            None if has_custom => CUSTOM_THEME.into(),
+            None => AUTO_THEME.into(),
+        };
+        let light = config.light_theme.unwrap_or_else(|| "light".into());
+        let dark = config.dark_theme.unwrap_or_else(|| themes[0].name.clone());
+        set_themes(
+            themes,
+            &active,
+            (&light, &dark),
+            config.depth.unwrap_or_else(ColorDepth::detect),
+        );
+        if config.ls_colors {
+            match std::env::var("LS_COLORS") {
+                Ok(value) if !value.is_empty() => {
+                    LS_COLORS.get_or_init(|| {
+                        info!("Using the file colors from $LS_COLORS");
+                        LsColors::parse(&value)
+                    });
+                }
+                _ => warn!("'ls_colors' is enabled, but $LS_COLORS is not set"),
+            }
+        }
         Ok(())
     }
 
     pub fn colors_from_default() {
-        COLOR_MAIN
-            .set(Color::DarkGreen)
-            .expect("color must be unset");
-        COLOR_MARKED
-            .set(Color::DarkYellow)
-            .expect("color must be unset");
-        COLOR_HIGHLIGHT
-            .set(Color::Red)
-            .expect("color must be unset");
-        COLOR_DIR_PATH
-            .set(Color::DarkBlue)
-            .expect("color must be unset");
+        set_themes(
+            builtin_themes(),
+            AUTO_THEME,
+            ("light", "default"),
+            ColorDepth::detect(),
+        );
+    }
+
+    /// Switches to the light theme, if the theme is "auto" and the terminal has a light background.
+    ///
+    /// The terminal must be in raw mode, so that its answer is not printed.
+    pub fn detect_theme() {
+        let Some((light, dark)) = *AUTO_THEMES.lock() else {
+            return;
+        };
+        let is_light = match query_background() {
+            Some(rgb) => is_light(rgb),
+            None => std::env::var("COLORFGBG")
+                .ok()
+                .and_then(|value| colorfgbg_is_light(&value))
+                .unwrap_or(false),
+        };
+        info!(
+            "Detected a {} terminal background",
+            if is_light { "light" } else { "dark" }
+        );
+        LIGHT_BACKGROUND.store(is_light, Ordering::Relaxed);
+        ACTIVE_THEME.store(if is_light { light } else { dark }, Ordering::Relaxed);
+    }
+
+    /// Asks the terminal for its background color (with OSC 11).
+    ///
+    /// The question is followed by a request for the device attributes, which every terminal answers,
+    /// so that we know when to stop reading - even if the terminal does not know OSC 11.
+    /// We never read after the timeout, so that no key presses get lost.
+    fn query_background() -> Option<(u8, u8, u8)> {
+        let mut tty = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open("/dev/tty")
+            .ok()?;
+        tty.write_all(b"\x1b]11;?\x07\x1b[c").ok()?;
+        tty.flush().ok()?;
+        let deadline = Instant::now() + BACKGROUND_TIMEOUT;
+        let mut response = Vec::new();
+        let mut attributes = false;
+        let mut byte = [0u8];
+        loop {
+            let left = deadline.saturating_duration_since(Instant::now());
+            if left.is_zero() || !wait_readable(&tty, left) || tty.read(&mut byte).ok()? != 1 {
+                break;
+            }
+            response.push(byte[0]);
+            // The device attributes look like "\x1b[?62;22c"
+            attributes |= response.ends_with(b"\x1b[?");
+            if attributes && byte[0] == b'c' {
+                break;
+            }
+        }
+        parse_background(&String::from_utf8_lossy(&response))
+    }
+
+    /// Parses the answer to OSC 11, e.g. "\x1b]11;rgb:ffff/ffff/dddd\x07".
+    fn parse_background(response: &str) -> Option<(u8, u8, u8)> {
+        let start = response.find("]11;rgb:")? + "]11;rgb:".len();
+        let rgb = response[start..].split(['\x07', '\x1b']).next()?;
+        let mut channels = rgb.split('/').map(|hex| {
+            let value = u32::from_str_radix(hex, 16).ok()?;
+            let max = 16u32.checked_pow(hex.len() as u32)?.checked_sub(1)?;
+            Some((value * 255 / max.max(1)) as u8)
+        });
+        Some((channels.next()??, channels.next()??, channels.next()??))
+    }
+
+    fn is_light((r, g, b): (u8, u8, u8)) -> bool {
+        // Perceived brightness, see ITU-R BT.601
+        299 * r as u32 + 587 * g as u32 + 114 * b as u32 > 127_500
+    }
+
+    /// Reads `$COLORFGBG` (e.g. "15;0"), that some terminals set - its last number is the background.
+    fn colorfgbg_is_light(value: &str) -> Option<bool> {
+        let bg: u8 = value.rsplit(';').next()?.parse().ok()?;
+        Some(matches!(bg, 7 | 9..=15))
+    }
+
+    /// Returns a color of the active theme.
+    #[inline]
+    fn theme_color(color: impl FnOnce(&Theme) -> Color) -> Color {
+        let themes = THEMES.read();
+        assert!(!themes.is_empty(), "themes must be set");
+        color(&themes[ACTIVE_THEME.load(Ordering::Relaxed) % themes.len()])
+    }
+
+    /// Switches to the next theme and returns its name.
+    pub fn cycle_theme() -> String {
+        let themes = THEMES.read();
+        let next = (ACTIVE_THEME.load(Ordering::Relaxed) + 1) % themes.len();
+        ACTIVE_THEME.store(next, Ordering::Relaxed);
+        themes[next].name.clone()
     }
 
     #[inline]
@@ -91,21 +1085,129 @@ pub mod color {
 
     #[inline]
     pub fn color_main() -> Color {
-        *COLOR_MAIN.get().expect("color must be set")
+        theme_color(|theme| theme.main)
     }
 
     #[inline]
     pub fn color_marked() -> Color {
-        *COLOR_MARKED.get().expect("color must be set")
+        theme_color(|theme| theme.marked)
     }
 
     #[inline]
     pub fn color_highlight() -> Color {
-        *COLOR_HIGHLIGHT.get().expect("color must be set")
+        theme_color(|theme| theme.highlight)
     }
 
     #[inline]
     pub fn color_dir_path() -> Color {
-        *COLOR_DIR_PATH.get().expect("color must be set")
+        theme_color(|theme| theme.dir_path)
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn reload_colors() {
+            let config = |main: &str| {
+                toml::from_str::<ColorConfig>(&format!("main = \"{main}\"\ndepth = \"truecolor\""))
+                    .unwrap()
+            };
+            colors_from_config(config("#ff0000"), Vec::new()).unwrap();
+            let red = color_main();
+            colors_from_config(config("#0000ff"), Vec::new()).unwrap();
+            assert_ne!(color_main(), red);
+            // Invalid colors keep the current theme
+            assert!(colors_from_config(config("nocolor"), Vec::new()).is_err());
+            assert_ne!(color_main(), red);
+        }
+
+        #[test]
+        fn parse_ls_colors() {
+            let colors =
+                LsColors::parse("rs=0:di=01;34:ex=01;32:*.tar=01;31:*.TAR.GZ=38;5;208:*~=02");
+            let dir = tempfile::tempdir().unwrap();
+            let file = |name: &str| {
+                let path = dir.path().join(name);
+                std::fs::write(&path, "").unwrap();
+                path
+            };
+            let style = |path: &Path, exec| colors.style(path, exec).unwrap();
+
+            assert_eq!(
+                style(dir.path(), false),
+                ContentStyle::new().dark_blue().bold()
+            );
+            assert_eq!(
+                style(&file("run.sh"), true),
+                ContentStyle::new().dark_green().bold()
+            );
+            assert_eq!(
+                style(&file("a.tar"), false),
+                ContentStyle::new().dark_red().bold()
+            );
+            // The longest suffix wins, and the case is ignored
+            assert_eq!(
+                style(&file("a.tar.gz"), false),
+                ContentStyle::new().with(Color::AnsiValue(208))
+            );
+            assert_eq!(style(&file("backup~"), false), ContentStyle::new().dim());
+            // No style for regular files ("fi")
+            assert!(colors.style(&file("plain"), false).is_none());
+        }
+
+        #[test]
+        fn theme_colors() {
+            assert_eq!(
+                parse_color("#83a598").unwrap(),
+                Color::Rgb {
+                    r: 0x83,
+                    g: 0xa5,
+                    b: 0x98
+                }
+            );
+            assert_eq!(parse_color("208").unwrap(), Color::AnsiValue(208));
+            assert_eq!(parse_color("Dark-Green").unwrap(), Color::DarkGreen);
+            assert!(parse_color("#83a5").is_err());
+            assert!(parse_color("sky").is_err());
+
+            let orange = Color::Rgb {
+                r: 255,
+                g: 135,
+                b: 0,
+            };
+            assert_eq!(degrade(orange, ColorDepth::TrueColor), orange);
+            assert_eq!(degrade(orange, ColorDepth::Ansi256), Color::AnsiValue(208));
+            assert_eq!(degrade(orange, ColorDepth::Ansi16), Color::DarkYellow);
+            assert_eq!(
+                degrade(Color::AnsiValue(250), ColorDepth::Ansi16),
+                Color::Grey
+            );
+            assert_eq!(
+                degrade(Color::AnsiValue(4), ColorDepth::Ansi16),
+                Color::DarkBlue
+            );
+            // Named colors are always shown
+            assert_eq!(
+                degrade(Color::DarkCyan, ColorDepth::Ansi16),
+                Color::DarkCyan
+            );
+        }
+
+        #[test]
+        fn terminal_background() {
+            let white = parse_background("\x1b]11;rgb:ffff/ffff/ffff\x07\x1b[?62;22c").unwrap();
+            assert_eq!(white, (255, 255, 255));
+            assert!(is_light(white));
+            let dark = parse_background("\x1b]11;rgb:28/2c/34\x1b\\").unwrap();
+            assert_eq!(dark, (0x28, 0x2c, 0x34));
+            assert!(!is_light(dark));
+            // The terminal only answered the device attributes
+            assert!(parse_background("\x1b[?62;22c").is_none());
+
+            assert_eq!(colorfgbg_is_light("0;15"), Some(true));
+            assert_eq!(colorfgbg_is_light("15;default;0"), Some(false));
+            assert_eq!(colorfgbg_is_light(""), None);
+        }
     }
 }
@@ -1,51 +1,277 @@
 use cached::{Cached, SizedCache};
-use log::debug;
+use log::{debug, warn};
 use once_cell::sync::Lazy;
 use parking_lot::Mutex;
 use std::{
+    collections::{HashMap, VecDeque},
+    io,
     path::{Path, PathBuf},
-    sync::{atomic::AtomicBool, Arc},
-    time::SystemTime,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant, SystemTime},
+};
+use tokio::{
+    sync::{mpsc, Semaphore},
+    task::{spawn_blocking, JoinError, JoinHandle},
 };
-use tokio::{sync::mpsc, task::spawn_blocking};
 use walkdir::WalkDir;
 
+use crate::config::CacheConfig;
+use crate::jobs::{self, CancelToken, JobKind, SHUTDOWN_FLAG};
 use crate::panel::{
-    DirElem, DirPanel, FilePreview, PanelContent, PanelState, PanelUpdate, PreviewPanel,
+    previews_enabled, tree_preview, DirElem, DirPanel, FilePreview, PanelContent, PanelState,
+    PanelUpdate, PreviewPanel,
 };
+use crate::vfs;
+
+/// Pauses the background crawls (see [`fill_cache`]) while set.
+pub static CACHE_PAUSED: AtomicBool = AtomicBool::new(false);
+
+/// Minimum time between two [`CacheProgress::Running`] messages of the same crawl.
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Progress messages of the background crawls.
+#[derive(Debug, Clone, Copy)]
+pub enum CacheProgress {
+    Started,
+    /// Number of visited entries and generated panels so far
+    Running {
+        entries: usize,
+        panels: usize,
+    },
+    Finished,
+}
+
+/// Time after an internal operation, in which its filesystem events are still ignored.
+///
+/// The watchers receive their events asynchronously, so they usually arrive
+/// after the operation itself has already finished.
+const INTERNAL_GRACE_PERIOD: Duration = Duration::from_millis(500);
+
+/// Paths that are currently accessed by rfm itself.
+///
+/// Maps each path to the number of running operations and the time when it expires.
+static INTERNAL_PATHS: Lazy<Mutex<HashMap<PathBuf, (usize, Instant)>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Guard that marks a path as internal, see [`internal_operation`].
+pub struct InternalOperation {
+    path: PathBuf,
+}
+
+impl Drop for InternalOperation {
+    fn drop(&mut self) {
+        if let Some((active, expires)) = INTERNAL_PATHS.lock().get_mut(&self.path) {
+            *active = active.saturating_sub(1);
+            *expires = Instant::now() + INTERNAL_GRACE_PERIOD;
+        }
+    }
+}
 
-/// Shutdown flag
+/// Marks `path` as accessed by rfm itself until the returned guard is dropped.
 ///
-/// This is used to abort long running blocking tasks like `fill_cache`
-pub static SHUTDOWN_FLAG: Lazy<AtomicBool> = Lazy::new(|| AtomicBool::new(false));
+/// Reading directories, generating previews or writing thumbnails triggers the file-watchers,
+/// which would result in redundant panel updates. Events for internal paths are ignored instead.
+pub fn internal_operation(path: impl Into<PathBuf>) -> InternalOperation {
+    let path = path.into();
+    let mut paths = INTERNAL_PATHS.lock();
+    let now = Instant::now();
+    paths.retain(|_, (active, expires)| *active > 0 || *expires > now);
+    paths.entry(path.clone()).or_insert((0, now)).0 += 1;
+    InternalOperation { path }
+}
+
+/// Returns true if the path is (or just was) accessed by rfm itself.
+pub fn is_internal(path: &Path) -> bool {
+    INTERNAL_PATHS
+        .lock()
+        .get(path)
+        .map(|(active, expires)| *active > 0 || *expires > Instant::now())
+        .unwrap_or_default()
+}
+
+/// Minimum time between two sweeps over the cache, that remove expired entries.
+const PURGE_INTERVAL: Duration = Duration::from_secs(30);
+
+#[derive(Clone)]
+struct CacheEntry<Item> {
+    item: Item,
+    /// Approximate memory of the item (see [`PanelContent::mem_size`])
+    size: usize,
+    inserted: Instant,
+}
+
+impl<Item> CacheEntry<Item> {
+    fn is_expired(&self, ttl: Option<Duration>) -> bool {
+        ttl.map(|ttl| self.inserted.elapsed() > ttl)
+            .unwrap_or_default()
+    }
+}
+
+struct CacheInner<Item> {
+    entries: SizedCache<PathBuf, CacheEntry<Item>>,
+    /// Approximate memory of all entries.
+    ///
+    /// The entries that the [`SizedCache`] drops by itself (when it is full) are not subtracted,
+    /// so this is an upper bound - it is corrected, when it exceeds `max_bytes`.
+    bytes: usize,
+    /// Least recently used entries are dropped, when `bytes` exceeds this
+    max_bytes: usize,
+    /// Entries older than this are treated as if they were not cached
+    ttl: Option<Duration>,
+    last_purge: Instant,
+}
+
+impl<Item> CacheInner<Item> {
+    fn remove(&mut self, path: &PathBuf) -> Option<CacheEntry<Item>> {
+        let entry = self.entries.cache_remove(path)?;
+        self.bytes = self.bytes.saturating_sub(entry.size);
+        Some(entry)
+    }
+
+    /// Drops the least recently used entries, until `size` more bytes fit into the cache.
+    ///
+    /// The number of entries is limited by the [`SizedCache`] itself.
+    fn make_room(&mut self, size: usize) {
+        if self.bytes.saturating_add(size) <= self.max_bytes {
+            return;
+        }
+        // Keep the most recently used entries, as long as they fit
+        let budget = self.max_bytes.saturating_sub(size);
+        let mut kept = 0;
+        let dropped: Vec<PathBuf> = self
+            .entries
+            .key_order()
+            .zip(self.entries.value_order())
+            .skip_while(|(_, entry)| {
+                kept += entry.size;
+                kept <= budget
+            })
+            .map(|(path, _)| path.clone())
+            .collect();
+        for path in dropped.iter() {
+            self.entries.cache_remove(path);
+        }
+        self.bytes = self.exact_bytes();
+    }
+
+    fn exact_bytes(&self) -> usize {
+        self.entries.value_order().map(|entry| entry.size).sum()
+    }
+
+    /// Removes all expired entries (at most once per [`PURGE_INTERVAL`]).
+    fn purge_expired(&mut self) {
+        if self.ttl.is_none() || self.last_purge.elapsed() < PURGE_INTERVAL {
+            return;
+        }
+        self.last_purge = Instant::now();
+        let expired: Vec<PathBuf> = self
+            .entries
+            .key_order()
+            .zip(self.entries.value_order())
+            .filter(|(_, entry)| entry.is_expired(self.ttl))
+            .map(|(path, _)| path.clone())
+            .collect();
+        for path in expired {
+            self.remove(&path);
+        }
+    }
+}
 
 /// Cache that is shared by the content-manager and the panel-manager.
+///
+/// Besides the number of entries, the cache is limited by the approximate memory of its panels,
+/// and entries expire after some time (see [`PanelCache::with_limits`]).
 #[derive(Clone)]
 pub struct PanelCache<Item: Clone> {
-    inner: Arc<Mutex<SizedCache<PathBuf, Item>>>,
+    inner: Arc<Mutex<CacheInner<Item>>>,
 }
 
 impl<Item: PanelContent> PanelCache<Item> {
     /// Creates a new cache with given size
     pub fn with_size(size: usize) -> Self {
         PanelCache {
-            inner: Arc::new(Mutex::new(SizedCache::with_size(size))),
+            inner: Arc::new(Mutex::new(CacheInner {
+                entries: SizedCache::with_size(size),
+                bytes: 0,
+                max_bytes: usize::MAX,
+                ttl: None,
+                last_purge: Instant::now(),
+            })),
+        }
+    }
+
+    /// Limits the memory of the cached panels to (approximately) `max_bytes`,
+    /// and lets the entries expire after `ttl`.
+    pub fn with_limits(self, max_bytes: usize, ttl: Option<Duration>) -> Self {
+        {
+            let mut inner = self.inner.lock();
+            inner.max_bytes = max_bytes;
+            inner.ttl = ttl;
         }
+        self
     }
 
     /// Attempt to retrieve a cached value
     pub fn get(&self, path: &PathBuf) -> Option<Item> {
-        self.inner.lock().cache_get(path).cloned()
+        let mut inner = self.inner.lock();
+        let ttl = inner.ttl;
+        let entry = inner.entries.cache_get(path)?;
+        if entry.is_expired(ttl) {
+            inner.remove(path);
+            return None;
+        }
+        Some(entry.item.clone())
     }
 
     /// Inserts a new key-value pair
     pub fn insert(&self, path: PathBuf, item: Item) -> Option<Item> {
-        self.inner.lock().cache_set(path, item)
+        let mut inner = self.inner.lock();
+        inner.purge_expired();
+        let old = inner.remove(&path).map(|entry| entry.item);
+        let size = item.mem_size();
+        // Panels that do not fit at all are not cached
+        if size > inner.max_bytes {
+            return old;
+        }
+        inner.make_room(size);
+        inner.bytes += size;
+        let entry = CacheEntry {
+            item,
+            size,
+            inserted: Instant::now(),
+        };
+        inner.entries.cache_set(path, entry);
+        old
+    }
+
+    /// Drops all cached panels
+    pub fn clear(&self) {
+        let mut inner = self.inner.lock();
+        inner.entries.cache_clear();
+        inner.bytes = 0;
+    }
+
+    /// Returns true if nothing is cached
+    pub fn is_empty(&self) -> bool {
+        self.inner.lock().entries.cache_size() == 0
+    }
+
+    /// Number of cached panels and their approximate memory in bytes
+    pub fn usage(&self) -> (usize, usize) {
+        let inner = self.inner.lock();
+        (inner.entries.cache_size(), inner.exact_bytes())
     }
 
     /// Returns the cache capacity
     pub fn capacity(&self) -> usize {
-        self.inner.lock().cache_capacity().unwrap_or_default()
+        self.inner
+            .lock()
+            .entries
+            .cache_capacity()
+            .unwrap_or_default()
     }
 
     /// Checks if the modification time of the path differs from the
@@ -55,39 +281,332 @@ impl<Item: PanelContent> PanelCache<Item> {
             .metadata()
             .and_then(|p| p.modified())
             .unwrap_or_else(|_| SystemTime::now());
-        self.inner
-            .lock()
-            .cache_get(path)
-            .map(|item| item.modified() < path_modification)
-            .unwrap_or(true)
+        let mut inner = self.inner.lock();
+        let ttl = inner.ttl;
+        match inner.entries.cache_get(path) {
+            Some(entry) if !entry.is_expired(ttl) => entry.item.modified() < path_modification,
+            _ => true,
+        }
+    }
+}
+
+/// Number of attempts for operations that fail with transient errors
+const MAX_ATTEMPTS: u32 = 4;
+
+/// Delay before the first retry - it is doubled after every attempt
+const INITIAL_BACKOFF: Duration = Duration::from_millis(50);
+
+/// Time in which a path is skipped after a transient error.
+///
+/// Doubled for every consecutive failure, up to [`MAX_FAILURE_COOLDOWN`].
+const FAILURE_COOLDOWN: Duration = Duration::from_secs(2);
+
+const MAX_FAILURE_COOLDOWN: Duration = Duration::from_secs(120);
+
+/// Returns true for errors that are likely to disappear if we just try again,
+/// which happens quite often on network filesystems.
+fn is_transient(e: &io::Error) -> bool {
+    matches!(
+        e.kind(),
+        io::ErrorKind::WouldBlock
+            | io::ErrorKind::Interrupted
+            | io::ErrorKind::TimedOut
+            | io::ErrorKind::ResourceBusy
+            | io::ErrorKind::StaleNetworkFileHandle
+    )
+}
+
+/// Runs a blocking io operation and retries it with exponential backoff on transient errors.
+fn with_retry<T>(mut op: impl FnMut() -> io::Result<T>) -> io::Result<T> {
+    let mut backoff = INITIAL_BACKOFF;
+    let mut attempt = 1;
+    loop {
+        match op() {
+            Err(e)
+                if is_transient(&e)
+                    && attempt < MAX_ATTEMPTS
+                    && !SHUTDOWN_FLAG.load(Ordering::Relaxed) =>
+            {
+                debug!("transient error (attempt {attempt}/{MAX_ATTEMPTS}): {e}");
+                std::thread::sleep(backoff);
+                backoff *= 2;
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Remembers paths that could not be read, so that dead paths don't get hammered.
+///
+/// Only transient errors block a path - a directory that was not found or
+/// could not be accessed is read again as soon as it is requested,
+/// because a `mkdir` or `chmod` may have fixed it in the meantime.
+#[derive(Default)]
+struct FailureCache {
+    /// Number of consecutive failures and the time until the path is skipped
+    failures: HashMap<PathBuf, (u32, Instant)>,
+}
+
+impl FailureCache {
+    /// Returns true if the path failed recently and should not be accessed yet.
+    fn is_blocked(&self, path: &Path) -> bool {
+        self.failures
+            .get(path)
+            .map(|(_, until)| *until > Instant::now())
+            .unwrap_or_default()
+    }
+
+    fn record<T>(&mut self, path: &Path, result: &io::Result<T>) {
+        match result {
+            Err(e) if is_transient(e) => {
+                let (count, until) = self
+                    .failures
+                    .entry(path.to_path_buf())
+                    .or_insert((0, Instant::now()));
+                let cooldown = FAILURE_COOLDOWN
+                    .saturating_mul(1 << (*count).min(16))
+                    .min(MAX_FAILURE_COOLDOWN);
+                *count += 1;
+                *until = Instant::now() + cooldown;
+                warn!(
+                    "Cannot read {}: {e} (skipping it for {}s)",
+                    path.display(),
+                    cooldown.as_secs()
+                );
+            }
+            _ => {
+                self.failures.remove(path);
+            }
+        }
+    }
+}
+
+/// Incoming requests of a content manager.
+///
+/// A newer request for a panel supersedes the older ones, that are still waiting
+/// or running, because the user has already moved on. Running reads are cancelled
+/// cooperatively through their [`CancelToken`].
+struct Requests {
+    rx: mpsc::UnboundedReceiver<PanelUpdate>,
+    queued: VecDeque<PanelUpdate>,
+}
+
+impl Requests {
+    fn new(rx: mpsc::UnboundedReceiver<PanelUpdate>) -> Self {
+        Requests {
+            rx,
+            queued: VecDeque::new(),
+        }
+    }
+
+    /// Queues the request and drops any waiting request for the same panel.
+    fn push(&mut self, update: PanelUpdate) {
+        self.queued
+            .retain(|queued| !queued.state.same_panel(&update.state));
+        self.queued.push_back(update);
+    }
+
+    /// Returns the next request, or `None` if the sender has been dropped.
+    async fn next(&mut self) -> Option<PanelUpdate> {
+        while let Ok(update) = self.rx.try_recv() {
+            self.push(update);
+        }
+        match self.queued.pop_front() {
+            Some(update) => Some(update),
+            None => self.rx.recv().await,
+        }
+    }
+
+    /// Waits for a read that was started for `state`, while new requests are queued.
+    ///
+    /// If one of them is for the same panel, the read is cancelled.
+    /// Returns the result of the read and whether or not it was superseded.
+    async fn wait_for<T>(
+        &mut self,
+        mut read: JoinHandle<T>,
+        state: &PanelState,
+        cancel: &CancelToken,
+    ) -> (Result<T, JoinError>, bool) {
+        let mut superseded = false;
+        loop {
+            tokio::select! {
+                result = &mut read => break (result, superseded),
+                Some(update) = self.rx.recv() => {
+                    if update.state.same_panel(state) && !superseded {
+                        debug!("cancel reading {}", state.path().display());
+                        cancel.cancel();
+                        superseded = true;
+                    }
+                    self.push(update);
+                }
+            }
+        }
     }
 }
 
 /// Receives commands to parse the directory or generate a new preview.
 pub struct DirManager {
     tx: mpsc::Sender<(DirPanel, PanelState)>,
-    rx: mpsc::UnboundedReceiver<PanelUpdate>,
+    requests: Requests,
     directory_cache: PanelCache<DirPanel>,
     preview_cache: PanelCache<PreviewPanel>,
+    crawler: CacheCrawler,
+    failures: FailureCache,
+}
+
+/// Controls the background crawls that fill the caches (see [`fill_cache`]).
+///
+/// Every crawl gets its own cancellation token. Starting a new crawl cancels
+/// the previous one, because the user has already moved on to another directory.
+struct CacheCrawler {
+    config: CacheConfig,
+    permits: Arc<Semaphore>,
+    cancel: Option<CancelToken>,
+    progress: mpsc::UnboundedSender<CacheProgress>,
+}
+
+impl CacheCrawler {
+    fn new(config: CacheConfig, progress: mpsc::UnboundedSender<CacheProgress>) -> Self {
+        let permits = Arc::new(Semaphore::new(config.concurrency.max(1)));
+        CacheCrawler {
+            config,
+            permits,
+            cancel: None,
+            progress,
+        }
+    }
+
+    /// Cancels the running crawl (if any) and starts a new one for the given path.
+    fn crawl(
+        &mut self,
+        path: PathBuf,
+        directory_cache: PanelCache<DirPanel>,
+        preview_cache: PanelCache<PreviewPanel>,
+    ) {
+        if let Some(token) = self.cancel.take() {
+            token.cancel();
+        }
+        if self.config.depth == 0 || self.config.max_entries == 0 {
+            return;
+        }
+        let job = jobs::start(JobKind::Cache, format!("caching {}", path.display()));
+        self.cancel = Some(job.token().clone());
+        let permits = self.permits.clone();
+        let config = self.config.clone();
+        let progress = self.progress.clone();
+        tokio::spawn(async move {
+            // Wait until we are allowed to run
+            let Ok(_permit) = permits.acquire_owned().await else {
+                return;
+            };
+            if job.is_cancelled() {
+                return;
+            }
+            let _ = progress.send(CacheProgress::Started);
+            let _ = spawn_blocking(move || {
+                fill_cache(
+                    path,
+                    directory_cache,
+                    preview_cache,
+                    &config,
+                    job.token(),
+                    &progress,
+                );
+                let _ = progress.send(CacheProgress::Finished);
+            })
+            .await;
+        });
+    }
 }
 
 /// Receives commands to parse the directory or generate a new preview.
 pub struct PreviewManager {
     tx: mpsc::Sender<(PreviewPanel, PanelState)>,
-    rx: mpsc::UnboundedReceiver<PanelUpdate>,
+    requests: Requests,
     preview_cache: PanelCache<PreviewPanel>,
+    failures: FailureCache,
 }
 
 pub fn dir_content(path: impl AsRef<Path>) -> Vec<DirElem> {
-    // read directory
-    match std::fs::read_dir(path) {
-        Ok(dir) => dir
-            .into_iter()
-            .flatten()
-            .map(|p| DirElem::from(p.path()))
-            .collect(),
-        Err(_) => Vec::new(),
+    try_dir_content(path).unwrap_or_default()
+}
+
+fn try_dir_content(path: impl AsRef<Path>) -> io::Result<Vec<DirElem>> {
+    Ok(vfs::read_dir(path.as_ref())?
+        .flatten()
+        .map(DirElem::from)
+        .collect())
+}
+
+/// Maximum number of elements in the flat view of a directory
+const MAX_FLAT_ELEMENTS: usize = 20_000;
+
+/// Reads the directory and its subdirectories up to `depth` levels (the "flat view").
+///
+/// The elements are named by their path relative to the directory.
+/// Stops early, when `cancel` is triggered.
+pub fn flat_dir_content(
+    path: impl AsRef<Path>,
+    depth: usize,
+    cancel: &CancelToken,
+) -> Vec<DirElem> {
+    let path = path.as_ref();
+    let elements: Vec<DirElem> = WalkDir::new(path)
+        .min_depth(1)
+        .max_depth(depth.max(1))
+        .into_iter()
+        .take_while(|_| !cancel.is_cancelled())
+        .flatten()
+        .take(MAX_FLAT_ELEMENTS)
+        .map(|entry| DirElem::relative(entry.path(), path))
+        .collect();
+    if elements.len() == MAX_FLAT_ELEMENTS {
+        warn!(
+            "Flat view of {} is limited to {MAX_FLAT_ELEMENTS} elements",
+            path.display()
+        );
+    }
+    elements
+}
+
+/// Number of elements, after which the first partial panel of a directory is shown.
+pub const CHUNK_SIZE: usize = 5_000;
+
+/// Reads at most `limit` elements of the directory.
+///
+/// Returns `true` as second value, if there are more elements left.
+pub fn dir_content_head(path: impl AsRef<Path>, limit: usize) -> io::Result<(Vec<DirElem>, bool)> {
+    let mut entries = vfs::read_dir(path.as_ref())?.flatten();
+    let elements = entries.by_ref().take(limit).map(DirElem::from).collect();
+    Ok((elements, entries.next().is_some()))
+}
+
+/// Reads the directory like [`try_dir_content`], but calls `partial` with the elements read so far
+/// after the first [`CHUNK_SIZE`] elements, and every time their number has doubled since.
+/// Stops with an error, when `cancel` is triggered.
+///
+/// This allows to show huge directories (like `/nix/store`) while they are still being read.
+/// Since every partial panel has to be sorted again, the number of elements is doubled
+/// (instead of growing in fixed steps), which keeps the overhead below a second sort.
+fn dir_content_chunked(
+    path: impl AsRef<Path>,
+    cancel: &CancelToken,
+    mut partial: impl FnMut(&[DirElem]),
+) -> io::Result<Vec<DirElem>> {
+    let mut elements = Vec::new();
+    let mut next_partial = CHUNK_SIZE;
+    for entry in vfs::read_dir(path.as_ref())?.flatten() {
+        if cancel.is_cancelled() {
+            return Err(io::Error::other("cancelled"));
+        }
+        elements.push(DirElem::from(entry));
+        if elements.len() >= next_partial {
+            partial(&elements);
+            next_partial *= 2;
+        }
     }
+    Ok(elements)
 }
 
 /// Walks the given directory path and fills both caches.
@@ -95,33 +614,71 @@ pub fn dir_content(path: impl AsRef<Path>) -> Vec<DirElem> {
 /// Since we most likely want to access a directory that the cursor went over,
 /// it is smart to prepare the cache here. This allows us to be as fast as possible
 /// with the generated previews.
+///
+/// The crawl stops early when `cancel` is triggered, or when more than
+/// `config.max_entries` entries have been visited. It waits as long as [`CACHE_PAUSED`] is set.
 fn fill_cache(
     path: PathBuf,
     directory_cache: PanelCache<DirPanel>,
     preview_cache: PanelCache<PreviewPanel>,
+    config: &CacheConfig,
+    cancel: &CancelToken,
+    progress: &mpsc::UnboundedSender<CacheProgress>,
 ) {
-    if !path.is_dir() {
+    // Remote hosts are only read on demand
+    if !path.is_dir() || vfs::is_remote(&path) {
         return;
     }
     let file_capacity = preview_cache.capacity() / 16;
     let dir_capacity = directory_cache.capacity() / 16;
     let mut n_dir_previews = 0;
     let mut n_file_previews = 0;
-    for entry in WalkDir::new(&path).max_depth(2).into_iter().flatten() {
+    let mut n_entries = 0;
+    let mut cancelled = false;
+    let mut last_progress = Instant::now();
+    for entry in WalkDir::new(&path)
+        .max_depth(config.depth)
+        .into_iter()
+        .flatten()
+    {
+        while CACHE_PAUSED.load(Ordering::Relaxed) && !cancel.is_cancelled() {
+            std::thread::sleep(PROGRESS_INTERVAL);
+        }
+        n_entries += 1;
+        if last_progress.elapsed() >= PROGRESS_INTERVAL {
+            last_progress = Instant::now();
+            let _ = progress.send(CacheProgress::Running {
+                entries: n_entries,
+                panels: n_dir_previews + n_file_previews,
+            });
+        }
         if entry.file_type().is_dir() && n_dir_previews < dir_capacity {
             let dir_path = entry.into_path();
+            let _internal = internal_operation(&dir_path);
             if directory_cache.requires_update(&dir_path) {
-                let content = dir_content(&dir_path);
+                // Unreadable directories are not cached, so that they are read again when visited
+                let Ok(content) = try_dir_content(&dir_path) else {
+                    continue;
+                };
                 let panel = DirPanel::new(content, dir_path.clone());
                 directory_cache.insert(dir_path.clone(), panel.clone());
-                preview_cache.insert(dir_path, PreviewPanel::Dir(panel));
+                if previews_enabled() {
+                    let preview = if tree_preview() {
+                        PreviewPanel::File(FilePreview::tree(dir_path.clone()))
+                    } else {
+                        PreviewPanel::Dir(panel)
+                    };
+                    preview_cache.insert(dir_path, preview);
+                }
                 n_dir_previews += 1;
             }
         } else if entry.file_type().is_file()
+            && previews_enabled()
             && entry.depth() == 1
             && n_file_previews < file_capacity
         {
             let file_path = entry.into_path();
+            let _internal = internal_operation(&file_path);
             if preview_cache.requires_update(&file_path) {
                 let preview = FilePreview::new(file_path.clone());
                 preview_cache.insert(file_path, PreviewPanel::File(preview));
@@ -133,12 +690,27 @@ fn fill_cache(
         if n_dir_previews >= dir_capacity && n_file_previews >= file_capacity {
             break;
         }
+        if n_entries >= config.max_entries {
+            debug!("Entry limit reached while caching {}", path.display());
+            break;
+        }
 
-        if SHUTDOWN_FLAG.load(std::sync::atomic::Ordering::Relaxed) {
-            debug!("Shutdown requested");
+        if cancel.is_cancelled() {
+            cancelled = true;
             break;
         }
     }
+    if cancelled {
+        debug!(
+            "cache: cancelled {} after {n_entries} entries",
+            path.display()
+        );
+    } else {
+        debug!(
+            "cache: {} ({n_dir_previews} dirs, {n_file_previews} files, {n_entries} entries)",
+            path.display()
+        );
+    }
 }
 
 impl DirManager {
@@ -147,49 +719,133 @@ impl DirManager {
         preview_cache: PanelCache<PreviewPanel>,
         tx: mpsc::Sender<(DirPanel, PanelState)>,
         rx: mpsc::UnboundedReceiver<PanelUpdate>,
+        cache_config: CacheConfig,
+        cache_progress: mpsc::UnboundedSender<CacheProgress>,
     ) -> Self {
         DirManager {
             tx,
-            rx,
+            requests: Requests::new(rx),
             directory_cache,
             preview_cache,
+            crawler: CacheCrawler::new(cache_config, cache_progress),
+            failures: FailureCache::default(),
         }
     }
 
     pub async fn run(mut self) {
         let mut last_cache_path = PathBuf::default();
-        while let Some(update) = self.rx.recv().await {
-            if !update.state.path().is_dir() {
+        while let Some(update) = self.requests.next().await {
+            let dir_path = update.state.path().clone();
+            if self.failures.is_blocked(&dir_path) {
+                debug!("skipping {} after previous failure", dir_path.display());
+                // Answer anyway, otherwise the panel is stuck with "loading..."
+                let panel = DirPanel::new(Vec::new(), dir_path);
+                if let Err(e) = self.tx.send((panel, update.state.increased())).await {
+                    debug!("Cannot send panel-update: {e}");
+                }
+                continue;
+            }
+            if let Some(depth) = update.state.flat() {
+                self.read_flat(update, depth).await;
                 continue;
             }
-            let dir_path = update.state.path().clone();
             debug!("request new dir-panel for {}", dir_path.display());
-            let result = spawn_blocking(move || dir_content(dir_path)).await;
-            if let Ok(content) = result {
-                // Only update when the hash has changed
-                let panel = DirPanel::new(content, update.state.path().clone());
-                if let Err(e) = self
-                    .tx
-                    .send((panel.clone(), update.state.increased().increased()))
-                    .await
-                {
-                    debug!("Cannot send panel-update: {e}");
+            let tx = self.tx.clone();
+            let mut state = update.state.increased();
+            let job = jobs::start(JobKind::Read, format!("reading {}", dir_path.display()));
+            let cancel = job.token().clone();
+            let read = spawn_blocking(move || {
+                let _internal = internal_operation(&dir_path);
+                let result = with_retry(|| {
+                    if vfs::metadata(&dir_path)?.is_dir {
+                        dir_content_chunked(&dir_path, job.token(), |elements| {
+                            // Every part needs a higher counter, otherwise the panel manager ignores it
+                            state = state.increased();
+                            let panel = DirPanel::partial(elements.to_vec(), dir_path.clone());
+                            if let Err(e) = tx.blocking_send((panel, state.clone())) {
+                                debug!("Cannot send partial panel-update: {e}");
+                            }
+                        })
+                        .map(Some)
+                    } else {
+                        Ok(None)
+                    }
+                });
+                (result, state.increased())
+            });
+            let (result, superseded) = self.requests.wait_for(read, &update.state, &cancel).await;
+            let Ok((result, state)) = result else {
+                continue;
+            };
+            if superseded {
+                continue;
+            }
+            self.failures.record(&update.state.path(), &result);
+            let content = match result {
+                Ok(Some(content)) => content,
+                // Not a directory
+                Ok(None) => continue,
+                Err(e) => {
+                    // Send an empty panel, so that we are not stuck with "loading..."
+                    let panel = if e.kind() == io::ErrorKind::PermissionDenied {
+                        DirPanel::denied(update.state.path())
+                    } else {
+                        DirPanel::new(Vec::new(), update.state.path())
+                    };
+                    if let Err(e) = self.tx.send((panel, state)).await {
+                        debug!("Cannot send panel-update: {e}");
+                    }
                     continue;
-                };
-                self.directory_cache
-                    .insert(update.state.path().clone(), panel.clone());
+                }
+            };
+            let panel = DirPanel::new(content, update.state.path());
+            if let Err(e) = self.tx.send((panel.clone(), state)).await {
+                debug!("Cannot send panel-update: {e}");
+                continue;
+            };
+            // The cache was cleared, so it has to be filled again
+            let refill = self.directory_cache.is_empty();
+            self.directory_cache
+                .insert(update.state.path().clone(), panel.clone());
+            // Tree previews are generated by the preview manager
+            if !tree_preview() && previews_enabled() {
                 self.preview_cache
                     .insert(update.state.path().clone(), PreviewPanel::Dir(panel));
             }
-            if update.state.path() != last_cache_path.as_path() {
+            if refill || update.state.path() != last_cache_path.as_path() {
                 last_cache_path = update.state.path().to_path_buf();
                 let path = update.state.path();
                 let dir_cache = self.directory_cache.clone();
                 let prev_cache = self.preview_cache.clone();
-                tokio::task::spawn_blocking(move || fill_cache(path, dir_cache, prev_cache));
+                self.crawler.crawl(path, dir_cache, prev_cache);
             }
         }
     }
+
+    /// Reads the directory and its subdirectories for the flat view (see [`flat_dir_content`]).
+    ///
+    /// Flat panels are not cached, because the cache holds the normal view of the directory.
+    async fn read_flat(&mut self, update: PanelUpdate, depth: usize) {
+        let dir_path = update.state.path();
+        debug!("request flat dir-panel for {}", dir_path.display());
+        let job = jobs::start(JobKind::Read, format!("reading {}", dir_path.display()));
+        let cancel = job.token().clone();
+        let read = spawn_blocking(move || {
+            let _internal = internal_operation(&dir_path);
+            let content = flat_dir_content(&dir_path, depth, job.token());
+            DirPanel::new(content, dir_path)
+        });
+        let (result, superseded) = self.requests.wait_for(read, &update.state, &cancel).await;
+        let Ok(panel) = result else {
+            return;
+        };
+        if superseded {
+            return;
+        }
+        if let Err(e) = self.tx.send((panel, update.state.increased())).await {
+            debug!("Cannot send panel-update: {e}");
+        }
+    }
 }
 
 impl PreviewManager {
@@ -200,46 +856,243 @@ impl PreviewManager {
     ) -> Self {
         PreviewManager {
             tx,
-            rx,
+            requests: Requests::new(rx),
             preview_cache,
+            failures: FailureCache::default(),
         }
     }
 
     pub async fn run(mut self) {
-        while let Some(update) = self.rx.recv().await {
-            if update.state.path().is_dir() {
-                let dir_path = update.state.path().clone();
-                let result = spawn_blocking(move || dir_content(dir_path)).await;
-                if let Ok(content) = result {
-                    let panel =
-                        PreviewPanel::Dir(DirPanel::new(content, update.state.path().clone()));
-                    if let Err(e) = self
-                        .tx
-                        .send((panel.clone(), update.state.increased()))
-                        .await
-                    {
-                        debug!("Cannot send panel-update: {e}");
-                        continue;
-                    }
-                    self.preview_cache.insert(update.state.path(), panel);
+        while let Some(update) = self.requests.next().await {
+            let path = update.state.path();
+            if self.failures.is_blocked(&path) || !previews_enabled() {
+                let state = update.state.increased();
+                if let Err(e) = self.tx.send((PreviewPanel::Empty, state)).await {
+                    debug!("Cannot send panel-update: {e}");
                 }
-            } else {
-                // Create preview
-                let file_path = update.state.path().clone();
-                let result = spawn_blocking(move || FilePreview::new(file_path)).await;
-                if let Ok(preview) = result {
-                    let panel = PreviewPanel::File(preview);
-                    if let Err(e) = self
-                        .tx
-                        .send((panel.clone(), update.state.increased()))
-                        .await
-                    {
-                        debug!("Cannot send panel-update: {e}");
-                        continue;
+                continue;
+            }
+            let tx = self.tx.clone();
+            let mut state = update.state.clone();
+            let job = jobs::start(JobKind::Read, format!("previewing {}", path.display()));
+            let cancel = job.token().clone();
+            let read = spawn_blocking(move || {
+                let _internal = internal_operation(&path);
+                let result = with_retry(|| vfs::metadata(&path)).and_then(|metadata| {
+                    if metadata.is_dir && tree_preview() && !vfs::is_remote(&path) {
+                        Ok(PreviewPanel::File(FilePreview::tree(path)))
+                    } else if metadata.is_dir {
+                        let content = with_retry(|| {
+                            dir_content_chunked(&path, job.token(), |elements| {
+                                state = state.increased();
+                                let panel = DirPanel::partial(elements.to_vec(), path.clone());
+                                if let Err(e) =
+                                    tx.blocking_send((PreviewPanel::Dir(panel), state.clone()))
+                                {
+                                    debug!("Cannot send partial panel-update: {e}");
+                                }
+                            })
+                        });
+                        let content = match content {
+                            Err(e) if e.kind() == io::ErrorKind::PermissionDenied => {
+                                return Ok(PreviewPanel::Dir(DirPanel::denied(path)));
+                            }
+                            content => content?,
+                        };
+                        Ok(PreviewPanel::Dir(DirPanel::new(content, path).with_stats()))
+                    } else {
+                        Ok(PreviewPanel::File(FilePreview::new(path)))
                     }
-                    self.preview_cache.insert(update.state.path(), panel);
-                }
+                });
+                (result, state.increased())
+            });
+            let (result, superseded) = self.requests.wait_for(read, &update.state, &cancel).await;
+            let Ok((result, state)) = result else {
+                continue;
+            };
+            if superseded {
+                continue;
+            }
+            self.failures.record(&update.state.path(), &result);
+            // Errors get an empty preview, so that we are not stuck with "loading..."
+            let panel = result.unwrap_or(PreviewPanel::Empty);
+            if let Err(e) = self.tx.send((panel.clone(), state)).await {
+                debug!("Cannot send panel-update: {e}");
+                continue;
+            }
+            if !matches!(panel, PreviewPanel::Empty) {
+                self.preview_cache.insert(update.state.path(), panel);
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn retry_transient_errors() {
+        let mut attempts = 0;
+        let result = with_retry(|| {
+            attempts += 1;
+            if attempts < 3 {
+                Err(io::Error::from(io::ErrorKind::WouldBlock))
+            } else {
+                Ok(attempts)
+            }
+        });
+        assert_eq!(result.unwrap(), 3);
+
+        // Permanent errors are not retried
+        let mut attempts = 0;
+        let result: io::Result<()> = with_retry(|| {
+            attempts += 1;
+            Err(io::Error::from(io::ErrorKind::NotFound))
+        });
+        assert!(result.is_err());
+        assert_eq!(attempts, 1);
+    }
+
+    #[test]
+    fn cache_memory_and_ttl() {
+        let dir = tempfile::tempdir().unwrap();
+        let panel = |name: &str| {
+            let path = dir.path().join(name);
+            std::fs::create_dir(&path).unwrap();
+            std::fs::write(path.join("file"), "").unwrap();
+            (path.clone(), DirPanel::new(dir_content(&path), path))
+        };
+        let (a, b, c) = (panel("a"), panel("b"), panel("c"));
+        let size = a.1.mem_size();
+
+        // Only two panels fit into the memory limit, so the least recently used one is dropped
+        let cache = PanelCache::with_size(16).with_limits(2 * size, None);
+        cache.insert(a.0.clone(), a.1.clone());
+        cache.insert(b.0.clone(), b.1.clone());
+        assert!(cache.get(&a.0).is_some());
+        cache.insert(c.0.clone(), c.1.clone());
+        assert!(cache.get(&a.0).is_some());
+        assert!(cache.get(&b.0).is_none());
+        assert_eq!(cache.usage(), (2, 2 * size));
+
+        // The number of entries is limited as well
+        let cache = PanelCache::with_size(2).with_limits(3 * size, None);
+        cache.insert(a.0.clone(), a.1.clone());
+        cache.insert(b.0.clone(), b.1.clone());
+        cache.insert(c.0.clone(), c.1.clone());
+        assert!(cache.get(&a.0).is_none());
+        assert_eq!(cache.usage(), (2, 2 * size));
+        // Dropped entries free their memory, so both newer panels fit again
+        cache.insert(a.0.clone(), a.1);
+        cache.insert(b.0.clone(), b.1);
+        assert_eq!(cache.usage(), (2, 2 * size));
+        assert!(cache.get(&c.0).is_none());
+
+        let cache =
+            PanelCache::with_size(16).with_limits(usize::MAX, Some(Duration::from_millis(1)));
+        cache.insert(c.0.clone(), c.1);
+        std::thread::sleep(Duration::from_millis(5));
+        assert!(cache.get(&c.0).is_none());
+        assert!(cache.requires_update(&c.0));
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn huge_directories_in_parts() {
+        let dir = tempfile::tempdir().unwrap();
+        for idx in 0..(2 * CHUNK_SIZE + 1) {
+            std::fs::File::create(dir.path().join(idx.to_string())).unwrap();
+        }
+        let mut parts = Vec::new();
+        let cancel = CancelToken::default();
+        let content =
+            dir_content_chunked(dir.path(), &cancel, |elements| parts.push(elements.len()))
+                .unwrap();
+        assert_eq!(content.len(), 2 * CHUNK_SIZE + 1);
+        assert_eq!(parts, vec![CHUNK_SIZE, 2 * CHUNK_SIZE]);
+
+        let (head, more) = dir_content_head(dir.path(), CHUNK_SIZE).unwrap();
+        assert_eq!(head.len(), CHUNK_SIZE);
+        assert!(more);
+        let (head, more) = dir_content_head(dir.path(), 3 * CHUNK_SIZE).unwrap();
+        assert_eq!(head.len(), 2 * CHUNK_SIZE + 1);
+        assert!(!more);
+    }
+
+    #[test]
+    fn flatten_subdirectories() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("a/b/c")).unwrap();
+        std::fs::create_dir(dir.path().join(".git")).unwrap();
+        std::fs::write(dir.path().join("a/b/file.txt"), "").unwrap();
+        std::fs::write(dir.path().join(".git/config"), "").unwrap();
+
+        let cancel = CancelToken::default();
+        let mut names: Vec<(String, bool)> = flat_dir_content(dir.path(), 2, &cancel)
+            .iter()
+            .map(|elem| (elem.name().clone(), elem.is_hidden()))
+            .collect();
+        names.sort();
+        let expected = [
+            (".git", true),
+            (".git/config", true),
+            ("a", false),
+            ("a/b", false),
+        ];
+        assert_eq!(names, expected.map(|(n, h)| (n.to_string(), h)));
+        assert_eq!(flat_dir_content(dir.path(), 3, &cancel).len(), 6);
+    }
+
+    #[test]
+    fn failed_paths_are_blocked() {
+        let mut failures = FailureCache::default();
+        let path = Path::new("/some/dead/mount");
+        assert!(!failures.is_blocked(path));
+        failures.record::<()>(path, &Err(io::Error::from(io::ErrorKind::TimedOut)));
+        assert!(failures.is_blocked(path));
+        failures.record(path, &Ok(()));
+        assert!(!failures.is_blocked(path));
+
+        // Permanent errors may be fixed at any time, so they are not blocked
+        for kind in [io::ErrorKind::NotFound, io::ErrorKind::PermissionDenied] {
+            failures.record::<()>(path, &Err(io::Error::from(kind)));
+            assert!(!failures.is_blocked(path));
+        }
+    }
+
+    #[test]
+    fn superseded_requests() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime.block_on(async {
+            let (tx, rx) = mpsc::unbounded_channel();
+            let mut requests = Requests::new(rx);
+            let center = PanelState::default();
+            let right = PanelState::default();
+            for state in [center.clone(), right.clone(), center.increased()] {
+                tx.send(PanelUpdate { state }).unwrap();
+            }
+            // The first request of the center panel was replaced by the newer one
+            let next = requests.next().await.unwrap();
+            assert!(next.state.same_panel(&right));
+            let next = requests.next().await.unwrap();
+            assert!(next.state.same_panel(&center) && next.state.cnt == 1);
+
+            // A new request for the same panel cancels the running read
+            let cancel = CancelToken::default();
+            let token = cancel.clone();
+            let read = spawn_blocking(move || {
+                while !token.is_cancelled() {
+                    std::thread::sleep(Duration::from_millis(1));
+                }
+            });
+            tx.send(PanelUpdate {
+                state: center.increased().increased(),
+            })
+            .unwrap();
+            let (result, superseded) = requests.wait_for(read, &center, &cancel).await;
+            assert!(result.is_ok() && superseded);
+            assert_eq!(requests.next().await.unwrap().state.cnt, 2);
+        });
+    }
+}
@@ -0,0 +1,249 @@
+//! Mounted filesystems and removable drives.
+//!
+//! Mounted filesystems are read from `/proc/self/mounts`, removable drives are listed with `lsblk`.
+//! Drives are mounted and unmounted with `udisksctl`, which asks udisks2 (via dbus) to do it,
+//! so no root privileges are required.
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    process::{Command, Stdio},
+};
+
+use anyhow::{anyhow, Context, Result};
+use log::{debug, info};
+
+/// Filesystems without a block device, that are still worth listing
+const NETWORK_FILESYSTEMS: [&str; 5] = ["nfs", "nfs4", "cifs", "smb3", "fuse.sshfs"];
+
+/// A mounted filesystem or a removable drive, that can be mounted
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Drive {
+    /// Block device (like `/dev/sdb1`) or remote location
+    pub device: String,
+    pub mountpoint: Option<PathBuf>,
+    pub fstype: String,
+    pub label: String,
+    pub size: String,
+    pub removable: bool,
+}
+
+impl Drive {
+    /// Name that is shown in the list of drives
+    pub fn name(&self) -> String {
+        let location = match &self.mountpoint {
+            Some(mountpoint) => mountpoint.display().to_string(),
+            None => "(not mounted)".to_string(),
+        };
+        if self.label.is_empty() {
+            location
+        } else {
+            format!("{location} [{}]", self.label)
+        }
+    }
+}
+
+/// Lists all mounted filesystems and the removable drives that are not mounted yet.
+pub fn list() -> Vec<Drive> {
+    let mounts = std::fs::read_to_string("/proc/self/mounts").unwrap_or_default();
+    let devices = Command::new("lsblk")
+        .args(["-P", "-o", "PATH,RM,HOTPLUG,MOUNTPOINT,LABEL,SIZE,FSTYPE"])
+        .stdin(Stdio::null())
+        .output()
+        .map(|output| String::from_utf8_lossy(&output.stdout).to_string())
+        .unwrap_or_else(|e| {
+            debug!("Cannot execute lsblk: {e}");
+            String::new()
+        });
+    merge(parse_mounts(&mounts), parse_lsblk(&devices))
+}
+
+/// Mount points of all "real" filesystems
+pub fn mountpoints() -> Vec<PathBuf> {
+    let mounts = std::fs::read_to_string("/proc/self/mounts").unwrap_or_default();
+    parse_mounts(&mounts)
+        .into_iter()
+        .filter_map(|drive| drive.mountpoint)
+        .collect()
+}
+
+/// Parses the content of `/proc/mounts` and keeps only "real" filesystems.
+fn parse_mounts(mounts: &str) -> Vec<Drive> {
+    let mut drives: Vec<Drive> = Vec::new();
+    for line in mounts.lines() {
+        let mut fields = line.split_whitespace();
+        let (Some(device), Some(mountpoint), Some(fstype)) =
+            (fields.next(), fields.next(), fields.next())
+        else {
+            continue;
+        };
+        let block_device = device.starts_with("/dev/") && !device.starts_with("/dev/loop");
+        if !block_device && !NETWORK_FILESYSTEMS.contains(&fstype) {
+            continue;
+        }
+        // Spaces in mount points are escaped as "\040"
+        let mountpoint = PathBuf::from(mountpoint.replace("\\040", " "));
+        if drives
+            .iter()
+            .any(|d| d.mountpoint.as_ref() == Some(&mountpoint))
+        {
+            continue;
+        }
+        drives.push(Drive {
+            device: device.to_string(),
+            mountpoint: Some(mountpoint),
+            fstype: fstype.to_string(),
+            label: String::new(),
+            size: String::new(),
+            removable: false,
+        });
+    }
+    drives
+}
+
+/// Parses the output of `lsblk -P`, which prints one device per line as `KEY="value"` pairs.
+fn parse_lsblk(output: &str) -> Vec<HashMap<String, String>> {
+    output
+        .lines()
+        .map(|line| {
+            let mut pairs = HashMap::new();
+            let mut rest = line;
+            while let Some((key, value)) = rest.split_once("=\"") {
+                let Some((value, next)) = value.split_once('"') else {
+                    break;
+                };
+                pairs.insert(key.trim().to_string(), unescape(value));
+                rest = next;
+            }
+            pairs
+        })
+        .collect()
+}
+
+/// Replaces the `\xNN` escapes of lsblk
+fn unescape(value: &str) -> String {
+    let mut out = Vec::new();
+    let bytes = value.as_bytes();
+    let mut idx = 0;
+    while idx < bytes.len() {
+        let escaped = value
+            .get(idx..idx + 4)
+            .filter(|s| s.starts_with("\\x"))
+            .and_then(|s| u8::from_str_radix(&s[2..], 16).ok());
+        match escaped {
+            Some(byte) => {
+                out.push(byte);
+                idx += 4;
+            }
+            None => {
+                out.push(bytes[idx]);
+                idx += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).to_string()
+}
+
+/// Adds label and size to the mounted drives and appends the removable drives,
+/// that are not mounted yet.
+fn merge(mut drives: Vec<Drive>, devices: Vec<HashMap<String, String>>) -> Vec<Drive> {
+    let get =
+        |device: &HashMap<String, String>, key: &str| device.get(key).cloned().unwrap_or_default();
+    for device in devices.iter() {
+        let path = get(device, "PATH");
+        let removable = get(device, "RM") == "1" || get(device, "HOTPLUG") == "1";
+        if let Some(drive) = drives.iter_mut().find(|d| d.device == path) {
+            drive.label = get(device, "LABEL");
+            drive.size = get(device, "SIZE");
+            drive.removable = removable;
+        } else if removable && get(device, "MOUNTPOINT").is_empty() {
+            // Only devices with a filesystem can be mounted (e.g. not the disk of a partitioned drive)
+            let fstype = get(device, "FSTYPE");
+            if fstype.is_empty() {
+                continue;
+            }
+            drives.push(Drive {
+                device: path,
+                mountpoint: None,
+                fstype,
+                label: get(device, "LABEL"),
+                size: get(device, "SIZE"),
+                removable,
+            });
+        }
+    }
+    drives
+}
+
+fn udisksctl(action: &str, device: &str) -> Result<String> {
+    let output = Command::new("udisksctl")
+        .args([action, "--no-user-interaction", "-b", device])
+        .stdin(Stdio::null())
+        .output()
+        .context("failed to execute udisksctl")?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "{}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+/// Mounts the drive and returns its mountpoint.
+pub fn mount(drive: &Drive) -> Result<PathBuf> {
+    if let Some(mountpoint) = &drive.mountpoint {
+        return Ok(mountpoint.clone());
+    }
+    // e.g. "Mounted /dev/sdb1 at /run/media/user/STICK" (older versions add a trailing ".")
+    let output = udisksctl("mount", &drive.device)?;
+    let mountpoint = output
+        .trim()
+        .split_once(" at ")
+        .map(|(_, mountpoint)| PathBuf::from(mountpoint.trim_end_matches('.')))
+        .ok_or_else(|| anyhow!("unexpected output of udisksctl: {output}"))?;
+    info!("mounted {} at {}", drive.device, mountpoint.display());
+    Ok(mountpoint)
+}
+
+/// Unmounts the drive.
+pub fn unmount(drive: &Drive) -> Result<()> {
+    if !drive.removable {
+        return Err(anyhow!("only removable drives can be unmounted"));
+    }
+    udisksctl("unmount", &drive.device)?;
+    info!("unmounted {}", drive.device);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn list_drives() {
+        let mounts = "proc /proc proc rw 0 0\n\
+                      /dev/sda2 / ext4 rw 0 0\n\
+                      /dev/loop3 /snap/core squashfs ro 0 0\n\
+                      /dev/sdb1 /run/media/user/My\\040Stick vfat rw 0 0\n\
+                      server:/export /mnt/nfs nfs4 rw 0 0\n";
+        let drives = parse_mounts(mounts);
+        assert_eq!(drives.len(), 3);
+        assert_eq!(
+            drives[1].mountpoint,
+            Some(PathBuf::from("/run/media/user/My Stick"))
+        );
+
+        let lsblk = "PATH=\"/dev/sda2\" RM=\"0\" HOTPLUG=\"0\" MOUNTPOINT=\"/\" LABEL=\"\" SIZE=\"200G\" FSTYPE=\"ext4\"\n\
+                     PATH=\"/dev/sdb1\" RM=\"1\" HOTPLUG=\"1\" MOUNTPOINT=\"/run/media/user/My\\x20Stick\" LABEL=\"My\\x20Stick\" SIZE=\"8G\" FSTYPE=\"vfat\"\n\
+                     PATH=\"/dev/sdc\" RM=\"0\" HOTPLUG=\"1\" MOUNTPOINT=\"\" LABEL=\"\" SIZE=\"1T\" FSTYPE=\"\"\n\
+                     PATH=\"/dev/sdc1\" RM=\"0\" HOTPLUG=\"1\" MOUNTPOINT=\"\" LABEL=\"Backup\" SIZE=\"1T\" FSTYPE=\"ext4\"\n";
+        let drives = merge(drives, parse_lsblk(lsblk));
+        assert_eq!(drives.len(), 4);
+        assert!(!drives[0].removable);
+        assert_eq!(drives[1].label, "My Stick");
+        assert!(drives[1].removable);
+        assert_eq!(drives[3].device, "/dev/sdc1");
+        assert_eq!(drives[3].mountpoint, None);
+        assert_eq!(drives[3].name(), "(not mounted) [Backup]");
+    }
+}
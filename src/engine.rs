@@ -1,6 +1,21 @@
+pub mod actions;
+pub mod archive;
+pub mod checksum;
+pub mod clipboard;
 pub mod commands;
+pub mod doctor;
+pub mod frecency;
+pub mod grep;
+pub mod history;
 pub mod opener;
+pub mod protect;
+pub mod recent;
+pub mod script;
+pub mod shortcut;
+pub mod staging;
 pub mod symbols;
+pub mod tags;
+pub mod trash;
 
 pub use opener::OpenEngine;
 pub use symbols::SymbolEngine;
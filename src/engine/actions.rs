@@ -0,0 +1,271 @@
+//! Registry of the actions, that are shown in the context menu.
+//!
+//! The menu consists of the built-in actions (see [`BUILTIN`]), the applications that are
+//! configured for the mime-type in `open.toml` and the custom actions from `config.toml`.
+//! Every action has a condition, so only the actions that make sense for the selected item are listed.
+use std::path::Path;
+
+use log::warn;
+use once_cell::sync::OnceCell;
+
+use super::{
+    commands::{Command, Move},
+    doctor::ConfigProgram,
+    opener::{get_mime_type, is_archive, Application},
+    OpenEngine,
+};
+use crate::config::ActionConfig;
+
+static CUSTOM_ACTIONS: OnceCell<Vec<ActionConfig>> = OnceCell::new();
+
+/// What happens, when an entry of the context menu is chosen
+#[derive(Debug, Clone)]
+pub enum Action {
+    /// Executes a built-in command
+    Command(Command),
+    /// Opens the selected file with the given application
+    OpenWith(Application),
+    /// Opens the selected file with the default application of the system
+    OpenDefault,
+    /// Copies the absolute path of the selected item into the clipboard
+    CopyPath,
+    /// Runs a shell command with the marked (or selected) items as arguments
+    Shell { command: String, terminal: bool },
+}
+
+/// Decides for which items a built-in action is shown
+enum Condition {
+    Always,
+    File,
+    Archive,
+}
+
+struct Builtin {
+    label: &'static str,
+    key: char,
+    condition: Condition,
+    action: Action,
+}
+
+/// Built-in actions (in the order they appear in the menu)
+static BUILTIN: [Builtin; 10] = [
+    Builtin {
+        label: "Open",
+        key: 'o',
+        condition: Condition::Always,
+        action: Action::Command(Command::Move(Move::Right)),
+    },
+    Builtin {
+        label: "Open with default application",
+        key: 'O',
+        condition: Condition::File,
+        action: Action::OpenDefault,
+    },
+    Builtin {
+        label: "Rename",
+        key: 'r',
+        condition: Condition::Always,
+        action: Action::Command(Command::Rename),
+    },
+    Builtin {
+        label: "Copy path",
+        key: 'p',
+        condition: Condition::Always,
+        action: Action::CopyPath,
+    },
+    Builtin {
+        label: "Copy",
+        key: 'y',
+        condition: Condition::Always,
+        action: Action::Command(Command::Copy),
+    },
+    Builtin {
+        label: "Cut",
+        key: 'd',
+        condition: Condition::Always,
+        action: Action::Command(Command::Cut),
+    },
+    Builtin {
+        label: "Delete",
+        key: 'D',
+        condition: Condition::Always,
+        action: Action::Command(Command::Delete),
+    },
+    Builtin {
+        label: "Extract here",
+        key: 'e',
+        condition: Condition::Archive,
+        action: Action::Command(Command::Extract),
+    },
+    Builtin {
+        label: "Extract to ...",
+        key: 'E',
+        condition: Condition::Archive,
+        action: Action::Command(Command::ExtractTo),
+    },
+    Builtin {
+        label: "Toggle executable",
+        key: 'x',
+        condition: Condition::File,
+        action: Action::Command(Command::ToggleExec),
+    },
+];
+
+/// Single entry of the context menu
+#[derive(Debug, Clone)]
+pub struct MenuEntry {
+    pub label: String,
+    /// Key that chooses the entry directly
+    pub key: Option<char>,
+    pub action: Action,
+}
+
+/// Sets the custom actions from the config. Must be called before the first menu is opened.
+pub fn set_custom_actions(actions: Vec<ActionConfig>) {
+    if CUSTOM_ACTIONS.set(actions).is_err() {
+        warn!("custom actions are already set");
+    }
+}
+
+/// Programs that the custom actions run (the first word of their command).
+///
+/// Commands that start with a variable or an assignment are skipped.
+pub fn custom_programs() -> Vec<ConfigProgram> {
+    CUSTOM_ACTIONS
+        .get()
+        .into_iter()
+        .flatten()
+        .filter_map(|action| {
+            let program = action.command.split_whitespace().next()?;
+            (!program.contains(['$', '='])).then(|| {
+                ConfigProgram::new(format!("config.toml: action '{}'", action.name), program)
+            })
+        })
+        .collect()
+}
+
+/// Returns true if the custom action applies to the given item.
+fn custom_matches(action: &ActionConfig, path: &Path) -> bool {
+    let is_dir = path.is_dir();
+    if action.directory.is_some_and(|dir| dir != is_dir) {
+        return false;
+    }
+    if let Some(extensions) = &action.extensions {
+        let extension = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .unwrap_or_default();
+        if !extensions.iter().any(|e| e.eq_ignore_ascii_case(extension)) {
+            return false;
+        }
+    }
+    if let Some(mime) = &action.mime {
+        if is_dir {
+            return false;
+        }
+        let file_mime = get_mime_type(path);
+        // "image" matches all images, "image/png" only png files
+        let matches = match mime.split_once('/') {
+            Some(_) => file_mime.essence_str() == mime,
+            None => file_mime.type_() == mime.as_str(),
+        };
+        if !matches {
+            return false;
+        }
+    }
+    true
+}
+
+/// Collects all actions that apply to the given item.
+pub fn menu_entries(path: &Path, opener: &OpenEngine) -> Vec<MenuEntry> {
+    let is_dir = path.is_dir();
+    let mut entries = Vec::new();
+    for builtin in BUILTIN.iter() {
+        let applies = match builtin.condition {
+            Condition::Always => true,
+            Condition::File => !is_dir,
+            Condition::Archive => !is_dir && is_archive(path),
+        };
+        if !applies {
+            continue;
+        }
+        entries.push(MenuEntry {
+            label: builtin.label.to_string(),
+            key: Some(builtin.key),
+            action: builtin.action.clone(),
+        });
+        // The configured applications directly follow "Open"
+        if matches!(builtin.action, Action::Command(Command::Move(Move::Right))) && !is_dir {
+            let applications = opener.applications(path);
+            for (idx, app) in applications.into_iter().enumerate() {
+                entries.push(MenuEntry {
+                    label: format!("Open with {}", app.name()),
+                    key: char::from_digit(idx as u32 + 1, 10),
+                    action: Action::OpenWith(app),
+                });
+            }
+        }
+    }
+    for custom in CUSTOM_ACTIONS.get().into_iter().flatten() {
+        if !custom_matches(custom, path) {
+            continue;
+        }
+        // Built-in keys have priority
+        let key = custom
+            .key
+            .filter(|key| !entries.iter().any(|e| e.key == Some(*key)));
+        entries.push(MenuEntry {
+            label: custom.name.clone(),
+            key,
+            action: Action::Shell {
+                command: custom.command.clone(),
+                terminal: custom.terminal,
+            },
+        });
+    }
+    entries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn custom_action_filters() {
+        let action = |mime: Option<&str>, extensions: Option<Vec<&str>>, directory| ActionConfig {
+            name: "test".into(),
+            key: None,
+            command: "true".into(),
+            terminal: false,
+            mime: mime.map(String::from),
+            extensions: extensions.map(|e| e.into_iter().map(String::from).collect()),
+            directory,
+        };
+        let image = Path::new("/tmp/rfm-does-not-exist/wallpaper.PNG");
+        assert!(custom_matches(&action(None, None, None), image));
+        assert!(custom_matches(&action(Some("image"), None, None), image));
+        assert!(custom_matches(
+            &action(Some("image/png"), None, None),
+            image
+        ));
+        assert!(!custom_matches(
+            &action(Some("image/jpeg"), None, None),
+            image
+        ));
+        assert!(!custom_matches(&action(Some("video"), None, None), image));
+        assert!(custom_matches(
+            &action(None, Some(vec!["png"]), None),
+            image
+        ));
+        assert!(!custom_matches(
+            &action(None, Some(vec!["jpg"]), None),
+            image
+        ));
+        assert!(!custom_matches(&action(None, None, Some(true)), image));
+        assert!(custom_matches(&action(None, None, Some(false)), image));
+
+        let dir = std::env::temp_dir();
+        assert!(custom_matches(&action(None, None, Some(true)), &dir));
+        assert!(!custom_matches(&action(Some("image"), None, None), &dir));
+    }
+}
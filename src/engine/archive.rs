@@ -0,0 +1,313 @@
+//! Creation of archives (zip, tar.gz, tar.zst and 7z).
+//!
+//! The archive program runs as a background job. It lists every entry that it adds,
+//! which is compared with the number of entries of the items to log the progress.
+use std::{
+    fmt::Display,
+    io::{BufRead, BufReader},
+    ops::RangeInclusive,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    str::FromStr,
+};
+
+use anyhow::{anyhow, bail};
+use log::info;
+
+use crate::{jobs::CancelToken, util::check_filename};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveFormat {
+    Zip,
+    TarGz,
+    TarZst,
+    SevenZip,
+}
+
+impl Display for ArchiveFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.extension())
+    }
+}
+
+impl ArchiveFormat {
+    /// All formats in the order of the archive dialog
+    pub const ALL: [ArchiveFormat; 4] = [
+        ArchiveFormat::Zip,
+        ArchiveFormat::TarGz,
+        ArchiveFormat::TarZst,
+        ArchiveFormat::SevenZip,
+    ];
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ArchiveFormat::Zip => "zip",
+            ArchiveFormat::TarGz => "tar.gz",
+            ArchiveFormat::TarZst => "tar.zst",
+            ArchiveFormat::SevenZip => "7z",
+        }
+    }
+
+    /// Compression levels that the archive program accepts
+    pub fn levels(&self) -> RangeInclusive<u32> {
+        match self {
+            ArchiveFormat::Zip | ArchiveFormat::SevenZip => 0..=9,
+            ArchiveFormat::TarGz => 1..=9,
+            // Higher levels require `--ultra`
+            ArchiveFormat::TarZst => 1..=19,
+        }
+    }
+
+    /// Default level of the archive program
+    pub fn default_level(&self) -> u32 {
+        match self {
+            ArchiveFormat::Zip | ArchiveFormat::TarGz => 6,
+            ArchiveFormat::TarZst => 3,
+            ArchiveFormat::SevenZip => 5,
+        }
+    }
+
+    /// Command that writes the items (relative to the working directory) into the output file.
+    ///
+    /// Every command prints one line per added entry to stdout.
+    fn command(&self, output: &Path, level: u32, items: &[&Path]) -> Command {
+        let mut command = match self {
+            ArchiveFormat::Zip => {
+                let mut command = Command::new("zip");
+                command.arg("-r").arg(format!("-{level}")).arg(output);
+                command
+            }
+            ArchiveFormat::TarGz | ArchiveFormat::TarZst => {
+                let compressor = if *self == ArchiveFormat::TarGz {
+                    "gzip"
+                } else {
+                    "zstd"
+                };
+                let mut command = Command::new("tar");
+                command
+                    .arg("-cvf")
+                    .arg(output)
+                    .arg(format!("--use-compress-program={compressor} -{level}"));
+                command
+            }
+            ArchiveFormat::SevenZip => {
+                let mut command = Command::new("7z");
+                command
+                    .arg("a")
+                    .arg("-bb1")
+                    .arg(format!("-mx={level}"))
+                    .arg(output);
+                command
+            }
+        };
+        command.arg("--").args(items);
+        command
+    }
+}
+
+/// Everything that the archive dialog asks for
+#[derive(Debug, Clone)]
+pub struct ArchivePlan {
+    pub items: Vec<PathBuf>,
+    /// Directory in which the archive is created
+    pub dir: PathBuf,
+    /// Name of the archive without the extension
+    pub name: String,
+    pub format: ArchiveFormat,
+    pub level: u32,
+}
+
+/// Archive that a script creates, written as `<name>.<extension> [level]` (e.g. `backup.tar.zst 9`).
+///
+/// Without a level, the default level of the format is used.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArchiveSpec {
+    pub name: String,
+    pub format: ArchiveFormat,
+    pub level: u32,
+}
+
+impl FromStr for ArchiveSpec {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        let mut words = s.split_whitespace();
+        let file = words.next().unwrap_or_default();
+        let (name, format) = ArchiveFormat::ALL
+            .into_iter()
+            .find_map(|format| {
+                let name = file.strip_suffix(format.extension())?.strip_suffix('.')?;
+                Some((name, format))
+            })
+            .ok_or_else(|| anyhow!("expected <name>.<zip|tar.gz|tar.zst|7z> [level], got '{s}'"))?;
+        if name.is_empty() || name.contains('/') {
+            bail!("invalid archive name '{name}'");
+        }
+        let level = match words.next() {
+            Some(level) => level.parse()?,
+            None => format.default_level(),
+        };
+        if !format.levels().contains(&level) {
+            let levels = format.levels();
+            bail!(
+                "the level of {format} must be between {} and {}",
+                levels.start(),
+                levels.end()
+            );
+        }
+        if let Some(extra) = words.next() {
+            bail!("unexpected '{extra}'");
+        }
+        Ok(ArchiveSpec {
+            name: name.to_string(),
+            format,
+            level,
+        })
+    }
+}
+
+impl ArchiveSpec {
+    /// Plans to archive the items into the directory.
+    pub fn plan(self, items: Vec<PathBuf>, dir: PathBuf) -> ArchivePlan {
+        ArchivePlan {
+            items,
+            dir,
+            name: self.name,
+            format: self.format,
+            level: self.level,
+        }
+    }
+}
+
+/// Path of the archive, that is created by the plan.
+///
+/// If a file with the same name exists, underscores are appended to the name (see [`check_filename`]).
+pub fn output_path(plan: &ArchivePlan) -> std::io::Result<PathBuf> {
+    // The trailing dot keeps names like "backup-1.2" intact, because only the empty extension is removed
+    check_filename(
+        format!("{}.", plan.name),
+        &plan.dir,
+        plan.format.extension(),
+    )
+}
+
+/// Creates the archive (see [`output_path`]) and returns its path.
+///
+/// When the job is cancelled, the incomplete archive is removed.
+pub fn create(plan: &ArchivePlan, cancel: &CancelToken) -> std::io::Result<PathBuf> {
+    let output = output_path(plan)?;
+    let items: Vec<&Path> = plan
+        .items
+        .iter()
+        .filter_map(|path| path.file_name())
+        .map(Path::new)
+        .collect();
+    let total: usize = plan
+        .items
+        .iter()
+        .map(|path| walkdir::WalkDir::new(path).into_iter().count())
+        .sum();
+    info!(
+        "Creating {} with {total} entries (level {})",
+        output.display(),
+        plan.level
+    );
+
+    let mut command = plan.format.command(&output, plan.level, &items);
+    let program = command.get_program().to_string_lossy().into_owned();
+    let mut child = command
+        .current_dir(&plan.dir)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()?;
+    let mut added = 0;
+    let mut reported = 0;
+    if let Some(stdout) = child.stdout.take() {
+        for _ in BufReader::new(stdout).lines().map_while(|l| l.ok()) {
+            if cancel.is_cancelled() {
+                let _ = child.kill();
+                let _ = child.wait();
+                let _ = std::fs::remove_file(&output);
+                return Err(std::io::Error::other("cancelled"));
+            }
+            added += 1;
+            // Log every 10 percent
+            let percent = (added * 100 / total.max(1)).min(100);
+            if percent / 10 > reported / 10 {
+                reported = percent;
+                info!(
+                    "{}: {percent}% ({added} of {total} entries)",
+                    output.display()
+                );
+            }
+        }
+    }
+    let status = child.wait()?;
+    if !status.success() {
+        let _ = std::fs::remove_file(&output);
+        return Err(std::io::Error::other(format!(
+            "{program} exited with {status}"
+        )));
+    }
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_archive_specs() {
+        let spec: ArchiveSpec = "backup-1.2.tar.zst 9".parse().unwrap();
+        assert_eq!(spec.name, "backup-1.2");
+        assert_eq!((spec.format, spec.level), (ArchiveFormat::TarZst, 9));
+        let spec: ArchiveSpec = "photos.zip".parse().unwrap();
+        assert_eq!((spec.format, spec.level), (ArchiveFormat::Zip, 6));
+        for invalid in [
+            "photos",
+            ".zip",
+            "a/b.zip",
+            "photos.7z 10",
+            "photos.zip 1 2",
+        ] {
+            assert!(invalid.parse::<ArchiveSpec>().is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn create_archives() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("sub")).unwrap();
+        std::fs::write(dir.path().join("sub/a.txt"), "a").unwrap();
+        std::fs::write(dir.path().join("b.txt"), "b").unwrap();
+        let mut plan = ArchivePlan {
+            items: vec![dir.path().join("sub"), dir.path().join("b.txt")],
+            dir: dir.path().to_path_buf(),
+            name: "backup-1.2".to_string(),
+            format: ArchiveFormat::TarGz,
+            level: 9,
+        };
+        let cancel = CancelToken::default();
+        let output = create(&plan, &cancel).unwrap();
+        assert_eq!(output, dir.path().join("backup-1.2.tar.gz"));
+
+        let listing = Command::new("tar")
+            .arg("-tzf")
+            .arg(&output)
+            .output()
+            .unwrap();
+        let listing = String::from_utf8_lossy(&listing.stdout);
+        assert!(listing.lines().any(|l| l == "sub/a.txt"));
+        assert!(listing.lines().any(|l| l == "b.txt"));
+
+        // Existing archives are not overwritten
+        let output = create(&plan, &cancel).unwrap();
+        assert_eq!(output, dir.path().join("backup-1.2_.tar.gz"));
+
+        plan.format = ArchiveFormat::Zip;
+        plan.level = 0;
+        let output = create(&plan, &cancel).unwrap();
+        assert_eq!(output, dir.path().join("backup-1.2.zip"));
+    }
+}
@@ -0,0 +1,213 @@
+//! Checksums (md5 and sha256) of files.
+//!
+//! Checksums are calculated in the background. The results are kept in a registry,
+//! so that the panels can show if a file matches the checksum that is listed for it
+//! in a checksum file of the same directory (like `file.iso.sha256` or `SHA256SUMS`).
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    fs::File,
+    io::Read,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use log::{info, warn};
+use md5::Md5;
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use sha2::{Digest, Sha256};
+use tokio::sync::mpsc;
+
+use crate::jobs::{self, CancelToken, JobKind};
+
+static RESULTS: Lazy<Mutex<HashMap<PathBuf, Checksum>>> = Lazy::new(Default::default);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HashAlgorithm {
+    Md5,
+    Sha256,
+}
+
+impl Display for HashAlgorithm {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HashAlgorithm::Md5 => write!(f, "md5"),
+            HashAlgorithm::Sha256 => write!(f, "sha256"),
+        }
+    }
+}
+
+impl HashAlgorithm {
+    /// Names of the checksum files, that list the checksums of several files
+    fn sum_files(&self) -> &'static [&'static str] {
+        match self {
+            HashAlgorithm::Md5 => &["MD5SUMS", "md5sums.txt"],
+            HashAlgorithm::Sha256 => &["SHA256SUMS", "sha256sums.txt"],
+        }
+    }
+}
+
+/// Checksum of a file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Checksum {
+    pub algorithm: HashAlgorithm,
+    /// Lowercase hex digest
+    pub digest: String,
+    /// `Some(true)` if the digest matches the one in the checksum file
+    /// and `None` if there is no checksum file that lists the file.
+    pub verified: Option<bool>,
+    /// Modification time of the file, when the checksum was calculated
+    modified: Option<SystemTime>,
+}
+
+/// Returns the checksum of the file, if it was calculated and the file has not changed since.
+pub fn result(path: &Path) -> Option<Checksum> {
+    let checksum = RESULTS.lock().get(path).cloned()?;
+    let modified = path.metadata().and_then(|m| m.modified()).ok();
+    (checksum.modified == modified).then_some(checksum)
+}
+
+/// Calculates the checksums of the files in a background job.
+///
+/// `notify` receives a message after every file.
+pub fn spawn(files: Vec<PathBuf>, algorithm: HashAlgorithm, notify: mpsc::UnboundedSender<()>) {
+    let job = jobs::start(
+        JobKind::Checksum,
+        format!("{algorithm} of {} files", files.len()),
+    );
+    tokio::task::spawn_blocking(move || {
+        for path in files.into_iter().filter(|p| p.is_file()) {
+            if job.is_cancelled() {
+                return;
+            }
+            match calculate(&path, algorithm, job.token()) {
+                Ok(checksum) => {
+                    info!("{algorithm} of {}: {}", path.display(), checksum.digest);
+                    RESULTS.lock().insert(path, checksum);
+                    let _ = notify.send(());
+                }
+                Err(e) => warn!("Cannot calculate {algorithm} of {}: {e}", path.display()),
+            }
+        }
+    });
+}
+
+/// Calculates the checksum and compares it with the checksum files in the same directory.
+fn calculate(
+    path: &Path,
+    algorithm: HashAlgorithm,
+    cancel: &CancelToken,
+) -> std::io::Result<Checksum> {
+    let modified = path.metadata().and_then(|m| m.modified()).ok();
+    let digest = match algorithm {
+        HashAlgorithm::Md5 => hex_digest::<Md5>(path, cancel)?,
+        HashAlgorithm::Sha256 => hex_digest::<Sha256>(path, cancel)?,
+    };
+    let verified = expected(path, algorithm).map(|expected| expected == digest);
+    Ok(Checksum {
+        algorithm,
+        digest,
+        verified,
+        modified,
+    })
+}
+
+/// Returns the sha256 digest of the file as hex string.
+pub fn sha256(path: &Path) -> std::io::Result<String> {
+    hex_digest::<Sha256>(path, &CancelToken::default())
+}
+
+fn hex_digest<D: Digest>(path: &Path, cancel: &CancelToken) -> std::io::Result<String> {
+    let mut file = File::open(path)?;
+    let mut hasher = D::new();
+    let mut buffer = vec![0; 64 * 1024];
+    loop {
+        if cancel.is_cancelled() {
+            return Err(std::io::Error::other("cancelled"));
+        }
+        let n = file.read(&mut buffer)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buffer[..n]);
+    }
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect())
+}
+
+/// Looks for the checksum of the file in `<file>.sha256` (or `.md5`),
+/// and in the common checksum files like `SHA256SUMS`.
+fn expected(path: &Path, algorithm: HashAlgorithm) -> Option<String> {
+    let name = path.file_name()?.to_string_lossy();
+    let dir = path.parent()?;
+    let own_file = dir.join(format!("{name}.{algorithm}"));
+    std::iter::once(own_file)
+        .chain(algorithm.sum_files().iter().map(|f| dir.join(f)))
+        .filter_map(|file| std::fs::read_to_string(file).ok())
+        .find_map(|content| find_checksum(&content, &name))
+}
+
+/// Finds the checksum of a file in the content of a checksum file.
+///
+/// Lines have the format of `sha256sum` and `md5sum`: `<digest>  <name>` (or `<digest> *<name>`
+/// for binary mode). A file with a single digest and no name is accepted, too.
+fn find_checksum(content: &str, name: &str) -> Option<String> {
+    let lines = content.lines().map(str::trim).filter(|l| !l.is_empty());
+    let mut single = None;
+    for (idx, line) in lines.enumerate() {
+        let (digest, file) = match line.split_once(char::is_whitespace) {
+            Some((digest, file)) => (digest, file.trim_start().trim_start_matches('*')),
+            None if idx == 0 => {
+                single = Some(line);
+                continue;
+            }
+            None => continue,
+        };
+        let file = file.trim_start_matches("./");
+        if file == name {
+            return Some(digest.to_lowercase());
+        }
+    }
+    single.map(str::to_lowercase)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn verify_checksums() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("hello.txt");
+        std::fs::write(&file, "hello\n").unwrap();
+        let cancel = CancelToken::default();
+
+        let checksum = calculate(&file, HashAlgorithm::Md5, &cancel).unwrap();
+        assert_eq!(checksum.digest, "b1946ac92492d2347c6235b4d2611184");
+        assert_eq!(checksum.verified, None);
+
+        let sha256 = "5891b5b522d5df086d0ff0b110fbd9d21bb4fc7163af34d08286a2e846f6be03";
+        std::fs::write(
+            dir.path().join("SHA256SUMS"),
+            format!("0000  other.txt\n{sha256} *hello.txt\n"),
+        )
+        .unwrap();
+        let checksum = calculate(&file, HashAlgorithm::Sha256, &cancel).unwrap();
+        assert_eq!(checksum.verified, Some(true));
+
+        // A checksum file for the single file has priority
+        std::fs::write(dir.path().join("hello.txt.sha256"), "ABCD\n").unwrap();
+        let checksum = calculate(&file, HashAlgorithm::Sha256, &cancel).unwrap();
+        assert_eq!(checksum.verified, Some(false));
+
+        assert_eq!(
+            find_checksum("abc  ./a b.txt\n", "a b.txt"),
+            Some("abc".into())
+        );
+        assert_eq!(find_checksum("abc  other\n", "a"), None);
+    }
+}
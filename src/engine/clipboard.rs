@@ -0,0 +1,519 @@
+use std::{
+    collections::VecDeque,
+    io::{Read, Write},
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use log::{debug, warn};
+use tokio::sync::oneshot::{self, error::TryRecvError};
+
+use super::trash::{decode_path, encode_path};
+use crate::util::{lock_file, write_atomic};
+
+/// Maximum number of earlier clipboards, that are kept in the history
+const MAX_HISTORY: usize = 10;
+
+/// Time after which reading the system clipboard is given up
+/// (the application that owns the clipboard has to answer, and it may hang).
+const READ_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Minimum time between two reads of the system clipboard
+const CHECK_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Mime-type of file lists in the system clipboard (see RFC 2483)
+const URI_LIST: &str = "text/uri-list";
+
+/// Programs that put a file list into the system clipboard,
+/// together with the environment variable that their display server requires.
+const URI_COPY_PROGRAMS: [(&str, &[&str], &str); 2] = [
+    ("wl-copy", &["--type", URI_LIST], "WAYLAND_DISPLAY"),
+    (
+        "xclip",
+        &["-selection", "clipboard", "-target", URI_LIST, "-in"],
+        "DISPLAY",
+    ),
+];
+
+/// Programs that read a file list from the system clipboard (see [`URI_COPY_PROGRAMS`])
+const URI_PASTE_PROGRAMS: [(&str, &[&str], &str); 2] = [
+    (
+        "wl-paste",
+        &["--no-newline", "--type", URI_LIST],
+        "WAYLAND_DISPLAY",
+    ),
+    (
+        "xclip",
+        &["-selection", "clipboard", "-target", URI_LIST, "-out"],
+        "DISPLAY",
+    ),
+];
+
+/// Items that were cut or copied
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Clipboard {
+    /// Items we put into the clipboard
+    pub files: Vec<PathBuf>,
+    /// Weather or not we want to cut or copy the items.
+    ///
+    /// `True`  : Cut
+    /// `False` : Copy
+    pub cut: bool,
+    /// Unix timestamp (in milliseconds) of the cut or copy
+    pub time: u128,
+}
+
+impl Clipboard {
+    pub fn new(files: Vec<PathBuf>, cut: bool) -> Self {
+        let time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|t| t.as_millis())
+            .unwrap_or_default();
+        Clipboard { files, cut, time }
+    }
+
+    /// Removes all items that no longer exist (e.g. because they were pasted by another instance).
+    ///
+    /// Returns false, if there is nothing left to paste.
+    pub fn retain_existing(&mut self) -> bool {
+        self.files.retain(|f| f.symlink_metadata().is_ok());
+        !self.files.is_empty()
+    }
+}
+
+/// Earlier clipboards of this instance, that were replaced by cutting or copying something else.
+///
+/// Every set of items is only listed once.
+#[derive(Debug, Default)]
+pub struct ClipboardHistory {
+    /// The most recent clipboard first
+    entries: VecDeque<Clipboard>,
+}
+
+impl ClipboardHistory {
+    /// Keeps a replaced clipboard.
+    ///
+    /// Returns the oldest clipboard, if it no longer fits into the history.
+    pub fn push(&mut self, clipboard: Clipboard) -> Option<Clipboard> {
+        self.remove(&clipboard.files);
+        self.entries.push_front(clipboard);
+        if self.entries.len() > MAX_HISTORY {
+            self.entries.pop_back()
+        } else {
+            None
+        }
+    }
+
+    /// Removes the clipboard with the given items (e.g. because it is the current one again).
+    pub fn remove(&mut self, files: &[PathBuf]) {
+        self.entries.retain(|c| c.files != files);
+    }
+
+    /// Returns the clipboards that still have items to paste, the most recent one first.
+    pub fn entries(&mut self) -> Vec<Clipboard> {
+        self.entries.retain_mut(Clipboard::retain_existing);
+        self.entries.iter().cloned().collect()
+    }
+}
+
+/// Clipboard that is shared between all running instances of rfm.
+///
+/// The clipboard is a plain text file: The first line is `cut<TAB>timestamp` (or `copy<TAB>timestamp`),
+/// followed by one item per line. Without a file, nothing is shared.
+///
+/// All accesses are guarded by an advisory lock on `<file>.lock`.
+/// Pasting takes the clipboard out of the file under one exclusive lock (see [`SharedClipboard::take`]),
+/// so that two instances cannot both paste (and move) the same items.
+#[derive(Debug, Default)]
+pub struct SharedClipboard {
+    file: Option<PathBuf>,
+}
+
+fn read(file: &Path) -> Option<Clipboard> {
+    match std::fs::read_to_string(file) {
+        Ok(content) => parse(&content),
+        Err(e) => {
+            debug!("Cannot read {}: {e}", file.display());
+            None
+        }
+    }
+}
+
+impl SharedClipboard {
+    pub fn new(file: PathBuf) -> Self {
+        SharedClipboard { file: Some(file) }
+    }
+
+    /// Publishes the clipboard to all other instances.
+    pub fn store(&self, clipboard: &Clipboard) {
+        if let Err(e) = self.write(clipboard) {
+            warn!("Failed to share the clipboard: {e}");
+        }
+    }
+
+    fn write(&self, clipboard: &Clipboard) -> std::io::Result<()> {
+        let Some(file) = &self.file else {
+            return Ok(());
+        };
+        let _lock = lock_file(file, true)?;
+        // Other instances must never read a half-written clipboard (even if they ignore the lock)
+        write_atomic(file, |out| {
+            let mode = if clipboard.cut { "cut" } else { "copy" };
+            writeln!(out, "{mode}\t{}", clipboard.time)?;
+            for path in clipboard.files.iter() {
+                writeln!(out, "{}", path.display())?;
+            }
+            Ok(())
+        })
+    }
+
+    /// Reads the clipboard, that was last published by any instance.
+    ///
+    /// Items that no longer exist are dropped. If none is left, the clipboard is cleared.
+    pub fn load(&self) -> Option<Clipboard> {
+        let file = self.file.as_ref()?;
+        let mut clipboard = {
+            let _lock = lock_file(file, false)
+                .map_err(|e| warn!("Cannot lock the shared clipboard: {e}"))
+                .ok()?;
+            read(file)?
+        };
+        if !clipboard.retain_existing() {
+            debug!("Shared clipboard is stale");
+            self.take(&clipboard);
+            return None;
+        }
+        Some(clipboard)
+    }
+
+    /// Takes the clipboard away from all instances, before its items are pasted.
+    ///
+    /// Returns false, if another instance has already taken it, or has cut or copied something else
+    /// in the meantime. The clipboard is read and removed under the same exclusive lock,
+    /// so only one instance can take it.
+    pub fn take(&self, clipboard: &Clipboard) -> bool {
+        let Some(file) = &self.file else {
+            return true;
+        };
+        let _lock = match lock_file(file, true) {
+            Ok(lock) => lock,
+            Err(e) => {
+                warn!("Cannot lock the shared clipboard: {e}");
+                return true;
+            }
+        };
+        if read(file).is_some_and(|shared| shared.time == clipboard.time) {
+            let _ = std::fs::remove_file(file);
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// Converts the files to a `text/uri-list`, e.g. `file:///home/user/my%20file.txt`.
+pub fn uri_list(files: &[PathBuf]) -> String {
+    files
+        .iter()
+        .map(|file| format!("file://{}\r\n", encode_path(file)))
+        .collect()
+}
+
+/// Returns the local files of a `text/uri-list` (other URIs and comments are skipped).
+pub fn parse_uri_list(list: &str) -> Vec<PathBuf> {
+    list.lines()
+        .map(str::trim)
+        .filter_map(|uri| uri.strip_prefix("file://"))
+        // The host is empty or "localhost"
+        .map(|uri| uri.strip_prefix("localhost").unwrap_or(uri))
+        .filter(|path| path.starts_with('/'))
+        .map(decode_path)
+        .collect()
+}
+
+/// Puts the files into the system clipboard, so that they can be pasted in graphical applications.
+///
+/// Requires `wl-copy` or `xclip`, returns false if none of them is available.
+pub fn publish_files(files: &[PathBuf]) -> std::io::Result<bool> {
+    let list = uri_list(files);
+    let programs = URI_COPY_PROGRAMS
+        .iter()
+        .filter(|(_, _, env)| std::env::var_os(env).is_some());
+    for (program, args, _) in programs {
+        let mut child = match Command::new(program)
+            .args(*args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+        {
+            Ok(child) => child,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e),
+        };
+        // Both programs serve the clipboard in the background, once their input is closed
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(list.as_bytes())?;
+        }
+        if !child.wait()?.success() {
+            return Err(std::io::Error::other(format!("{program} failed")));
+        }
+        return Ok(true);
+    }
+    Ok(false)
+}
+
+/// Returns the files in the system clipboard, e.g. copied in a graphical file manager.
+///
+/// The list is empty, if the clipboard contains no files (or cannot be read in time).
+/// This is a blocking operation, [`SystemClipboard`] runs it in the background.
+pub fn system_files() -> Vec<PathBuf> {
+    let programs = URI_PASTE_PROGRAMS
+        .iter()
+        .filter(|(_, _, env)| std::env::var_os(env).is_some());
+    for (program, args, _) in programs {
+        let mut child = match Command::new(program)
+            .args(*args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+        {
+            Ok(child) => child,
+            Err(_) => continue,
+        };
+        // Read the output in a thread, so that a full pipe cannot block the program
+        let mut stdout = child.stdout.take();
+        let reader = std::thread::spawn(move || {
+            let mut list = Vec::new();
+            if let Some(stdout) = &mut stdout {
+                let _ = stdout.read_to_end(&mut list);
+            }
+            list
+        });
+        let deadline = Instant::now() + READ_TIMEOUT;
+        let status = loop {
+            match child.try_wait() {
+                Ok(Some(status)) => break Some(status),
+                Ok(None) if Instant::now() < deadline => {
+                    std::thread::sleep(Duration::from_millis(10));
+                }
+                Ok(None) => {
+                    warn!("{program} did not answer in time");
+                    let _ = child.kill();
+                    let _ = child.wait();
+                    break None;
+                }
+                Err(e) => {
+                    warn!("Cannot read the system clipboard: {e}");
+                    break None;
+                }
+            }
+        };
+        let list = reader.join().unwrap_or_default();
+        return match status {
+            Some(status) if status.success() => parse_uri_list(&String::from_utf8_lossy(&list)),
+            // There is something else in the clipboard
+            _ => Vec::new(),
+        };
+    }
+    Vec::new()
+}
+
+/// Exchanges copied files with graphical applications via the system clipboard.
+///
+/// Reading the clipboard may hang, so it is read in the background (see [`SystemClipboard::check`]).
+/// Files are only taken over, if they changed since the previous read or since we published our own files -
+/// a list that was in the system clipboard before rfm started never replaces a newer clipboard of rfm.
+#[derive(Debug, Default)]
+pub struct SystemClipboard {
+    enabled: bool,
+    /// Files in the system clipboard at the last read (`None` before the first read)
+    files: Option<Vec<PathBuf>>,
+    /// Read that is still running
+    pending: Option<oneshot::Receiver<Vec<PathBuf>>>,
+    last_check: Option<Instant>,
+}
+
+impl SystemClipboard {
+    pub fn new(enabled: bool) -> Self {
+        let mut clipboard = SystemClipboard {
+            enabled,
+            ..Default::default()
+        };
+        clipboard.check();
+        clipboard
+    }
+
+    /// Starts to read the system clipboard in the background, unless it was read recently.
+    pub fn check(&mut self) {
+        if !self.enabled
+            || self.pending.is_some()
+            || self
+                .last_check
+                .is_some_and(|t| t.elapsed() < CHECK_INTERVAL)
+        {
+            return;
+        }
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            return;
+        };
+        let (tx, rx) = oneshot::channel();
+        runtime.spawn_blocking(move || {
+            let _ = tx.send(system_files());
+        });
+        self.pending = Some(rx);
+        self.last_check = Some(Instant::now());
+    }
+
+    /// Returns the files that were copied in a graphical application, once the running read has finished.
+    pub fn changed(&mut self) -> Option<Vec<PathBuf>> {
+        let files = match self.pending.as_mut()?.try_recv() {
+            Ok(files) => files,
+            Err(TryRecvError::Empty) => return None,
+            Err(TryRecvError::Closed) => Vec::new(),
+        };
+        self.pending = None;
+        let previous = self.files.replace(files.clone());
+        // The first read only tells us what was in the clipboard before
+        (!files.is_empty() && previous.is_some_and(|previous| previous != files)).then_some(files)
+    }
+
+    /// Puts the files into the system clipboard, so that they can be pasted in graphical applications.
+    pub fn publish(&mut self, files: &[PathBuf]) {
+        if !self.enabled {
+            return;
+        }
+        // A read that is still running may return the files that we are replacing
+        self.pending = None;
+        self.files = Some(files.to_vec());
+        match publish_files(files) {
+            Ok(true) => (),
+            Ok(false) => debug!("Cannot publish the copied files without wl-copy or xclip"),
+            Err(e) => warn!("Cannot put the files into the system clipboard: {e}"),
+        }
+    }
+}
+
+fn parse(content: &str) -> Option<Clipboard> {
+    let mut lines = content.lines();
+    let (mode, time) = lines.next()?.split_once('\t')?;
+    let cut = match mode {
+        "cut" => true,
+        "copy" => false,
+        _ => return None,
+    };
+    let time = time.parse().ok()?;
+    let files = lines
+        .filter(|line| !line.is_empty())
+        .map(PathBuf::from)
+        .collect();
+    Some(Clipboard { files, cut, time })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn uri_lists() {
+        let files = vec![PathBuf::from("/tmp/my file.txt"), PathBuf::from("/ä")];
+        let list = uri_list(&files);
+        assert_eq!(list, "file:///tmp/my%20file.txt\r\nfile:///%C3%A4\r\n");
+        assert_eq!(parse_uri_list(&list), files);
+
+        let foreign =
+            "# copied\nfile://localhost/etc/hosts\nhttps://example.com\nfile://server/share\n";
+        assert_eq!(parse_uri_list(foreign), vec![PathBuf::from("/etc/hosts")]);
+    }
+
+    #[test]
+    fn share_between_instances() {
+        let dir = tempfile::tempdir().unwrap();
+        let item = dir.path().join("item");
+        std::fs::write(&item, "content").unwrap();
+        let gone = dir.path().join("gone");
+
+        let first = SharedClipboard::new(dir.path().join("clipboard"));
+        let second = SharedClipboard::new(dir.path().join("clipboard"));
+        assert!(second.load().is_none());
+
+        let clipboard = Clipboard::new(vec![item.clone(), gone], true);
+        first.store(&clipboard);
+        let shared = second.load().unwrap();
+        assert!(shared.cut);
+        assert_eq!(shared.time, clipboard.time);
+        assert_eq!(shared.files, vec![item.clone()]);
+
+        // A newer clipboard of another instance is not removed by pasting the old one
+        let newer = Clipboard {
+            time: clipboard.time + 1,
+            ..clipboard.clone()
+        };
+        second.store(&newer);
+        assert!(!first.take(&clipboard));
+        assert_eq!(first.load().map(|c| c.time), Some(newer.time));
+        // Only one instance gets the items
+        let shared = first.load().unwrap();
+        assert!(second.take(&newer));
+        assert!(!first.take(&shared));
+        assert!(first.load().is_none());
+        first.store(&clipboard);
+
+        // Once all items are gone, the clipboard is stale
+        std::fs::remove_file(&item).unwrap();
+        assert!(second.load().is_none());
+        assert!(!dir.path().join("clipboard").exists());
+
+        assert!(parse("move\t0\n/a\n").is_none());
+    }
+
+    #[test]
+    fn system_clipboard_changes() {
+        let mut system = SystemClipboard::default();
+        let mut read = |files: &[&str]| {
+            let (tx, rx) = oneshot::channel();
+            tx.send(files.iter().map(PathBuf::from).collect()).unwrap();
+            system.pending = Some(rx);
+            system.changed()
+        };
+        // Files that were in the clipboard before are not taken over
+        assert_eq!(read(&["/old"]), None);
+        assert_eq!(read(&["/old"]), None);
+        assert_eq!(read(&["/new"]), Some(vec![PathBuf::from("/new")]));
+        assert_eq!(read(&[]), None);
+        assert_eq!(read(&["/new"]), Some(vec![PathBuf::from("/new")]));
+    }
+
+    #[test]
+    fn history() {
+        let dir = tempfile::tempdir().unwrap();
+        let items: Vec<PathBuf> = (0..=MAX_HISTORY)
+            .map(|n| {
+                let item = dir.path().join(n.to_string());
+                std::fs::write(&item, "").unwrap();
+                item
+            })
+            .collect();
+        let mut history = ClipboardHistory::default();
+        let dropped: Vec<Clipboard> = items
+            .iter()
+            .filter_map(|item| history.push(Clipboard::new(vec![item.clone()], true)))
+            .collect();
+        // The oldest clipboard fell out, the same items are only listed once
+        assert_eq!(dropped.len(), 1);
+        assert_eq!(dropped[0].files, [items[0].clone()]);
+        let entries = history.entries();
+        assert_eq!(entries.len(), MAX_HISTORY);
+        assert_eq!(entries[0].files, [items[MAX_HISTORY].clone()]);
+        history.push(Clipboard::new(vec![items[5].clone()], false));
+        assert_eq!(history.entries().len(), MAX_HISTORY);
+        assert!(!history.entries()[0].cut);
+
+        // Clipboards without existing items are dropped
+        std::fs::remove_file(&items[5]).unwrap();
+        history.remove(&[items[MAX_HISTORY].clone()]);
+        assert_eq!(history.entries().len(), MAX_HISTORY - 2);
+    }
+}
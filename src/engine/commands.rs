@@ -1,36 +1,133 @@
 use std::{
-    collections::HashMap,
     fmt::Display,
     path::{Path, PathBuf},
+    time::{Duration, Instant},
 };
 
 use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
-use log::trace;
+use log::{trace, warn};
 use patricia_tree::StringPatriciaMap;
 use serde::Deserialize;
 
-const CTRL_C: KeyEvent = KeyEvent::new(KeyCode::Char('c'), KeyModifiers::CONTROL);
-const CTRL_X: KeyEvent = KeyEvent::new(KeyCode::Char('x'), KeyModifiers::CONTROL);
-const CTRL_V: KeyEvent = KeyEvent::new(KeyCode::Char('v'), KeyModifiers::CONTROL);
-const CTRL_F: KeyEvent = KeyEvent::new(KeyCode::Char('f'), KeyModifiers::CONTROL);
-const CTRL_SHIFT_V: KeyEvent = KeyEvent::new(KeyCode::Char('V'), KeyModifiers::CONTROL);
+use super::checksum::HashAlgorithm;
 
-#[derive(Debug, Clone)]
+/// Path with a leading `~` (or `~user`) and the environment variables (`$VAR` or `${VAR}`) expanded.
+///
+/// Unknown variables and users are kept as they are.
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct ExpandedPath(PathBuf);
 
+impl ExpandedPath {
+    /// Expands the path, and returns the variables and users that are unknown (like `$FOO` or `~bob`).
+    pub fn with_unknown(path: &str) -> (Self, Vec<String>) {
+        let mut unknown = Vec::new();
+        let path = expand_tilde(path, &mut unknown);
+        let path = expand_variables(&path, &mut unknown);
+        (ExpandedPath(path.into()), unknown)
+    }
+}
+
 impl<S: AsRef<str>> From<S> for ExpandedPath {
     fn from(path: S) -> Self {
-        let mut string = path.as_ref().to_string();
-
-        // Replace with users home directory
-        let home = std::env::var("HOME").unwrap_or_default();
+        ExpandedPath::with_unknown(path.as_ref()).0
+    }
+}
 
-        // Expand "~" and "$HOME"
-        string = string.replace('~', &home);
-        string = string.replace("$HOME", &home);
+/// Replaces a leading `~` with the home directory, and `~user` with the one of the user.
+fn expand_tilde(path: &str, unknown: &mut Vec<String>) -> String {
+    let Some(rest) = path.strip_prefix('~') else {
+        return path.to_string();
+    };
+    let (user, rest) = rest.split_at(rest.find('/').unwrap_or(rest.len()));
+    let home = if user.is_empty() {
+        std::env::var_os("HOME").map(PathBuf::from)
+    } else {
+        use users::os::unix::UserExt;
+        users::get_user_by_name(user).map(|user| user.home_dir().to_path_buf())
+    };
+    match home {
+        Some(home) => format!("{}{rest}", home.display()),
+        None => {
+            unknown.push(format!("~{user}"));
+            path.to_string()
+        }
+    }
+}
 
-        ExpandedPath(string.into())
+/// Replaces `$VAR` and `${VAR}` with the value of the environment variable.
+fn expand_variables(path: &str, unknown: &mut Vec<String>) -> String {
+    let mut expanded = String::with_capacity(path.len());
+    let mut rest = path;
+    while let Some(idx) = rest.find('$') {
+        expanded.push_str(&rest[..idx]);
+        let after = &rest[idx + 1..];
+        let (name, len) = match after.strip_prefix('{') {
+            Some(braced) => match braced.find('}') {
+                Some(end) => (&braced[..end], end + 2),
+                None => ("", 0),
+            },
+            None => {
+                let end = after
+                    .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                    .unwrap_or(after.len());
+                (&after[..end], end)
+            }
+        };
+        let is_name = name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+            && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if !is_name {
+            // Not a variable (like a lonely '$'), so the '$' is kept
+            expanded.push('$');
+            rest = after;
+            continue;
+        }
+        match std::env::var(name) {
+            Ok(value) => expanded.push_str(&value),
+            Err(_) => {
+                let original = &rest[idx..idx + 1 + len];
+                unknown.push(original.to_string());
+                expanded.push_str(original);
+            }
+        }
+        rest = &after[len..];
     }
+    expanded.push_str(rest);
+    expanded
+}
+
+#[test]
+fn expand_paths() {
+    let home = std::env::var("HOME").unwrap();
+    std::env::set_var("RFM_TEST_EXPAND", "music");
+    let expand = |path: &str| ExpandedPath::with_unknown(path);
+    let path = |path: String| ExpandedPath(path.into());
+
+    assert_eq!(expand("~"), (path(home.clone()), vec![]));
+    assert_eq!(expand("~/docs"), (path(format!("{home}/docs")), vec![]));
+    assert_eq!(expand("$HOME/a~b"), (path(format!("{home}/a~b")), vec![]));
+    assert_eq!(
+        expand("/mnt/${RFM_TEST_EXPAND}/$RFM_TEST_EXPAND-2"),
+        (path("/mnt/music/music-2".into()), vec![])
+    );
+    assert_eq!(
+        expand("~root/$RFM_TEST_UNSET/${RFM_TEST_UNSET}"),
+        (
+            path("/root/$RFM_TEST_UNSET/${RFM_TEST_UNSET}".into()),
+            vec!["$RFM_TEST_UNSET".into(), "${RFM_TEST_UNSET}".into()]
+        )
+    );
+    assert_eq!(
+        expand("~rfm-test-nobody/x"),
+        (
+            path("~rfm-test-nobody/x".into()),
+            vec!["~rfm-test-nobody".into()]
+        )
+    );
+    // Dollars that do not start a variable are kept
+    assert_eq!(
+        expand("/tmp/$/${/$1"),
+        (path("/tmp/$/${/$1".into()), vec![])
+    );
 }
 
 impl AsRef<Path> for ExpandedPath {
@@ -49,6 +146,15 @@ impl From<ExpandedPath> for PathBuf {
 struct Manipulation {
     change_directory: Option<Vec<String>>,
     zoxide_query: Option<Vec<String>>,
+    jump_frecent: Option<Vec<String>>,
+    recent_files: Option<Vec<String>>,
+    dir_history: Option<Vec<String>>,
+    breadcrumbs: Option<Vec<String>>,
+    connect_remote: Option<Vec<String>>,
+    drives: Option<Vec<String>>,
+    shell: Option<Vec<String>>,
+    open_marked: Option<Vec<String>>,
+    terminal: Option<Vec<String>>,
     rename: Vec<String>,
     mkdir: Vec<String>,
     touch: Vec<String>,
@@ -57,9 +163,33 @@ struct Manipulation {
     delete: Vec<String>,
     paste: Vec<String>,
     paste_overwrite: Vec<String>,
-    zip: Vec<String>,
-    tar: Vec<String>,
+    paste_verified: Option<Vec<String>>,
+    paste_preview: Option<Vec<String>>,
+    paste_symlink: Option<Vec<String>>,
+    paste_relative_symlink: Option<Vec<String>>,
+    paste_hardlink: Option<Vec<String>>,
+    clipboard_history: Option<Vec<String>>,
+    copy_to_previous: Option<Vec<String>>,
+    move_to_previous: Option<Vec<String>>,
+    toggle_exec: Option<Vec<String>>,
+    checksum_md5: Option<Vec<String>>,
+    checksum_sha256: Option<Vec<String>>,
+    archive: Option<Vec<String>>,
+    /// Deprecated: opens the archive dialog as well
+    zip: Option<Vec<String>>,
+    /// Deprecated: opens the archive dialog as well
+    tar: Option<Vec<String>>,
     extract: Vec<String>,
+    extract_to: Option<Vec<String>>,
+    restore_trash: Option<Vec<String>>,
+    empty_trash: Option<Vec<String>>,
+    copy_contents: Option<Vec<String>>,
+    paste_to_file: Option<Vec<String>>,
+    stage: Option<Vec<String>>,
+    unstage_all: Option<Vec<String>>,
+    tag: Option<Vec<String>>,
+    untag: Option<Vec<String>>,
+    tagged_items: Option<Vec<String>>,
 }
 
 #[derive(Deserialize, Debug)]
@@ -75,20 +205,59 @@ struct Movement {
     half_page_forward: Vec<String>,
     half_page_backward: Vec<String>,
     jump_previous: Vec<String>,
+    history_back: Option<Vec<String>>,
+    history_forward: Option<Vec<String>>,
     jump_to: Vec<(String, String)>,
 }
 
 #[derive(Deserialize, Debug)]
 struct General {
     search: Vec<String>,
+    search_backward: Option<Vec<String>>,
+    grep: Option<Vec<String>>,
     mark: Vec<String>,
+    mark_glob: Option<Vec<String>>,
+    mark_by_age: Option<Vec<String>>,
+    mark_by_type: Option<Vec<String>>,
+    visual_mode: Option<Vec<String>>,
+    mark_all: Option<Vec<String>>,
+    invert_marks: Option<Vec<String>>,
+    unmark_all: Option<Vec<String>>,
     next: Vec<String>,
     previous: Vec<String>,
+    next_tagged: Option<Vec<String>>,
+    previous_tagged: Option<Vec<String>>,
     view_trash: Vec<String>,
     toggle_hidden: Vec<String>,
+    toggle_details: Option<Vec<String>>,
+    toggle_flat: Option<Vec<String>>,
+    group_by_extension: Option<Vec<String>>,
+    toggle_group: Option<Vec<String>>,
+    expand_groups: Option<Vec<String>>,
+    toggle_preview: Option<Vec<String>>,
+    toggle_zen: Option<Vec<String>>,
+    cycle_layout: Option<Vec<String>>,
+    toggle_caching: Option<Vec<String>>,
+    clear_cache: Option<Vec<String>>,
+    cycle_sort: Option<Vec<String>>,
+    cycle_theme: Option<Vec<String>>,
+    reload_config: Option<Vec<String>>,
+    image_zoom_in: Option<Vec<String>>,
+    image_zoom_out: Option<Vec<String>>,
+    image_fit: Option<Vec<String>>,
+    image_pan_left: Option<Vec<String>>,
+    image_pan_right: Option<Vec<String>>,
+    image_pan_up: Option<Vec<String>>,
+    image_pan_down: Option<Vec<String>>,
     toggle_log: Option<Vec<String>>,
+    context_menu: Option<Vec<String>>,
+    help: Option<Vec<String>>,
+    jobs: Option<Vec<String>>,
+    doctor: Option<Vec<String>>,
+    properties: Option<Vec<String>>,
     quit: Vec<String>,
     quit_no_cd: Option<Vec<String>>,
+    select_for_picker: Option<Vec<String>>,
 }
 
 #[derive(Deserialize, Debug)]
@@ -105,7 +274,7 @@ fn test_split() {
     assert_eq!(key, "f");
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Move {
     Up,
     Down,
@@ -119,32 +288,124 @@ pub enum Move {
     HalfPageBackward,
     JumpTo(ExpandedPath),
     JumpPrevious,
+    /// Goes back in the directory history (without changing it)
+    HistoryBack,
+    /// Goes forward in the directory history
+    HistoryForward,
+}
+
+/// Changes the view of an image in the preview panel
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageCmd {
+    ZoomIn,
+    ZoomOut,
+    /// Cycle between "fit", "fit width", "fit height" and "1:1"
+    CycleFit,
+    PanLeft,
+    PanRight,
+    PanUp,
+    PanDown,
 }
 
 /// Set of commands that the filemanager should perform during its runtime
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Command {
     Move(Move),
     Next,
     Previous,
+    NextTagged,
+    PreviousTagged,
     ToggleHidden,
+    ToggleDetails,
+    ToggleFlat,
+    GroupByExtension,
+    ToggleGroup,
+    ExpandGroups,
+    TogglePreview,
+    ToggleZen,
+    /// Switches between the layout presets (miller columns, two columns and a single column)
+    CycleLayout,
+    ToggleCaching,
+    ClearCache,
+    CycleSort,
+    CycleTheme,
+    /// Reads the keybindings, opener rules and colors again
+    ReloadConfig,
+    Image(ImageCmd),
     ToggleLog,
+    ContextMenu,
+    Help,
+    Jobs,
+    /// Lists the optional programs and the ones of the config, that are missing (see [`super::doctor`])
+    Doctor,
+    Properties,
     ViewTrash,
-    Zip,
-    Tar,
+    RestoreTrash,
+    EmptyTrash,
+    ToggleExec,
+    Checksum(HashAlgorithm),
+    Archive,
     Extract,
-    Cd { zoxide: bool },
+    ExtractTo,
+    CopyContents,
+    PasteToFile,
+    Stage,
+    UnstageAll,
+    Tag,
+    Untag,
+    TaggedItems,
+    Cd {
+        zoxide: bool,
+    },
+    JumpFrecent,
+    RecentFiles,
+    DirHistory,
+    /// Selects one of the directories of the path in the header
+    Breadcrumbs,
+    ConnectRemote,
+    Drives,
+    Shell,
+    /// Opens all marked files at once (see `multi` in the opener config)
+    OpenMarked,
+    Terminal,
     Search,
+    SearchBackward,
+    Grep,
     Rename,
     Mkdir,
     Touch,
     Cut,
     Copy,
     Delete,
-    Paste { overwrite: bool },
+    Paste {
+        overwrite: bool,
+    },
+    PasteVerified,
+    PastePreview,
+    /// Creates symlinks to the items of the clipboard
+    PasteSymlink {
+        relative: bool,
+    },
+    /// Creates hard links to the files of the clipboard
+    PasteHardlink,
+    /// Lists the current and the earlier clipboards, to paste one of them
+    ClipboardHistory,
+    SendToPrevious {
+        cut: bool,
+    },
     Mark,
+    MarkGlob,
+    MarkByAge,
+    /// Marks all files of a kind like images or archives (see [`super::opener::MimeCategory`])
+    MarkByType,
+    /// Marks everything between an anchor and the cursor, until the next command
+    VisualMode,
+    MarkAll,
+    InvertMarks,
+    UnmarkAll,
     Quit,
     QuitWithoutPath,
+    SelectForPicker,
     None,
 }
 
@@ -164,17 +425,70 @@ impl Display for Command {
                 Move::HalfPageBackward => write!(f, "half page backward"),
                 Move::JumpTo(path) => write!(f, "{}", path.0.display()),
                 Move::JumpPrevious => write!(f, "jump back"),
+                Move::HistoryBack => write!(f, "back in directory history"),
+                Move::HistoryForward => write!(f, "forward in directory history"),
             },
             Command::Next => write!(f, "next match"),
             Command::Previous => write!(f, "previous match"),
+            Command::NextTagged => write!(f, "next tagged item"),
+            Command::PreviousTagged => write!(f, "previous tagged item"),
             Command::ToggleHidden => write!(f, "toggle hidden files"),
+            Command::ToggleDetails => write!(f, "toggle detail view"),
+            Command::ToggleFlat => write!(f, "toggle the flat view of subdirectories"),
+            Command::GroupByExtension => write!(f, "group the items by extension"),
+            Command::ToggleGroup => write!(f, "collapse/expand a group of items"),
+            Command::ExpandGroups => write!(f, "expand all groups of items"),
+            Command::TogglePreview => write!(f, "toggle the preview column"),
+            Command::ToggleZen => write!(f, "toggle zen mode"),
+            Command::CycleLayout => write!(f, "cycle the layout (miller, dual, single)"),
+            Command::ToggleCaching => write!(f, "pause/resume background caching"),
+            Command::ClearCache => write!(f, "clear cache"),
+            Command::CycleSort => write!(f, "cycle the sort mode (lowercase, natural, locale)"),
+            Command::CycleTheme => write!(f, "switch to the next color theme"),
+            Command::ReloadConfig => write!(f, "reload keybindings, colors and opener rules"),
+            Command::Image(cmd) => match cmd {
+                ImageCmd::ZoomIn => write!(f, "zoom into image"),
+                ImageCmd::ZoomOut => write!(f, "zoom out of image"),
+                ImageCmd::CycleFit => write!(f, "cycle image fit"),
+                ImageCmd::PanLeft => write!(f, "pan image left"),
+                ImageCmd::PanRight => write!(f, "pan image right"),
+                ImageCmd::PanUp => write!(f, "pan image up"),
+                ImageCmd::PanDown => write!(f, "pan image down"),
+            },
             Command::ToggleLog => write!(f, "toggle developer log"),
+            Command::ContextMenu => write!(f, "show actions for the selected item"),
+            Command::Help => write!(f, "show all keybindings"),
+            Command::Jobs => write!(f, "show the background jobs"),
+            Command::Doctor => write!(f, "check which optional programs are installed"),
+            Command::Properties => write!(f, "show the properties of the selected item"),
             Command::ViewTrash => write!(f, "go to trash"),
-            Command::Zip => write!(f, "zip selected items"),
-            Command::Tar => write!(f, "tar selected items"),
+            Command::RestoreTrash => write!(f, "restore items from the trash"),
+            Command::EmptyTrash => write!(f, "empty the trash"),
+            Command::ToggleExec => write!(f, "toggle executable bit"),
+            Command::Checksum(algorithm) => write!(f, "calculate {algorithm} checksums"),
+            Command::Archive => write!(f, "create an archive from selected items"),
             Command::Extract => write!(f, "extract selected archive"),
+            Command::ExtractTo => write!(f, "extract marked archives into another directory"),
+            Command::CopyContents => write!(f, "copy the content of the file to the clipboard"),
+            Command::PasteToFile => write!(f, "create a file from the clipboard"),
+            Command::Stage => write!(f, "stage items (kept across directories)"),
+            Command::UnstageAll => write!(f, "clear the staging area"),
+            Command::Tag => write!(f, "tag items with a colored label"),
+            Command::Untag => write!(f, "remove all tags of the items"),
+            Command::TaggedItems => write!(f, "list all tagged items"),
             Command::Cd { .. } => write!(f, "enter 'cd' mode"),
+            Command::JumpFrecent => write!(f, "jump to a frequently visited directory"),
+            Command::RecentFiles => write!(f, "jump to a recently opened file"),
+            Command::DirHistory => write!(f, "jump to a recently visited directory"),
+            Command::Breadcrumbs => write!(f, "jump to a parent directory in the header"),
+            Command::ConnectRemote => write!(f, "connect to a remote host"),
+            Command::Drives => write!(f, "list drives"),
+            Command::Shell => write!(f, "open a shell in the current directory"),
+            Command::OpenMarked => write!(f, "open all marked files"),
+            Command::Terminal => write!(f, "open a new terminal in the current directory"),
             Command::Search => write!(f, "search for items"),
+            Command::SearchBackward => write!(f, "search for items backwards"),
+            Command::Grep => write!(f, "search the content of files"),
             Command::Rename => write!(f, "rename selected items"),
             Command::Mkdir => write!(f, "create a new directory"),
             Command::Touch => write!(f, "create a new file"),
@@ -188,30 +502,381 @@ impl Display for Command {
                     write!(f, "paste without overwrite")
                 }
             }
+            Command::PasteVerified => write!(f, "paste and verify checksums"),
+            Command::PastePreview => write!(f, "preview paste"),
+            Command::PasteSymlink { relative } => {
+                if *relative {
+                    write!(f, "paste as relative symlinks")
+                } else {
+                    write!(f, "paste as symlinks")
+                }
+            }
+            Command::PasteHardlink => write!(f, "paste as hard links"),
+            Command::ClipboardHistory => write!(f, "paste from the clipboard history"),
+            Command::SendToPrevious { cut } => {
+                if *cut {
+                    write!(f, "move selected items to previous directory")
+                } else {
+                    write!(f, "copy selected items to previous directory")
+                }
+            }
             Command::Mark => write!(f, "mark selected item"),
+            Command::MarkGlob => write!(f, "mark items matching a pattern"),
+            Command::MarkByAge => write!(f, "mark items by their modification time"),
+            Command::MarkByType => write!(f, "mark files by their type"),
+            Command::VisualMode => write!(f, "mark a range of items"),
+            Command::MarkAll => write!(f, "mark all items"),
+            Command::InvertMarks => write!(f, "invert marked items"),
+            Command::UnmarkAll => write!(f, "unmark all items"),
             Command::Quit => write!(f, "quit"),
             Command::QuitWithoutPath => write!(f, "quit without changing path"),
+            Command::SelectForPicker => {
+                write!(f, "quit and write the selected files to --choosefile")
+            }
             Command::None => write!(f, "no command"),
         }
     }
 }
 
+impl Command {
+    /// Section of the `keys.toml` in which the command is configured
+    pub fn category(&self) -> &'static str {
+        match self {
+            Command::Move(_) => "movement",
+            Command::ToggleExec
+            | Command::Checksum(_)
+            | Command::Archive
+            | Command::Extract
+            | Command::ExtractTo
+            | Command::CopyContents
+            | Command::RestoreTrash
+            | Command::EmptyTrash
+            | Command::PasteToFile
+            | Command::Stage
+            | Command::UnstageAll
+            | Command::Tag
+            | Command::Untag
+            | Command::TaggedItems
+            | Command::Cd { .. }
+            | Command::JumpFrecent
+            | Command::RecentFiles
+            | Command::DirHistory
+            | Command::Breadcrumbs
+            | Command::ConnectRemote
+            | Command::Drives
+            | Command::Shell
+            | Command::OpenMarked
+            | Command::Terminal
+            | Command::Rename
+            | Command::Mkdir
+            | Command::Touch
+            | Command::Cut
+            | Command::Copy
+            | Command::Delete
+            | Command::Paste { .. }
+            | Command::PasteVerified
+            | Command::PastePreview
+            | Command::PasteSymlink { .. }
+            | Command::PasteHardlink
+            | Command::ClipboardHistory
+            | Command::SendToPrevious { .. } => "manipulation",
+            _ => "general",
+        }
+    }
+
+    /// Returns true if the command changes the filesystem (see `--readonly`)
+    pub fn is_mutating(&self) -> bool {
+        matches!(
+            self,
+            Command::RestoreTrash
+                | Command::EmptyTrash
+                | Command::ToggleExec
+                | Command::Archive
+                | Command::Extract
+                | Command::ExtractTo
+                | Command::PasteToFile
+                | Command::Rename
+                | Command::Mkdir
+                | Command::Touch
+                | Command::Cut
+                | Command::Delete
+                | Command::Paste { .. }
+                | Command::PasteVerified
+                | Command::PastePreview
+                | Command::PasteSymlink { .. }
+                | Command::PasteHardlink
+                | Command::ClipboardHistory
+                | Command::SendToPrevious { .. }
+                | Command::ConnectRemote
+                | Command::Drives
+                | Command::Tag
+                | Command::Untag
+        )
+    }
+}
+
 /// Set of commands that the filemanager should perform just before closing
 pub enum CloseCmd {
-    QuitWithPath { path: PathBuf },
-    QuitErr { error: &'static str },
+    QuitWithPath {
+        path: PathBuf,
+    },
+    /// Quit and return the chosen files (see `--choosefile`)
+    QuitWithFiles {
+        path: PathBuf,
+        files: Vec<PathBuf>,
+    },
+    QuitErr {
+        error: &'static str,
+    },
     Quit,
 }
 
+/// Commands that are explained by the hints in the footer
+const HINT_COMMANDS: [Command; 22] = [
+    Command::Help,
+    Command::ToggleHidden,
+    Command::Cd { zoxide: false },
+    Command::Search,
+    Command::Grep,
+    Command::ContextMenu,
+    Command::Mark,
+    Command::MarkGlob,
+    Command::InvertMarks,
+    Command::JumpFrecent,
+    Command::Move(Move::JumpPrevious),
+    Command::ToggleDetails,
+    Command::TogglePreview,
+    Command::ToggleZen,
+    Command::CycleLayout,
+    Command::PastePreview,
+    Command::SendToPrevious { cut: false },
+    Command::ViewTrash,
+    Command::Rename,
+    Command::Mkdir,
+    Command::Extract,
+    Command::UnmarkAll,
+];
+
+/// Order of the categories in the help screen (see [`Command::category`])
+const HELP_CATEGORIES: [&str; 3] = ["movement", "manipulation", "general"];
+
+/// Names of the special keys, as they are written in the `keys.toml`
+const KEY_NAMES: [(&str, KeyCode); 16] = [
+    ("Space", KeyCode::Char(' ')),
+    ("Enter", KeyCode::Enter),
+    ("Tab", KeyCode::Tab),
+    ("BackTab", KeyCode::BackTab),
+    ("Esc", KeyCode::Esc),
+    ("Backspace", KeyCode::Backspace),
+    ("Delete", KeyCode::Delete),
+    ("Insert", KeyCode::Insert),
+    ("Home", KeyCode::Home),
+    ("End", KeyCode::End),
+    ("PageUp", KeyCode::PageUp),
+    ("PageDown", KeyCode::PageDown),
+    ("Up", KeyCode::Up),
+    ("Down", KeyCode::Down),
+    ("Left", KeyCode::Left),
+    ("Right", KeyCode::Right),
+];
+
+/// Returns the key with the given name (like `Enter` or `F2`).
+///
+/// Names are only case-insensitive, if `ignore_case` is set.
+fn named_key(name: &str, ignore_case: bool) -> Option<KeyCode> {
+    let matches = |key: &str| {
+        if ignore_case {
+            key.eq_ignore_ascii_case(name)
+        } else {
+            key == name
+        }
+    };
+    if let Some((_, code)) = KEY_NAMES.iter().find(|(key, _)| matches(key)) {
+        return Some(*code);
+    }
+    let number = match name.strip_prefix('F') {
+        Some(number) => number,
+        None if ignore_case => name.strip_prefix('f')?,
+        None => return None,
+    };
+    number
+        .parse()
+        .ok()
+        .filter(|n| (1..=24).contains(n))
+        .map(KeyCode::F)
+}
+
+/// Normalized form of a key, that is stored in the key sequences of the [`CommandParser`].
+///
+/// Characters without modifiers are stored as they are (shift only changes the case),
+/// everything else is written in angle brackets, like `<ctrl-d>`, `<F2>` or `<alt-Enter>`.
+/// A literal `<` is stored as `<lt>`.
+fn key_token(code: KeyCode, modifiers: KeyModifiers) -> Option<String> {
+    let mut prefix = String::new();
+    for (modifier, name) in [
+        (KeyModifiers::CONTROL, "ctrl-"),
+        (KeyModifiers::ALT, "alt-"),
+        (KeyModifiers::META, "meta-"),
+    ] {
+        if modifiers.contains(modifier) {
+            prefix.push_str(name);
+        }
+    }
+    let key = match code {
+        // Shift is part of the character itself
+        KeyCode::Char(c) if c.is_ascii_lowercase() && modifiers.contains(KeyModifiers::SHIFT) => {
+            c.to_ascii_uppercase().to_string()
+        }
+        KeyCode::Char(' ') if !prefix.is_empty() => "Space".to_string(),
+        KeyCode::Char('<') => "lt".to_string(),
+        KeyCode::Char(c) if prefix.is_empty() => return Some(c.to_string()),
+        KeyCode::Char(c) => c.to_string(),
+        KeyCode::F(n) => format!("F{n}"),
+        code => {
+            let (name, _) = KEY_NAMES.iter().find(|(_, key)| *key == code)?;
+            if modifiers.contains(KeyModifiers::SHIFT) {
+                prefix.push_str("shift-");
+            }
+            name.to_string()
+        }
+    };
+    Some(format!("<{prefix}{key}>"))
+}
+
+/// Parses a single key with optional modifiers (like `ctrl-shift-p`, `F2` or `alt-Enter`).
+fn parse_key(key: &str) -> Result<String, String> {
+    let mut modifiers = KeyModifiers::NONE;
+    let mut rest = key;
+    loop {
+        let lowercase = rest.to_ascii_lowercase();
+        let (modifier, len) = if lowercase.starts_with("ctrl-") {
+            (KeyModifiers::CONTROL, 5)
+        } else if lowercase.starts_with("alt-") {
+            (KeyModifiers::ALT, 4)
+        } else if lowercase.starts_with("meta-") {
+            (KeyModifiers::META, 5)
+        } else if lowercase.starts_with("shift-") {
+            (KeyModifiers::SHIFT, 6)
+        } else {
+            break;
+        };
+        // "ctrl--" binds the minus key
+        if rest.len() == len {
+            break;
+        }
+        modifiers |= modifier;
+        rest = &rest[len..];
+    }
+    let mut chars = rest.chars();
+    let code = match (chars.next(), chars.next()) {
+        (Some(c), None) => KeyCode::Char(c),
+        _ if rest.eq_ignore_ascii_case("lt") => KeyCode::Char('<'),
+        _ => named_key(rest, true).ok_or_else(|| format!("unknown key '{rest}'"))?,
+    };
+    key_token(code, modifiers).ok_or_else(|| format!("unsupported key '{key}'"))
+}
+
+/// Converts a keybinding of the `keys.toml` into its normalized key sequence (see [`key_token`]).
+///
+/// A binding is a sequence of keys, that are separated by spaces:
+/// plain characters are typed one after another (`gg`, `delete`),
+/// keys with modifiers are written like `ctrl-d` or `ctrl-shift-p`,
+/// and special keys by their name (`F2`, `Enter`, `Space`, `Esc`, `Up`, ...).
+/// All keys can also be written in angle brackets (`<ctrl-d>`, `<enter>`),
+/// in which case their names are case-insensitive. A single space binds the space bar.
+pub fn parse_binding(binding: &str) -> Result<String, String> {
+    if binding.trim().is_empty() {
+        return match binding {
+            "" => Err("empty keybinding".to_string()),
+            _ => Ok(" ".to_string()),
+        };
+    }
+    let mut sequence = String::new();
+    for token in binding.split_whitespace() {
+        let has_modifier = ["ctrl-", "alt-", "meta-", "shift-"].iter().any(|m| {
+            token.len() > m.len()
+                && token
+                    .get(..m.len())
+                    .is_some_and(|p| p.eq_ignore_ascii_case(m))
+        });
+        if let Some(key) = token
+            .strip_prefix('<')
+            .and_then(|t| t.strip_suffix('>'))
+            .filter(|key| !key.is_empty())
+        {
+            sequence.push_str(&parse_key(key)?);
+        } else if has_modifier || named_key(token, false).is_some() {
+            sequence.push_str(&parse_key(token)?);
+        } else {
+            for c in token.chars() {
+                match c {
+                    '<' => sequence.push_str("<lt>"),
+                    c => sequence.push(c),
+                }
+            }
+        }
+    }
+    Ok(sequence)
+}
+
+/// Converts a normalized key sequence back into the syntax of the `keys.toml` (e.g. `g ctrl-d`).
+fn display_binding(sequence: &str) -> String {
+    let mut display = String::new();
+    let mut rest = sequence;
+    let mut after_key = false;
+    while let Some(c) = rest.chars().next() {
+        let key = rest
+            .strip_prefix('<')
+            .and_then(|r| r.split_once('>'))
+            .filter(|(key, _)| !key.is_empty());
+        match key {
+            Some(("lt", tail)) => {
+                if after_key {
+                    display.push(' ');
+                }
+                display.push('<');
+                after_key = false;
+                rest = tail;
+            }
+            Some((key, tail)) => {
+                if !display.is_empty() {
+                    display.push(' ');
+                }
+                display.push_str(key);
+                after_key = true;
+                rest = tail;
+            }
+            None => {
+                if after_key {
+                    display.push(' ');
+                }
+                display.push(c);
+                after_key = false;
+                rest = &rest[c.len_utf8()..];
+            }
+        }
+    }
+    display
+}
+
 /// Takes the incoming key-events, and returns the corresponding command.
 ///
-/// Uses a `StringPatriciaMap` to match patterns of keystrokes,
-/// and a normal `HashMap` to match "oneshot"-commands,
-/// that don't require any key combinations but may require a modifier.
+/// Uses a `StringPatriciaMap` to match the sequences of keystrokes.
+/// All keys are stored in their normalized form (see [`key_token`]),
+/// so that special keys and keys with modifiers can be part of a sequence.
 pub struct CommandParser {
     key_commands: StringPatriciaMap<Command>,
-    mod_commands: HashMap<KeyEvent, Command>,
-    buffer: String,
+    /// Normalized keys that were typed so far
+    buffer: Vec<String>,
+    /// Digits that were typed before the command (like the "5" in "5j")
+    count: String,
+    /// Count of the last command that was returned by `add_event`
+    last_count: Option<usize>,
+    /// Time after the last key, after which an unfinished sequence is dropped
+    timeout: Option<Duration>,
+    /// Time after the last key, after which the continuations of an unfinished sequence are shown
+    popup_delay: Option<Duration>,
+    last_key: Instant,
 }
 
 impl CommandParser {
@@ -219,19 +884,139 @@ impl CommandParser {
         let mut parser = CommandParser::new();
         // General commands
         parser.insert(config.general.search, Command::Search);
+        parser.insert(
+            config.general.search_backward.unwrap_or_default(),
+            Command::SearchBackward,
+        );
+        parser.insert(config.general.grep.unwrap_or_default(), Command::Grep);
         parser.insert(config.general.mark, Command::Mark);
+        parser.insert(
+            config.general.mark_glob.unwrap_or_default(),
+            Command::MarkGlob,
+        );
+        parser.insert(
+            config.general.mark_by_age.unwrap_or_default(),
+            Command::MarkByAge,
+        );
+        parser.insert(
+            config.general.mark_by_type.unwrap_or_default(),
+            Command::MarkByType,
+        );
+        parser.insert(
+            config.general.visual_mode.unwrap_or_default(),
+            Command::VisualMode,
+        );
+        parser.insert(
+            config.general.mark_all.unwrap_or_default(),
+            Command::MarkAll,
+        );
+        parser.insert(
+            config.general.invert_marks.unwrap_or_default(),
+            Command::InvertMarks,
+        );
+        parser.insert(
+            config.general.unmark_all.unwrap_or_default(),
+            Command::UnmarkAll,
+        );
         parser.insert(config.general.next, Command::Next);
         parser.insert(config.general.previous, Command::Previous);
+        parser.insert(
+            config.general.next_tagged.unwrap_or_default(),
+            Command::NextTagged,
+        );
+        parser.insert(
+            config.general.previous_tagged.unwrap_or_default(),
+            Command::PreviousTagged,
+        );
         parser.insert(config.general.toggle_hidden, Command::ToggleHidden);
+        parser.insert(
+            config.general.toggle_details.unwrap_or_default(),
+            Command::ToggleDetails,
+        );
+        parser.insert(
+            config.general.toggle_flat.unwrap_or_default(),
+            Command::ToggleFlat,
+        );
+        parser.insert(
+            config.general.group_by_extension.unwrap_or_default(),
+            Command::GroupByExtension,
+        );
+        parser.insert(
+            config.general.toggle_group.unwrap_or_default(),
+            Command::ToggleGroup,
+        );
+        parser.insert(
+            config.general.expand_groups.unwrap_or_default(),
+            Command::ExpandGroups,
+        );
+        parser.insert(
+            config.general.toggle_preview.unwrap_or_default(),
+            Command::TogglePreview,
+        );
+        parser.insert(
+            config.general.toggle_zen.unwrap_or_default(),
+            Command::ToggleZen,
+        );
+        parser.insert(
+            config.general.cycle_layout.unwrap_or_default(),
+            Command::CycleLayout,
+        );
+        parser.insert(
+            config.general.toggle_caching.unwrap_or_default(),
+            Command::ToggleCaching,
+        );
+        parser.insert(
+            config.general.clear_cache.unwrap_or_default(),
+            Command::ClearCache,
+        );
+        parser.insert(
+            config.general.cycle_sort.unwrap_or_default(),
+            Command::CycleSort,
+        );
+        parser.insert(
+            config.general.cycle_theme.unwrap_or_default(),
+            Command::CycleTheme,
+        );
+        parser.insert(
+            config.general.reload_config.unwrap_or_default(),
+            Command::ReloadConfig,
+        );
+        let image_commands = [
+            (config.general.image_zoom_in, ImageCmd::ZoomIn),
+            (config.general.image_zoom_out, ImageCmd::ZoomOut),
+            (config.general.image_fit, ImageCmd::CycleFit),
+            (config.general.image_pan_left, ImageCmd::PanLeft),
+            (config.general.image_pan_right, ImageCmd::PanRight),
+            (config.general.image_pan_up, ImageCmd::PanUp),
+            (config.general.image_pan_down, ImageCmd::PanDown),
+        ];
+        for (bindings, cmd) in image_commands {
+            parser.insert(bindings.unwrap_or_default(), Command::Image(cmd));
+        }
         parser.insert(
             config.general.toggle_log.unwrap_or_default(),
             Command::ToggleLog,
         );
+        parser.insert(
+            config.general.context_menu.unwrap_or_default(),
+            Command::ContextMenu,
+        );
+        parser.insert(config.general.help.unwrap_or_default(), Command::Help);
+        parser.insert(config.general.jobs.unwrap_or_default(), Command::Jobs);
+        parser.insert(config.general.doctor.unwrap_or_default(), Command::Doctor);
+        parser.insert(
+            config.general.properties.unwrap_or_default(),
+            Command::Properties,
+        );
         parser.insert(config.general.view_trash, Command::ViewTrash);
         parser.insert(config.general.quit, Command::Quit);
         if let Some(quit_cmd) = config.general.quit_no_cd {
             parser.insert(quit_cmd, Command::QuitWithoutPath);
         }
+        parser.insert(
+            config.general.select_for_picker.unwrap_or_default(),
+            Command::SelectForPicker,
+        );
 
         // Movement commands
         parser.insert(config.movement.up, Command::Move(Move::Up));
@@ -260,10 +1045,25 @@ impl CommandParser {
             config.movement.jump_previous,
             Command::Move(Move::JumpPrevious),
         );
+        parser.insert(
+            config.movement.history_back.unwrap_or_default(),
+            Command::Move(Move::HistoryBack),
+        );
+        parser.insert(
+            config.movement.history_forward.unwrap_or_default(),
+            Command::Move(Move::HistoryForward),
+        );
         for (keys, path) in config.movement.jump_to {
+            let (expanded, unknown) = ExpandedPath::with_unknown(&path);
+            if !unknown.is_empty() {
+                warn!(
+                    "jump target '{path}' of '{keys}' uses unknown variables or users: {}",
+                    unknown.join(", ")
+                );
+            }
             parser
                 .key_commands
-                .insert(keys, Command::Move(Move::JumpTo(path.into())));
+                .insert(keys, Command::Move(Move::JumpTo(expanded)));
         }
         // Manipulation commands
         parser.insert(
@@ -274,15 +1074,108 @@ impl CommandParser {
             config.manipulation.zoxide_query.unwrap_or_default(),
             Command::Cd { zoxide: true },
         );
+        parser.insert(
+            config.manipulation.jump_frecent.unwrap_or_default(),
+            Command::JumpFrecent,
+        );
+        parser.insert(
+            config.manipulation.recent_files.unwrap_or_default(),
+            Command::RecentFiles,
+        );
+        parser.insert(
+            config.manipulation.dir_history.unwrap_or_default(),
+            Command::DirHistory,
+        );
+        parser.insert(
+            config.manipulation.breadcrumbs.unwrap_or_default(),
+            Command::Breadcrumbs,
+        );
+        parser.insert(
+            config.manipulation.connect_remote.unwrap_or_default(),
+            Command::ConnectRemote,
+        );
+        parser.insert(
+            config.manipulation.drives.unwrap_or_default(),
+            Command::Drives,
+        );
+        parser.insert(
+            config.manipulation.shell.unwrap_or_default(),
+            Command::Shell,
+        );
+        parser.insert(
+            config.manipulation.open_marked.unwrap_or_default(),
+            Command::OpenMarked,
+        );
+        parser.insert(
+            config.manipulation.terminal.unwrap_or_default(),
+            Command::Terminal,
+        );
         parser.insert(config.manipulation.rename, Command::Rename);
         parser.insert(config.manipulation.mkdir, Command::Mkdir);
         parser.insert(config.manipulation.touch, Command::Touch);
         parser.insert(config.manipulation.cut, Command::Cut);
         parser.insert(config.manipulation.copy, Command::Copy);
         parser.insert(config.manipulation.delete, Command::Delete);
-        parser.insert(config.manipulation.zip, Command::Zip);
-        parser.insert(config.manipulation.tar, Command::Tar);
+        parser.insert(
+            config.manipulation.toggle_exec.unwrap_or_default(),
+            Command::ToggleExec,
+        );
+        parser.insert(
+            config.manipulation.checksum_md5.unwrap_or_default(),
+            Command::Checksum(HashAlgorithm::Md5),
+        );
+        parser.insert(
+            config.manipulation.checksum_sha256.unwrap_or_default(),
+            Command::Checksum(HashAlgorithm::Sha256),
+        );
+        let archive = config.manipulation.archive.into_iter().flatten();
+        let deprecated = config
+            .manipulation
+            .zip
+            .into_iter()
+            .chain(config.manipulation.tar);
+        parser.insert(
+            archive.chain(deprecated.flatten()).collect(),
+            Command::Archive,
+        );
         parser.insert(config.manipulation.extract, Command::Extract);
+        parser.insert(
+            config.manipulation.extract_to.unwrap_or_default(),
+            Command::ExtractTo,
+        );
+        parser.insert(
+            config.manipulation.restore_trash.unwrap_or_default(),
+            Command::RestoreTrash,
+        );
+        parser.insert(
+            config.manipulation.empty_trash.unwrap_or_default(),
+            Command::EmptyTrash,
+        );
+        parser.insert(
+            config.manipulation.copy_contents.unwrap_or_default(),
+            Command::CopyContents,
+        );
+        parser.insert(
+            config.manipulation.paste_to_file.unwrap_or_default(),
+            Command::PasteToFile,
+        );
+        parser.insert(
+            config.manipulation.stage.unwrap_or_default(),
+            Command::Stage,
+        );
+        parser.insert(
+            config.manipulation.unstage_all.unwrap_or_default(),
+            Command::UnstageAll,
+        );
+        parser.insert(config.manipulation.tag.unwrap_or_default(), Command::Tag);
+        parser.insert(
+            config.manipulation.untag.unwrap_or_default(),
+            Command::Untag,
+        );
+        parser.insert(
+            config.manipulation.tagged_items.unwrap_or_default(),
+            Command::TaggedItems,
+        );
         parser.insert(
             config.manipulation.paste,
             Command::Paste { overwrite: false },
@@ -291,85 +1184,73 @@ impl CommandParser {
             config.manipulation.paste_overwrite,
             Command::Paste { overwrite: true },
         );
-
-        parser
-    }
-
-    pub fn new() -> Self {
-        let mut mod_commands = HashMap::new();
-        // Insert basic arrow key movement
-        mod_commands.insert(
-            KeyEvent::new(KeyCode::Up, KeyModifiers::NONE),
-            Command::Move(Move::Up),
+        parser.insert(
+            config.manipulation.paste_verified.unwrap_or_default(),
+            Command::PasteVerified,
         );
-        mod_commands.insert(
-            KeyEvent::new(KeyCode::Down, KeyModifiers::NONE),
-            Command::Move(Move::Down),
+        parser.insert(
+            config.manipulation.paste_preview.unwrap_or_default(),
+            Command::PastePreview,
         );
-        mod_commands.insert(
-            KeyEvent::new(KeyCode::Left, KeyModifiers::NONE),
-            Command::Move(Move::Left),
+        parser.insert(
+            config.manipulation.paste_symlink.unwrap_or_default(),
+            Command::PasteSymlink { relative: false },
         );
-        mod_commands.insert(
-            KeyEvent::new(KeyCode::Right, KeyModifiers::NONE),
-            Command::Move(Move::Right),
+        parser.insert(
+            config
+                .manipulation
+                .paste_relative_symlink
+                .unwrap_or_default(),
+            Command::PasteSymlink { relative: true },
         );
-        mod_commands.insert(
-            KeyEvent::new(KeyCode::PageUp, KeyModifiers::NONE),
-            Command::Move(Move::PageBackward),
+        parser.insert(
+            config.manipulation.paste_hardlink.unwrap_or_default(),
+            Command::PasteHardlink,
         );
-        mod_commands.insert(
-            KeyEvent::new(KeyCode::PageDown, KeyModifiers::NONE),
-            Command::Move(Move::PageForward),
+        parser.insert(
+            config.manipulation.clipboard_history.unwrap_or_default(),
+            Command::ClipboardHistory,
+        );
+        parser.insert(
+            config.manipulation.copy_to_previous.unwrap_or_default(),
+            Command::SendToPrevious { cut: false },
         );
+        parser.insert(
+            config.manipulation.move_to_previous.unwrap_or_default(),
+            Command::SendToPrevious { cut: true },
+        );
+
+        parser
+    }
+
+    pub fn new() -> Self {
+        let mut key_commands = StringPatriciaMap::new();
+        // Insert basic arrow key movement
+        key_commands.insert("<Up>", Command::Move(Move::Up));
+        key_commands.insert("<Down>", Command::Move(Move::Down));
+        key_commands.insert("<Left>", Command::Move(Move::Left));
+        key_commands.insert("<Right>", Command::Move(Move::Right));
+        key_commands.insert("<PageUp>", Command::Move(Move::PageBackward));
+        key_commands.insert("<PageDown>", Command::Move(Move::PageForward));
         CommandParser {
-            key_commands: StringPatriciaMap::new(),
-            mod_commands,
-            buffer: "".to_string(),
+            key_commands,
+            buffer: Vec::new(),
+            count: "".to_string(),
+            last_count: None,
+            timeout: None,
+            popup_delay: None,
+            last_key: Instant::now(),
         }
     }
 
+    /// Adds the keybindings of the command (see [`parse_binding`] for the syntax).
     fn insert(&mut self, bindings: Vec<String>, cmd: Command) {
-        for b in bindings {
-            // Check if b starts with "ctrl"
-            if b.starts_with("ctrl-") {
-                let (_, key) = b.split_at(5);
-                if key.is_empty() {
-                    continue;
-                }
-                self.mod_commands.insert(
-                    KeyEvent::new(
-                        KeyCode::Char(key.chars().next().unwrap()),
-                        KeyModifiers::CONTROL,
-                    ),
-                    cmd.clone(),
-                );
-            } else if b.starts_with("alt-") {
-                let (_, key) = b.split_at(4);
-                if key.is_empty() {
-                    continue;
-                }
-                self.mod_commands.insert(
-                    KeyEvent::new(
-                        KeyCode::Char(key.chars().next().unwrap()),
-                        KeyModifiers::ALT,
-                    ),
-                    cmd.clone(),
-                );
-            } else if b.starts_with("meta-") {
-                let (_, key) = b.split_at(5);
-                if key.is_empty() {
-                    continue;
+        for binding in bindings {
+            match parse_binding(&binding) {
+                Ok(sequence) => {
+                    self.key_commands.insert(sequence, cmd.clone());
                 }
-                self.mod_commands.insert(
-                    KeyEvent::new(
-                        KeyCode::Char(key.chars().next().unwrap()),
-                        KeyModifiers::META,
-                    ),
-                    cmd.clone(),
-                );
-            } else {
-                self.key_commands.insert(b, cmd.clone());
+                Err(e) => warn!("Invalid keybinding '{binding}' for '{cmd}': {e}"),
             }
         }
     }
@@ -422,14 +1303,54 @@ impl CommandParser {
         // Toggle hidden files
         key_commands.insert("zh", Command::ToggleHidden);
 
+        // Toggle detail view
+        key_commands.insert("zd", Command::ToggleDetails);
+        key_commands.insert("zF", Command::ToggleFlat);
+        key_commands.insert("zg", Command::GroupByExtension);
+        key_commands.insert("za", Command::ToggleGroup);
+        key_commands.insert("zR", Command::ExpandGroups);
+        key_commands.insert("zp", Command::TogglePreview);
+        key_commands.insert("zz", Command::ToggleZen);
+        key_commands.insert("zl", Command::CycleLayout);
+        key_commands.insert("zc", Command::ToggleCaching);
+        key_commands.insert("zC", Command::ClearCache);
+        key_commands.insert("zs", Command::CycleSort);
+        key_commands.insert("zt", Command::CycleTheme);
+        key_commands.insert("zr", Command::ReloadConfig);
+
+        // Zoom into image previews
+        key_commands.insert("z+", Command::Image(ImageCmd::ZoomIn));
+        key_commands.insert("z-", Command::Image(ImageCmd::ZoomOut));
+        key_commands.insert("zf", Command::Image(ImageCmd::CycleFit));
+
+        // Context menu
+        key_commands.insert(".", Command::ContextMenu);
+
+        // Help
+        key_commands.insert("?", Command::Help);
+
+        // Background jobs
+        key_commands.insert("gj", Command::Jobs);
+        key_commands.insert("g!", Command::Doctor);
+        key_commands.insert("gi", Command::Properties);
+
         // Toggle log visibility
         key_commands.insert("devlog", Command::ToggleLog);
 
         // Jump to previous location
         key_commands.insert("\'\'", Command::Move(Move::JumpPrevious));
+        key_commands.insert("H", Command::Move(Move::HistoryBack));
+        key_commands.insert("L", Command::Move(Move::HistoryForward));
 
         // Mark current file
         key_commands.insert(" ", Command::Mark);
+        key_commands.insert("*", Command::MarkGlob);
+        key_commands.insert("mt", Command::MarkByAge);
+        key_commands.insert("mf", Command::MarkByType);
+        key_commands.insert("V", Command::VisualMode);
+        key_commands.insert("ma", Command::MarkAll);
+        key_commands.insert("v", Command::InvertMarks);
+        key_commands.insert("uv", Command::UnmarkAll);
 
         // Copy, Paste, Cut, Delete
         key_commands.insert("yy", Command::Copy);
@@ -439,15 +1360,48 @@ impl CommandParser {
         key_commands.insert("pp", Command::Paste { overwrite: false });
         key_commands.insert("paste", Command::Paste { overwrite: false });
         key_commands.insert("po", Command::Paste { overwrite: true });
+        key_commands.insert("pv", Command::PasteVerified);
+        key_commands.insert("pd", Command::PastePreview);
+        key_commands.insert("pl", Command::PasteSymlink { relative: false });
+        key_commands.insert("pL", Command::PasteSymlink { relative: true });
+        key_commands.insert("phl", Command::PasteHardlink);
+        key_commands.insert("pc", Command::ClipboardHistory);
+        key_commands.insert("yc", Command::CopyContents);
+        key_commands.insert("pf", Command::PasteToFile);
+        key_commands.insert("ys", Command::Stage);
+        key_commands.insert("us", Command::UnstageAll);
+        key_commands.insert("+t", Command::Tag);
+        key_commands.insert("-t", Command::Untag);
+        key_commands.insert("gt", Command::TaggedItems);
         key_commands.insert("delete", Command::Delete);
+        key_commands.insert("restore", Command::RestoreTrash);
+        key_commands.insert("empty_trash", Command::EmptyTrash);
+        key_commands.insert("+x", Command::ToggleExec);
+        key_commands.insert("md5", Command::Checksum(HashAlgorithm::Md5));
+        key_commands.insert("sha256", Command::Checksum(HashAlgorithm::Sha256));
+        key_commands.insert("yp", Command::SendToPrevious { cut: false });
+        key_commands.insert("dp", Command::SendToPrevious { cut: true });
 
         // Search
         key_commands.insert("/", Command::Search);
+        key_commands.insert("g?", Command::SearchBackward);
+        key_commands.insert("rg", Command::Grep);
         key_commands.insert("n", Command::Next);
         key_commands.insert("N", Command::Previous);
+        key_commands.insert("]t", Command::NextTagged);
+        key_commands.insert("[t", Command::PreviousTagged);
 
         // cd, mkdir, touch
         key_commands.insert("cd", Command::Cd { zoxide: false });
+        key_commands.insert("gf", Command::JumpFrecent);
+        key_commands.insert("go", Command::RecentFiles);
+        key_commands.insert("gH", Command::DirHistory);
+        key_commands.insert("gB", Command::Breadcrumbs);
+        key_commands.insert("ssh", Command::ConnectRemote);
+        key_commands.insert("gM", Command::Drives);
+        key_commands.insert("S", Command::Shell);
+        key_commands.insert("O", Command::OpenMarked);
+        key_commands.insert("T", Command::Terminal);
         key_commands.insert("mkdir", Command::Mkdir);
         key_commands.insert("touch", Command::Touch);
 
@@ -456,115 +1410,415 @@ impl CommandParser {
 
         // Quit
         key_commands.insert("q", Command::Quit);
+        key_commands.insert("pick", Command::SelectForPicker);
 
         // --- Commands for modifier + key:
-        let mut mod_commands = HashMap::new();
 
-        // Search
-        mod_commands.insert(CTRL_F, Command::Search);
+        // Pan image previews
+        key_commands.insert("<alt-h>", Command::Image(ImageCmd::PanLeft));
+        key_commands.insert("<alt-j>", Command::Image(ImageCmd::PanDown));
+        key_commands.insert("<alt-k>", Command::Image(ImageCmd::PanUp));
+        key_commands.insert("<alt-l>", Command::Image(ImageCmd::PanRight));
 
         // Copy, Paste, Cut
-        mod_commands.insert(CTRL_C, Command::Copy);
-        mod_commands.insert(CTRL_X, Command::Cut);
-        mod_commands.insert(CTRL_V, Command::Paste { overwrite: false });
-        mod_commands.insert(CTRL_SHIFT_V, Command::Paste { overwrite: true });
+        key_commands.insert("<ctrl-c>", Command::Copy);
+        key_commands.insert("<ctrl-x>", Command::Cut);
+        key_commands.insert("<ctrl-v>", Command::Paste { overwrite: false });
+        key_commands.insert("<ctrl-V>", Command::Paste { overwrite: true });
 
-        // Escape from what you are doing
-        // mod_commands.insert(CTRL_C, Command::Esc);
+        // Midnight-commander style transfer to the "other" (previous) directory
+        key_commands.insert("<F5>", Command::SendToPrevious { cut: false });
+        key_commands.insert("<F6>", Command::SendToPrevious { cut: true });
 
         // Advanced movement
-        mod_commands.insert(
-            KeyEvent::new(KeyCode::Char('f'), KeyModifiers::CONTROL),
-            Command::Move(Move::PageForward),
-        );
-        mod_commands.insert(
-            KeyEvent::new(KeyCode::Char('b'), KeyModifiers::CONTROL),
-            Command::Move(Move::PageBackward),
-        );
-        mod_commands.insert(
-            KeyEvent::new(KeyCode::Char('d'), KeyModifiers::CONTROL),
-            Command::Move(Move::HalfPageForward),
-        );
-        mod_commands.insert(
-            KeyEvent::new(KeyCode::Char('u'), KeyModifiers::CONTROL),
-            Command::Move(Move::HalfPageBackward),
-        );
-
-        // Toggle hidden (backspace)
-        // mod_commands.insert(
-        //     KeyEvent::new(KeyCode::Backspace, KeyModifiers::NONE),
-        //     Command::ToggleHidden,
-        // );
+        key_commands.insert("<ctrl-f>", Command::Move(Move::PageForward));
+        key_commands.insert("<ctrl-b>", Command::Move(Move::PageBackward));
+        key_commands.insert("<ctrl-d>", Command::Move(Move::HalfPageForward));
+        key_commands.insert("<ctrl-u>", Command::Move(Move::HalfPageBackward));
 
         CommandParser {
             key_commands,
-            mod_commands,
-            buffer: "".to_string(),
+            buffer: Vec::new(),
+            count: "".to_string(),
+            last_count: None,
+            timeout: None,
+            popup_delay: None,
+            last_key: Instant::now(),
         }
     }
 
     pub fn buffer(&self) -> String {
-        self.buffer.clone()
+        format!("{}{}", self.count, display_binding(&self.buffer.concat()))
+    }
+
+    /// Returns the count prefix of the last parsed command (e.g. `5` for `5j`).
+    pub fn count(&self) -> Option<usize> {
+        self.last_count
     }
 
+    /// Returns the typed keys of an unfinished sequence (without the count).
+    pub fn pending_keys(&self) -> String {
+        display_binding(&self.buffer.concat())
+    }
+
+    /// Returns the remaining keys and the description of all commands,
+    /// that continue the typed sequence.
     pub fn matching_commands(&self) -> Vec<(String, String)> {
         if self.buffer.is_empty() {
             Vec::new()
         } else {
+            let prefix = self.buffer.concat();
             self.key_commands
-                .iter_prefix(&self.buffer)
-                .map(|(k, v)| (k.clone(), v.to_string()))
+                .iter_prefix(&prefix)
+                .filter(|(k, _)| k.len() > prefix.len())
+                .map(|(k, v)| (display_binding(&k[prefix.len()..]), v.to_string()))
                 .collect()
         }
     }
 
     pub fn clear(&mut self) {
         self.buffer.clear();
+        self.count.clear();
+    }
+
+    /// Drops unfinished sequences (and counts), if no key was pressed for `timeout`.
+    pub fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.timeout = timeout;
+    }
+
+    /// Returns the time at which the typed keys are dropped (see [`CommandParser::set_timeout`]),
+    /// or `None` if nothing is pending.
+    pub fn deadline(&self) -> Option<Instant> {
+        if self.buffer.is_empty() && self.count.is_empty() {
+            return None;
+        }
+        self.timeout.map(|timeout| self.last_key + timeout)
+    }
+
+    /// Replaces the keybindings with the ones of the config (the timeouts are kept).
+    pub fn set_bindings(&mut self, config: KeyConfig) {
+        self.key_commands = CommandParser::from_config(config).key_commands;
+        self.clear();
+    }
+
+    /// Shows the continuations of unfinished sequences after `delay` (see [`CommandParser::popup_deadline`]).
+    pub fn set_popup_delay(&mut self, delay: Option<Duration>) {
+        self.popup_delay = delay;
+    }
+
+    /// Returns the time at which the continuations of the typed keys should be shown,
+    /// or `None` if no sequence is pending.
+    pub fn popup_deadline(&self) -> Option<Instant> {
+        if self.buffer.is_empty() {
+            return None;
+        }
+        self.popup_delay.map(|delay| self.last_key + delay)
+    }
+
+    /// Returns the command and remembers the count that was typed before it.
+    fn finish(&mut self, command: Command) -> Command {
+        trace!("Command: {:?}", command);
+        self.buffer.clear();
+        self.last_count = self.count.parse().ok();
+        self.count.clear();
+        command
+    }
+
+    /// Returns all keybindings of the given command (e.g. `"zh"` or `"ctrl-f"`).
+    ///
+    /// Sequences of plain characters come first.
+    pub fn bindings(&self, command: &Command) -> Vec<String> {
+        let mut bindings: Vec<String> = self
+            .key_commands
+            .iter()
+            .filter(|(_, c)| *c == command)
+            .map(|(k, _)| k)
+            .collect();
+        bindings.sort_by_key(|k| k.starts_with('<') && !k.starts_with("<lt>"));
+        bindings.iter().map(|k| display_binding(k)).collect()
+    }
+
+    /// Lists the keybindings of all commands, grouped by their category.
+    ///
+    /// Every entry consists of the keys and the description of the command.
+    /// The entries of a category are sorted by their description.
+    pub fn help(&self) -> Vec<(&'static str, Vec<(String, String)>)> {
+        let mut commands: Vec<&Command> = Vec::new();
+        for command in self.key_commands.values() {
+            if !commands.contains(&command) {
+                commands.push(command);
+            }
+        }
+        HELP_CATEGORIES
+            .iter()
+            .map(|&category| {
+                let mut entries: Vec<(String, String)> = commands
+                    .iter()
+                    .filter(|command| command.category() == category)
+                    .map(|command| {
+                        let keys: Vec<String> = self
+                            .bindings(command)
+                            .into_iter()
+                            .map(|k| if k == " " { "space".to_string() } else { k })
+                            .collect();
+                        (keys.join(", "), command.to_string())
+                    })
+                    .filter(|(keys, _)| !keys.is_empty())
+                    .collect();
+                entries.sort_by(|a, b| a.1.cmp(&b.1));
+                (category, entries)
+            })
+            .collect()
+    }
+
+    /// Generates the tips, that are shown in the footer while rfm is idle.
+    ///
+    /// The tips use the shortest binding of each command,
+    /// and commands without any binding are left out.
+    pub fn hints(&self) -> Vec<String> {
+        HINT_COMMANDS
+            .iter()
+            .filter_map(|command| {
+                let key = self
+                    .bindings(command)
+                    .into_iter()
+                    .min_by_key(|b| b.chars().count())?;
+                let key = if key == " " { "space".to_string() } else { key };
+                Some(format!("press {key} to {command}"))
+            })
+            .collect()
     }
 
     /// Parse an event and return the command that is assigned to it
+    ///
+    /// Leading digits are a count prefix (like in vim), unless there is a keybinding
+    /// that starts with them. The count is available via [`CommandParser::count`].
+    /// Backspace removes the last typed key, if there is any.
     pub fn add_event(&mut self, event: KeyEvent) -> Command {
-        if let KeyCode::Backspace = event.code {
-            self.buffer.pop();
+        self.last_count = None;
+        self.last_key = Instant::now();
+        if event.code == KeyCode::Backspace && !(self.buffer.is_empty() && self.count.is_empty()) {
+            if self.buffer.pop().is_none() {
+                self.count.pop();
+            }
             return Command::None;
         }
-        match event.modifiers {
-            // First parse for "normal" characters:
-            KeyModifiers::NONE | KeyModifiers::SHIFT => {
-                // Put character into buffer
-                if let KeyCode::Char(c) = event.code {
-                    if event.modifiers.contains(KeyModifiers::SHIFT) {
-                        // uppercase
-                        self.buffer.push(c.to_ascii_uppercase());
-                    } else {
-                        // lowercase
-                        self.buffer.push(c.to_ascii_lowercase());
-                    }
-                }
-
-                // Check if there are commands with that prefix
-                if self.key_commands.iter_prefix(&self.buffer).count() == 0 {
-                    self.buffer.clear();
-                    return Command::None;
-                }
+        let Some(key) = key_token(event.code, event.modifiers) else {
+            return Command::None;
+        };
+        // "0" alone is not a count, so it can still be used as a keybinding
+        if let Some(c) = key.chars().next().filter(|c| c.is_ascii_digit()) {
+            let is_count = key.len() == 1
+                && self.buffer.is_empty()
+                && (c != '0' || !self.count.is_empty())
+                && self.key_commands.iter_prefix(&key).next().is_none();
+            if is_count {
+                self.count.push(c);
+                return Command::None;
+            }
+        }
+        self.buffer.push(key);
 
-                // Check if we have a valid command
-                if let Some(command) = self.key_commands.get(&self.buffer) {
-                    self.buffer.clear();
-                    trace!("Command: {:?}", command);
-                    return command.clone();
-                }
+        // Check if there are commands with that prefix
+        let mut sequence = self.buffer.concat();
+        if self.key_commands.iter_prefix(&sequence).next().is_none() {
+            // Special keys (like "ctrl-d" or "F5") also work in the middle of an unfinished sequence
+            let key = self.buffer.pop().unwrap_or_default();
+            if self.buffer.is_empty() || !key.starts_with('<') || key == "<lt>" {
+                self.clear();
+                return Command::None;
+            }
+            self.buffer = vec![key];
+            sequence = self.buffer.concat();
+            if self.key_commands.iter_prefix(&sequence).next().is_none() {
+                self.clear();
+                return Command::None;
             }
-            _ => {}
         }
-        // If we have not returned yet,
-        // always check if there is a oneshot command assigned to the
-        // incoming event.
-        if let Some(command) = self.mod_commands.get(&event) {
-            self.buffer.clear();
-            trace!("Command: {:?}", command);
-            return command.clone();
+
+        // Check if we have a valid command
+        if let Some(command) = self.key_commands.get(&sequence).cloned() {
+            return self.finish(command);
         }
         Command::None
     }
 }
+
+#[test]
+fn hints_follow_bindings() {
+    let parser = CommandParser::default_bindings();
+    let hints = parser.hints();
+    assert!(hints.contains(&"press zh to toggle hidden files".to_string()));
+    assert!(hints.contains(&"press space to mark selected item".to_string()));
+    assert_eq!(
+        parser.bindings(&Command::SendToPrevious { cut: false }),
+        vec!["yp", "F5"]
+    );
+
+    let mut parser = CommandParser::new();
+    parser.insert(vec!["H".into(), "alt-h".into()], Command::ToggleHidden);
+    let hints = parser.hints();
+    assert_eq!(hints[0], "press H to toggle hidden files");
+    assert!(!hints.iter().any(|h| h.contains("keybindings")));
+    assert!(!hints.iter().any(|h| h.contains("mark")));
+}
+
+#[test]
+fn help_lists_active_bindings() {
+    let mut parser = CommandParser::new();
+    parser.insert(vec!["H".into(), "alt-h".into()], Command::ToggleHidden);
+    parser.insert(vec!["dd".into()], Command::Cut);
+    let help = parser.help();
+    let categories: Vec<&str> = help.iter().map(|(c, _)| *c).collect();
+    assert_eq!(categories, HELP_CATEGORIES);
+    assert_eq!(
+        help[1].1,
+        vec![("dd".to_string(), "cut selected items".to_string())]
+    );
+    assert_eq!(
+        help[2].1,
+        vec![("H, alt-h".to_string(), "toggle hidden files".to_string())]
+    );
+}
+
+#[test]
+fn count_prefix() {
+    let mut parser = CommandParser::default_bindings();
+    let key = |c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE);
+
+    assert_eq!(parser.add_event(key('1')), Command::None);
+    assert_eq!(parser.add_event(key('2')), Command::None);
+    assert_eq!(parser.buffer(), "12");
+    assert_eq!(parser.add_event(key('j')), Command::Move(Move::Down));
+    assert_eq!(parser.count(), Some(12));
+
+    // The count is not kept for the next command
+    assert_eq!(parser.add_event(key('g')), Command::None);
+    assert_eq!(parser.add_event(key('g')), Command::Move(Move::Top));
+    assert_eq!(parser.count(), None);
+
+    // Invalid sequences discard the count as well
+    parser.add_event(key('3'));
+    parser.add_event(key('x'));
+    assert_eq!(parser.buffer(), "");
+
+    // "0" without a count is no count
+    parser.add_event(key('0'));
+    assert_eq!(parser.buffer(), "");
+    parser.add_event(key('4'));
+    parser.add_event(key('0'));
+    assert_eq!(parser.add_event(key('k')), Command::Move(Move::Up));
+    assert_eq!(parser.count(), Some(40));
+}
+
+#[test]
+fn binding_syntax() {
+    let parse = |binding: &str| parse_binding(binding).unwrap();
+    assert_eq!(parse("gg"), "gg");
+    assert_eq!(parse(" "), " ");
+    assert_eq!(parse("ctrl-x"), "<ctrl-x>");
+    assert_eq!(parse("ctrl-V"), "<ctrl-V>");
+    assert_eq!(parse("ctrl-shift-p"), "<ctrl-P>");
+    assert_eq!(parse("F2"), "<F2>");
+    assert_eq!(parse("<f2>"), "<F2>");
+    assert_eq!(parse("Space"), " ");
+    assert_eq!(parse("<enter>"), "<Enter>");
+    assert_eq!(parse("alt-Enter"), "<alt-Enter>");
+    assert_eq!(parse("shift-Up"), "<shift-Up>");
+    assert_eq!(parse("g ctrl-d"), "g<ctrl-d>");
+    assert_eq!(parse("z-"), "z-");
+    assert_eq!(parse("a<b"), "a<lt>b");
+    // Words are still typed character by character
+    assert_eq!(parse("delete"), "delete");
+    assert_eq!(parse("üüü"), "üüü");
+    assert_eq!(parse("ä ctrl-ö"), "ä<ctrl-ö>");
+    assert!(parse_binding("<hyper-x>").is_err());
+    assert!(parse_binding("").is_err());
+
+    for binding in ["g ctrl-d", "F2", "ctrl-alt-Space", "a<b", "x Enter y"] {
+        assert_eq!(display_binding(&parse(binding)), binding);
+    }
+}
+
+#[test]
+fn readonly_refuses_all_mutating_commands() {
+    // Everything else in the "manipulation" section changes the filesystem,
+    // so new commands there are refused by `--readonly` unless they are listed here.
+    let reading = |command: &Command| {
+        matches!(
+            command,
+            Command::Checksum(_)
+                | Command::CopyContents
+                | Command::Stage
+                | Command::UnstageAll
+                | Command::TaggedItems
+                | Command::Cd { .. }
+                | Command::JumpFrecent
+                | Command::RecentFiles
+                | Command::DirHistory
+                | Command::Breadcrumbs
+                | Command::Shell
+                | Command::OpenMarked
+                | Command::Terminal
+                | Command::Copy
+        )
+    };
+    let parser = CommandParser::default_bindings();
+    let mut mutating = 0;
+    for command in parser.key_commands.values() {
+        let expected = command.category() == "manipulation" && !reading(command);
+        assert_eq!(command.is_mutating(), expected, "{command:?}");
+        mutating += usize::from(expected);
+    }
+    assert!(mutating > 0);
+}
+
+#[test]
+fn sequences_with_special_keys() {
+    let mut parser = CommandParser::new();
+    parser.insert(vec!["g ctrl-d".into()], Command::Drives);
+    parser.insert(vec!["F2".into()], Command::Rename);
+    parser.insert(vec!["ctrl-shift-p".into()], Command::Help);
+
+    let g = KeyEvent::new(KeyCode::Char('g'), KeyModifiers::NONE);
+    let ctrl_d = KeyEvent::new(KeyCode::Char('d'), KeyModifiers::CONTROL);
+    assert_eq!(parser.add_event(g), Command::None);
+    assert_eq!(parser.buffer(), "g");
+    assert_eq!(parser.add_event(ctrl_d), Command::Drives);
+
+    let f2 = KeyEvent::new(KeyCode::F(2), KeyModifiers::NONE);
+    assert_eq!(parser.add_event(f2), Command::Rename);
+    // Terminals report the shifted letter with or without the shift modifier
+    let ctrl_p = KeyEvent::new(KeyCode::Char('P'), KeyModifiers::CONTROL);
+    assert_eq!(parser.add_event(ctrl_p), Command::Help);
+    let ctrl_shift_p = KeyEvent::new(
+        KeyCode::Char('p'),
+        KeyModifiers::CONTROL | KeyModifiers::SHIFT,
+    );
+    assert_eq!(parser.add_event(ctrl_shift_p), Command::Help);
+
+    // Special keys interrupt an unfinished sequence
+    let down = KeyEvent::new(KeyCode::Down, KeyModifiers::NONE);
+    parser.add_event(g);
+    assert_eq!(parser.add_event(down), Command::Move(Move::Down));
+    assert_eq!(parser.buffer(), "");
+}
+
+#[test]
+fn pending_keys_time_out() {
+    let mut parser = CommandParser::default_bindings();
+    let key = |c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE);
+    parser.add_event(key('g'));
+    // Without a timeout the key is kept forever
+    assert_eq!(parser.deadline(), None);
+
+    parser.set_timeout(Some(Duration::from_secs(1)));
+    let deadline = parser.deadline().unwrap();
+    assert!(deadline <= Instant::now() + Duration::from_secs(1));
+    assert!(parser
+        .matching_commands()
+        .iter()
+        .any(|(keys, _)| keys == "g"));
+    assert_eq!(parser.add_event(key('g')), Command::Move(Move::Top));
+    assert_eq!(parser.deadline(), None);
+}
@@ -0,0 +1,168 @@
+//! Checks which of the optional programs are installed.
+//!
+//! rfm works without them, but some features (like syntax highlighting or video thumbnails)
+//! quietly fall back to something simpler, when a program is missing.
+//! The probe runs once at startup and logs what is missing - the doctor overlay
+//! (see [`Command::Doctor`](super::commands::Command::Doctor)) shows the full picture,
+//! including the programs of the config files, that cannot be found.
+use std::{
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+};
+
+use log::{info, warn};
+
+use super::{actions::custom_programs, OpenEngine};
+
+/// Optional programs (any of them is enough) and the feature that needs them
+const TOOLS: [(&[&str], &str); 16] = [
+    (&["bat"], "syntax highlighting in previews"),
+    (&["ffmpeg"], "video thumbnails"),
+    (&["ffprobe"], "thumbnails from the middle of videos"),
+    (
+        &["mediainfo"],
+        "previews of audio files (and videos without ffmpeg)",
+    ),
+    (&["zoxide"], "zoxide queries"),
+    (
+        &["rg"],
+        "fast content search (the built-in search is used instead)",
+    ),
+    (
+        &["tar"],
+        "previews, creation and extraction of tar archives",
+    ),
+    (&["unzip"], "previews and extraction of zip archives"),
+    (&["zip"], "creation of zip archives"),
+    (&["zstd"], "creation of tar.zst archives"),
+    (&["7z"], "creation of 7z archives"),
+    (&["lsblk"], "listing removable drives"),
+    (&["udisksctl"], "mounting and unmounting drives"),
+    (&["notify-send"], "desktop notifications"),
+    (
+        &["wl-copy", "xclip"],
+        "sharing copied files with other applications",
+    ),
+    (
+        &["wl-paste", "xclip", "xsel", "pbpaste"],
+        "pasting text from the system clipboard",
+    ),
+];
+
+/// Whether an optional program is installed
+#[derive(Debug, Clone)]
+pub struct ToolStatus {
+    /// Programs that provide the feature (any of them is enough)
+    pub programs: &'static [&'static str],
+    pub feature: &'static str,
+    /// Path of the first program that was found
+    pub found: Option<PathBuf>,
+}
+
+/// Program of a config entry (like the application for images in the `open.toml`)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigProgram {
+    /// Where the program is configured
+    pub entry: String,
+    pub program: String,
+}
+
+impl ConfigProgram {
+    pub fn new(entry: impl Into<String>, program: impl Into<String>) -> Self {
+        ConfigProgram {
+            entry: entry.into(),
+            program: program.into(),
+        }
+    }
+}
+
+/// Returns the path of the executable, like `which` does.
+///
+/// Names with a slash are used as they are, all others are searched in `$PATH`.
+pub fn find_program(name: &str) -> Option<PathBuf> {
+    let is_executable = |path: &Path| {
+        path.metadata()
+            .is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+    };
+    if name.contains('/') {
+        let path = PathBuf::from(name);
+        return is_executable(&path).then_some(path);
+    }
+    let paths = std::env::var_os("PATH")?;
+    std::env::split_paths(&paths)
+        .map(|dir| dir.join(name))
+        .find(|path| is_executable(path))
+}
+
+/// Checks all optional programs.
+pub fn tools() -> Vec<ToolStatus> {
+    TOOLS
+        .iter()
+        .map(|(programs, feature)| ToolStatus {
+            programs,
+            feature,
+            found: programs.iter().find_map(|program| find_program(program)),
+        })
+        .collect()
+}
+
+/// Programs of the config files: applications of the `open.toml`, the terminal and the custom actions.
+pub fn config_programs(opener: &OpenEngine) -> Vec<ConfigProgram> {
+    let mut programs = opener.programs();
+    programs.extend(custom_programs());
+    programs
+}
+
+/// Returns the programs of the config, that are not installed.
+pub fn missing_programs(programs: Vec<ConfigProgram>) -> Vec<ConfigProgram> {
+    programs
+        .into_iter()
+        .filter(|p| find_program(&p.program).is_none())
+        .collect()
+}
+
+/// Logs the missing optional programs, and warns about the programs of the config, that are missing.
+///
+/// This is a blocking operation, so it should be run inside of `spawn_blocking`.
+pub fn probe(programs: Vec<ConfigProgram>) {
+    for tool in tools().into_iter().filter(|tool| tool.found.is_none()) {
+        info!(
+            "{} is not installed - no {}",
+            tool.programs.join(" or "),
+            tool.feature
+        );
+    }
+    for missing in missing_programs(programs) {
+        warn!(
+            "'{}' of {} is not installed (see the doctor overlay)",
+            missing.program, missing.entry
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn find_programs() {
+        assert!(find_program("sh").is_some());
+        assert!(find_program("/bin/sh").is_some());
+        assert!(find_program("rfm-test-missing-program").is_none());
+        // Directories and files that are not executable do not count
+        assert!(find_program("/tmp").is_none());
+        let dir = tempfile::tempdir().unwrap();
+        let script = dir.path().join("script");
+        std::fs::write(&script, "").unwrap();
+        assert!(find_program(script.to_str().unwrap()).is_none());
+
+        let missing = missing_programs(vec![
+            ConfigProgram::new("open.toml: text", "sh"),
+            ConfigProgram::new("open.toml: image", "rfm-test-viewer"),
+        ]);
+        assert_eq!(
+            missing,
+            [ConfigProgram::new("open.toml: image", "rfm-test-viewer")]
+        );
+    }
+}
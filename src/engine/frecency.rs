@@ -0,0 +1,283 @@
+use std::{
+    collections::HashMap,
+    io::Write,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use log::{debug, warn};
+use parking_lot::Mutex;
+
+use crate::util::{lock_file, write_atomic};
+
+/// Maximum sum of all ranks, before the entries are aged.
+const MAX_TOTAL_RANK: f64 = 10_000.0;
+
+/// Delay between a visit and the save of the database, so that quickly changing
+/// directories results in a single write.
+const SAVE_DELAY: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Entry {
+    /// Number of visits (reduced over time by aging)
+    rank: f64,
+    /// Unix timestamp of the last visit
+    last_access: u64,
+}
+
+impl Entry {
+    /// Combines the number of visits with the time since the last visit (like `zoxide` does).
+    fn score(&self, now: u64) -> f64 {
+        let factor = match now.saturating_sub(self.last_access) {
+            0..=3599 => 4.0,
+            3600..=86399 => 2.0,
+            86400..=604799 => 0.5,
+            _ => 0.25,
+        };
+        self.rank * factor
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|t| t.as_secs())
+        .unwrap_or_default()
+}
+
+/// Database of visited directories, ranked by frequency and recency ("frecency").
+///
+/// The database is a plain text file with one `rank<TAB>last-access<TAB>path` entry per line.
+/// Without a file, visits are only tracked in memory.
+///
+/// Visits are saved in the background. Since several instances share the same file,
+/// a save merges the new visits into the entries on disk instead of overwriting them.
+#[derive(Debug, Default)]
+pub struct Frecency {
+    file: Option<PathBuf>,
+    entries: HashMap<PathBuf, Entry>,
+    /// Visits since the last save (shared with the background save)
+    unsaved: Arc<Mutex<HashMap<PathBuf, Visits>>>,
+}
+
+/// Visits of a directory, that are not saved yet.
+#[derive(Debug, Clone, Copy, Default)]
+struct Visits {
+    count: u32,
+    last_access: u64,
+}
+
+impl Frecency {
+    /// Loads the database from the given file. A missing file results in an empty database.
+    pub fn load(file: PathBuf) -> Self {
+        let entries = match std::fs::read_to_string(&file) {
+            Ok(content) => parse(&content),
+            Err(e) => {
+                debug!("Cannot read {}: {e}", file.display());
+                HashMap::new()
+            }
+        };
+        Frecency {
+            file: Some(file),
+            entries,
+            unsaved: Default::default(),
+        }
+    }
+
+    /// Records a visit of the given directory.
+    pub fn visit(&mut self, dir: &Path) {
+        let visit = Visits {
+            count: 1,
+            last_access: now(),
+        };
+        add_visits(&mut self.entries, [(dir.to_path_buf(), visit)]);
+
+        let Some(file) = self.file.clone() else {
+            return;
+        };
+        let schedule = {
+            let mut unsaved = self.unsaved.lock();
+            let schedule = unsaved.is_empty();
+            let visits = unsaved.entry(dir.to_path_buf()).or_default();
+            visits.count += 1;
+            visits.last_access = visit.last_access;
+            schedule
+        };
+        // A save is already scheduled, if there were unsaved visits before
+        if !schedule {
+            return;
+        }
+        let unsaved = self.unsaved.clone();
+        let job = move || save(&file, std::mem::take(&mut *unsaved.lock()));
+        match tokio::runtime::Handle::try_current() {
+            Ok(runtime) => {
+                runtime.spawn(async move {
+                    tokio::time::sleep(SAVE_DELAY).await;
+                    let _ = tokio::task::spawn_blocking(job).await;
+                });
+            }
+            Err(_) => job(),
+        }
+    }
+
+    /// Returns all existing directories, ordered by their score (best first).
+    pub fn ranked(&self) -> Vec<PathBuf> {
+        let now = now();
+        let mut entries: Vec<(&PathBuf, f64)> = self
+            .entries
+            .iter()
+            .map(|(path, entry)| (path, entry.score(now)))
+            .collect();
+        entries.sort_by(|a, b| b.1.total_cmp(&a.1));
+        entries
+            .into_iter()
+            .map(|(path, _)| path.clone())
+            .filter(|path| path.is_dir())
+            .collect()
+    }
+}
+
+impl Drop for Frecency {
+    /// Saves the visits, whose background save has not happened yet.
+    fn drop(&mut self) {
+        if let Some(file) = &self.file {
+            save(file, std::mem::take(&mut *self.unsaved.lock()));
+        }
+    }
+}
+
+/// Adds the visits to the entries and ages them, if the total rank becomes too high.
+fn add_visits(
+    entries: &mut HashMap<PathBuf, Entry>,
+    visits: impl IntoIterator<Item = (PathBuf, Visits)>,
+) {
+    for (path, visits) in visits {
+        let entry = entries.entry(path).or_insert(Entry {
+            rank: 0.0,
+            last_access: 0,
+        });
+        entry.rank += f64::from(visits.count);
+        entry.last_access = entry.last_access.max(visits.last_access);
+    }
+
+    // Age all entries, so that directories we no longer visit eventually disappear
+    if entries.values().map(|e| e.rank).sum::<f64>() > MAX_TOTAL_RANK {
+        entries.retain(|_, e| {
+            e.rank *= 0.9;
+            e.rank >= 1.0
+        });
+    }
+}
+
+/// Merges the visits into the database file.
+///
+/// The file is re-read under a lock, so that visits saved by other instances in the meantime are kept.
+fn save(file: &Path, visits: HashMap<PathBuf, Visits>) {
+    if visits.is_empty() {
+        return;
+    }
+    let result = lock_file(file, true).and_then(|_lock| {
+        let mut entries = match std::fs::read_to_string(file) {
+            Ok(content) => parse(&content),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => HashMap::new(),
+            Err(e) => return Err(e),
+        };
+        add_visits(&mut entries, visits);
+        write_atomic(file, |out| {
+            for (path, entry) in entries.iter() {
+                writeln!(
+                    out,
+                    "{}\t{}\t{}",
+                    entry.rank,
+                    entry.last_access,
+                    path.display()
+                )?;
+            }
+            Ok(())
+        })
+    });
+    if let Err(e) = result {
+        warn!("Failed to save directory history: {e}");
+    }
+}
+
+fn parse(content: &str) -> HashMap<PathBuf, Entry> {
+    content
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.splitn(3, '\t');
+            let rank = fields.next()?.parse().ok()?;
+            let last_access = fields.next()?.parse().ok()?;
+            let path = PathBuf::from(fields.next()?);
+            Some((path, Entry { rank, last_access }))
+        })
+        .collect()
+}
+
+/// Checks if all keywords appear in the path in the given order (ignoring case).
+///
+/// Like in `zoxide`, the last keyword must match the last component of the path.
+pub fn matches(path: &Path, keywords: &[&str]) -> bool {
+    let path = path.to_string_lossy().to_lowercase();
+    let mut rest = path.as_str();
+    for keyword in keywords {
+        let keyword = keyword.to_lowercase();
+        match rest.find(&keyword) {
+            Some(idx) => rest = &rest[idx + keyword.len()..],
+            None => return false,
+        }
+    }
+    match keywords.last() {
+        Some(last) => {
+            let last_component = path.rsplit('/').next().unwrap_or_default();
+            last_component.contains(&last.to_lowercase())
+        }
+        None => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_and_rank() {
+        let now = now();
+        let content = format!(
+            "10\t{}\t/old/but/frequent\n3\t{now}\t/recent\ninvalid line\n",
+            now - 30 * 86400
+        );
+        let entries = parse(&content);
+        assert_eq!(entries.len(), 2);
+        let old = entries[Path::new("/old/but/frequent")].score(now);
+        let recent = entries[Path::new("/recent")].score(now);
+        assert!(recent > old);
+    }
+
+    #[test]
+    fn merge_visits_of_other_instances() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("history.db");
+        let mut first = Frecency::load(file.clone());
+        let mut second = Frecency::load(file.clone());
+        first.visit(Path::new("/first"));
+        second.visit(Path::new("/second"));
+        second.visit(Path::new("/first"));
+        drop((first, second));
+
+        let entries = parse(&std::fs::read_to_string(&file).unwrap());
+        assert_eq!(entries[Path::new("/first")].rank, 2.0);
+        assert_eq!(entries[Path::new("/second")].rank, 1.0);
+    }
+
+    #[test]
+    fn keyword_matching() {
+        let path = Path::new("/home/user/Projects/rfm");
+        assert!(matches(path, &[]));
+        assert!(matches(path, &["rfm"]));
+        assert!(matches(path, &["proj", "rfm"]));
+        assert!(!matches(path, &["rfm", "proj"]));
+        assert!(!matches(path, &["proj"]));
+    }
+}
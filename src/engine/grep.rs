@@ -0,0 +1,302 @@
+//! Search for the content of files below a directory.
+//!
+//! Uses `ripgrep` if it is installed (which supports regular expressions and respects `.gitignore`),
+//! and a slower built-in search for fixed strings otherwise.
+//! Both use "smart case": The search ignores case, unless the pattern contains an uppercase letter.
+use std::{
+    io::{BufRead, BufReader, Read},
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use log::{debug, info};
+use once_cell::sync::OnceCell;
+use parking_lot::Mutex;
+use tokio::sync::mpsc;
+use walkdir::WalkDir;
+
+use crate::jobs::{self, CancelToken, JobKind};
+
+/// The search stops after this many matches
+pub const MAX_MATCHES: usize = 10_000;
+
+/// Larger files are skipped by the built-in search
+const MAX_FILE_SIZE: u64 = 16 * 1024 * 1024;
+
+/// Minimum time between two notifications about new matches
+const NOTIFY_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Returns true if `rg` is installed on the system.
+pub fn ripgrep_installed() -> bool {
+    static RIPGREP_INSTALLED: OnceCell<bool> = OnceCell::new();
+    *RIPGREP_INSTALLED.get_or_init(|| {
+        Command::new("rg")
+            .arg("--version")
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .map(|s| s.success())
+            .unwrap_or_default()
+    })
+}
+
+/// Single line that matches the pattern
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GrepMatch {
+    pub path: PathBuf,
+    /// Line number (starting at 1)
+    pub line: usize,
+    /// Content of the line (without leading whitespace)
+    pub text: String,
+}
+
+impl GrepMatch {
+    fn new(path: PathBuf, line: usize, text: &str) -> Self {
+        // Tabs and control characters would break the layout of the console
+        let text = text
+            .trim()
+            .chars()
+            .map(|c| if c.is_control() { ' ' } else { c })
+            .collect();
+        GrepMatch { path, line, text }
+    }
+}
+
+/// Matches of a running (or finished) search
+#[derive(Debug, Default)]
+pub struct GrepResults {
+    pub matches: Vec<GrepMatch>,
+    pub finished: bool,
+    pub error: Option<String>,
+}
+
+/// Search that runs in a background thread. It is cancelled when dropped.
+pub struct GrepSearch {
+    results: Arc<Mutex<GrepResults>>,
+    cancel: CancelToken,
+}
+
+impl GrepSearch {
+    /// Starts searching all files below `dir`.
+    ///
+    /// `notify` receives a message whenever new matches arrive and when the search is finished.
+    pub fn start(
+        dir: PathBuf,
+        pattern: String,
+        show_hidden: bool,
+        notify: mpsc::UnboundedSender<()>,
+    ) -> Self {
+        let results = Arc::new(Mutex::new(GrepResults::default()));
+        let job = jobs::start(
+            JobKind::Search,
+            format!("grep '{pattern}' in {}", dir.display()),
+        );
+        let cancel = job.token().clone();
+        let mut sink = Sink {
+            results: results.clone(),
+            cancel: cancel.clone(),
+            notify,
+            last_notify: Instant::now(),
+        };
+        std::thread::spawn(move || {
+            let _job = job;
+            info!("grep '{pattern}' in {}", dir.display());
+            let outcome = if ripgrep_installed() {
+                ripgrep(&dir, &pattern, show_hidden, &mut sink)
+            } else {
+                builtin_search(&dir, &pattern, show_hidden, &mut sink);
+                Ok(())
+            };
+            let mut results = sink.results.lock();
+            results.finished = true;
+            results.error = outcome.err();
+            debug!("grep finished with {} matches", results.matches.len());
+            drop(results);
+            let _ = sink.notify.send(());
+        });
+        GrepSearch { results, cancel }
+    }
+
+    pub fn results(&self) -> parking_lot::MutexGuard<'_, GrepResults> {
+        self.results.lock()
+    }
+}
+
+impl Drop for GrepSearch {
+    fn drop(&mut self) {
+        self.cancel.cancel();
+    }
+}
+
+/// Collects the matches of the search thread
+struct Sink {
+    results: Arc<Mutex<GrepResults>>,
+    cancel: CancelToken,
+    notify: mpsc::UnboundedSender<()>,
+    last_notify: Instant,
+}
+
+impl Sink {
+    /// Adds a match. Returns false, if the search should stop.
+    fn push(&mut self, m: GrepMatch) -> bool {
+        let len = {
+            let mut results = self.results.lock();
+            results.matches.push(m);
+            results.matches.len()
+        };
+        if self.last_notify.elapsed() >= NOTIFY_INTERVAL {
+            self.last_notify = Instant::now();
+            let _ = self.notify.send(());
+        }
+        len < MAX_MATCHES && !self.cancelled()
+    }
+
+    fn cancelled(&self) -> bool {
+        self.cancel.is_cancelled()
+    }
+}
+
+/// Parses a line of `rg --null --line-number` output: `path\0line:text`
+fn parse_rg_line(dir: &Path, line: &[u8]) -> Option<GrepMatch> {
+    let split = line.iter().position(|b| *b == 0)?;
+    let path = String::from_utf8_lossy(&line[..split]);
+    let rest = String::from_utf8_lossy(&line[split + 1..]);
+    let (number, text) = rest.split_once(':')?;
+    let path = path.strip_prefix("./").unwrap_or(&path);
+    Some(GrepMatch::new(dir.join(path), number.parse().ok()?, text))
+}
+
+fn ripgrep(dir: &Path, pattern: &str, show_hidden: bool, sink: &mut Sink) -> Result<(), String> {
+    let mut cmd = Command::new("rg");
+    cmd.args(["--null", "--line-number", "--no-heading", "--no-messages"])
+        .args(["--color", "never", "--smart-case", "--max-columns", "512"])
+        .arg("--max-columns-preview");
+    if show_hidden {
+        cmd.arg("--hidden");
+    }
+    let mut child = cmd
+        .arg("--")
+        .arg(pattern)
+        .arg(".")
+        .current_dir(dir)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("failed to run rg: {e}"))?;
+
+    let stdout = child.stdout.take().ok_or("no stdout")?;
+    for line in BufReader::new(stdout).split(b'\n') {
+        let Ok(line) = line else {
+            break;
+        };
+        if let Some(m) = parse_rg_line(dir, &line) {
+            if !sink.push(m) {
+                let _ = child.kill();
+                break;
+            }
+        }
+    }
+    let mut stderr = String::new();
+    if let Some(mut err) = child.stderr.take() {
+        let _ = err.read_to_string(&mut stderr);
+    }
+    let status = child.wait().map_err(|e| e.to_string())?;
+    // Exit code 1 means "no matches"
+    if status.code() == Some(2) && !stderr.trim().is_empty() {
+        return Err(stderr.trim().to_string());
+    }
+    Ok(())
+}
+
+/// Searches for a fixed string (used if ripgrep is not installed).
+///
+/// Hidden files are skipped (unless `show_hidden` is set), as well as binary and very large files.
+fn builtin_search(dir: &Path, pattern: &str, show_hidden: bool, sink: &mut Sink) {
+    let ignore_case = !pattern.chars().any(char::is_uppercase);
+    let needle = if ignore_case {
+        pattern.to_lowercase()
+    } else {
+        pattern.to_string()
+    };
+    let walker = WalkDir::new(dir).into_iter().filter_entry(|entry| {
+        show_hidden || entry.depth() == 0 || !entry.file_name().to_string_lossy().starts_with('.')
+    });
+    for entry in walker.flatten() {
+        if sink.cancelled() {
+            return;
+        }
+        let is_small_file = entry
+            .metadata()
+            .map(|m| m.is_file() && m.len() <= MAX_FILE_SIZE)
+            .unwrap_or_default();
+        if !is_small_file {
+            continue;
+        }
+        let Ok(content) = std::fs::read(entry.path()) else {
+            continue;
+        };
+        if content.iter().take(8192).any(|b| *b == 0) {
+            continue;
+        }
+        for (idx, line) in String::from_utf8_lossy(&content).lines().enumerate() {
+            let found = if ignore_case {
+                line.to_lowercase().contains(&needle)
+            } else {
+                line.contains(&needle)
+            };
+            if found && !sink.push(GrepMatch::new(entry.path().to_path_buf(), idx + 1, line)) {
+                return;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sink() -> (Sink, Arc<Mutex<GrepResults>>) {
+        let results = Arc::new(Mutex::new(GrepResults::default()));
+        let (notify, _) = mpsc::unbounded_channel();
+        let sink = Sink {
+            results: results.clone(),
+            cancel: CancelToken::default(),
+            notify,
+            last_notify: Instant::now(),
+        };
+        (sink, results)
+    }
+
+    #[test]
+    fn parse_ripgrep_output() {
+        let dir = Path::new("/home/user");
+        let m = parse_rg_line(dir, b"./src/a:b.rs\x0012:\tlet x = 1; // a:b").unwrap();
+        assert_eq!(m.path, Path::new("/home/user/src/a:b.rs"));
+        assert_eq!(m.line, 12);
+        assert_eq!(m.text, "let x = 1; // a:b");
+        assert!(parse_rg_line(dir, b"no separator").is_none());
+    }
+
+    #[test]
+    fn builtin_search_smart_case() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("a.txt"), "first\nFoo bar\nfoo\n").unwrap();
+        std::fs::write(dir.path().join(".hidden"), "foo\n").unwrap();
+        std::fs::write(dir.path().join("binary"), b"foo\x00").unwrap();
+
+        let (mut s, results) = sink();
+        builtin_search(dir.path(), "foo", false, &mut s);
+        let lines: Vec<usize> = results.lock().matches.iter().map(|m| m.line).collect();
+        assert_eq!(lines, vec![2, 3]);
+
+        let (mut s, results) = sink();
+        builtin_search(dir.path(), "Foo", true, &mut s);
+        let matches = &results.lock().matches;
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].text, "Foo bar");
+    }
+}
@@ -0,0 +1,114 @@
+use std::{
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+/// Maximum number of directories in the history
+const MAX_ENTRIES: usize = 100;
+
+/// Directories that were visited in this session, like the back/forward list of a browser.
+///
+/// Every directory is only listed once (at the time of its last visit).
+/// Going back and forward moves through the list without changing it,
+/// until a directory is visited that is not the current entry.
+#[derive(Debug, Default)]
+pub struct DirHistory {
+    /// Visited directories, the oldest one first
+    entries: Vec<(PathBuf, SystemTime)>,
+    /// Index of the current directory in `entries`
+    position: usize,
+}
+
+impl DirHistory {
+    /// Adds the directory as the newest entry.
+    ///
+    /// Nothing changes, if the directory is the current entry
+    /// (e.g. because we just moved there with [`DirHistory::back`]).
+    pub fn visit(&mut self, dir: &Path) {
+        if self.current() == Some(dir) {
+            return;
+        }
+        self.entries.retain(|(path, _)| path != dir);
+        self.entries.push((dir.to_path_buf(), SystemTime::now()));
+        if self.entries.len() > MAX_ENTRIES {
+            self.entries.remove(0);
+        }
+        self.position = self.entries.len() - 1;
+    }
+
+    fn current(&self) -> Option<&Path> {
+        self.entries
+            .get(self.position)
+            .map(|(path, _)| path.as_path())
+    }
+
+    /// Moves to the previous directory that still exists.
+    pub fn back(&mut self) -> Option<PathBuf> {
+        let idx = self.entries[..self.position.min(self.entries.len())]
+            .iter()
+            .rposition(|(path, _)| path.is_dir())?;
+        self.position = idx;
+        self.current().map(Path::to_path_buf)
+    }
+
+    /// Moves to the next directory that still exists.
+    pub fn forward(&mut self) -> Option<PathBuf> {
+        let start = self.position + 1;
+        let idx = self
+            .entries
+            .get(start..)?
+            .iter()
+            .position(|(path, _)| path.is_dir())?;
+        self.position = start + idx;
+        self.current().map(Path::to_path_buf)
+    }
+
+    /// Returns the directories that still exist with the time of their last visit,
+    /// the most recent one first.
+    pub fn entries(&self) -> Vec<(PathBuf, SystemTime)> {
+        self.entries
+            .iter()
+            .rev()
+            .filter(|(path, _)| path.is_dir())
+            .cloned()
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn back_and_forward() {
+        let dir = tempfile::tempdir().unwrap();
+        let [a, b, c] = ["a", "b", "c"].map(|name| {
+            let path = dir.path().join(name);
+            std::fs::create_dir(&path).unwrap();
+            path
+        });
+        let mut history = DirHistory::default();
+        assert_eq!(history.back(), None);
+        history.visit(&a);
+        history.visit(&b);
+        history.visit(&c);
+        assert_eq!(history.back(), Some(b.clone()));
+        // Arriving in the directory does not change the history
+        history.visit(&b);
+        assert_eq!(history.back(), Some(a.clone()));
+        assert_eq!(history.back(), None);
+        assert_eq!(history.forward(), Some(b.clone()));
+        assert_eq!(history.forward(), Some(c.clone()));
+        assert_eq!(history.forward(), None);
+
+        // Visiting a directory again moves it to the end
+        history.visit(&a);
+        let entries: Vec<PathBuf> = history.entries().into_iter().map(|(p, _)| p).collect();
+        assert_eq!(entries, vec![a.clone(), c.clone(), b.clone()]);
+
+        // Deleted directories are skipped
+        std::fs::remove_dir(&c).unwrap();
+        assert_eq!(history.back(), Some(b));
+        assert_eq!(history.entries().len(), 2);
+    }
+}
@@ -1,19 +1,90 @@
 use std::{
-    io::{stdout, Write},
+    collections::VecDeque,
+    io::{stdout, BufRead, BufReader, Write},
     path::{Path, PathBuf},
-    process::Command,
+    process::{Child, Command, ExitStatus},
+    sync::atomic::{AtomicBool, Ordering},
 };
 
 use crossterm::{
     cursor,
-    terminal::{self, Clear, ClearType},
+    event::{DisableBracketedPaste, DisableMouseCapture, EnableBracketedPaste, EnableMouseCapture},
+    terminal::{
+        self, Clear, ClearType, DisableLineWrap, EnableLineWrap, EnterAlternateScreen,
+        LeaveAlternateScreen,
+    },
     QueueableCommand, Result,
 };
-use log::{debug, info, warn};
+use log::{debug, error, info, warn};
 use mime::Mime;
+use parking_lot::Mutex;
 use serde::{Deserialize, Serialize};
 
-use crate::util::check_filename;
+use super::{doctor::ConfigProgram, recent::RecentFiles};
+use crate::server;
+
+/// Number of stderr lines, that are logged when a program fails
+const STDERR_LINES: usize = 5;
+
+/// Set when a program, that ran in the background, has exited (see [`child_exited`])
+static CHILD_EXITED: AtomicBool = AtomicBool::new(false);
+
+/// Returns true (once) if a program, that was started in the background, has exited since the last call.
+///
+/// Programs can write to the terminal directly, so the screen should be redrawn.
+pub fn child_exited() -> bool {
+    CHILD_EXITED.swap(false, Ordering::Relaxed)
+}
+
+/// Waits for the program to exit and collects the last lines of its stderr (which must be piped).
+fn wait_for_child(name: &str, child: &mut Child) -> std::io::Result<(ExitStatus, Vec<String>)> {
+    let mut lines = VecDeque::with_capacity(STDERR_LINES);
+    if let Some(stderr) = child.stderr.take() {
+        for line in BufReader::new(stderr)
+            .lines()
+            .map_while(std::io::Result::ok)
+        {
+            debug!("{name}: {line}");
+            if lines.len() == STDERR_LINES {
+                lines.pop_front();
+            }
+            lines.push_back(line);
+        }
+    }
+    let status = child.wait()?;
+    Ok((status, lines.into()))
+}
+
+/// Waits in the background for the program to exit.
+///
+/// If it fails, the end of its stderr and the exit code are logged.
+fn watch_child(name: String, mut child: Child) {
+    std::thread::spawn(move || {
+        match wait_for_child(&name, &mut child) {
+            Ok((status, _)) if status.success() => debug!("'{name}' has exited"),
+            Ok((status, mut lines)) => {
+                // The last line is the most relevant one, so it ends up in the footer
+                let last = lines.pop();
+                for line in lines {
+                    warn!("{name}: {line}");
+                }
+                match last {
+                    Some(last) => error!("'{name}' failed ({status}): {last}"),
+                    None => error!("'{name}' failed ({status})"),
+                }
+            }
+            Err(e) => warn!("Cannot wait for '{name}': {e}"),
+        }
+        CHILD_EXITED.store(true, Ordering::Relaxed);
+    });
+}
+
+/// Logs the exit code of a program, that ran in the terminal.
+fn check_status(name: &str, status: ExitStatus) {
+    if !status.success() {
+        error!("'{name}' failed ({status})");
+    }
+}
 
 /// Uses mime_guess to extract the mime-type.
 ///
@@ -31,29 +102,191 @@ pub fn get_mime_type<P: AsRef<Path>>(path: P) -> Mime {
     mime_guess::from_path(path).first_or_text_plain()
 }
 
-#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+/// Returns true if the file is an archive, that can be extracted with [`OpenEngine::extract`].
+pub fn is_archive<P: AsRef<Path>>(path: P) -> bool {
+    let extension = path
+        .as_ref()
+        .extension()
+        .and_then(|s| s.to_str())
+        .unwrap_or_default();
+    let mime = mime_guess::from_ext(extension).first_or_text_plain();
+    matches!(
+        (mime.type_().as_str(), mime.subtype().as_str()),
+        ("application", "gzip") | ("application", "zip")
+    )
+}
+
+/// Kind of files, that can be marked all at once (like all images of a directory)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MimeCategory {
+    Images,
+    Videos,
+    Audio,
+    Archives,
+    Documents,
+    Text,
+}
+
+impl MimeCategory {
+    pub const ALL: [MimeCategory; 6] = [
+        MimeCategory::Images,
+        MimeCategory::Videos,
+        MimeCategory::Audio,
+        MimeCategory::Archives,
+        MimeCategory::Documents,
+        MimeCategory::Text,
+    ];
+
+    /// Parses the name of a category, like `images` or `image`.
+    pub fn parse(name: &str) -> Option<Self> {
+        let name = name.trim().to_lowercase();
+        Self::ALL.into_iter().find(|category| {
+            let plural = category.to_string();
+            name == plural || Some(name.as_str()) == plural.strip_suffix('s')
+        })
+    }
+
+    /// Returns true if the mime-type of the file (see [`get_mime_type`]) belongs to the category.
+    pub fn matches<P: AsRef<Path>>(&self, path: P) -> bool {
+        let mime = get_mime_type(path);
+        let subtype = mime.subtype().as_str();
+        match (self, mime.type_().as_str()) {
+            (MimeCategory::Images, "image") => true,
+            (MimeCategory::Videos, "video") => true,
+            (MimeCategory::Audio, "audio") => true,
+            (MimeCategory::Text, "text") => true,
+            (MimeCategory::Archives, "application") => matches!(
+                subtype,
+                "gzip"
+                    | "zip"
+                    | "zstd"
+                    | "x-tar"
+                    | "x-bzip2"
+                    | "x-xz"
+                    | "x-7z-compressed"
+                    | "vnd.rar"
+                    | "x-rar-compressed"
+            ),
+            (MimeCategory::Documents, "application") => {
+                matches!(subtype, "pdf" | "msword" | "rtf" | "epub+zip")
+                    || subtype.starts_with("vnd.oasis.opendocument")
+                    || subtype.starts_with("vnd.openxmlformats-officedocument")
+                    || subtype.starts_with("vnd.ms-")
+            }
+            _ => false,
+        }
+    }
+}
+
+impl std::fmt::Display for MimeCategory {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MimeCategory::Images => write!(f, "images"),
+            MimeCategory::Videos => write!(f, "videos"),
+            MimeCategory::Audio => write!(f, "audio"),
+            MimeCategory::Archives => write!(f, "archives"),
+            MimeCategory::Documents => write!(f, "documents"),
+            MimeCategory::Text => write!(f, "text"),
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Application {
     name: String,
     terminal: bool,
     args: Vec<String>,
+    /// Command that passes the file to a running instance of the application (like `nvim --remote`)
+    server: Option<Vec<String>>,
+    /// The application accepts several files at once (like `mpv a.mp4 b.mp4`)
+    #[serde(default)]
+    multi: bool,
 }
 
 impl Application {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
     pub fn open<P: AsRef<Path>>(&self, path: P) -> Result<()> {
-        info!("Opening '{}' with '{}'", path.as_ref().display(), self.name);
-        if self.terminal {
-            stdout().queue(terminal::EnableLineWrap)?.flush()?;
+        self.open_all(&[path.as_ref().to_path_buf()])
+    }
+
+    /// Passes all files to one invocation of the application.
+    fn open_all(&self, paths: &[PathBuf]) -> Result<()> {
+        if self.open_in_server(paths) {
+            return Ok(());
         }
-        let mut handle = Command::new(&self.name)
-            .args(&self.args)
-            .arg(path.as_ref())
-            .spawn()?;
+        info!("Opening {} with '{}'", describe(paths), self.name);
+        let mut command = Command::new(&self.name);
+        server::announce(&mut command).args(&self.args).args(paths);
         if self.terminal {
-            handle.wait()?;
+            stdout().queue(terminal::EnableLineWrap)?.flush()?;
+            let status = command.status()?;
             stdout().queue(terminal::DisableLineWrap)?.flush()?;
+            check_status(&self.name, status);
+        } else {
+            // Graphical applications must not write over rfm (or read its keys)
+            let child = command
+                .stdin(std::process::Stdio::null())
+                .stdout(std::process::Stdio::null())
+                .stderr(std::process::Stdio::piped())
+                .spawn()?;
+            watch_child(self.name.clone(), child);
         }
         Ok(())
     }
+
+    /// Sends the files to a running instance of the application with the `server` command.
+    ///
+    /// Arguments like `$NVIM` are replaced by the environment variable. Returns false,
+    /// if there is no server command, a variable is not set (e.g. outside of nvim) or the command fails,
+    /// so that a new instance of the application is started instead.
+    fn open_in_server(&self, paths: &[PathBuf]) -> bool {
+        let Some((program, args)) = self.server.as_ref().and_then(|cmd| cmd.split_first()) else {
+            return false;
+        };
+        let args: Option<Vec<String>> = args
+            .iter()
+            .map(|arg| match arg.strip_prefix('$') {
+                Some(var) => std::env::var(var).ok().filter(|value| !value.is_empty()),
+                None => Some(arg.clone()),
+            })
+            .collect();
+        let Some(args) = args else {
+            debug!("No running instance of '{program}' (variable not set)");
+            return false;
+        };
+        let status = server::announce(&mut Command::new(program))
+            .args(args)
+            .args(paths)
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null())
+            .stdin(std::process::Stdio::null())
+            .status();
+        match status {
+            Ok(status) if status.success() => {
+                info!("Opened {} in a running '{program}'", describe(paths));
+                true
+            }
+            Ok(status) => {
+                debug!("'{program}' exited with {status}");
+                false
+            }
+            Err(e) => {
+                warn!("Cannot run '{program}': {e}");
+                false
+            }
+        }
+    }
+}
+
+/// Describes the files for the log, e.g. "'notes.txt'" or "3 files"
+fn describe(paths: &[PathBuf]) -> String {
+    match paths {
+        [path] => format!("'{}'", path.display()),
+        _ => format!("{} files", paths.len()),
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -75,6 +308,19 @@ impl OpenOptions {
         }
         self.default.open(absolute)
     }
+
+    /// All applications, that can open the given file (the preferred one first).
+    fn applications(&self, path: &Path) -> Vec<&Application> {
+        let path_extension = path.extension().and_then(|s| s.to_str());
+        let mut applications = Vec::new();
+        for (ext, application) in self.extensions.iter().flatten() {
+            if Some(ext.as_str()) == path_extension {
+                applications.push(application);
+            }
+        }
+        applications.push(&self.default);
+        applications
+    }
 }
 
 // #[derive(Debug, Default, Clone, Serialize, Deserialize)]
@@ -92,26 +338,257 @@ pub struct OpenerConfig {
 #[derive(Default)]
 pub struct OpenEngine {
     config: OpenerConfig,
+    /// Weather or not rfm captures the mouse (which must be released for child processes)
+    mouse_capture: bool,
+    /// Terminal emulator that is launched by [`OpenEngine::terminal`]
+    terminal: Option<String>,
+    /// Files that were opened last
+    recent: Mutex<RecentFiles>,
 }
 
 impl OpenEngine {
     pub fn with_config(config: OpenerConfig) -> Self {
-        OpenEngine { config }
+        OpenEngine {
+            config,
+            mouse_capture: false,
+            terminal: None,
+            recent: Default::default(),
+        }
     }
 
-    pub fn open(&self, path: PathBuf) -> Result<()> {
-        let absolute = if path.is_absolute() {
-            path
-        } else {
-            path.canonicalize().unwrap_or_default()
-        };
+    pub fn set_mouse_capture(&mut self, mouse_capture: bool) {
+        self.mouse_capture = mouse_capture;
+    }
+
+    /// Replaces the rules, e.g. when the config is reloaded.
+    pub fn set_config(&mut self, config: OpenerConfig) {
+        self.config = config;
+    }
+
+    /// Sets the terminal emulator (if not set, `$TERMINAL` is used).
+    pub fn set_terminal(&mut self, terminal: Option<String>) {
+        self.terminal = terminal;
+    }
+
+    /// Programs of the `open.toml` and the terminal emulator of the `config.toml`.
+    pub fn programs(&self) -> Vec<ConfigProgram> {
+        let categories = [
+            ("application", &self.config.application),
+            ("audio", &self.config.audio),
+            ("video", &self.config.video),
+            ("image", &self.config.image),
+            ("text", &self.config.text),
+        ];
+        let mut programs = Vec::new();
+        for (category, options) in categories {
+            let Some(options) = options else {
+                continue;
+            };
+            let entry = format!("open.toml: {category}");
+            programs.push(ConfigProgram::new(&entry, &options.default.name));
+            for (extension, application) in options.extensions.iter().flatten() {
+                programs.push(ConfigProgram::new(
+                    format!("{entry} (.{extension})"),
+                    &application.name,
+                ));
+            }
+        }
+        if let Some(terminal) = &self.terminal {
+            programs.push(ConfigProgram::new("config.toml: terminal", terminal));
+        }
+        programs
+    }
+
+    /// Sets the list, in which the opened files are remembered.
+    pub fn set_recent_files(&mut self, recent: RecentFiles) {
+        self.recent = Mutex::new(recent);
+    }
+
+    /// Returns the files that were opened last, the most recent one first.
+    pub fn recent_files(&self) -> Vec<PathBuf> {
+        self.recent.lock().files()
+    }
+
+    fn remember(&self, path: &Path) {
+        if path.is_file() {
+            self.recent.lock().push(path);
+        }
+    }
+
+    /// Gives the terminal to a child process, that is started by 'run'.
+    fn release_terminal<F: FnOnce() -> Result<()>>(&self, run: F) -> Result<()> {
         terminal::disable_raw_mode()?;
         let mut stdout = stdout();
+        if self.mouse_capture {
+            stdout.queue(DisableMouseCapture)?;
+        }
         stdout
+            .queue(DisableBracketedPaste)?
             .queue(Clear(ClearType::All))?
             .queue(cursor::MoveTo(0, 0))?;
         stdout.flush()?;
+        let result = run();
+        stdout.queue(EnableBracketedPaste)?;
+        if self.mouse_capture {
+            stdout.queue(EnableMouseCapture)?;
+        }
+        stdout.flush()?;
+        terminal::enable_raw_mode()?;
+        result
+    }
+
+    /// Returns the configured applications for the mime-type of the given file.
+    pub fn applications(&self, path: &Path) -> Vec<Application> {
+        let options = match get_mime_type(path).type_().as_str() {
+            "text" => &self.config.text,
+            "image" => &self.config.image,
+            "audio" => &self.config.audio,
+            "video" => &self.config.video,
+            "application" => &self.config.application,
+            _ => &None,
+        };
+        let mut applications: Vec<Application> = Vec::new();
+        for app in options.iter().flat_map(|o| o.applications(path)) {
+            if !applications
+                .iter()
+                .any(|a| a.name == app.name && a.args == app.args)
+            {
+                applications.push(app.clone());
+            }
+        }
+        applications
+    }
+
+    /// Opens the file with the given application.
+    pub fn open_with(&self, application: &Application, path: &Path) -> Result<()> {
+        self.remember(path);
+        self.release_terminal(|| application.open(path))
+    }
 
+    /// Opens the file with the default application of the system (e.g. `xdg-open`).
+    pub fn open_default(&self, path: &Path) -> Result<()> {
+        info!("Opening '{}' with the default opener", path.display());
+        self.remember(path);
+        opener::open(path).map_err(std::io::Error::other)
+    }
+
+    /// Runs a shell command with the given paths as arguments (`$1`, `$2`, ... or `"$@"`).
+    ///
+    /// If `terminal` is false, the command runs in the background.
+    pub fn run_shell(&self, command: &str, paths: &[PathBuf], terminal: bool) -> Result<()> {
+        info!("Running '{command}' on {} items", paths.len());
+        let mut process = Command::new("sh");
+        server::announce(&mut process)
+            .arg("-c")
+            .arg(command)
+            .arg("rfm")
+            .args(paths);
+        if terminal {
+            return self.release_terminal(|| {
+                check_status(command, process.status()?);
+                Ok(())
+            });
+        }
+        let child = process
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::piped())
+            .stdin(std::process::Stdio::null())
+            .spawn()?;
+        watch_child(command.to_string(), child);
+        Ok(())
+    }
+
+    /// Suspends rfm and starts an interactive shell (`$SHELL`) in the working directory.
+    ///
+    /// rfm continues, when the shell exits.
+    pub fn shell(&self) -> Result<()> {
+        let shell = std::env::var("SHELL").unwrap_or_else(|_| "sh".to_string());
+        info!("Starting shell '{shell}'");
+        self.release_terminal(|| {
+            // The shell should see the content of the terminal before rfm was started
+            let mut stdout = stdout();
+            stdout
+                .queue(LeaveAlternateScreen)?
+                .queue(EnableLineWrap)?
+                .queue(cursor::Show)?
+                .flush()?;
+            let result = server::announce(&mut Command::new(&shell))
+                .spawn()
+                .and_then(|mut c| c.wait());
+            stdout
+                .queue(EnterAlternateScreen)?
+                .queue(DisableLineWrap)?
+                .queue(cursor::Hide)?
+                .flush()?;
+            result?;
+            Ok(())
+        })
+    }
+
+    /// Opens a new window of the terminal emulator in the working directory (without blocking).
+    pub fn terminal(&self) -> Result<()> {
+        let terminal = self
+            .terminal
+            .clone()
+            .or_else(|| std::env::var("TERMINAL").ok())
+            .unwrap_or_else(|| "x-terminal-emulator".to_string());
+        info!("Starting terminal '{terminal}'");
+        server::announce(&mut Command::new(&terminal))
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null())
+            .stdin(std::process::Stdio::null())
+            .spawn()
+            .map_err(|e| std::io::Error::new(e.kind(), format!("cannot start {terminal}: {e}")))?;
+        Ok(())
+    }
+
+    pub fn open(&self, path: PathBuf) -> Result<()> {
+        let absolute = if path.is_absolute() {
+            path
+        } else {
+            path.canonicalize().unwrap_or_default()
+        };
+        self.remember(&absolute);
+        self.release_terminal(|| self.open_by_mime_type(absolute))
+    }
+
+    /// Opens several files at once.
+    ///
+    /// The files of an application with `multi = true` are passed to a single invocation of it,
+    /// all other files are opened one after another.
+    pub fn open_all(&self, paths: &[PathBuf]) -> Result<()> {
+        let mut groups: Vec<(Option<Application>, Vec<PathBuf>)> = Vec::new();
+        for path in paths {
+            self.remember(path);
+            let application = self.applications(path).into_iter().next();
+            match groups.iter_mut().find(|(app, _)| *app == application) {
+                Some((_, group)) => group.push(path.clone()),
+                None => groups.push((application, vec![path.clone()])),
+            }
+        }
+        self.release_terminal(|| {
+            for (application, paths) in groups {
+                match application {
+                    Some(app) if app.multi => app.open_all(&paths)?,
+                    Some(app) => {
+                        for path in paths {
+                            app.open(path)?;
+                        }
+                    }
+                    None => {
+                        for path in paths {
+                            if let Err(e) = opener::open(&path) {
+                                warn!("Error while opening {}: {e}", path.display());
+                            }
+                        }
+                    }
+                }
+            }
+            Ok(())
+        })
+    }
+
+    fn open_by_mime_type(&self, absolute: PathBuf) -> Result<()> {
         // Check mime-type
         let mime_type = get_mime_type(&absolute);
         match mime_type.type_().as_str() {
@@ -181,80 +658,153 @@ impl OpenEngine {
                 }
             }
         }
-        terminal::enable_raw_mode()?;
         Ok(())
     }
+}
+
+/// Extracts the archive into the `target` directory, which is created if it does not exist.
+///
+/// Fails if the file is not an archive or the archive program exits with an error.
+pub fn extract(archive: &Path, target: &Path) -> Result<()> {
+    info!(
+        "Extracting archive '{}' to '{}'",
+        archive.display(),
+        target.display()
+    );
+    let extension = archive
+        .extension()
+        .and_then(|s| s.to_str())
+        .unwrap_or_default();
 
-    pub fn zip(&self, items: Vec<PathBuf>) -> Result<()> {
-        info!("Creating zip archive from {} files", items.len());
-        let mut process = std::process::Command::new("zip");
-        let archive_path = check_filename("output", ".", "zip")?;
-        process.arg(archive_path.as_os_str());
-        process.arg("--");
-        for path in items.iter().flat_map(|p| p.file_name()) {
-            process.arg(path);
+    let mime = mime_guess::from_ext(extension).first_or_text_plain();
+
+    let mut process = match (mime.type_().as_str(), mime.subtype().as_str()) {
+        ("application", "gzip") => {
+            let mut process = Command::new("tar");
+            process.arg("-xzf").arg(archive).arg("-C").arg(target);
+            process
         }
-        process
-            .stdout(std::process::Stdio::null())
-            .stderr(std::process::Stdio::null())
-            .stdin(std::process::Stdio::null());
-        let mut handle = process.spawn()?;
-        handle.wait()?;
-        Ok(())
+        ("application", "zip") => {
+            let mut process = Command::new("unzip");
+            process.arg("-q").arg(archive).arg("-d").arg(target);
+            process
+        }
+        _ => {
+            return Err(std::io::Error::other(format!(
+                "{} is not an archive",
+                archive.display()
+            )));
+        }
+    };
+    std::fs::create_dir_all(target)?;
+    let status = process
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .stdin(std::process::Stdio::null())
+        .status()?;
+    if !status.success() {
+        return Err(std::io::Error::other(format!(
+            "{} exited with {status}",
+            process.get_program().to_string_lossy()
+        )));
     }
+    Ok(())
+}
 
-    pub fn tar(&self, items: Vec<PathBuf>) -> Result<()> {
-        info!("Creating tar.gz archive from {} files", items.len());
-        let mut process = std::process::Command::new("tar");
-        process.arg("-czf");
-        let archive_path = check_filename("output", ".", "tar.gz")?;
-        process.arg(archive_path.as_os_str());
-        process.arg("--");
-        for path in items.iter().flat_map(|p| p.file_name()) {
-            process.arg(path);
-        }
-        process
-            .stdout(std::process::Stdio::null())
-            .stderr(std::process::Stdio::null())
-            .stdin(std::process::Stdio::null());
-        let mut handle = process.spawn()?;
-        handle.wait()?;
-        Ok(())
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mime_categories() {
+        assert_eq!(MimeCategory::parse("Images"), Some(MimeCategory::Images));
+        assert_eq!(MimeCategory::parse(" video "), Some(MimeCategory::Videos));
+        assert_eq!(MimeCategory::parse("audio"), Some(MimeCategory::Audio));
+        assert_eq!(MimeCategory::parse("pictures"), None);
+
+        let category = |name: &str| {
+            MimeCategory::ALL
+                .into_iter()
+                .filter(|category| category.matches(name))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(category("cat.PNG"), [MimeCategory::Images]);
+        assert_eq!(category("movie.mkv"), [MimeCategory::Videos]);
+        assert_eq!(category("song.mp3"), [MimeCategory::Audio]);
+        assert_eq!(category("backup.tar.gz"), [MimeCategory::Archives]);
+        assert_eq!(category("photos.zip"), [MimeCategory::Archives]);
+        assert_eq!(category("paper.pdf"), [MimeCategory::Documents]);
+        assert_eq!(category("letter.docx"), [MimeCategory::Documents]);
+        assert_eq!(category("notes.md"), [MimeCategory::Text]);
+        assert!(category("program.exe").is_empty());
     }
 
-    pub fn extract(&self, archive: PathBuf) -> Result<()> {
-        info!("Extracting archive '{}'", archive.display());
-        let extension = archive
-            .extension()
-            .and_then(|s| s.to_str())
-            .unwrap_or_default();
-
-        let mime = mime_guess::from_ext(extension).first_or_text_plain();
-
-        match (mime.type_().as_str(), mime.subtype().as_str()) {
-            ("application", "gzip") => {
-                std::process::Command::new("tar")
-                    .arg("-xzf")
-                    .arg(archive.as_os_str())
-                    .stdout(std::process::Stdio::null())
-                    .stderr(std::process::Stdio::null())
-                    .stdin(std::process::Stdio::null())
-                    .spawn()?
-                    .wait()?;
-            }
-            ("application", "zip") => {
-                std::process::Command::new("unzip")
-                    .arg(archive.as_os_str())
-                    .stdout(std::process::Stdio::null())
-                    .stderr(std::process::Stdio::null())
-                    .stdin(std::process::Stdio::null())
-                    .spawn()?
-                    .wait()?;
-            }
-            _ => {
-                log::warn!("{} is not an archive", archive.display());
+    #[test]
+    fn capture_stderr_of_failed_programs() {
+        let mut child = Command::new("sh")
+            .args([
+                "-c",
+                "for i in 1 2 3 4 5 6 7; do echo line $i >&2; done; exit 3",
+            ])
+            .stderr(std::process::Stdio::piped())
+            .spawn()
+            .unwrap();
+        let (status, lines) = wait_for_child("sh", &mut child).unwrap();
+        assert_eq!(status.code(), Some(3));
+        assert_eq!(lines.len(), STDERR_LINES);
+        assert_eq!(lines.first().map(String::as_str), Some("line 3"));
+        assert_eq!(lines.last().map(String::as_str), Some("line 7"));
+    }
+
+    #[test]
+    fn open_in_running_instance() {
+        let app = |server: &[&str]| Application {
+            name: "vim".into(),
+            terminal: true,
+            args: Vec::new(),
+            server: Some(server.iter().map(|arg| arg.to_string()).collect()),
+            multi: false,
+        };
+        let file = &[PathBuf::from("notes.txt")];
+        assert!(app(&["true", "--remote"]).open_in_server(file));
+        // Without a running instance, the application is started normally
+        assert!(!app(&["false"]).open_in_server(file));
+        assert!(!app(&["true", "$RFM_TEST_UNSET_SERVER"]).open_in_server(file));
+        assert!(!app(&[]).open_in_server(file));
+        assert!(!Application::default().open_in_server(file));
+    }
+
+    #[test]
+    fn open_several_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let out = dir.path().join("out");
+        let app = Application {
+            name: "sh".into(),
+            terminal: false,
+            args: vec![
+                "-c".into(),
+                format!(
+                    "printf '%s\\n' \"$@\" > {}.tmp && mv {0}.tmp {0}",
+                    out.display()
+                ),
+                "sh".into(),
+            ],
+            server: None,
+            multi: true,
+        };
+        let files = [dir.path().join("a.mp4"), dir.path().join("b.mp4")];
+        app.open_all(&files).unwrap();
+        // The application runs in the background
+        for _ in 0..100 {
+            if out.exists() {
+                break;
             }
+            std::thread::sleep(std::time::Duration::from_millis(20));
         }
-        Ok(())
+        let args = std::fs::read_to_string(&out).unwrap();
+        assert_eq!(
+            args.lines().collect::<Vec<_>>(),
+            files.map(|f| f.display().to_string())
+        );
     }
 }
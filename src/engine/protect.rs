@@ -0,0 +1,87 @@
+//! Guard rails for destructive operations.
+//!
+//! Deleting or cutting a protected path - or an item directly inside of one -
+//! has to be confirmed by typing [`CONFIRMATION`]. This prevents accidents like deleting
+//! `/usr` after a jump mark landed somewhere unexpected.
+use std::path::{Path, PathBuf};
+
+use once_cell::sync::OnceCell;
+
+use super::commands::ExpandedPath;
+use crate::drives;
+
+/// Text that has to be typed to confirm an operation on a protected path
+pub const CONFIRMATION: &str = "YES";
+
+/// Protected paths, if nothing is configured
+pub const DEFAULT_PROTECTED: [&str; 3] = ["/", "/home", "~/.config"];
+
+struct Protected {
+    paths: Vec<PathBuf>,
+    /// Protect the mount points of all mounted filesystems
+    mounts: bool,
+}
+
+static PROTECTED: OnceCell<Protected> = OnceCell::new();
+
+/// Expands "~" and resolves symlinks, since the items of the panels are canonicalized as well
+fn expand(path: &str) -> PathBuf {
+    let path = PathBuf::from(ExpandedPath::from(path));
+    path.canonicalize().unwrap_or(path)
+}
+
+/// Sets the protected paths. Must be called before the first check.
+pub fn set_protected_paths(paths: &[String], mounts: bool) {
+    let paths = paths.iter().map(|path| expand(path)).collect();
+    if PROTECTED.set(Protected { paths, mounts }).is_err() {
+        log::warn!("protected paths are already set");
+    }
+}
+
+fn protected() -> &'static Protected {
+    PROTECTED.get_or_init(|| Protected {
+        paths: DEFAULT_PROTECTED.iter().map(|path| expand(path)).collect(),
+        mounts: true,
+    })
+}
+
+/// Returns true if `path` is one of the `protected` paths or directly inside of one.
+fn is_protected(path: &Path, protected: &[PathBuf], mountpoints: &[PathBuf]) -> bool {
+    protected
+        .iter()
+        .any(|p| path == p || path.parent() == Some(p.as_path()))
+        || mountpoints.iter().any(|p| path == p)
+}
+
+/// Returns the first of the items that is protected (if any).
+pub fn find_protected(items: &[PathBuf]) -> Option<&PathBuf> {
+    let protected = protected();
+    let mountpoints = if protected.mounts {
+        drives::mountpoints()
+    } else {
+        Vec::new()
+    };
+    items
+        .iter()
+        .find(|item| is_protected(item, &protected.paths, &mountpoints))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn protected_paths() {
+        let protected = [PathBuf::from("/"), PathBuf::from("/home/user/.config")];
+        let mounts = [PathBuf::from("/mnt/usb")];
+        let check = |path: &str| is_protected(Path::new(path), &protected, &mounts);
+        assert!(check("/"));
+        assert!(check("/usr"));
+        assert!(check("/home/user/.config"));
+        assert!(check("/home/user/.config/nvim"));
+        assert!(check("/mnt/usb"));
+        assert!(!check("/usr/bin"));
+        assert!(!check("/home/user/.config/nvim/init.lua"));
+        assert!(!check("/mnt/usb/photos"));
+    }
+}
@@ -0,0 +1,101 @@
+use std::{
+    collections::VecDeque,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use log::{debug, warn};
+
+use crate::util::write_atomic;
+
+/// Maximum number of files in the list
+const MAX_ENTRIES: usize = 200;
+
+/// List of the files that were opened last (like the `oldfiles` of vim).
+///
+/// The list is a plain text file with one path per line, the most recent file first.
+/// Without a file, the opened files are only tracked in memory.
+#[derive(Debug, Default)]
+pub struct RecentFiles {
+    file: Option<PathBuf>,
+    entries: VecDeque<PathBuf>,
+}
+
+impl RecentFiles {
+    /// Loads the list from the given file. A missing file results in an empty list.
+    pub fn load(file: PathBuf) -> Self {
+        let entries = match std::fs::read_to_string(&file) {
+            Ok(content) => content
+                .lines()
+                .filter(|line| !line.is_empty())
+                .map(PathBuf::from)
+                .take(MAX_ENTRIES)
+                .collect(),
+            Err(e) => {
+                debug!("Cannot read {}: {e}", file.display());
+                VecDeque::new()
+            }
+        };
+        RecentFiles {
+            file: Some(file),
+            entries,
+        }
+    }
+
+    /// Moves the file to the top of the list.
+    pub fn push(&mut self, path: &Path) {
+        self.entries.retain(|p| p != path);
+        self.entries.push_front(path.to_path_buf());
+        self.entries.truncate(MAX_ENTRIES);
+        if let Err(e) = self.save() {
+            warn!("Failed to save recent files: {e}");
+        }
+    }
+
+    /// Writes the list to its file (if any).
+    fn save(&self) -> std::io::Result<()> {
+        let Some(file) = &self.file else {
+            return Ok(());
+        };
+        write_atomic(file, |out| {
+            for path in self.entries.iter() {
+                writeln!(out, "{}", path.display())?;
+            }
+            Ok(())
+        })
+    }
+
+    /// Returns the files that still exist, the most recent one first.
+    pub fn files(&self) -> Vec<PathBuf> {
+        self.entries
+            .iter()
+            .filter(|path| path.is_file())
+            .cloned()
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn remember_opened_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let a = dir.path().join("a.txt");
+        let b = dir.path().join("b.txt");
+        std::fs::write(&a, "a").unwrap();
+        std::fs::write(&b, "b").unwrap();
+
+        let list = dir.path().join("recent");
+        let mut recent = RecentFiles::load(list.clone());
+        recent.push(&a);
+        recent.push(&b);
+        recent.push(&a);
+        recent.push(&dir.path().join("deleted.txt"));
+        assert_eq!(RecentFiles::load(list.clone()).entries.len(), 3);
+
+        // Files that no longer exist are skipped
+        assert_eq!(RecentFiles::load(list).files(), vec![a, b]);
+    }
+}
@@ -0,0 +1,209 @@
+use std::fmt::Display;
+
+use anyhow::{anyhow, Result};
+
+use super::{
+    archive::ArchiveSpec,
+    checksum::HashAlgorithm,
+    commands::{Command, ImageCmd, Move},
+};
+
+/// A single step of an rfm script.
+///
+/// Scripts are plain text files with one command per line.
+/// The command names are identical to the ones in the `keys.toml`,
+/// e.g. `down`, `mark`, `copy`, `paste` or `jump_to ~/Downloads`.
+/// Commands that open an input field (`search`, `search_backward`, `mark_glob`, `mark_by_age`, `mark_by_type`, `rename`, `mkdir` and `touch`)
+/// take the text that should be entered as an argument.
+/// `archive` takes the archive instead of opening the dialog (see [`ArchiveSpec`]).
+#[derive(Debug, Clone)]
+pub struct ScriptStep {
+    /// Command that is executed
+    pub command: Command,
+    /// Text that is entered into the input field opened by the command
+    pub input: Option<String>,
+}
+
+impl Display for ScriptStep {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.input {
+            Some(input) => write!(f, "{} '{input}'", self.command),
+            None => write!(f, "{}", self.command),
+        }
+    }
+}
+
+/// Parses the content of a script file.
+///
+/// Empty lines and lines starting with `#` are ignored.
+pub fn parse_script(content: &str) -> Result<Vec<ScriptStep>> {
+    let mut steps = Vec::new();
+    for (idx, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (name, argument) = match line.split_once(char::is_whitespace) {
+            Some((name, argument)) => (name, Some(argument.trim().to_string())),
+            None => (line, None),
+        };
+        let step = parse_step(name, argument).map_err(|e| anyhow!("line {}: {e}", idx + 1))?;
+        steps.push(step);
+    }
+    Ok(steps)
+}
+
+fn parse_step(name: &str, argument: Option<String>) -> Result<ScriptStep> {
+    let command = match name {
+        // General
+        "mark" => Command::Mark,
+        "visual_mode" => Command::VisualMode,
+        "mark_all" => Command::MarkAll,
+        "invert_marks" => Command::InvertMarks,
+        "unmark_all" => Command::UnmarkAll,
+        "next" => Command::Next,
+        "previous" => Command::Previous,
+        "toggle_hidden" => Command::ToggleHidden,
+        "toggle_details" => Command::ToggleDetails,
+        "toggle_flat" => Command::ToggleFlat,
+        "group_by_extension" => Command::GroupByExtension,
+        "toggle_group" => Command::ToggleGroup,
+        "expand_groups" => Command::ExpandGroups,
+        "toggle_preview" => Command::TogglePreview,
+        "toggle_zen" => Command::ToggleZen,
+        "cycle_layout" => Command::CycleLayout,
+        "toggle_caching" => Command::ToggleCaching,
+        "clear_cache" => Command::ClearCache,
+        "cycle_sort" => Command::CycleSort,
+        "cycle_theme" => Command::CycleTheme,
+        "reload_config" => Command::ReloadConfig,
+        "image_zoom_in" => Command::Image(ImageCmd::ZoomIn),
+        "image_zoom_out" => Command::Image(ImageCmd::ZoomOut),
+        "image_fit" => Command::Image(ImageCmd::CycleFit),
+        "image_pan_left" => Command::Image(ImageCmd::PanLeft),
+        "image_pan_right" => Command::Image(ImageCmd::PanRight),
+        "image_pan_up" => Command::Image(ImageCmd::PanUp),
+        "image_pan_down" => Command::Image(ImageCmd::PanDown),
+        "view_trash" => Command::ViewTrash,
+        "restore_trash" => Command::RestoreTrash,
+        "empty_trash" => Command::EmptyTrash,
+        "stage" => Command::Stage,
+        "unstage_all" => Command::UnstageAll,
+        "untag" => Command::Untag,
+        "next_tagged" => Command::NextTagged,
+        "previous_tagged" => Command::PreviousTagged,
+        "context_menu" => Command::ContextMenu,
+        "quit" => Command::Quit,
+        "quit_no_cd" => Command::QuitWithoutPath,
+        "select_for_picker" => Command::SelectForPicker,
+        // Movement
+        "up" => Command::Move(Move::Up),
+        "down" => Command::Move(Move::Down),
+        "left" => Command::Move(Move::Left),
+        "right" => Command::Move(Move::Right),
+        "top" => Command::Move(Move::Top),
+        "bottom" => Command::Move(Move::Bottom),
+        "page_forward" => Command::Move(Move::PageForward),
+        "page_backward" => Command::Move(Move::PageBackward),
+        "half_page_forward" => Command::Move(Move::HalfPageForward),
+        "half_page_backward" => Command::Move(Move::HalfPageBackward),
+        "jump_previous" => Command::Move(Move::JumpPrevious),
+        "history_back" => Command::Move(Move::HistoryBack),
+        "history_forward" => Command::Move(Move::HistoryForward),
+        "jump_to" => {
+            let path = argument.ok_or_else(|| anyhow!("'jump_to' requires a path"))?;
+            Command::Move(Move::JumpTo(path.into()))
+        }
+        // Manipulation
+        "cut" => Command::Cut,
+        "copy" => Command::Copy,
+        "delete" => Command::Delete,
+        "paste" => Command::Paste { overwrite: false },
+        "paste_overwrite" => Command::Paste { overwrite: true },
+        "paste_verified" => Command::PasteVerified,
+        "paste_preview" => Command::PastePreview,
+        "paste_symlink" => Command::PasteSymlink { relative: false },
+        "paste_relative_symlink" => Command::PasteSymlink { relative: true },
+        "paste_hardlink" => Command::PasteHardlink,
+        "copy_to_previous" => Command::SendToPrevious { cut: false },
+        "move_to_previous" => Command::SendToPrevious { cut: true },
+        "toggle_exec" => Command::ToggleExec,
+        "checksum_md5" => Command::Checksum(HashAlgorithm::Md5),
+        "checksum_sha256" => Command::Checksum(HashAlgorithm::Sha256),
+        "extract" => Command::Extract,
+        "archive" => {
+            let spec = argument.ok_or_else(|| anyhow!("'archive' requires <name>.<extension>"))?;
+            spec.parse::<ArchiveSpec>()?;
+            return Ok(ScriptStep {
+                command: Command::Archive,
+                input: Some(spec),
+            });
+        }
+        // The archive commands of older versions
+        "zip" | "tar" => {
+            let extension = if name == "zip" { "zip" } else { "tar.gz" };
+            return Ok(ScriptStep {
+                command: Command::Archive,
+                input: Some(format!("output.{extension}")),
+            });
+        }
+        // Commands with an input field
+        "search" | "search_backward" | "mark_glob" | "mark_by_age" | "mark_by_type" | "rename"
+        | "mkdir" | "touch" | "tag" => {
+            let command = match name {
+                "search" => Command::Search,
+                "search_backward" => Command::SearchBackward,
+                "mark_glob" => Command::MarkGlob,
+                "mark_by_age" => Command::MarkByAge,
+                "mark_by_type" => Command::MarkByType,
+                "rename" => Command::Rename,
+                "mkdir" => Command::Mkdir,
+                "tag" => Command::Tag,
+                _ => Command::Touch,
+            };
+            let input = argument.ok_or_else(|| anyhow!("'{name}' requires an argument"))?;
+            return Ok(ScriptStep {
+                command,
+                input: Some(input),
+            });
+        }
+        other => return Err(anyhow!("unknown command '{other}'")),
+    };
+    Ok(ScriptStep {
+        command,
+        input: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_simple_script() {
+        let script = "\
+# create a directory and copy a file into it
+mkdir  new dir
+down
+copy
+jump_to ~/somewhere
+
+paste
+quit
+";
+        let steps = parse_script(script).expect("valid script");
+        assert_eq!(steps.len(), 6);
+        assert!(matches!(steps[0].command, Command::Mkdir));
+        assert_eq!(steps[0].input.as_deref(), Some("new dir"));
+        assert!(matches!(steps[1].command, Command::Move(Move::Down)));
+        assert!(matches!(steps[3].command, Command::Move(Move::JumpTo(_))));
+        assert!(matches!(steps[5].command, Command::Quit));
+    }
+
+    #[test]
+    fn parse_invalid_script() {
+        let err = parse_script("down\nfly-away\n").unwrap_err();
+        assert!(err.to_string().starts_with("line 2"), "{err}");
+        assert!(parse_script("rename").is_err());
+    }
+}
@@ -0,0 +1,88 @@
+//! Shortcut files of desktops and browsers (`.desktop` links and `.url` files).
+//!
+//! Moving right on a shortcut follows it: directories are entered,
+//! everything else (like a web address) is given to the default opener.
+use std::path::{Path, PathBuf};
+
+use super::trash::decode_path;
+
+/// Where a shortcut leads to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Shortcut {
+    Directory(PathBuf),
+    Url(String),
+}
+
+/// Reads the shortcut, returns `None` if the file is no shortcut.
+pub fn read(path: &Path) -> Option<Shortcut> {
+    let section = match path.extension()?.to_str()?.to_lowercase().as_str() {
+        "desktop" => "[Desktop Entry]",
+        "url" => "[InternetShortcut]",
+        _ => return None,
+    };
+    let content = std::fs::read_to_string(path).ok()?;
+    parse(&content, section)
+}
+
+fn parse(content: &str, section: &str) -> Option<Shortcut> {
+    let mut in_section = false;
+    let mut is_link = section == "[InternetShortcut]";
+    let mut url = None;
+    for line in content.lines().map(str::trim) {
+        if line.starts_with('[') {
+            in_section = line == section;
+            continue;
+        }
+        if !in_section {
+            continue;
+        }
+        match line.split_once('=').map(|(k, v)| (k.trim(), v.trim())) {
+            Some(("Type", kind)) => is_link = kind == "Link",
+            Some(("URL", target)) => url = Some(target.to_string()),
+            _ => (),
+        }
+    }
+    let url = url.filter(|_| is_link)?;
+    let local = match url.strip_prefix("file://") {
+        Some(path) => Some(decode_path(path)),
+        None if url.starts_with('/') => Some(PathBuf::from(&url)),
+        None => None,
+    };
+    match local {
+        Some(dir) if dir.is_dir() => Some(Shortcut::Directory(dir)),
+        _ => Some(Shortcut::Url(url)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn follow_shortcuts() {
+        let dir = tempfile::tempdir().unwrap();
+        let target = dir.path().join("my projects");
+        std::fs::create_dir(&target).unwrap();
+
+        let desktop = dir.path().join("projects.desktop");
+        let url = format!("file://{}", dir.path().join("my%20projects").display());
+        std::fs::write(
+            &desktop,
+            format!("[Desktop Entry]\nName=Projects\nType=Link\nURL={url}\n"),
+        )
+        .unwrap();
+        assert_eq!(read(&desktop), Some(Shortcut::Directory(target)));
+
+        let web = "[InternetShortcut]\nURL=https://example.com/?a=b\n";
+        assert_eq!(
+            parse(web, "[InternetShortcut]"),
+            Some(Shortcut::Url("https://example.com/?a=b".to_string()))
+        );
+        // Applications are no shortcuts
+        let app = "[Desktop Entry]\nType=Application\nExec=firefox\nURL=x\n";
+        assert_eq!(parse(app, "[Desktop Entry]"), None);
+        let other = "[Desktop Action new]\nType=Link\nURL=https://example.com\n";
+        assert_eq!(parse(other, "[Desktop Entry]"), None);
+        assert_eq!(read(&dir.path().join("notes.txt")), None);
+    }
+}
@@ -0,0 +1,67 @@
+//! Staging area for items of several directories.
+//!
+//! Marks belong to the panels and are gone once you leave a directory. Staged items are kept
+//! in a global list instead, so you can collect items from different directories and then
+//! copy or cut all of them at once.
+use std::path::{Path, PathBuf};
+
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+
+static STAGED: Lazy<Mutex<Vec<PathBuf>>> = Lazy::new(Default::default);
+
+/// Stages the items, or unstages them if all of them are staged already.
+///
+/// Returns true if the items were staged.
+pub fn toggle(paths: Vec<PathBuf>) -> bool {
+    let mut staged = STAGED.lock();
+    if paths.iter().all(|path| staged.contains(path)) {
+        staged.retain(|path| !paths.contains(path));
+        false
+    } else {
+        for path in paths {
+            if !staged.contains(&path) {
+                staged.push(path);
+            }
+        }
+        true
+    }
+}
+
+/// Returns true if the item is staged.
+pub fn is_staged(path: &Path) -> bool {
+    STAGED.lock().iter().any(|p| p == path)
+}
+
+/// Number of staged items
+pub fn len() -> usize {
+    STAGED.lock().len()
+}
+
+/// Removes all items from the staging area and returns them (in the order they were staged).
+pub fn take() -> Vec<PathBuf> {
+    std::mem::take(&mut *STAGED.lock())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stage_and_unstage() {
+        let a = PathBuf::from("/tmp/a/one.txt");
+        let b = PathBuf::from("/tmp/b/two.txt");
+        assert!(toggle(vec![a.clone()]));
+        assert!(toggle(vec![a.clone(), b.clone()]));
+        assert!(is_staged(&a) && is_staged(&b));
+        assert_eq!(len(), 2);
+
+        // Everything is staged already, so the items are removed again
+        assert!(!toggle(vec![b.clone()]));
+        assert!(!is_staged(&b));
+
+        assert!(toggle(vec![b.clone()]));
+        assert_eq!(take(), vec![a, b]);
+        assert_eq!(len(), 0);
+    }
+}
@@ -1,57 +1,321 @@
-use std::path::Path;
+use std::{collections::HashMap, path::Path};
 
 use super::opener::get_mime_type;
-use log::error;
+use crate::util::ExactWidth;
+use log::{error, info};
 use once_cell::sync::OnceCell;
 use patricia_tree::StringPatriciaMap;
+use serde::Deserialize;
+use unicode_display_width::width as unicode_width;
 
 pub static SYMBOLS: OnceCell<SymbolEngine> = OnceCell::new();
 
+/// Width of all symbols in cells, shorter symbols are padded with spaces
+const SYMBOL_WIDTH: usize = 2;
+
+/// Glyphs that are used for the icons in front of the names
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum IconSet {
+    /// Picks one of the other sets, depending on the locale and the installed fonts
+    #[default]
+    Auto,
+    /// Plain ascii characters, that work everywhere
+    Ascii,
+    /// Symbols of the regular unicode fonts
+    Unicode,
+    /// Icons of a patched "Nerd Font" (see <https://www.nerdfonts.com>)
+    Nerd,
+}
+
+impl IconSet {
+    /// Guesses which icons the terminal can show.
+    ///
+    /// Nerd Font icons are only used, if a Nerd Font is installed,
+    /// and without a UTF-8 locale (or on the linux console) we stick to ascii.
+    fn detect() -> Self {
+        let locale = ["LC_ALL", "LC_CTYPE", "LANG"]
+            .into_iter()
+            .filter_map(|var| std::env::var(var).ok())
+            .find(|value| !value.is_empty());
+        let utf8 = match locale {
+            Some(locale) => {
+                let locale = locale.to_lowercase();
+                locale.contains("utf-8") || locale.contains("utf8")
+            }
+            // Nothing configured, assume a modern terminal
+            None => true,
+        };
+        if !utf8 || std::env::var("TERM").is_ok_and(|term| term == "linux") {
+            IconSet::Ascii
+        } else if nerd_font_installed() {
+            IconSet::Nerd
+        } else {
+            IconSet::Unicode
+        }
+    }
+}
+
+/// Returns true if there is a font with "Nerd" in its filename in one of the font directories.
+fn nerd_font_installed() -> bool {
+    let mut dirs = vec!["/usr/share/fonts".into(), "/usr/local/share/fonts".into()];
+    if let Ok(data_home) = crate::util::xdg_data_home() {
+        dirs.push(data_home.join("fonts"));
+    }
+    if let Some(home) = std::env::var_os("HOME") {
+        dirs.push(Path::new(&home).join(".fonts"));
+    }
+    dirs.iter().any(|dir| {
+        walkdir::WalkDir::new(dir)
+            .max_depth(4)
+            .into_iter()
+            .filter_map(|entry| entry.ok())
+            .any(|entry| {
+                entry
+                    .file_name()
+                    .to_string_lossy()
+                    .to_lowercase()
+                    .contains("nerd")
+            })
+    })
+}
+
+/// Pads the symbol with spaces, so that all symbols have the same width
+fn padded(symbol: &str) -> String {
+    if unicode_width(symbol) as usize >= SYMBOL_WIDTH {
+        symbol.to_string()
+    } else {
+        symbol.exact_width(SYMBOL_WIDTH)
+    }
+}
+
 pub struct SymbolEngine {
-    symbols: StringPatriciaMap<&'static str>,
+    /// Symbols for mime-types (like `image/png`) and their main types (like `image`)
+    symbols: StringPatriciaMap<String>,
+    /// Symbols for lowercase file extensions, they are preferred over the mime-types
+    extensions: HashMap<&'static str, String>,
+    /// Symbols for special filenames (like `Makefile`)
+    names: HashMap<&'static str, String>,
+    directory: String,
+    /// Symbol of directories that we cannot enter
+    locked: String,
+    file: String,
 }
 
 impl SymbolEngine {
-    pub fn new() -> Self {
+    pub fn new(set: IconSet) -> Self {
         let mut symbols = StringPatriciaMap::new();
-        symbols.insert(mime::IMAGE, "\u{1F5BB}");
-        symbols.insert(mime::IMAGE_BMP, "\u{1F5BB}");
-        symbols.insert(mime::IMAGE_PNG, "\u{1F5BB}");
-        symbols.insert(mime::IMAGE_JPEG, "\u{1F5BB}");
-        symbols.insert(mime::IMAGE_GIF, "\u{1F5BB}");
-        symbols.insert(mime::IMAGE_SVG, "\u{1F5BB}");
-        symbols.insert(mime::IMAGE_STAR, "\u{1F5BB}");
-
-        symbols.insert(mime::AUDIO, "\u{266B}");
+        let mut extensions = HashMap::new();
+        let mut names = HashMap::new();
+        let (directory, locked, file) = match set {
+            IconSet::Auto => return SymbolEngine::new(IconSet::detect()),
+            IconSet::Ascii => {
+                symbols.insert(mime::IMAGE, "#");
+                symbols.insert(mime::AUDIO, "~");
+                symbols.insert(mime::VIDEO, ">");
+                for ext in ARCHIVES {
+                    extensions.insert(ext, "@");
+                }
+                for ext in SHORTCUTS {
+                    extensions.insert(ext, "&");
+                }
+                ("/", "!", "-")
+            }
+            IconSet::Unicode => {
+                symbols.insert(mime::IMAGE, "\u{1F5BB}");
+                symbols.insert(mime::IMAGE_BMP, "\u{1F5BB}");
+                symbols.insert(mime::IMAGE_PNG, "\u{1F5BB}");
+                symbols.insert(mime::IMAGE_JPEG, "\u{1F5BB}");
+                symbols.insert(mime::IMAGE_GIF, "\u{1F5BB}");
+                symbols.insert(mime::IMAGE_SVG, "\u{1F5BB}");
+                symbols.insert(mime::IMAGE_STAR, "\u{1F5BB}");
 
-        symbols.insert(mime::PDF, "\u{202C}");
-        symbols.insert(mime::VIDEO, "\u{1F39E}");
+                symbols.insert(mime::AUDIO, "\u{266B}");
 
-        symbols.insert("text/markdown", "\u{1F89B}");
-        symbols.insert("text/x-toml", "\u{2699}");
+                symbols.insert(mime::PDF, "\u{1F5CE}");
+                symbols.insert(mime::VIDEO, "\u{1F39E}");
 
-        SymbolEngine { symbols }
+                symbols.insert("text/markdown", "\u{1F89B}");
+                symbols.insert("text/x-toml", "\u{2699}");
+                for ext in SHORTCUTS {
+                    extensions.insert(ext, "\u{1F517}");
+                }
+                ("\u{1F4C1}", "\u{1F512}", "\u{1F5B9}")
+            }
+            IconSet::Nerd => {
+                symbols.insert(mime::IMAGE, "\u{f1c5}");
+                symbols.insert(mime::AUDIO, "\u{f1c7}");
+                symbols.insert(mime::VIDEO, "\u{f1c8}");
+                symbols.insert(mime::PDF, "\u{f1c1}");
+                symbols.insert(mime::TEXT, "\u{f15c}");
+                for ext in ARCHIVES {
+                    extensions.insert(ext, "\u{f410}");
+                }
+                for ext in SHORTCUTS {
+                    extensions.insert(ext, "\u{f0c1}");
+                }
+                extensions.extend(NERD_EXTENSIONS);
+                names.extend(NERD_NAMES);
+                ("\u{f07b}", "\u{f023}", "\u{f15b}")
+            }
+        };
+        SymbolEngine {
+            symbols: symbols
+                .into_iter()
+                .map(|(mime, symbol)| (mime, padded(symbol)))
+                .collect(),
+            extensions: extensions
+                .into_iter()
+                .map(|(ext, symbol)| (ext, padded(symbol)))
+                .collect(),
+            names: names
+                .into_iter()
+                .map(|(name, symbol)| (name, padded(symbol)))
+                .collect(),
+            directory: padded(directory),
+            locked: padded(locked),
+            file: padded(file),
+        }
     }
 
-    pub fn init() {
-        if SYMBOLS.set(SymbolEngine::new()).is_err() {
+    pub fn init(set: IconSet) {
+        let engine = SymbolEngine::new(set);
+        info!("Using icons: {} {}", engine.directory, engine.file);
+        if SYMBOLS.set(engine).is_err() {
             error!("Symbol engine was already initialized.");
         }
     }
 
+    /// Returns the symbol of the file, which is always two cells wide.
+    pub fn symbol(&self, path: &Path) -> &str {
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy())
+            .unwrap_or_default();
+        if let Some(icon) = self.names.get(name.as_ref()) {
+            return icon;
+        }
+        let extension = path
+            .extension()
+            .map(|ext| ext.to_string_lossy().to_lowercase());
+        if let Some(icon) = extension.and_then(|ext| self.extensions.get(ext.as_str())) {
+            return icon;
+        }
+        let mime_type = get_mime_type(path);
+        if let Some(icon) = self.symbols.get(&mime_type) {
+            icon
+        } else if let Some(icon) = self.symbols.get(mime_type.type_()) {
+            icon
+        } else {
+            &self.file
+        }
+    }
+
     pub fn get_symbol<P: AsRef<Path>>(path: P) -> &'static str {
         if let Some(engine) = SYMBOLS.get() {
-            let mime_type = get_mime_type(path);
-            if let Some(icon) = engine.symbols.get(&mime_type) {
-                return icon;
-            } else if let Some(icon) = engine.symbols.get(mime_type.type_()) {
-                return icon;
-            } else {
-                return "\u{1F5B9}";
-            }
+            return engine.symbol(path.as_ref());
         } else {
             error!("Symbol engine was not initialized.");
         }
-        " "
+        "  "
+    }
+
+    /// Symbol of directories
+    pub fn dir_symbol() -> &'static str {
+        SYMBOLS.get().map(|e| e.directory.as_str()).unwrap_or("  ")
+    }
+
+    /// Symbol of directories, that cannot be read or entered
+    pub fn locked_dir_symbol() -> &'static str {
+        SYMBOLS.get().map(|e| e.locked.as_str()).unwrap_or("  ")
+    }
+
+    /// Symbol of files, that have no special symbol
+    pub fn file_symbol() -> &'static str {
+        SYMBOLS.get().map(|e| e.file.as_str()).unwrap_or("  ")
+    }
+}
+
+const ARCHIVES: [&str; 11] = [
+    "zip", "tar", "gz", "tgz", "xz", "bz2", "zst", "7z", "rar", "deb", "rpm",
+];
+
+/// Shortcut files, that can be followed like directories (see [`crate::engine::shortcut`])
+const SHORTCUTS: [&str; 2] = ["desktop", "url"];
+
+const NERD_EXTENSIONS: [(&str, &str); 35] = [
+    ("rs", "\u{e7a8}"),
+    ("py", "\u{e73c}"),
+    ("js", "\u{e74e}"),
+    ("mjs", "\u{e74e}"),
+    ("ts", "\u{e628}"),
+    ("html", "\u{e736}"),
+    ("htm", "\u{e736}"),
+    ("css", "\u{e749}"),
+    ("scss", "\u{e749}"),
+    ("md", "\u{e73e}"),
+    ("json", "\u{e60b}"),
+    ("toml", "\u{e615}"),
+    ("yaml", "\u{e615}"),
+    ("yml", "\u{e615}"),
+    ("ini", "\u{e615}"),
+    ("conf", "\u{e615}"),
+    ("sh", "\u{f489}"),
+    ("bash", "\u{f489}"),
+    ("zsh", "\u{f489}"),
+    ("fish", "\u{f489}"),
+    ("c", "\u{e61e}"),
+    ("h", "\u{e61e}"),
+    ("cpp", "\u{e61d}"),
+    ("cc", "\u{e61d}"),
+    ("hpp", "\u{e61d}"),
+    ("go", "\u{e626}"),
+    ("java", "\u{e738}"),
+    ("rb", "\u{e739}"),
+    ("php", "\u{e73d}"),
+    ("lua", "\u{e620}"),
+    ("vim", "\u{e62b}"),
+    ("hs", "\u{e777}"),
+    ("lock", "\u{f023}"),
+    ("iso", "\u{f0a0}"),
+    ("db", "\u{f1c0}"),
+];
+
+const NERD_NAMES: [(&str, &str); 6] = [
+    ("Makefile", "\u{e779}"),
+    ("Dockerfile", "\u{f308}"),
+    (".gitignore", "\u{e702}"),
+    (".gitmodules", "\u{e702}"),
+    (".gitattributes", "\u{e702}"),
+    ("Cargo.toml", "\u{e7a8}"),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn icon_sets() {
+        let nerd = SymbolEngine::new(IconSet::Nerd);
+        assert_eq!(nerd.symbol(Path::new("main.RS")), "\u{e7a8} ");
+        assert_eq!(nerd.symbol(Path::new("photo.png")), "\u{f1c5} ");
+        assert_eq!(nerd.symbol(Path::new("backup.tar.gz")), "\u{f410} ");
+        assert_eq!(nerd.symbol(Path::new("/src/Makefile")), "\u{e779} ");
+        assert_eq!(nerd.symbol(Path::new("firmware.bin")), "\u{f15b} ");
+
+        let ascii = SymbolEngine::new(IconSet::Ascii);
+        assert_eq!(ascii.symbol(Path::new("photo.png")), "# ");
+        assert_eq!(ascii.symbol(Path::new("main.rs")), "- ");
+        assert_eq!(ascii.directory, "/ ");
+        assert_eq!(ascii.symbol(Path::new("Projects.desktop")), "& ");
+
+        // Every symbol takes two cells, wide symbols are not padded
+        let unicode = SymbolEngine::new(IconSet::Unicode);
+        assert_eq!(unicode.directory, "\u{1F4C1}");
+        assert_eq!(unicode.locked, "\u{1F512}");
+        assert_eq!(unicode.symbol(Path::new("song.mp3")), "\u{266B} ");
+        for (_, symbol) in unicode.symbols.iter() {
+            assert_eq!(unicode_width(symbol), 2, "{symbol:?}");
+        }
     }
 }
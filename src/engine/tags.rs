@@ -0,0 +1,226 @@
+//! Named, colored tags for files and directories.
+//!
+//! Unlike marks, tags are kept in a sidecar file (`$XDG_DATA_HOME/rfm/tags`), so they survive
+//! restarts. The file has one line per item: the comma-separated tags, a tab and the path.
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{bail, Result};
+use crossterm::style::Color;
+use log::{debug, warn};
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+
+use crate::{
+    config::{color::adapt, TagConfig},
+    util::write_atomic,
+};
+
+static TAGS: Lazy<Mutex<Tags>> = Lazy::new(Default::default);
+
+/// Colors of tags, that have no color in the config
+const PALETTE: [Color; 6] = [
+    Color::Red,
+    Color::Green,
+    Color::Yellow,
+    Color::Blue,
+    Color::Magenta,
+    Color::Cyan,
+];
+
+#[derive(Debug, Default)]
+struct Tags {
+    file: Option<PathBuf>,
+    items: BTreeMap<PathBuf, BTreeSet<String>>,
+    colors: HashMap<String, Color>,
+}
+
+impl Tags {
+    fn parse(content: &str) -> BTreeMap<PathBuf, BTreeSet<String>> {
+        content
+            .lines()
+            .filter_map(|line| line.split_once('\t'))
+            .map(|(tags, path)| {
+                let tags = tags.split(',').filter(|t| !t.is_empty()).map(String::from);
+                (PathBuf::from(path), tags.collect())
+            })
+            .collect()
+    }
+
+    /// Writes the tags to their file (if any).
+    fn save(&self) -> std::io::Result<()> {
+        let Some(file) = &self.file else {
+            return Ok(());
+        };
+        write_atomic(file, |out| {
+            for (path, tags) in self.items.iter() {
+                let tags: Vec<&str> = tags.iter().map(String::as_str).collect();
+                writeln!(out, "{}\t{}", tags.join(","), path.display())?;
+            }
+            Ok(())
+        })
+    }
+
+    fn changed(&mut self) {
+        self.items.retain(|_, tags| !tags.is_empty());
+        if let Err(e) = self.save() {
+            warn!("Failed to save tags: {e}");
+        }
+    }
+}
+
+/// Loads the tags from the given file. Without a file, tags are only kept in memory.
+pub fn load(file: PathBuf) {
+    let items = match std::fs::read_to_string(&file) {
+        Ok(content) => Tags::parse(&content),
+        Err(e) => {
+            debug!("Cannot read {}: {e}", file.display());
+            BTreeMap::new()
+        }
+    };
+    let mut tags = TAGS.lock();
+    tags.file = Some(file);
+    tags.items = items;
+}
+
+/// Sets the colors of the tags from the config.
+pub fn set_colors(config: TagConfig) {
+    let mut tags = TAGS.lock();
+    for (name, color) in config.colors {
+        match crate::config::color::parse_color(&color) {
+            Ok(color) => {
+                tags.colors.insert(name, color);
+            }
+            Err(e) => warn!("Invalid color of tag '{name}': {e}"),
+        }
+    }
+}
+
+/// Tags the items, fails if the name of the tag is invalid.
+pub fn add(paths: &[PathBuf], tag: &str) -> Result<()> {
+    let tag = tag.trim();
+    if tag.is_empty() || tag.contains([',', '\t', '\n']) {
+        bail!("'{tag}' is not a valid tag (tags cannot be empty or contain commas)");
+    }
+    let mut tags = TAGS.lock();
+    for path in paths {
+        tags.items
+            .entry(path.clone())
+            .or_default()
+            .insert(tag.to_string());
+    }
+    tags.changed();
+    Ok(())
+}
+
+/// Removes all tags of the items and returns the number of items that were tagged.
+pub fn remove(paths: &[PathBuf]) -> usize {
+    let mut tags = TAGS.lock();
+    let n = paths
+        .iter()
+        .filter(|path| tags.items.remove(*path).is_some())
+        .count();
+    tags.changed();
+    n
+}
+
+/// Moves the tags of a renamed item (and of everything inside of it).
+pub fn rename(from: &Path, to: &Path) {
+    let mut tags = TAGS.lock();
+    let moved: Vec<PathBuf> = tags
+        .items
+        .keys()
+        .filter(|path| path.starts_with(from))
+        .cloned()
+        .collect();
+    if moved.is_empty() {
+        return;
+    }
+    for path in moved {
+        if let (Some(item_tags), Ok(rest)) = (tags.items.remove(&path), path.strip_prefix(from)) {
+            tags.items.insert(to.join(rest), item_tags);
+        }
+    }
+    tags.changed();
+}
+
+/// Returns the tags of the item in alphabetical order.
+pub fn of(path: &Path) -> Vec<String> {
+    TAGS.lock()
+        .items
+        .get(path)
+        .map(|tags| tags.iter().cloned().collect())
+        .unwrap_or_default()
+}
+
+/// Returns true if the item has a tag.
+pub fn is_tagged(path: &Path) -> bool {
+    TAGS.lock().items.contains_key(path)
+}
+
+/// Color of the dot in front of a tagged item (the color of its first tag).
+pub fn dot_color(path: &Path) -> Option<Color> {
+    let tags = TAGS.lock();
+    let tag = tags.items.get(path)?.first()?;
+    Some(color_of(&tags.colors, tag))
+}
+
+fn color_of(colors: &HashMap<String, Color>, tag: &str) -> Color {
+    let color = colors.get(tag).copied().unwrap_or_else(|| {
+        let hash = tag
+            .bytes()
+            .fold(0usize, |h, b| h.wrapping_mul(31).wrapping_add(b as usize));
+        PALETTE[hash % PALETTE.len()]
+    });
+    adapt(color)
+}
+
+/// Returns all tagged items that still exist with their tags.
+pub fn tagged() -> Vec<(PathBuf, Vec<String>)> {
+    TAGS.lock()
+        .items
+        .iter()
+        .filter(|(path, _)| path.exists())
+        .map(|(path, tags)| (path.clone(), tags.iter().cloned().collect()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tag_and_untag() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = dir.path().join("tags");
+        let a = dir.path().join("a");
+        let b = dir.path().join("b");
+        std::fs::create_dir(&a).unwrap();
+        std::fs::write(a.join("inner.txt"), "").unwrap();
+        std::fs::write(&b, "").unwrap();
+
+        load(db.clone());
+        add(&[a.clone(), b.clone()], "work").unwrap();
+        add(&[a.join("inner.txt")], " todo ").unwrap();
+        add(std::slice::from_ref(&a), "todo").unwrap();
+        assert!(add(std::slice::from_ref(&b), "a,b").is_err());
+        assert_eq!(of(&a), vec!["todo", "work"]);
+        assert!(is_tagged(&b) && !is_tagged(dir.path()));
+
+        // Tags are written to the file, and follow renamed items
+        assert_eq!(Tags::parse(&std::fs::read_to_string(&db).unwrap()).len(), 3);
+        let renamed = dir.path().join("renamed");
+        std::fs::rename(&a, &renamed).unwrap();
+        rename(&a, &renamed);
+        assert_eq!(of(&renamed.join("inner.txt")), vec!["todo"]);
+        assert!(of(&a).is_empty());
+
+        assert_eq!(remove(&[b.clone(), dir.path().to_path_buf()]), 1);
+        assert_eq!(tagged().len(), 2);
+        load(db);
+        assert_eq!(tagged().len(), 2);
+    }
+}
@@ -0,0 +1,482 @@
+//! Trash that remembers where the deleted items came from.
+//!
+//! The layout follows the freedesktop.org trash specification: the items are moved into `files/`,
+//! and `info/<name>.trashinfo` records the original path and the time of deletion of every item.
+//! The session trash is a temporary directory, which is removed when rfm exits,
+//! while the XDG trash is shared with the desktop.
+//!
+//! How items are deleted is abstracted by [`TrashBackend`], see [`backend`].
+use std::{
+    ffi::{OsStr, OsString},
+    fs::File,
+    io::{self, Write},
+    os::unix::ffi::{OsStrExt, OsStringExt},
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use serde::Deserialize;
+use tempfile::TempDir;
+use time::OffsetDateTime;
+
+use crate::util::xdg_data_home;
+
+/// Where deleted items go to
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TrashKind {
+    /// A temporary trash, that is removed when rfm exits
+    Session,
+    /// The trash of the desktop (`$XDG_DATA_HOME/Trash`)
+    Xdg,
+    /// The trash of the operating system (`~/.Trash` on macOS, the XDG trash everywhere else)
+    System,
+    /// Items are deleted permanently
+    Remove,
+}
+
+impl TrashKind {
+    /// Picks the trash from the config.
+    ///
+    /// An explicit `trash` wins, otherwise `use_trash` chooses between the session trash
+    /// (the system trash on macOS) and deleting permanently.
+    pub fn select(use_trash: bool, kind: Option<TrashKind>) -> Self {
+        match kind {
+            Some(kind) => kind,
+            None if !use_trash => TrashKind::Remove,
+            None if cfg!(target_os = "macos") => TrashKind::System,
+            None => TrashKind::Session,
+        }
+    }
+}
+
+/// Strategy that is used to delete items.
+pub trait TrashBackend: Send {
+    /// Moves the item into the trash (or deletes it).
+    fn put(&self, path: &Path) -> io::Result<()>;
+
+    /// Directory that contains the deleted items, if rfm can show it
+    fn files(&self) -> Option<PathBuf>;
+
+    /// Returns true if deleted items cannot be brought back.
+    fn is_permanent(&self) -> bool {
+        false
+    }
+
+    /// Returns true if the path is an item of the trash.
+    fn contains(&self, path: &Path) -> bool {
+        self.files()
+            .is_some_and(|files| path.parent() == Some(files.as_path()))
+    }
+
+    /// Moves an item of the trash back to its original location.
+    fn restore(&self, _item: &Path) -> io::Result<PathBuf> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+
+    /// Returns the number of items in the trash.
+    fn len(&self) -> usize {
+        self.files()
+            .and_then(|files| std::fs::read_dir(files).ok())
+            .map(|entries| entries.count())
+            .unwrap_or_default()
+    }
+
+    /// Deletes all items of the trash permanently and returns their number.
+    fn empty(&self) -> io::Result<usize> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+}
+
+/// Creates the trash of the given kind.
+pub fn backend(kind: TrashKind) -> io::Result<Box<dyn TrashBackend>> {
+    Ok(match kind {
+        TrashKind::Session => Box::new(Trash::new()?),
+        TrashKind::Xdg => Box::new(Trash::xdg()?),
+        #[cfg(target_os = "macos")]
+        TrashKind::System => Box::new(SystemTrash),
+        #[cfg(not(target_os = "macos"))]
+        TrashKind::System => Box::new(Trash::xdg()?),
+        TrashKind::Remove => Box::new(Remove),
+    })
+}
+
+/// Trash with the layout of the freedesktop.org trash specification
+pub struct Trash {
+    root: PathBuf,
+    /// Keeps the temporary directory of a session trash alive
+    _temp: Option<TempDir>,
+}
+
+impl Trash {
+    /// Creates a temporary trash, that is removed together with this value.
+    pub fn new() -> io::Result<Self> {
+        let dir = tempfile::tempdir()?;
+        let mut trash = Trash::at(dir.path().to_path_buf())?;
+        trash._temp = Some(dir);
+        Ok(trash)
+    }
+
+    /// Opens (or creates) the trash of the desktop.
+    pub fn xdg() -> io::Result<Self> {
+        let data_home = xdg_data_home().map_err(io::Error::other)?;
+        Trash::at(data_home.join("Trash"))
+    }
+
+    fn at(root: PathBuf) -> io::Result<Self> {
+        std::fs::create_dir_all(root.join("files"))?;
+        std::fs::create_dir_all(root.join("info"))?;
+        Ok(Trash { root, _temp: None })
+    }
+
+    fn files_dir(&self) -> PathBuf {
+        self.root.join("files")
+    }
+
+    fn info_file(&self, name: &OsStr) -> PathBuf {
+        let mut file_name = name.to_os_string();
+        file_name.push(".trashinfo");
+        self.root.join("info").join(file_name)
+    }
+
+    /// Moves the item into the trash and records its original location.
+    ///
+    /// Returns the path of the item inside of the trash.
+    pub fn put_item(&self, path: &Path) -> io::Result<PathBuf> {
+        let original = std::path::absolute(path)?;
+        let name = original
+            .file_name()
+            .ok_or_else(|| io::Error::other("invalid item"))?;
+        let info = format!(
+            "[Trash Info]\nPath={}\nDeletionDate={}\n",
+            encode_path(&original),
+            deletion_date()
+        );
+        // The name is reserved by creating the info file first (like the specification demands),
+        // so that we never take the name of an item that another program is trashing right now.
+        let mut candidate = name.to_os_string();
+        let (destination, info_file) = loop {
+            let destination = self.files_dir().join(&candidate);
+            let info_file = self.info_file(&candidate);
+            if destination.symlink_metadata().is_err() {
+                match File::options()
+                    .write(true)
+                    .create_new(true)
+                    .open(&info_file)
+                {
+                    Ok(mut file) => {
+                        if let Err(e) = file.write_all(info.as_bytes()) {
+                            let _ = std::fs::remove_file(&info_file);
+                            return Err(e);
+                        }
+                        break (destination, info_file);
+                    }
+                    Err(e) if e.kind() == io::ErrorKind::AlreadyExists => (),
+                    Err(e) => return Err(e),
+                }
+            }
+            // Append underscores until the name is free
+            candidate.push("_");
+        };
+        if let Err(e) = move_item(&original, &destination) {
+            let _ = std::fs::remove_file(&info_file);
+            return Err(e);
+        }
+        Ok(destination)
+    }
+}
+
+/// Formats the current time for the `DeletionDate` of an info file.
+///
+/// The specification requires the local time (without a timezone).
+fn deletion_date() -> String {
+    let now = OffsetDateTime::from(SystemTime::now());
+    let mut tm = std::mem::MaybeUninit::<libc::tm>::uninit();
+    let secs = now.unix_timestamp() as libc::time_t;
+    // SAFETY: 'secs' is a valid time_t and 'tm' is a `struct tm`
+    let (year, month, day, hour, minute, second) =
+        if unsafe { libc::localtime_r(&secs, tm.as_mut_ptr()) }.is_null() {
+            // Fall back to UTC, which is better than no date at all
+            let (hour, minute, second) = now.to_hms();
+            (
+                now.year(),
+                u8::from(now.month()) as i32,
+                now.day() as i32,
+                hour as i32,
+                minute as i32,
+                second as i32,
+            )
+        } else {
+            // SAFETY: localtime_r has filled 'tm'
+            let tm = unsafe { tm.assume_init() };
+            (
+                tm.tm_year + 1900,
+                tm.tm_mon + 1,
+                tm.tm_mday,
+                tm.tm_hour,
+                tm.tm_min,
+                tm.tm_sec,
+            )
+        };
+    format!("{year:04}-{month:02}-{day:02}T{hour:02}:{minute:02}:{second:02}")
+}
+
+impl TrashBackend for Trash {
+    fn put(&self, path: &Path) -> io::Result<()> {
+        self.put_item(path).map(|_| ())
+    }
+
+    fn files(&self) -> Option<PathBuf> {
+        Some(self.files_dir())
+    }
+
+    /// Fails if something else exists at the original location by now.
+    fn restore(&self, item: &Path) -> io::Result<PathBuf> {
+        let name = item
+            .file_name()
+            .ok_or_else(|| io::Error::other("invalid item"))?;
+        let info_file = self.info_file(name);
+        let info = std::fs::read_to_string(&info_file)?;
+        let original = info
+            .lines()
+            .find_map(|line| line.strip_prefix("Path="))
+            .map(decode_path)
+            .ok_or_else(|| io::Error::other("the original location is unknown"))?;
+        if original.symlink_metadata().is_ok() {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("{} already exists", original.display()),
+            ));
+        }
+        if let Some(parent) = original.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        move_item(item, &original)?;
+        std::fs::remove_file(info_file)?;
+        Ok(original)
+    }
+
+    fn empty(&self) -> io::Result<usize> {
+        let mut removed = 0;
+        for entry in std::fs::read_dir(self.files_dir())? {
+            let path = entry?.path();
+            remove_item(&path)?;
+            if let Some(name) = path.file_name() {
+                let _ = std::fs::remove_file(self.info_file(name));
+            }
+            removed += 1;
+        }
+        Ok(removed)
+    }
+}
+
+/// The trash of macOS, which is managed by the Finder.
+///
+/// rfm can show its content, but restoring and emptying is left to the Finder.
+#[cfg(target_os = "macos")]
+pub struct SystemTrash;
+
+#[cfg(target_os = "macos")]
+impl TrashBackend for SystemTrash {
+    fn put(&self, path: &Path) -> io::Result<()> {
+        trash::delete(path).map_err(io::Error::other)
+    }
+
+    fn files(&self) -> Option<PathBuf> {
+        std::env::var_os("HOME").map(|home| Path::new(&home).join(".Trash"))
+    }
+}
+
+/// Deletes items permanently.
+pub struct Remove;
+
+impl TrashBackend for Remove {
+    fn put(&self, path: &Path) -> io::Result<()> {
+        remove_item(path)
+    }
+
+    fn files(&self) -> Option<PathBuf> {
+        None
+    }
+
+    fn is_permanent(&self) -> bool {
+        true
+    }
+}
+
+/// Renames the item, or copies and removes it, if it lives on another filesystem than 'to'.
+fn move_item(from: &Path, to: &Path) -> io::Result<()> {
+    match std::fs::rename(from, to) {
+        Err(e) if e.raw_os_error() == Some(libc::EXDEV) => {
+            if let Err(e) = copy_item(from, to) {
+                if to.symlink_metadata().is_ok() {
+                    let _ = remove_item(to);
+                }
+                return Err(e);
+            }
+            remove_item(from)
+        }
+        result => result,
+    }
+}
+
+/// Copies the item recursively, symlinks are copied as symlinks.
+fn copy_item(from: &Path, to: &Path) -> io::Result<()> {
+    let metadata = from.symlink_metadata()?;
+    if metadata.is_symlink() {
+        std::os::unix::fs::symlink(std::fs::read_link(from)?, to)
+    } else if metadata.is_dir() {
+        std::fs::create_dir(to)?;
+        for entry in std::fs::read_dir(from)? {
+            let entry = entry?;
+            copy_item(&entry.path(), &to.join(entry.file_name()))?;
+        }
+        std::fs::set_permissions(to, metadata.permissions())
+    } else {
+        std::fs::copy(from, to).map(|_| ())
+    }
+}
+
+fn remove_item(path: &Path) -> io::Result<()> {
+    if path.is_dir() && !path.is_symlink() {
+        std::fs::remove_dir_all(path)
+    } else {
+        std::fs::remove_file(path)
+    }
+}
+
+/// Percent-encodes the path like the `Path` key of a `.trashinfo` file (or the path of a `file://` URI).
+pub fn encode_path(path: &Path) -> String {
+    let mut encoded = String::new();
+    for &byte in path.as_os_str().as_bytes() {
+        match byte {
+            b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'/' | b'-' | b'_' | b'.' | b'~' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{byte:02X}")),
+        }
+    }
+    encoded
+}
+
+/// Decodes the `Path` key of a `.trashinfo` file (or any other percent-encoded path).
+pub fn decode_path(encoded: &str) -> PathBuf {
+    let bytes = encoded.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut idx = 0;
+    while idx < bytes.len() {
+        let hex = bytes
+            .get(idx + 1..idx + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[idx], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                idx += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                idx += 1;
+            }
+        }
+    }
+    PathBuf::from(OsString::from_vec(decoded))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn restore_deleted_items() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("my notes %1.txt");
+        let sub = dir.path().join("sub");
+        std::fs::write(&file, "notes").unwrap();
+        std::fs::create_dir(&sub).unwrap();
+        std::fs::write(sub.join("a.txt"), "a").unwrap();
+
+        let trash = Trash::new().unwrap();
+        let trashed_file = trash.put_item(&file).unwrap();
+        let trashed_sub = trash.put_item(&sub).unwrap();
+        assert!(!file.exists() && !sub.exists());
+        assert!(trash.contains(&trashed_file));
+        assert_eq!(trash.len(), 2);
+
+        assert_eq!(trash.restore(&trashed_file).unwrap(), file);
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "notes");
+
+        // Nothing is overwritten by a restore
+        std::fs::create_dir(&sub).unwrap();
+        assert!(trash.restore(&trashed_sub).is_err());
+
+        assert_eq!(trash.empty().unwrap(), 1);
+        assert_eq!(trash.len(), 0);
+    }
+
+    #[test]
+    fn reserve_names_with_info_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("file");
+        std::fs::write(&file, "").unwrap();
+
+        // Another program is trashing an item with the same name right now
+        let trash = Trash::new().unwrap();
+        std::fs::write(trash.info_file(OsStr::new("file")), "other").unwrap();
+        let trashed = trash.put_item(&file).unwrap();
+        assert_eq!(trashed, trash.files_dir().join("file_"));
+        assert_eq!(
+            std::fs::read_to_string(trash.info_file(OsStr::new("file"))).unwrap(),
+            "other"
+        );
+        let info = std::fs::read_to_string(trash.info_file(OsStr::new("file_"))).unwrap();
+        let date = info
+            .lines()
+            .find_map(|line| line.strip_prefix("DeletionDate="))
+            .unwrap();
+        assert_eq!((date.len(), &date[10..11]), (19, "T"));
+    }
+
+    #[test]
+    fn copy_items_with_links() {
+        let dir = tempfile::tempdir().unwrap();
+        let item = dir.path().join("item");
+        std::fs::create_dir_all(item.join("sub")).unwrap();
+        std::fs::write(item.join("sub/file"), "content").unwrap();
+        std::os::unix::fs::symlink("sub/file", item.join("link")).unwrap();
+
+        let copy = dir.path().join("copy");
+        copy_item(&item, &copy).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(copy.join("sub/file")).unwrap(),
+            "content"
+        );
+        assert_eq!(
+            std::fs::read_link(copy.join("link")).unwrap(),
+            Path::new("sub/file")
+        );
+    }
+
+    #[test]
+    fn select_backend() {
+        assert_eq!(TrashKind::select(false, None), TrashKind::Remove);
+        assert_eq!(
+            TrashKind::select(false, Some(TrashKind::Xdg)),
+            TrashKind::Xdg
+        );
+
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("file");
+        let link = dir.path().join("link");
+        std::fs::write(&file, "").unwrap();
+        std::os::unix::fs::symlink(dir.path(), &link).unwrap();
+        let remove = backend(TrashKind::Remove).unwrap();
+        assert!(remove.is_permanent() && remove.files().is_none());
+        // Symlinks are removed without touching their target
+        remove.put(&link).unwrap();
+        assert!(!link.exists() && file.exists());
+        remove.put(&file).unwrap();
+        assert!(!file.exists());
+        assert!(remove.restore(&file).is_err());
+    }
+}
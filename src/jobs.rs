@@ -0,0 +1,514 @@
+//! Registry of the background jobs.
+//!
+//! Every long running task (background crawls, directory reads, thumbnails, file operations, ...)
+//! registers itself here and checks its [`CancelToken`] regularly.
+//! On shutdown all jobs are cancelled, and rfm waits a bounded amount of time for them to stop,
+//! before it writes a summary of the aborted jobs to the log.
+//!
+//! The last finished jobs (except for the ones that only fill caches and previews) are kept,
+//! so that they can be listed in the jobs overlay.
+//! Jobs can report their progress, which is summed up in the header while several of them run.
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt::Display,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use log::{debug, info, warn};
+use once_cell::sync::{Lazy, OnceCell};
+use parking_lot::Mutex;
+use serde::Deserialize;
+
+use crate::config::NotificationConfig;
+
+/// Shutdown flag
+///
+/// Cancels all jobs - including the ones that are started after the shutdown.
+pub static SHUTDOWN_FLAG: AtomicBool = AtomicBool::new(false);
+
+static JOBS: Lazy<Registry> = Lazy::new(Registry::default);
+
+static NOTIFICATIONS: OnceCell<NotificationConfig> = OnceCell::new();
+
+/// Number of finished jobs, that are kept for the jobs overlay
+const MAX_FINISHED: usize = 32;
+
+/// What a background job is doing
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobKind {
+    /// Background crawl, that fills the caches
+    Cache,
+    /// Reads a directory for a panel
+    Read,
+    /// Generates a preview (e.g. a video thumbnail)
+    Preview,
+    /// Copies or moves files
+    FileOperation,
+    /// Searches the content of files
+    Search,
+    /// Calculates checksums of files
+    Checksum,
+}
+
+impl Display for JobKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            JobKind::Cache => write!(f, "cache"),
+            JobKind::Read => write!(f, "read"),
+            JobKind::Preview => write!(f, "preview"),
+            JobKind::FileOperation => write!(f, "file operation"),
+            JobKind::Search => write!(f, "search"),
+            JobKind::Checksum => write!(f, "checksum"),
+        }
+    }
+}
+
+impl JobKind {
+    /// Returns true for the jobs, that rfm starts by itself all the time
+    pub fn is_background(&self) -> bool {
+        matches!(self, JobKind::Cache | JobKind::Read | JobKind::Preview)
+    }
+}
+
+/// How a job ended
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    Done,
+    Failed(String),
+    Cancelled,
+}
+
+impl Display for Outcome {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Outcome::Done => write!(f, "done"),
+            Outcome::Failed(_) => write!(f, "failed"),
+            Outcome::Cancelled => write!(f, "cancelled"),
+        }
+    }
+}
+
+/// Snapshot of a running or finished job
+#[derive(Debug, Clone)]
+pub struct JobInfo {
+    pub id: u64,
+    pub kind: JobKind,
+    pub description: String,
+    /// Time since the start (or the total time of a finished job)
+    pub elapsed: Duration,
+    /// `None` while the job is running
+    pub outcome: Option<Outcome>,
+    /// Progress in percent, if the job reports it
+    pub progress: Option<u8>,
+}
+
+/// Tells a job that it should stop.
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    /// Returns true if the job was cancelled, or rfm is shutting down.
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed) || SHUTDOWN_FLAG.load(Ordering::Relaxed)
+    }
+}
+
+struct Entry {
+    kind: JobKind,
+    description: String,
+    token: CancelToken,
+    started: Instant,
+    progress: Option<u8>,
+}
+
+#[derive(Default)]
+struct Registry {
+    next_id: AtomicU64,
+    jobs: Mutex<HashMap<u64, Entry>>,
+    /// Last finished jobs, the newest one first
+    finished: Mutex<VecDeque<JobInfo>>,
+}
+
+impl Registry {
+    fn start(&'static self, kind: JobKind, description: String) -> Job {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let token = CancelToken::default();
+        self.jobs.lock().insert(
+            id,
+            Entry {
+                kind,
+                description,
+                token: token.clone(),
+                started: Instant::now(),
+                progress: None,
+            },
+        );
+        Job {
+            id,
+            token,
+            outcome: None,
+            registry: self,
+        }
+    }
+
+    /// Removes the job from the running jobs and remembers how it ended.
+    fn finish(&self, id: u64, outcome: Outcome) {
+        let Some(entry) = self.jobs.lock().remove(&id) else {
+            return;
+        };
+        if entry.kind.is_background() {
+            return;
+        }
+        let info = JobInfo {
+            id,
+            kind: entry.kind,
+            description: entry.description,
+            elapsed: entry.started.elapsed(),
+            outcome: Some(outcome),
+            progress: entry.progress,
+        };
+        if let Some(config) = NOTIFICATIONS.get() {
+            announce(config, &info);
+        }
+        let mut finished = self.finished.lock();
+        finished.push_front(info);
+        finished.truncate(MAX_FINISHED);
+    }
+
+    fn list(&self) -> Vec<JobInfo> {
+        let mut running: Vec<JobInfo> = self
+            .jobs
+            .lock()
+            .iter()
+            .map(|(id, entry)| JobInfo {
+                id: *id,
+                kind: entry.kind,
+                description: entry.description.clone(),
+                elapsed: entry.started.elapsed(),
+                outcome: None,
+                progress: entry.progress,
+            })
+            .collect();
+        running.sort_by_key(|job| job.id);
+        running.extend(self.finished.lock().iter().cloned());
+        running
+    }
+
+    /// Summary of the running jobs, that the user started (like "2 jobs: 45%, 80%").
+    ///
+    /// Returns `None` unless at least two of them are running.
+    fn summary(&self) -> Option<String> {
+        let mut running: Vec<(u64, Option<u8>)> = self
+            .jobs
+            .lock()
+            .iter()
+            .filter(|(_, entry)| !entry.kind.is_background())
+            .map(|(id, entry)| (*id, entry.progress))
+            .collect();
+        if running.len() < 2 {
+            return None;
+        }
+        running.sort_unstable();
+        let progress: Vec<String> = running
+            .iter()
+            .map(|(_, progress)| match progress {
+                Some(percent) => format!("{percent}%"),
+                None => "...".to_string(),
+            })
+            .collect();
+        Some(format!("{} jobs: {}", running.len(), progress.join(", ")))
+    }
+
+    fn cancel(&self, id: u64) -> bool {
+        match self.jobs.lock().get(&id) {
+            Some(entry) => {
+                entry.token.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Cancels all running jobs and returns them.
+    fn cancel_all(&self) -> Vec<(JobKind, String)> {
+        let jobs = self.jobs.lock();
+        jobs.values()
+            .map(|entry| {
+                entry.token.cancel();
+                (entry.kind, entry.description.clone())
+            })
+            .collect()
+    }
+
+    fn len(&self) -> usize {
+        self.jobs.lock().len()
+    }
+}
+
+/// Running job. It is removed from the registry when dropped.
+pub struct Job {
+    id: u64,
+    token: CancelToken,
+    outcome: Option<Outcome>,
+    registry: &'static Registry,
+}
+
+impl Job {
+    pub fn token(&self) -> &CancelToken {
+        &self.token
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.token.is_cancelled()
+    }
+
+    /// Reports that `done` of `total` steps are finished.
+    pub fn set_progress(&self, done: u64, total: u64) {
+        let percent = (done.min(total) * 100).checked_div(total).unwrap_or(100);
+        if let Some(entry) = self.registry.jobs.lock().get_mut(&self.id) {
+            entry.progress = Some(percent as u8);
+        }
+    }
+
+    /// Marks the job as failed. It is listed with the reason, when it is finished.
+    pub fn fail(&mut self, reason: impl Into<String>) {
+        self.outcome = Some(Outcome::Failed(reason.into()));
+    }
+}
+
+impl Drop for Job {
+    fn drop(&mut self) {
+        let outcome = match self.outcome.take() {
+            _ if self.token.is_cancelled() => Outcome::Cancelled,
+            Some(outcome) => outcome,
+            None => Outcome::Done,
+        };
+        self.registry.finish(self.id, outcome);
+    }
+}
+
+/// Announces finished jobs with a desktop notification or the terminal bell.
+/// Must be called before the first job finishes.
+pub fn set_notifications(config: NotificationConfig) {
+    if NOTIFICATIONS.set(config).is_err() {
+        warn!("notifications are already set");
+    }
+}
+
+/// Returns true if the end of the job should be announced.
+///
+/// Cancelled jobs are never announced, because the user knows about them already.
+fn should_announce(config: &NotificationConfig, job: &JobInfo) -> bool {
+    (config.desktop || config.bell)
+        && config.jobs.contains(&job.kind)
+        && job.elapsed >= Duration::from_secs(config.min_duration)
+        && !matches!(job.outcome, Some(Outcome::Cancelled) | None)
+        && !SHUTDOWN_FLAG.load(Ordering::Relaxed)
+}
+
+fn announce(config: &NotificationConfig, job: &JobInfo) {
+    if !should_announce(config, job) {
+        return;
+    }
+    if config.bell {
+        use std::io::Write;
+        let mut stdout = std::io::stdout();
+        let _ = stdout.write_all(b"\x07").and_then(|()| stdout.flush());
+    }
+    if config.desktop {
+        let (summary, urgency) = match &job.outcome {
+            Some(Outcome::Failed(reason)) => (format!("{} failed: {reason}", job.kind), "critical"),
+            _ => (format!("{} finished", job.kind), "normal"),
+        };
+        let body = format!("{} ({}s)", job.description, job.elapsed.as_secs());
+        let child = std::process::Command::new("notify-send")
+            .args(["--app-name=rfm", "--urgency", urgency, &summary, &body])
+            .stdin(std::process::Stdio::null())
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null())
+            .spawn();
+        match child {
+            // Reap the process in the background
+            Ok(mut child) => {
+                std::thread::spawn(move || child.wait());
+            }
+            Err(e) => debug!("cannot run notify-send: {e}"),
+        }
+    }
+}
+
+/// Registers a new background job.
+pub fn start(kind: JobKind, description: impl Into<String>) -> Job {
+    JOBS.start(kind, description.into())
+}
+
+/// Returns the running jobs (oldest first), followed by the finished ones (newest first).
+pub fn list() -> Vec<JobInfo> {
+    JOBS.list()
+}
+
+/// Returns a summary of the progress, while at least two jobs of the user are running.
+pub fn summary() -> Option<String> {
+    JOBS.summary()
+}
+
+/// Cancels the running job. Returns false if the job has already finished.
+pub fn cancel(id: u64) -> bool {
+    JOBS.cancel(id)
+}
+
+/// Cancels all jobs and waits at most `grace_period` for them to stop.
+///
+/// Returns the descriptions of the aborted file operations, because they may have left
+/// incomplete copies behind. All other aborted jobs are only logged for debugging.
+pub async fn shutdown(grace_period: Duration) -> Vec<String> {
+    SHUTDOWN_FLAG.store(true, Ordering::Relaxed);
+    let aborted = JOBS.cancel_all();
+    let deadline = Instant::now() + grace_period;
+    while JOBS.len() > 0 && Instant::now() < deadline {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    let mut file_operations = Vec::new();
+    for (kind, description) in aborted.iter() {
+        match kind {
+            JobKind::FileOperation => {
+                warn!("Aborted on exit: {description}");
+                file_operations.push(description.clone());
+            }
+            _ => debug!("aborted {kind} job: {description}"),
+        }
+    }
+    if !aborted.is_empty() {
+        info!("Aborted {} background jobs on exit", aborted.len());
+    }
+    let remaining = JOBS.len();
+    if remaining > 0 {
+        warn!(
+            "{remaining} background jobs did not stop within {:.1}s",
+            grace_period.as_secs_f64()
+        );
+    }
+    file_operations
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cancel_running_jobs() {
+        let registry: &'static Registry = Box::leak(Box::default());
+        let finished = registry.start(JobKind::Read, "finished".into());
+        let running = registry.start(JobKind::FileOperation, "running".into());
+        drop(finished);
+        assert_eq!(registry.len(), 1);
+
+        let aborted = registry.cancel_all();
+        assert_eq!(
+            aborted,
+            vec![(JobKind::FileOperation, "running".to_string())]
+        );
+        assert!(running.is_cancelled());
+        drop(running);
+        assert_eq!(registry.len(), 0);
+    }
+
+    #[test]
+    fn list_finished_jobs() {
+        let registry: &'static Registry = Box::leak(Box::default());
+        let mut failed = registry.start(JobKind::FileOperation, "failed".into());
+        let cancelled = registry.start(JobKind::Checksum, "cancelled".into());
+        let running = registry.start(JobKind::Search, "running".into());
+        drop(registry.start(JobKind::Read, "background".into()));
+        failed.fail("exit status 1");
+        drop(failed);
+        assert!(registry.cancel(cancelled.id));
+        drop(cancelled);
+
+        let jobs: Vec<(String, Option<Outcome>)> = registry
+            .list()
+            .into_iter()
+            .map(|job| (job.description, job.outcome))
+            .collect();
+        assert_eq!(
+            jobs,
+            vec![
+                ("running".to_string(), None),
+                ("cancelled".to_string(), Some(Outcome::Cancelled)),
+                (
+                    "failed".to_string(),
+                    Some(Outcome::Failed("exit status 1".into()))
+                ),
+            ]
+        );
+        drop(running);
+    }
+
+    #[test]
+    fn summarize_progress() {
+        let registry: &'static Registry = Box::leak(Box::default());
+        let copy = registry.start(JobKind::FileOperation, "copy".into());
+        let _read = registry.start(JobKind::Read, "read".into());
+        assert_eq!(registry.summary(), None);
+
+        let search = registry.start(JobKind::Search, "search".into());
+        assert_eq!(registry.summary().as_deref(), Some("2 jobs: ..., ..."));
+        copy.set_progress(9, 20);
+        search.set_progress(4, 5);
+        assert_eq!(registry.summary().as_deref(), Some("2 jobs: 45%, 80%"));
+        assert_eq!(registry.list()[0].progress, Some(45));
+
+        drop(copy);
+        assert_eq!(registry.summary(), None);
+        assert_eq!(registry.list()[2].progress, Some(45));
+    }
+
+    #[test]
+    fn announce_long_jobs() {
+        let config = NotificationConfig {
+            bell: true,
+            ..Default::default()
+        };
+        let job = |kind, secs, outcome| JobInfo {
+            id: 0,
+            kind,
+            description: "copying 3 items".into(),
+            elapsed: Duration::from_secs(secs),
+            outcome: Some(outcome),
+            progress: None,
+        };
+        assert!(should_announce(
+            &config,
+            &job(JobKind::FileOperation, 12, Outcome::Done)
+        ));
+        assert!(should_announce(
+            &config,
+            &job(JobKind::Checksum, 30, Outcome::Failed("denied".into()))
+        ));
+        assert!(!should_announce(
+            &config,
+            &job(JobKind::FileOperation, 2, Outcome::Done)
+        ));
+        assert!(!should_announce(
+            &config,
+            &job(JobKind::FileOperation, 60, Outcome::Cancelled)
+        ));
+        assert!(!should_announce(
+            &config,
+            &job(JobKind::Read, 60, Outcome::Done)
+        ));
+        assert!(!should_announce(
+            &NotificationConfig::default(),
+            &job(JobKind::FileOperation, 60, Outcome::Done)
+        ));
+    }
+}
@@ -1,9 +1,10 @@
 use anyhow::Context;
 use clap::Parser;
-use content::{PanelCache, SHUTDOWN_FLAG};
+use content::PanelCache;
 use crossterm::{
     cursor,
-    event::DisableMouseCapture,
+    event::{DisableBracketedPaste, DisableMouseCapture, EnableBracketedPaste, EnableMouseCapture},
+    style::Print,
     terminal::{
         disable_raw_mode, enable_raw_mode, Clear, ClearType, DisableLineWrap, EnableLineWrap,
         EnterAlternateScreen, LeaveAlternateScreen,
@@ -11,12 +12,25 @@ use crossterm::{
     QueueableCommand,
 };
 use engine::{
-    commands::{CloseCmd, CommandParser},
+    actions::set_custom_actions,
+    clipboard::SharedClipboard,
+    commands::{CloseCmd, CommandParser, ExpandedPath},
+    doctor,
+    frecency::Frecency,
+    protect::{set_protected_paths, DEFAULT_PROTECTED},
+    recent::RecentFiles,
+    script::parse_script,
+    symbols::IconSet,
+    tags,
+    trash::TrashKind,
     OpenEngine, SymbolEngine,
 };
 use log::{error, info, warn};
 use logger::LogBuffer;
-use panel::{init_miller_panels, manager::PanelManager};
+use panel::{
+    init_miller_panels, manager::PanelManager, set_preview_config, set_sort_mode, set_status_line,
+    status_line,
+};
 use rust_embed::Embed;
 use std::{
     fs::{File, OpenOptions},
@@ -25,16 +39,24 @@ use std::{
     time::Duration,
 };
 use tokio::sync::mpsc;
-use util::xdg_config_home;
+use util::{xdg_config_home, xdg_data_home};
 
-use crate::config::color::{colors_from_config, colors_from_default};
+use crate::config::{
+    color::{colors_from_config, colors_from_default, detect_theme},
+    read_with_profile, ConfigFiles, LayoutPreset,
+};
 
 mod config;
 mod content;
+mod drives;
 mod engine;
+mod jobs;
 mod logger;
 mod panel;
+mod remote;
+mod server;
 mod util;
+mod vfs;
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
@@ -43,7 +65,39 @@ struct Args {
     /// it will write the full path of the last visited directory to CHOOSEDIR
     #[arg(long)]
     choosedir: Option<PathBuf>,
-    /// Path to open (defaults to ".")
+    /// Makes rfm act like a file picker. Opening a file (or the 'select_for_picker' command)
+    /// quits and writes the full paths of the chosen files to CHOOSEFILE (one per line)
+    #[arg(long)]
+    choosefile: Option<PathBuf>,
+    /// Starts in the parent directory of SELECTFILE, with SELECTFILE selected
+    #[arg(long)]
+    selectfile: Option<PathBuf>,
+    /// Listens for commands of other programs on the unix socket SOCKET.
+    /// Together with --send, the command is sent to SOCKET instead
+    #[arg(long)]
+    socket: Option<PathBuf>,
+    /// Sends a command (like "jump ~/Downloads" or "reveal file.txt") to a running instance and exits
+    #[arg(long)]
+    send: Option<String>,
+    /// Disables all previews (and hides the preview column)
+    #[arg(long)]
+    no_previews: bool,
+    /// Disables the file-watchers, panels are only refreshed when you move
+    #[arg(long)]
+    no_watchers: bool,
+    /// Disables all commands that change the filesystem (delete, paste, rename, ...)
+    #[arg(long)]
+    readonly: bool,
+    /// Runs rfm without a terminal and executes the commands from the given file.
+    /// The results of every command are written to stdout
+    #[arg(long)]
+    script: Option<PathBuf>,
+    /// Name of a profile in $XDG_CONFIG_HOME/rfm/profiles/.
+    /// The config files of the profile override the values of the base config
+    #[arg(long)]
+    profile: Option<String>,
+    /// Directory to open (defaults to "."). If PATH is a file,
+    /// rfm starts in its directory with the file selected
     path: Option<PathBuf>,
 }
 
@@ -63,30 +117,75 @@ const ERROR_MSG: &str = "\
 #[folder = "examples/"]
 struct Examples;
 
-#[tokio::main(flavor = "multi_thread", worker_threads = 4)]
-async fn main() -> anyhow::Result<()> {
+/// Saves and restores the title of the terminal (xterm window operations, ignored by others)
+const PUSH_TITLE: &str = "\x1b[22;0t";
+const POP_TITLE: &str = "\x1b[23;0t";
+
+/// Time that background jobs get to stop after quitting
+const SHUTDOWN_GRACE_PERIOD: Duration = Duration::from_secs(2);
+
+/// Milliseconds after the last key, after which an unfinished key sequence is dropped
+const DEFAULT_KEY_TIMEOUT: u64 = 2000;
+
+/// Milliseconds after which the continuations of an unfinished key sequence pop up
+const DEFAULT_KEY_POPUP: u64 = 500;
+
+fn main() -> anyhow::Result<()> {
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(4)
+        .enable_all()
+        .build()?;
+    let result = runtime.block_on(run());
+    // Jobs that did not stop within the grace period are abandoned,
+    // instead of blocking the exit until they are finished.
+    runtime.shutdown_background();
+    result
+}
+
+async fn run() -> anyhow::Result<()> {
+    let args = Args::parse();
+
+    // Act as a client of the control server
+    if let Some(command) = &args.send {
+        let socket = args
+            .socket
+            .clone()
+            .or_else(|| std::env::var_os(server::SOCKET_ENV).map(PathBuf::from))
+            .or_else(server::default_socket)
+            .context("cannot determine the location of the socket")?;
+        let answer = server::send(&socket, command)?;
+        println!("{answer}");
+        if answer.starts_with("error") {
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
+    // Read the script first, so we don't touch the terminal if it is invalid
+    let script = match &args.script {
+        Some(script_file) => {
+            let content = std::fs::read_to_string(script_file)
+                .context(format!("failed to read {}", script_file.display()))?;
+            Some(parse_script(&content).context("invalid script")?)
+        }
+        None => None,
+    };
+    let headless = script.is_some();
+
     // Check if we run from a terminal
     let mut stdout = stdout();
-    if !stdout.is_terminal() {
+    if !headless && !stdout.is_terminal() {
         eprintln!("Error: Stdout handle does not refer to a terminal/tty");
         eprintln!();
         eprintln!("Please note: The output of rfm can be neither piped nor redirected.");
+        eprintln!("Use --script if you want to run rfm without a terminal.");
         std::process::exit(1);
     }
 
-    let args = Args::parse();
-
     std::panic::set_hook(Box::new(|panic_info| {
         error!("{panic_info}");
     }));
 
-    // Remember starting path
-    let starting_path = if let Some(path) = args.path {
-        path
-    } else {
-        std::env::current_dir().context("failed to get current directory from env")?
-    };
-
     // Initialize logger
     let logger = LogBuffer::default()
         .with_level(log::Level::Debug)
@@ -116,6 +215,19 @@ async fn main() -> anyhow::Result<()> {
         std::fs::create_dir(&config_dir).context("failed to create config directory")?;
     }
 
+    // --- Profile specific config overlays
+    let profile_dir = match &args.profile {
+        Some(name) => {
+            let dir = config_dir.join("profiles").join(name);
+            if !dir.is_dir() {
+                return Err(anyhow::anyhow!("profile '{name}' does not exist"))
+                    .context(format!("{} is not a directory", dir.display()));
+            }
+            Some(dir)
+        }
+        None => None,
+    };
+
     // --- Set or generate color configuration
     let general_config_file = config_dir.join("config.toml");
     if !general_config_file.exists() {
@@ -129,14 +241,68 @@ async fn main() -> anyhow::Result<()> {
     }
 
     // Weather or not we activate the trash
-    let mut use_trash = false;
-
-    if let Ok(content) = std::fs::read_to_string(&general_config_file) {
+    let mut trash_kind = TrashKind::Remove;
+    let mut confirm_trash = true;
+    let mut search_wrap = true;
+    let mut cursor_wrap = false;
+    let mut cache_config = config::CacheConfig::default();
+    let mut watcher_config = config::WatcherConfig::default();
+    let mut preview_config = config::PreviewConfig::default();
+    let mut start_dir = None;
+    let mut mouse = false;
+    let mut hints = true;
+    let mut key_timeout = DEFAULT_KEY_TIMEOUT;
+    let mut key_popup = DEFAULT_KEY_POPUP;
+    let mut autosize = None;
+    let mut layout = LayoutPreset::default();
+    let mut disk_warning = Some(90);
+    let mut terminal = None;
+    let mut icons = IconSet::default();
+    let mut server = false;
+    let mut system_clipboard = true;
+
+    if let Ok(content) = read_with_profile(&general_config_file, profile_dir.as_deref()) {
         match toml::from_str::<config::Config>(&content) {
             Ok(config) => {
                 info!("Using general config: {}", general_config_file.display());
-                colors_from_config(config.colors)?;
-                use_trash = config.general.use_trash;
+                colors_from_config(config.colors, config.themes)?;
+                trash_kind = TrashKind::select(config.general.use_trash, config.general.trash);
+                confirm_trash = config.general.confirm_trash.unwrap_or(true);
+                search_wrap = config.general.search_wrap.unwrap_or(true);
+                cursor_wrap = config.general.cursor_wrap.unwrap_or(false);
+                set_sort_mode(config.general.sort.unwrap_or_default());
+                cache_config = config.cache;
+                watcher_config = config.watcher;
+                preview_config = config.preview;
+                // Scripts write to stdout, so they are never interrupted by a bell
+                if !headless {
+                    jobs::set_notifications(config.notifications);
+                }
+                set_status_line(config.statusline);
+                set_custom_actions(config.actions);
+                tags::set_colors(config.tags);
+                mouse = config.general.mouse.unwrap_or(false);
+                hints = config.general.hints.unwrap_or(true);
+                key_timeout = config.general.key_timeout.unwrap_or(DEFAULT_KEY_TIMEOUT);
+                key_popup = config.general.key_popup.unwrap_or(DEFAULT_KEY_POPUP);
+                if config.general.autosize.unwrap_or(false) {
+                    autosize = Some(config.general.autosize_max.unwrap_or(75));
+                }
+                layout = config.general.layout.unwrap_or_default();
+                disk_warning = match config.general.disk_usage {
+                    Some(false) => None,
+                    _ => Some(config.general.disk_warning.unwrap_or(90)),
+                };
+                terminal = config.general.terminal;
+                icons = config.general.icons.unwrap_or_default();
+                server = config.general.server.unwrap_or(false);
+                system_clipboard = config.general.system_clipboard.unwrap_or(true);
+                let protected = config
+                    .general
+                    .protected_paths
+                    .unwrap_or_else(|| DEFAULT_PROTECTED.map(String::from).to_vec());
+                set_protected_paths(&protected, config.general.protect_mounts.unwrap_or(true));
+                start_dir = config.general.start_dir.map(ExpandedPath::from);
             }
             Err(e) => {
                 warn!("Configuration error: {e}. Using default color config");
@@ -148,6 +314,15 @@ async fn main() -> anyhow::Result<()> {
         colors_from_default();
     }
 
+    // Flags for minimal environments override the config
+    if args.no_previews {
+        preview_config.enabled = false;
+    }
+    if args.no_watchers {
+        watcher_config.enabled = false;
+    }
+    set_preview_config(preview_config);
+
     // --- Keyboard configuration
     let key_config_file = config_dir.join("keys.toml");
     if !key_config_file.exists() {
@@ -158,24 +333,27 @@ async fn main() -> anyhow::Result<()> {
         file.write_all(&default.data)?;
     }
 
-    let parser = if let Ok(content) = std::fs::read_to_string(&key_config_file) {
-        match toml::from_str(&content) {
-            Ok(key_config) => {
-                info!("Using keyboard config: {}", key_config_file.display());
-                CommandParser::from_config(key_config)
-            }
-            Err(e) => {
-                warn!("Configuration error: {e}. Using default keyboard bindings");
-                CommandParser::default_bindings()
+    let mut parser =
+        if let Ok(content) = read_with_profile(&key_config_file, profile_dir.as_deref()) {
+            match toml::from_str(&content) {
+                Ok(key_config) => {
+                    info!("Using keyboard config: {}", key_config_file.display());
+                    CommandParser::from_config(key_config)
+                }
+                Err(e) => {
+                    warn!("Configuration error: {e}. Using default keyboard bindings");
+                    CommandParser::default_bindings()
+                }
             }
-        }
-    } else {
-        warn!(
-            "Cannot find keyboard config '{}'. Using default keyboard bindings",
-            key_config_file.display()
-        );
-        CommandParser::default_bindings()
-    };
+        } else {
+            warn!(
+                "Cannot find keyboard config '{}'. Using default keyboard bindings",
+                key_config_file.display()
+            );
+            CommandParser::default_bindings()
+        };
+    parser.set_timeout(Some(Duration::from_millis(key_timeout)).filter(|t| !t.is_zero()));
+    parser.set_popup_delay(Some(Duration::from_millis(key_popup)).filter(|t| !t.is_zero()));
 
     // --- Opener configuration
     let open_config_file = config_dir.join("open.toml");
@@ -187,51 +365,130 @@ async fn main() -> anyhow::Result<()> {
         file.write_all(&default.data)?;
     }
 
-    let opener = if let Ok(content) = std::fs::read_to_string(&open_config_file) {
-        match toml::from_str(&content) {
-            Ok(open_config) => {
-                info!("Using open-engine config: {}", open_config_file.display());
-                OpenEngine::with_config(open_config)
-            }
-            Err(e) => {
-                warn!("Configuration error: {e}. Using default open engine");
-                OpenEngine::default()
+    let mut opener =
+        if let Ok(content) = read_with_profile(&open_config_file, profile_dir.as_deref()) {
+            match toml::from_str(&content) {
+                Ok(open_config) => {
+                    info!("Using open-engine config: {}", open_config_file.display());
+                    OpenEngine::with_config(open_config)
+                }
+                Err(e) => {
+                    warn!("Configuration error: {e}. Using default open engine");
+                    OpenEngine::default()
+                }
             }
-        }
+        } else {
+            info!("Using default open engine");
+            OpenEngine::default()
+        };
+    opener.set_terminal(terminal);
+
+    // Tell the user early, which programs are missing
+    let programs = doctor::config_programs(&opener);
+    tokio::task::spawn_blocking(move || doctor::probe(programs));
+
+    // Remember starting path
+    let mut selection = None;
+    let starting_path = if let Some(file) = args
+        .selectfile
+        .or_else(|| args.path.clone().filter(|path| path.is_file()))
+    {
+        let file = file
+            .canonicalize()
+            .context(format!("failed to find {}", file.display()))?;
+        let parent = file.parent().unwrap_or(&file).to_path_buf();
+        selection = Some(file);
+        parent
+    } else if let Some(path) = args.path {
+        path
+    } else if let Some(dir) = start_dir {
+        dir.into()
     } else {
-        info!("Using default open engine");
-        OpenEngine::default()
+        std::env::current_dir().context("failed to get current directory from env")?
     };
 
-    enable_raw_mode()?;
-
-    stdout
-        .queue(DisableMouseCapture)?
-        .queue(DisableLineWrap)?
-        .queue(cursor::SavePosition)?
-        // NOTE: We move to the alternate screen,
-        // to not mess with the current content of the terminal
-        .queue(EnterAlternateScreen)?
-        .queue(cursor::Hide)?
-        .queue(Clear(ClearType::All))?
-        .queue(cursor::MoveTo(0, 0))?;
+    if !headless {
+        enable_raw_mode()?;
+        detect_theme();
+
+        stdout
+            .queue(DisableMouseCapture)?
+            .queue(EnableBracketedPaste)?
+            .queue(DisableLineWrap)?
+            .queue(cursor::SavePosition)?
+            // NOTE: We move to the alternate screen,
+            // to not mess with the current content of the terminal
+            .queue(EnterAlternateScreen)?
+            .queue(cursor::Hide)?
+            .queue(Clear(ClearType::All))?
+            .queue(cursor::MoveTo(0, 0))?;
+        // Remember the title of the terminal, so that it can be restored on exit
+        if status_line().title.is_some() {
+            stdout.queue(Print(PUSH_TITLE))?;
+        }
+        if mouse {
+            stdout.queue(EnableMouseCapture)?;
+            opener.set_mouse_capture(true);
+        }
+    }
 
-    SymbolEngine::init();
+    SymbolEngine::init(icons);
 
-    let directory_cache = PanelCache::with_size(16384);
-    let preview_cache = PanelCache::with_size(4096);
+    // The memory is split like the number of entries
+    let max_bytes = cache_config.max_memory.saturating_mul(1024 * 1024);
+    let directory_cache =
+        PanelCache::with_size(16384).with_limits(max_bytes / 5 * 4, cache_config.ttl());
+    let preview_cache = PanelCache::with_size(4096).with_limits(max_bytes / 5, cache_config.ttl());
 
     let (dir_tx, dir_rx) = mpsc::channel(32);
     let (prev_tx, prev_rx) = mpsc::channel(32);
 
     let (preview_tx, preview_rx) = mpsc::unbounded_channel();
     let (directory_tx, directory_rx) = mpsc::unbounded_channel();
+    let (cache_tx, cache_rx) = mpsc::unbounded_channel();
+    let (server_tx, server_rx) = mpsc::unbounded_channel();
+    let (config_tx, config_rx) = mpsc::unbounded_channel();
+
+    // Programs that are started from rfm can control it via $RFM_SOCKET
+    let socket = args
+        .socket
+        .clone()
+        .or_else(|| server.then(server::default_socket).flatten());
+    let _server = match socket {
+        Some(socket) if !headless => match server::start(socket, server_tx) {
+            Ok(server) => Some(server),
+            Err(e) => {
+                warn!("Cannot start control server: {e:#}");
+                None
+            }
+        },
+        _ => None,
+    };
+
+    // Keybindings, colors and opener rules are applied as soon as their files change
+    let config_files = ConfigFiles {
+        dir: config_dir,
+        profile_dir,
+    };
+    let _config_watcher = if watcher_config.enabled && !headless {
+        match config_files.watch(config_tx) {
+            Ok(watcher) => Some(watcher),
+            Err(e) => {
+                warn!("Cannot watch the config files: {e}");
+                None
+            }
+        }
+    } else {
+        None
+    };
 
     let dir_manager = content::DirManager::new(
         directory_cache.clone(),
         preview_cache.clone(),
         dir_tx,
         directory_rx,
+        cache_config,
+        cache_tx,
     );
 
     let preview_manager = content::PreviewManager::new(preview_cache.clone(), prev_tx, preview_rx);
@@ -241,43 +498,103 @@ async fn main() -> anyhow::Result<()> {
 
     let miller_panels = init_miller_panels(
         starting_path.clone(),
+        selection,
         directory_cache,
         preview_cache,
         directory_tx,
         preview_tx,
+        &watcher_config,
     );
 
+    if let Ok(data_dir) = xdg_data_home() {
+        tags::load(data_dir.join("rfm").join("tags"));
+    }
+
+    // Scripts should not pollute the directory history
+    let frecency = match xdg_data_home() {
+        Ok(data_dir) if !headless => {
+            opener.set_recent_files(RecentFiles::load(data_dir.join("rfm").join("recent")));
+            Frecency::load(data_dir.join("rfm").join("history.db"))
+        }
+        Ok(_) => Frecency::default(),
+        Err(e) => {
+            warn!("Directory history is disabled: {e}");
+            Frecency::default()
+        }
+    };
+
+    // Scripts should not touch the clipboard of running instances either
+    let clipboard_dir = std::env::var_os("XDG_RUNTIME_DIR")
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_absolute())
+        .or_else(|| xdg_data_home().ok());
+    let shared_clipboard = match clipboard_dir {
+        Some(dir) if !headless => SharedClipboard::new(dir.join("rfm").join("clipboard")),
+        _ => SharedClipboard::default(),
+    };
+
     let panel_manager = PanelManager::new(
         miller_panels,
-        use_trash,
+        trash_kind,
+        confirm_trash,
+        search_wrap,
+        cursor_wrap,
+        hints,
+        autosize,
+        layout,
+        disk_warning,
         parser,
         dir_rx,
         prev_rx,
+        cache_rx,
+        server_rx,
+        config_rx,
+        config_files,
         logger.clone(),
         opener,
+        frecency,
+        shared_clipboard,
+        system_clipboard,
+        headless,
+        args.choosefile.is_some(),
+        args.readonly,
     )?;
-    let panel_handle = tokio::spawn(panel_manager.run());
+    let panel_handle = match script {
+        Some(steps) => tokio::spawn(panel_manager.run_script(steps)),
+        None => tokio::spawn(panel_manager.run()),
+    };
 
     // If the panel manager returns, we essentially want to shutdown the entire program.
     let panel_result = panel_handle.await;
 
-    // Stop all blocking tasks by setting the shutdown handle to "true":
-    SHUTDOWN_FLAG.store(true, std::sync::atomic::Ordering::Relaxed);
-
-    // The .await here is okay, because the PanelManager dropped the queue sender,
-    // which makes these two guys instantly return:
+    // The PanelManager dropped the queue sender, so we don't need to wait for these two guys
     dir_mngr_handle.abort();
     prev_mngr_handle.abort();
 
     // Be a good citizen, cleanup
-    stdout
-        .queue(EnableLineWrap)?
-        .queue(Clear(ClearType::All))?
-        .queue(LeaveAlternateScreen)?
-        .queue(cursor::RestorePosition)?
-        .queue(cursor::Show)?
-        .flush()?;
-    disable_raw_mode()?;
+    if !headless {
+        stdout
+            .queue(DisableMouseCapture)?
+            .queue(DisableBracketedPaste)?
+            .queue(EnableLineWrap)?
+            .queue(Clear(ClearType::All))?
+            .queue(LeaveAlternateScreen)?
+            .queue(cursor::RestorePosition)?
+            .queue(cursor::Show)?;
+        if status_line().title.is_some() {
+            stdout.queue(Print(POP_TITLE))?;
+        }
+        stdout.flush()?;
+        disable_raw_mode()?;
+    }
+
+    // Stop all background jobs (with the terminal already restored, in case this takes a moment)
+    for description in jobs::shutdown(SHUTDOWN_GRACE_PERIOD).await {
+        eprintln!("Aborted on exit: {description}");
+    }
+
+    // Release all remote directories that were mounted during the session
+    remote::unmount_all();
 
     match panel_result {
         Ok(Ok(close_cmd)) => {
@@ -293,8 +610,10 @@ async fn main() -> anyhow::Result<()> {
                     eprintln!("Error: {} is not a file!", choosedir.display());
                 }
                 if choosedir.exists() && choosedir.is_file() {
-                    let path = match close_cmd {
-                        CloseCmd::QuitWithPath { path } => path,
+                    let path = match &close_cmd {
+                        CloseCmd::QuitWithPath { path } | CloseCmd::QuitWithFiles { path, .. } => {
+                            path.clone()
+                        }
                         _ => starting_path,
                     };
                     // Write output to file
@@ -305,6 +624,17 @@ async fn main() -> anyhow::Result<()> {
                     file.write_all(format!("{}", path.display()).as_bytes())?;
                 }
             }
+            // Nothing is written, if rfm was quit without choosing a file
+            if let (Some(choosefile), CloseCmd::QuitWithFiles { files, .. }) =
+                (args.choosefile, &close_cmd)
+            {
+                let content: String = files
+                    .iter()
+                    .map(|file| format!("{}\n", file.display()))
+                    .collect();
+                std::fs::write(&choosefile, content)
+                    .context(format!("failed to write {}", choosefile.display()))?;
+            }
         }
         Ok(e) => {
             e.context("panel manager returned an error")?;
@@ -372,4 +702,26 @@ mod tests {
         let parsed: Result<Config, _> = toml::from_str(content);
         assert!(parsed.is_ok(), "invalid keys.toml example");
     }
+
+    #[test]
+    fn profile_overrides_config() {
+        let dir = tempfile::tempdir().unwrap();
+        let profile_dir = dir.path().join("work");
+        std::fs::create_dir(&profile_dir).unwrap();
+        let base = dir.path().join("config.toml");
+        std::fs::write(&base, Examples::get("config.toml").unwrap().data).unwrap();
+        std::fs::write(
+            profile_dir.join("config.toml"),
+            "[general]\nuse_trash = true\nstart_dir = \"~/work\"\n",
+        )
+        .unwrap();
+
+        let content = read_with_profile(&base, Some(&profile_dir)).unwrap();
+        let config: Config = toml::from_str(&content).expect("merged config must be valid");
+        assert!(config.general.use_trash);
+        assert_eq!(config.general.start_dir.as_deref(), Some("~/work"));
+        // Unset values are taken from the base config
+        assert_eq!(config.general.sort, Some(config::SortMode::Lowercase));
+        assert_eq!(config.cache.depth, 2);
+    }
 }
@@ -1,19 +1,61 @@
 use anyhow::Context;
 use crossterm::event::{KeyCode, KeyEvent};
+use once_cell::sync::OnceCell;
 use patricia_tree::PatriciaSet;
 use std::{
     io::{BufRead, BufReader},
+    os::unix::fs::MetadataExt,
     process::{Command, Stdio},
 };
+use tokio::sync::oneshot::{self, error::TryRecvError};
 
-use super::*;
+use super::{input::Input, *};
 use crate::{
-    config::color::{print_horizontal_bar, print_horz_bot, print_horz_top},
+    config::color::{
+        color_dir_path, color_highlight, color_main, color_marked, print_horizontal_bar,
+        print_horz_bot, print_horz_top,
+    },
     content::dir_content,
+    drives::{self, Drive},
+    engine::{
+        actions::{Action, MenuEntry},
+        archive::{ArchiveFormat, ArchivePlan},
+        clipboard::Clipboard,
+        frecency,
+        grep::{ripgrep_installed, GrepSearch, MAX_MATCHES},
+        tags,
+    },
+    jobs::{self, CancelToken, JobInfo, JobKind, Outcome},
+    remote::{self, RemoteLocation},
+    util::{
+        extended_attributes, file_size_str, fuzzy_score, timestamp_str, ExactWidth, TransferPlan,
+    },
 };
 
 pub enum ConsoleOp {
     Cd(PathBuf),
+    /// Change to the given directory and close the console
+    Open(PathBuf),
+    /// Execute the (accepted) copy or move operation and close the console
+    Transfer(TransferPlan),
+    /// Create the archive and close the console
+    Archive(ArchivePlan),
+    /// Extract the archives into the target directory and close the console
+    Extract {
+        archives: Vec<PathBuf>,
+        target: PathBuf,
+    },
+    /// Execute the chosen action of the context menu and close the console
+    Action(Action),
+    /// Paste the items of the chosen clipboard and close the console
+    Paste(Clipboard),
+    /// Select the file (in its directory) and close the console
+    Select(PathBuf),
+    /// Select the file and show the given line in the preview, then close the console
+    ShowLine {
+        path: PathBuf,
+        line: usize,
+    },
     None,
     Exit,
 }
@@ -24,6 +66,31 @@ pub enum ConsoleOp {
 pub trait Console: Draw + Send + Sync {
     /// Inserts the given key to the console
     fn handle_key(&mut self, key_event: KeyEvent) -> ConsoleOp;
+
+    /// Returns true if the console shows data that changes by itself,
+    /// so it has to be redrawn regularly
+    fn needs_refresh(&self) -> bool {
+        false
+    }
+
+    /// Returns the result of work, that the console has finished in the background.
+    ///
+    /// Called regularly, as long as [`Console::needs_refresh`] returns true.
+    fn poll(&mut self) -> ConsoleOp {
+        ConsoleOp::None
+    }
+}
+
+/// Runs a blocking operation (like mounting a drive) in the background,
+/// so that the ui does not freeze. The result can be received, once it is finished.
+fn in_background<T: Send + 'static>(
+    op: impl FnOnce() -> anyhow::Result<T> + Send + 'static,
+) -> oneshot::Receiver<anyhow::Result<T>> {
+    let (tx, rx) = oneshot::channel();
+    tokio::task::spawn_blocking(move || {
+        let _ = tx.send(op());
+    });
+    rx
 }
 
 /// Input console for our custom `cd` mode
@@ -340,8 +407,94 @@ impl Console for DirConsole {
     }
 }
 
-#[derive(Default)]
-pub struct Zoxide {
+/// Console that asks for the directory, into which the archives are extracted.
+///
+/// The directory is chosen like in the `cd` mode (with the same completion),
+/// but the panels stay where they are. A name that does not exist yet is created.
+pub struct ExtractConsole {
+    archives: Vec<PathBuf>,
+    dir_console: DirConsole,
+}
+
+impl ExtractConsole {
+    pub fn from_panel(panel: &DirPanel, archives: Vec<PathBuf>) -> Self {
+        ExtractConsole {
+            archives,
+            dir_console: DirConsole::from_panel(panel),
+        }
+    }
+
+    fn target(&self) -> PathBuf {
+        self.dir_console.path.join(&self.dir_console.input)
+    }
+}
+
+impl Draw for ExtractConsole {
+    fn draw(
+        &mut self,
+        stdout: &mut Stdout,
+        x_range: Range<u16>,
+        y_range: Range<u16>,
+    ) -> Result<()> {
+        let width = x_range.end.saturating_sub(x_range.start) as usize;
+        let header = match self.archives.as_slice() {
+            [archive] => format!(
+                " extract {} to:",
+                archive.file_name().unwrap_or_default().to_string_lossy()
+            ),
+            archives => format!(" extract {} archives to:", archives.len()),
+        };
+        queue!(
+            stdout,
+            cursor::MoveTo(x_range.start, y_range.start),
+            PrintStyledContent(header.exact_width(width).bold()),
+        )?;
+        self.dir_console.draw(stdout, x_range, y_range)
+    }
+}
+
+impl Console for ExtractConsole {
+    fn handle_key(&mut self, key_event: KeyEvent) -> ConsoleOp {
+        if key_event.code == KeyCode::Enter {
+            return ConsoleOp::Extract {
+                archives: self.archives.clone(),
+                target: self.target(),
+            };
+        }
+        // The console only changes its own directory, not the one of the panels
+        match self.dir_console.handle_key(key_event) {
+            ConsoleOp::Exit => ConsoleOp::Exit,
+            _ => ConsoleOp::None,
+        }
+    }
+}
+
+/// Returns true if `zoxide` is installed on the system.
+pub fn zoxide_installed() -> bool {
+    static ZOXIDE_INSTALLED: OnceCell<bool> = OnceCell::new();
+    *ZOXIDE_INSTALLED.get_or_init(|| {
+        Command::new("zoxide")
+            .arg("--version")
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .map(|s| s.success())
+            .unwrap_or_default()
+    })
+}
+
+/// Where the [`QueryConsole`] gets its results from.
+pub enum QuerySource {
+    /// The external `zoxide` binary
+    Zoxide,
+    /// rfm's own directory history, ordered by frecency
+    Frecency(Vec<PathBuf>),
+}
+
+/// Console that jumps to the best match for some keywords
+pub struct QueryConsole {
+    source: QuerySource,
     starting_path: PathBuf,
     input: String,
     path: String,
@@ -349,11 +502,12 @@ pub struct Zoxide {
     opt_idx: usize,
 }
 
-impl Zoxide {
-    pub fn from_panel(panel: &DirPanel) -> Self {
+impl QueryConsole {
+    pub fn from_panel(panel: &DirPanel, source: QuerySource) -> Self {
         let path = ".".to_string();
         let starting_path = panel.path().to_path_buf();
-        Zoxide {
+        QueryConsole {
+            source,
             starting_path,
             input: String::new(),
             path,
@@ -362,25 +516,52 @@ impl Zoxide {
         }
     }
 
-    fn query_zoxide(&mut self) -> anyhow::Result<()> {
-        let mut handle = Command::new("zoxide")
-            .arg("query")
-            .arg("-l")
-            .args(self.input.split_ascii_whitespace())
-            .stdin(Stdio::piped())
-            .stdout(Stdio::piped())
-            .spawn()?;
-
-        let stdout = handle
-            .stdout
-            .take()
-            .context("could not get stdout of child process")?;
-        self.options = BufReader::new(stdout).lines().flatten().collect();
+    fn query(&mut self) -> anyhow::Result<()> {
+        self.options = query(&self.source, &self.input)?;
         Ok(())
     }
 }
 
-impl Draw for Zoxide {
+/// Queries zoxide (or rfm's own history) with the keywords of the input.
+///
+/// Returns the matching directories, the best match first.
+fn query(source: &QuerySource, input: &str) -> anyhow::Result<Vec<String>> {
+    match source {
+        QuerySource::Zoxide => query_zoxide(input),
+        QuerySource::Frecency(ranked) => {
+            let keywords: Vec<&str> = input.split_ascii_whitespace().collect();
+            Ok(ranked
+                .iter()
+                .filter(|path| frecency::matches(path, &keywords))
+                .map(|path| path.display().to_string())
+                .collect())
+        }
+    }
+}
+
+fn query_zoxide(input: &str) -> anyhow::Result<Vec<String>> {
+    let mut handle = Command::new("zoxide")
+        .arg("query")
+        .arg("-l")
+        .args(input.split_ascii_whitespace())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()?;
+
+    let stdout = handle
+        .stdout
+        .take()
+        .context("could not get stdout of child process")?;
+    let options = BufReader::new(stdout)
+        .lines()
+        .map_while(|l| l.ok())
+        .collect();
+    // Don't leave a zombie behind for every keystroke
+    let _ = handle.wait();
+    Ok(options)
+}
+
+impl Draw for QueryConsole {
     fn draw(
         &mut self,
         stdout: &mut Stdout,
@@ -444,7 +625,7 @@ impl Draw for Zoxide {
     }
 }
 
-impl Console for Zoxide {
+impl Console for QueryConsole {
     fn handle_key(&mut self, key_event: KeyEvent) -> ConsoleOp {
         match key_event.code {
             KeyCode::Backspace => {
@@ -475,7 +656,7 @@ impl Console for Zoxide {
             _ => (),
         }
 
-        let result = self.query_zoxide();
+        let result = self.query();
 
         match result {
             Ok(_) => {
@@ -515,3 +696,2285 @@ impl Console for Zoxide {
         ConsoleOp::None
     }
 }
+
+/// Maximum number of zoxide (or history) results in the [`JumpConsole`]
+const MAX_QUERY_RESULTS: usize = 32;
+
+/// Group of an entry in the [`JumpConsole`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum JumpGroup {
+    /// Subdirectory of the current directory
+    Subdir,
+    /// Result of zoxide (or rfm's own history)
+    Query,
+}
+
+/// Console that filters the subdirectories of the current directory and the zoxide database
+/// (or rfm's own history) at the same time.
+///
+/// Both groups are listed while typing. `tab` cycles through the merged list (subdirectories first)
+/// and `enter` jumps to the selected directory.
+pub struct JumpConsole {
+    dir: PathBuf,
+    /// Names of the subdirectories of `dir`
+    subdirs: Vec<String>,
+    source: QuerySource,
+    input: String,
+    entries: Vec<(JumpGroup, PathBuf)>,
+    selected: usize,
+    error: Option<String>,
+    /// Number of rows that fit on the screen (updated when drawn)
+    page: usize,
+}
+
+impl JumpConsole {
+    pub fn from_panel(panel: &DirPanel, source: QuerySource) -> Self {
+        let subdirs = panel
+            .elements()
+            .filter(|item| item.path().is_dir() && (panel.show_hidden() || !item.is_hidden()))
+            .map(|item| item.name().clone())
+            .collect();
+        let mut console = JumpConsole {
+            dir: panel.path().to_path_buf(),
+            subdirs,
+            source,
+            input: String::new(),
+            entries: Vec::new(),
+            selected: 0,
+            error: None,
+            page: 1,
+        };
+        console.update();
+        console
+    }
+
+    /// Filters both groups with the current input.
+    fn update(&mut self) {
+        self.selected = 0;
+        self.error = None;
+        self.entries = rank_subdirs(&self.subdirs, &self.input)
+            .into_iter()
+            .map(|name| (JumpGroup::Subdir, self.dir.join(name)))
+            .collect();
+        // Without keywords, zoxide would list its whole database
+        if self.input.trim().is_empty() {
+            return;
+        }
+        match query(&self.source, &self.input) {
+            Ok(results) => {
+                let results: Vec<PathBuf> = results
+                    .into_iter()
+                    .map(PathBuf::from)
+                    .filter(|path| {
+                        *path != self.dir && !self.entries.iter().any(|(_, p)| p == path)
+                    })
+                    .take(MAX_QUERY_RESULTS)
+                    .collect();
+                self.entries
+                    .extend(results.into_iter().map(|path| (JumpGroup::Query, path)));
+            }
+            Err(e) => self.error = Some(format!("failed to execute zoxide: {e}")),
+        }
+    }
+
+    fn select_by(&mut self, step: isize) {
+        if self.entries.is_empty() {
+            return;
+        }
+        let len = self.entries.len() as isize;
+        self.selected = (self.selected as isize + step).rem_euclid(len) as usize;
+    }
+}
+
+/// Returns the names, that contain all keywords of the input (ignoring case).
+///
+/// Names that start with the first keyword come first, the rest is sorted alphabetically.
+fn rank_subdirs<'a>(names: &'a [String], input: &str) -> Vec<&'a String> {
+    let keywords: Vec<String> = input.split_whitespace().map(str::to_lowercase).collect();
+    let mut matches: Vec<&String> = names
+        .iter()
+        .filter(|name| {
+            let name = name.to_lowercase();
+            keywords.iter().all(|k| name.contains(k.as_str()))
+        })
+        .collect();
+    matches.sort_by_cached_key(|name| {
+        let name = name.to_lowercase();
+        let prefix = keywords
+            .first()
+            .is_some_and(|k| name.starts_with(k.as_str()));
+        (!prefix, name)
+    });
+    matches
+}
+
+impl Draw for JumpConsole {
+    fn draw(
+        &mut self,
+        stdout: &mut Stdout,
+        x_range: Range<u16>,
+        y_range: Range<u16>,
+    ) -> Result<()> {
+        let width = x_range.end.saturating_sub(x_range.start) as usize;
+        let height = y_range.end.saturating_sub(y_range.start);
+        // input, two separators and the status line
+        if height < 5 {
+            return Ok(());
+        }
+        self.page = height.saturating_sub(4) as usize;
+
+        for x in x_range.clone() {
+            queue!(
+                stdout,
+                cursor::MoveTo(x, y_range.start + 1),
+                print_horizontal_bar(),
+                cursor::MoveTo(x, y_range.end - 2),
+                print_horizontal_bar(),
+            )?;
+        }
+
+        let n_subdirs = self
+            .entries
+            .iter()
+            .filter(|(group, _)| *group == JumpGroup::Subdir)
+            .count();
+        let status = match &self.error {
+            Some(error) => format!(" {error}"),
+            None => format!(
+                " {n_subdirs} subdirectories, {} from {}, tab to cycle, enter to jump",
+                self.entries.len() - n_subdirs,
+                match self.source {
+                    QuerySource::Zoxide => "zoxide",
+                    QuerySource::Frecency(_) => "history",
+                },
+            ),
+        };
+        queue!(
+            stdout,
+            cursor::MoveTo(x_range.start, y_range.end - 1),
+            PrintStyledContent(status.exact_width(width).dark_grey()),
+        )?;
+
+        // Every group starts with a heading
+        let mut rows: Vec<Option<usize>> = Vec::with_capacity(self.entries.len() + 2);
+        for (idx, (group, _)) in self.entries.iter().enumerate() {
+            if idx == 0 || self.entries[idx - 1].0 != *group {
+                rows.push(None);
+            }
+            rows.push(Some(idx));
+        }
+        let selected_row = rows
+            .iter()
+            .position(|row| *row == Some(self.selected))
+            .unwrap_or_default();
+        let scroll = selected_row.saturating_sub(self.page.saturating_sub(1));
+        let mut rows = rows.into_iter().skip(scroll);
+        for y in y_range.start + 2..y_range.end - 2 {
+            queue!(stdout, cursor::MoveTo(x_range.start, y))?;
+            let line = match rows.next() {
+                Some(Some(idx)) => {
+                    let (group, path) = &self.entries[idx];
+                    let line = match group {
+                        JumpGroup::Subdir => format!(
+                            "   {}/",
+                            path.strip_prefix(&self.dir).unwrap_or(path).display()
+                        ),
+                        JumpGroup::Query => format!("   {}", path.display()),
+                    };
+                    let line = line.exact_width(width).with(color_dir_path());
+                    if idx == self.selected {
+                        line.reverse()
+                    } else {
+                        line
+                    }
+                }
+                Some(None) => {
+                    let next = rows.clone().next().flatten().unwrap_or_default();
+                    let heading = match self.entries[next].0 {
+                        JumpGroup::Subdir => format!(" in {}", self.dir.display()),
+                        JumpGroup::Query => match self.source {
+                            QuerySource::Zoxide => " zoxide".to_string(),
+                            QuerySource::Frecency(_) => " history".to_string(),
+                        },
+                    };
+                    heading.exact_width(width).with(color_main()).bold()
+                }
+                None => " ".repeat(width).stylize(),
+            };
+            queue!(stdout, PrintStyledContent(line))?;
+        }
+
+        let line = format!(" cd {}", self.input);
+        queue!(
+            stdout,
+            cursor::MoveTo(x_range.start, y_range.start),
+            PrintStyledContent(line.exact_width(width).bold()),
+        )?;
+        let x_cursor = unicode_display_width::width(&line) as u16;
+        queue!(
+            stdout,
+            cursor::MoveTo(x_range.start.saturating_add(x_cursor), y_range.start),
+            cursor::Show,
+            cursor::SetCursorStyle::DefaultUserShape,
+            cursor::EnableBlinking,
+        )?;
+        Ok(())
+    }
+}
+
+impl Console for JumpConsole {
+    fn handle_key(&mut self, key_event: KeyEvent) -> ConsoleOp {
+        match key_event.code {
+            KeyCode::Enter => {
+                return match self.entries.get(self.selected) {
+                    Some((_, path)) => ConsoleOp::Open(path.clone()),
+                    None => ConsoleOp::Exit,
+                };
+            }
+            KeyCode::Tab | KeyCode::Down => self.select_by(1),
+            KeyCode::BackTab | KeyCode::Up => self.select_by(-1),
+            KeyCode::Backspace => {
+                self.input.pop();
+                self.update();
+            }
+            KeyCode::Char(c) => {
+                self.input.push(c);
+                self.update();
+            }
+            _ => (),
+        }
+        ConsoleOp::None
+    }
+}
+
+/// Console that picks one of the recently opened files with a fuzzy search (see [`fuzzy_score`]).
+///
+/// `tab` cycles through the matches and `enter` jumps to the directory of the selected file.
+/// With [`RecentConsole::history`], it lists the visited directories instead,
+/// and with [`RecentConsole::tagged`] all tagged items.
+pub struct RecentConsole {
+    kind: RecentKind,
+    files: Vec<PathBuf>,
+    /// Note that is shown right of each item (the time of the visit, or the tags)
+    notes: Vec<String>,
+    input: String,
+    matches: Vec<usize>,
+    selected: usize,
+    /// Number of rows that fit on the screen (updated when drawn)
+    page: usize,
+}
+
+/// What a [`RecentConsole`] lists
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RecentKind {
+    Files,
+    Directories,
+    Tagged,
+}
+
+impl RecentConsole {
+    pub fn new(files: Vec<PathBuf>) -> Self {
+        Self::with_notes(RecentKind::Files, files, Vec::new())
+    }
+
+    fn with_notes(kind: RecentKind, files: Vec<PathBuf>, notes: Vec<String>) -> Self {
+        let mut console = RecentConsole {
+            kind,
+            files,
+            notes,
+            input: String::new(),
+            matches: Vec::new(),
+            selected: 0,
+            page: 1,
+        };
+        console.update();
+        console
+    }
+
+    /// Lists the visited directories, the most recent one first.
+    pub fn history(dirs: Vec<(PathBuf, SystemTime)>) -> Self {
+        let (files, notes) = dirs
+            .into_iter()
+            .map(|(dir, visited)| (dir, ago(visited)))
+            .unzip();
+        Self::with_notes(RecentKind::Directories, files, notes)
+    }
+
+    /// Lists the tagged items, the search also matches the names of their tags.
+    pub fn tagged(items: Vec<(PathBuf, Vec<String>)>) -> Self {
+        let (files, notes) = items
+            .into_iter()
+            .map(|(path, tags)| (path, tags.join(", ")))
+            .unzip();
+        Self::with_notes(RecentKind::Tagged, files, notes)
+    }
+
+    fn update(&mut self) {
+        self.selected = 0;
+        let mut scored: Vec<(usize, i64)> = self
+            .files
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, path)| {
+                let text = match self.kind {
+                    RecentKind::Tagged => format!("{} {}", self.notes[idx], path.display()),
+                    _ => path.to_string_lossy().to_string(),
+                };
+                Some((idx, fuzzy_score(&self.input, &text)?))
+            })
+            .collect();
+        // The sort is stable, so equal scores keep the most recent file first
+        scored.sort_by_key(|(_, score)| std::cmp::Reverse(*score));
+        self.matches = scored.into_iter().map(|(idx, _)| idx).collect();
+    }
+
+    fn select_by(&mut self, step: isize) {
+        if self.matches.is_empty() {
+            return;
+        }
+        let len = self.matches.len() as isize;
+        self.selected = (self.selected as isize + step).rem_euclid(len) as usize;
+    }
+}
+
+impl Draw for RecentConsole {
+    fn draw(
+        &mut self,
+        stdout: &mut Stdout,
+        x_range: Range<u16>,
+        y_range: Range<u16>,
+    ) -> Result<()> {
+        let width = x_range.end.saturating_sub(x_range.start) as usize;
+        let height = y_range.end.saturating_sub(y_range.start);
+        // input, two separators and the status line
+        if height < 5 {
+            return Ok(());
+        }
+        self.page = height.saturating_sub(4) as usize;
+
+        for x in x_range.clone() {
+            queue!(
+                stdout,
+                cursor::MoveTo(x, y_range.start + 1),
+                print_horizontal_bar(),
+                cursor::MoveTo(x, y_range.end - 2),
+                print_horizontal_bar(),
+            )?;
+        }
+        let status = format!(
+            " {} of {} {}, tab to cycle, enter to jump",
+            self.matches.len(),
+            self.files.len(),
+            match self.kind {
+                RecentKind::Files => "recent files",
+                RecentKind::Directories => "visited directories",
+                RecentKind::Tagged => "tagged items",
+            }
+        );
+        queue!(
+            stdout,
+            cursor::MoveTo(x_range.start, y_range.end - 1),
+            PrintStyledContent(status.exact_width(width).dark_grey()),
+        )?;
+
+        let scroll = self.selected.saturating_sub(self.page.saturating_sub(1));
+        let mut rows = self.matches.iter().enumerate().skip(scroll);
+        for y in y_range.start + 2..y_range.end - 2 {
+            queue!(stdout, cursor::MoveTo(x_range.start, y))?;
+            let Some((idx, file)) = rows.next() else {
+                queue!(stdout, Print(" ".repeat(width)))?;
+                continue;
+            };
+            let path = &self.files[*file];
+            let dir = path
+                .parent()
+                .map(|p| format!("{}/", p.display()))
+                .unwrap_or_default();
+            let name = path
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_default();
+            let note = self
+                .notes
+                .get(*file)
+                .map(|note| format!(" {note} "))
+                .unwrap_or_default();
+            let note_width = (unicode_display_width::width(&note) as usize).min(width);
+            let dir_width =
+                (unicode_display_width::width(&dir) as usize + 1).min(width - note_width);
+            let dir = format!(" {dir}")
+                .exact_width(dir_width)
+                .with(color_dir_path());
+            let name = name.exact_width(width - dir_width - note_width).bold();
+            let note = note.exact_width(note_width).dark_grey();
+            if idx == self.selected {
+                queue!(
+                    stdout,
+                    PrintStyledContent(dir.reverse()),
+                    PrintStyledContent(name.reverse()),
+                    PrintStyledContent(note.reverse())
+                )?;
+            } else {
+                queue!(
+                    stdout,
+                    PrintStyledContent(dir),
+                    PrintStyledContent(name),
+                    PrintStyledContent(note)
+                )?;
+            }
+        }
+
+        let prompt = match self.kind {
+            RecentKind::Files => "open",
+            RecentKind::Directories | RecentKind::Tagged => "go to",
+        };
+        let line = format!(" {prompt} {}", self.input);
+        queue!(
+            stdout,
+            cursor::MoveTo(x_range.start, y_range.start),
+            PrintStyledContent(line.exact_width(width).bold()),
+        )?;
+        let x_cursor = unicode_display_width::width(&line) as u16;
+        queue!(
+            stdout,
+            cursor::MoveTo(x_range.start.saturating_add(x_cursor), y_range.start),
+            cursor::Show,
+            cursor::SetCursorStyle::DefaultUserShape,
+            cursor::EnableBlinking,
+        )?;
+        Ok(())
+    }
+}
+
+impl Console for RecentConsole {
+    fn handle_key(&mut self, key_event: KeyEvent) -> ConsoleOp {
+        match key_event.code {
+            KeyCode::Enter => {
+                let path = self.matches.get(self.selected).map(|idx| &self.files[*idx]);
+                return match path {
+                    Some(dir) if self.kind == RecentKind::Directories => {
+                        ConsoleOp::Open(dir.clone())
+                    }
+                    Some(file) => ConsoleOp::Select(file.clone()),
+                    None => ConsoleOp::Exit,
+                };
+            }
+            KeyCode::Tab | KeyCode::Down => self.select_by(1),
+            KeyCode::BackTab | KeyCode::Up => self.select_by(-1),
+            KeyCode::Backspace => {
+                self.input.pop();
+                self.update();
+            }
+            KeyCode::Char(c) => {
+                self.input.push(c);
+                self.update();
+            }
+            _ => (),
+        }
+        ConsoleOp::None
+    }
+}
+
+/// Console to connect to a remote host
+///
+/// The user enters `[user@]host:[path]`, which is then mounted via SFTP (see [`remote::mount`]).
+#[derive(Default)]
+pub struct RemoteConsole {
+    input: String,
+    message: String,
+    /// Mount that is still running (connecting may take a while)
+    connecting: Option<oneshot::Receiver<anyhow::Result<PathBuf>>>,
+}
+
+impl Draw for RemoteConsole {
+    fn draw(
+        &mut self,
+        stdout: &mut Stdout,
+        x_range: Range<u16>,
+        y_range: Range<u16>,
+    ) -> Result<()> {
+        let width = x_range.end.saturating_sub(x_range.start);
+        let height = y_range.end.saturating_sub(y_range.start);
+
+        let x_start = x_range.start;
+        let y_center = y_range.end.saturating_add(y_range.start) / 2;
+
+        let div_left = 0;
+        let div_center = width / 8;
+        let div_right = width / 2;
+
+        let message = if self.connecting.is_some() {
+            format!("connecting to {} ...", self.input).dark_grey()
+        } else if self.message.is_empty() {
+            "[user@]host:[path]".to_string().dark_grey()
+        } else {
+            self.message.clone().red()
+        };
+        let text_len = unicode_display_width::width(&self.input) as u16;
+        let message_len = unicode_display_width::width(message.content()) as u16;
+        let input_offset = width.saturating_sub(text_len).saturating_sub(1) / 2;
+        let message_offset = width.saturating_sub(message_len) / 2;
+
+        if height >= 3 {
+            for x in x_range {
+                let (top, bot) = if x == div_left || x == div_center || x == div_right {
+                    (print_horz_top(), print_horz_bot())
+                } else {
+                    (print_horizontal_bar(), print_horizontal_bar())
+                };
+                queue!(
+                    stdout,
+                    cursor::MoveTo(x, y_center.saturating_sub(1)),
+                    top,
+                    cursor::MoveTo(x, y_center.saturating_add(2)),
+                    bot,
+                )?;
+            }
+        }
+        queue!(
+            stdout,
+            cursor::MoveTo(x_start.saturating_add(message_offset), y_center + 1),
+            Clear(ClearType::CurrentLine),
+            PrintStyledContent(message),
+            cursor::MoveTo(x_start.saturating_add(input_offset), y_center),
+            Clear(ClearType::CurrentLine),
+            PrintStyledContent(self.input.clone().green()),
+            cursor::Show,
+            cursor::SetCursorStyle::DefaultUserShape,
+            cursor::EnableBlinking,
+        )?;
+        Ok(())
+    }
+}
+
+impl Console for RemoteConsole {
+    fn handle_key(&mut self, key_event: KeyEvent) -> ConsoleOp {
+        match key_event.code {
+            _ if self.connecting.is_some() => (),
+            KeyCode::Backspace => {
+                self.input.pop();
+            }
+            KeyCode::Char(c) => self.input.push(c),
+            KeyCode::Enter => {
+                if self.input.is_empty() {
+                    return ConsoleOp::Exit;
+                }
+                match self.input.parse::<RemoteLocation>() {
+                    Ok(location) => {
+                        self.connecting = Some(in_background(move || remote::mount(&location)));
+                    }
+                    Err(e) => {
+                        warn!("Failed to connect to {}: {e}", self.input);
+                        self.message = e.to_string();
+                    }
+                }
+            }
+            _ => (),
+        }
+        ConsoleOp::None
+    }
+
+    fn needs_refresh(&self) -> bool {
+        self.connecting.is_some()
+    }
+
+    fn poll(&mut self) -> ConsoleOp {
+        let Some(rx) = &mut self.connecting else {
+            return ConsoleOp::None;
+        };
+        let result = match rx.try_recv() {
+            Ok(result) => result,
+            Err(TryRecvError::Empty) => return ConsoleOp::None,
+            Err(TryRecvError::Closed) => Err(anyhow::anyhow!("aborted")),
+        };
+        self.connecting = None;
+        match result {
+            Ok(mountpoint) => return ConsoleOp::Open(mountpoint),
+            Err(e) => {
+                warn!("Failed to connect to {}: {e}", self.input);
+                self.message = e.to_string();
+            }
+        }
+        ConsoleOp::None
+    }
+}
+
+/// Console that lists the mounted filesystems and removable drives.
+///
+/// `enter` opens the selected drive (and mounts it, if necessary),
+/// `m` mounts and `u` unmounts a removable drive (requires `udisksctl`).
+pub struct DrivesConsole {
+    drives: Vec<Drive>,
+    selected: usize,
+    message: String,
+    /// Mount or unmount, that is still running (it may wait for a password prompt of polkit)
+    pending: Option<(DriveOp, Drive, oneshot::Receiver<anyhow::Result<PathBuf>>)>,
+}
+
+/// What happens with a drive, once it is mounted or unmounted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DriveOp {
+    Open,
+    Mount,
+    Unmount,
+}
+
+impl Default for DrivesConsole {
+    fn default() -> Self {
+        DrivesConsole {
+            drives: drives::list(),
+            selected: 0,
+            message: String::new(),
+            pending: None,
+        }
+    }
+}
+
+impl DrivesConsole {
+    fn selected(&self) -> Option<&Drive> {
+        self.drives.get(self.selected)
+    }
+
+    /// Reads the list of drives again and keeps the selected device.
+    fn refresh(&mut self) {
+        let device = self.selected().map(|d| d.device.clone());
+        self.drives = drives::list();
+        self.selected = self
+            .drives
+            .iter()
+            .position(|d| Some(&d.device) == device.as_ref())
+            .unwrap_or_default();
+    }
+
+    /// Mounts or unmounts the selected drive in the background.
+    fn start(&mut self, op: DriveOp) {
+        let Some(drive) = self.selected().cloned() else {
+            return;
+        };
+        let rx = {
+            let drive = drive.clone();
+            if op == DriveOp::Unmount {
+                self.message = format!("unmounting {} ...", drive.device);
+                in_background(move || drives::unmount(&drive).map(|()| PathBuf::new()))
+            } else {
+                self.message = format!("mounting {} ...", drive.device);
+                in_background(move || drives::mount(&drive))
+            }
+        };
+        self.pending = Some((op, drive, rx));
+    }
+}
+
+impl Draw for DrivesConsole {
+    fn draw(
+        &mut self,
+        stdout: &mut Stdout,
+        x_range: Range<u16>,
+        y_range: Range<u16>,
+    ) -> Result<()> {
+        let width = x_range.end.saturating_sub(x_range.start) as usize;
+        let height = y_range.end.saturating_sub(y_range.start);
+        // header, two separators and the help line
+        if height < 5 {
+            return Ok(());
+        }
+        let page = height.saturating_sub(4) as usize;
+        let scroll = self.selected.saturating_sub(page.saturating_sub(1));
+
+        let help = if self.message.is_empty() {
+            " enter: open, m: mount, u: unmount, q: quit"
+                .exact_width(width)
+                .dark_grey()
+        } else {
+            format!(" {}", self.message).exact_width(width).red()
+        };
+        queue!(
+            stdout,
+            cursor::Hide,
+            cursor::MoveTo(x_range.start, y_range.start),
+            PrintStyledContent(" Drives".exact_width(width).bold()),
+            cursor::MoveTo(x_range.start, y_range.end - 1),
+            PrintStyledContent(help),
+        )?;
+        for x in x_range.clone() {
+            queue!(
+                stdout,
+                cursor::MoveTo(x, y_range.start + 1),
+                print_horizontal_bar(),
+                cursor::MoveTo(x, y_range.end - 2),
+                print_horizontal_bar(),
+            )?;
+        }
+
+        let name_width = width / 2;
+        let mut drives = self.drives.iter().enumerate().skip(scroll);
+        for y in y_range.start + 2..y_range.end - 2 {
+            queue!(stdout, cursor::MoveTo(x_range.start, y))?;
+            let Some((idx, drive)) = drives.next() else {
+                queue!(stdout, Print(" ".repeat(width)))?;
+                continue;
+            };
+            let name = format!(" {}", drive.name()).exact_width(name_width);
+            let info = format!(" {}  {}  {}", drive.device, drive.fstype, drive.size)
+                .exact_width(width.saturating_sub(name_width));
+            let name = if drive.mountpoint.is_none() {
+                name.dark_grey()
+            } else if drive.removable {
+                name.with(color_dir_path())
+            } else {
+                name.stylize()
+            };
+            let (name, info) = if idx == self.selected {
+                (name.reverse(), info.reverse())
+            } else {
+                (name, info.dark_grey())
+            };
+            queue!(stdout, PrintStyledContent(name), PrintStyledContent(info))?;
+        }
+        Ok(())
+    }
+}
+
+impl Console for DrivesConsole {
+    fn handle_key(&mut self, key_event: KeyEvent) -> ConsoleOp {
+        let last = self.drives.len().saturating_sub(1);
+        match key_event.code {
+            KeyCode::Down | KeyCode::Char('j') => self.selected = (self.selected + 1).min(last),
+            KeyCode::Up | KeyCode::Char('k') => self.selected = self.selected.saturating_sub(1),
+            // Only one mount or unmount at a time
+            _ if self.pending.is_some() => (),
+            KeyCode::Enter | KeyCode::Char('l') => self.start(DriveOp::Open),
+            KeyCode::Char('m') => self.start(DriveOp::Mount),
+            KeyCode::Char('u') => self.start(DriveOp::Unmount),
+            KeyCode::Char('q') => return ConsoleOp::Exit,
+            _ => (),
+        }
+        ConsoleOp::None
+    }
+
+    fn needs_refresh(&self) -> bool {
+        self.pending.is_some()
+    }
+
+    fn poll(&mut self) -> ConsoleOp {
+        let Some((op, drive, rx)) = &mut self.pending else {
+            return ConsoleOp::None;
+        };
+        let result = match rx.try_recv() {
+            Ok(result) => result,
+            Err(TryRecvError::Empty) => return ConsoleOp::None,
+            Err(TryRecvError::Closed) => Err(anyhow::anyhow!("aborted")),
+        };
+        let (op, drive) = (*op, drive.clone());
+        self.pending = None;
+        match (op, result) {
+            (DriveOp::Open, Ok(mountpoint)) => return ConsoleOp::Open(mountpoint),
+            (DriveOp::Mount, Ok(mountpoint)) => {
+                self.message = format!("mounted at {}", mountpoint.display());
+                self.refresh();
+            }
+            (DriveOp::Unmount, Ok(_)) => {
+                self.message = format!("unmounted {}", drive.device);
+                self.refresh();
+            }
+            (DriveOp::Unmount, Err(e)) => {
+                warn!("Failed to unmount {}: {e}", drive.device);
+                self.message = e.to_string();
+            }
+            (_, Err(e)) => {
+                warn!("Failed to mount {}: {e}", drive.device);
+                self.message = e.to_string();
+            }
+        }
+        ConsoleOp::None
+    }
+}
+
+/// Console that lists what a paste operation is going to do, before it is executed.
+///
+/// The operation is executed with `y` or `enter` and aborted with `n`, `q` or `escape`.
+pub struct TransferConsole {
+    plan: TransferPlan,
+    destination: PathBuf,
+    scroll: usize,
+    /// Number of items that fit on the screen (updated when drawn)
+    page: usize,
+}
+
+impl TransferConsole {
+    pub fn new(plan: TransferPlan, destination: PathBuf) -> Self {
+        TransferConsole {
+            plan,
+            destination,
+            scroll: 0,
+            page: 1,
+        }
+    }
+
+    fn scroll_by(&mut self, step: isize) {
+        let max_scroll = self.plan.items.len().saturating_sub(self.page);
+        self.scroll = self.scroll.saturating_add_signed(step).min(max_scroll);
+    }
+}
+
+impl Draw for TransferConsole {
+    fn draw(
+        &mut self,
+        stdout: &mut Stdout,
+        x_range: Range<u16>,
+        y_range: Range<u16>,
+    ) -> Result<()> {
+        let width = x_range.end.saturating_sub(x_range.start) as usize;
+        let height = y_range.end.saturating_sub(y_range.start);
+        // header, two separators and the help line
+        if height < 5 {
+            return Ok(());
+        }
+        self.page = height.saturating_sub(4) as usize;
+        self.scroll_by(0);
+
+        let header = format!(
+            " {} {} items to {} ({} renamed)",
+            if self.plan.cut { "move" } else { "copy" },
+            self.plan.items.len(),
+            self.destination.display(),
+            self.plan.renamed(),
+        );
+        let help = format!(
+            " [y]es / [n]o, j/k to scroll ({}-{} of {})",
+            (self.scroll + 1).min(self.plan.items.len()),
+            (self.scroll + self.page).min(self.plan.items.len()),
+            self.plan.items.len()
+        );
+        queue!(
+            stdout,
+            cursor::Hide,
+            cursor::MoveTo(x_range.start, y_range.start),
+            PrintStyledContent(header.exact_width(width).bold()),
+            cursor::MoveTo(x_range.start, y_range.end - 1),
+            PrintStyledContent(help.exact_width(width).dark_grey()),
+        )?;
+        for x in x_range.clone() {
+            queue!(
+                stdout,
+                cursor::MoveTo(x, y_range.start + 1),
+                print_horizontal_bar(),
+                cursor::MoveTo(x, y_range.end - 2),
+                print_horizontal_bar(),
+            )?;
+        }
+
+        let mut items = self.plan.items.iter().skip(self.scroll);
+        for y in y_range.start + 2..y_range.end - 2 {
+            queue!(stdout, cursor::MoveTo(x_range.start, y))?;
+            let Some((from, to)) = items.next() else {
+                queue!(stdout, Print(" ".repeat(width)))?;
+                continue;
+            };
+            let name = to
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_default();
+            let name_width = unicode_display_width::width(&name) as usize;
+            let source = format!(" {} -> ", from.display());
+            let source_width = width.saturating_sub(name_width.min(width / 2));
+            let name = name.exact_width(width - source_width);
+            let name = if from == to {
+                name.dark_grey()
+            } else if from.file_name() != to.file_name() {
+                name.with(color_marked())
+            } else {
+                name.stylize()
+            };
+            queue!(
+                stdout,
+                Print(source.exact_width(source_width)),
+                PrintStyledContent(name),
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl Console for TransferConsole {
+    fn handle_key(&mut self, key_event: KeyEvent) -> ConsoleOp {
+        match key_event.code {
+            KeyCode::Enter | KeyCode::Char('y') => {
+                return ConsoleOp::Transfer(self.plan.clone());
+            }
+            KeyCode::Char('n') | KeyCode::Char('q') => return ConsoleOp::Exit,
+            KeyCode::Down | KeyCode::Char('j') => self.scroll_by(1),
+            KeyCode::Up | KeyCode::Char('k') => self.scroll_by(-1),
+            KeyCode::PageDown => self.scroll_by(self.page as isize),
+            KeyCode::PageUp => self.scroll_by(-(self.page as isize)),
+            _ => (),
+        }
+        ConsoleOp::None
+    }
+}
+
+/// Context menu with the actions for the selected item.
+///
+/// An entry is chosen with `enter` or directly with its key.
+pub struct MenuConsole {
+    /// Name of the selected item
+    title: String,
+    entries: Vec<MenuEntry>,
+    selected: usize,
+}
+
+impl MenuConsole {
+    pub fn new(title: String, entries: Vec<MenuEntry>) -> Self {
+        MenuConsole {
+            title,
+            entries,
+            selected: 0,
+        }
+    }
+}
+
+/// Draws a box with a title and a list of lines (with the selected one highlighted)
+/// in the middle of the panels.
+fn draw_menu_box(
+    stdout: &mut Stdout,
+    x_range: Range<u16>,
+    y_range: Range<u16>,
+    title: &str,
+    lines: &[String],
+    selected: usize,
+) -> Result<()> {
+    let width = x_range.end.saturating_sub(x_range.start);
+    let height = y_range.end.saturating_sub(y_range.start);
+
+    let line_width = lines
+        .iter()
+        .map(|line| unicode_display_width::width(line) as u16)
+        .chain(std::iter::once(
+            (unicode_display_width::width(title) as u16).saturating_add(4),
+        ))
+        .max()
+        .unwrap_or_default();
+    // border and padding
+    let box_width = line_width.saturating_add(4).min(width);
+    let box_height = (lines.len() as u16).saturating_add(4).min(height);
+    if box_width < 10 || box_height < 5 {
+        return Ok(());
+    }
+    let inner = box_width as usize - 2;
+    let x_start = x_range.start + (width - box_width) / 2;
+    let y_start = y_range.start + (height - box_height) / 2;
+    let y_end = y_start + box_height - 1;
+
+    let border = |s: &'static str| PrintStyledContent(s.with(color_main()).bold());
+    let bar = "─".repeat(inner);
+    queue!(
+        stdout,
+        cursor::Hide,
+        cursor::MoveTo(x_start, y_start),
+        PrintStyledContent(format!("┌{bar}┐").with(color_main()).bold()),
+        cursor::MoveTo(x_start, y_start + 1),
+        border("│"),
+        PrintStyledContent(format!(" {title}").exact_width(inner).bold()),
+        border("│"),
+        cursor::MoveTo(x_start, y_start + 2),
+        PrintStyledContent(format!("├{bar}┤").with(color_main()).bold()),
+        cursor::MoveTo(x_start, y_end),
+        PrintStyledContent(format!("└{bar}┘").with(color_main()).bold()),
+    )?;
+
+    // Scroll, so that the selected entry is always visible
+    let page = box_height as usize - 4;
+    let scroll = selected.saturating_sub(page.saturating_sub(1));
+    let mut lines = lines.iter().enumerate().skip(scroll);
+    for y in y_start + 3..y_end {
+        queue!(stdout, cursor::MoveTo(x_start, y), border("│"))?;
+        match lines.next() {
+            Some((idx, line)) => {
+                let line = line.as_str().exact_width(inner);
+                if idx == selected {
+                    queue!(stdout, PrintStyledContent(line.reverse()))?;
+                } else {
+                    queue!(stdout, Print(line))?;
+                }
+            }
+            None => queue!(stdout, Print(" ".repeat(inner)))?,
+        }
+        queue!(stdout, border("│"))?;
+    }
+    Ok(())
+}
+
+/// Space between the columns of the key popup
+const POPUP_GAP: usize = 3;
+
+/// Number of columns and rows of the key popup, for `n` entries of the given width.
+fn popup_grid(n: usize, entry_width: usize, width: usize, max_rows: usize) -> (usize, usize) {
+    let columns = ((width + POPUP_GAP) / (entry_width + POPUP_GAP)).clamp(1, n.max(1));
+    (columns, n.div_ceil(columns).min(max_rows))
+}
+
+/// Lists how a typed key sequence can continue in a box at the bottom of the panels.
+///
+/// The entries are arranged in columns. If they don't fit into half of the panels,
+/// the last entry shows how many are left out.
+pub fn draw_key_popup(
+    stdout: &mut Stdout,
+    x_range: Range<u16>,
+    y_range: Range<u16>,
+    typed: &str,
+    entries: &[(String, String)],
+) -> Result<()> {
+    let width = x_range.end.saturating_sub(x_range.start) as usize;
+    let height = y_range.end.saturating_sub(y_range.start) as usize;
+    // border and padding
+    let inner = width.saturating_sub(4);
+    let max_rows = (height / 2).saturating_sub(2);
+    if entries.is_empty() || inner < 10 || max_rows == 0 {
+        return Ok(());
+    }
+    let entry_width = entries
+        .iter()
+        .map(|(keys, description)| unicode_display_width::width(&format!("{keys} {description}")))
+        .max()
+        .unwrap_or_default() as usize;
+    let (columns, rows) = popup_grid(entries.len(), entry_width, inner, max_rows);
+    let column_width = (inner + POPUP_GAP) / columns;
+    let hidden = entries.len().saturating_sub(columns * rows);
+
+    let border = |s: &'static str| PrintStyledContent(s.with(color_main()).bold());
+    let title = format!(" {typed} ");
+    let bar = "─".repeat(width.saturating_sub(3 + unicode_display_width::width(&title) as usize));
+    let y_start = y_range.end.saturating_sub(rows as u16 + 2);
+    queue!(
+        stdout,
+        cursor::MoveTo(x_range.start, y_start),
+        border("┌─"),
+        PrintStyledContent(title.with(color_main()).bold().reverse()),
+        PrintStyledContent(format!("{bar}┐").with(color_main()).bold()),
+    )?;
+    for row in 0..rows {
+        queue!(
+            stdout,
+            cursor::MoveTo(x_range.start, y_start + 1 + row as u16),
+            border("│"),
+            Print(" "),
+        )?;
+        let mut used = 0;
+        for column in 0..columns {
+            let text_width = column_width
+                .min(inner.saturating_sub(used))
+                .saturating_sub(POPUP_GAP);
+            used += column_width;
+            // Entries run down the columns
+            let idx = column * rows + row;
+            match entries.get(idx) {
+                _ if hidden > 0 && idx + 1 == columns * rows => {
+                    let more = format!("+{} more", hidden + 1);
+                    queue!(
+                        stdout,
+                        PrintStyledContent(more.exact_width(text_width).dark_grey())
+                    )?;
+                }
+                Some((keys, description)) => {
+                    let keys_width = unicode_display_width::width(keys) as usize;
+                    queue!(
+                        stdout,
+                        PrintStyledContent(keys.as_str().with(color_marked()).bold()),
+                        Print(" "),
+                        PrintStyledContent(
+                            description
+                                .exact_width(text_width.saturating_sub(keys_width + 1))
+                                .dark_grey()
+                        ),
+                    )?;
+                }
+                None => queue!(stdout, Print(" ".repeat(text_width)))?,
+            }
+            if column + 1 < columns {
+                queue!(stdout, Print(" ".repeat(POPUP_GAP)))?;
+            }
+        }
+        let rest = inner.saturating_sub(columns * column_width - POPUP_GAP);
+        queue!(stdout, Print(" ".repeat(rest + 1)), border("│"))?;
+    }
+    queue!(
+        stdout,
+        cursor::MoveTo(x_range.start, y_start + 1 + rows as u16),
+        PrintStyledContent(
+            format!("└{}┘", "─".repeat(width.saturating_sub(2)))
+                .with(color_main())
+                .bold()
+        ),
+    )?;
+    Ok(())
+}
+
+impl Draw for MenuConsole {
+    fn draw(
+        &mut self,
+        stdout: &mut Stdout,
+        x_range: Range<u16>,
+        y_range: Range<u16>,
+    ) -> Result<()> {
+        let lines: Vec<String> = self
+            .entries
+            .iter()
+            .map(|entry| {
+                let key = entry.key.map(String::from).unwrap_or_default();
+                format!(" {key:>1}  {}", entry.label)
+            })
+            .collect();
+        draw_menu_box(stdout, x_range, y_range, &self.title, &lines, self.selected)
+    }
+}
+
+impl Console for MenuConsole {
+    fn handle_key(&mut self, key_event: KeyEvent) -> ConsoleOp {
+        let last = self.entries.len().saturating_sub(1);
+        // The keys of the entries have priority over the navigation keys
+        if let KeyCode::Char(c) = key_event.code {
+            if let Some(entry) = self.entries.iter().find(|e| e.key == Some(c)) {
+                return ConsoleOp::Action(entry.action.clone());
+            }
+        }
+        match key_event.code {
+            KeyCode::Enter => {
+                if let Some(entry) = self.entries.get(self.selected) {
+                    return ConsoleOp::Action(entry.action.clone());
+                }
+            }
+            KeyCode::Down | KeyCode::Char('j') => {
+                self.selected = if self.selected >= last {
+                    0
+                } else {
+                    self.selected + 1
+                };
+            }
+            KeyCode::Up | KeyCode::Char('k') => {
+                self.selected = if self.selected == 0 {
+                    last
+                } else {
+                    self.selected - 1
+                };
+            }
+            KeyCode::Char('q') => return ConsoleOp::Exit,
+            _ => (),
+        }
+        ConsoleOp::None
+    }
+}
+
+/// Lists the current clipboard and the earlier ones, that were replaced by cutting or copying.
+///
+/// The chosen clipboard is pasted into the current directory with `enter` or directly with its number.
+pub struct ClipboardConsole {
+    /// The current clipboard first
+    clipboards: Vec<Clipboard>,
+    selected: usize,
+}
+
+impl ClipboardConsole {
+    pub fn new(clipboards: Vec<Clipboard>) -> Self {
+        ClipboardConsole {
+            clipboards,
+            selected: 0,
+        }
+    }
+}
+
+/// Time, mode and the first few names of the items
+fn clipboard_line(idx: usize, clipboard: &Clipboard) -> String {
+    let key = if idx < 9 {
+        (idx + 1).to_string()
+    } else {
+        String::new()
+    };
+    let time = SystemTime::UNIX_EPOCH + Duration::from_millis(clipboard.time as u64);
+    let mode = if clipboard.cut { "cut" } else { "copy" };
+    let mut names: Vec<String> = clipboard
+        .files
+        .iter()
+        .take(3)
+        .filter_map(|f| f.file_name())
+        .map(|n| n.to_string_lossy().to_string())
+        .collect();
+    if clipboard.files.len() > 3 {
+        names.push("...".to_string());
+    }
+    format!(
+        " {key:>1}  {}  {mode:<4} {:>3} items: {}",
+        timestamp_str(time),
+        clipboard.files.len(),
+        names.join(", ")
+    )
+}
+
+impl Draw for ClipboardConsole {
+    fn draw(
+        &mut self,
+        stdout: &mut Stdout,
+        x_range: Range<u16>,
+        y_range: Range<u16>,
+    ) -> Result<()> {
+        let lines: Vec<String> = self
+            .clipboards
+            .iter()
+            .enumerate()
+            .map(|(idx, clipboard)| clipboard_line(idx, clipboard))
+            .collect();
+        let title = "Clipboards (enter pastes, the current one first)";
+        draw_menu_box(stdout, x_range, y_range, title, &lines, self.selected)
+    }
+}
+
+impl Console for ClipboardConsole {
+    fn handle_key(&mut self, key_event: KeyEvent) -> ConsoleOp {
+        let last = self.clipboards.len().saturating_sub(1);
+        match key_event.code {
+            KeyCode::Enter => {
+                if let Some(clipboard) = self.clipboards.get(self.selected) {
+                    return ConsoleOp::Paste(clipboard.clone());
+                }
+            }
+            KeyCode::Char(c @ '1'..='9') => {
+                let idx = c as usize - '1' as usize;
+                if let Some(clipboard) = self.clipboards.get(idx) {
+                    return ConsoleOp::Paste(clipboard.clone());
+                }
+            }
+            KeyCode::Down | KeyCode::Char('j') => {
+                self.selected = if self.selected >= last {
+                    0
+                } else {
+                    self.selected + 1
+                };
+            }
+            KeyCode::Up | KeyCode::Char('k') => {
+                self.selected = if self.selected == 0 {
+                    last
+                } else {
+                    self.selected - 1
+                };
+            }
+            KeyCode::Char('q') => return ConsoleOp::Exit,
+            _ => (),
+        }
+        ConsoleOp::None
+    }
+}
+
+/// Overlay that lists all active keybindings, grouped by category.
+///
+/// The list is scrolled with `j`/`k` and closed with `q` or `?`.
+pub struct HelpConsole {
+    title: &'static str,
+    /// Lines of the list: the keys (`None` for the heading of a category) and the description
+    lines: Vec<(Option<String>, String)>,
+    scroll: usize,
+    /// Number of lines that fit into the box (updated when drawn)
+    page: usize,
+}
+
+impl HelpConsole {
+    pub fn new(help: Vec<(&'static str, Vec<(String, String)>)>) -> Self {
+        let mut lines = Vec::new();
+        for (category, entries) in help.into_iter().filter(|(_, e)| !e.is_empty()) {
+            if !lines.is_empty() {
+                lines.push((Some(String::new()), String::new()));
+            }
+            lines.push((None, category.to_string()));
+            lines.extend(entries.into_iter().map(|(keys, text)| (Some(keys), text)));
+        }
+        HelpConsole {
+            title: "Keybindings",
+            lines,
+            scroll: 0,
+            page: 1,
+        }
+    }
+
+    /// Replaces the title (the console can show any list with a key column, like the doctor overlay).
+    pub fn with_title(mut self, title: &'static str) -> Self {
+        self.title = title;
+        self
+    }
+
+    fn scroll_by(&mut self, step: isize) {
+        let max_scroll = self.lines.len().saturating_sub(self.page);
+        self.scroll = self.scroll.saturating_add_signed(step).min(max_scroll);
+    }
+}
+
+impl Draw for HelpConsole {
+    fn draw(
+        &mut self,
+        stdout: &mut Stdout,
+        x_range: Range<u16>,
+        y_range: Range<u16>,
+    ) -> Result<()> {
+        let width = x_range.end.saturating_sub(x_range.start);
+        let height = y_range.end.saturating_sub(y_range.start);
+
+        let key_width = self
+            .lines
+            .iter()
+            .filter_map(|(keys, _)| keys.as_ref())
+            .map(|keys| unicode_display_width::width(keys) as usize)
+            .max()
+            .unwrap_or_default()
+            .min(width as usize / 3);
+        let text_width = self
+            .lines
+            .iter()
+            .map(|(_, text)| unicode_display_width::width(text) as usize)
+            .max()
+            .unwrap_or_default();
+        // border, padding and the space between the columns
+        let box_width = ((key_width + text_width) as u16)
+            .saturating_add(6)
+            .min(width);
+        let box_height = (self.lines.len() as u16).saturating_add(4).min(height);
+        if box_width < 10 || box_height < 5 {
+            return Ok(());
+        }
+        let inner = box_width as usize - 2;
+        let x_start = x_range.start + (width - box_width) / 2;
+        let y_start = y_range.start + (height - box_height) / 2;
+        let y_end = y_start + box_height - 1;
+        self.page = box_height as usize - 4;
+        self.scroll_by(0);
+
+        let border = |s: &'static str| PrintStyledContent(s.with(color_main()).bold());
+        let bar = "─".repeat(inner);
+        let title = format!(
+            " {} ({}-{} of {})",
+            self.title,
+            self.scroll + 1,
+            (self.scroll + self.page).min(self.lines.len()),
+            self.lines.len()
+        );
+        queue!(
+            stdout,
+            cursor::Hide,
+            cursor::MoveTo(x_start, y_start),
+            PrintStyledContent(format!("┌{bar}┐").with(color_main()).bold()),
+            cursor::MoveTo(x_start, y_start + 1),
+            border("│"),
+            PrintStyledContent(title.exact_width(inner).bold()),
+            border("│"),
+            cursor::MoveTo(x_start, y_start + 2),
+            PrintStyledContent(format!("├{bar}┤").with(color_main()).bold()),
+            cursor::MoveTo(x_start, y_end),
+            PrintStyledContent(format!("└{bar}┘").with(color_main()).bold()),
+        )?;
+
+        let mut lines = self.lines.iter().skip(self.scroll);
+        for y in y_start + 3..y_end {
+            queue!(stdout, cursor::MoveTo(x_start, y), border("│"))?;
+            match lines.next() {
+                Some((None, category)) => {
+                    let line = format!(" {category}").exact_width(inner);
+                    queue!(stdout, PrintStyledContent(line.with(color_main()).bold()))?;
+                }
+                Some((Some(keys), text)) => {
+                    let keys = format!(" {}", keys.exact_width(key_width));
+                    let text = format!("  {text}").exact_width(inner - key_width - 1);
+                    queue!(
+                        stdout,
+                        PrintStyledContent(keys.with(color_marked())),
+                        Print(text)
+                    )?;
+                }
+                None => queue!(stdout, Print(" ".repeat(inner)))?,
+            }
+            queue!(stdout, border("│"))?;
+        }
+        Ok(())
+    }
+}
+
+impl Console for HelpConsole {
+    fn handle_key(&mut self, key_event: KeyEvent) -> ConsoleOp {
+        match key_event.code {
+            KeyCode::Char('q') | KeyCode::Char('?') => return ConsoleOp::Exit,
+            KeyCode::Down | KeyCode::Char('j') => self.scroll_by(1),
+            KeyCode::Up | KeyCode::Char('k') => self.scroll_by(-1),
+            KeyCode::PageDown => self.scroll_by(self.page as isize),
+            KeyCode::PageUp => self.scroll_by(-(self.page as isize)),
+            _ => (),
+        }
+        ConsoleOp::None
+    }
+}
+
+/// Overlay with the running and the last finished background jobs.
+///
+/// `j`/`k` select a job and `x` cancels it, if it is still running.
+/// Finished jobs are listed with their duration and the reason, if they failed.
+#[derive(Default)]
+pub struct JobsConsole {
+    jobs: Vec<JobInfo>,
+    selected: usize,
+    /// Number of jobs that fit on the screen (updated when drawn)
+    page: usize,
+}
+
+impl JobsConsole {
+    fn select_by(&mut self, step: isize) {
+        let max = self.jobs.len().saturating_sub(1);
+        self.selected = self.selected.saturating_add_signed(step).min(max);
+    }
+}
+
+/// Time since the given moment, like "5m ago"
+fn ago(time: SystemTime) -> String {
+    let secs = time.elapsed().unwrap_or_default().as_secs();
+    match secs {
+        0..=59 => format!("{secs}s ago"),
+        60..=3599 => format!("{}m ago", secs / 60),
+        3600..=86399 => format!("{}h ago", secs / 3600),
+        _ => format!("{}d ago", secs / 86400),
+    }
+}
+
+/// Formats a duration like "4.2s" or "3m07s"
+fn elapsed_str(elapsed: Duration) -> String {
+    let secs = elapsed.as_secs();
+    if secs < 60 {
+        format!("{:.1}s", elapsed.as_secs_f64())
+    } else {
+        format!("{}m{:02}s", secs / 60, secs % 60)
+    }
+}
+
+impl Draw for JobsConsole {
+    fn draw(
+        &mut self,
+        stdout: &mut Stdout,
+        x_range: Range<u16>,
+        y_range: Range<u16>,
+    ) -> Result<()> {
+        let width = x_range.end.saturating_sub(x_range.start) as usize;
+        let height = y_range.end.saturating_sub(y_range.start);
+        // header, two separators and the help line
+        if height < 5 {
+            return Ok(());
+        }
+        self.jobs = jobs::list();
+        self.page = height.saturating_sub(4) as usize;
+        self.select_by(0);
+
+        let running = self.jobs.iter().filter(|job| job.outcome.is_none()).count();
+        let header = format!(
+            " {running} running jobs, {} finished",
+            self.jobs.len() - running
+        );
+        let help = " j/k to select, x to cancel the selected job, q to close";
+        queue!(
+            stdout,
+            cursor::Hide,
+            cursor::MoveTo(x_range.start, y_range.start),
+            PrintStyledContent(header.exact_width(width).bold()),
+            cursor::MoveTo(x_range.start, y_range.end - 1),
+            PrintStyledContent(help.exact_width(width).dark_grey()),
+        )?;
+        for x in x_range.clone() {
+            queue!(
+                stdout,
+                cursor::MoveTo(x, y_range.start + 1),
+                print_horizontal_bar(),
+                cursor::MoveTo(x, y_range.end - 2),
+                print_horizontal_bar(),
+            )?;
+        }
+
+        let scroll = self.selected.saturating_sub(self.page.saturating_sub(1));
+        let mut rows = self.jobs.iter().enumerate().skip(scroll);
+        for y in y_range.start + 2..y_range.end - 2 {
+            queue!(stdout, cursor::MoveTo(x_range.start, y))?;
+            let Some((idx, job)) = rows.next() else {
+                queue!(stdout, Print(" ".repeat(width)))?;
+                continue;
+            };
+            let state = match &job.outcome {
+                None => "running".to_string(),
+                Some(outcome) => outcome.to_string(),
+            };
+            let mut line = format!(
+                " {state:<9} {:>7}  {:<14} {}",
+                elapsed_str(job.elapsed),
+                job.kind.to_string(),
+                job.description
+            );
+            match (&job.outcome, job.progress) {
+                (Some(Outcome::Failed(reason)), _) => line.push_str(&format!(" ({reason})")),
+                (None, Some(percent)) => line.push_str(&format!(" ({percent}%)")),
+                _ => (),
+            }
+            let line = line.exact_width(width);
+            let line = match &job.outcome {
+                None => line.with(color_main()),
+                Some(Outcome::Failed(_)) => line.with(color_highlight()),
+                Some(_) => line.dark_grey(),
+            };
+            let line = if idx == self.selected {
+                line.reverse()
+            } else {
+                line
+            };
+            queue!(stdout, PrintStyledContent(line))?;
+        }
+        Ok(())
+    }
+}
+
+impl Console for JobsConsole {
+    fn handle_key(&mut self, key_event: KeyEvent) -> ConsoleOp {
+        match key_event.code {
+            KeyCode::Char('q') => return ConsoleOp::Exit,
+            KeyCode::Down | KeyCode::Char('j') => self.select_by(1),
+            KeyCode::Up | KeyCode::Char('k') => self.select_by(-1),
+            KeyCode::Char('x') | KeyCode::Delete => {
+                if let Some(job) = self.jobs.get(self.selected) {
+                    if job.outcome.is_none() && jobs::cancel(job.id) {
+                        info!("Cancelled {} job: {}", job.kind, job.description);
+                    }
+                }
+            }
+            _ => (),
+        }
+        ConsoleOp::None
+    }
+
+    fn needs_refresh(&self) -> bool {
+        true
+    }
+}
+
+/// Size of a directory, that is summed up in the background
+#[derive(Debug, Clone, Default)]
+struct DirSize {
+    bytes: u64,
+    files: usize,
+    dirs: usize,
+    done: bool,
+}
+
+impl DirSize {
+    fn value(&self) -> String {
+        let size = format!(
+            "{} ({} bytes), {} files, {} directories",
+            file_size_str(self.bytes),
+            self.bytes,
+            self.files,
+            self.dirs
+        );
+        if self.done {
+            size
+        } else {
+            format!("{size} (counting...)")
+        }
+    }
+}
+
+/// Overlay with all metadata of a file or directory.
+///
+/// The size of a directory is summed up in the background, and the counting
+/// is cancelled when the overlay is closed. Scroll with `j`/`k`, close with `q`.
+pub struct PropertiesConsole {
+    name: String,
+    /// Label and value of every property
+    lines: Vec<(&'static str, String)>,
+    /// Index of the size in `lines` and the size of a directory
+    dir_size: Option<(usize, Arc<Mutex<DirSize>>)>,
+    cancel: CancelToken,
+    scroll: usize,
+    /// Number of lines that fit into the box (updated when drawn)
+    page: usize,
+}
+
+impl PropertiesConsole {
+    pub fn new(path: &Path) -> Self {
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_else(|| path.display().to_string());
+        let mut console = PropertiesConsole {
+            name,
+            lines: vec![("Path", path.display().to_string())],
+            dir_size: None,
+            cancel: CancelToken::default(),
+            scroll: 0,
+            page: 1,
+        };
+        let (link, metadata) = match (path.symlink_metadata(), path.metadata()) {
+            (Ok(link), Ok(metadata)) => (link, metadata),
+            (Ok(link), Err(_)) => (link.clone(), link),
+            (Err(e), _) => {
+                console.lines.push(("Error", e.to_string()));
+                return console;
+            }
+        };
+        let file_type = if metadata.is_dir() {
+            "directory"
+        } else if metadata.is_file() {
+            "file"
+        } else {
+            "special file"
+        };
+        if link.is_symlink() {
+            let target = std::fs::read_link(path)
+                .map(|target| target.display().to_string())
+                .unwrap_or_else(|e| e.to_string());
+            let broken = if path.exists() { "" } else { " (broken)" };
+            console
+                .lines
+                .push(("Type", format!("symlink to {file_type}")));
+            console
+                .lines
+                .push(("Link target", format!("{target}{broken}")));
+        } else {
+            console.lines.push(("Type", file_type.to_string()));
+        }
+        if metadata.is_dir() {
+            console.dir_size = Some((console.lines.len(), console.count_size(path)));
+            console.lines.push(("Size", String::new()));
+        } else {
+            let size = format!(
+                "{} ({} bytes)",
+                file_size_str(metadata.len()),
+                metadata.len()
+            );
+            console.lines.push(("Size", size));
+        }
+        let owner = users::get_user_by_uid(metadata.uid())
+            .map(|user| user.name().to_string_lossy().to_string())
+            .unwrap_or_default();
+        let group = users::get_group_by_gid(metadata.gid())
+            .map(|group| group.name().to_string_lossy().to_string())
+            .unwrap_or_default();
+        let mode = metadata.permissions().mode();
+        console.lines.extend([
+            ("Owner", format!("{owner} ({})", metadata.uid())),
+            ("Group", format!("{group} ({})", metadata.gid())),
+            (
+                "Permissions",
+                format!("{} ({:04o})", unix_mode::to_string(mode), mode & 0o7777),
+            ),
+        ]);
+        for (label, time) in [
+            ("Created", metadata.created()),
+            ("Modified", metadata.modified()),
+            ("Accessed", metadata.accessed()),
+        ] {
+            let time = time
+                .map(timestamp_str)
+                .unwrap_or_else(|_| "not available".to_string());
+            console.lines.push((label, time));
+        }
+        let mime = if metadata.is_dir() {
+            "inode/directory".to_string()
+        } else {
+            mime_guess::from_path(path)
+                .first_raw()
+                .unwrap_or("unknown")
+                .to_string()
+        };
+        console.lines.push(("Mime type", mime));
+        let item_tags = tags::of(path);
+        if !item_tags.is_empty() {
+            console.lines.push(("Tags", item_tags.join(", ")));
+        }
+        let attributes = extended_attributes(path);
+        if attributes.is_empty() {
+            console.lines.push(("Attributes", "none".to_string()));
+        }
+        for (idx, (name, value)) in attributes.into_iter().enumerate() {
+            let label = if idx == 0 { "Attributes" } else { "" };
+            console.lines.push((label, format!("{name} = {value}")));
+        }
+        console
+    }
+
+    /// Sums up the size of the directory in the background.
+    fn count_size(&mut self, path: &Path) -> Arc<Mutex<DirSize>> {
+        let size = Arc::new(Mutex::new(DirSize::default()));
+        let job = jobs::start(JobKind::Read, format!("size of {}", path.display()));
+        self.cancel = job.token().clone();
+        let (path, shared) = (path.to_path_buf(), size.clone());
+        tokio::task::spawn_blocking(move || {
+            let mut local = DirSize::default();
+            for (idx, entry) in walkdir::WalkDir::new(path)
+                .min_depth(1)
+                .into_iter()
+                .flatten()
+                .enumerate()
+            {
+                if job.is_cancelled() {
+                    return;
+                }
+                if entry.file_type().is_dir() {
+                    local.dirs += 1;
+                } else {
+                    local.files += 1;
+                    local.bytes += entry.metadata().map(|m| m.len()).unwrap_or_default();
+                }
+                if idx % 1024 == 0 {
+                    *shared.lock() = local.clone();
+                }
+            }
+            local.done = true;
+            *shared.lock() = local;
+        });
+        size
+    }
+
+    fn scroll_by(&mut self, step: isize) {
+        let max_scroll = self.lines.len().saturating_sub(self.page);
+        self.scroll = self.scroll.saturating_add_signed(step).min(max_scroll);
+    }
+}
+
+impl Drop for PropertiesConsole {
+    fn drop(&mut self) {
+        self.cancel.cancel();
+    }
+}
+
+impl Draw for PropertiesConsole {
+    fn draw(
+        &mut self,
+        stdout: &mut Stdout,
+        x_range: Range<u16>,
+        y_range: Range<u16>,
+    ) -> Result<()> {
+        if let Some((idx, size)) = &self.dir_size {
+            self.lines[*idx].1 = size.lock().value();
+        }
+        let width = x_range.end.saturating_sub(x_range.start);
+        let height = y_range.end.saturating_sub(y_range.start);
+
+        let label_width = self
+            .lines
+            .iter()
+            .map(|(label, _)| label.len())
+            .max()
+            .unwrap_or_default();
+        let value_width = self
+            .lines
+            .iter()
+            .map(|(_, value)| unicode_display_width::width(value) as usize)
+            .max()
+            .unwrap_or_default();
+        // border, padding and the space between the columns
+        let box_width = ((label_width + value_width) as u16)
+            .saturating_add(6)
+            .min(width);
+        let box_height = (self.lines.len() as u16).saturating_add(4).min(height);
+        if box_width < 10 || box_height < 5 {
+            return Ok(());
+        }
+        let inner = box_width as usize - 2;
+        let x_start = x_range.start + (width - box_width) / 2;
+        let y_start = y_range.start + (height - box_height) / 2;
+        let y_end = y_start + box_height - 1;
+        self.page = box_height as usize - 4;
+        self.scroll_by(0);
+
+        let border = |s: &'static str| PrintStyledContent(s.with(color_main()).bold());
+        let bar = "─".repeat(inner);
+        let title = format!(" Properties of {}", self.name);
+        queue!(
+            stdout,
+            cursor::Hide,
+            cursor::MoveTo(x_start, y_start),
+            PrintStyledContent(format!("┌{bar}┐").with(color_main()).bold()),
+            cursor::MoveTo(x_start, y_start + 1),
+            border("│"),
+            PrintStyledContent(title.exact_width(inner).bold()),
+            border("│"),
+            cursor::MoveTo(x_start, y_start + 2),
+            PrintStyledContent(format!("├{bar}┤").with(color_main()).bold()),
+            cursor::MoveTo(x_start, y_end),
+            PrintStyledContent(format!("└{bar}┘").with(color_main()).bold()),
+        )?;
+
+        let mut lines = self.lines.iter().skip(self.scroll);
+        for y in y_start + 3..y_end {
+            queue!(stdout, cursor::MoveTo(x_start, y), border("│"))?;
+            match lines.next() {
+                Some((label, value)) => {
+                    let label = format!(" {label:<label_width$}");
+                    let value = format!("  {value}").exact_width(inner - label_width - 1);
+                    queue!(
+                        stdout,
+                        PrintStyledContent(label.with(color_marked())),
+                        Print(value)
+                    )?;
+                }
+                None => queue!(stdout, Print(" ".repeat(inner)))?,
+            }
+            queue!(stdout, border("│"))?;
+        }
+        Ok(())
+    }
+}
+
+impl Console for PropertiesConsole {
+    fn handle_key(&mut self, key_event: KeyEvent) -> ConsoleOp {
+        match key_event.code {
+            KeyCode::Char('q') => return ConsoleOp::Exit,
+            KeyCode::Down | KeyCode::Char('j') => self.scroll_by(1),
+            KeyCode::Up | KeyCode::Char('k') => self.scroll_by(-1),
+            _ => (),
+        }
+        ConsoleOp::None
+    }
+
+    fn needs_refresh(&self) -> bool {
+        matches!(&self.dir_size, Some((_, size)) if !size.lock().done)
+    }
+}
+
+/// Field of the [`ArchiveConsole`], that is changed by the keys
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ArchiveField {
+    Format,
+    Name,
+    Level,
+}
+
+/// Dialog that asks for the format, name and compression level of a new archive.
+///
+/// `tab` (or the arrow keys up and down) switches between the fields, left and right change
+/// the format and level, and `enter` creates the archive in the background.
+pub struct ArchiveConsole {
+    items: Vec<PathBuf>,
+    dir: PathBuf,
+    format: ArchiveFormat,
+    name: Input,
+    level: u32,
+    field: ArchiveField,
+}
+
+impl ArchiveConsole {
+    pub fn new(items: Vec<PathBuf>, dir: PathBuf) -> Self {
+        let format = ArchiveFormat::Zip;
+        // A single item gives the archive its name, otherwise the directory does
+        let name = match items.as_slice() {
+            [item] if item.is_dir() => item.file_name(),
+            [item] => item.file_stem(),
+            _ => dir.file_name(),
+        }
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| "archive".to_string());
+        ArchiveConsole {
+            items,
+            dir,
+            format,
+            name: Input::from_str(name),
+            level: format.default_level(),
+            field: ArchiveField::Name,
+        }
+    }
+
+    fn switch_field(&mut self, forward: bool) {
+        self.field = match (self.field, forward) {
+            (ArchiveField::Format, true) | (ArchiveField::Level, false) => ArchiveField::Name,
+            (ArchiveField::Name, true) | (ArchiveField::Format, false) => ArchiveField::Level,
+            (ArchiveField::Level, true) | (ArchiveField::Name, false) => ArchiveField::Format,
+        };
+    }
+
+    fn change(&mut self, forward: bool) {
+        match self.field {
+            ArchiveField::Format => {
+                let formats = ArchiveFormat::ALL;
+                let idx = formats.iter().position(|f| *f == self.format).unwrap_or(0);
+                let idx = if forward {
+                    (idx + 1) % formats.len()
+                } else {
+                    (idx + formats.len() - 1) % formats.len()
+                };
+                self.format = formats[idx];
+                self.level = self.format.default_level();
+            }
+            ArchiveField::Level => {
+                let levels = self.format.levels();
+                self.level = if forward {
+                    self.level.saturating_add(1)
+                } else {
+                    self.level.saturating_sub(1)
+                }
+                .clamp(*levels.start(), *levels.end());
+            }
+            ArchiveField::Name => (),
+        }
+    }
+}
+
+impl Draw for ArchiveConsole {
+    fn draw(
+        &mut self,
+        stdout: &mut Stdout,
+        x_range: Range<u16>,
+        y_range: Range<u16>,
+    ) -> Result<()> {
+        let width = x_range.end.saturating_sub(x_range.start);
+        let height = y_range.end.saturating_sub(y_range.start);
+        let box_width = 60.min(width);
+        let box_height = 9;
+        if box_width < 40 || height < box_height {
+            return Ok(());
+        }
+        let inner = box_width as usize - 2;
+        let x_start = x_range.start + (width - box_width) / 2;
+        let y_start = y_range.start + (height - box_height) / 2;
+        let y_end = y_start + box_height - 1;
+
+        let border = |s: &'static str| PrintStyledContent(s.with(color_main()).bold());
+        let bar = "─".repeat(inner);
+        let title = format!(" Create archive from {} items", self.items.len());
+        queue!(
+            stdout,
+            cursor::Hide,
+            cursor::MoveTo(x_start, y_start),
+            PrintStyledContent(format!("┌{bar}┐").with(color_main()).bold()),
+            cursor::MoveTo(x_start, y_start + 1),
+            border("│"),
+            PrintStyledContent(title.exact_width(inner).bold()),
+            border("│"),
+            cursor::MoveTo(x_start, y_start + 2),
+            PrintStyledContent(format!("├{bar}┤").with(color_main()).bold()),
+            cursor::MoveTo(x_start, y_end),
+            PrintStyledContent(format!("└{bar}┘").with(color_main()).bold()),
+        )?;
+        for y in y_start + 3..y_end {
+            queue!(
+                stdout,
+                cursor::MoveTo(x_start, y),
+                border("│"),
+                Print(" ".repeat(inner)),
+                border("│"),
+            )?;
+        }
+
+        let label = |field: ArchiveField, text: &str| {
+            let text = format!(" {text:<8}");
+            if self.field == field {
+                text.with(color_marked()).bold()
+            } else {
+                text.stylize()
+            }
+        };
+        let x_value = x_start + 10;
+
+        queue!(
+            stdout,
+            cursor::MoveTo(x_start + 1, y_start + 3),
+            PrintStyledContent(label(ArchiveField::Format, "Format")),
+        )?;
+        for format in ArchiveFormat::ALL {
+            let text = format!(" {format} ");
+            let text = if format == self.format {
+                text.reverse()
+            } else {
+                text.stylize()
+            };
+            queue!(stdout, PrintStyledContent(text))?;
+        }
+
+        // The extension is shown after the name, so that the result is clear
+        let name_width = unicode_display_width::width(self.name.get()) as u16 + 1;
+        queue!(
+            stdout,
+            cursor::MoveTo(x_start + 1, y_start + 4),
+            PrintStyledContent(label(ArchiveField::Name, "Name")),
+            cursor::MoveTo(x_value, y_start + 4),
+        )?;
+        self.name.print(stdout, color_main())?;
+        queue!(
+            stdout,
+            cursor::MoveTo(x_value + name_width, y_start + 4),
+            PrintStyledContent(format!(".{}", self.format.extension()).dark_grey()),
+        )?;
+
+        let levels = self.format.levels();
+        queue!(
+            stdout,
+            cursor::MoveTo(x_start + 1, y_start + 5),
+            PrintStyledContent(label(ArchiveField::Level, "Level")),
+            cursor::MoveTo(x_value, y_start + 5),
+            Print(format!(
+                " {} ({}-{})",
+                self.level,
+                levels.start(),
+                levels.end()
+            )),
+        )?;
+
+        let help = " tab: next field, ←/→: change, enter: create".exact_width(inner);
+        queue!(
+            stdout,
+            cursor::MoveTo(x_start + 1, y_end - 1),
+            PrintStyledContent(help.dark_grey()),
+        )?;
+        Ok(())
+    }
+}
+
+impl Console for ArchiveConsole {
+    fn handle_key(&mut self, key_event: KeyEvent) -> ConsoleOp {
+        match key_event.code {
+            KeyCode::Enter => {
+                let name = self.name.get().trim();
+                if name.is_empty() || name.contains('/') {
+                    return ConsoleOp::None;
+                }
+                return ConsoleOp::Archive(ArchivePlan {
+                    items: self.items.clone(),
+                    dir: self.dir.clone(),
+                    name: name.to_string(),
+                    format: self.format,
+                    level: self.level,
+                });
+            }
+            KeyCode::Tab | KeyCode::Down => self.switch_field(true),
+            KeyCode::BackTab | KeyCode::Up => self.switch_field(false),
+            KeyCode::Left if self.field != ArchiveField::Name => self.change(false),
+            KeyCode::Right if self.field != ArchiveField::Name => self.change(true),
+            code if self.field == ArchiveField::Name => self.name.update(code, key_event.modifiers),
+            _ => (),
+        }
+        ConsoleOp::None
+    }
+}
+
+/// Console that searches the content of all files below the current directory.
+///
+/// After the pattern is entered, the matches are listed while the search is running.
+/// `j`/`k` select a match, `enter` jumps to it and `/` edits the pattern again.
+pub struct GrepConsole {
+    dir: PathBuf,
+    input: String,
+    show_hidden: bool,
+    /// Receives a message whenever the search has new matches (to trigger a redraw)
+    notify: mpsc::UnboundedSender<()>,
+    /// Running or finished search. If `None`, the pattern is edited.
+    search: Option<GrepSearch>,
+    selected: usize,
+    /// Number of matches that fit on the screen (updated when drawn)
+    page: usize,
+}
+
+impl GrepConsole {
+    pub fn new(panel: &DirPanel, notify: mpsc::UnboundedSender<()>) -> Self {
+        GrepConsole {
+            dir: panel.path().to_path_buf(),
+            input: String::new(),
+            show_hidden: panel.show_hidden(),
+            notify,
+            search: None,
+            selected: 0,
+            page: 1,
+        }
+    }
+
+    fn select_by(&mut self, step: isize) {
+        let Some(search) = &self.search else {
+            return;
+        };
+        let last = search.results().matches.len().saturating_sub(1);
+        self.selected = self.selected.saturating_add_signed(step).min(last);
+    }
+}
+
+impl Draw for GrepConsole {
+    fn draw(
+        &mut self,
+        stdout: &mut Stdout,
+        x_range: Range<u16>,
+        y_range: Range<u16>,
+    ) -> Result<()> {
+        let width = x_range.end.saturating_sub(x_range.start) as usize;
+        let height = y_range.end.saturating_sub(y_range.start);
+        // input, two separators and the status line
+        if height < 5 {
+            return Ok(());
+        }
+        self.page = height.saturating_sub(4) as usize;
+        let scroll = self.selected.saturating_sub(self.page.saturating_sub(1));
+
+        for x in x_range.clone() {
+            queue!(
+                stdout,
+                cursor::MoveTo(x, y_range.start + 1),
+                print_horizontal_bar(),
+                cursor::MoveTo(x, y_range.end - 2),
+                print_horizontal_bar(),
+            )?;
+        }
+
+        let status = match &self.search {
+            None if ripgrep_installed() => " enter to search (regular expression)".to_string(),
+            None => {
+                " enter to search (fixed string - install 'rg' for regular expressions)".to_string()
+            }
+            Some(search) => {
+                let results = search.results();
+                let n = results.matches.len();
+                if let Some(error) = &results.error {
+                    format!(" error: {error}")
+                } else if !results.finished {
+                    format!(" searching... {n} matches")
+                } else if n >= MAX_MATCHES {
+                    format!(" {n} matches (stopped), j/k to move, enter to open, / to edit")
+                } else {
+                    format!(" {n} matches, j/k to move, enter to open, / to edit")
+                }
+            }
+        };
+        queue!(
+            stdout,
+            cursor::MoveTo(x_range.start, y_range.end - 1),
+            PrintStyledContent(status.exact_width(width).dark_grey()),
+        )?;
+
+        let results = self.search.as_ref().map(|s| s.results());
+        let matches = results
+            .as_ref()
+            .map(|r| r.matches.as_slice())
+            .unwrap_or_default();
+        let mut rows = matches.iter().enumerate().skip(scroll);
+        for y in y_range.start + 2..y_range.end - 2 {
+            queue!(stdout, cursor::MoveTo(x_range.start, y))?;
+            let Some((idx, m)) = rows.next() else {
+                queue!(stdout, Print(" ".repeat(width)))?;
+                continue;
+            };
+            let path = m.path.strip_prefix(&self.dir).unwrap_or(&m.path);
+            let location = format!(" {}:{}: ", path.display(), m.line);
+            let location_width = (unicode_display_width::width(&location) as usize).min(width);
+            let location = location.exact_width(location_width).with(color_dir_path());
+            let text = m.text.exact_width(width - location_width).stylize();
+            if idx == self.selected {
+                queue!(
+                    stdout,
+                    PrintStyledContent(location.reverse()),
+                    PrintStyledContent(text.reverse()),
+                )?;
+            } else {
+                queue!(
+                    stdout,
+                    PrintStyledContent(location),
+                    PrintStyledContent(text)
+                )?;
+            }
+        }
+        drop(results);
+
+        let prompt = format!(" grep in {}: ", self.dir.display());
+        let line = format!("{prompt}{}", self.input);
+        queue!(
+            stdout,
+            cursor::MoveTo(x_range.start, y_range.start),
+            PrintStyledContent(line.exact_width(width).bold()),
+        )?;
+        if self.search.is_none() {
+            let x_cursor = unicode_display_width::width(&line) as u16;
+            queue!(
+                stdout,
+                cursor::MoveTo(x_range.start.saturating_add(x_cursor), y_range.start),
+                cursor::Show,
+                cursor::SetCursorStyle::DefaultUserShape,
+                cursor::EnableBlinking,
+            )?;
+        } else {
+            queue!(stdout, cursor::Hide)?;
+        }
+        Ok(())
+    }
+}
+
+impl Console for GrepConsole {
+    fn handle_key(&mut self, key_event: KeyEvent) -> ConsoleOp {
+        // Edit the pattern
+        if self.search.is_none() {
+            match key_event.code {
+                KeyCode::Enter if !self.input.is_empty() => {
+                    self.selected = 0;
+                    self.search = Some(GrepSearch::start(
+                        self.dir.clone(),
+                        self.input.clone(),
+                        self.show_hidden,
+                        self.notify.clone(),
+                    ));
+                }
+                KeyCode::Backspace => {
+                    self.input.pop();
+                }
+                KeyCode::Char(c) => self.input.push(c),
+                _ => (),
+            }
+            return ConsoleOp::None;
+        }
+        // Navigate the matches
+        match key_event.code {
+            KeyCode::Enter => {
+                let selected = self
+                    .search
+                    .as_ref()
+                    .and_then(|s| s.results().matches.get(self.selected).cloned());
+                if let Some(m) = selected {
+                    return ConsoleOp::ShowLine {
+                        path: m.path,
+                        line: m.line,
+                    };
+                }
+            }
+            // Dropping the search cancels it
+            KeyCode::Char('/') | KeyCode::Backspace => self.search = None,
+            KeyCode::Char('q') => return ConsoleOp::Exit,
+            KeyCode::Down | KeyCode::Char('j') => self.select_by(1),
+            KeyCode::Up | KeyCode::Char('k') => self.select_by(-1),
+            KeyCode::PageDown => self.select_by(self.page as isize),
+            KeyCode::PageUp => self.select_by(-(self.page as isize)),
+            KeyCode::Home | KeyCode::Char('g') => self.selected = 0,
+            KeyCode::End | KeyCode::Char('G') => self.select_by(isize::MAX),
+            _ => (),
+        }
+        ConsoleOp::None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rank_subdirectories() {
+        let names: Vec<String> = ["src", "Documents", "docs", "old-docs", "target"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        assert_eq!(
+            rank_subdirs(&names, "doc"),
+            ["docs", "Documents", "old-docs"]
+        );
+        assert_eq!(rank_subdirs(&names, "DOCS old"), ["old-docs"]);
+        assert_eq!(rank_subdirs(&names, "").len(), names.len());
+    }
+
+    #[test]
+    fn key_popup_grid() {
+        // Three columns of width 10 fit, with a gap between them
+        assert_eq!(popup_grid(7, 10, 36, 10), (3, 3));
+        assert_eq!(popup_grid(7, 10, 35, 10), (2, 4));
+        // Never more columns than entries
+        assert_eq!(popup_grid(2, 10, 100, 10), (2, 1));
+        // Long entries still get a column
+        assert_eq!(popup_grid(3, 50, 20, 10), (1, 3));
+        // Too many entries are cut off
+        assert_eq!(popup_grid(30, 10, 36, 4), (3, 4));
+    }
+}
@@ -1,21 +1,212 @@
 use std::{
-    fs::read_dir,
-    os::unix::prelude::MetadataExt,
     slice::{Iter, IterMut},
-    time::SystemTime,
+    time::{Duration, Instant, SystemTime},
 };
 
-use crossterm::style::{ContentStyle, StyledContent};
+use crossterm::style::{Color, ContentStyle, StyledContent};
+use feruca::Collator;
+use time::OffsetDateTime;
+use unicode_display_width::width as unicode_width;
 use unix_mode::is_allowed;
 
 use crate::{
-    config::color::{color_highlight, color_main, color_marked, print_vertical_bar},
-    content::dir_content,
-    engine::SymbolEngine,
-    util::{file_size_str, ExactWidth},
+    config::{
+        color::{color_highlight, color_main, color_marked, ls_colors, print_vertical_bar},
+        SortMode,
+    },
+    content::{dir_content_head, CHUNK_SIZE},
+    engine::{checksum, opener::MimeCategory, staging, tags, SymbolEngine},
+    util::{file_size_str, glob_match, natural_key, styled_permissions, ExactWidth},
+    vfs,
 };
 
-use super::*;
+use super::{
+    normalize::{self, Normalized},
+    *,
+};
+
+/// Sort mode that is used for all directory panels.
+static SORT_MODE: Mutex<SortMode> = Mutex::new(SortMode::Lowercase);
+
+/// Sets the sort mode for all directory panels.
+///
+/// Existing panels are sorted again, when they are shown the next time.
+pub fn set_sort_mode(mode: SortMode) {
+    *SORT_MODE.lock() = mode;
+}
+
+/// Returns the current sort mode.
+pub fn sort_mode() -> SortMode {
+    *SORT_MODE.lock()
+}
+
+/// Sorts the elements by name - directories first.
+fn sort_elements(elements: &mut [DirElem], mode: SortMode) {
+    match mode {
+        SortMode::Lowercase => elements.sort_by_cached_key(|a| a.name_lowercase().clone()),
+        SortMode::Natural => elements.sort_by_cached_key(|a| natural_key(a.name_lowercase())),
+        SortMode::Locale => {
+            let mut collator = Collator::default();
+            elements.sort_by(|a, b| collator.collate(a.name(), b.name()));
+        }
+    }
+    elements.sort_by_key(|a| !a.is_dir);
+}
+
+/// Group of an element, if the elements are grouped by extension (see [`DirPanel::toggle_groups`]).
+///
+/// Directories come first, then the files by their extension and finally the files without one.
+type GroupKey = (u8, String);
+
+fn group_key(elem: &DirElem) -> GroupKey {
+    if elem.is_dir {
+        return (0, String::new());
+    }
+    match elem.path.extension() {
+        Some(extension) => (1, extension.to_string_lossy().to_lowercase()),
+        None => (2, String::new()),
+    }
+}
+
+fn group_label(key: &GroupKey) -> &str {
+    match key.0 {
+        0 => "directories",
+        1 => &key.1,
+        _ => "no extension",
+    }
+}
+
+/// Frames of the spinner, that is shown while a panel is loading
+const SPINNER: [char; 10] = ['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+
+/// Time between two frames of the spinner
+pub const SPINNER_INTERVAL: Duration = Duration::from_millis(100);
+
+/// After this time, a loading panel suggests to move on
+const LOADING_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Maximum number of elements, whose metadata is read for the statistics of a directory
+const MAX_STATS_ELEMENTS: usize = 10_000;
+
+/// Number of pages above and below the visible elements, that are normalized in the background
+const PREFETCH_PAGES: usize = 2;
+
+/// Statistics of a directory, that are shown above its preview
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DirStats {
+    pub dirs: usize,
+    pub files: usize,
+    /// Total size of the files (not including the content of subdirectories)
+    pub size: u64,
+    /// Newest modification time of all elements
+    pub newest: Option<SystemTime>,
+    /// False if the directory has too many elements to read all of them
+    pub complete: bool,
+}
+
+impl DirStats {
+    /// Reads the metadata of the elements (or the first [`MAX_STATS_ELEMENTS`] of them).
+    fn collect(elements: &[DirElem]) -> Self {
+        let mut stats = DirStats {
+            complete: elements.len() <= MAX_STATS_ELEMENTS,
+            ..Default::default()
+        };
+        for elem in elements.iter().take(MAX_STATS_ELEMENTS) {
+            let Ok(metadata) = vfs::metadata(&elem.path) else {
+                continue;
+            };
+            if metadata.is_dir {
+                stats.dirs += 1;
+            } else {
+                stats.files += 1;
+                stats.size += metadata.len;
+            }
+            stats.newest = stats.newest.max(metadata.modified);
+        }
+        stats
+    }
+
+    /// Summary like "3 dirs, 12 files, 1.20 M, newest 2024-05-01 12:00"
+    fn summary(&self) -> String {
+        let more = if self.complete { "" } else { "+" };
+        let mut summary = format!(
+            "{}{more} dirs, {}{more} files, {}{more}",
+            self.dirs,
+            self.files,
+            file_size_str(self.size)
+        );
+        if let Some(newest) = self.newest {
+            let t = OffsetDateTime::from(newest);
+            summary.push_str(&format!(
+                ", newest {}-{:02}-{:02} {:02}:{:02}",
+                t.year(),
+                u8::from(t.month()),
+                t.day(),
+                t.hour(),
+                t.minute()
+            ));
+        }
+        summary
+    }
+}
+
+/// A row of the grouped view
+enum GroupRow {
+    Header {
+        label: String,
+        count: usize,
+        collapsed: bool,
+    },
+    /// Index of the element
+    Elem(usize),
+}
+
+/// Width of the permission column in the detail view
+const PERMISSIONS_WIDTH: u16 = 11;
+
+/// Width of the modification time column in the detail view
+const MODIFIED_WIDTH: u16 = 17;
+
+/// Minimum width that is left for the names, before we drop columns of the detail view
+const MIN_NAME_WIDTH: u16 = 16;
+
+/// Space around the name of an entry (the symbol and the surrounding spaces)
+const ENTRY_PADDING: u64 = 6;
+
+/// Space that is reserved for the size suffix of entries that were not normalized yet (e.g. "123.4 M")
+const SUFFIX_WIDTH: usize = 7;
+
+/// Columns of the detail view that are displayed next to the names.
+#[derive(Debug, Clone, Copy, Default)]
+struct DetailColumns {
+    permissions: bool,
+    modified: bool,
+}
+
+impl DetailColumns {
+    /// Selects all columns that fit into the given width.
+    ///
+    /// The modification time is dropped first, because it takes up the most space.
+    fn fit(width: u16) -> Self {
+        let available = width.saturating_sub(MIN_NAME_WIDTH);
+        DetailColumns {
+            permissions: available >= PERMISSIONS_WIDTH,
+            modified: available >= PERMISSIONS_WIDTH + MODIFIED_WIDTH,
+        }
+    }
+
+    fn width(&self) -> u16 {
+        let mut width = 0;
+        if self.permissions {
+            width += PERMISSIONS_WIDTH;
+        }
+        if self.modified {
+            width += MODIFIED_WIDTH;
+        }
+        width
+    }
+}
+
 /// An element of a directory.
 ///
 /// Shorthand for saving a path together whith what we want to display.
@@ -34,12 +225,26 @@ pub struct DirElem {
     /// Full (canonicalized) path of the element
     path: PathBuf,
 
+    /// True if the element is a directory (or a link to one)
+    ///
+    /// Is read once when the element is created, because sorting and grouping need it for every element.
+    is_dir: bool,
+
     /// Weather or not the file is an executable
     is_executable: bool,
 
+    /// True if the element is a directory that we cannot read or enter
+    is_locked: bool,
+
     /// String to display either file-size or number of elements in directory
     suffix: String,
 
+    /// Unix permission bits of the element
+    mode: u32,
+
+    /// Last modification time of the element
+    modified: Option<SystemTime>,
+
     /// True if element is a hidden file or directory.
     is_hidden: bool,
 
@@ -53,6 +258,15 @@ pub struct DirElem {
 }
 
 impl DirElem {
+    /// Approximate number of bytes that the element occupies
+    fn mem_size(&self) -> usize {
+        std::mem::size_of::<Self>()
+            + self.name.len()
+            + self.lowercase.len()
+            + self.path.as_os_str().len()
+            + self.suffix.len()
+    }
+
     pub fn name(&self) -> &String {
         &self.name
     }
@@ -69,6 +283,21 @@ impl DirElem {
         self.is_hidden
     }
 
+    /// Creates an element that is named by its path relative to `base` (e.g. "src/main.rs").
+    ///
+    /// Elements inside of hidden directories are hidden as well.
+    pub fn relative(path: &Path, base: &Path) -> Self {
+        let mut elem = DirElem::from(path);
+        if let Ok(relative) = path.strip_prefix(base) {
+            elem.is_hidden |= relative
+                .components()
+                .any(|c| c.as_os_str().to_string_lossy().starts_with('.'));
+            elem.name = relative.to_string_lossy().into_owned();
+            elem.lowercase = elem.name.to_lowercase();
+        }
+        elem
+    }
+
     pub fn is_marked(&self) -> bool {
         self.is_marked
     }
@@ -77,31 +306,54 @@ impl DirElem {
         self.is_marked = false;
     }
 
-    /// Creates a [`PrintStyledContent`] from the `DirElem` itself.
+    /// Creates a [`PrintEntry`] from the `DirElem` itself.
     ///
     /// If the element has not been normalized yet, we do so before we create the styled content.
-    pub fn print_styled(&mut self, selected: bool, max_len: u16) -> PrintStyledContent<String> {
+    pub fn print_styled(&mut self, selected: bool, max_len: u16) -> PrintEntry {
         // Only print normalized items
         self.normalize();
+        // Files that were compared with a checksum file get a marker
+        let verified = checksum::result(&self.path).and_then(|c| c.verified);
+        let mut suffix = match verified {
+            Some(true) => format!("✓ {}", self.suffix),
+            Some(false) => format!("✗ {}", self.suffix),
+            None => self.suffix.clone(),
+        };
+        // Staged items keep their badge in every directory
+        if staging::is_staged(&self.path) {
+            suffix = format!("◆ {suffix}");
+        }
         // Prepare output
         let name_len = usize::from(max_len)
-            .saturating_sub(self.suffix.chars().count())
+            .saturating_sub(suffix.chars().count())
             .saturating_sub(6);
         let name = self.name.exact_width(name_len);
 
         let string: String;
         let mut style = ContentStyle::new();
-        if self.path.is_dir() {
+        if self.is_dir {
             style = style.with(color_main()).bold();
-            string = format!(" \u{1F4C1}{name} {} ", self.suffix);
+            let symbol = if self.is_locked {
+                SymbolEngine::locked_dir_symbol()
+            } else {
+                SymbolEngine::dir_symbol()
+            };
+            string = format!(" {symbol}{name} {suffix} ");
         } else if self.is_executable {
             style = style.green().bold();
             let symbol = SymbolEngine::get_symbol(self.path());
-            string = format!(" {symbol} {name} {} ", self.suffix);
+            string = format!(" {symbol}{name} {suffix} ");
         } else {
             style = style.grey();
             let symbol = SymbolEngine::get_symbol(self.path());
-            string = format!(" {symbol} {name} {} ", self.suffix);
+            string = format!(" {symbol}{name} {suffix} ");
+        }
+        // $LS_COLORS replaces the default styles, if it is enabled and covers the file
+        if let Some(ls_style) = ls_colors().and_then(|c| c.style(&self.path, self.is_executable)) {
+            style = ls_style;
+        }
+        if verified == Some(false) {
+            style = style.red();
         }
         if self.is_marked {
             style = style.with(color_marked());
@@ -109,13 +361,70 @@ impl DirElem {
         if selected {
             style = style.negative().bold();
         }
-        PrintStyledContent(StyledContent::new(style, string))
+        // The dot of a tagged item replaces the leading space
+        let tag = tags::dot_color(&self.path);
+        let string = match tag {
+            Some(_) => string[1..].to_string(),
+            None => string,
+        };
+        PrintEntry {
+            tag,
+            content: PrintStyledContent(StyledContent::new(style, string)),
+        }
+    }
+
+    /// Prints the columns of the detail view (permissions and modification time).
+    ///
+    /// The element must have been normalized before, which is done by [`DirElem::print_styled`].
+    fn queue_details(
+        &self,
+        stdout: &mut Stdout,
+        columns: DetailColumns,
+        selected: bool,
+    ) -> Result<()> {
+        if columns.permissions {
+            let permissions = unix_mode::to_string(self.mode);
+            for c in styled_permissions(&permissions) {
+                let c = if selected { c.negative() } else { c };
+                queue!(stdout, PrintStyledContent(c))?;
+            }
+            queue!(stdout, Print(" "))?;
+        }
+        if columns.modified {
+            let modified = self
+                .modified
+                .map(OffsetDateTime::from)
+                .map(|t| {
+                    format!(
+                        "{}-{:02}-{:02} {:02}:{:02} ",
+                        t.year(),
+                        u8::from(t.month()),
+                        t.day(),
+                        t.hour(),
+                        t.minute(),
+                    )
+                })
+                .unwrap_or_default()
+                .exact_width(MODIFIED_WIDTH as usize);
+            let style = if selected {
+                modified.dark_grey().negative()
+            } else {
+                modified.dark_grey()
+            };
+            queue!(stdout, PrintStyledContent(style))?;
+        }
+        Ok(())
+    }
+
+    /// Re-reads the metadata of the element, e.g. after its permissions have changed.
+    pub fn refresh(&mut self) {
+        // Don't use metadata that was read in the background, it may be outdated
+        self.set_metadata(Normalized::read(&self.path));
     }
 
     /// Normalizes the `DirElem` to make it viewable by the user.
     ///
     /// Normalization means that:
-    /// - the path is canonicalized
     /// - the metadata was parsed
     /// - the file-size or directory-size is parsed
     ///
@@ -124,32 +433,25 @@ impl DirElem {
     /// it will eat up a lot of time.
     /// To work with the `DirElem` itself however, all of this is not necessary.
     /// It only becomes mandatory, once we want to display it.
+    /// Elements around the visible ones are normalized in the background (see [`normalize`]).
     pub fn normalize(&mut self) {
         if self.is_normalized {
             return;
         }
-        // Always use an absolute pathhere
-        self.path.canonicalize().unwrap_or_default();
-
-        let (mode, size) = self
-            .path
-            .metadata()
-            .map(|m| (m.permissions().mode(), m.size()))
-            .unwrap_or_default();
+        let metadata = normalize::take(&self.path).unwrap_or_else(|| Normalized::read(&self.path));
+        self.set_metadata(metadata);
+    }
 
+    fn set_metadata(&mut self, metadata: Normalized) {
+        let mode = metadata.mode;
         self.is_executable =
             is_allowed(unix_mode::Accessor::User, unix_mode::Access::Execute, mode)
                 | is_allowed(unix_mode::Accessor::Group, unix_mode::Access::Execute, mode)
                 | is_allowed(unix_mode::Accessor::Other, unix_mode::Access::Execute, mode);
-
-        self.suffix = if self.path.is_dir() {
-            read_dir(&self.path)
-                .map(|res| res.into_iter().count().to_string())
-                .unwrap_or_default()
-        } else {
-            file_size_str(size)
-        };
-
+        self.mode = mode;
+        self.modified = metadata.modified;
+        self.is_locked = metadata.is_locked;
+        self.suffix = metadata.suffix;
         self.is_normalized = true;
     }
 }
@@ -172,14 +474,19 @@ impl<P: AsRef<Path>> From<P> for DirElem {
         let suffix = "".into();
         let is_executable = false;
         let path = path.as_ref().to_path_buf();
+        let is_dir = vfs::is_dir(&path);
 
         DirElem {
             name,
             lowercase,
             path,
+            is_dir,
             is_hidden,
             suffix,
+            mode: 0,
+            modified: None,
             is_executable,
+            is_locked: false,
             is_marked: false,
             is_normalized: false,
         }
@@ -194,8 +501,8 @@ impl AsRef<DirElem> for DirElem {
 
 impl PartialOrd for DirElem {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        if self.path.is_dir() {
-            if other.path.is_dir() {
+        if self.is_dir {
+            if other.is_dir {
                 return self
                     .name()
                     .to_lowercase()
@@ -203,7 +510,7 @@ impl PartialOrd for DirElem {
             } else {
                 Some(Ordering::Less)
             }
-        } else if other.path.is_dir() {
+        } else if other.is_dir {
             Some(Ordering::Greater)
         } else {
             return self
@@ -214,6 +521,33 @@ impl PartialOrd for DirElem {
     }
 }
 
+/// Styled name of an entry, with a colored dot in front of tagged items
+/// (see [`DirElem::print_styled`]).
+pub struct PrintEntry {
+    tag: Option<Color>,
+    content: PrintStyledContent<String>,
+}
+
+impl crossterm::Command for PrintEntry {
+    fn write_ansi(&self, f: &mut impl std::fmt::Write) -> std::fmt::Result {
+        if let Some(color) = self.tag {
+            PrintStyledContent("●".with(color)).write_ansi(f)?;
+        }
+        self.content.write_ansi(f)
+    }
+}
+
+/// Result of [`DirPanel::select_next_marked`] and [`DirPanel::select_prev_marked`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MarkedJump {
+    /// Selected a marked item in search direction
+    Found,
+    /// Selected a marked item after continuing at the other end of the directory
+    Wrapped,
+    /// There is no (further) marked item
+    NotFound,
+}
+
 #[derive(Debug, Clone)]
 pub struct DirPanel {
     /// Elements of the directory
@@ -245,11 +579,32 @@ pub struct DirPanel {
     /// Last modification time.
     modified: SystemTime,
 
-    /// Weather or not the panel is still loading some data
-    loading: bool,
+    /// Since when the panel is loading some data (if it is still loading)
+    loading: Option<Instant>,
+
+    /// Weather or not the directory is still being read, and only the first elements are shown
+    partial: bool,
 
     /// Weather or not to show hidden files
     show_hidden: bool,
+
+    /// Weather or not to show the detail columns
+    show_details: bool,
+
+    /// Sort mode that the elements were sorted with
+    sort_mode: SortMode,
+
+    /// Collapsed groups (with their elements), if the elements are grouped by extension
+    groups: Option<Vec<(GroupKey, Vec<DirElem>)>>,
+
+    /// Statistics that are shown above the elements (only used for previews)
+    stats: Option<DirStats>,
+
+    /// True if the directory could not be read, because we lack the permissions
+    denied: bool,
+
+    /// Anchor of the visual selection and the marks from before it started
+    visual: Option<(usize, Vec<bool>)>,
 }
 
 impl Draw for DirPanel {
@@ -260,23 +615,36 @@ impl Draw for DirPanel {
         y_range: Range<u16>,
     ) -> Result<()> {
         let width = x_range.end.saturating_sub(x_range.start);
+        // The statistics take the first line
+        let y_range = match &self.stats {
+            Some(stats) if y_range.len() > 1 => {
+                queue!(
+                    stdout,
+                    cursor::MoveTo(x_range.start, y_range.start),
+                    print_vertical_bar(),
+                    PrintStyledContent(
+                        format!(" {}", stats.summary())
+                            .exact_width(width.saturating_sub(1) as usize)
+                            .dark_grey()
+                            .italic()
+                    ),
+                )?;
+                y_range.start + 1..y_range.end
+            }
+            _ => y_range,
+        };
         let height = y_range.end.saturating_sub(y_range.start);
-
-        // Calculate page-scroll
-        let h = (height.saturating_add(1)) as usize / 2;
-        let bot = if self.show_hidden {
-            self.elements.len().min(self.selected_idx.saturating_add(h))
+        let columns = if self.show_details {
+            DetailColumns::fit(width)
         } else {
-            self.non_hidden
-                .len()
-                .min(self.non_hidden_idx.saturating_add(h))
-        };
-        let scroll: usize = {
-            // if selected should be in the middle all the time:
-            // bot = min(max-items, selected + height / 2)
-            // scroll = min(0, bot - (height + 1))
-            bot.saturating_sub(height as usize)
+            DetailColumns::default()
         };
+        let name_width = width.saturating_sub(columns.width());
+
+        let scroll = self.scroll(height);
+        if self.search.is_none() {
+            self.prefetch(scroll, height as usize);
+        }
 
         // Then print new buffer
         let mut y_offset = 0_u16;
@@ -297,8 +665,9 @@ impl Draw for DirPanel {
                         stdout,
                         cursor::MoveTo(x_range.start, y),
                         print_vertical_bar(),
-                        entry.print_styled(false, width),
+                        entry.print_styled(false, name_width),
                     )?;
+                    entry.queue_details(stdout, columns, false)?;
                     let pattern_x = x_range.start + 4 + offset as u16;
                     if pattern_x <= width {
                         queue!(
@@ -328,24 +697,26 @@ impl Draw for DirPanel {
             }
         } else {
             if let Some((new_element, is_dir)) = &self.new_element {
-                let lowercase_name = new_element.to_lowercase();
+                // Items in subdirectories are shown at the position of the first directory
+                let first_component = new_element.split('/').next().unwrap_or_default();
+                let lowercase_name = first_component.to_lowercase();
                 let (partition, symbol) = if *is_dir {
                     (
                         self.elements
                             // NOTE: This only works, because everything is sorted by name
                             .partition_point(|elem| {
-                                elem.path().is_dir() && (elem.lowercase < lowercase_name)
+                                elem.is_dir && (elem.lowercase < lowercase_name)
                             }),
-                        "\u{1F4C1}",
+                        SymbolEngine::dir_symbol(),
                     )
                 } else {
                     (
                         self.elements
                             // NOTE: This only works, because everything is sorted by name
                             .partition_point(|elem| {
-                                elem.path().is_dir() || (elem.lowercase < lowercase_name)
+                                elem.is_dir || (elem.lowercase < lowercase_name)
                             }),
-                        "\u{1F5B9} ",
+                        SymbolEngine::file_symbol(),
                     )
                 };
                 log::debug!("new_element: {new_element}, partition-point: {partition}");
@@ -377,8 +748,9 @@ impl Draw for DirPanel {
                         stdout,
                         cursor::MoveTo(x_range.start, y_range.start + y_offset),
                         print_vertical_bar(),
-                        entry.print_styled(self.selected_idx == idx, width),
+                        entry.print_styled(self.selected_idx == idx, name_width),
                     )?;
+                    entry.queue_details(stdout, columns, self.selected_idx == idx)?;
                     y_offset += 1;
                 }
                 if y_offset as usize == partition && !new_element.is_empty() {
@@ -395,6 +767,14 @@ impl Draw for DirPanel {
                     )?;
                     y_offset += 1;
                 }
+            } else if self.groups.is_some() {
+                y_offset = self.draw_groups(
+                    stdout,
+                    x_range.clone(),
+                    y_range.clone(),
+                    name_width,
+                    columns,
+                )?;
             } else {
                 // Write "height" items to the screen
                 for (idx, entry) in self
@@ -410,8 +790,9 @@ impl Draw for DirPanel {
                         stdout,
                         cursor::MoveTo(x_range.start, y),
                         print_vertical_bar(),
-                        entry.print_styled(self.selected_idx == idx, width),
+                        entry.print_styled(self.selected_idx == idx, name_width),
                     )?;
+                    entry.queue_details(stdout, columns, self.selected_idx == idx)?;
                     y_offset += 1;
                 }
             }
@@ -429,11 +810,24 @@ impl Draw for DirPanel {
         }
 
         // Check if we are loading or not
-        if self.loading {
+        if let Some(since) = self.loading {
+            let elapsed = since.elapsed();
+            let frame = SPINNER
+                [(elapsed.as_millis() / SPINNER_INTERVAL.as_millis()) as usize % SPINNER.len()];
+            let mut status = format!("{frame} Loading...");
+            if elapsed.as_secs() > 0 {
+                status.push_str(&format!(" {}s", elapsed.as_secs()));
+            }
             queue!(
                 stdout,
                 cursor::MoveTo(x_range.start + 2, y_range.start + 1),
-                PrintStyledContent("Loading...".with(color_main()).bold().italic()),
+                PrintStyledContent(
+                    status
+                        .exact_width(width.saturating_sub(2) as usize)
+                        .with(color_main())
+                        .bold()
+                        .italic()
+                ),
                 cursor::MoveTo(x_range.start + 2, y_range.start + 2),
                 PrintStyledContent(
                     format!("{}", self.path.display())
@@ -442,10 +836,26 @@ impl Draw for DirPanel {
                         .italic()
                 ),
             )?;
+            if elapsed >= LOADING_TIMEOUT && y_range.end > y_range.start + 4 {
+                queue!(
+                    stdout,
+                    cursor::MoveTo(x_range.start + 2, y_range.start + 4),
+                    PrintStyledContent(
+                        "This takes a while - move on to cancel it"
+                            .exact_width(width.saturating_sub(2) as usize)
+                            .dark_grey()
+                            .italic()
+                    ),
+                )?;
+            }
         } else if self.elements.is_empty() {
             if let Some((new_element, is_dir)) = &self.new_element {
                 if !new_element.is_empty() {
-                    let symbol = if *is_dir { "\u{1F4C1}" } else { "\u{1F5B9} " };
+                    let symbol = if *is_dir {
+                        SymbolEngine::dir_symbol()
+                    } else {
+                        SymbolEngine::file_symbol()
+                    };
                     queue!(
                         stdout,
                         cursor::MoveTo(x_range.start + 1, y_range.start),
@@ -463,6 +873,12 @@ impl Draw for DirPanel {
                         PrintStyledContent("(empty)".dark_grey().italic()),
                     )?;
                 }
+            } else if self.denied {
+                queue!(
+                    stdout,
+                    cursor::MoveTo(x_range.start + 1, y_range.start),
+                    PrintStyledContent("(permission denied)".red().italic()),
+                )?;
             } else {
                 queue!(
                     stdout,
@@ -485,10 +901,20 @@ impl PanelContent for DirPanel {
     }
 
     fn update_content(&mut self, mut content: Self) {
-        // Keep "hidden" state
+        // Cached panels may have been sorted differently
+        content.resort();
+        // Keep "hidden" and "details" state
         content.show_hidden = self.show_hidden;
+        content.show_details = self.show_details;
         // If the content is for the same directory
         if content.path == self.path {
+            // Keep the groups, and which of them are collapsed
+            if let Some(collapsed) = &self.groups {
+                content.toggle_groups();
+                for (key, _) in collapsed {
+                    content.collapse_group(key.clone());
+                }
+            }
             // Set the selection accordingly
             if let Some(path) = self.selected_path() {
                 content.select_path(path, Some(self.selected_idx));
@@ -496,6 +922,18 @@ impl PanelContent for DirPanel {
         }
         *self = content;
     }
+
+    fn mem_size(&self) -> usize {
+        let grouped = self.groups.iter().flatten().flat_map(|(_, elems)| elems);
+        std::mem::size_of::<Self>()
+            + self.non_hidden.len() * std::mem::size_of::<usize>()
+            + self
+                .elements
+                .iter()
+                .chain(grouped)
+                .map(DirElem::mem_size)
+                .sum::<usize>()
+    }
 }
 
 impl BasePanel for DirPanel {
@@ -507,18 +945,28 @@ impl BasePanel for DirPanel {
         DirPanel::loading(path)
     }
 
+    fn is_loading(&self) -> bool {
+        self.loading.is_some()
+    }
+
     fn from_path(path: PathBuf) -> Self {
-        let content = dir_content(path.clone());
-        DirPanel::new(content, path)
+        // Huge directories are read completely in the background
+        match dir_content_head(&path, CHUNK_SIZE) {
+            Ok((content, true)) => DirPanel::partial(content, path),
+            Ok((content, false)) => DirPanel::new(content, path),
+            Err(e) if e.kind() == std::io::ErrorKind::PermissionDenied => DirPanel::denied(path),
+            Err(_) => DirPanel::new(Vec::new(), path),
+        }
     }
 }
 
 impl DirPanel {
     pub fn new(mut elements: Vec<DirElem>, path: PathBuf) -> Self {
         // Sort the elements before you use them
-        elements.sort_by_cached_key(|a| a.name_lowercase().clone());
-        elements.sort_by_cached_key(|a| !a.path().is_dir());
+        let sort_mode = sort_mode();
+        sort_elements(&mut elements, sort_mode);
         // Normalize the first elements, so the first drawing is still really quick
+        normalize::forget(&path);
         elements.iter_mut().take(128).for_each(|e| e.normalize());
 
         let non_hidden = elements
@@ -530,10 +978,9 @@ impl DirPanel {
 
         let selected = *non_hidden.first().unwrap_or(&0);
 
-        let modified = path
-            .metadata()
+        let modified = vfs::metadata(&path)
             .ok()
-            .and_then(|m| m.modified().ok())
+            .and_then(|m| m.modified)
             .unwrap_or_else(SystemTime::now);
 
         DirPanel {
@@ -545,12 +992,300 @@ impl DirPanel {
             new_element: None,
             path,
             modified,
-            loading: false,
+            loading: None,
+            partial: false,
             show_hidden: false,
+            show_details: false,
+            sort_mode,
+            groups: None,
+            stats: None,
+            denied: false,
+            visual: None,
+        }
+    }
+
+    /// Sorts the elements again, if the sort mode has changed since the panel was created.
+    ///
+    /// Marks and the selection are kept.
+    pub fn resort(&mut self) {
+        let mode = sort_mode();
+        if self.sort_mode == mode {
+            return;
+        }
+        let grouped = self.groups.is_some();
+        self.rearrange(|elements| {
+            sort_elements(elements, mode);
+            if grouped {
+                elements.sort_by_cached_key(group_key);
+            }
+        });
+        self.sort_mode = mode;
+    }
+
+    /// Changes the order (or number) of the elements, while the selection stays the same.
+    fn rearrange<F: FnOnce(&mut Vec<DirElem>)>(&mut self, f: F) {
+        let selected = self.selected_path().map(Path::to_path_buf);
+        f(&mut self.elements);
+        self.non_hidden = self
+            .elements
+            .iter()
+            .enumerate()
+            .filter(|(_, elem)| !elem.is_hidden)
+            .map(|(idx, _)| idx)
+            .collect();
+        // If the selected item is gone, the one that took its place is selected
+        self.selected_idx = selected
+            .and_then(|path| self.elements.iter().position(|elem| elem.path == path))
+            .unwrap_or(self.selected_idx)
+            .min(self.elements.len().saturating_sub(1));
+        self.non_hidden_idx = 0;
+        self.set_non_hidden_idx();
+        if !self.show_hidden {
+            self.selected_idx = *self.non_hidden.get(self.non_hidden_idx).unwrap_or(&0);
+        }
+    }
+
+    /// Groups the elements by their extension, or shows them in the normal order again.
+    pub fn toggle_groups(&mut self) {
+        match self.groups.take() {
+            Some(collapsed) => {
+                let mode = self.sort_mode;
+                self.rearrange(|elements| {
+                    elements.extend(collapsed.into_iter().flat_map(|(_, group)| group));
+                    sort_elements(elements, mode);
+                });
+            }
+            None => {
+                self.rearrange(|elements| elements.sort_by_cached_key(group_key));
+                self.groups = Some(Vec::new());
+            }
+        }
+    }
+
+    /// Returns true if the elements are grouped by their extension.
+    pub fn is_grouped(&self) -> bool {
+        self.groups.is_some()
+    }
+
+    /// Collapses the group of the selected item.
+    ///
+    /// If the selected item is the first one below a collapsed group, that group is expanded instead.
+    pub fn toggle_group(&mut self) {
+        let Some(collapsed) = &self.groups else {
+            return;
+        };
+        let selected = self.selected().map(group_key);
+        // Group of the visible item above the selection
+        let previous = self
+            .elements
+            .iter()
+            .take(self.selected_idx)
+            .rfind(|elem| self.show_hidden || !elem.is_hidden)
+            .map(group_key);
+        // Nearest collapsed group between the previous and the selected item
+        let above = collapsed
+            .iter()
+            .map(|(key, _)| key)
+            .filter(|key| previous.as_ref().is_none_or(|prev| *key > prev))
+            .filter(|key| selected.as_ref().is_none_or(|sel| *key < sel))
+            .max()
+            .cloned();
+        match (above, selected) {
+            (Some(key), _) => self.expand_group(&key),
+            (None, Some(key)) => self.collapse_group(key),
+            (None, None) => (),
+        }
+    }
+
+    fn collapse_group(&mut self, key: GroupKey) {
+        let mut group = Vec::new();
+        self.rearrange(|elements| {
+            let (inside, outside) = std::mem::take(elements)
+                .into_iter()
+                .partition(|elem| group_key(elem) == key);
+            group = inside;
+            *elements = outside;
+        });
+        if let Some(collapsed) = &mut self.groups {
+            collapsed.push((key, group));
+        }
+    }
+
+    fn expand_group(&mut self, key: &GroupKey) {
+        let Some(collapsed) = &mut self.groups else {
+            return;
+        };
+        let Some(pos) = collapsed.iter().position(|(k, _)| k == key) else {
+            return;
+        };
+        let (_, group) = collapsed.remove(pos);
+        self.rearrange(|elements| {
+            let pos = elements.partition_point(|elem| group_key(elem) < *key);
+            elements.splice(pos..pos, group);
+        });
+    }
+
+    /// Expands all collapsed groups.
+    pub fn expand_groups(&mut self) {
+        let keys: Vec<GroupKey> = self
+            .groups
+            .iter()
+            .flatten()
+            .map(|(key, _)| key.clone())
+            .collect();
+        for key in keys {
+            self.expand_group(&key);
+        }
+    }
+
+    /// Rows of the grouped view: a header for every group, followed by its elements (unless it is collapsed).
+    fn group_rows(&self) -> Vec<GroupRow> {
+        let visible = |elem: &&DirElem| self.show_hidden || !elem.is_hidden;
+        let mut collapsed: Vec<(&GroupKey, usize)> = self
+            .groups
+            .iter()
+            .flatten()
+            .map(|(key, group)| (key, group.iter().filter(visible).count()))
+            .collect();
+        collapsed.sort();
+        let mut collapsed = collapsed.into_iter().peekable();
+        let collapsed_header = |(key, count): (&GroupKey, usize)| GroupRow::Header {
+            label: group_label(key).to_string(),
+            count,
+            collapsed: true,
+        };
+
+        let mut rows = Vec::new();
+        let mut current: Option<(GroupKey, usize)> = None;
+        for (idx, elem) in self.elements.iter().enumerate().filter(|(_, e)| visible(e)) {
+            let key = group_key(elem);
+            match &mut current {
+                Some((current_key, header)) if *current_key == key => {
+                    if let GroupRow::Header { count, .. } = &mut rows[*header] {
+                        *count += 1;
+                    }
+                }
+                _ => {
+                    while let Some(group) = collapsed.next_if(|(k, _)| **k < key) {
+                        rows.push(collapsed_header(group));
+                    }
+                    rows.push(GroupRow::Header {
+                        label: group_label(&key).to_string(),
+                        count: 1,
+                        collapsed: false,
+                    });
+                    current = Some((key, rows.len() - 1));
+                }
+            }
+            rows.push(GroupRow::Elem(idx));
+        }
+        rows.extend(collapsed.map(collapsed_header));
+        rows
+    }
+
+    /// Draws the grouped view and returns the number of rows.
+    fn draw_groups(
+        &mut self,
+        stdout: &mut Stdout,
+        x_range: Range<u16>,
+        y_range: Range<u16>,
+        name_width: u16,
+        columns: DetailColumns,
+    ) -> Result<u16> {
+        let height = y_range.end.saturating_sub(y_range.start);
+        let rows = self.group_rows();
+        // Keep the selection in the middle, like in the normal view
+        let selected_row = rows
+            .iter()
+            .position(|row| matches!(row, GroupRow::Elem(idx) if *idx == self.selected_idx))
+            .unwrap_or_default();
+        let bottom = rows
+            .len()
+            .min(selected_row.saturating_add((height as usize).div_ceil(2)));
+        let scroll = bottom.saturating_sub(height as usize);
+
+        let mut y_offset = 0;
+        for row in rows.into_iter().skip(scroll).take(height as usize) {
+            let y = y_range.start + y_offset;
+            match row {
+                GroupRow::Header {
+                    label,
+                    count,
+                    collapsed,
+                } => {
+                    let symbol = if collapsed { '▸' } else { '▾' };
+                    let header = format!(" {symbol} {label} ({count})")
+                        .exact_width(name_width.saturating_sub(1) as usize);
+                    let header = if collapsed {
+                        header.dark_grey().bold()
+                    } else {
+                        header.with(color_main()).bold()
+                    };
+                    queue!(
+                        stdout,
+                        cursor::MoveTo(x_range.start, y),
+                        print_vertical_bar(),
+                        PrintStyledContent(header),
+                    )?;
+                }
+                GroupRow::Elem(idx) => {
+                    let selected = self.selected_idx == idx;
+                    let entry = &mut self.elements[idx];
+                    queue!(
+                        stdout,
+                        cursor::MoveTo(x_range.start, y),
+                        print_vertical_bar(),
+                        entry.print_styled(selected, name_width),
+                    )?;
+                    entry.queue_details(stdout, columns, selected)?;
+                }
+            }
+            y_offset += 1;
+        }
+        Ok(y_offset)
+    }
+
+    /// Reads the statistics of the directory, which are shown above the elements.
+    ///
+    /// This reads the metadata of all elements, so it should not be called on the main thread.
+    pub fn with_stats(mut self) -> Self {
+        self.stats = Some(DirStats::collect(&self.elements));
+        self
+    }
+
+    /// Returns the statistics, if they were read.
+    pub fn stats(&self) -> Option<&DirStats> {
+        self.stats.as_ref()
+    }
+
+    /// Creates a panel from the elements that have been read so far,
+    /// while the rest of a (huge) directory is still being read.
+    pub fn partial(elements: Vec<DirElem>, path: PathBuf) -> Self {
+        DirPanel {
+            partial: true,
+            ..DirPanel::new(elements, path)
         }
     }
 
+    /// Creates an empty panel for a directory that we are not allowed to read.
+    pub fn denied(path: PathBuf) -> Self {
+        DirPanel {
+            denied: true,
+            visual: None,
+            ..DirPanel::new(Vec::new(), path)
+        }
+    }
+
+    /// Returns true if the directory is still being read (see [`DirPanel::partial`]).
+    pub fn is_partial(&self) -> bool {
+        self.partial
+    }
+
+    /// Shows the item, that is about to be created, at its position in the panel.
+    ///
+    /// For a nested path like `a/b/c` that is the position of the directory `a`.
     pub fn inject_new_element(&mut self, new_element: String, is_dir: bool) {
+        let is_dir = is_dir || new_element.trim_end_matches('/').contains('/');
         self.new_element = Some((new_element, is_dir));
     }
 
@@ -587,12 +1322,149 @@ impl DirPanel {
         self.show_hidden
     }
 
+    /// Returns the width that the longest visible entry needs to be printed without truncation.
+    ///
+    /// The size suffix is only known for normalized entries, so some space is reserved for it.
+    pub fn content_width(&self) -> u16 {
+        self.elements
+            .iter()
+            .filter(|elem| self.show_hidden || !elem.is_hidden)
+            .map(|elem| {
+                unicode_width(&elem.name)
+                    + elem.suffix.chars().count().max(SUFFIX_WIDTH) as u64
+                    + ENTRY_PADDING
+            })
+            .max()
+            .unwrap_or_default()
+            .try_into()
+            .unwrap_or(u16::MAX)
+    }
+
+    /// Shows or hides the detail columns (permissions and modification time).
+    pub fn toggle_details(&mut self) {
+        self.show_details = !self.show_details;
+    }
+
     pub fn mark_selected_item(&mut self) {
         if let Some(elem) = self.elements.get_mut(self.selected_idx) {
             elem.is_marked = !elem.is_marked;
         }
     }
 
+    /// Re-reads the metadata of the selected item.
+    pub fn refresh_selected_item(&mut self) {
+        if let Some(elem) = self.elements.get_mut(self.selected_idx) {
+            elem.refresh();
+        }
+    }
+
+    /// Returns all elements that are visible with the current hidden-file state.
+    fn visible_elements_mut(&mut self) -> impl Iterator<Item = &mut DirElem> {
+        let show_hidden = self.show_hidden;
+        self.elements
+            .iter_mut()
+            .filter(move |elem| show_hidden || !elem.is_hidden)
+    }
+
+    /// Marks all visible items whose name matches the glob pattern (e.g. `*.png`).
+    ///
+    /// Returns the number of newly marked items.
+    pub fn mark_glob(&mut self, pattern: &str) -> usize {
+        let mut n_marked = 0;
+        for elem in self.visible_elements_mut() {
+            if !elem.is_marked && glob_match(pattern, &elem.name) {
+                elem.is_marked = true;
+                n_marked += 1;
+            }
+        }
+        n_marked
+    }
+
+    /// Marks all visible items that were modified within the last `age`,
+    /// or before that, if `older` is true.
+    ///
+    /// Returns the number of newly marked items.
+    pub fn mark_by_age(&mut self, age: Duration, older: bool) -> usize {
+        let limit = SystemTime::now()
+            .checked_sub(age)
+            .unwrap_or(SystemTime::UNIX_EPOCH);
+        let mut n_marked = 0;
+        for elem in self.visible_elements_mut() {
+            elem.normalize();
+            let Some(modified) = elem.modified else {
+                continue;
+            };
+            if !elem.is_marked && (modified < limit) == older {
+                elem.is_marked = true;
+                n_marked += 1;
+            }
+        }
+        n_marked
+    }
+
+    /// Marks all visible files whose mime-type belongs to the category (directories are skipped).
+    ///
+    /// Returns the number of newly marked items.
+    pub fn mark_by_category(&mut self, category: MimeCategory) -> usize {
+        let mut n_marked = 0;
+        for elem in self.visible_elements_mut() {
+            if !elem.is_marked && !elem.is_dir && category.matches(&elem.path) {
+                elem.is_marked = true;
+                n_marked += 1;
+            }
+        }
+        n_marked
+    }
+
+    /// Starts a visual selection at the selected item.
+    pub fn start_visual(&mut self) {
+        let marks = self.elements.iter().map(|elem| elem.is_marked).collect();
+        self.visual = Some((self.selected_idx, marks));
+        self.update_visual();
+    }
+
+    /// Marks all visible items between the anchor of the visual selection and the selection.
+    ///
+    /// Items that leave the range get back the mark they had before the selection started.
+    pub fn update_visual(&mut self) {
+        let Some((anchor, marks)) = &self.visual else {
+            return;
+        };
+        let visible = self.visible_indices_where(|_| true);
+        let pos = |idx: usize| visible.iter().position(|i| *i == idx);
+        let (Some(a), Some(b)) = (pos(*anchor), pos(self.selected_idx)) else {
+            return;
+        };
+        for (elem, marked) in self.elements.iter_mut().zip(marks) {
+            elem.is_marked = *marked;
+        }
+        for idx in &visible[a.min(b)..=a.max(b)] {
+            self.elements[*idx].is_marked = true;
+        }
+    }
+
+    /// Ends the visual selection and keeps its marks.
+    pub fn end_visual(&mut self) {
+        self.visual = None;
+    }
+
+    /// Returns true if there is an active visual selection.
+    pub fn is_visual(&self) -> bool {
+        self.visual.is_some()
+    }
+
+    /// Marks all visible items.
+    pub fn mark_all(&mut self) {
+        self.visible_elements_mut()
+            .for_each(|elem| elem.is_marked = true);
+    }
+
+    /// Inverts the marks of all visible items.
+    pub fn invert_marks(&mut self) {
+        self.visible_elements_mut()
+            .for_each(|elem| elem.is_marked = !elem.is_marked);
+    }
+
     /// Changes the selection to the given path.
     ///
     /// If the path is not found, the selection remains unchanged.
@@ -631,64 +1503,107 @@ impl DirPanel {
         }
     }
 
-    /// Selects the next marked item
-    pub fn select_next_marked(&mut self) {
-        // Search from selected-idx to end
-        if let Some(idx) = self
-            .elements
+    /// Selects the next marked item.
+    ///
+    /// If there is no marked item below the selection, the search continues at the top
+    /// (if `wrap` is true).
+    pub fn select_next_marked(&mut self, wrap: bool) -> MarkedJump {
+        self.select_next_where(wrap, |elem| elem.is_marked)
+    }
+
+    /// Selects the next tagged item (see [`DirPanel::select_next_marked`]).
+    pub fn select_next_tagged(&mut self, wrap: bool) -> MarkedJump {
+        self.select_next_where(wrap, |elem| tags::is_tagged(&elem.path))
+    }
+
+    fn select_next_where(&mut self, wrap: bool, pred: impl Fn(&DirElem) -> bool) -> MarkedJump {
+        let marked = self.visible_indices_where(pred);
+        let result = match marked.iter().find(|idx| **idx > self.selected_idx) {
+            Some(idx) => Some((*idx, MarkedJump::Found)),
+            None if wrap => marked.first().map(|idx| (*idx, MarkedJump::Wrapped)),
+            None => None,
+        };
+        self.select_marked(result)
+    }
+
+    /// Selects the previous marked item.
+    ///
+    /// If there is no marked item above the selection, the search continues at the bottom
+    /// (if `wrap` is true).
+    pub fn select_prev_marked(&mut self, wrap: bool) -> MarkedJump {
+        self.select_prev_where(wrap, |elem| elem.is_marked)
+    }
+
+    /// Selects the previous tagged item (see [`DirPanel::select_prev_marked`]).
+    pub fn select_prev_tagged(&mut self, wrap: bool) -> MarkedJump {
+        self.select_prev_where(wrap, |elem| tags::is_tagged(&elem.path))
+    }
+
+    fn select_prev_where(&mut self, wrap: bool, pred: impl Fn(&DirElem) -> bool) -> MarkedJump {
+        let marked = self.visible_indices_where(pred);
+        let result = match marked.iter().rev().find(|idx| **idx < self.selected_idx) {
+            Some(idx) => Some((*idx, MarkedJump::Found)),
+            None if wrap => marked.last().map(|idx| (*idx, MarkedJump::Wrapped)),
+            None => None,
+        };
+        self.select_marked(result)
+    }
+
+    fn visible_indices_where(&self, pred: impl Fn(&DirElem) -> bool) -> Vec<usize> {
+        self.elements
             .iter()
             .enumerate()
-            .skip(self.selected_idx + 1)
-            .filter(|(_, elem)| self.show_hidden || !elem.is_hidden)
-            .find(|(_, elem)| elem.is_marked)
+            .filter(|(_, elem)| (self.show_hidden || !elem.is_hidden) && pred(elem))
             .map(|(idx, _)| idx)
-        {
-            self.selected_idx = idx;
-        } else {
-            // Search again from start
-            self.selected_idx = self
-                .elements
-                .iter()
-                .enumerate()
-                .filter(|(_, elem)| self.show_hidden || !elem.is_hidden)
-                .find(|(_, elem)| elem.is_marked)
-                .map(|(idx, _)| idx)
-                .unwrap_or(self.selected_idx);
-        }
+            .collect()
+    }
+
+    fn select_marked(&mut self, result: Option<(usize, MarkedJump)>) -> MarkedJump {
+        let Some((idx, jump)) = result else {
+            return MarkedJump::NotFound;
+        };
+        self.selected_idx = idx;
         if !self.show_hidden {
             self.set_non_hidden_idx();
         }
+        jump
     }
 
-    /// Selects the next marked item
-    pub fn select_prev_marked(&mut self) {
-        // Search from selected-idx to end
-        if let Some(idx) = self
-            .elements
-            .iter()
-            .enumerate()
-            .rev()
-            .filter(|(idx, _)| idx < &self.selected_idx)
-            .filter(|(_, elem)| self.show_hidden || !elem.is_hidden)
-            .find(|(_, elem)| elem.is_marked)
-            .map(|(idx, _)| idx)
-        {
-            self.selected_idx = idx;
+    /// Requests the background normalization of the pages above and below the visible elements.
+    ///
+    /// The closest elements are normalized first. Nothing is requested for elements further away,
+    /// so scrolling through huge directories only reads the metadata of what is shown next.
+    fn prefetch(&self, scroll: usize, height: usize) {
+        let count = if self.show_hidden {
+            self.elements.len()
         } else {
-            // Search again from end
-            self.selected_idx = self
-                .elements
-                .iter()
-                .enumerate()
-                .rev()
-                .filter(|(_, elem)| self.show_hidden || !elem.is_hidden)
-                .find(|(_, elem)| elem.is_marked)
-                .map(|(idx, _)| idx)
-                .unwrap_or(self.selected_idx);
-        }
-        if !self.show_hidden {
-            self.set_non_hidden_idx();
-        }
+            self.non_hidden.len()
+        };
+        let visible = scroll..scroll + height;
+        let mut paths = (scroll.saturating_sub(PREFETCH_PAGES * height)
+            ..(visible.end + PREFETCH_PAGES * height).min(count))
+            .filter(|n| !visible.contains(n))
+            .filter_map(|n| {
+                let idx = if self.show_hidden {
+                    n
+                } else {
+                    *self.non_hidden.get(n)?
+                };
+                let elem = self.elements.get(idx)?;
+                let distance = if n < scroll {
+                    scroll - n
+                } else {
+                    n - visible.end
+                };
+                (!elem.is_normalized).then(|| (distance, elem.path.clone()))
+            })
+            .collect::<Vec<_>>();
+        // Farthest first, as the queue is processed from the end
+        paths.sort_unstable_by_key(|(distance, _)| std::cmp::Reverse(*distance));
+        normalize::request(
+            &self.path,
+            paths.into_iter().map(|(_, path)| path).collect(),
+        );
     }
 
     /// Sets non-hidden-idx to the value closest to selection
@@ -727,8 +1642,15 @@ impl DirPanel {
             new_element: None,
             path,
             modified: SystemTime::now(),
-            loading: true,
+            loading: Some(Instant::now()),
+            partial: false,
             show_hidden: false,
+            show_details: false,
+            sort_mode: sort_mode(),
+            groups: None,
+            stats: None,
+            denied: false,
+            visual: None,
         }
     }
 
@@ -745,8 +1667,15 @@ impl DirPanel {
             new_element: None,
             modified: SystemTime::now(),
             path: "path-of-empty-panel".into(),
-            loading: false,
+            loading: None,
+            partial: false,
             show_hidden: false,
+            show_details: false,
+            sort_mode: sort_mode(),
+            groups: None,
+            stats: None,
+            denied: false,
+            visual: None,
         }
     }
 
@@ -804,6 +1733,23 @@ impl DirPanel {
         true
     }
 
+    /// Selects the `n`-th visible item (starting at zero), or the last one if `n` is too big.
+    ///
+    /// Returns true if the panel has changed and
+    /// requires a redraw.
+    pub fn select_nth(&mut self, n: usize) -> bool {
+        let current = if self.show_hidden {
+            self.selected_idx
+        } else {
+            self.non_hidden_idx
+        };
+        match n.cmp(&current) {
+            std::cmp::Ordering::Less => self.up(current - n),
+            std::cmp::Ordering::Equal => false,
+            std::cmp::Ordering::Greater => self.down(n - current),
+        }
+    }
+
     /// Returns the selected path of the panel.
     ///
     /// If the panel is empty `None` is returned.
@@ -826,6 +1772,44 @@ impl DirPanel {
         }
     }
 
+    /// Number of (visible) items that are scrolled out at the top, if the panel has the given height.
+    fn scroll(&self, height: u16) -> usize {
+        let h = (height.saturating_add(1)) as usize / 2;
+        let bot = if self.show_hidden {
+            self.elements.len().min(self.selected_idx.saturating_add(h))
+        } else {
+            self.non_hidden
+                .len()
+                .min(self.non_hidden_idx.saturating_add(h))
+        };
+        // if selected should be in the middle all the time:
+        // bot = min(max-items, selected + height / 2)
+        // scroll = min(0, bot - (height + 1))
+        bot.saturating_sub(height as usize)
+    }
+
+    /// Selects the item that is drawn in the given row (starting at 0) of a panel with the given height.
+    ///
+    /// Returns false, if there is no item in that row.
+    pub fn select_row(&mut self, row: u16, height: u16) -> bool {
+        // Search results and groups are drawn in a different order
+        if self.search.is_some() || self.groups.is_some() || row >= height {
+            return false;
+        }
+        let index = self.scroll(height) + row as usize;
+        let current = self.index();
+        let total = self.index_vs_total().1;
+        if index >= total {
+            return false;
+        }
+        if index < current {
+            self.up(current - index);
+        } else if index > current {
+            self.down(index - current);
+        }
+        true
+    }
+
     /// Returns a reference to the selected [`DirElem`].
     ///
     /// If the panel is empty `None` is returned.
@@ -842,3 +1826,190 @@ impl DirPanel {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{content::dir_content, util::is_traversable};
+
+    #[test]
+    fn jump_between_marked_items() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["a1", "b", "c1"] {
+            std::fs::write(dir.path().join(name), name).unwrap();
+        }
+        let mut panel = DirPanel::new(dir_content(dir.path()), dir.path().to_path_buf());
+        panel.finish_search("1");
+        let selected = |panel: &DirPanel| panel.selected_path().unwrap().to_path_buf();
+
+        assert_eq!(panel.select_next_marked(false), MarkedJump::Found);
+        assert_eq!(selected(&panel), dir.path().join("c1"));
+        assert_eq!(panel.select_next_marked(false), MarkedJump::NotFound);
+        assert_eq!(selected(&panel), dir.path().join("c1"));
+        assert_eq!(panel.select_next_marked(true), MarkedJump::Wrapped);
+        assert_eq!(selected(&panel), dir.path().join("a1"));
+        assert_eq!(panel.select_prev_marked(true), MarkedJump::Wrapped);
+        assert_eq!(selected(&panel), dir.path().join("c1"));
+    }
+
+    #[test]
+    fn group_by_extension() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["b.rs", "a.png", "c.rs", "notes"] {
+            std::fs::write(dir.path().join(name), name).unwrap();
+        }
+        std::fs::create_dir(dir.path().join("src")).unwrap();
+        let mut panel = DirPanel::new(dir_content(dir.path()), dir.path().to_path_buf());
+        let names = |panel: &DirPanel| -> Vec<String> {
+            panel.elements().map(|e| e.name().clone()).collect()
+        };
+        let headers = |panel: &DirPanel| -> Vec<String> {
+            panel
+                .group_rows()
+                .into_iter()
+                .filter_map(|row| match row {
+                    GroupRow::Header { label, count, .. } => Some(format!("{label} ({count})")),
+                    GroupRow::Elem(_) => None,
+                })
+                .collect()
+        };
+
+        panel.toggle_groups();
+        assert_eq!(names(&panel), ["src", "a.png", "b.rs", "c.rs", "notes"]);
+        assert_eq!(
+            headers(&panel),
+            ["directories (1)", "png (1)", "rs (2)", "no extension (1)"]
+        );
+
+        // Collapse the "rs" group, the selection moves to the next item
+        panel.select_path(&dir.path().join("c.rs"), None);
+        panel.toggle_group();
+        assert_eq!(names(&panel), ["src", "a.png", "notes"]);
+        assert_eq!(
+            panel.selected_path(),
+            Some(dir.path().join("notes").as_path())
+        );
+        assert_eq!(headers(&panel).len(), 4);
+
+        // The collapsed group is right above the selection, so it is expanded again
+        panel.toggle_group();
+        assert_eq!(names(&panel), ["src", "a.png", "b.rs", "c.rs", "notes"]);
+        assert_eq!(
+            panel.selected_path(),
+            Some(dir.path().join("notes").as_path())
+        );
+
+        panel.toggle_group();
+        panel.toggle_groups();
+        assert_eq!(names(&panel), ["src", "a.png", "b.rs", "c.rs", "notes"]);
+        assert!(!panel.is_grouped());
+    }
+
+    #[test]
+    fn directory_stats() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("a.txt"), "hello").unwrap();
+        std::fs::write(dir.path().join("b.txt"), "world!").unwrap();
+        std::fs::create_dir(dir.path().join("sub")).unwrap();
+        let panel = DirPanel::new(dir_content(dir.path()), dir.path().to_path_buf()).with_stats();
+        let stats = panel.stats().unwrap();
+        assert_eq!((stats.dirs, stats.files, stats.size), (1, 2, 11));
+        assert!(stats.complete && stats.newest.is_some());
+        assert!(stats
+            .summary()
+            .starts_with("1 dirs, 2 files, 11 B, newest "));
+    }
+
+    #[test]
+    fn mark_by_modification_time() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("new.txt"), "").unwrap();
+        let old = std::fs::File::create(dir.path().join("old.txt")).unwrap();
+        old.set_modified(SystemTime::now() - Duration::from_secs(10 * 24 * 60 * 60))
+            .unwrap();
+        let mut panel = DirPanel::new(dir_content(dir.path()), dir.path().to_path_buf());
+        let day = Duration::from_secs(24 * 60 * 60);
+        assert_eq!(panel.mark_by_age(7 * day, false), 1);
+        let marked: Vec<&String> = panel
+            .elements()
+            .filter(|elem| elem.is_marked())
+            .map(|elem| elem.name())
+            .collect();
+        assert_eq!(marked, ["new.txt"]);
+        assert_eq!(panel.mark_by_age(7 * day, true), 1);
+        assert_eq!(panel.mark_by_age(30 * day, false), 0);
+    }
+
+    #[test]
+    fn mark_by_mime_category() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["a.png", "b.jpg", "c.txt", "d.zip"] {
+            std::fs::write(dir.path().join(name), "").unwrap();
+        }
+        std::fs::create_dir(dir.path().join("e.png")).unwrap();
+        let mut panel = DirPanel::new(dir_content(dir.path()), dir.path().to_path_buf());
+        assert_eq!(panel.mark_by_category(MimeCategory::Images), 2);
+        assert_eq!(panel.mark_by_category(MimeCategory::Archives), 1);
+        let marked: Vec<&String> = panel
+            .elements()
+            .filter(|elem| elem.is_marked())
+            .map(|elem| elem.name())
+            .collect();
+        assert_eq!(marked, ["a.png", "b.jpg", "d.zip"]);
+        assert_eq!(panel.mark_by_category(MimeCategory::Videos), 0);
+    }
+
+    #[test]
+    fn visual_selection() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["a", "b", "c", "d", "e"] {
+            std::fs::write(dir.path().join(name), "").unwrap();
+        }
+        let mut panel = DirPanel::new(dir_content(dir.path()), dir.path().to_path_buf());
+        let marked = |panel: &DirPanel| -> String {
+            panel
+                .elements()
+                .filter(|elem| elem.is_marked())
+                .map(|elem| elem.name().as_str())
+                .collect()
+        };
+        panel.select_nth(5);
+        panel.mark_all();
+        panel.invert_marks();
+        panel.elements_mut().last().unwrap().is_marked = true;
+        panel.up(3);
+        panel.start_visual();
+        assert_eq!(marked(&panel), "be");
+        panel.down(2);
+        panel.update_visual();
+        assert_eq!(marked(&panel), "bcde");
+        // Moving past the anchor restores the marks below it
+        panel.up(3);
+        panel.update_visual();
+        assert_eq!(marked(&panel), "abe");
+        panel.end_visual();
+        panel.down(4);
+        panel.update_visual();
+        assert_eq!(marked(&panel), "abe");
+    }
+
+    #[test]
+    fn unreadable_directories() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = tempfile::tempdir().unwrap();
+        let locked = dir.path().join("locked");
+        std::fs::create_dir(&locked).unwrap();
+        std::fs::set_permissions(&locked, std::fs::Permissions::from_mode(0o000)).unwrap();
+        let mut elem = DirElem::from(&locked);
+        elem.normalize();
+        // Root can read every directory
+        if !is_traversable(&locked) {
+            assert!(elem.is_locked);
+            assert!(DirPanel::from_path(locked.clone()).denied);
+        } else {
+            assert!(!elem.is_locked);
+        }
+        assert!(!DirPanel::from_path(dir.path().to_path_buf()).denied);
+        std::fs::set_permissions(&locked, std::fs::Permissions::from_mode(0o755)).unwrap();
+    }
+}
@@ -1,4 +1,4 @@
-use std::io::Stdout;
+use std::{io::Stdout, ops::Range};
 
 use crossterm::{
     event::{KeyCode, KeyModifiers},
@@ -10,6 +10,8 @@ use log::debug;
 pub struct Input {
     input: String,
     cursor: usize,
+    /// Selected text, that is replaced by the next typed character
+    selection: Option<Range<usize>>,
 }
 
 impl Input {
@@ -18,6 +20,7 @@ impl Input {
         Self {
             input: "".to_owned(),
             cursor: 0,
+            selection: None,
         }
     }
 
@@ -26,6 +29,36 @@ impl Input {
         Self {
             input: string.as_ref().to_owned(),
             cursor: string.as_ref().len(),
+            selection: None,
+        }
+    }
+
+    /// Creates an input element for renaming, where the stem of the filename is selected.
+    ///
+    /// Typing replaces the stem, while `End` (or `Right`) keeps the name and moves behind the extension.
+    /// Names of directories and hidden files without an extension are selected completely.
+    pub fn from_filename<S: AsRef<str>>(name: S, is_dir: bool) -> Self {
+        let name = name.as_ref();
+        let stem_end = match name.rfind('.') {
+            Some(idx) if idx > 0 && !is_dir => idx,
+            _ => name.len(),
+        };
+        Self {
+            input: name.to_owned(),
+            cursor: stem_end,
+            selection: Some(0..stem_end).filter(|s| !s.is_empty()),
+        }
+    }
+
+    /// Removes the selected text. Returns false, if nothing was selected.
+    fn delete_selection(&mut self) -> bool {
+        match self.selection.take() {
+            Some(selection) => {
+                self.cursor = selection.start;
+                self.input.drain(selection);
+                true
+            }
+            None => false,
         }
     }
 
@@ -69,6 +102,48 @@ impl Input {
             self.input.len(),
             self.cursor
         );
+        // Moving the cursor keeps the selected text, everything else replaces it
+        if let Some(selection) = self.selection.clone() {
+            match key_code {
+                KeyCode::Left => {
+                    self.selection = None;
+                    self.cursor = selection.start;
+                    return;
+                }
+                KeyCode::Right => {
+                    self.selection = None;
+                    self.cursor = selection.end;
+                    return;
+                }
+                KeyCode::Char(_) if !modifiers.contains(KeyModifiers::CONTROL) => {
+                    self.delete_selection();
+                }
+                KeyCode::Backspace | KeyCode::Delete => {
+                    self.delete_selection();
+                    return;
+                }
+                _ => self.selection = None,
+            }
+        }
+        if modifiers.contains(KeyModifiers::CONTROL) {
+            match key_code {
+                KeyCode::Char('a') => self.cursor = 0,
+                KeyCode::Char('e') => self.cursor = self.input.len(),
+                KeyCode::Char('w') => self.delete_word(),
+                KeyCode::Char('u') => {
+                    self.input.drain(..self.cursor);
+                    self.cursor = 0;
+                }
+                KeyCode::Left => {
+                    self.cursor = self.word_start();
+                }
+                KeyCode::Right => {
+                    self.cursor = self.word_end();
+                }
+                _ => (),
+            }
+            return;
+        }
         match key_code {
             KeyCode::Char(c) => {
                 let insert_char = if modifiers.contains(KeyModifiers::SHIFT) {
@@ -104,15 +179,73 @@ impl Input {
             KeyCode::Right => {
                 self.increase_cursor();
             }
+            KeyCode::Home => self.cursor = 0,
+            KeyCode::End => self.cursor = self.input.len(),
             _ => (),
         }
     }
 
+    /// Inserts text at the cursor (e.g. from a paste), line breaks are dropped.
+    pub fn insert_str(&mut self, text: &str) {
+        let text: String = text.chars().filter(|c| !c.is_control()).collect();
+        self.delete_selection();
+        self.input.insert_str(self.cursor, &text);
+        self.cursor += text.len();
+    }
+
+    /// Position of the start of the word before the cursor.
+    ///
+    /// Words are separated by everything that is not alphanumeric (like in a path or filename).
+    fn word_start(&self) -> usize {
+        let left = &self.input[..self.cursor];
+        let trimmed = left.trim_end_matches(|c: char| !c.is_alphanumeric());
+        trimmed
+            .rfind(|c: char| !c.is_alphanumeric())
+            .map(|idx| idx + trimmed[idx..].chars().next().map_or(1, char::len_utf8))
+            .unwrap_or(0)
+    }
+
+    /// Position of the end of the word after the cursor.
+    fn word_end(&self) -> usize {
+        let right = &self.input[self.cursor..];
+        let skipped = right.len()
+            - right
+                .trim_start_matches(|c: char| !c.is_alphanumeric())
+                .len();
+        right[skipped..]
+            .find(|c: char| !c.is_alphanumeric())
+            .map(|idx| self.cursor + skipped + idx)
+            .unwrap_or(self.input.len())
+    }
+
+    /// Deletes the word before the cursor.
+    fn delete_word(&mut self) {
+        let start = self.word_start();
+        self.input.drain(start..self.cursor);
+        self.cursor = start;
+    }
+
     pub fn get(&self) -> &str {
         &self.input
     }
 
+    /// Replaces the text and moves the cursor to the end.
+    fn set(&mut self, text: &str) {
+        self.input = text.to_owned();
+        self.cursor = self.input.len();
+        self.selection = None;
+    }
+
     pub fn print(&self, stdout: &mut Stdout, color: Color) -> crossterm::Result<()> {
+        if let Some(selection) = &self.selection {
+            let (left, rest) = self.input.split_at(selection.start);
+            let (selected, right) = rest.split_at(selection.len());
+            stdout
+                .queue(PrintStyledContent(left.bold().with(color)))?
+                .queue(PrintStyledContent(selected.bold().with(color).reverse()))?
+                .queue(PrintStyledContent(right.bold().with(color)))?;
+            return Ok(());
+        }
         let (left, right) = self.input.as_str().split_at(self.cursor);
         // let left: String = self.input.chars().take(self.cursor).collect();
         // let right: String = self.input.chars().skip(self.cursor).collect();
@@ -127,3 +260,160 @@ impl Input {
         Ok(())
     }
 }
+
+/// Maximum number of entries of an input history
+const MAX_HISTORY: usize = 100;
+
+/// Previous inputs of an input field, which can be recalled with `Up` and `Down`.
+#[derive(Default)]
+pub struct History {
+    entries: Vec<String>,
+    /// Index of the currently recalled entry
+    position: Option<usize>,
+    /// What was typed before the first entry was recalled
+    draft: String,
+}
+
+impl History {
+    /// Adds an entry to the history (the newest entry comes last).
+    pub fn push(&mut self, entry: &str) {
+        self.position = None;
+        if entry.is_empty() {
+            return;
+        }
+        self.entries.retain(|e| e != entry);
+        self.entries.push(entry.to_owned());
+        if self.entries.len() > MAX_HISTORY {
+            self.entries.remove(0);
+        }
+    }
+
+    /// Stops browsing the history, so that the next recall starts with the newest entry again.
+    pub fn reset(&mut self) {
+        self.position = None;
+    }
+
+    /// Replaces the input with an older (or newer) entry of the history.
+    ///
+    /// Going past the newest entry restores what was typed before.
+    pub fn browse(&mut self, input: &mut Input, older: bool) {
+        let position = match (self.position, older) {
+            (None, true) if !self.entries.is_empty() => {
+                self.draft = input.get().to_owned();
+                Some(self.entries.len() - 1)
+            }
+            (None, _) => return,
+            (Some(idx), true) => Some(idx.saturating_sub(1)),
+            (Some(idx), false) if idx + 1 < self.entries.len() => Some(idx + 1),
+            (Some(_), false) => None,
+        };
+        self.position = position;
+        match position {
+            Some(idx) => input.set(&self.entries[idx]),
+            None => input.set(&self.draft),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn type_keys(input: &mut Input, keys: &[(KeyCode, KeyModifiers)]) {
+        for (code, modifiers) in keys {
+            input.update(*code, *modifiers);
+        }
+    }
+
+    #[test]
+    fn edit_words() {
+        let ctrl = KeyModifiers::CONTROL;
+        let mut input = Input::from_str("my_notes.txt");
+        input.update(KeyCode::Char('w'), ctrl);
+        assert_eq!(input.get(), "my_notes.");
+        input.update(KeyCode::Char('w'), ctrl);
+        assert_eq!(input.get(), "my_");
+
+        type_keys(
+            &mut input,
+            &[
+                (KeyCode::Char('a'), ctrl),
+                (KeyCode::Char('x'), KeyModifiers::NONE),
+                (KeyCode::Char('e'), ctrl),
+            ],
+        );
+        input.insert_str("pasted\n");
+        assert_eq!(input.get(), "xmy_pasted");
+
+        type_keys(
+            &mut input,
+            &[
+                (KeyCode::Left, ctrl),
+                (KeyCode::Left, ctrl),
+                (KeyCode::Char('u'), ctrl),
+            ],
+        );
+        assert_eq!(input.get(), "xmy_pasted");
+        type_keys(
+            &mut input,
+            &[(KeyCode::Right, ctrl), (KeyCode::Char('u'), ctrl)],
+        );
+        assert_eq!(input.get(), "_pasted");
+    }
+
+    #[test]
+    fn rename_selects_stem() {
+        let none = KeyModifiers::NONE;
+        let mut input = Input::from_filename("holiday.jpeg", false);
+        type_keys(
+            &mut input,
+            &[(KeyCode::Char('b'), none), (KeyCode::Char('y'), none)],
+        );
+        assert_eq!(input.get(), "by.jpeg");
+
+        let mut input = Input::from_filename("holiday.jpeg", false);
+        type_keys(
+            &mut input,
+            &[(KeyCode::End, none), (KeyCode::Char('2'), none)],
+        );
+        assert_eq!(input.get(), "holiday.jpeg2");
+
+        let mut input = Input::from_filename("holiday.jpeg", false);
+        type_keys(&mut input, &[(KeyCode::Backspace, none)]);
+        assert_eq!(input.get(), ".jpeg");
+
+        let mut input = Input::from_filename("holiday.jpeg", false);
+        type_keys(
+            &mut input,
+            &[(KeyCode::Left, none), (KeyCode::Char('x'), none)],
+        );
+        assert_eq!(input.get(), "xholiday.jpeg");
+
+        // Hidden files and directories are selected completely
+        let mut input = Input::from_filename(".bashrc", false);
+        input.insert_str("profile");
+        assert_eq!(input.get(), "profile");
+        let mut input = Input::from_filename("photos.2024", true);
+        type_keys(&mut input, &[(KeyCode::Char('x'), none)]);
+        assert_eq!(input.get(), "x");
+    }
+
+    #[test]
+    fn browse_history() {
+        let mut history = History::default();
+        history.push("first");
+        history.push("second");
+        history.push("first");
+
+        let mut input = Input::from_str("draft");
+        history.browse(&mut input, true);
+        assert_eq!(input.get(), "first");
+        history.browse(&mut input, true);
+        assert_eq!(input.get(), "second");
+        history.browse(&mut input, true);
+        assert_eq!(input.get(), "second");
+        history.browse(&mut input, false);
+        history.browse(&mut input, false);
+        assert_eq!(input.get(), "draft");
+    }
+}
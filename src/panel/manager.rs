@@ -1,26 +1,93 @@
-use std::fs::OpenOptions;
+use std::{
+    fs::OpenOptions,
+    time::{Duration, Instant, SystemTime},
+};
 
+use anyhow::{bail, Context};
 use crossterm::{
-    event::{Event, EventStream, KeyCode},
-    style::PrintStyledContent,
-    terminal::{BeginSynchronizedUpdate, EndSynchronizedUpdate},
+    event::{
+        Event, EventStream, KeyCode, KeyEvent, KeyModifiers, MouseButton, MouseEvent,
+        MouseEventKind,
+    },
+    style::{PrintStyledContent, StyledContent},
+    terminal::{BeginSynchronizedUpdate, EndSynchronizedUpdate, SetTitle},
     ExecutableCommand,
 };
 use futures::{FutureExt, StreamExt};
 use log::{debug, error, info, trace, Level};
-use tempfile::TempDir;
+use tokio::sync::oneshot::{self, error::TryRecvError};
 
 use crate::{
-    config::color::{color_dir_path, color_main},
-    engine::commands::{CloseCmd, Command, CommandParser},
-    engine::OpenEngine,
+    config::{
+        color::{
+            color_dir_path, color_highlight, color_main, color_marked, colors_from_config,
+            cycle_theme,
+        },
+        Config, ConfigFiles, LayoutPreset,
+    },
+    content::{CacheProgress, CACHE_PAUSED},
+    engine::commands::{CloseCmd, Command, CommandParser, ImageCmd, KeyConfig},
+    engine::{
+        actions::{menu_entries, Action},
+        archive::{self, ArchiveSpec},
+        checksum,
+        clipboard::{Clipboard, ClipboardHistory, SharedClipboard, SystemClipboard},
+        doctor,
+        frecency::Frecency,
+        history::DirHistory,
+        opener::{self, extract, is_archive, MimeCategory, OpenerConfig},
+        protect::{find_protected, CONFIRMATION},
+        script::ScriptStep,
+        shortcut::{self, Shortcut},
+        staging, tags,
+        trash::{self, TrashBackend, TrashKind},
+        OpenEngine,
+    },
+    jobs::{self, Job, JobKind},
     logger::LogBuffer,
-    util::{copy_item, get_destination, move_item, print_metadata},
+    remote,
+    server::Request,
+    util::{
+        breadcrumbs, copy_to_clipboard, create_item, disk_usage, file_info, file_size_str,
+        get_destination, git_branch, link_item, parse_age, paste_from_clipboard, print_metadata,
+        read_text_file, styled_permissions, tilde_path, toggle_executable, total_size,
+        transfer_item, verify_copy, DiskUsage, FileInfo, LinkKind, TransferPlan,
+    },
+    vfs,
+};
+
+use self::console::{
+    draw_key_popup, zoxide_installed, ArchiveConsole, ClipboardConsole, Console, ConsoleOp,
+    DirConsole, DrivesConsole, ExtractConsole, GrepConsole, HelpConsole, JobsConsole, JumpConsole,
+    MenuConsole, PropertiesConsole, QueryConsole, QuerySource, RecentConsole, RemoteConsole,
+    TransferConsole,
+};
+
+use super::{
+    directory::SPINNER_INTERVAL,
+    input::{History, Input},
+    template::{status_line, Field, Template},
+    *,
 };
 
-use self::console::{Console, ConsoleOp, DirConsole, Zoxide};
+/// Time without a keystroke, after which the next hint is shown in the footer
+const HINT_INTERVAL: Duration = Duration::from_secs(8);
+
+/// Time between two redraws of consoles that show changing data (like the running jobs)
+const REFRESH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Time after which the free space of the current filesystem is read again
+const DISK_USAGE_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Time after which a created item is no longer selected, when it shows up (see [`PanelManager::expect_item`])
+const EXPECTED_ITEM_TIMEOUT: Duration = Duration::from_secs(10);
 
-use super::{input::Input, *};
+/// Depth of the flat view, if no count is given
+const DEFAULT_FLAT_DEPTH: usize = 3;
+
+/// Time without further changes of the config files, after which they are read again
+/// (editors often write a file in several steps)
+const RELOAD_DELAY: Duration = Duration::from_millis(200);
 
 struct Redraw {
     left: bool,
@@ -46,20 +113,127 @@ impl Redraw {
 
 enum Mode {
     Normal,
-    Console { console: Box<dyn Console> },
-    CreateItem { input: Input, is_dir: bool },
-    Search { input: Input },
-    Rename { input: Input },
+    Console {
+        console: Box<dyn Console>,
+    },
+    CreateItem {
+        input: Input,
+        is_dir: bool,
+        /// Text that is written into the new file (see [`Command::PasteToFile`])
+        content: Option<String>,
+        /// Item that already exists with the name (the next enter overwrites it, if possible)
+        exists: Option<PathBuf>,
+    },
+    Search {
+        input: Input,
+    },
+    MarkGlob {
+        input: Input,
+    },
+    /// Asks for the age of the items that are marked (see [`Command::MarkByAge`])
+    MarkByAge {
+        input: Input,
+    },
+    /// Asks for the kind of files that are marked (see [`Command::MarkByType`])
+    MarkByType {
+        input: Input,
+    },
+    /// Asks for the name of the tag (see [`crate::engine::tags`])
+    Tag {
+        input: Input,
+    },
+    Rename {
+        input: Input,
+        /// Item that already exists with the new name (the next enter overwrites it)
+        exists: Option<PathBuf>,
+    },
+    /// Selects one of the directories of the path in the header (see [`breadcrumbs`])
+    Breadcrumbs {
+        crumbs: Vec<(String, PathBuf)>,
+        idx: usize,
+    },
+    /// Asks the user before the items are deleted
+    ConfirmDelete {
+        files: Vec<PathBuf>,
+        permanent: bool,
+        prompt: String,
+        /// Total size of the items, while it is calculated in the background
+        size: Option<oneshot::Receiver<(u64, bool)>>,
+    },
+    /// Asks the user before the trash is emptied
+    ConfirmEmptyTrash {
+        prompt: String,
+    },
+    /// The operation affects a protected path, so the user has to type [`CONFIRMATION`]
+    ConfirmProtected {
+        input: Input,
+        prompt: String,
+        operation: Guarded,
+    },
 }
 
-struct Clipboard {
-    /// Items we put into the clipboard
-    files: Vec<PathBuf>,
-    /// Weather or not we want to cut or copy the items.
-    ///
-    /// `True`  : Cut
-    /// `False` : Copy
-    cut: bool,
+/// Operations that must be confirmed for protected paths (see [`crate::engine::protect`])
+///
+/// Pasting is not guarded, because it never overwrites anything (see [`TransferPlan`]).
+enum Guarded {
+    Delete(Vec<PathBuf>),
+    Cut(Vec<PathBuf>),
+}
+
+impl Guarded {
+    fn name(&self) -> &'static str {
+        match self {
+            Guarded::Delete(_) => "delete",
+            Guarded::Cut(_) => "cut",
+        }
+    }
+}
+
+/// State of the background caching, that is shown in the footer.
+#[derive(Default)]
+struct CacheStatus {
+    /// Number of running crawls
+    active: usize,
+    /// Progress of the most recent crawl
+    entries: usize,
+    panels: usize,
+    /// Frame of the spinner, which advances with every progress message
+    frame: usize,
+}
+
+impl CacheStatus {
+    const SPINNER: [char; 4] = ['|', '/', '-', '\\'];
+
+    fn update(&mut self, progress: CacheProgress) {
+        match progress {
+            CacheProgress::Started => {
+                self.active += 1;
+                self.entries = 0;
+                self.panels = 0;
+            }
+            CacheProgress::Running { entries, panels } => {
+                self.entries = entries;
+                self.panels = panels;
+            }
+            CacheProgress::Finished => self.active = self.active.saturating_sub(1),
+        }
+        self.frame = self.frame.wrapping_add(1);
+    }
+
+    /// Text for the footer, or `None` if there is nothing to show
+    fn indicator(&self) -> Option<String> {
+        if CACHE_PAUSED.load(std::sync::atomic::Ordering::Relaxed) {
+            return Some("caching paused ".to_string());
+        }
+        if self.active == 0 || self.entries == 0 {
+            return None;
+        }
+        let spinner = Self::SPINNER[self.frame % Self::SPINNER.len()];
+        Some(format!(
+            "caching {spinner} {} entries, {} panels ",
+            self.entries, self.panels
+        ))
+    }
 }
 
 // enum Operation {
@@ -87,10 +261,19 @@ pub struct PanelManager {
     /// Clipboard
     clipboard: Option<Clipboard>,
 
+    /// Clipboard that is shared with other instances of rfm
+    shared_clipboard: SharedClipboard,
+
+    /// Earlier clipboards, that were replaced before their items were pasted
+    clipboard_history: ClipboardHistory,
+
+    /// Exchange copied files with graphical applications via the system clipboard
+    system_clipboard: SystemClipboard,
+
     // /// Undo/Redo stack
     // stack: Vec<Operation>,
     /// Miller-Columns layout
-    layout: MillerColumns,
+    layout: Layout,
 
     /// Show hidden files
     show_hidden: bool,
@@ -98,11 +281,58 @@ pub struct PanelManager {
     /// Show log
     show_log: bool,
 
+    /// Ask before items are moved to the trash (permanent deletes always ask)
+    confirm_trash: bool,
+
+    /// Weather or not `next`/`previous` continue at the other end of the directory
+    search_wrap: bool,
+    /// Weather or not `up` at the top continues at the bottom (and vice versa)
+    cursor_wrap: bool,
+
+    /// Weather or not the last search was a backward search (which reverses `next` and `previous`)
+    search_backward: bool,
+
+    /// Short message in the footer, that is cleared with the next keystroke
+    notice: Option<&'static str>,
+
+    /// Shown instead of the preview, while more than one item is marked
+    marked_summary: Option<MarkedSummary>,
+
+    /// Tips about the keybindings, that rotate in the footer while rfm is idle
+    hints: Vec<String>,
+
+    /// Index of the hint that is currently shown
+    hint: Option<usize>,
+
+    /// Weather or not the continuations of the typed keys are shown in a popup
+    key_popup: bool,
+
+    /// Progress of the running jobs in the header, while more than one job is running
+    jobs_summary: Option<String>,
+
+    /// Item that was just created or pasted, and since when it is expected.
+    /// It is selected as soon as it shows up in the center panel.
+    expected_item: Option<(PathBuf, Instant)>,
+
+    /// Time of the last keystroke
+    last_key: Instant,
+
     /// Elements that needs to be redrawn
     redraw: Redraw,
 
-    /// Event-stream from the terminal
-    event_reader: EventStream,
+    /// Event-stream from the terminal.
+    ///
+    /// Is `None` in headless mode, where there is no terminal to read from.
+    event_reader: Option<EventStream>,
+
+    /// Weather or not we run without a terminal (see [`PanelManager::run_script`])
+    headless: bool,
+
+    /// Opening a file chooses it instead (see [`Command::SelectForPicker`])
+    picker: bool,
+
+    /// Refuses all commands that change the filesystem (see [`Command::is_mutating`])
+    readonly: bool,
 
     /// History when going "forward"
     fwd_history: Vec<(PathBuf, PathBuf)>,
@@ -114,8 +344,8 @@ pub struct PanelManager {
     previous: PathBuf,
     pre_console_path: PathBuf,
 
-    /// Trash directory. If `None`, the trash mechanism should not be used.
-    trash_dir: Option<TempDir>,
+    /// Where deleted items go to
+    trash: Box<dyn TrashBackend>,
 
     /// command-parser
     parser: CommandParser,
@@ -128,40 +358,151 @@ pub struct PanelManager {
 
     /// Receiver for incoming preview-panels
     prev_rx: mpsc::Receiver<(PreviewPanel, PanelState)>,
+
+    /// Receiver for progress messages of the background caching
+    cache_rx: mpsc::UnboundedReceiver<CacheProgress>,
+
+    /// Receiver for requests of external tools (see [`crate::server`])
+    server_rx: mpsc::UnboundedReceiver<Request>,
+
+    /// Receiver for changes of the config files (see [`ConfigFiles::watch`])
+    config_rx: mpsc::UnboundedReceiver<()>,
+
+    /// Config files, that are read again when they change
+    config_files: ConfigFiles,
+
+    /// Time at which the changed config files are read again
+    reload_deadline: Option<Instant>,
+
+    /// Background caching status, that is shown in the footer
+    cache_status: CacheStatus,
+
+    /// History of visited directories
+    frecency: Frecency,
+
+    /// Directories visited in this session, for going back and forward
+    dir_history: DirHistory,
+
+    /// Git branch of a directory (only used by the header and footer templates)
+    git_branch: (PathBuf, Option<String>),
+
+    /// Last title that was set for the terminal
+    title: String,
+
+    /// Usage in percent, above which the free space in the footer is shown as a warning
+    /// (`None` if the footer does not show it)
+    disk_warning: Option<u8>,
+
+    /// Free space of the filesystem of a directory, and when it was read
+    disk_usage: (PathBuf, Instant, Option<DiskUsage>),
+
+    /// Notifies the main loop about new matches of a running grep search
+    grep_tx: mpsc::UnboundedSender<()>,
+    grep_rx: mpsc::UnboundedReceiver<()>,
+
+    /// Receives a message whenever a checksum was calculated
+    checksum_tx: mpsc::UnboundedSender<()>,
+    checksum_rx: mpsc::UnboundedReceiver<()>,
+
+    /// File and line, that the preview should show (after jumping to a grep match)
+    preview_line: Option<(PathBuf, usize)>,
+
+    /// Previous inputs of the footer input fields
+    histories: InputHistories,
+}
+
+/// Input histories, one for every kind of input field in the footer
+#[derive(Default)]
+struct InputHistories {
+    search: History,
+    mark_glob: History,
+    mark_by_age: History,
+    mark_by_type: History,
+    rename: History,
+    create: History,
+    tag: History,
+}
+
+impl InputHistories {
+    fn reset(&mut self) {
+        self.search.reset();
+        self.mark_glob.reset();
+        self.mark_by_age.reset();
+        self.mark_by_type.reset();
+        self.rename.reset();
+        self.create.reset();
+        self.tag.reset();
+    }
+}
+
+/// Passes a key to the input field, `Up` and `Down` browse its history.
+fn edit_input(input: &mut Input, history: &mut History, key_event: KeyEvent) {
+    match key_event.code {
+        KeyCode::Up => history.browse(input, true),
+        KeyCode::Down => history.browse(input, false),
+        code => input.update(code, key_event.modifiers),
+    }
 }
 
 impl PanelManager {
     #[allow(clippy::too_many_arguments)]
     pub fn new(
         miller_panels: MillerPanels,
-        use_trash: bool,
+        trash_kind: TrashKind,
+        confirm_trash: bool,
+        search_wrap: bool,
+        cursor_wrap: bool,
+        hints: bool,
+        autosize: Option<u16>,
+        layout_preset: LayoutPreset,
+        disk_warning: Option<u8>,
         parser: CommandParser,
         dir_rx: mpsc::Receiver<(DirPanel, PanelState)>,
         prev_rx: mpsc::Receiver<(PreviewPanel, PanelState)>,
+        cache_rx: mpsc::UnboundedReceiver<CacheProgress>,
+        server_rx: mpsc::UnboundedReceiver<Request>,
+        config_rx: mpsc::UnboundedReceiver<()>,
+        config_files: ConfigFiles,
         logger: LogBuffer,
         opener: OpenEngine,
+        frecency: Frecency,
+        shared_clipboard: SharedClipboard,
+        system_clipboard: bool,
+        headless: bool,
+        picker: bool,
+        readonly: bool,
     ) -> Result<Self> {
         // Prepare terminal
         let stdout = stdout();
-        let event_reader = EventStream::new();
-        let terminal_size = terminal::size()?;
-        let layout = MillerColumns::from_size(terminal_size);
+        let (event_reader, terminal_size) = if headless {
+            (None, (80, 24))
+        } else {
+            (Some(EventStream::new()), terminal::size()?)
+        };
+        let mut layout = Layout::from_size(terminal_size);
+        layout.set_autosize(autosize);
+        layout.set_preset(layout_preset);
+        if !previews_enabled() {
+            layout.toggle_preview();
+        }
+        let (grep_tx, grep_rx) = mpsc::unbounded_channel();
+        let (checksum_tx, checksum_rx) = mpsc::unbounded_channel();
+        let hints = if hints { parser.hints() } else { Vec::new() };
 
         // Split panels
         let (left, center, right) = miller_panels;
+        let mut dir_history = DirHistory::default();
+        dir_history.visit(center.panel().path());
 
-        // TODO: If the user has multiple disks, the temp-dir may be on another disk,
+        // TODO: If the user has multiple disks, the trash may be on another disk,
         // so deleting would effectively be a copy - which is not what we want here.
         // Add a mechanism to check, if the file that should get deleted is on the same disk or not
         //
         // -> For now we mark the feature as experimental and turn it off by default
-        let trash_dir = if use_trash {
-            let trash_dir = tempfile::tempdir()?;
-            debug!("Using {} as temporary trash", trash_dir.path().display());
-            Some(trash_dir)
-        } else {
-            None
-        };
+        let trash = trash::backend(trash_kind)?;
+        if let Some(files) = trash.files() {
+            debug!("Using {} as trash ({trash_kind:?})", files.display());
+        }
 
         Ok(PanelManager {
             left,
@@ -170,11 +511,26 @@ impl PanelManager {
             mode: Mode::Normal,
             logger,
             clipboard: None,
+            shared_clipboard,
+            clipboard_history: ClipboardHistory::default(),
+            system_clipboard: SystemClipboard::new(system_clipboard && !headless),
             layout,
             opener,
             // stack: Vec::new(),
             show_hidden: false,
             show_log: false,
+            confirm_trash,
+            search_wrap,
+            cursor_wrap,
+            search_backward: false,
+            notice: None,
+            marked_summary: None,
+            hints,
+            hint: None,
+            key_popup: false,
+            jobs_summary: None,
+            expected_item: None,
+            last_key: Instant::now(),
             redraw: Redraw {
                 left: true,
                 center: true,
@@ -185,15 +541,36 @@ impl PanelManager {
                 footer: true,
             },
             event_reader,
+            headless,
+            picker,
+            readonly,
             fwd_history: Vec::new(),
             rev_history: Vec::new(),
             previous: ".".into(),
             pre_console_path: ".".into(),
-            trash_dir,
+            trash,
             parser,
             stdout,
             dir_rx,
             prev_rx,
+            cache_rx,
+            server_rx,
+            config_rx,
+            config_files,
+            reload_deadline: None,
+            cache_status: CacheStatus::default(),
+            frecency,
+            dir_history,
+            git_branch: (PathBuf::new(), None),
+            title: String::new(),
+            disk_warning,
+            disk_usage: (PathBuf::new(), Instant::now(), None),
+            grep_tx,
+            grep_rx,
+            checksum_tx,
+            checksum_rx,
+            preview_line: None,
+            histories: InputHistories::default(),
         })
     }
 
@@ -205,6 +582,26 @@ impl PanelManager {
         self.redraw.footer = true;
     }
 
+    /// Shows the next hint in the footer, if no key was pressed for a while.
+    fn next_hint(&mut self) {
+        if self.hints.is_empty()
+            || !matches!(self.mode, Mode::Normal)
+            || self.last_key.elapsed() < HINT_INTERVAL
+        {
+            return;
+        }
+        let next = match self.hint {
+            Some(idx) => idx + 1,
+            // Don't start with the same hint in every session
+            None => SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .map(|d| d.as_secs() as usize)
+                .unwrap_or_default(),
+        };
+        self.hint = Some(next % self.hints.len());
+        self.redraw_footer();
+    }
+
     fn redraw_panels(&mut self) {
         self.redraw.left = true;
         self.redraw.center = true;
@@ -246,6 +643,106 @@ impl PanelManager {
         self.redraw.console = true;
     }
 
+    /// Puts the items into the clipboard of this and all other instances.
+    ///
+    /// The previous clipboard is kept in the clipboard history.
+    fn set_clipboard(&mut self, clipboard: Clipboard) {
+        self.sync_clipboard();
+        self.shared_clipboard.store(&clipboard);
+        // Graphical applications would copy cut items, instead of moving them
+        if !clipboard.cut {
+            self.system_clipboard.publish(&clipboard.files);
+        }
+        self.clipboard_history.remove(&clipboard.files);
+        if let Some(previous) = self.clipboard.replace(clipboard) {
+            self.keep_clipboard(previous);
+        }
+    }
+
+    /// Moves a replaced clipboard into the history and warns about cut items, that were not pasted.
+    fn keep_clipboard(&mut self, previous: Clipboard) {
+        if self
+            .clipboard
+            .as_ref()
+            .is_some_and(|c| c.files == previous.files)
+        {
+            return;
+        }
+        if previous.cut {
+            warn!(
+                "{} cut items were not pasted yet, they are kept in the clipboard history",
+                previous.files.len()
+            );
+        }
+        if let Some(dropped) = self.clipboard_history.push(previous) {
+            if dropped.cut {
+                warn!(
+                    "Dropped {} cut items from the clipboard history",
+                    dropped.files.len()
+                );
+            }
+        }
+    }
+
+    /// Takes over the files that were copied in a graphical application (as found by the last background read),
+    /// and starts the next read.
+    fn check_system_clipboard(&mut self) {
+        if let Some(files) = self.system_clipboard.changed() {
+            info!("Using {} items of the system clipboard", files.len());
+            let clipboard = Clipboard::new(files, false);
+            self.shared_clipboard.store(&clipboard);
+            if let Some(previous) = self.clipboard.replace(clipboard) {
+                self.keep_clipboard(previous);
+            }
+        }
+        self.system_clipboard.check();
+    }
+
+    /// Replaces our clipboard with the shared one, if another instance has cut or copied something since.
+    /// Files that were copied in a graphical application since replace it as well.
+    ///
+    /// Items that no longer exist are removed from the clipboard.
+    fn sync_clipboard(&mut self) {
+        self.check_system_clipboard();
+        if let Some(shared) = self.shared_clipboard.load() {
+            if self
+                .clipboard
+                .as_ref()
+                .is_none_or(|c| c.time <= shared.time)
+            {
+                if let Some(previous) = self.clipboard.replace(shared) {
+                    self.keep_clipboard(previous);
+                }
+            }
+        }
+        if let Some(clipboard) = &mut self.clipboard {
+            if !clipboard.retain_existing() {
+                self.clipboard = None;
+            }
+        }
+    }
+
+    /// Takes the items out of the clipboard for pasting.
+    fn take_clipboard(&mut self) -> Option<Clipboard> {
+        self.sync_clipboard();
+        let clipboard = self.clipboard.take()?;
+        // Cut items must only be moved by one instance
+        if !self.shared_clipboard.take(&clipboard) && clipboard.cut {
+            info!("The items were already pasted by another instance");
+            return None;
+        }
+        Some(clipboard)
+    }
+
+    /// Clears the screen and redraws everything, after the columns have changed.
+    fn relayout(&mut self) -> Result<()> {
+        if !self.headless {
+            self.stdout.queue(Clear(ClearType::All))?;
+        }
+        self.redraw_everything();
+        Ok(())
+    }
+
     fn redraw_log(&mut self) {
         self.redraw.log = true;
     }
@@ -304,17 +801,93 @@ impl PanelManager {
         if !self.redraw.header {
             return Ok(());
         }
-        let prompt = format!(
-            "{}@{}",
-            whoami::username(),
-            whoami::fallible::hostname().unwrap_or_else(|e| e.to_string())
-        );
-        let absolute = self
-            .center
+        if self.layout.zen() {
+            self.redraw.header = false;
+            return Ok(());
+        }
+        queue!(
+            self.stdout,
+            cursor::MoveTo(0, 0),
+            Clear(ClearType::CurrentLine),
+        )?;
+        if let Mode::Breadcrumbs { crumbs, idx } = &self.mode {
+            self.stdout
+                .queue(PrintStyledContent(
+                    header_prompt().with(color_main()).bold(),
+                ))?
+                .queue(Print(" "))?;
+            for (n, (segment, path)) in crumbs.iter().enumerate() {
+                let mut content = if vfs::is_dir(path) {
+                    segment.clone().with(color_dir_path()).bold()
+                } else {
+                    segment.clone().bold()
+                };
+                if n == *idx {
+                    content = content.reverse();
+                }
+                self.stdout.queue(PrintStyledContent(content))?;
+            }
+            self.redraw.header = false;
+            return Ok(());
+        }
+        if let Some(template) = &status_line().header {
+            self.update_git_branch();
+            for content in self.render_template(template) {
+                self.stdout.queue(PrintStyledContent(content))?;
+            }
+        } else {
+            self.stdout
+                .queue(PrintStyledContent(
+                    header_prompt().with(color_main()).bold(),
+                ))?
+                .queue(Print(" "))?;
+            for content in self.styled_selected_path() {
+                self.stdout.queue(PrintStyledContent(content))?;
+            }
+        }
+        if let Some(summary) = &self.jobs_summary {
+            let start = self.jobs_summary_start();
+            queue!(
+                self.stdout,
+                cursor::MoveTo(start, 0),
+                PrintStyledContent(format!(" {summary} ").with(color_main()).reverse()),
+            )?;
+        }
+        self.redraw.header = false;
+        Ok(())
+    }
+
+    /// Column where the summary of the running jobs starts (it is aligned to the right)
+    fn jobs_summary_start(&self) -> u16 {
+        let len = self
+            .jobs_summary
+            .as_ref()
+            .map(|summary| unicode_display_width::width(summary) as u16 + 2)
+            .unwrap_or_default();
+        self.layout.width().saturating_sub(len)
+    }
+
+    /// Shows the progress of the running jobs in the header, when it has changed.
+    fn update_jobs_summary(&mut self) {
+        let summary = jobs::summary();
+        if summary != self.jobs_summary {
+            self.jobs_summary = summary;
+            self.redraw.header = true;
+        }
+    }
+
+    /// Absolute path of the selected item (or of the directory, if it is empty)
+    fn absolute_selected_path(&self) -> PathBuf {
+        self.center
             .panel()
             .selected_path()
             .and_then(|f| f.canonicalize().ok())
-            .unwrap_or_else(|| self.center.panel().path().to_path_buf());
+            .unwrap_or_else(|| self.center.panel().path().to_path_buf())
+    }
+
+    /// Absolute path of the selected item, with the parent directory in `color_dir_path`
+    fn styled_selected_path(&self) -> Vec<StyledContent<String>> {
+        let absolute = self.absolute_selected_path();
         let file_name = absolute
             .file_name()
             .unwrap_or_default()
@@ -323,17 +896,107 @@ impl PanelManager {
         let absolute = absolute.to_str().unwrap_or_default();
 
         let (prefix, suffix) = absolute.split_at(absolute.len() - file_name.len());
+        vec![
+            prefix.to_string().with(color_dir_path()).bold(),
+            suffix.to_string().bold(),
+        ]
+    }
 
-        queue!(
-            self.stdout,
-            cursor::MoveTo(0, 0),
-            Clear(ClearType::CurrentLine),
-            style::PrintStyledContent(prompt.with(color_main()).bold()),
-            style::Print(" "),
-            style::PrintStyledContent(prefix.to_string().with(color_dir_path()).bold()),
-            style::PrintStyledContent(suffix.to_string().bold()),
-        )?;
-        self.redraw.header = false;
+    /// Reads the free space of the current filesystem, after the directory has changed
+    /// or when the last value is outdated (e.g. after a file operation).
+    fn update_disk_usage(&mut self) {
+        if self.disk_warning.is_none() && !status_line().contains(Field::Disk) {
+            return;
+        }
+        let path = self.center.panel().path();
+        let (dir, read_at, _) = &self.disk_usage;
+        if dir != path || read_at.elapsed() > DISK_USAGE_INTERVAL {
+            self.disk_usage = (path.to_path_buf(), Instant::now(), disk_usage(path));
+        }
+    }
+
+    /// Free and total space of the current filesystem, e.g. "12.30 G / 250.00 G"
+    fn disk_usage_str(&self) -> Option<String> {
+        let usage = self.disk_usage.2?;
+        Some(format!(
+            "{} / {}",
+            file_size_str(usage.free),
+            file_size_str(usage.total)
+        ))
+    }
+
+    /// Reads the git branch of the current directory, if any template needs it.
+    fn update_git_branch(&mut self) {
+        let path = self.center.panel().path();
+        if status_line().contains(Field::GitBranch) && self.git_branch.0 != path {
+            self.git_branch = (path.to_path_buf(), git_branch(path));
+        }
+    }
+
+    /// Replaces the placeholders of a header or footer template.
+    fn render_template(&self, template: &Template) -> Vec<StyledContent<String>> {
+        let selected = self.center.panel().selected_path();
+        let mut info: Option<FileInfo> = None;
+        template.render(|field| {
+            let text = match field {
+                Field::User => return vec![whoami::username().with(color_main()).bold()],
+                Field::Host => {
+                    let host = whoami::fallible::hostname().unwrap_or_default();
+                    return vec![host.with(color_main()).bold()];
+                }
+                Field::Path => return self.styled_selected_path(),
+                Field::Dir => {
+                    let dir = self.center.panel().path().display().to_string();
+                    return vec![dir.with(color_dir_path()).bold()];
+                }
+                Field::ShortDir => {
+                    let dir = tilde_path(self.center.panel().path());
+                    return vec![dir.with(color_dir_path()).bold()];
+                }
+                Field::Name => selected
+                    .and_then(|p| p.file_name())
+                    .map(|n| n.to_string_lossy().to_string())
+                    .unwrap_or_default(),
+                Field::Index => self.center.panel().index_vs_total().0.to_string(),
+                Field::Total => self.center.panel().index_vs_total().1.to_string(),
+                Field::GitBranch => self.git_branch.1.clone().unwrap_or_default(),
+                Field::Disk => self.disk_usage_str().unwrap_or_default(),
+                file_field => {
+                    let info = info.get_or_insert_with(|| file_info(selected));
+                    match file_field {
+                        Field::Perm => {
+                            return styled_permissions(&info.permissions)
+                                .into_iter()
+                                .map(|c| StyledContent::new(*c.style(), c.content().to_string()))
+                                .collect()
+                        }
+                        Field::Owner => info.owner.clone(),
+                        Field::Group => info.group.clone(),
+                        Field::Size => info.size.clone(),
+                        Field::Modified => info.modified.clone(),
+                        _ => info.mime.clone(),
+                    }
+                }
+            };
+            vec![text.stylize()]
+        })
+    }
+
+    /// Sets the title of the terminal, if it has changed.
+    fn update_title(&mut self) -> Result<()> {
+        let Some(template) = &status_line().title else {
+            return Ok(());
+        };
+        self.update_git_branch();
+        let title: String = self
+            .render_template(template)
+            .into_iter()
+            .map(|content| content.content().clone())
+            .collect();
+        if title != self.title {
+            self.stdout.queue(SetTitle(&title))?;
+            self.title = title;
+        }
         Ok(())
     }
 
@@ -350,25 +1013,97 @@ impl PanelManager {
         )?;
 
         if let Mode::Search { input } = &self.mode {
+            let prompt = if self.search_backward {
+                "Search backward"
+            } else {
+                "Search"
+            };
             self.stdout
                 .queue(PrintStyledContent(
-                    "Search".bold().with(color_main()).reverse(),
+                    prompt.bold().with(color_main()).reverse(),
                 ))?
                 .queue(Print(" "))?;
             input.print(&mut self.stdout, style::Color::Red)?;
             return self.stdout.flush();
         }
-        if let Mode::Rename { input } = &self.mode {
+        if let Mode::MarkGlob { input } = &self.mode {
+            self.stdout
+                .queue(PrintStyledContent(
+                    "Mark:".bold().with(color_main()).reverse(),
+                ))?
+                .queue(Print(" "))?;
+            input.print(&mut self.stdout, color_marked())?;
+            return self.stdout.flush();
+        }
+        if let Mode::MarkByAge { input } = &self.mode {
+            self.stdout
+                .queue(PrintStyledContent(
+                    "Mark modified within:".bold().with(color_main()).reverse(),
+                ))?
+                .queue(Print(" "))?;
+            input.print(&mut self.stdout, color_marked())?;
+            return self.stdout.flush();
+        }
+        if let Mode::MarkByType { input } = &self.mode {
+            self.stdout
+                .queue(PrintStyledContent(
+                    "Mark all:".bold().with(color_main()).reverse(),
+                ))?
+                .queue(Print(" "))?;
+            input.print(&mut self.stdout, color_marked())?;
+            return self.stdout.flush();
+        }
+        if let Mode::Tag { input } = &self.mode {
+            self.stdout
+                .queue(PrintStyledContent(
+                    "Tag:".bold().with(color_main()).reverse(),
+                ))?
+                .queue(Print(" "))?;
+            input.print(&mut self.stdout, color_highlight())?;
+            return self.stdout.flush();
+        }
+        if let Mode::Rename { input, exists } = &self.mode {
             self.stdout
                 .queue(PrintStyledContent(
                     "Rename:".bold().with(color_main()).reverse(),
                 ))?
                 .queue(Print(" "))?;
             input.print(&mut self.stdout, style::Color::Yellow)?;
+            if exists.is_some() {
+                print_exists_warning(&mut self.stdout, true)?;
+            }
+            return self.stdout.flush();
+        }
+        if let Mode::ConfirmProtected { input, prompt, .. } = &self.mode {
+            self.stdout
+                .queue(PrintStyledContent(
+                    prompt.as_str().bold().with(style::Color::Red).reverse(),
+                ))?
+                .queue(Print(" "))?;
+            input.print(&mut self.stdout, style::Color::Yellow)?;
+            return self.stdout.flush();
+        }
+        if let Mode::ConfirmDelete { prompt, .. } | Mode::ConfirmEmptyTrash { prompt } = &self.mode
+        {
+            self.stdout
+                .queue(PrintStyledContent(
+                    prompt.as_str().bold().with(color_main()).reverse(),
+                ))?
+                .queue(Print(" y/N"))?;
             return self.stdout.flush();
         }
-        if let Mode::CreateItem { input, is_dir } = &self.mode {
-            let prompt = if *is_dir { "Make Directory:" } else { "Touch:" };
+        if let Mode::CreateItem {
+            input,
+            is_dir,
+            content,
+            exists,
+        } = &self.mode
+        {
+            let prompt = match (is_dir, content) {
+                (true, _) => "Make Directory:",
+                (false, None) => "Touch:",
+                (false, Some(_)) => "Paste into:",
+            };
             self.stdout
                 .queue(PrintStyledContent(
                     prompt.bold().with(color_main()).reverse(),
@@ -379,44 +1114,83 @@ impl PanelManager {
             } else {
                 input.print(&mut self.stdout, style::Color::Grey)?;
             }
+            if exists.is_some() {
+                // Only pasted text replaces an existing file
+                print_exists_warning(&mut self.stdout, content.is_some())?;
+            }
             return self.stdout.flush();
         }
-        let (permissions, metadata) = print_metadata(self.center.panel().selected_path());
-        queue!(
-            self.stdout,
-            style::PrintStyledContent(permissions.dark_cyan()),
-            Print("   "),
-            Print(metadata)
-        )?;
+        // In zen mode, only the prompts are shown
+        if self.layout.zen() {
+            self.redraw.footer = false;
+            return self.stdout.flush();
+        }
+        self.update_git_branch();
+        self.update_disk_usage();
+        if let Some(template) = &status_line().footer {
+            for content in self.render_template(template) {
+                self.stdout.queue(PrintStyledContent(content))?;
+            }
+        } else {
+            let selected = self.center.panel().selected_path();
+            let (permissions, metadata) = print_metadata(selected);
+            for c in styled_permissions(&permissions) {
+                self.stdout.queue(PrintStyledContent(c))?;
+            }
+            queue!(self.stdout, Print("   "))?;
+            // Once calculated, the checksum replaces the metadata
+            match selected.and_then(checksum::result) {
+                Some(checksum) => {
+                    let text = format!("{} {}", checksum.algorithm, checksum.digest);
+                    let status = match checksum.verified {
+                        Some(true) => " ✓ matches checksum file".green(),
+                        Some(false) => " ✗ does not match checksum file".red(),
+                        None => "".stylize(),
+                    };
+                    queue!(self.stdout, Print(text), PrintStyledContent(status))?;
+                }
+                None => queue!(self.stdout, Print(metadata))?,
+            }
+        }
 
         // TODO: We could place this into its own line, and also print some recommendations
         let key_buffer = self.parser.buffer();
         let (n, m) = self.center.panel().index_vs_total();
-        let n_files_string = format!("{n}/{m} ");
+        let n_files = match &status_line().footer_right {
+            Some(template) => self.render_template(template),
+            // The "+" shows that the directory is still being read
+            None if self.center.panel().is_partial() => vec![format!("{n}/{m}+ ").stylize()],
+            None => vec![format!("{n}/{m} ").stylize()],
+        };
+        let n_files_width: usize = n_files
+            .iter()
+            .map(|c| unicode_display_width::width(c.content()) as usize)
+            .sum();
 
-        // Okay, we CAN print the matching commands, but currently I am not very happy with this.
-        if false {
+        let visual = self.center.panel().is_visual().then_some("-- VISUAL --");
+        if let (true, Some(notice)) = (key_buffer.is_empty(), self.notice.or(visual)) {
             queue!(
                 self.stdout,
                 cursor::MoveTo(
-                    // (self.layout.width() / 2).saturating_sub(key_buffer.len() as u16 / 2),
-                    0,
-                    self.layout.footer().saturating_sub(2),
+                    (self.layout.width() / 2).saturating_sub(notice.len() as u16 / 2),
+                    self.layout.footer()
                 ),
-                Clear(ClearType::CurrentLine),
-                style::PrintStyledContent(key_buffer.clone().on_dark_grey()),
-                Print("    "),
+                style::PrintStyledContent(notice.with(color_marked())),
             )?;
-            let key_buffer_len = key_buffer.chars().count();
-            for (cmd, desc) in self.parser.matching_commands() {
-                let sub_cmd: String = cmd.chars().skip(key_buffer_len).collect();
+        } else if let (true, Some(hint)) = (
+            key_buffer.is_empty(),
+            self.hint.and_then(|idx| self.hints.get(idx)),
+        ) {
+            // Don't cover the permissions and file count in narrow terminals
+            let hint_width = hint.chars().count() as u16;
+            if hint_width <= self.layout.width() / 2 {
                 queue!(
                     self.stdout,
-                    style::PrintStyledContent(key_buffer.clone().on_dark_grey()),
-                    style::PrintStyledContent(sub_cmd.dark_grey()),
-                    Print(": "),
-                    style::PrintStyledContent(desc.dark_grey()),
-                    Print("   "),
+                    cursor::MoveTo(
+                        (self.layout.width() / 2).saturating_sub(hint_width / 2),
+                        self.layout.footer()
+                    ),
+                    style::PrintStyledContent(hint.as_str().dark_grey().italic()),
                 )?;
             }
         } else {
@@ -430,36 +1204,134 @@ impl PanelManager {
             )?;
         }
         // ---
-        queue!(
-            self.stdout,
-            cursor::MoveTo(
-                self.layout
-                    .width()
-                    .saturating_sub(n_files_string.len() as u16),
-                self.layout.footer(),
-            ),
-            style::Print(n_files_string),
-        )?;
-        self.redraw.footer = false;
-        Ok(())
-    }
-
-    fn draw(&mut self) -> Result<()> {
-        if !self.redraw.any() {
+        let mut x_files = self.layout.width().saturating_sub(n_files_width as u16);
+        if let (Some(threshold), Some(usage), Some(text)) =
+            (self.disk_warning, self.disk_usage.2, self.disk_usage_str())
+        {
+            let text = format!("{text} ");
+            x_files = x_files.saturating_sub(text.chars().count() as u16);
+            let text = if usage.used_percent() > u64::from(threshold) {
+                text.red().bold()
+            } else {
+                text.dark_grey()
+            };
+            queue!(
+                self.stdout,
+                cursor::MoveTo(x_files, self.layout.footer()),
+                style::PrintStyledContent(text),
+            )?;
+        }
+        let n_staged = staging::len();
+        if n_staged > 0 {
+            let indicator = format!("◆ {n_staged} staged ");
+            x_files = x_files.saturating_sub(indicator.chars().count() as u16);
+            queue!(
+                self.stdout,
+                cursor::MoveTo(x_files, self.layout.footer()),
+                style::PrintStyledContent(indicator.with(color_marked())),
+            )?;
+        }
+        if let Some(indicator) = self.cache_status.indicator() {
+            queue!(
+                self.stdout,
+                cursor::MoveTo(
+                    x_files.saturating_sub(indicator.chars().count() as u16),
+                    self.layout.footer()
+                ),
+                style::PrintStyledContent(indicator.dark_grey()),
+            )?;
+        }
+        self.stdout
+            .queue(cursor::MoveTo(x_files, self.layout.footer()))?;
+        for content in n_files {
+            self.stdout.queue(PrintStyledContent(content))?;
+        }
+        self.redraw.footer = false;
+        Ok(())
+    }
+
+    fn draw(&mut self) -> Result<()> {
+        if !self.redraw.any() || self.headless {
             return Ok(());
         }
+        // The center column may grow or shrink with its content
+        if self.redraw.center && self.layout.fit_content(self.center.panel().content_width()) {
+            self.relayout()?;
+        }
         self.stdout.execute(BeginSynchronizedUpdate)?;
         self.stdout.queue(cursor::Hide)?;
+        // The continuations of a key sequence are drawn above the panels
+        // The popup vanishes together with the typed keys
+        self.key_popup &= !self.parser.pending_keys().is_empty();
+        let pending_keys =
+            self.redraw.footer || self.redraw.left || self.redraw.center || self.redraw.right;
         self.draw_footer()?;
         self.draw_header()?;
+        self.update_title()?;
         self.draw_panels()?;
+        if pending_keys {
+            self.draw_pending_keys()?;
+        }
         self.draw_console()?;
         self.draw_log()?;
         self.stdout.execute(EndSynchronizedUpdate)?;
         Ok(())
     }
 
+    /// Lists how the typed key sequence can continue, in the line above the footer
+    /// (or in a popup, if the sequence is unfinished for a while).
+    fn draw_pending_keys(&mut self) -> Result<()> {
+        let matches = self.parser.matching_commands();
+        if matches.is_empty() || !matches!(self.mode, Mode::Normal) {
+            return Ok(());
+        }
+        if self.key_popup {
+            return draw_key_popup(
+                &mut self.stdout,
+                self.layout.left_x_range.start..self.layout.right_x_range.end,
+                self.layout.y_range.clone(),
+                &self.parser.pending_keys(),
+                &matches,
+            );
+        }
+        let width = self.layout.width() as usize;
+        let typed = format!(" {} ", self.parser.pending_keys());
+        let mut used = unicode_display_width::width(&typed) as usize;
+        queue!(
+            self.stdout,
+            cursor::MoveTo(0, self.layout.footer().saturating_sub(2)),
+            Clear(ClearType::CurrentLine),
+            PrintStyledContent(typed.with(color_main()).bold().reverse()),
+        )?;
+        for (idx, (keys, description)) in matches.iter().enumerate() {
+            let entry_width = unicode_display_width::width(&format!("  {keys} {description}"));
+            // Leave room for the number of hidden entries
+            let more = format!("  +{}", matches.len() - idx);
+            let last = idx + 1 == matches.len();
+            if used + entry_width as usize + if last { 0 } else { more.len() } > width {
+                queue!(self.stdout, PrintStyledContent(more.dark_grey()))?;
+                break;
+            }
+            used += entry_width as usize;
+            queue!(
+                self.stdout,
+                Print("  "),
+                PrintStyledContent(keys.as_str().with(color_marked()).bold()),
+                Print(" "),
+                PrintStyledContent(description.as_str().dark_grey()),
+            )?;
+        }
+        Ok(())
+    }
+
     fn draw_panels(&mut self) -> Result<()> {
+        // Marks only change together with the center panel
+        if self.redraw.center {
+            self.update_marked_summary();
+        }
+        // Hidden columns are not drawn
+        self.redraw.left &= !self.layout.left_x_range.is_empty();
+        self.redraw.right &= !self.layout.right_x_range.is_empty();
         let (start, end) = (self.layout.y_range.start, self.layout.y_range.end);
         let height = if self.show_log {
             let cap = self.logger.capacity();
@@ -483,7 +1355,12 @@ impl PanelManager {
             )?;
             self.redraw.center = false;
         }
-        if self.redraw.right {
+        if let (true, Some(summary)) = (self.redraw.right, &mut self.marked_summary) {
+            summary.draw(&mut self.stdout, self.layout.right_x_range.clone(), height)?;
+            self.redraw.right = false;
+        } else if self.redraw.right {
+            // Previews are replaced all the time, so they get the hidden-state right before drawing
+            self.right.panel_mut().set_hidden(self.show_hidden);
             self.right.panel_mut().draw(
                 &mut self.stdout,
                 self.layout.right_x_range.clone(),
@@ -494,6 +1371,66 @@ impl PanelManager {
         Ok(())
     }
 
+    /// Reads the keybindings, opener rules and colors again.
+    ///
+    /// Invalid files are skipped with a warning, so that an unfinished edit keeps the current config.
+    fn reload_config(&mut self) {
+        let files = &self.config_files;
+        match files.read::<KeyConfig>("keys.toml") {
+            Ok(config) => {
+                self.parser.set_bindings(config);
+                if !self.hints.is_empty() {
+                    self.hints = self.parser.hints();
+                    self.hint = None;
+                }
+            }
+            Err(e) => warn!("Keeping the keybindings: {e:#}"),
+        }
+        match files.read::<OpenerConfig>("open.toml") {
+            Ok(config) => self.opener.set_config(config),
+            Err(e) => warn!("Keeping the opener rules: {e:#}"),
+        }
+        let colors = files
+            .read::<Config>("config.toml")
+            .and_then(|config| colors_from_config(config.colors, config.themes));
+        if let Err(e) = colors {
+            warn!("Keeping the colors: {e:#}");
+        }
+        info!("Reloaded the config");
+        self.redraw_everything();
+    }
+
+    /// Shows the next frame of a previewed video (unless a console covers the preview).
+    fn next_video_frame(&mut self) {
+        if let (Mode::Console { .. }, _) | (_, Some(_)) = (&self.mode, &self.marked_summary) {
+            return;
+        }
+        if let PreviewPanel::File(preview) = self.right.panel_mut() {
+            if preview.next_frame() {
+                self.redraw_right();
+            }
+        }
+    }
+
+    /// Replaces the preview by a summary of the marked items, while more than one item is marked.
+    fn update_marked_summary(&mut self) {
+        let marked: Vec<PathBuf> = self
+            .center
+            .panel()
+            .elements()
+            .filter(|elem| elem.is_marked())
+            .map(|elem| elem.path().to_path_buf())
+            .collect();
+        if marked.len() < 2 {
+            if self.marked_summary.take().is_some() {
+                self.redraw.right = true;
+            }
+        } else if self.marked_summary.as_ref().map(|s| s.paths()) != Some(&marked) {
+            self.marked_summary = Some(MarkedSummary::new(marked));
+            self.redraw.right = true;
+        }
+    }
+
     fn draw_console(&mut self) -> Result<()> {
         if self.redraw.console {
             if let Mode::Console { console } = &mut self.mode {
@@ -512,9 +1449,7 @@ impl PanelManager {
         self.show_hidden = !self.show_hidden;
         self.left.panel_mut().set_hidden(self.show_hidden);
         self.center.panel_mut().set_hidden(self.show_hidden);
-        if let PreviewPanel::Dir(panel) = self.right.panel_mut() {
-            panel.set_hidden(self.show_hidden);
-        };
+        self.right.panel_mut().set_hidden(self.show_hidden);
         // FIX: Re-selecting path. If we are in a hidden directory, we want to re-select the
         // correct path in the left panel.
         self.left.panel_mut().select_path(
@@ -545,35 +1480,49 @@ impl PanelManager {
     //     self.redraw_right();
     // }
 
-    fn move_up(&mut self, step: usize) {
+    /// Returns false, if the cursor is already at the top.
+    fn move_up(&mut self, step: usize) -> bool {
         trace!("move-up");
-        if self.center.panel_mut().up(step) {
-            self.right
-                .new_panel_delayed(self.center.panel().selected_path());
-            self.redraw_center();
-            self.redraw_right();
-            self.rev_history.clear();
+        let moved = self.center.panel_mut().up(step);
+        if moved {
+            self.selection_changed();
             // self.stack.push(Operation::Move(Movement::Up));
         }
+        moved
     }
 
-    fn move_down(&mut self, step: usize) {
+    /// Returns false, if the cursor is already at the bottom.
+    fn move_down(&mut self, step: usize) -> bool {
         trace!("move-down");
-        if self.center.panel_mut().down(step) {
-            self.right
-                .new_panel_delayed(self.center.panel().selected_path());
-            self.redraw_center();
-            self.redraw_right();
-            self.rev_history.clear();
+        let moved = self.center.panel_mut().down(step);
+        if moved {
+            self.selection_changed();
             // self.stack.push(Operation::Move(Movement::Down));
         }
+        moved
+    }
+
+    /// Moves the cursor to the given line (starting at one), like `10G` in vim.
+    fn move_to_line(&mut self, line: usize) {
+        trace!("move-to-line {line}");
+        if self.center.panel_mut().select_nth(line.saturating_sub(1)) {
+            self.selection_changed();
+        }
+    }
+
+    fn selection_changed(&mut self) {
+        self.right
+            .new_panel_delayed(self.center.panel().selected_path());
+        self.redraw_center();
+        self.redraw_right();
+        self.rev_history.clear();
     }
 
     fn move_right(&mut self) {
         trace!("move-right");
         if let Some(selected) = self.center.panel().selected_path().map(|p| p.to_path_buf()) {
             // If the selected item is a directory, all panels will shift to the left
-            if selected.is_dir() {
+            if vfs::is_dir(&selected) {
                 self.previous = self.center.panel().path().to_path_buf();
                 debug!(
                     "push to history: {}, len={}",
@@ -591,8 +1540,13 @@ impl PanelManager {
                         .unwrap_or_default(),
                 ));
                 self.left.update_panel(self.center.panel().clone());
+                // The rest of a partially read directory was meant for the center panel
+                if self.left.panel().is_partial() {
+                    self.left.reload();
+                }
                 self.center
                     .new_panel_instant(self.right.panel().maybe_path());
+                self.record_visit();
 
                 if let Some(path) = self.rev_history.pop() {
                     info!(
@@ -613,6 +1567,8 @@ impl PanelManager {
                 }
 
                 self.redraw_panels();
+            } else if let Some(shortcut) = shortcut::read(&selected) {
+                self.follow_shortcut(shortcut);
             } else {
                 // NOTE: This is a blocking call, if we have a terminal application.
                 // The watchers are still active in the background.
@@ -625,13 +1581,18 @@ impl PanelManager {
                 //
                 // Solution:
                 // "Freeze" the panel and deactivate the watchers while the open function is blocked.
+                if self.headless {
+                    info!("Not opening '{}' in headless mode", selected.display());
+                    return;
+                }
+                let Some(selected) = self.local_copy(selected) else {
+                    return;
+                };
                 info!("Opening '{}'", selected.display());
                 self.center.freeze();
 
                 // Change working directory so that child processes gets spawned from the currently active directory.
-                if let Err(e) = std::env::set_current_dir(self.center.panel().path()) {
-                    error!("Failed to set working-directory for process: {e}");
-                }
+                self.set_working_dir();
                 if let Err(e) = self.opener.open(selected) {
                     /* failed to open selected */
                     error!("Opening failed: {e}");
@@ -645,6 +1606,50 @@ impl PanelManager {
         }
     }
 
+    /// Enters the directory of the shortcut, or opens its address with the default opener.
+    fn follow_shortcut(&mut self, shortcut: Shortcut) {
+        match shortcut {
+            Shortcut::Directory(dir) => {
+                info!("Following shortcut to {}", dir.display());
+                self.jump(dir);
+            }
+            Shortcut::Url(url) if self.headless => info!("Not opening '{url}' in headless mode"),
+            Shortcut::Url(url) => {
+                if let Err(e) = self.opener.open_default(Path::new(&url)) {
+                    error!("Cannot open '{url}': {e}");
+                }
+            }
+        }
+    }
+
+    /// Changes the working directory to the current directory, so that child processes are
+    /// spawned from there (directories on remote hosts are skipped).
+    fn set_working_dir(&self) {
+        let path = self.center.panel().path();
+        if vfs::is_remote(path) {
+            return;
+        }
+        if let Err(e) = std::env::set_current_dir(path) {
+            error!("Failed to set working-directory for process: {e}");
+        }
+    }
+
+    /// Returns the path, that applications can open the file with.
+    ///
+    /// Files on remote hosts are downloaded first, which blocks like opening a terminal application.
+    fn local_copy(&self, path: PathBuf) -> Option<PathBuf> {
+        if !vfs::is_remote(&path) {
+            return Some(path);
+        }
+        match remote::fetch(&path) {
+            Ok(copy) => Some(copy),
+            Err(e) => {
+                warn!("Cannot open '{}': {e:#}", path.display());
+                None
+            }
+        }
+    }
+
     fn move_left(&mut self) {
         trace!("move-left");
         // If the left panel is empty, we cannot move left:
@@ -660,9 +1665,20 @@ impl PanelManager {
             self.rev_history.push(path);
         }
         self.previous = self.center.panel().path().to_path_buf();
-        self.right
-            .update_panel(PreviewPanel::Dir(self.center.panel().clone()));
+        if tree_preview() {
+            self.right
+                .new_panel_delayed(Some(self.center.panel().path().to_path_buf()));
+        } else {
+            self.right
+                .update_panel(PreviewPanel::Dir(self.center.panel().clone()));
+            // The statistics of the directory are read by the preview manager
+            self.right.reload();
+        }
         self.center.update_panel(self.left.panel().clone());
+        if self.center.panel().is_partial() {
+            self.center.reload();
+        }
+        self.dir_history.visit(self.center.panel().path());
         // | m | l | m |
         // TODO: When we followed some symlink we don't want to take the parent here.
         match self.fwd_history.pop() {
@@ -711,27 +1727,133 @@ impl PanelManager {
             self.right
                 .new_panel_delayed(self.center.panel().selected_path());
             self.redraw_panels();
+            self.record_visit();
         }
     }
 
-    fn move_cursor(&mut self, movement: Move) {
+    /// Selects the file and shows the given line in the preview.
+    fn show_match(&mut self, path: PathBuf, line: usize) {
+        let path = self.select_file(path);
+        self.preview_line = Some((path, line));
+        self.show_preview_line();
+    }
+
+    /// Jumps to the directory of the file and selects it.
+    ///
+    /// Returns the canonical path of the file.
+    fn select_file(&mut self, path: PathBuf) -> PathBuf {
+        let path = path.canonicalize().unwrap_or(path);
+        if let Some(parent) = path.parent() {
+            self.jump(parent.to_path_buf());
+        }
+        self.center.panel_mut().select_path(&path, None);
+        self.right
+            .new_panel_delayed(self.center.panel().selected_path());
+        self.redraw_panels();
+        path
+    }
+
+    /// Scrolls the preview to the line of the last grep match, if the file is still previewed.
+    fn show_preview_line(&mut self) {
+        let Some((path, line)) = &self.preview_line else {
+            return;
+        };
+        if self.right.panel().path() != path {
+            self.preview_line = None;
+            return;
+        }
+        if let PreviewPanel::File(preview) = self.right.panel_mut() {
+            preview.show_line(*line);
+        }
+    }
+
+    /// Adds the current directory to the history of visited directories.
+    fn record_visit(&mut self) {
+        self.frecency.visit(self.center.panel().path());
+        self.dir_history.visit(self.center.panel().path());
+    }
+
+    /// Moves the cursor.
+    ///
+    /// The `count` (like the "5" in "5j") repeats vertical movements,
+    /// and selects the n-th line for `top` and `bottom`.
+    fn move_cursor(&mut self, movement: Move, count: Option<usize>) {
         // NOTE: Movement functions needs to determine which panels require a redraw.
+        let times = count.unwrap_or(1);
+        let page = self.layout.height() as usize;
         match movement {
-            Move::Up => self.move_up(1),
-            Move::Down => self.move_down(1),
+            Move::Up => {
+                if !self.move_up(times) && self.cursor_wrap {
+                    self.move_down(usize::MAX);
+                }
+            }
+            Move::Down => {
+                if !self.move_down(times) && self.cursor_wrap {
+                    self.move_up(usize::MAX);
+                }
+            }
             Move::Left => self.move_left(),
             Move::Right => self.move_right(),
-            Move::Top => self.move_up(usize::MAX),
-            Move::Bottom => self.move_down(usize::MAX),
-            Move::HalfPageForward => self.move_down(self.layout.height() as usize / 2),
-            Move::HalfPageBackward => self.move_up(self.layout.height() as usize / 2),
-            Move::PageForward => self.move_down(self.layout.height() as usize),
-            Move::PageBackward => self.move_up(self.layout.height() as usize),
+            Move::Top | Move::Bottom if count.is_some() => self.move_to_line(times),
+            Move::Top => {
+                self.move_up(usize::MAX);
+            }
+            Move::Bottom => {
+                self.move_down(usize::MAX);
+            }
+            Move::HalfPageForward => {
+                self.move_down((page / 2).saturating_mul(times));
+            }
+            Move::HalfPageBackward => {
+                self.move_up((page / 2).saturating_mul(times));
+            }
+            Move::PageForward => {
+                self.move_down(page.saturating_mul(times));
+            }
+            Move::PageBackward => {
+                self.move_up(page.saturating_mul(times));
+            }
             Move::JumpTo(path) => self.jump(path.into()),
             Move::JumpPrevious => self.jump(self.previous.clone()),
+            Move::HistoryBack => {
+                if let Some(dir) = self.dir_history.back() {
+                    self.jump(dir);
+                }
+            }
+            Move::HistoryForward => {
+                if let Some(dir) = self.dir_history.forward() {
+                    self.jump(dir);
+                }
+            }
         };
     }
 
+    /// Selects the next marked (or tagged) item into the given direction.
+    ///
+    /// If the search continues at the other end of the directory
+    /// (or there is no further match), a notice is shown in the footer.
+    fn select_marked(&mut self, forward: bool, tagged: bool) {
+        let wrap = self.search_wrap;
+        let panel = self.center.panel_mut();
+        let jump = match (forward, tagged) {
+            (true, false) => panel.select_next_marked(wrap),
+            (false, false) => panel.select_prev_marked(wrap),
+            (true, true) => panel.select_next_tagged(wrap),
+            (false, true) => panel.select_prev_tagged(wrap),
+        };
+        self.notice = match jump {
+            MarkedJump::Found => None,
+            MarkedJump::Wrapped if forward => Some("search hit BOTTOM, continuing at TOP"),
+            MarkedJump::Wrapped => Some("search hit TOP, continuing at BOTTOM"),
+            MarkedJump::NotFound => Some("no more matches"),
+        };
+        self.right
+            .new_panel_delayed(self.center.panel().selected_path());
+        self.redraw_center();
+        self.redraw_right();
+        self.redraw_footer();
+    }
+
     /// Returns a reference to all marked items.
     fn marked_items(&self) -> Vec<&DirElem> {
         let mut out = Vec::new();
@@ -790,37 +1912,312 @@ impl PanelManager {
         }
     }
 
-    /// Deletes a file or directory, based on the trash strategy.
-    fn delete_file(&self, file: &Path) {
-        // Check if we use the trash or not
-        if let Some(trash_path) = &self.trash_dir {
-            let destination = get_destination(file, trash_path.path()).unwrap();
-            let result = std::fs::rename(file, &destination);
-            if let Err(e) = result {
-                error!("Cannot delete {}: {e}", file.display());
+    /// Takes the items of the staging area, or the marked (or selected) items if nothing is staged.
+    fn staged_or_marked(&mut self) -> Vec<PathBuf> {
+        let staged = staging::take();
+        if staged.is_empty() {
+            return self.marked_or_selected();
+        }
+        self.unmark_all_items();
+        staged
+    }
+
+    /// Quits with the marked (or selected) items as result for a file picker.
+    fn pick(&mut self) -> CloseCmd {
+        let files = self.marked_or_selected();
+        info!("picked {} items", files.len());
+        CloseCmd::QuitWithFiles {
+            path: self.center.panel().path().to_path_buf(),
+            files,
+        }
+    }
+
+    /// Asks for a typed confirmation, if one of the affected items is protected.
+    ///
+    /// Returns true if the operation must not be executed right away.
+    /// Scripts cannot confirm anything, so the operation is refused instead.
+    fn guard(&mut self, operation: Guarded, affected: &[PathBuf]) -> bool {
+        let Some(protected) = find_protected(affected) else {
+            return false;
+        };
+        if self.headless {
+            warn!(
+                "refusing to {} protected path '{}'",
+                operation.name(),
+                protected.display()
+            );
+            return true;
+        }
+        let prompt = format!(
+            "'{}' is protected! Type {CONFIRMATION} to {}:",
+            protected.display(),
+            operation.name()
+        );
+        self.mode = Mode::ConfirmProtected {
+            input: Input::empty(),
+            prompt,
+            operation,
+        };
+        self.redraw_footer();
+        true
+    }
+
+    fn paste(&mut self, overwrite: bool) {
+        self.unmark_all_items();
+        let current_path = self.center.panel().path().to_path_buf();
+        let Some(clipboard) = self.take_clipboard() else {
+            return;
+        };
+        self.expect_transfer(&clipboard, &current_path);
+        let description = transfer_description(&clipboard, &current_path);
+        self.spawn_file_op(description, move |job| {
+            info!(
+                "paste {} items, overwrite = {}",
+                clipboard.files.len(),
+                overwrite
+            );
+            transfer_items(&clipboard.files, &current_path, clipboard.cut, false, job)
+        });
+        self.left.reload();
+        self.center.reload();
+        self.right.reload();
+        self.redraw_panels();
+    }
+
+    /// Selects the first pasted item, once it shows up (see [`Self::expect_item`]).
+    fn expect_transfer(&mut self, clipboard: &Clipboard, destination: &Path) {
+        if let Ok(plan) = TransferPlan::new(&clipboard.files, destination, clipboard.cut) {
+            if let Some((_, first)) = plan.items.first() {
+                self.expect_item(first);
             }
-        } else {
-            if file.is_file() {
-                let result = std::fs::remove_file(file);
-                if let Err(e) = result {
-                    error!("Cannot delete {}: {e}", file.display());
+        }
+    }
+
+    /// Creates links to the items of the clipboard in the current directory.
+    ///
+    /// Unlike pasting, the clipboard is kept - even if the items were cut.
+    fn paste_links(&mut self, kind: LinkKind) {
+        self.unmark_all_items();
+        self.sync_clipboard();
+        let Some(clipboard) = &self.clipboard else {
+            info!("Nothing to paste");
+            return;
+        };
+        let current_path = self.center.panel().path().to_path_buf();
+        let mut links = Vec::new();
+        for file in clipboard.files.iter() {
+            match link_item(file, &current_path, kind) {
+                Ok(link) => {
+                    debug!("Linked {} to {}", link.display(), file.display());
+                    links.push(link);
                 }
-            } else if file.is_dir() {
-                let result = std::fs::remove_dir_all(file);
-                if let Err(e) = result {
-                    error!("Cannot delete {}: {e}", file.display());
+                Err(e) => error!("Cannot link {}: {e}", file.display()),
+            }
+        }
+        info!("Created {} links", links.len());
+        if let Some(first) = links.first() {
+            self.expect_item(first);
+        }
+        self.left.reload();
+        self.center.reload();
+        self.right.reload();
+        self.redraw_panels();
+    }
+
+    /// Deletes all items and reloads the panels.
+    fn delete_items(&mut self, files: Vec<PathBuf>) {
+        info!("Deleted {} items", files.len());
+        self.unmark_all_items();
+        // self.stack.push(Operation::MoveItems { from: files.clone(), to: trash_dir.path().to_path_buf() });
+        for file in files {
+            self.delete_file(&file);
+        }
+        self.left.reload();
+        self.center.reload();
+        self.right.reload();
+    }
+
+    /// Deletes a file or directory, based on the trash strategy.
+    fn delete_file(&self, file: &Path) {
+        if let Err(e) = self.trash.put(file) {
+            error!("Cannot delete {}: {e}", file.display());
+        }
+    }
+
+    /// Moves the marked (or selected) items of the trash back to their original location.
+    fn restore_trash_items(&mut self) {
+        let Some(trash_files) = self.trash.files() else {
+            warn!("Trash feature is not activated - therefore there is nothing to restore.");
+            return;
+        };
+        if self.center.panel().path() != trash_files {
+            warn!("Items can only be restored inside of the trash (see 'view_trash')");
+            return;
+        }
+        let items = self.marked_or_selected();
+        let mut restored = 0;
+        for item in items.iter().filter(|item| self.trash.contains(item)) {
+            match self.trash.restore(item) {
+                Ok(original) => {
+                    debug!("Restored {}", original.display());
+                    restored += 1;
                 }
+                Err(e) => error!("Cannot restore {}: {e}", item.display()),
             }
         }
+        info!("Restored {restored} items from the trash");
+        self.unmark_all_items();
+        self.left.reload();
+        self.center.reload();
+        self.right.reload();
+    }
+
+    /// Applies an incoming dir-panel to the panel it belongs to.
+    fn handle_dir_update(&mut self, panel: DirPanel, state: PanelState) {
+        // Find panel and update it
+        if self.center.check_update(&state) {
+            self.center.update_panel(panel);
+            self.select_expected_item();
+            // update preview (if necessary)
+            self.right
+                .new_panel_delayed(self.center.panel().selected_path());
+            self.redraw_center();
+            self.redraw_right();
+            self.redraw_console();
+        } else if self.left.check_update(&state) {
+            self.left.update_panel(panel);
+            self.left.panel_mut().select_path(
+                self.center.panel().path(),
+                Some(self.center.panel().selected_idx()),
+            );
+            self.redraw_left();
+            self.redraw_console();
+        } else {
+            // Reduce log level here, this is not that important
+            debug!("unknown panel update: {:?}", state);
+        }
+    }
+
+    /// Selects the item after the reload, that brings it into the center panel.
+    ///
+    /// For nested paths (like `a/b/c` of a mkdir) the topmost new directory is selected.
+    fn expect_item(&mut self, path: &Path) {
+        let dir = self.center.panel().path();
+        self.expected_item = path
+            .ancestors()
+            .find(|ancestor| ancestor.parent() == Some(dir))
+            .map(|item| (item.to_path_buf(), Instant::now()));
+    }
+
+    /// Selects the expected item (see [`Self::expect_item`]), if it is in the center panel by now.
+    fn select_expected_item(&mut self) {
+        let Some((path, since)) = &self.expected_item else {
+            return;
+        };
+        if since.elapsed() > EXPECTED_ITEM_TIMEOUT
+            || path.parent() != Some(self.center.panel().path())
+        {
+            self.expected_item = None;
+            return;
+        }
+        let path = path.clone();
+        self.center.panel_mut().select_path(&path, None);
+        if self.center.panel().selected_path() == Some(path.as_path()) {
+            self.expected_item = None;
+        }
+    }
+
+    /// Shows the subdirectories of the center panel in a flat list (or returns to the normal view).
+    ///
+    /// The count (like the "3" in "3zF") sets the depth, which also changes the depth of a flat view.
+    fn toggle_flat(&mut self) {
+        let count = self.parser.count();
+        let depth = match (self.center.flat_depth(), count) {
+            (Some(_), None) => None,
+            (_, depth) => Some(depth.unwrap_or(DEFAULT_FLAT_DEPTH)),
+        };
+        // The content is read by the dir-manager, see `handle_dir_update`
+        self.center.set_flat(depth);
+    }
+
+    /// Shows the size of the items in the delete prompt, once it is calculated.
+    fn update_delete_prompt(&mut self) {
+        let Mode::ConfirmDelete {
+            files,
+            permanent,
+            prompt,
+            size: size @ Some(_),
+        } = &mut self.mode
+        else {
+            return;
+        };
+        match size.as_mut().map(|rx| rx.try_recv()) {
+            Some(Ok(total)) => *prompt = delete_prompt(files, *permanent, Some(total)),
+            Some(Err(TryRecvError::Empty)) => return,
+            _ => (),
+        }
+        *size = None;
+        self.redraw_footer();
+    }
+
+    /// Shows the preview of the selected item, after the groups of the center panel have changed.
+    fn update_groups(&mut self) {
+        self.right
+            .new_panel_delayed(self.center.panel().selected_path());
+        self.redraw_center();
+        self.redraw_right();
+    }
+
+    /// Redraws the panels that are still loading, to show that rfm is not stuck.
+    fn animate_loading(&mut self) {
+        if self.left.panel().is_loading() {
+            self.redraw_left();
+        }
+        if self.center.panel().is_loading() {
+            self.redraw_center();
+        }
+        if self.right.panel().is_loading() {
+            self.redraw_right();
+        }
+    }
+
+    /// Applies an incoming preview-panel to the right panel.
+    fn handle_preview_update(&mut self, panel: PreviewPanel, state: PanelState) {
+        if self.right.check_update(&state) {
+            self.right.update_panel(panel);
+            self.show_preview_line();
+            self.redraw_right();
+            self.redraw_console();
+        }
     }
 
     pub async fn run(mut self) -> Result<CloseCmd> {
+        let Some(mut event_reader) = self.event_reader.take() else {
+            return Ok(CloseCmd::QuitErr {
+                error: "cannot run interactively without a terminal",
+            });
+        };
+
         // Initial draw
         self.redraw_everything();
         self.draw()?;
 
+        let mut hint_timer = tokio::time::interval(HINT_INTERVAL);
+        let mut refresh_timer = tokio::time::interval(REFRESH_INTERVAL);
+        let mut spinner_timer = tokio::time::interval(SPINNER_INTERVAL);
+        spinner_timer.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+
         let close_cmd = loop {
-            let event_reader = self.event_reader.next().fuse();
+            let next_event = event_reader.next().fuse();
+            let key_deadline = self.parser.deadline().map(tokio::time::Instant::from_std);
+            let reload_deadline = self.reload_deadline.map(tokio::time::Instant::from_std);
+            let popup_deadline = if self.key_popup {
+                None
+            } else {
+                self.parser
+                    .popup_deadline()
+                    .map(tokio::time::Instant::from_std)
+            };
             tokio::select! {
                 // Check incoming new logs
                 () = self.logger.update() => {
@@ -829,45 +2226,91 @@ impl PanelManager {
                 // Check incoming new dir-panels
                 result = self.dir_rx.recv() => {
                     // Shutdown if sender has been dropped
-                    if result.is_none() {
+                    let Some((panel, state)) = result else {
                         break CloseCmd::QuitErr { error: "DirPanel receiver has been dropped" };
-                    }
-                    let (panel, state) = result.unwrap();
-
-                    // Find panel and update it
-                    if self.center.check_update(&state) {
-                        self.center.update_panel(panel);
-                        // update preview (if necessary)
-                        self.right.new_panel_delayed(self.center.panel().selected_path());
-                        self.redraw_center();
-                        self.redraw_right();
-                        self.redraw_console();
-                    } else if self.left.check_update(&state) {
-                        self.left.update_panel(panel);
-                        self.left.panel_mut().select_path(self.center.panel().path(), Some(self.center.panel().selected_idx()));
-                        self.redraw_left();
-                        self.redraw_console();
-                    } else {
-                        // Reduce log level here, this is not that important
-                        debug!("unknown panel update: {:?}", state);
-                    }
+                    };
+                    self.handle_dir_update(panel, state);
                 }
                 // Check incoming new preview-panels
                 result = self.prev_rx.recv() => {
                     // Shutdown if sender has been dropped
-                    if result.is_none() {
+                    let Some((panel, state)) = result else {
                         break CloseCmd::QuitErr { error: "Preview receiver has been dropped" };
-                    }
-                    let (panel, state) = result.unwrap();
-
-                    if self.right.check_update(&state) {
-                        self.right.update_panel(panel);
-                        self.redraw_right();
-                        self.redraw_console();
-                    }
+                    };
+                    self.handle_preview_update(panel, state);
+                }
+                // Check for new matches of a grep search
+                Some(()) = self.grep_rx.recv() => {
+                    self.redraw_console();
+                }
+                // Show calculated checksums
+                Some(()) = self.checksum_rx.recv() => {
+                    self.redraw_center();
+                    self.redraw_footer();
+                }
+                // Check progress of the background caching
+                Some(progress) = self.cache_rx.recv() => {
+                    self.cache_status.update(progress);
+                    self.redraw_footer();
+                }
+                // Execute requests of external tools
+                Some(request) = self.server_rx.recv() => {
+                    if let Some(close_cmd) = self.handle_request(request)? {
+                        break close_cmd;
+                    }
+                }
+                // Wait until the config files are completely written
+                Some(()) = self.config_rx.recv() => {
+                    self.reload_deadline = Some(Instant::now() + RELOAD_DELAY);
+                }
+                _ = tokio::time::sleep_until(reload_deadline.unwrap_or_else(tokio::time::Instant::now)), if reload_deadline.is_some() => {
+                    self.reload_deadline = None;
+                    self.reload_config();
+                }
+                // Rotate the hints in the footer
+                _ = hint_timer.tick() => {
+                    self.next_hint();
+                }
+                // Keep the jobs overlay up to date
+                _ = refresh_timer.tick() => {
+                    if let Mode::Console { console } = &mut self.mode {
+                        if console.needs_refresh() {
+                            let op = console.poll();
+                            if let Some(close_cmd) = self.handle_console_op(op)? {
+                                break close_cmd;
+                            }
+                            self.redraw_console();
+                        }
+                    }
+                    self.update_jobs_summary();
+                    self.check_system_clipboard();
+                    self.next_video_frame();
+                    // Programs in the background may have written over the panels
+                    if opener::child_exited() {
+                        self.relayout()?;
+                    }
+                }
+                // Drop unfinished key sequences after a while
+                _ = tokio::time::sleep_until(key_deadline.unwrap_or_else(tokio::time::Instant::now)), if key_deadline.is_some() => {
+                    self.parser.clear();
+                    self.redraw_panels();
+                    self.redraw_footer();
+                }
+                // Show the continuations of unfinished key sequences in a popup
+                _ = tokio::time::sleep_until(popup_deadline.unwrap_or_else(tokio::time::Instant::now)), if popup_deadline.is_some() => {
+                    self.key_popup = true;
+                    self.redraw_panels();
+                }
+                // Animate the panels that are still loading
+                _ = spinner_timer.tick() => {
+                    self.animate_loading();
+                    if self.marked_summary.as_mut().is_some_and(|s| s.poll()) {
+                        self.redraw_right();
+                    }
+                    self.update_delete_prompt();
                 }
                 // Check incoming new events
-                result = event_reader => {
+                result = next_event => {
                     // Shutdown if reader has been dropped
                     match result {
                         Some(event) => {
@@ -892,11 +2335,1110 @@ impl PanelManager {
         Ok(close_cmd)
     }
 
+    /// Executes the steps of a script without a terminal.
+    ///
+    /// After every step, the command together with the current directory and selection
+    /// is written to stdout. If the script does not quit by itself,
+    /// rfm quits after the last step.
+    pub async fn run_script(mut self, steps: Vec<ScriptStep>) -> Result<CloseCmd> {
+        for step in steps {
+            self.settle().await;
+            let close_cmd = self.run_step(&step)?;
+            self.settle().await;
+            let selected = self
+                .center
+                .panel()
+                .selected_path()
+                .map(|p| p.display().to_string())
+                .unwrap_or_default();
+            writeln!(
+                self.stdout,
+                "{step}: {} [{selected}]",
+                self.center.panel().path().display()
+            )?;
+            if let Some(close_cmd) = close_cmd {
+                return Ok(close_cmd);
+            }
+        }
+        Ok(CloseCmd::QuitWithPath {
+            path: self.center.panel().path().to_path_buf(),
+        })
+    }
+
+    /// Executes a command, and enters its input (if any).
+    fn run_step(&mut self, step: &ScriptStep) -> Result<Option<CloseCmd>> {
+        // The archive is created without the dialog
+        if let (Command::Archive, Some(spec)) = (&step.command, &step.input) {
+            let spec: ArchiveSpec = match spec.parse() {
+                Ok(spec) => spec,
+                Err(e) => {
+                    warn!("{e}");
+                    return Ok(None);
+                }
+            };
+            let items = self.marked_or_selected();
+            if items.is_empty() {
+                warn!("Nothing is selected");
+                return Ok(None);
+            }
+            let plan = spec.plan(items, self.center.panel().path().to_path_buf());
+            return self.handle_console_op(ConsoleOp::Archive(plan));
+        }
+        let close_cmd = self.handle_command(step.command.clone())?;
+        if let Some(input) = &step.input {
+            self.set_input(input);
+            let enter = KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE);
+            self.handle_event(Event::Key(enter))?;
+        }
+        Ok(close_cmd)
+    }
+
+    /// Handles a request of the control server (see [`crate::server`]).
+    fn handle_request(&mut self, request: Request) -> Result<Option<CloseCmd>> {
+        // Leave open consoles and input fields first
+        if !matches!(self.mode, Mode::Normal) {
+            let esc = KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE);
+            self.handle_event(Event::Key(esc))?;
+        }
+        match request {
+            Request::Step(step) => return self.run_step(&step),
+            Request::Select(name) => {
+                let path = self.center.panel().path().join(name);
+                self.select_file(path);
+            }
+            Request::Reveal(path) => {
+                self.select_file(path);
+            }
+        }
+        Ok(None)
+    }
+
+    /// Applies all incoming panel updates, until there was no update for some time.
+    async fn settle(&mut self) {
+        loop {
+            tokio::select! {
+                Some((panel, state)) = self.dir_rx.recv() => {
+                    self.handle_dir_update(panel, state);
+                }
+                Some((panel, state)) = self.prev_rx.recv() => {
+                    self.handle_preview_update(panel, state);
+                }
+                _ = tokio::time::sleep(Duration::from_millis(100)) => break,
+            }
+        }
+    }
+
+    /// Replaces the content of the active input field.
+    fn set_input(&mut self, text: &str) {
+        match &mut self.mode {
+            Mode::Search { input }
+            | Mode::MarkGlob { input }
+            | Mode::MarkByAge { input }
+            | Mode::MarkByType { input }
+            | Mode::Tag { input }
+            | Mode::Rename { input, .. }
+            | Mode::CreateItem { input, .. } => {
+                *input = Input::from_str(text);
+            }
+            _ => (),
+        }
+    }
+
+    /// Inserts pasted text into the active input field.
+    fn paste_input(&mut self, text: &str) {
+        match &mut self.mode {
+            Mode::Search { input } => {
+                input.insert_str(text);
+                self.center
+                    .panel_mut()
+                    .update_search(input.get().to_string());
+            }
+            Mode::CreateItem { input, is_dir, .. } => {
+                input.insert_str(text);
+                self.center
+                    .panel_mut()
+                    .inject_new_element(input.get().to_string(), *is_dir);
+            }
+            Mode::MarkGlob { input }
+            | Mode::MarkByAge { input }
+            | Mode::MarkByType { input }
+            | Mode::Tag { input }
+            | Mode::Rename { input, .. } => input.insert_str(text),
+            _ => return,
+        }
+        self.redraw_center();
+        self.redraw_footer();
+    }
+
+    /// Runs a blocking file operation as a background job (see [`jobs`]).
+    ///
+    /// In headless mode, the operation is executed directly, so that subsequent script
+    /// commands can rely on its result. Otherwise it is moved to a blocking task.
+    /// An error marks the job as failed in the jobs overlay.
+    fn spawn_file_op<F>(&self, description: String, op: F)
+    where
+        F: FnOnce(&Job) -> anyhow::Result<()> + Send + 'static,
+    {
+        let mut job = jobs::start(JobKind::FileOperation, description);
+        let run = move || {
+            if let Err(e) = op(&job) {
+                warn!("{e}");
+                job.fail(e.to_string());
+            }
+        };
+        if self.headless {
+            run();
+        } else {
+            tokio::task::spawn_blocking(run);
+        }
+    }
+
+    /// Executes a command in normal mode.
+    ///
+    /// Returns `Some(CloseCmd)` if the application needs to shut down.
+    fn handle_command(&mut self, command: Command) -> Result<Option<CloseCmd>> {
+        if self.readonly && command.is_mutating() {
+            warn!("'{command}' is not available in read-only mode");
+            return Ok(None);
+        }
+        if command.is_mutating()
+            && vfs::is_remote(self.center.panel().path())
+            && !matches!(
+                command,
+                Command::ConnectRemote
+                    | Command::Drives
+                    | Command::RestoreTrash
+                    | Command::EmptyTrash
+            )
+        {
+            warn!("'{command}' is not available on remote hosts");
+            return Ok(None);
+        }
+        // Any other command finishes the visual selection, and acts on its marks
+        if !matches!(
+            command,
+            Command::Move(_) | Command::VisualMode | Command::None
+        ) {
+            self.center.panel_mut().end_visual();
+        }
+        match command {
+            Command::Move(Move::Right)
+                if self.picker
+                    && self
+                        .center
+                        .panel()
+                        .selected_path()
+                        .is_some_and(|path| !vfs::is_dir(path)) =>
+            {
+                return Ok(Some(self.pick()));
+            }
+            Command::SelectForPicker => return Ok(Some(self.pick())),
+            Command::Move(direction) => {
+                // The user has moved on, so a late reload must not take the cursor away
+                self.expected_item = None;
+                let count = self.parser.count();
+                self.move_cursor(direction, count);
+                if self.center.panel().is_visual() {
+                    self.center.panel_mut().update_visual();
+                    self.redraw_center();
+                }
+            }
+            Command::ViewTrash => {
+                if let Some(files) = self.trash.files() {
+                    self.jump(files);
+                } else {
+                    warn!("Trash feature is not activated - therefore there is no trash-directory to jump to.")
+                }
+            }
+            Command::RestoreTrash => self.restore_trash_items(),
+            Command::EmptyTrash => {
+                if self.trash.is_permanent() {
+                    warn!("Trash feature is not activated - therefore there is no trash to empty.");
+                    return Ok(None);
+                };
+                let prompt = format!(
+                    "Permanently delete {} items in the trash?",
+                    self.trash.len()
+                );
+                if self.headless {
+                    if let Err(e) = self.trash.empty() {
+                        error!("Cannot empty the trash: {e}");
+                    }
+                } else {
+                    self.mode = Mode::ConfirmEmptyTrash { prompt };
+                    self.redraw_footer();
+                }
+            }
+            Command::ToggleHidden => self.toggle_hidden(),
+            Command::TogglePreview => {
+                self.layout.toggle_preview();
+                self.relayout()?;
+            }
+            Command::ToggleZen => {
+                self.layout.toggle_zen();
+                self.relayout()?;
+            }
+            Command::CycleLayout => {
+                let preset = self.layout.preset().next();
+                self.layout.set_preset(preset);
+                info!("using the {preset} layout");
+                self.relayout()?;
+            }
+            Command::ToggleDetails => {
+                self.center.panel_mut().toggle_details();
+                self.redraw_center();
+            }
+            Command::ToggleFlat => self.toggle_flat(),
+            Command::GroupByExtension => {
+                self.center.panel_mut().toggle_groups();
+                if self.center.panel().is_grouped() {
+                    info!("grouped items by extension");
+                }
+                self.update_groups();
+            }
+            Command::ToggleGroup => {
+                self.center.panel_mut().toggle_group();
+                self.update_groups();
+            }
+            Command::ExpandGroups => {
+                self.center.panel_mut().expand_groups();
+                self.update_groups();
+            }
+            Command::CycleSort => {
+                let mode = sort_mode().next();
+                set_sort_mode(mode);
+                info!("sorting by {mode}");
+                self.left.panel_mut().resort();
+                self.center.panel_mut().resort();
+                if let PreviewPanel::Dir(panel) = self.right.panel_mut() {
+                    panel.resort();
+                }
+                self.redraw_panels();
+            }
+            Command::CycleTheme => {
+                info!("using the color theme '{}'", cycle_theme());
+                self.redraw_everything();
+            }
+            Command::ReloadConfig => self.reload_config(),
+            Command::ToggleCaching => {
+                let paused = !CACHE_PAUSED.load(std::sync::atomic::Ordering::Relaxed);
+                CACHE_PAUSED.store(paused, std::sync::atomic::Ordering::Relaxed);
+                info!(
+                    "background caching {}",
+                    if paused { "paused" } else { "resumed" }
+                );
+                self.redraw_footer();
+            }
+            Command::ClearCache => {
+                // The left and center panel share the same cache
+                let (dirs, dir_bytes) = self.center.clear_cache();
+                let (previews, preview_bytes) = self.right.clear_cache();
+                info!(
+                    "cleared cache ({dirs} directories, {previews} previews, {})",
+                    file_size_str((dir_bytes + preview_bytes) as u64)
+                );
+                // Reading the current directory starts a new crawl, that fills the caches again
+                self.left.reload();
+                self.center.reload();
+                self.right.reload();
+            }
+            Command::Image(cmd) => {
+                let PreviewPanel::File(preview) = self.right.panel_mut() else {
+                    return Ok(None);
+                };
+                let Some(view) = preview.image_view_mut() else {
+                    return Ok(None);
+                };
+                match cmd {
+                    ImageCmd::ZoomIn => view.zoom(1.25),
+                    ImageCmd::ZoomOut => view.zoom(0.8),
+                    ImageCmd::CycleFit => view.cycle_fit(),
+                    ImageCmd::PanLeft => view.pan(-1.0, 0.0),
+                    ImageCmd::PanRight => view.pan(1.0, 0.0),
+                    ImageCmd::PanUp => view.pan(0.0, -1.0),
+                    ImageCmd::PanDown => view.pan(0.0, 1.0),
+                }
+                self.redraw_right();
+            }
+            Command::ToggleLog => self.toggle_log(),
+            Command::ContextMenu => {
+                let Some(selected) = self.center.panel().selected_path() else {
+                    info!("Nothing selected");
+                    return Ok(None);
+                };
+                let entries = menu_entries(selected, &self.opener);
+                // Scripts just print the available actions
+                if self.headless {
+                    for entry in entries.iter() {
+                        let key = entry.key.map(String::from).unwrap_or_default();
+                        writeln!(self.stdout, "{key:>1} {}", entry.label)?;
+                    }
+                    return Ok(None);
+                }
+                let title = selected
+                    .file_name()
+                    .map(|n| n.to_string_lossy().to_string())
+                    .unwrap_or_default();
+                self.pre_console_path = self.center.panel().path().to_path_buf();
+                self.mode = Mode::Console {
+                    console: Box::new(MenuConsole::new(title, entries)),
+                };
+                self.redraw_console();
+            }
+            Command::Cd { zoxide } => {
+                self.pre_console_path = self.center.panel().path().to_path_buf();
+                self.mode = if zoxide {
+                    // Fall back to our own history, if zoxide is not installed
+                    let source = if zoxide_installed() {
+                        QuerySource::Zoxide
+                    } else {
+                        QuerySource::Frecency(self.frecency.ranked())
+                    };
+                    Mode::Console {
+                        console: Box::new(JumpConsole::from_panel(self.center.panel(), source)),
+                    }
+                } else {
+                    Mode::Console {
+                        console: Box::new(DirConsole::from_panel(self.center.panel())),
+                    }
+                };
+                self.redraw_console();
+            }
+            Command::JumpFrecent => {
+                self.pre_console_path = self.center.panel().path().to_path_buf();
+                let source = QuerySource::Frecency(self.frecency.ranked());
+                self.mode = Mode::Console {
+                    console: Box::new(QueryConsole::from_panel(self.center.panel(), source)),
+                };
+                self.redraw_console();
+            }
+            Command::RecentFiles => {
+                self.pre_console_path = self.center.panel().path().to_path_buf();
+                self.mode = Mode::Console {
+                    console: Box::new(RecentConsole::new(self.opener.recent_files())),
+                };
+                self.redraw_console();
+            }
+            Command::DirHistory => {
+                self.pre_console_path = self.center.panel().path().to_path_buf();
+                self.mode = Mode::Console {
+                    console: Box::new(RecentConsole::history(self.dir_history.entries())),
+                };
+                self.redraw_console();
+            }
+            Command::ConnectRemote => {
+                self.pre_console_path = self.center.panel().path().to_path_buf();
+                self.mode = Mode::Console {
+                    console: Box::<RemoteConsole>::default(),
+                };
+                self.redraw_console();
+            }
+            Command::Shell => self.run_external(|opener| opener.shell()),
+            Command::OpenMarked => {
+                let files: Vec<PathBuf> = self
+                    .marked_or_selected()
+                    .into_iter()
+                    .filter(|path| !vfs::is_dir(path))
+                    .filter_map(|path| self.local_copy(path))
+                    .collect();
+                if files.is_empty() {
+                    info!("There are no files to open");
+                } else {
+                    self.run_external(|opener| opener.open_all(&files));
+                }
+                self.unmark_all_items();
+            }
+            Command::Terminal => self.run_external(|opener| opener.terminal()),
+            Command::Help => {
+                self.pre_console_path = self.center.panel().path().to_path_buf();
+                self.mode = Mode::Console {
+                    console: Box::new(HelpConsole::new(self.parser.help())),
+                };
+                self.redraw_console();
+            }
+            Command::Jobs => {
+                self.pre_console_path = self.center.panel().path().to_path_buf();
+                self.mode = Mode::Console {
+                    console: Box::<JobsConsole>::default(),
+                };
+                self.redraw_console();
+            }
+            Command::Properties => {
+                if let Some(selected) = self.center.panel().selected_path() {
+                    self.pre_console_path = self.center.panel().path().to_path_buf();
+                    self.mode = Mode::Console {
+                        console: Box::new(PropertiesConsole::new(selected)),
+                    };
+                    self.redraw_console();
+                }
+            }
+            Command::Doctor => {
+                let (installed, missing): (Vec<_>, Vec<_>) = doctor::tools()
+                    .into_iter()
+                    .partition(|tool| tool.found.is_some());
+                let installed = installed
+                    .into_iter()
+                    .map(|tool| {
+                        let path = tool.found.unwrap_or_default();
+                        let text = format!("{} ({})", path.display(), tool.feature);
+                        (tool.programs.join(" | "), text)
+                    })
+                    .collect();
+                let missing = missing
+                    .into_iter()
+                    .map(|tool| (tool.programs.join(" | "), tool.feature.to_string()))
+                    .collect();
+                let config = doctor::missing_programs(doctor::config_programs(&self.opener))
+                    .into_iter()
+                    .map(|missing| (missing.program, missing.entry))
+                    .collect();
+                let lines = vec![
+                    ("Installed programs", installed),
+                    ("Missing programs (these features are disabled)", missing),
+                    ("Missing programs of the config files", config),
+                ];
+                self.pre_console_path = self.center.panel().path().to_path_buf();
+                self.mode = Mode::Console {
+                    console: Box::new(HelpConsole::new(lines).with_title("Doctor")),
+                };
+                self.redraw_console();
+            }
+            Command::Drives => {
+                self.pre_console_path = self.center.panel().path().to_path_buf();
+                self.mode = Mode::Console {
+                    console: Box::<DrivesConsole>::default(),
+                };
+                self.redraw_console();
+            }
+            Command::Search | Command::SearchBackward => {
+                self.search_backward = matches!(command, Command::SearchBackward);
+                self.mode = Mode::Search {
+                    input: Input::empty(),
+                };
+                self.redraw_footer();
+            }
+            Command::Grep => {
+                self.pre_console_path = self.center.panel().path().to_path_buf();
+                self.mode = Mode::Console {
+                    console: Box::new(GrepConsole::new(self.center.panel(), self.grep_tx.clone())),
+                };
+                self.redraw_console();
+            }
+            Command::Rename => {
+                let selected_path = self.center.panel().selected_path();
+                let selected = selected_path
+                    .and_then(|p| p.file_name())
+                    .and_then(|f| f.to_owned().into_string().ok())
+                    .unwrap_or_default();
+                let is_dir = selected_path.is_some_and(|p| p.is_dir());
+                self.mode = Mode::Rename {
+                    input: Input::from_filename(selected, is_dir),
+                    exists: None,
+                };
+                self.redraw_footer();
+            }
+            Command::Next => self.select_marked(!self.search_backward, false),
+            Command::Previous => self.select_marked(self.search_backward, false),
+            Command::NextTagged => self.select_marked(true, true),
+            Command::PreviousTagged => self.select_marked(false, true),
+            Command::Mkdir => {
+                self.mode = Mode::CreateItem {
+                    input: Input::empty(),
+                    is_dir: true,
+                    content: None,
+                    exists: None,
+                };
+                self.redraw_footer();
+            }
+            Command::Touch => {
+                self.mode = Mode::CreateItem {
+                    input: Input::empty(),
+                    is_dir: false,
+                    content: None,
+                    exists: None,
+                };
+                self.redraw_footer();
+            }
+            Command::CopyContents => {
+                if let Some(path) = self.center.panel().selected_path() {
+                    match read_text_file(path).and_then(|text| copy_to_clipboard(&text)) {
+                        Ok(()) => info!(
+                            "Copied the content of '{}' to the clipboard",
+                            path.display()
+                        ),
+                        Err(e) => error!("Cannot copy {}: {e}", path.display()),
+                    }
+                }
+            }
+            Command::Stage => {
+                let files = self.marked_or_selected();
+                let n = files.len();
+                if staging::toggle(files) {
+                    info!("staged {n} items ({} in total)", staging::len());
+                } else {
+                    info!("unstaged {n} items ({} left)", staging::len());
+                }
+                self.unmark_all_items();
+                self.redraw_footer();
+            }
+            Command::Tag => {
+                self.mode = Mode::Tag {
+                    input: Input::empty(),
+                };
+                self.redraw_footer();
+            }
+            Command::Untag => {
+                let items = self.marked_or_selected();
+                let n = tags::remove(&items);
+                info!("removed the tags of {n} items");
+                self.unmark_all_items();
+                self.redraw_panels();
+            }
+            Command::TaggedItems => {
+                self.pre_console_path = self.center.panel().path().to_path_buf();
+                self.mode = Mode::Console {
+                    console: Box::new(RecentConsole::tagged(tags::tagged())),
+                };
+                self.redraw_console();
+            }
+            Command::UnstageAll => {
+                let n = staging::take().len();
+                info!("cleared {n} items from the staging area");
+                self.redraw_panels();
+                self.redraw_footer();
+            }
+            Command::PasteToFile => match paste_from_clipboard() {
+                Ok(text) => {
+                    self.mode = Mode::CreateItem {
+                        input: Input::empty(),
+                        is_dir: false,
+                        content: Some(text),
+                        exists: None,
+                    };
+                    self.redraw_footer();
+                }
+                Err(e) => error!("Cannot read the clipboard: {e}"),
+            },
+            Command::Mark => {
+                self.center.panel_mut().mark_selected_item();
+                self.move_cursor(Move::Down, None);
+            }
+            Command::Breadcrumbs => {
+                let crumbs = breadcrumbs(&self.absolute_selected_path());
+                let dir = self.center.panel().path();
+                let dir = dir.canonicalize().unwrap_or_else(|_| dir.to_path_buf());
+                let idx = crumbs
+                    .iter()
+                    .position(|(_, path)| *path == dir)
+                    .unwrap_or(crumbs.len().saturating_sub(1));
+                self.mode = Mode::Breadcrumbs { crumbs, idx };
+                self.redraw_panels();
+            }
+            Command::MarkGlob => {
+                self.mode = Mode::MarkGlob {
+                    input: Input::empty(),
+                };
+                self.redraw_footer();
+            }
+            Command::MarkByAge => {
+                self.mode = Mode::MarkByAge {
+                    input: Input::empty(),
+                };
+                self.redraw_footer();
+            }
+            Command::MarkByType => {
+                self.mode = Mode::MarkByType {
+                    input: Input::empty(),
+                };
+                self.redraw_footer();
+            }
+            Command::VisualMode => {
+                if self.center.panel().is_visual() {
+                    self.center.panel_mut().end_visual();
+                } else {
+                    self.center.panel_mut().start_visual();
+                }
+                self.redraw_center();
+            }
+            Command::MarkAll => {
+                self.center.panel_mut().mark_all();
+                self.redraw_center();
+            }
+            Command::InvertMarks => {
+                self.center.panel_mut().invert_marks();
+                self.redraw_center();
+            }
+            Command::UnmarkAll => {
+                self.unmark_all_items();
+            }
+            Command::Cut => {
+                let files = self.staged_or_marked();
+                if !self.guard(Guarded::Cut(files.clone()), &files) {
+                    info!("cut {} items", files.len());
+                    self.set_clipboard(Clipboard::new(files, true));
+                }
+            }
+            Command::Checksum(algorithm) => {
+                let files = self.marked_or_selected();
+                info!("calculating {algorithm} of {} items", files.len());
+                self.unmark_all_items();
+                checksum::spawn(files, algorithm, self.checksum_tx.clone());
+            }
+            Command::Copy => {
+                let files = self.staged_or_marked();
+                info!("copying {} items", files.len());
+                self.set_clipboard(Clipboard::new(files, false));
+            }
+            Command::Delete => {
+                let files = self.marked_or_selected();
+                if files.is_empty() || self.guard(Guarded::Delete(files.clone()), &files) {
+                    return Ok(None);
+                }
+                // Permanent deletes are always confirmed, scripts never ask
+                let permanent = self.trash.is_permanent();
+                if self.headless || !(permanent || self.confirm_trash) {
+                    self.delete_items(files);
+                } else {
+                    let (tx, rx) = oneshot::channel();
+                    let paths = files.clone();
+                    tokio::task::spawn_blocking(move || {
+                        let _ = tx.send(total_size(&paths, 100_000));
+                    });
+                    let prompt = delete_prompt(&files, permanent, None);
+                    self.mode = Mode::ConfirmDelete {
+                        files,
+                        permanent,
+                        prompt,
+                        size: Some(rx),
+                    };
+                    self.redraw_center();
+                    self.redraw_footer();
+                }
+            }
+            Command::Paste { overwrite } => self.paste(overwrite),
+            Command::PasteVerified => {
+                self.unmark_all_items();
+                let current_path = self.center.panel().path().to_path_buf();
+                let Some(clipboard) = self.take_clipboard() else {
+                    return Ok(None);
+                };
+                self.expect_transfer(&clipboard, &current_path);
+                let description = transfer_description(&clipboard, &current_path);
+                self.spawn_file_op(description, move |job| {
+                    info!("paste and verify {} items", clipboard.files.len());
+                    transfer_items(&clipboard.files, &current_path, clipboard.cut, true, job)
+                });
+                self.left.reload();
+                self.center.reload();
+                self.right.reload();
+                self.redraw_panels();
+            }
+            Command::PasteSymlink { relative } => self.paste_links(if relative {
+                LinkKind::Relative
+            } else {
+                LinkKind::Absolute
+            }),
+            Command::PasteHardlink => self.paste_links(LinkKind::Hard),
+            Command::ClipboardHistory => {
+                self.sync_clipboard();
+                let clipboards: Vec<Clipboard> = self
+                    .clipboard
+                    .iter()
+                    .cloned()
+                    .chain(self.clipboard_history.entries())
+                    .collect();
+                if clipboards.is_empty() {
+                    info!("The clipboard is empty");
+                    return Ok(None);
+                }
+                self.pre_console_path = self.center.panel().path().to_path_buf();
+                self.mode = Mode::Console {
+                    console: Box::new(ClipboardConsole::new(clipboards)),
+                };
+                self.redraw_console();
+            }
+            Command::PastePreview => {
+                self.sync_clipboard();
+                let Some(clipboard) = &self.clipboard else {
+                    info!("Nothing to paste");
+                    return Ok(None);
+                };
+                let current_path = self.center.panel().path().to_path_buf();
+                let plan = match TransferPlan::new(&clipboard.files, &current_path, clipboard.cut) {
+                    Ok(plan) => plan,
+                    Err(e) => {
+                        error!("{e}");
+                        return Ok(None);
+                    }
+                };
+                // Scripts just print the plan (without executing it)
+                if self.headless {
+                    for (from, to) in plan.items.iter() {
+                        writeln!(self.stdout, "{} -> {}", from.display(), to.display())?;
+                    }
+                    return Ok(None);
+                }
+                self.pre_console_path = current_path.clone();
+                self.mode = Mode::Console {
+                    console: Box::new(TransferConsole::new(plan, current_path)),
+                };
+                self.redraw_console();
+            }
+            Command::SendToPrevious { cut } => {
+                let destination = match self.previous.canonicalize() {
+                    Ok(path) if path.is_dir() => path,
+                    _ => {
+                        warn!(
+                            "Previous directory {} does not exist",
+                            self.previous.display()
+                        );
+                        return Ok(None);
+                    }
+                };
+                if destination.as_path() == self.center.panel().path() {
+                    warn!("Previous directory is the current directory - nothing to do");
+                    return Ok(None);
+                }
+                let files = self.marked_or_selected();
+                info!(
+                    "{} {} items to {}",
+                    if cut { "move" } else { "copy" },
+                    files.len(),
+                    destination.display()
+                );
+                self.unmark_all_items();
+                let description = format!(
+                    "{} {} items to {}",
+                    if cut { "moving" } else { "copying" },
+                    files.len(),
+                    destination.display()
+                );
+                self.spawn_file_op(description, move |job| {
+                    transfer_items(&files, &destination, cut, false, job)
+                });
+                self.left.reload();
+                self.center.reload();
+                self.right.reload();
+                self.redraw_panels();
+            }
+            Command::ToggleExec => {
+                if let Some(path) = self.center.panel().selected_path().map(Path::to_path_buf) {
+                    match toggle_executable(&path) {
+                        Ok(true) => info!("{} is now executable", path.display()),
+                        Ok(false) => info!("{} is no longer executable", path.display()),
+                        Err(e) => error!("Failed to change permissions: {e}"),
+                    }
+                    self.center.panel_mut().refresh_selected_item();
+                    self.redraw_center();
+                }
+            }
+            Command::Archive => {
+                let items = self.marked_or_selected();
+                if items.is_empty() {
+                    warn!("Nothing is selected");
+                } else {
+                    self.pre_console_path = self.center.panel().path().to_path_buf();
+                    self.mode = Mode::Console {
+                        console: Box::new(ArchiveConsole::new(
+                            items,
+                            self.center.panel().path().to_path_buf(),
+                        )),
+                    };
+                    self.redraw_console();
+                }
+            }
+            Command::Extract => {
+                self.center.freeze();
+                if let Some(archive) = self.center.panel().selected_path() {
+                    self.set_working_dir();
+                    if let Err(e) = extract(archive, self.center.panel().path()) {
+                        warn!("Failed to extract archive: {e}");
+                    }
+                    self.redraw_center();
+                } else {
+                    warn!("Nothing extractable is selected");
+                }
+                self.center.unfreeze();
+            }
+            Command::ExtractTo => {
+                let archives: Vec<PathBuf> = self
+                    .marked_or_selected()
+                    .into_iter()
+                    .filter(|path| is_archive(path) && path.is_file())
+                    .collect();
+                if archives.is_empty() {
+                    warn!("Nothing extractable is selected");
+                } else {
+                    self.pre_console_path = self.center.panel().path().to_path_buf();
+                    self.mode = Mode::Console {
+                        console: Box::new(ExtractConsole::from_panel(
+                            self.center.panel(),
+                            archives,
+                        )),
+                    };
+                    self.redraw_console();
+                }
+            }
+            Command::Quit => {
+                return Ok(Some(CloseCmd::QuitWithPath {
+                    path: self.center.panel().path().to_path_buf(),
+                }));
+            }
+            Command::QuitWithoutPath => {
+                return Ok(Some(CloseCmd::Quit));
+            }
+            Command::None => {}
+        }
+        Ok(None)
+    }
+
+    /// Executes an action that was chosen in the context menu.
+    fn run_action(&mut self, action: Action) -> Result<Option<CloseCmd>> {
+        let Some(selected) = self.center.panel().selected_path().map(Path::to_path_buf) else {
+            return Ok(None);
+        };
+        match action {
+            Action::Command(command) => return self.handle_command(command),
+            Action::CopyPath => match copy_to_clipboard(&selected.to_string_lossy()) {
+                Ok(()) => info!("Copied '{}' to the clipboard", selected.display()),
+                Err(e) => error!("Failed to copy path: {e}"),
+            },
+            Action::OpenDefault => {
+                if let Some(selected) = self.local_copy(selected) {
+                    self.run_external(|opener| opener.open_default(&selected));
+                }
+            }
+            Action::OpenWith(application) => {
+                if let Some(selected) = self.local_copy(selected) {
+                    self.run_external(|opener| opener.open_with(&application, &selected));
+                }
+            }
+            Action::Shell { .. } if self.readonly => {
+                warn!("Shell actions are not available in read-only mode");
+            }
+            Action::Shell { .. } if vfs::is_remote(&selected) => {
+                warn!("Shell actions are not available on remote hosts");
+            }
+            Action::Shell { command, terminal } => {
+                let items = self.marked_or_selected();
+                self.run_external(|opener| opener.run_shell(&command, &items, terminal));
+                self.center.reload();
+                self.right.reload();
+            }
+        }
+        Ok(None)
+    }
+
+    /// Executes the operation, that a console has returned.
+    fn handle_console_op(&mut self, op: ConsoleOp) -> Result<Option<CloseCmd>> {
+        match op {
+            ConsoleOp::Cd(path) => {
+                self.jump(path);
+            }
+            ConsoleOp::Open(path) => {
+                self.jump(path);
+                self.mode = Mode::Normal;
+                self.redraw_panels();
+            }
+            ConsoleOp::Transfer(plan) => {
+                // Cut items may have been pasted by another instance in the meantime
+                if self.take_clipboard().is_some() || !plan.cut {
+                    info!(
+                        "paste {} items ({} renamed)",
+                        plan.items.len(),
+                        plan.renamed()
+                    );
+                    self.unmark_all_items();
+                    if let Some((_, first)) = plan.items.first() {
+                        self.expect_item(first);
+                    }
+                    let description = format!(
+                        "{} {} items",
+                        if plan.cut { "moving" } else { "copying" },
+                        plan.items.len()
+                    );
+                    self.spawn_file_op(description, move |job| execute_transfer(&plan, false, job));
+                }
+                self.mode = Mode::Normal;
+                self.left.reload();
+                self.center.reload();
+                self.right.reload();
+                self.redraw_panels();
+            }
+            ConsoleOp::Paste(clipboard) => {
+                // An earlier clipboard becomes the current one again (with a new
+                // timestamp, so that it is not replaced by the shared clipboard)
+                if self
+                    .clipboard
+                    .as_ref()
+                    .is_none_or(|c| c.files != clipboard.files)
+                {
+                    self.set_clipboard(Clipboard::new(clipboard.files, clipboard.cut));
+                }
+                self.mode = Mode::Normal;
+                self.paste(false);
+            }
+            ConsoleOp::Action(action) => {
+                self.mode = Mode::Normal;
+                self.redraw_panels();
+                if let Some(close_cmd) = self.run_action(action)? {
+                    return Ok(Some(close_cmd));
+                }
+            }
+            ConsoleOp::Archive(plan) => {
+                self.unmark_all_items();
+                if let Ok(output) = archive::output_path(&plan) {
+                    self.expect_item(&output);
+                }
+                let description = format!(
+                    "creating {}.{} from {} items",
+                    plan.name,
+                    plan.format.extension(),
+                    plan.items.len()
+                );
+                self.spawn_file_op(description, move |job| {
+                    let path =
+                        archive::create(&plan, job.token()).context("Failed to create archive")?;
+                    info!("Created {}", path.display());
+                    Ok(())
+                });
+                self.mode = Mode::Normal;
+                self.redraw_panels();
+            }
+            ConsoleOp::Extract { archives, target } => {
+                self.unmark_all_items();
+                self.expect_item(&target);
+                let description = format!(
+                    "extracting {} archives to {}",
+                    archives.len(),
+                    target.display()
+                );
+                self.spawn_file_op(description, move |job| extract_all(&archives, &target, job));
+                self.mode = Mode::Normal;
+                self.redraw_panels();
+            }
+            ConsoleOp::Select(path) => {
+                self.mode = Mode::Normal;
+                self.select_file(path);
+            }
+            ConsoleOp::ShowLine { path, line } => {
+                self.mode = Mode::Normal;
+                self.show_match(path, line);
+            }
+            ConsoleOp::None => (),
+            ConsoleOp::Exit => {
+                self.mode = Mode::Normal;
+                self.redraw_panels();
+            }
+        }
+        Ok(None)
+    }
+
+    /// Runs an external program from the current directory.
+    ///
+    /// The center panel is frozen in the meantime (see [`PanelManager::move_right`]).
+    fn run_external<F: FnOnce(&OpenEngine) -> Result<()>>(&mut self, run: F) {
+        if self.headless {
+            info!("Not running external programs in headless mode");
+            return;
+        }
+        self.center.freeze();
+        self.set_working_dir();
+        if let Err(e) = run(&self.opener) {
+            error!("{e}");
+        }
+        // The program may have changed the title, and left its output on the screen
+        self.title.clear();
+        self.center.unfreeze();
+        if let Err(e) = self.relayout() {
+            error!("{e}");
+        }
+    }
+
+    /// Jumps to a directory of the header path and selects the item that we came from.
+    fn jump_to_crumb(&mut self, dir: PathBuf, child: Option<PathBuf>) {
+        self.jump(dir);
+        if let Some(child) = child {
+            self.center.panel_mut().select_path(&child, None);
+            self.right
+                .new_panel_delayed(self.center.panel().selected_path());
+        }
+        self.redraw_panels();
+    }
+
+    /// Jumps to the directory of the header path that was clicked on.
+    fn click_header(&mut self, column: u16) {
+        if self.layout.zen() || status_line().header.is_some() {
+            return;
+        }
+        let crumbs = breadcrumbs(&self.absolute_selected_path());
+        let mut start = unicode_display_width::width(&header_prompt()) as u16 + 1;
+        for (n, (segment, path)) in crumbs.iter().enumerate() {
+            let end = start + unicode_display_width::width(segment) as u16;
+            if (start..end).contains(&column) {
+                if vfs::is_dir(path) {
+                    let child = crumbs.get(n + 1).map(|(_, child)| child.clone());
+                    self.jump_to_crumb(path.clone(), child);
+                }
+                return;
+            }
+            start = end;
+        }
+    }
+
+    /// Handles mouse events (only received if the mouse is captured).
+    ///
+    /// A right-click on an item of the center panel selects it and opens the context menu,
+    /// a click on a directory of the path in the header jumps there
+    /// and a click on the summary of the running jobs opens the jobs overlay.
+    fn handle_mouse(&mut self, event: MouseEvent) -> Result<Option<CloseCmd>> {
+        if matches!(self.mode, Mode::Normal)
+            && event.kind == MouseEventKind::Down(MouseButton::Left)
+            && event.row == 0
+        {
+            if self.jobs_summary.is_some()
+                && !self.layout.zen()
+                && event.column >= self.jobs_summary_start()
+            {
+                return self.handle_command(Command::Jobs);
+            }
+            self.click_header(event.column);
+            return Ok(None);
+        }
+        if !matches!(self.mode, Mode::Normal)
+            || event.kind != MouseEventKind::Down(MouseButton::Right)
+            || !self.layout.center_x_range.contains(&event.column)
+            || !self.layout.y_range.contains(&event.row)
+        {
+            return Ok(None);
+        }
+        let height = if self.show_log {
+            self.layout
+                .height()
+                .saturating_sub(self.logger.capacity() as u16)
+        } else {
+            self.layout.height()
+        };
+        let row = event.row - self.layout.y_range.start;
+        if !self.center.panel_mut().select_row(row, height) {
+            return Ok(None);
+        }
+        self.rev_history.clear();
+        self.right
+            .new_panel_delayed(self.center.panel().selected_path());
+        self.redraw_panels();
+        self.handle_command(Command::ContextMenu)
+    }
+
     /// Handles the terminal events.
     ///
     /// Returns Ok(true) if the application needs to shut down.
     fn handle_event(&mut self, event: Event) -> Result<Option<CloseCmd>> {
         if let Event::Key(key_event) = event {
+            self.last_key = Instant::now();
+            if self.hint.take().is_some() {
+                self.redraw_footer();
+            }
             // If we hit escape - go back to normal mode.
             if let KeyCode::Esc = key_event.code {
                 if let Mode::Console { .. } = self.mode {
@@ -904,226 +3446,85 @@ impl PanelManager {
                 }
                 self.mode = Mode::Normal;
                 self.parser.clear();
+                self.histories.reset();
                 self.center.panel_mut().clear_search();
                 self.center.panel_mut().clear_new_element();
+                self.center.panel_mut().end_visual();
                 self.redraw_panels();
                 self.redraw_footer();
                 self.unmark_all_items();
             }
             match &mut self.mode {
                 Mode::Normal => {
-                    match self.parser.add_event(key_event) {
-                        Command::Move(direction) => {
-                            self.move_cursor(direction);
-                        }
-                        Command::ViewTrash => {
-                            if let Some(trash_path) = &self.trash_dir {
-                                self.jump(trash_path.path().to_path_buf());
-                            } else {
-                                warn!("Trash feature is not activated - therefore there is no trash-directory to jump to.")
-                            }
-                        }
-                        Command::ToggleHidden => self.toggle_hidden(),
-                        Command::ToggleLog => self.toggle_log(),
-                        Command::Cd { zoxide } => {
-                            self.pre_console_path = self.center.panel().path().to_path_buf();
-                            self.mode = if zoxide {
-                                // TODO WIP: Test out zoxide console
-                                Mode::Console {
-                                    console: Box::new(Zoxide::from_panel(self.center.panel())),
-                                }
-                            } else {
-                                Mode::Console {
-                                    console: Box::new(DirConsole::from_panel(self.center.panel())),
-                                }
-                            };
-                            self.redraw_console();
-                        }
-                        Command::Search => {
-                            self.mode = Mode::Search {
-                                input: Input::empty(),
-                            };
-                            self.redraw_footer();
-                        }
-                        Command::Rename => {
-                            let selected = self
-                                .center
-                                .panel()
-                                .selected_path()
-                                .and_then(|p| p.file_name())
-                                .and_then(|f| f.to_owned().into_string().ok())
-                                .unwrap_or_default();
-                            self.mode = Mode::Rename {
-                                input: Input::from_str(selected),
-                            };
-                            self.redraw_footer();
-                        }
-                        Command::Next => {
-                            self.center.panel_mut().select_next_marked();
-                            self.right
-                                .new_panel_delayed(self.center.panel().selected_path());
-                            self.redraw_center();
-                            self.redraw_right();
-                        }
-                        Command::Previous => {
-                            self.center.panel_mut().select_prev_marked();
-                            self.right
-                                .new_panel_delayed(self.center.panel().selected_path());
-                            self.redraw_center();
-                            self.redraw_right();
-                        }
-                        Command::Mkdir => {
-                            self.mode = Mode::CreateItem {
-                                input: Input::empty(),
-                                is_dir: true,
-                            };
-                            self.redraw_footer();
-                        }
-                        Command::Touch => {
-                            self.mode = Mode::CreateItem {
-                                input: Input::empty(),
-                                is_dir: false,
-                            };
-                            self.redraw_footer();
-                        }
-                        Command::Mark => {
-                            self.center.panel_mut().mark_selected_item();
-                            self.move_cursor(Move::Down);
-                        }
-                        Command::Cut => {
-                            let files = self.marked_or_selected();
-                            info!("cut {} items", files.len());
-                            self.clipboard = Some(Clipboard { files, cut: true });
-                        }
-                        Command::Copy => {
-                            let files = self.marked_or_selected();
-                            info!("copying {} items", files.len());
-                            self.clipboard = Some(Clipboard { files, cut: false });
-                        }
-                        Command::Delete => {
-                            let files = self.marked_or_selected();
-                            info!("Deleted {} items", files.len());
-                            self.unmark_all_items();
-                            // self.stack.push(Operation::MoveItems { from: files.clone(), to: trash_dir.path().to_path_buf() });
-                            for file in files {
-                                self.delete_file(&file);
-                            }
-                            self.left.reload();
-                            self.center.reload();
-                            self.right.reload();
-                        }
-                        Command::Paste { overwrite } => {
-                            self.unmark_all_items();
-                            let current_path = self.center.panel().path().to_path_buf();
-                            let clipboard = self.clipboard.take();
-                            tokio::task::spawn_blocking(move || {
-                                if let Some(clipboard) = clipboard {
-                                    info!(
-                                        "paste {} items, overwrite = {}",
-                                        clipboard.files.len(),
-                                        overwrite
-                                    );
-                                    for file in clipboard.files.iter() {
-                                        if clipboard.cut {
-                                            if let Err(e) = move_item(file, &current_path) {
-                                                error!("Failed to move {}: {e}", file.display());
-                                            }
-                                        } else if let Err(e) = copy_item(file, &current_path) {
-                                            error!("Failed to copy {}: {e}", file.display());
-                                        }
-                                    }
-                                }
-                            });
-                            self.left.reload();
-                            self.center.reload();
-                            self.right.reload();
-                            self.redraw_panels();
-                        }
-                        Command::Zip => {
-                            let items = self.marked_or_selected();
-                            if let Err(e) = std::env::set_current_dir(self.center.panel().path()) {
-                                error!("Failed to set working-directory for process: {e}");
-                            }
-                            self.center.freeze();
-                            if let Err(e) = self.opener.zip(items) {
-                                warn!("Failed to create zip-archive: {e}");
-                            }
-                            self.center.unfreeze();
-                            self.redraw_center();
-                        }
-                        Command::Tar => {
-                            let items = self.marked_or_selected();
-                            if let Err(e) = std::env::set_current_dir(self.center.panel().path()) {
-                                error!("Failed to set working-directory for process: {e}");
-                            }
-                            self.center.freeze();
-                            if let Err(e) = self.opener.tar(items) {
-                                warn!("Failed to create tar-archive: {e}");
-                            }
-                            self.center.unfreeze();
-                            self.redraw_center();
-                        }
-                        Command::Extract => {
-                            self.center.freeze();
-                            if let Some(archive) = self.center.panel().selected_path() {
-                                if let Err(e) =
-                                    std::env::set_current_dir(self.center.panel().path())
-                                {
-                                    error!("Failed to set working-directory for process: {e}");
-                                }
-                                if let Err(e) = self.opener.extract(archive.to_owned()) {
-                                    warn!("Failed to extract archive: {e}");
-                                }
-                                self.redraw_center();
-                            } else {
-                                warn!("Nothing extractable is selected");
-                            }
-                            self.center.unfreeze();
-                        }
-                        Command::Quit => {
-                            return Ok(Some(CloseCmd::QuitWithPath {
-                                path: self.center.panel().path().to_path_buf(),
-                            }));
-                        }
-                        Command::QuitWithoutPath => {
-                            return Ok(Some(CloseCmd::Quit));
-                        }
-                        Command::None => {}
+                    self.notice = None;
+                    let pending = !self.parser.pending_keys().is_empty();
+                    let command = self.parser.add_event(key_event);
+                    // Remove the continuations of the finished (or aborted) sequence,
+                    // the popup is also redrawn for every new key
+                    if pending && (self.key_popup || self.parser.pending_keys().is_empty()) {
+                        self.redraw_panels();
+                    }
+                    if let Some(close_cmd) = self.handle_command(command)? {
+                        return Ok(Some(close_cmd));
                     }
                     // Always redraw footer
                     self.redraw_footer();
                 }
                 Mode::Console { console } => {
-                    match console.handle_key(key_event) {
-                        ConsoleOp::Cd(path) => {
-                            self.jump(path);
-                        }
-                        ConsoleOp::None => (),
-                        ConsoleOp::Exit => {
-                            self.mode = Mode::Normal;
-                            self.redraw_panels();
-                        }
+                    let op = console.handle_key(key_event);
+                    if let Some(close_cmd) = self.handle_console_op(op)? {
+                        return Ok(Some(close_cmd));
                     }
                     self.redraw_console();
                 }
-                Mode::CreateItem { input, is_dir } => {
+                Mode::CreateItem {
+                    input,
+                    is_dir,
+                    content,
+                    exists,
+                } => {
+                    let path = self.center.panel().path().join(input.get().trim());
+                    // Warn first, if the item exists - only a file with pasted text can be replaced
+                    let warn = path.symlink_metadata().is_ok()
+                        && (content.is_none() || exists.as_ref() != Some(&path));
                     match key_event.code {
-                        KeyCode::Enter => {
-                            let current_path = self.center.panel().path();
-                            let create_fn = if *is_dir {
-                                |item| fs_extra::dir::create(item, false)
-                            } else {
-                                |item| {
-                                    let _ = OpenOptions::new()
-                                        .read(true)
-                                        .append(true)
-                                        .create(true)
-                                        .open(item)?;
-                                    Ok(())
+                        KeyCode::Enter if warn => {
+                            *exists = Some(path);
+                            self.redraw_footer();
+                        }
+                        KeyCode::Enter if content.is_some() => {
+                            let text = content.take().unwrap_or_default();
+                            // Existing files are only overwritten after the warning
+                            let overwrite = exists.is_some();
+                            let result = path
+                                .parent()
+                                .map_or(Ok(()), std::fs::create_dir_all)
+                                .and_then(|()| {
+                                    OpenOptions::new()
+                                        .write(true)
+                                        .truncate(overwrite)
+                                        .create(overwrite)
+                                        .create_new(!overwrite)
+                                        .open(&path)
+                                })
+                                .and_then(|mut file| file.write_all(text.as_bytes()));
+                            match result {
+                                Ok(()) => {
+                                    info!("Pasted {} bytes into {}", text.len(), path.display());
+                                    self.expect_item(&path);
                                 }
-                            };
-                            if let Err(e) = create_fn(current_path.join(input.get().trim())) {
-                                error!("{e}");
+                                Err(e) => error!("Cannot create {}: {e}", path.display()),
+                            }
+                            self.mode = Mode::Normal;
+                            self.center.panel_mut().clear_new_element();
+                            self.redraw_panels();
+                        }
+                        KeyCode::Enter => {
+                            self.histories.create.push(input.get());
+                            let path = self.center.panel().path().join(input.get().trim());
+                            match create_item(&path, *is_dir) {
+                                Ok(()) => self.expect_item(&path),
+                                Err(e) => error!("{e}"),
                             }
                             // self.stack.push(Operation::Mkdir { path: new_dir.clone() });
                             self.mode = Mode::Normal;
@@ -1134,41 +3535,200 @@ impl PanelManager {
                             /* autocomplete here ? */
                             self.redraw_footer();
                         }
-                        key_code => {
-                            input.update(key_code, key_event.modifiers);
+                        _ => {
+                            edit_input(input, &mut self.histories.create, key_event);
                             self.center
                                 .panel_mut()
                                 .inject_new_element(input.get().to_string(), *is_dir);
+                            if exists.take().is_some() {
+                                self.redraw_footer();
+                            }
                             self.redraw_center();
                         }
                     }
                 }
                 Mode::Search { input } => {
                     if let KeyCode::Enter = key_event.code {
+                        self.histories.search.push(input.get());
                         self.center.panel_mut().finish_search(input.get());
-                        self.center.panel_mut().select_next_marked();
-                        self.right
-                            .new_panel_delayed(self.center.panel().selected_path());
                         self.mode = Mode::Normal;
-                        self.redraw_center();
-                        self.redraw_right();
+                        self.select_marked(!self.search_backward, false);
                     } else {
-                        input.update(key_event.code, key_event.modifiers);
+                        edit_input(input, &mut self.histories.search, key_event);
                         self.center
                             .panel_mut()
                             .update_search(input.get().to_string());
                         self.redraw_center();
                     }
                 }
-                Mode::Rename { input } => {
+                Mode::MarkGlob { input } => {
+                    if let KeyCode::Enter = key_event.code {
+                        self.histories.mark_glob.push(input.get());
+                        let n_marked = self.center.panel_mut().mark_glob(input.get());
+                        info!("marked {n_marked} items matching '{}'", input.get());
+                        self.mode = Mode::Normal;
+                        self.redraw_center();
+                    } else {
+                        edit_input(input, &mut self.histories.mark_glob, key_event);
+                    }
+                    self.redraw_footer();
+                }
+                Mode::MarkByAge { input } => {
+                    if let KeyCode::Enter = key_event.code {
+                        self.histories.mark_by_age.push(input.get());
+                        // Like `find -mtime +7`, a leading '+' selects the older items
+                        let (older, age) = match input.get().trim().strip_prefix('+') {
+                            Some(age) => (true, age),
+                            None => (false, input.get()),
+                        };
+                        match parse_age(age) {
+                            Some(duration) => {
+                                let n_marked = self.center.panel_mut().mark_by_age(duration, older);
+                                let relation = if older { "before" } else { "within" };
+                                info!("marked {n_marked} items modified {relation} the last {age}");
+                            }
+                            None => error!("'{}' is not an age like '7d' or '2h'", input.get()),
+                        }
+                        self.mode = Mode::Normal;
+                        self.redraw_center();
+                    } else {
+                        edit_input(input, &mut self.histories.mark_by_age, key_event);
+                    }
+                    self.redraw_footer();
+                }
+                Mode::MarkByType { input } => {
+                    if let KeyCode::Enter = key_event.code {
+                        self.histories.mark_by_type.push(input.get());
+                        match MimeCategory::parse(input.get()) {
+                            Some(category) => {
+                                let n_marked = self.center.panel_mut().mark_by_category(category);
+                                info!("marked {n_marked} {category}");
+                            }
+                            None => {
+                                let categories: Vec<String> =
+                                    MimeCategory::ALL.iter().map(ToString::to_string).collect();
+                                error!(
+                                    "'{}' is not a type of files ({})",
+                                    input.get(),
+                                    categories.join(", ")
+                                );
+                            }
+                        }
+                        self.mode = Mode::Normal;
+                        self.redraw_center();
+                    } else {
+                        edit_input(input, &mut self.histories.mark_by_type, key_event);
+                    }
+                    self.redraw_footer();
+                }
+                Mode::ConfirmDelete { files, .. } => {
+                    let files = std::mem::take(files);
+                    self.mode = Mode::Normal;
+                    if let KeyCode::Char('y' | 'Y') = key_event.code {
+                        self.delete_items(files);
+                    } else {
+                        self.notice = Some("delete aborted");
+                    }
+                    self.redraw_panels();
+                    self.redraw_footer();
+                }
+                Mode::ConfirmProtected {
+                    input, operation, ..
+                } => {
+                    if let KeyCode::Enter = key_event.code {
+                        let confirmed = input.get() == CONFIRMATION;
+                        let operation = std::mem::replace(operation, Guarded::Delete(Vec::new()));
+                        self.mode = Mode::Normal;
+                        match (confirmed, operation) {
+                            (true, Guarded::Delete(files)) => self.delete_items(files),
+                            (true, Guarded::Cut(files)) => {
+                                info!("cut {} items", files.len());
+                                self.set_clipboard(Clipboard::new(files, true));
+                            }
+                            (false, _) => self.notice = Some("operation aborted"),
+                        }
+                        self.redraw_panels();
+                    } else {
+                        input.update(key_event.code, key_event.modifiers);
+                    }
+                    self.redraw_footer();
+                }
+                Mode::ConfirmEmptyTrash { .. } => {
+                    self.mode = Mode::Normal;
+                    match key_event.code {
+                        KeyCode::Char('y' | 'Y') => match self.trash.empty() {
+                            Ok(n) => info!("Removed {n} items from the trash"),
+                            Err(e) => error!("Cannot empty the trash: {e}"),
+                        },
+                        _ => self.notice = Some("emptying the trash aborted"),
+                    }
+                    self.center.reload();
+                    self.redraw_panels();
+                    self.redraw_footer();
+                }
+                Mode::Breadcrumbs { crumbs, idx } => {
+                    match key_event.code {
+                        KeyCode::Left | KeyCode::Char('h') => *idx = idx.saturating_sub(1),
+                        KeyCode::Right | KeyCode::Char('l')
+                            if crumbs
+                                .get(*idx + 1)
+                                .is_some_and(|(_, path)| vfs::is_dir(path)) =>
+                        {
+                            *idx += 1;
+                        }
+                        KeyCode::Home => *idx = 0,
+                        KeyCode::Enter => {
+                            let dir = crumbs[*idx].1.clone();
+                            let child = crumbs.get(*idx + 1).map(|(_, child)| child.clone());
+                            self.mode = Mode::Normal;
+                            self.jump_to_crumb(dir, child);
+                        }
+                        _ => (),
+                    }
+                    self.redraw_panels();
+                }
+                Mode::Tag { input } => {
+                    if let KeyCode::Enter = key_event.code {
+                        self.histories.tag.push(input.get());
+                        let tag = input.get().trim().to_string();
+                        self.mode = Mode::Normal;
+                        let items = self.marked_or_selected();
+                        match tags::add(&items, &tag) {
+                            Ok(()) => info!("tagged {} items with '{tag}'", items.len()),
+                            Err(e) => error!("{e}"),
+                        }
+                        self.unmark_all_items();
+                        self.redraw_panels();
+                    } else {
+                        edit_input(input, &mut self.histories.tag, key_event);
+                    }
+                    self.redraw_footer();
+                }
+                Mode::Rename { input, exists } => {
+                    let target = self.center.panel().selected_path().and_then(|from| {
+                        let to = from.parent()?.join(input.get());
+                        replaces_other(from, &to).then_some(to)
+                    });
+                    if let (KeyCode::Enter, Some(to)) = (key_event.code, &target) {
+                        if exists.as_ref() != Some(to) {
+                            *exists = target;
+                            self.redraw_footer();
+                            return Ok(None);
+                        }
+                    }
                     if let KeyCode::Enter = key_event.code {
+                        self.histories.rename.push(input.get());
                         if let Some(from) = self.center.panel().selected_path() {
                             let to = from
                                 .parent()
                                 .map(|p| p.join(input.get()))
                                 .unwrap_or_default();
-                            if let Err(e) = std::fs::rename(from, to) {
-                                error!("{e}");
+                            match std::fs::rename(from, &to) {
+                                Ok(()) => {
+                                    tags::rename(from, &to);
+                                    self.expect_item(&to);
+                                }
+                                Err(e) => error!("{e}"),
                             }
                         }
                         self.mode = Mode::Normal;
@@ -1176,16 +3736,234 @@ impl PanelManager {
                         self.right.reload();
                         self.redraw_panels();
                     } else {
-                        input.update(key_event.code, key_event.modifiers);
+                        edit_input(input, &mut self.histories.rename, key_event);
+                        if exists.take().is_some() {
+                            self.redraw_footer();
+                        }
                         self.redraw_center();
                     }
                 }
             }
         }
+        if let Event::Paste(text) = &event {
+            self.paste_input(text);
+        }
+        if let Event::Mouse(mouse_event) = event {
+            if let Some(close_cmd) = self.handle_mouse(mouse_event)? {
+                return Ok(Some(close_cmd));
+            }
+        }
         if let Event::Resize(sx, sy) = event {
-            self.layout = MillerColumns::from_size((sx, sy));
+            self.layout.resize((sx, sy));
             self.redraw_everything();
         }
         Ok(None)
     }
 }
+
+/// Returns true if renaming 'from' to 'to' replaces another item.
+///
+/// On case-insensitive filesystems, 'to' may just be another name of 'from'.
+fn replaces_other(from: &Path, to: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    match (from.symlink_metadata(), to.symlink_metadata()) {
+        (Ok(from), Ok(to)) => (from.dev(), from.ino()) != (to.dev(), to.ino()),
+        (_, to) => to.is_ok(),
+    }
+}
+
+/// Prints the warning behind an input field, whose name already exists.
+fn print_exists_warning(stdout: &mut Stdout, overwrite: bool) -> Result<()> {
+    let warning = if overwrite {
+        "  already exists - press enter again to overwrite it"
+    } else {
+        "  already exists"
+    };
+    stdout.queue(PrintStyledContent(warning.red().bold()))?;
+    Ok(())
+}
+
+/// The "user@host" in front of the path in the header
+fn header_prompt() -> String {
+    format!(
+        "{}@{}",
+        whoami::username(),
+        whoami::fallible::hostname().unwrap_or_else(|e| e.to_string())
+    )
+}
+
+/// Question that is shown before the files are deleted, e.g. "Delete 12 items (3.20 G)?".
+///
+/// The size is shown as "…" until it is calculated. Only the first entries of huge directories
+/// are counted, so that it shows up quickly.
+fn delete_prompt(files: &[PathBuf], permanent: bool, size: Option<(u64, bool)>) -> String {
+    let size = match size {
+        Some((size, true)) => file_size_str(size),
+        Some((size, false)) => format!("more than {}", file_size_str(size)),
+        None => "…".to_string(),
+    };
+    let items = match files {
+        [file] => format!(
+            "'{}'",
+            file.file_name().unwrap_or_default().to_string_lossy()
+        ),
+        _ => format!("{} items", files.len()),
+    };
+    format!(
+        "{} {items} ({size})?",
+        if permanent {
+            "Permanently delete"
+        } else {
+            "Delete"
+        },
+    )
+}
+
+/// Describes a paste for the list of background jobs.
+fn transfer_description(clipboard: &Clipboard, destination: &Path) -> String {
+    format!(
+        "{} {} items to {}",
+        if clipboard.cut { "moving" } else { "copying" },
+        clipboard.files.len(),
+        destination.display()
+    )
+}
+
+/// Extracts the archives one after another and logs the result of each archive.
+fn extract_all(archives: &[PathBuf], target: &Path, job: &Job) -> anyhow::Result<()> {
+    let mut extracted = 0;
+    for (idx, archive) in archives.iter().enumerate() {
+        job.set_progress(idx as u64, archives.len() as u64);
+        if job.is_cancelled() {
+            warn!("Extraction to {} was cancelled", target.display());
+            return Ok(());
+        }
+        match extract(archive, target) {
+            Ok(()) => {
+                info!("Extracted {}", archive.display());
+                extracted += 1;
+            }
+            Err(e) => warn!("Failed to extract {}: {e}", archive.display()),
+        }
+    }
+    info!(
+        "Extracted {extracted} of {} archives to {}",
+        archives.len(),
+        target.display()
+    );
+    if extracted < archives.len() {
+        bail!(
+            "{} of {} archives could not be extracted",
+            archives.len() - extracted,
+            archives.len()
+        );
+    }
+    Ok(())
+}
+
+/// Copies or moves all files into the destination directory.
+///
+/// This is a blocking operation, so it should be run inside of `spawn_blocking`.
+fn transfer_items(
+    files: &[PathBuf],
+    destination: &Path,
+    cut: bool,
+    verify: bool,
+    job: &Job,
+) -> anyhow::Result<()> {
+    let plan = TransferPlan::new(files, destination, cut)?;
+    execute_transfer(&plan, verify, job)
+}
+
+/// Copies or moves all items of the plan.
+///
+/// This is a blocking operation, so it should be run inside of `spawn_blocking`.
+/// When the job is cancelled, the remaining items are skipped (the current one is still finished).
+/// The progress of the job is the share of the finished items.
+fn execute_transfer(plan: &TransferPlan, verify: bool, job: &Job) -> anyhow::Result<()> {
+    let cut = plan.cut;
+    let mut n_verified = 0;
+    let mut n_mismatches = 0;
+    let mut n_unverified = 0;
+    let mut n_failed = 0;
+    for (idx, (file, planned)) in plan.items.iter().enumerate() {
+        job.set_progress(idx as u64, plan.items.len() as u64);
+        if job.is_cancelled() {
+            warn!(
+                "Transfer cancelled, {} of {} items were skipped",
+                plan.items.len() - idx,
+                plan.items.len()
+            );
+            return Ok(());
+        }
+        if file == planned {
+            debug!("{} is already in place", file.display());
+            continue;
+        }
+        // Someone may have created the destination, since the plan was made
+        let copy = match planned.parent() {
+            Some(dir) if planned.exists() => match get_destination(file, dir) {
+                Ok(copy) => {
+                    warn!(
+                        "{} already exists, using {}",
+                        planned.display(),
+                        copy.display()
+                    );
+                    copy
+                }
+                Err(e) => {
+                    error!("{e}");
+                    n_failed += 1;
+                    continue;
+                }
+            },
+            _ => planned.clone(),
+        };
+        if let Err(e) = transfer_item(file, &copy, cut) {
+            let op = if cut { "move" } else { "copy" };
+            error!("Failed to {op} {}: {e}", file.display());
+            n_failed += 1;
+            continue;
+        }
+        // Moved items are renamed, so there is nothing to verify
+        if !verify || cut {
+            continue;
+        }
+        match verify_copy(file, &copy) {
+            Ok(result) => {
+                info!("verified {} files in {}", result.verified, copy.display());
+                n_verified += result.verified;
+                n_mismatches += result.mismatches.len();
+                n_unverified += result.unverified.len();
+                for path in result.mismatches {
+                    error!("Checksum mismatch: {}", path.display());
+                }
+                for path in result.unverified {
+                    warn!("Cannot read {}, its copy is not verified", path.display());
+                }
+            }
+            Err(e) => {
+                error!("Failed to verify {}: {e}", copy.display());
+                n_mismatches += 1;
+            }
+        }
+    }
+    if verify && cut {
+        warn!("Nothing was verified, because moved items are only renamed");
+    } else if verify {
+        if n_mismatches > 0 {
+            warn!("Verified {n_verified} files - {n_mismatches} mismatches");
+        } else if n_unverified > 0 {
+            warn!("Verified {n_verified} files - {n_unverified} files could not be read");
+        } else {
+            info!("Verified {n_verified} files - all checksums match");
+        }
+    }
+    if n_failed > 0 {
+        bail!("{n_failed} of {} items failed", plan.items.len());
+    }
+    if n_mismatches > 0 {
+        bail!("{n_mismatches} checksum mismatches");
+    }
+    Ok(())
+}
@@ -0,0 +1,127 @@
+//! Metadata of images and audio files, that is read without any external tools.
+//!
+//! Images show their resolution and the most interesting EXIF fields,
+//! audio files show their ID3 tags.
+use std::{fs::File, io::BufReader, path::Path};
+
+use exif::{DateTime, In, Tag, Value};
+use id3::TagLike;
+
+/// Formats a line of the metadata, so that all values start in the same column.
+fn line(label: &str, value: impl std::fmt::Display) -> String {
+    format!("{label:<10} {value}")
+}
+
+/// Returns the resolution of the image and the date, camera and location from its EXIF data.
+///
+/// The lines are shown above the thumbnail of the image.
+pub fn image_info(path: &Path, dimensions: Option<(u32, u32)>) -> Vec<String> {
+    let mut lines = Vec::new();
+    if let Some((width, height)) = dimensions {
+        lines.push(line("Resolution", format!("{width} x {height}")));
+    }
+    let Ok(file) = File::open(path) else {
+        return lines;
+    };
+    let exif = match exif::Reader::new().read_from_container(&mut BufReader::new(file)) {
+        Ok(exif) => exif,
+        Err(e) => {
+            log::trace!("no exif data in {}: {e}", path.display());
+            return lines;
+        }
+    };
+    let ascii = |tag: Tag| match &exif.get_field(tag, In::PRIMARY)?.value {
+        Value::Ascii(values) => values
+            .first()
+            .map(|v| String::from_utf8_lossy(v).trim().to_string())
+            .filter(|v| !v.is_empty()),
+        _ => None,
+    };
+    let date = ascii(Tag::DateTimeOriginal).or_else(|| ascii(Tag::DateTime));
+    if let Some(date) = date {
+        let date = DateTime::from_ascii(date.as_bytes())
+            .map(|d| d.to_string())
+            .unwrap_or(date);
+        lines.push(line("Date", date));
+    }
+    let camera = match (ascii(Tag::Make), ascii(Tag::Model)) {
+        // The model often repeats the make (like "Canon" and "Canon EOS 80D")
+        (Some(make), Some(model)) if model.starts_with(&make) => Some(model),
+        (Some(make), Some(model)) => Some(format!("{make} {model}")),
+        (make, model) => make.or(model),
+    };
+    if let Some(camera) = camera {
+        lines.push(line("Camera", camera));
+    }
+    if exif.get_field(Tag::GPSLatitude, In::PRIMARY).is_some() {
+        lines.push(line("GPS", "contains location"));
+    }
+    lines
+}
+
+/// Returns the ID3 tags of an audio file, or `None` if the file has no tags.
+pub fn audio_info(path: &Path) -> Option<Vec<String>> {
+    let tag = match id3::Tag::read_from_path(path) {
+        Ok(tag) => tag,
+        Err(e) => {
+            log::trace!("no id3 tag in {}: {e}", path.display());
+            return None;
+        }
+    };
+    let mut lines = Vec::new();
+    let fields = [
+        ("Title", tag.title().map(String::from)),
+        ("Artist", tag.artist().map(String::from)),
+        ("Album", tag.album().map(String::from)),
+        ("Year", tag.year().map(|y| y.to_string())),
+        ("Track", tag.track().map(|t| t.to_string())),
+        ("Genre", tag.genre_parsed().map(String::from)),
+    ];
+    for (label, value) in fields {
+        if let Some(value) = value.filter(|v| !v.is_empty()) {
+            lines.push(line(label, value));
+        }
+    }
+    if let Some(duration) = tag.duration() {
+        let seconds = duration / 1000;
+        lines.push(line(
+            "Duration",
+            format!("{}:{:02}", seconds / 60, seconds % 60),
+        ));
+    }
+    (!lines.is_empty()).then_some(lines)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_media_metadata() {
+        let dir = tempfile::tempdir().unwrap();
+
+        // Little endian TIFF with a single IFD, that contains the camera model
+        let tiff = dir.path().join("image.tiff");
+        let mut data = b"II*\0\x08\0\0\0".to_vec();
+        data.extend([1, 0]);
+        data.extend([0x10, 0x01, 2, 0, 4, 0, 0, 0]);
+        data.extend(b"Cam\0");
+        data.extend([0, 0, 0, 0]);
+        std::fs::write(&tiff, data).unwrap();
+        let lines = image_info(&tiff, Some((640, 480)));
+        assert_eq!(lines, vec!["Resolution 640 x 480", "Camera     Cam"]);
+
+        let mp3 = dir.path().join("song.mp3");
+        std::fs::write(&mp3, "").unwrap();
+        assert!(audio_info(&mp3).is_none());
+        let mut tag = id3::Tag::new();
+        tag.set_title("Song");
+        tag.set_artist("Band");
+        tag.set_year(1999);
+        tag.write_to_path(&mp3, id3::Version::Id3v24).unwrap();
+        assert_eq!(
+            audio_info(&mp3).unwrap(),
+            vec!["Title      Song", "Artist     Band", "Year       1999"]
+        );
+    }
+}
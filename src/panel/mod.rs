@@ -5,7 +5,10 @@ use crossterm::{
     QueueableCommand, Result,
 };
 use log::{debug, error, info, trace, warn};
-use notify::{RecommendedWatcher, Watcher};
+use notify::{
+    event::{MetadataKind, ModifyKind},
+    EventKind, PollWatcher, RecommendedWatcher, Watcher,
+};
 use parking_lot::Mutex;
 use std::{
     cmp::Ordering,
@@ -14,21 +17,36 @@ use std::{
     ops::Range,
     os::unix::prelude::PermissionsExt,
     path::{Path, PathBuf},
-    sync::Arc,
-    time::SystemTime,
+    sync::{
+        atomic::{self, AtomicU64},
+        Arc,
+    },
+    time::{Duration, SystemTime},
 };
 use tokio::sync::mpsc;
 
-use crate::{content::PanelCache, engine::commands::Move};
+use crate::{
+    config::{LayoutPreset, WatcherConfig},
+    content::{is_internal, PanelCache},
+    engine::commands::Move,
+    util::filesystem_type,
+    vfs,
+};
 
 mod console;
 mod directory;
 mod input;
 pub mod manager;
+mod metadata;
+mod normalize;
 mod preview;
+mod template;
 
-pub use directory::{DirElem, DirPanel};
-pub use preview::{FilePreview, PreviewPanel};
+pub use directory::{set_sort_mode, sort_mode, DirElem, DirPanel, MarkedJump};
+pub use preview::{
+    previews_enabled, set_preview_config, tree_preview, FilePreview, MarkedSummary, PreviewPanel,
+};
+pub use template::{set_status_line, status_line};
 
 pub type MillerPanels = (
     ManagedPanel<DirPanel>,
@@ -38,19 +56,28 @@ pub type MillerPanels = (
 
 pub fn init_miller_panels(
     starting_path: PathBuf,
+    selection: Option<PathBuf>,
     directory_cache: PanelCache<DirPanel>,
     preview_cache: PanelCache<PreviewPanel>,
     directory_tx: mpsc::UnboundedSender<PanelUpdate>,
     preview_tx: mpsc::UnboundedSender<PanelUpdate>,
+    watcher_config: &WatcherConfig,
 ) -> MillerPanels {
     // Create three panels
-    let mut left = ManagedPanel::new(directory_cache.clone(), directory_tx.clone(), false);
-    let mut center = ManagedPanel::new(directory_cache, directory_tx, false);
-    let mut right = ManagedPanel::new(preview_cache, preview_tx, true);
+    let mut left = ManagedPanel::new(
+        directory_cache.clone(),
+        directory_tx.clone(),
+        watcher_config,
+    );
+    let mut center = ManagedPanel::new(directory_cache, directory_tx, watcher_config);
+    let mut right = ManagedPanel::new(preview_cache, preview_tx, watcher_config);
 
     // Set the directories accordingly
     left.new_panel_instant(Some(starting_path.join("..")));
     center.new_panel_instant(Some(starting_path));
+    if let Some(selection) = selection {
+        center.panel_mut().select_path(&selection, None);
+    }
     right.new_panel_instant(center.panel().selected_path());
 
     // select the correct directory for the left panel
@@ -75,6 +102,15 @@ pub trait PanelContent: Draw + Clone + Send {
 
     /// Updates the content of the panel
     fn update_content(&mut self, content: Self);
+
+    /// Approximate number of bytes that the panel occupies (see [`PanelCache`]).
+    fn mem_size(&self) -> usize;
+
+    /// Returns false, if some content of the panel is generated later
+    /// (so that a cached panel is updated, even if the path has not changed).
+    fn is_complete(&self) -> bool {
+        true
+    }
 }
 
 /// Basic trait for our panels.
@@ -86,6 +122,10 @@ pub trait BasePanel: PanelContent {
     /// some data
     fn loading(path: PathBuf) -> Self;
 
+    /// Returns true for panels that were created by [`BasePanel::loading`],
+    /// which are redrawn periodically to animate them.
+    fn is_loading(&self) -> bool;
+
     /// Creates a panel from some path
     fn from_path(path: PathBuf) -> Self;
 }
@@ -110,6 +150,10 @@ pub struct PanelState {
 
     /// Path of the panel
     path: PathBuf,
+
+    /// Depth of the flat view, if the directory is requested flattened
+    /// (see [`crate::content::flat_dir_content`])
+    flat: Option<usize>,
 }
 
 impl Default for PanelState {
@@ -120,6 +164,7 @@ impl Default for PanelState {
             panel_id: rand::random(),
             cnt: 0,
             path: PathBuf::default(),
+            flat: None,
         }
     }
 }
@@ -134,17 +179,22 @@ impl PanelState {
             panel_id: self.panel_id,
             cnt: self.cnt + 1,
             path: self.path.clone(),
+            flat: self.flat,
         }
     }
 
     /// Returns `true` if the incoming panel-state:
     /// - has the same id
     /// - has a higher counter
+    /// - has the same path
     ///
     /// Otherwise it will return `false`.
+    ///
+    /// Checking the path is necessary, because huge directories are sent in multiple parts
+    /// with increasing counters - which must not overwrite the panel once we moved on.
     pub fn check_update(&self, other: &PanelState) -> bool {
         if self.panel_id == other.panel_id {
-            self.cnt < other.cnt
+            self.cnt < other.cnt && self.path == other.path
         } else {
             false
         }
@@ -153,12 +203,33 @@ impl PanelState {
     pub fn path(&self) -> PathBuf {
         self.path.clone()
     }
+
+    /// Returns the depth of the flat view, if the directory is requested flattened.
+    pub fn flat(&self) -> Option<usize> {
+        self.flat
+    }
+
+    /// Returns `true` if both states belong to the same panel.
+    pub fn same_panel(&self, other: &PanelState) -> bool {
+        self.panel_id == other.panel_id
+    }
+}
+
+/// Returns the canonical form of the path.
+///
+/// Paths on remote hosts cannot be canonicalized, but they are absolute already (see [`vfs`]).
+fn canonical_path(path: &Path) -> Option<PathBuf> {
+    if vfs::is_remote(path) {
+        Some(path.to_path_buf())
+    } else {
+        canonicalize(path).ok()
+    }
 }
 
 // Helper function to call 'unwatch' on some watcher
-fn unwatch_path<P: AsRef<Path>>(watcher: &mut RecommendedWatcher, path: P) {
+fn unwatch_path<P: AsRef<Path>>(watcher: &mut dyn Watcher, path: P) {
     let path = path.as_ref();
-    if path.exists() && path.is_dir() {
+    if path.exists() {
         match watcher.unwatch(path) {
             Ok(_) => {
                 trace!("unwatching {}", path.display());
@@ -171,9 +242,9 @@ fn unwatch_path<P: AsRef<Path>>(watcher: &mut RecommendedWatcher, path: P) {
 }
 
 // Helper function to call 'watch' on some watcher
-fn watch_path<P: AsRef<Path>>(watcher: &mut RecommendedWatcher, path: P) {
+fn watch_path<P: AsRef<Path>>(watcher: &mut dyn Watcher, path: P) {
     let path = path.as_ref();
-    if path.exists() && path.is_dir() {
+    if path.exists() {
         match watcher.watch(path, notify::RecursiveMode::NonRecursive) {
             Ok(_) => {
                 trace!("watching {}", path.display());
@@ -185,6 +256,128 @@ fn watch_path<P: AsRef<Path>>(watcher: &mut RecommendedWatcher, path: P) {
     }
 }
 
+/// Why a watched directory (or previewed file) has to be read again
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Change {
+    /// Items were created, removed or renamed
+    Items,
+    /// The content or the metadata of an item was modified
+    Content,
+}
+
+impl Change {
+    fn of(kind: &EventKind) -> Option<Change> {
+        match kind {
+            EventKind::Create(_)
+            | EventKind::Remove(_)
+            | EventKind::Modify(ModifyKind::Name(_)) => Some(Change::Items),
+            // Reading an item is no change
+            EventKind::Modify(ModifyKind::Metadata(MetadataKind::AccessTime)) => None,
+            EventKind::Modify(_) => Some(Change::Content),
+            _ => None,
+        }
+    }
+}
+
+/// Longest time that a reload is delayed by a continuous stream of changes
+const MAX_DEBOUNCE: Duration = Duration::from_secs(1);
+
+/// Collapses the reload requests of a watcher into one reload per burst of changes
+/// (e.g. `npm install` in the watched directory).
+///
+/// The panel is reloaded, once there was no further change for `delay` - but at the latest
+/// after [`MAX_DEBOUNCE`], so that continuous changes still show up.
+/// The task stops, when the returned sender is dropped.
+fn debounce(
+    delay: Duration,
+    state: Arc<Mutex<PanelState>>,
+    content_tx: mpsc::UnboundedSender<PanelUpdate>,
+) -> mpsc::UnboundedSender<()> {
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    tokio::spawn(async move {
+        // Wait for the first change of a burst
+        while rx.recv().await.is_some() {
+            let deadline = tokio::time::Instant::now() + MAX_DEBOUNCE;
+            loop {
+                let quiet = (tokio::time::Instant::now() + delay).min(deadline);
+                match tokio::time::timeout_at(quiet, rx.recv()).await {
+                    Ok(Some(())) => (),
+                    Ok(None) => return,
+                    Err(_) => break,
+                }
+            }
+            let state = state.lock().clone();
+            info!("Updating: {}", state.path().display());
+            if let Err(e) = content_tx.send(PanelUpdate { state }) {
+                error!("{e}");
+                return;
+            }
+        }
+    });
+    tx
+}
+
+/// Watches a single directory (or the previewed file).
+///
+/// inotify does not report changes on network filesystems (like nfs or sshfs),
+/// so directories on these filesystems are polled instead.
+struct PanelWatcher {
+    native: RecommendedWatcher,
+    poll: PollWatcher,
+    /// Filesystem types that are polled
+    poll_filesystems: Vec<String>,
+    /// Watched directory and whether it is polled
+    watched: Option<(PathBuf, bool)>,
+    /// If false, nothing is watched at all
+    enabled: bool,
+}
+
+impl PanelWatcher {
+    fn new<F>(handler: F, config: &WatcherConfig) -> notify::Result<Self>
+    where
+        F: notify::EventHandler + Clone,
+    {
+        let native = notify::recommended_watcher(handler.clone())?;
+        let interval = Duration::from_secs_f64(config.poll_interval.max(0.1));
+        let poll = PollWatcher::new(
+            handler,
+            notify::Config::default().with_poll_interval(interval),
+        )?;
+        Ok(PanelWatcher {
+            native,
+            poll,
+            poll_filesystems: config.poll_filesystems.clone(),
+            watched: None,
+            enabled: config.enabled,
+        })
+    }
+
+    fn watch(&mut self, path: &Path) {
+        self.unwatch();
+        if !self.enabled {
+            return;
+        }
+        let poll = filesystem_type(path)
+            .map(|fstype| self.poll_filesystems.contains(&fstype))
+            .unwrap_or(false);
+        if poll {
+            debug!("polling {} (network filesystem)", path.display());
+            watch_path(&mut self.poll, path);
+        } else {
+            watch_path(&mut self.native, path);
+        }
+        self.watched = Some((path.to_path_buf(), poll));
+    }
+
+    fn unwatch(&mut self) {
+        match self.watched.take() {
+            Some((path, true)) => unwatch_path(&mut self.poll, path),
+            Some((path, false)) => unwatch_path(&mut self.native, path),
+            None => (),
+        }
+    }
+}
+
 /// Combines all data that is necessary to update a panel.
 ///
 /// Will be send as a request to the [`ContentManager`].
@@ -201,7 +394,7 @@ pub struct ManagedPanel<PanelType: BasePanel> {
     state: Arc<Mutex<PanelState>>,
 
     /// File-watcher that sends update requests if the content of the directory changes
-    watcher: RecommendedWatcher,
+    watcher: PanelWatcher,
 
     /// Cached panels from previous requests.
     ///
@@ -220,35 +413,39 @@ impl<PanelType: BasePanel> ManagedPanel<PanelType> {
     pub fn new(
         cache: PanelCache<PanelType>,
         content_tx: mpsc::UnboundedSender<PanelUpdate>,
-        reload_on_modify: bool,
+        watcher_config: &WatcherConfig,
     ) -> Self {
         let state = Arc::new(Mutex::new(PanelState::default()));
         let watcher_state = state.clone();
-        let watcher_tx = content_tx.clone();
-        let watcher = notify::recommended_watcher(
+        let delay = Duration::from_secs_f64(watcher_config.debounce.max(0.0)).min(MAX_DEBOUNCE);
+        let watcher_tx = debounce(delay, state.clone(), content_tx.clone());
+        // Counter of the panel state, for which the watcher requested the last reload
+        let requested = Arc::new(AtomicU64::new(u64::MAX));
+        let watcher = PanelWatcher::new(
             move |res: std::result::Result<notify::Event, notify::Error>| {
                 if let Ok(event) = res {
-                    match event.kind {
-                        notify::EventKind::Create(_) | notify::EventKind::Remove(_) => {
-                            let state = watcher_state.lock().clone();
-                            info!("Updating: {}", state.path().display());
-                            if let Err(e) = watcher_tx.send(PanelUpdate { state }) {
-                                error!("{e}");
-                            }
-                        }
-                        notify::EventKind::Modify(_) => {
-                            if reload_on_modify {
-                                let state = watcher_state.lock().clone();
-                                info!("Updating: {}", state.path().display());
-                                if let Err(e) = watcher_tx.send(PanelUpdate { state }) {
-                                    error!("{e}");
-                                }
-                            }
-                        }
-                        _ => (),
+                    // Ignore everything that was caused by ourselves
+                    if !event.paths.is_empty() && event.paths.iter().all(|p| is_internal(p)) {
+                        trace!("ignoring internal event {:?}", event.kind);
+                        return;
+                    }
+                    let Some(change) = Change::of(&event.kind) else {
+                        return;
+                    };
+                    let state = watcher_state.lock().clone();
+                    // Modifications come in bursts (e.g. while a file is written), so they are
+                    // coalesced into one reload until the panel has received its new content
+                    let pending = requested.swap(state.cnt, atomic::Ordering::Relaxed) == state.cnt;
+                    if change == Change::Content && pending {
+                        trace!("coalescing {:?} of {}", event.kind, state.path().display());
+                        return;
+                    }
+                    if let Err(e) = watcher_tx.send(()) {
+                        error!("{e}");
                     }
                 }
             },
+            watcher_config,
         )
         .expect("File-watcher error");
         ManagedPanel {
@@ -269,7 +466,7 @@ impl<PanelType: BasePanel> ManagedPanel<PanelType> {
     /// The panel is created instantly, so there is no "loading..." or
     /// waiting for the content manager to fetch some data in the background.
     pub fn new_panel_instant<P: AsRef<Path>>(&mut self, path: Option<P>) {
-        if let Some(path) = path.and_then(|p| canonicalize(p.as_ref()).ok()) {
+        if let Some(path) = path.and_then(|p| canonical_path(p.as_ref())) {
             // Only create a new panel when the path has changed
             if path == self.panel.path() {
                 debug!("new-panel-instant: same path, path = {}", path.display());
@@ -319,7 +516,7 @@ impl<PanelType: BasePanel> ManagedPanel<PanelType> {
     /// If the cache is empty, a generic "loading..." panel is created.
     /// An empty panel is created if the given path is `None`.
     pub fn new_panel_delayed<P: AsRef<Path>>(&mut self, path: Option<P>) {
-        if let Some(path) = path.and_then(|p| canonicalize(p.as_ref()).ok()) {
+        if let Some(path) = path.and_then(|p| canonical_path(p.as_ref())) {
             // Only create a new panel when the path has changed
             if path == self.panel.path() {
                 return;
@@ -327,6 +524,7 @@ impl<PanelType: BasePanel> ManagedPanel<PanelType> {
 
             if let Some(cached) = self.cache.get(&path) {
                 let cached_access_time = cached.modified();
+                let complete = cached.is_complete();
                 // Update panel with content from cache
                 self.update_panel(cached);
 
@@ -338,7 +536,7 @@ impl<PanelType: BasePanel> ManagedPanel<PanelType> {
 
                 // If the access time is has not changed, dont trigger an update
                 // by returning early
-                if access_time == cached_access_time {
+                if access_time == cached_access_time && complete {
                     return;
                 }
             } else {
@@ -362,9 +560,36 @@ impl<PanelType: BasePanel> ManagedPanel<PanelType> {
             .expect("Receiver dropped or closed");
     }
 
+    /// Requests the directory flattened up to `depth` levels (or the normal view for `None`).
+    ///
+    /// The panel is shown unchanged until the new content arrives, and all later reloads
+    /// keep the depth until we leave the directory.
+    pub fn set_flat(&mut self, depth: Option<usize>) {
+        self.state.lock().flat = depth;
+        self.reload();
+    }
+
+    /// Returns the depth of the flat view, that was requested for the panel.
+    pub fn flat_depth(&self) -> Option<usize> {
+        self.state.lock().flat()
+    }
+
+    /// Drops all cached panels.
+    ///
+    /// The cache is shared, so this also affects all other panels that use it.
+    pub fn clear_cache(&self) -> (usize, usize) {
+        let usage = self.cache.usage();
+        self.cache.clear();
+        usage
+    }
+
     fn update(&mut self, panel: PanelType) {
         let mut state = self.state.lock();
         state.increase();
+        // The flat view ends, once we leave the directory
+        if state.path != panel.path() {
+            state.flat = None;
+        }
         state.path = panel.path().to_path_buf();
         self.panel.update_content(panel);
     }
@@ -373,7 +598,7 @@ impl<PanelType: BasePanel> ManagedPanel<PanelType> {
     ///
     /// Deactivates all watchers so that the panel will receive no updates until we call "unfreeze".
     pub fn freeze(&mut self) {
-        unwatch_path(&mut self.watcher, self.panel.path());
+        self.watcher.unwatch();
     }
 
     /// Unfreezes the panel in its current state.
@@ -381,7 +606,7 @@ impl<PanelType: BasePanel> ManagedPanel<PanelType> {
     /// Re-activates all watchers so that the panel will receive new updates.
     /// Also refreshes the panel in case the content has changed since the last freeze.
     pub fn unfreeze(&mut self) {
-        watch_path(&mut self.watcher, self.panel.path());
+        self.watcher.watch(self.panel.path());
         self.reload();
     }
 
@@ -392,8 +617,7 @@ impl<PanelType: BasePanel> ManagedPanel<PanelType> {
     pub fn update_panel(&mut self, panel: PanelType) {
         // Update watchers
         if self.panel.path() != panel.path() {
-            unwatch_path(&mut self.watcher, self.panel.path());
-            watch_path(&mut self.watcher, panel.path());
+            self.watcher.watch(panel.path());
         }
         self.update(panel);
     }
@@ -409,25 +633,121 @@ impl<PanelType: BasePanel> ManagedPanel<PanelType> {
     }
 }
 
+/// Arranges the parent, center and preview column on the screen.
+///
+/// The widths of the columns come from a [`LayoutPreset`], and the parent and preview column
+/// can be hidden on top of it - a hidden column leaves its space to the center column.
 #[derive(Clone)]
-struct MillerColumns {
+struct Layout {
     left_x_range: Range<u16>,
     center_x_range: Range<u16>,
     right_x_range: Range<u16>,
     y_range: Range<u16>,
     width: u16,
+    /// Size of the terminal
+    size: (u16, u16),
+    /// Weather or not the preview column is shown (otherwise the center column takes its space)
+    preview: bool,
+    /// Hides the left column, the header and the footer
+    zen: bool,
+    /// Maximum width of the center column in percent of the terminal width,
+    /// if the center column grows with its content
+    autosize: Option<u16>,
+    /// Width that the content of the center column needs
+    content_width: u16,
+    preset: LayoutPreset,
 }
 
-impl MillerColumns {
+impl Layout {
     pub fn from_size(terminal_size: (u16, u16)) -> Self {
-        let (sx, sy) = terminal_size;
-        Self {
-            left_x_range: 0..(sx / 8),
-            center_x_range: (sx / 8)..(sx / 2),
-            right_x_range: (sx / 2)..sx,
-            y_range: 1..sy.saturating_sub(1), // 1st line is reserved for the header, last for the footer
-            width: sx,
+        let mut layout = Self {
+            left_x_range: 0..0,
+            center_x_range: 0..0,
+            right_x_range: 0..0,
+            y_range: 0..0,
+            width: 0,
+            size: terminal_size,
+            preview: true,
+            zen: false,
+            autosize: None,
+            content_width: 0,
+            preset: LayoutPreset::default(),
+        };
+        layout.arrange();
+        layout
+    }
+
+    /// Calculates the columns from the terminal size and the options
+    fn arrange(&mut self) {
+        let (sx, sy) = self.size;
+        let [left, center, right] = self.preset.ratios().map(u32::from);
+        let show_left = left > 0 && !self.zen;
+        let show_right = right > 0 && self.preview;
+        let x = |ratio: u32| (u32::from(sx) * ratio / (left + center + right)) as u16;
+        let left_end = if show_left { x(left) } else { 0 };
+        let mut center_end = if show_right { x(left + center) } else { sx };
+        // The center column takes space from the preview, but never shrinks below its default width
+        if let (true, Some(max)) = (show_right, self.autosize) {
+            let max_end = (u32::from(sx) * u32::from(max.min(100)) / 100) as u16;
+            let wanted_end = left_end.saturating_add(self.content_width);
+            center_end = center_end.max(wanted_end.min(max_end));
         }
+        // 1st line is reserved for the header, last for the footer.
+        // The footer line is also kept in zen mode, because the prompts are shown there.
+        let y_start = if self.zen { 0 } else { 1 };
+        self.left_x_range = 0..left_end;
+        self.center_x_range = left_end..center_end;
+        self.right_x_range = center_end..sx;
+        self.y_range = y_start..sy.saturating_sub(1);
+        self.width = sx;
+    }
+
+    /// Recalculates the columns for a new terminal size
+    pub fn resize(&mut self, terminal_size: (u16, u16)) {
+        self.size = terminal_size;
+        self.arrange();
+    }
+
+    pub fn toggle_preview(&mut self) {
+        self.preview = !self.preview;
+        self.arrange();
+    }
+
+    pub fn toggle_zen(&mut self) {
+        self.zen = !self.zen;
+        self.arrange();
+    }
+
+    pub fn set_preset(&mut self, preset: LayoutPreset) {
+        self.preset = preset;
+        self.arrange();
+    }
+
+    pub fn preset(&self) -> LayoutPreset {
+        self.preset
+    }
+
+    /// Lets the center column grow with its content, up to `max` percent of the terminal width.
+    pub fn set_autosize(&mut self, max: Option<u16>) {
+        self.autosize = max;
+        self.arrange();
+    }
+
+    /// Fits the center column to content of the given width (if auto-sizing is enabled).
+    ///
+    /// Returns true if the columns have changed.
+    pub fn fit_content(&mut self, content_width: u16) -> bool {
+        if self.autosize.is_none() || self.content_width == content_width {
+            return false;
+        }
+        self.content_width = content_width;
+        let center = self.center_x_range.clone();
+        self.arrange();
+        self.center_x_range != center
+    }
+
+    pub fn zen(&self) -> bool {
+        self.zen
     }
 
     pub fn footer(&self) -> u16 {
@@ -442,3 +762,133 @@ impl MillerColumns {
         self.width
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The clock is paused, so the sleeps only advance the time of the runtime
+    #[tokio::test(start_paused = true)]
+    async fn debounced_reloads() {
+        let state = Arc::new(Mutex::new(PanelState::default()));
+        let (content_tx, mut content_rx) = mpsc::unbounded_channel();
+        let tx = debounce(Duration::from_millis(50), state, content_tx);
+        // A burst of changes causes one reload
+        for _ in 0..5 {
+            tx.send(()).unwrap();
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert!(content_rx.try_recv().is_err());
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(content_rx.try_recv().is_ok());
+        assert!(content_rx.try_recv().is_err());
+
+        // Continuous changes are not delayed forever
+        let start = tokio::time::Instant::now();
+        while start.elapsed() < MAX_DEBOUNCE + Duration::from_millis(200) {
+            tx.send(()).unwrap();
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        assert!(content_rx.try_recv().is_ok());
+    }
+
+    #[test]
+    fn watcher_changes() {
+        use notify::event::{CreateKind, DataChange, RenameMode};
+        assert_eq!(
+            Change::of(&EventKind::Create(CreateKind::File)),
+            Some(Change::Items)
+        );
+        // Renames are reported as modifications
+        assert_eq!(
+            Change::of(&EventKind::Modify(ModifyKind::Name(RenameMode::Both))),
+            Some(Change::Items)
+        );
+        assert_eq!(
+            Change::of(&EventKind::Modify(ModifyKind::Data(DataChange::Content))),
+            Some(Change::Content)
+        );
+        let access = ModifyKind::Metadata(MetadataKind::AccessTime);
+        assert_eq!(Change::of(&EventKind::Modify(access)), None);
+        assert_eq!(Change::of(&EventKind::Any), None);
+    }
+
+    #[test]
+    fn layout_options() {
+        let mut layout = Layout::from_size((80, 24));
+        assert_eq!(layout.center_x_range, 10..40);
+        assert_eq!(layout.y_range, 1..23);
+
+        layout.toggle_preview();
+        assert_eq!(layout.center_x_range, 10..80);
+        assert!(layout.right_x_range.is_empty());
+
+        layout.toggle_zen();
+        assert!(layout.left_x_range.is_empty());
+        assert_eq!(layout.center_x_range, 0..80);
+        assert_eq!(layout.y_range, 0..23);
+
+        // Options survive a resize
+        layout.resize((100, 30));
+        assert_eq!(layout.center_x_range, 0..100);
+
+        layout.toggle_preview();
+        assert_eq!(layout.center_x_range, 0..50);
+        assert_eq!(layout.right_x_range, 50..100);
+    }
+
+    #[test]
+    fn autosize_center_column() {
+        let mut layout = Layout::from_size((80, 24));
+        // Without auto-sizing, the content is ignored
+        assert!(!layout.fit_content(70));
+        assert_eq!(layout.center_x_range, 10..40);
+
+        layout.set_autosize(Some(75));
+        assert_eq!(layout.center_x_range, 10..40);
+        assert!(layout.fit_content(70));
+        assert_eq!(layout.center_x_range, 10..60);
+        assert_eq!(layout.right_x_range, 60..80);
+
+        // Short entries keep the default width
+        assert!(layout.fit_content(20));
+        assert_eq!(layout.center_x_range, 10..40);
+        assert!(layout.fit_content(40));
+        assert_eq!(layout.center_x_range, 10..50);
+        assert!(!layout.fit_content(40));
+
+        // The width is recomputed after a resize
+        layout.resize((100, 30));
+        assert_eq!(layout.center_x_range, 12..52);
+
+        // Without a preview, the center column takes the whole space anyway
+        layout.toggle_preview();
+        assert_eq!(layout.center_x_range, 12..100);
+    }
+
+    #[test]
+    fn layout_presets() {
+        let mut layout = Layout::from_size((80, 24));
+        layout.set_preset(LayoutPreset::Dual);
+        assert!(layout.left_x_range.is_empty());
+        assert_eq!(layout.center_x_range, 0..40);
+        assert_eq!(layout.right_x_range, 40..80);
+
+        layout.set_preset(LayoutPreset::Single);
+        assert_eq!(layout.center_x_range, 0..80);
+        assert!(layout.right_x_range.is_empty());
+
+        // Showing the preview again doesn't bring it back
+        layout.toggle_preview();
+        layout.toggle_preview();
+        assert!(layout.right_x_range.is_empty());
+
+        // Hidden columns stay hidden in the next preset
+        layout.toggle_zen();
+        layout.set_preset(LayoutPreset::Miller);
+        assert!(layout.left_x_range.is_empty());
+        assert_eq!(layout.center_x_range, 0..40);
+        layout.toggle_zen();
+        assert_eq!(layout.center_x_range, 10..40);
+    }
+}
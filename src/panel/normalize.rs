@@ -0,0 +1,185 @@
+//! Normalizes the elements around the visible part of the directory panels in the background.
+//!
+//! Normalizing an element (see [`DirElem::normalize`](super::DirElem::normalize)) reads its
+//! metadata and counts the entries of directories. Doing this for every new line while scrolling
+//! through huge directories (like `/nix/store`) makes scrolling stutter, so the panels request
+//! the elements above and below the visible ones, which are read by a background thread.
+//! Only the latest request of a directory is kept - everything else is dropped.
+use std::{
+    collections::{HashMap, VecDeque},
+    fs::read_dir,
+    os::unix::prelude::{MetadataExt, PermissionsExt},
+    path::{Path, PathBuf},
+    sync::Once,
+    time::SystemTime,
+};
+
+use log::warn;
+use once_cell::sync::Lazy;
+use parking_lot::{Condvar, Mutex};
+
+use crate::{
+    util::{file_size_str, is_traversable},
+    vfs,
+};
+
+/// Number of directories, whose requests are kept (one for each column)
+const MAX_REQUESTS: usize = 3;
+
+/// Maximum number of normalized elements, that are waiting for their panel
+const MAX_RESULTS: usize = 1024;
+
+static QUEUE: Lazy<(Mutex<Queue>, Condvar)> = Lazy::new(Default::default);
+
+#[derive(Default)]
+struct Queue {
+    /// Requested elements and their directory, the newest request last
+    requests: VecDeque<(PathBuf, Vec<PathBuf>)>,
+    results: HashMap<PathBuf, Normalized>,
+}
+
+/// Metadata of an element, that is read when it is normalized
+#[derive(Debug, Clone, Default)]
+pub struct Normalized {
+    pub mode: u32,
+    pub modified: Option<SystemTime>,
+    /// True for directories, that we cannot enter
+    pub is_locked: bool,
+    /// File size or number of entries of a directory
+    pub suffix: String,
+}
+
+impl Normalized {
+    pub fn read(path: &Path) -> Self {
+        if vfs::is_remote(path) {
+            // Counting the entries of remote directories would need a request per directory
+            let metadata = vfs::metadata(path).ok();
+            return Normalized {
+                mode: metadata.map(|m| m.mode).unwrap_or_default(),
+                modified: metadata.and_then(|m| m.modified),
+                is_locked: false,
+                suffix: metadata
+                    .filter(|m| !m.is_dir)
+                    .map(|m| file_size_str(m.len))
+                    .unwrap_or_default(),
+            };
+        }
+        let (mode, size, modified) = path
+            .metadata()
+            .map(|m| (m.permissions().mode(), m.size(), m.modified().ok()))
+            .unwrap_or_default();
+        let is_dir = path.is_dir();
+        let suffix = if is_dir {
+            read_dir(path)
+                .map(|res| res.count().to_string())
+                .unwrap_or_default()
+        } else {
+            file_size_str(size)
+        };
+        Normalized {
+            mode,
+            modified,
+            is_locked: is_dir && !is_traversable(path),
+            suffix,
+        }
+    }
+}
+
+/// Requests the normalization of some elements of a directory, which replaces its previous request.
+///
+/// The elements are normalized from the end of the list, so the most important one comes last.
+pub fn request(dir: &Path, mut paths: Vec<PathBuf>) {
+    static WORKER: Once = Once::new();
+    WORKER.call_once(|| {
+        if let Err(e) = std::thread::Builder::new()
+            .name("normalize".into())
+            .spawn(worker)
+        {
+            warn!("Cannot normalize in the background: {e}");
+        }
+    });
+    let (queue, ready) = &*QUEUE;
+    let mut queue = queue.lock();
+    paths.retain(|path| !queue.results.contains_key(path));
+    queue.requests.retain(|(requested, _)| requested != dir);
+    if !paths.is_empty() {
+        queue.requests.push_back((dir.to_path_buf(), paths));
+    }
+    while queue.requests.len() > MAX_REQUESTS {
+        queue.requests.pop_front();
+    }
+    ready.notify_one();
+}
+
+/// Takes the metadata of the element, if it was read in the background.
+pub fn take(path: &Path) -> Option<Normalized> {
+    QUEUE.0.lock().results.remove(path)
+}
+
+/// Drops the metadata of the elements of a directory, that was read again (it may be outdated).
+pub fn forget(dir: &Path) {
+    QUEUE
+        .0
+        .lock()
+        .results
+        .retain(|path, _| path.parent() != Some(dir));
+}
+
+fn worker() {
+    let (queue, ready) = &*QUEUE;
+    loop {
+        let path = {
+            let mut queue = queue.lock();
+            loop {
+                // The newest request first
+                let next = queue.requests.back_mut().map(|(_, paths)| paths.pop());
+                match next {
+                    Some(Some(path)) => break path,
+                    Some(None) => {
+                        queue.requests.pop_back();
+                    }
+                    None => ready.wait(&mut queue),
+                }
+            }
+        };
+        let normalized = Normalized::read(&path);
+        let mut queue = queue.lock();
+        if queue.results.len() >= MAX_RESULTS {
+            queue.results.clear();
+        }
+        queue.results.insert(path, normalized);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn normalize_in_background() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("file");
+        let sub = dir.path().join("sub");
+        std::fs::write(&file, "content").unwrap();
+        std::fs::create_dir(&sub).unwrap();
+        std::fs::write(sub.join("inner"), "").unwrap();
+
+        request(dir.path(), vec![file.clone(), sub.clone()]);
+        let start = Instant::now();
+        let mut normalized = HashMap::new();
+        while normalized.len() < 2 && start.elapsed() < Duration::from_secs(5) {
+            for path in [&file, &sub] {
+                if let Some(n) = take(path) {
+                    normalized.insert(path.clone(), n);
+                }
+            }
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        assert_eq!(normalized[&file].suffix, file_size_str(7));
+        assert_eq!(normalized[&sub].suffix, "1");
+        assert!(normalized[&sub].modified.is_some());
+        // Results are only taken once
+        assert!(take(&file).is_none());
+    }
+}
@@ -1,37 +1,239 @@
 use std::{
+    collections::HashMap,
     env::temp_dir,
     fs::File,
-    io::{self, BufRead, Stdout},
+    io::{self, BufRead, Read, Seek, SeekFrom, Stdout, Write},
     ops::Range,
     path::{Path, PathBuf},
     process::Stdio,
-    time::{SystemTime, UNIX_EPOCH},
+    time::{Duration, SystemTime, UNIX_EPOCH},
 };
 
 use crate::{
-    config::color::print_vertical_bar,
-    util::{truncate_with_color_codes, ExactWidth},
+    config::{
+        color::{adapt, color_main, print_vertical_bar},
+        PreviewConfig, PreviewMode,
+    },
+    content::internal_operation,
+    jobs::{self, JobKind},
+    util::{
+        cell_size, file_size_str, total_size, truncate_with_color_codes, write_atomic, ExactWidth,
+    },
+    vfs,
 };
 
-use super::{BasePanel, DirPanel, Draw, PanelContent};
+use super::{
+    metadata::{audio_info, image_info},
+    BasePanel, DirElem, DirPanel, Draw, PanelContent,
+};
 use crossterm::{
     cursor, queue,
-    style::{self, Colors, Print, ResetColor, SetColors},
+    style::{self, Colors, Print, PrintStyledContent, ResetColor, SetColors, Stylize},
     Result,
 };
 use fasthash::sea;
-use image::DynamicImage;
+use image::{imageops::FilterType, DynamicImage};
 use once_cell::sync::OnceCell;
+use tokio::sync::oneshot::{self, error::TryRecvError};
+
+/// Number of lines that are shown above a line, that is selected with [`FilePreview::show_line`]
+const LINE_CONTEXT: usize = 3;
+
+/// Number of bytes that are read from files, which are too large (or binary) for `bat`
+const HEAD_SIZE: u64 = 64 * 1024;
+
+/// Files with a null byte in this many first bytes are considered binary
+const SNIFF_SIZE: usize = 8 * 1024;
+
+/// Number of bytes that are read at once, when the end of a file is previewed
+const TAIL_CHUNK: u64 = 64 * 1024;
+
+/// Maximum number of entries, that are counted for the total size of the marked items
+const MARKED_SIZE_LIMIT: usize = 10_000;
+
+/// Maximum number of bytes that are read from the end of a file (in case of very long lines)
+const TAIL_SIZE: u64 = 16 * TAIL_CHUNK;
+
+static PREVIEW_CONFIG: OnceCell<PreviewConfig> = OnceCell::new();
+
+/// Sets the limits of the previews. Must be called before the first preview is generated.
+pub fn set_preview_config(config: PreviewConfig) {
+    if PREVIEW_CONFIG.set(config).is_err() {
+        log::warn!("preview config is already set");
+    }
+}
+
+fn preview_config() -> &'static PreviewConfig {
+    PREVIEW_CONFIG.get_or_init(PreviewConfig::default)
+}
+
+/// Returns false if previews are disabled (see `--no-previews`).
+pub fn previews_enabled() -> bool {
+    preview_config().enabled
+}
+
+/// Returns true if directories are previewed as a tree (see [`FilePreview::tree`]).
+pub fn tree_preview() -> bool {
+    preview_config().tree
+}
+
+/// Ratio of height to width of a half-block, if the terminal does not report its cell size
+/// (cells are usually about twice as high as wide)
+const DEFAULT_PIXEL_ASPECT: f32 = 1.0;
+
+/// Returns the height divided by the width of a half-block "pixel" of an image preview.
+fn pixel_aspect() -> f32 {
+    match cell_size() {
+        Some((width, height)) => height as f32 / (2.0 * width as f32),
+        None => DEFAULT_PIXEL_ASPECT,
+    }
+}
+
+/// Number of levels that are shown in the tree preview of a directory
+const TREE_DEPTH: usize = 2;
+
+/// Limits for [`ImageView::zoom`]
+const MIN_ZOOM: f32 = 0.25;
+const MAX_ZOOM: f32 = 16.0;
+
+/// How an image is scaled to the size of the preview panel
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ImageFit {
+    /// The whole image is visible
+    #[default]
+    Whole,
+    Width,
+    Height,
+    /// One pixel of the image per column
+    Original,
+}
+
+/// Zoom level and visible region of an image preview
+#[derive(Debug, Clone, Copy)]
+pub struct ImageView {
+    fit: ImageFit,
+    zoom: f32,
+    /// Center of the visible region (relative to the image size)
+    center: (f32, f32),
+    /// Size of the visible region (relative to the image size), which is updated when drawn
+    visible: (f32, f32),
+}
+
+impl Default for ImageView {
+    fn default() -> Self {
+        ImageView {
+            fit: ImageFit::default(),
+            zoom: 1.0,
+            center: (0.5, 0.5),
+            visible: (1.0, 1.0),
+        }
+    }
+}
+
+impl ImageView {
+    pub fn zoom(&mut self, factor: f32) {
+        self.zoom = (self.zoom * factor).clamp(MIN_ZOOM, MAX_ZOOM);
+    }
+
+    pub fn cycle_fit(&mut self) {
+        self.fit = match self.fit {
+            ImageFit::Whole => ImageFit::Width,
+            ImageFit::Width => ImageFit::Height,
+            ImageFit::Height => ImageFit::Original,
+            ImageFit::Original => ImageFit::Whole,
+        };
+        self.zoom = 1.0;
+        self.center = (0.5, 0.5);
+    }
+
+    /// Moves the visible region by a quarter of its size into the given direction.
+    pub fn pan(&mut self, dx: f32, dy: f32) {
+        self.center.0 += dx * self.visible.0 / 4.0;
+        self.center.1 += dy * self.visible.1 / 4.0;
+    }
+
+    /// Scales and crops the image, so that it fits into `width` x `height` pixels.
+    ///
+    /// `aspect` is the height of one of these pixels divided by its width
+    /// (see [`pixel_aspect`]), the image is squeezed accordingly to keep its proportions.
+    fn render(&mut self, img: &DynamicImage, width: u32, height: u32, aspect: f32) -> DynamicImage {
+        let (img_w, img_h) = (img.width() as f32, img.height() as f32);
+        let (w, h) = (width as f32, height as f32);
+        if img_w == 0.0 || img_h == 0.0 || w == 0.0 || h == 0.0 || aspect <= 0.0 {
+            return img.clone();
+        }
+        // Panel pixels per image pixel (horizontally - vertically it is 'scale / aspect')
+        let scale = self.zoom
+            * match self.fit {
+                ImageFit::Whole => (w / img_w).min(h * aspect / img_h),
+                ImageFit::Width => w / img_w,
+                ImageFit::Height => h * aspect / img_h,
+                ImageFit::Original => 1.0,
+            };
+        let scale_y = scale / aspect;
+        if self.fit == ImageFit::Whole && self.zoom == 1.0 {
+            self.visible = (1.0, 1.0);
+            self.center = (0.5, 0.5);
+            let target_w = ((img_w * scale).round() as u32).clamp(1, width);
+            let target_h = ((img_h * scale_y).round() as u32).clamp(1, height);
+            return img.thumbnail_exact(target_w, target_h);
+        }
+        // Region of the image that fits into the panel
+        let region_w = (w / scale).min(img_w);
+        let region_h = (h / scale_y).min(img_h);
+        self.visible = (region_w / img_w, region_h / img_h);
+
+        // Keep the region inside of the image
+        let center_x = (self.center.0 * img_w).clamp(region_w / 2.0, img_w - region_w / 2.0);
+        let center_y = (self.center.1 * img_h).clamp(region_h / 2.0, img_h - region_h / 2.0);
+        self.center = (center_x / img_w, center_y / img_h);
+
+        let region = img.crop_imm(
+            (center_x - region_w / 2.0) as u32,
+            (center_y - region_h / 2.0) as u32,
+            (region_w as u32).max(1),
+            (region_h as u32).max(1),
+        );
+        let target_w = ((region_w * scale) as u32).clamp(1, width);
+        let target_h = ((region_h * scale_y) as u32).clamp(1, height);
+        region.resize_exact(target_w, target_h, FilterType::Triangle)
+    }
+}
 
 #[derive(Debug, Clone)]
 pub enum Preview {
     Image {
         img: Option<DynamicImage>,
+        /// Metadata that is shown above the image
+        header: Vec<String>,
+        /// Metadata that is shown below the image
         info: Vec<String>,
+        view: ImageView,
+        /// Further frames of a video, that take turns with `img`
+        frames: Vec<DynamicImage>,
     },
     Text {
         lines: Vec<String>,
     },
+    /// The end of a file, the last line is drawn at the bottom of the panel
+    Tail {
+        lines: Vec<String>,
+    },
+    Tree {
+        entries: Vec<TreeEntry>,
+        show_hidden: bool,
+    },
+}
+
+/// Entry of the tree preview of a directory
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TreeEntry {
+    /// Level in the tree, starting at 1 for the content of the previewed directory
+    depth: usize,
+    name: String,
+    is_dir: bool,
+    /// True if the entry or one of its parents is hidden
+    is_hidden: bool,
 }
 
 #[derive(Debug, Clone)]
@@ -60,18 +262,33 @@ impl Draw for FilePreview {
             )?;
         }
 
-        match &self.preview {
-            Preview::Image { img, info } => {
+        match &mut self.preview {
+            Preview::Image {
+                img,
+                header,
+                info,
+                view,
+                ..
+            } => {
                 // load image
                 if let Some(img) = img {
+                    // The header takes at most half of the panel
+                    let header_height = (header.len() as u16).min(height / 2);
+                    for (idx, line) in header.iter().take(header_height as usize).enumerate() {
+                        let line = line.as_str().exact_width(width as usize);
+                        let cx = x_range.start.saturating_add(1);
+                        let cy = y_range.start + idx as u16;
+                        queue!(stdout, cursor::MoveTo(cx, cy), Print(" "), Print(line))?;
+                    }
+                    let height = height - header_height;
                     // Generate thumbnail
                     let thumbnail_height = if info.is_empty() {
                         2 * height
                     } else {
                         4 * height / 3
                     };
-                    let img = img
-                        .thumbnail(width as u32, thumbnail_height as u32)
+                    let img = view
+                        .render(img, width as u32, thumbnail_height as u32, pixel_aspect())
                         .into_rgb8();
                     log::debug!(
                         "img: {}x{}, wxh: {}x{}",
@@ -80,7 +297,7 @@ impl Draw for FilePreview {
                         width,
                         height,
                     );
-                    let mut cy = y_range.start;
+                    let mut cy = y_range.start + header_height;
                     for y in (0..img.height() as usize).step_by(2) {
                         for x in 0..width {
                             // cursor x
@@ -90,16 +307,16 @@ impl Draw for FilePreview {
                             let px_lo = img.get_pixel_checked(x as u32, (y + 1) as u32);
                             if let (Some(px_hi), Some(px_lo)) = (px_hi, px_lo) {
                                 let color = Colors::new(
-                                    style::Color::Rgb {
+                                    adapt(style::Color::Rgb {
                                         r: px_lo.0[0],
                                         g: px_lo.0[1],
                                         b: px_lo.0[2],
-                                    },
-                                    style::Color::Rgb {
+                                    }),
+                                    adapt(style::Color::Rgb {
                                         r: px_hi.0[0],
                                         g: px_hi.0[1],
                                         b: px_hi.0[2],
-                                    },
+                                    }),
                                 );
                                 queue!(stdout, SetColors(color), Print("▄"),)?;
                             } else {
@@ -129,7 +346,7 @@ impl Draw for FilePreview {
                     queue!(
                         stdout,
                         cursor::MoveTo(x_range.start + 1, y_range.start + 1),
-                        Print(format!("Failed to load image '{}'", self.path().display())),
+                        Print(format!("Failed to load image '{}'", self.path.display())),
                     )?;
                     for y in y_range.start + 1..y_range.end {
                         for x in x_range.start + 1..x_range.end {
@@ -138,35 +355,119 @@ impl Draw for FilePreview {
                     }
                 }
             }
-            Preview::Text { lines } => {
-                // Print preview
-                let mut idx = 0;
-                // Clear entire panel
-                for x in x_range.start + 1..x_range.end {
-                    for y in y_range.clone() {
-                        queue!(stdout, cursor::MoveTo(x, y), Print(" "),)?;
-                    }
-                }
-                for line in lines.iter().take(height as usize) {
-                    let cy = idx + y_range.start;
-                    let line = truncate_with_color_codes(line, width.saturating_sub(1) as usize);
-                    queue!(
-                        stdout,
-                        cursor::MoveTo(x_range.start + 1, cy),
-                        Print(" "),
-                        cursor::MoveTo(x_range.start + 2, cy),
-                        Print(line)
-                    )?;
-                    idx += 1;
-                }
+            Preview::Text { lines } => draw_lines(stdout, lines, x_range, y_range)?,
+            Preview::Tail { lines } => {
+                let height = y_range.end.saturating_sub(y_range.start) as usize;
+                let skip = lines.len().saturating_sub(height);
+                draw_lines(stdout, &lines[skip..], x_range, y_range)?
+            }
+            Preview::Tree {
+                entries,
+                show_hidden,
+            } => {
+                let lines = tree_lines(entries, *show_hidden, color_main());
+                draw_lines(stdout, &lines, x_range, y_range)?
             }
         }
         Ok(())
     }
 }
 
+/// Prints the lines of a text preview and clears the rest of the panel.
+fn draw_lines(
+    stdout: &mut Stdout,
+    lines: &[String],
+    x_range: Range<u16>,
+    y_range: Range<u16>,
+) -> Result<()> {
+    let width = x_range.end.saturating_sub(x_range.start.saturating_add(1));
+    let height = y_range.end.saturating_sub(y_range.start);
+    // Clear entire panel
+    for x in x_range.start + 1..x_range.end {
+        for y in y_range.clone() {
+            queue!(stdout, cursor::MoveTo(x, y), Print(" "),)?;
+        }
+    }
+    for (idx, line) in lines.iter().take(height as usize).enumerate() {
+        let cy = idx as u16 + y_range.start;
+        let line = truncate_with_color_codes(line, width.saturating_sub(1) as usize);
+        queue!(
+            stdout,
+            cursor::MoveTo(x_range.start + 1, cy),
+            Print(" "),
+            cursor::MoveTo(x_range.start + 2, cy),
+            Print(line)
+        )?;
+    }
+    Ok(())
+}
+
 impl FilePreview {
+    /// Creates a tree preview of the directory.
+    pub fn tree(path: PathBuf) -> Self {
+        let modified = path
+            .metadata()
+            .ok()
+            .and_then(|m| m.modified().ok())
+            .unwrap_or_else(SystemTime::now);
+        let mut entries = Vec::new();
+        read_tree(&path, 1, false, preview_config().tree_entries, &mut entries);
+        FilePreview {
+            path,
+            modified,
+            preview: Preview::Tree {
+                entries,
+                show_hidden: false,
+            },
+        }
+    }
+
+    /// Shows or hides the hidden entries of a tree preview.
+    pub fn set_hidden(&mut self, hidden: bool) {
+        if let Preview::Tree { show_hidden, .. } = &mut self.preview {
+            *show_hidden = hidden;
+        }
+    }
+
+    /// Shows the next frame of a video, returns false if there is only one.
+    pub fn next_frame(&mut self) -> bool {
+        match &mut self.preview {
+            Preview::Image {
+                img: Some(img),
+                frames,
+                ..
+            } if !frames.is_empty() => {
+                let next = frames.remove(0);
+                frames.push(std::mem::replace(img, next));
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Returns the view of an image preview, or `None` if this is not an image.
+    pub fn image_view_mut(&mut self) -> Option<&mut ImageView> {
+        match &mut self.preview {
+            Preview::Image {
+                img: Some(_), view, ..
+            } => Some(view),
+            _ => None,
+        }
+    }
+
+    /// Shows the text around the given line (starting at 1) and highlights it.
+    ///
+    /// Does nothing, if this is not a text preview.
+    pub fn show_line(&mut self, line: usize) {
+        if let Preview::Text { .. } | Preview::Tail { .. } = self.preview {
+            self.preview = text_around_line(&self.path, line);
+        }
+    }
+
     pub fn new(path: PathBuf) -> Self {
+        if vfs::is_remote(&path) {
+            return FilePreview::remote(path);
+        }
         let extension = path
             .extension()
             .and_then(|s| s.to_str())
@@ -181,8 +482,11 @@ impl FilePreview {
         let mime = mime_guess::from_ext(extension).first_or_text_plain();
 
         let preview = match (mime.type_().as_str(), mime.subtype().as_str()) {
-            ("image", _) => image_preview(&path, mediainfo(&path).unwrap_or_default()),
-            ("audio", _) => cmd_to_preview("mediainfo", mediainfo(&path)),
+            ("image", _) => image_preview(&path, Vec::new(), true),
+            ("audio", _) => match audio_info(&path) {
+                Some(lines) => Preview::Text { lines },
+                None => cmd_to_preview("mediainfo", mediainfo(&path)),
+            },
             ("video", _) => video_preview(&path, modified),
             ("application", "gzip") => cmd_to_preview("tar", tar_list(&path)),
             ("application", "x-tar") => cmd_to_preview("tar", tar_list(&path)),
@@ -201,16 +505,16 @@ impl FilePreview {
             | ("application", "javascript; charset=utf-8")
             | ("application", "rtf")
             | ("application", "xml")
-            | ("application", "xhtml+xml") => bat_preview(&path, false),
+            | ("application", "xhtml+xml") => text_preview(&path, false, preview_config()),
             // Binary based application/* types
             ("application", "octet-stream") | ("application", "msgpack") => {
-                bat_preview(&path, true)
+                text_preview(&path, true, preview_config())
             }
             // Use mediainfo for everything else
             ("application", _) => cmd_to_preview("mediainfo", mediainfo(&path)),
-            ("text", _) => bat_preview(&path, false),
+            ("text", _) => text_preview(&path, false, preview_config()),
             // Default to bat with binary mode enabled
-            _ext => bat_preview(&path, true),
+            _ext => text_preview(&path, true, preview_config()),
         };
 
         FilePreview {
@@ -219,17 +523,57 @@ impl FilePreview {
             preview,
         }
     }
+
+    /// Previews a file on a remote host - only its beginning is downloaded and shown as text.
+    fn remote(path: PathBuf) -> Self {
+        let modified = vfs::metadata(&path)
+            .ok()
+            .and_then(|m| m.modified)
+            .unwrap_or_else(SystemTime::now);
+        let preview = text_preview(&path, true, preview_config());
+        FilePreview {
+            path,
+            modified,
+            preview,
+        }
+    }
 }
 
-fn image_preview(path: impl AsRef<Path>, info: Vec<String>) -> Preview {
-    if let Ok(img_bytes) = image::io::Reader::open(&path) {
-        let img = img_bytes.decode().ok().map(|img| img.thumbnail(960, 540));
-        Preview::Image { img, info }
+/// Loads the image and (if `metadata` is set) shows its resolution and EXIF data above it.
+fn image_preview(path: impl AsRef<Path>, info: Vec<String>, metadata: bool) -> Preview {
+    let img = image::io::Reader::open(&path)
+        .ok()
+        .and_then(|reader| reader.decode().ok());
+    let header = if metadata {
+        image_info(
+            path.as_ref(),
+            img.as_ref().map(|img| (img.width(), img.height())),
+        )
     } else {
-        Preview::Image { img: None, info }
+        Vec::new()
+    };
+    Preview::Image {
+        img: img.map(|img| img.thumbnail(960, 540)),
+        header,
+        info,
+        view: ImageView::default(),
+        frames: Vec::new(),
     }
 }
 
+/// Shows the first frame of a video, the others are cycled with [`FilePreview::next_frame`].
+fn frames_preview(frames: &[PathBuf], info: Vec<String>) -> Preview {
+    let mut preview = image_preview(&frames[0], info, false);
+    if let Preview::Image { frames: images, .. } = &mut preview {
+        *images = frames[1..]
+            .iter()
+            .filter_map(|frame| image::open(frame).ok())
+            .map(|img| img.thumbnail(960, 540))
+            .collect();
+    }
+    preview
+}
+
 fn video_preview(path: impl AsRef<Path>, modified: SystemTime) -> Preview {
     // Check, if ffmpeg exists
     static FFMPEG_INSTALLED: OnceCell<bool> = OnceCell::new();
@@ -280,37 +624,120 @@ fn ffmpeg_thumbnail(path: impl AsRef<Path>, modified: u64) -> anyhow::Result<Pre
     static THUMBNAIL_DIR: OnceCell<PathBuf> = OnceCell::new();
     let full_path = path.as_ref().as_os_str();
     let path_hash = sea::hash64(full_path.as_encoded_bytes());
-    let identifier = format!("{path_hash}{modified}.jpg");
-    let thumbnail = THUMBNAIL_DIR.get_or_init(temp_dir).join(identifier);
-    if thumbnail.exists() {
-        log::debug!("using existing thumbnail {}", thumbnail.display());
-        Ok(image_preview(
-            thumbnail,
-            mediainfo(path).unwrap_or_default(),
-        ))
+    let thumbnail_dir = THUMBNAIL_DIR.get_or_init(temp_dir);
+    let n_frames = preview_config().video_frames.max(1);
+    let positions = if n_frames > 1 {
+        let duration_file = thumbnail_dir.join(format!("{path_hash}{modified}.duration"));
+        frame_positions(
+            cached_video_duration(path.as_ref(), &duration_file),
+            n_frames,
+        )
+    } else {
+        frame_positions(None, 1)
+    };
+    let frames: Vec<PathBuf> = (0..positions.len())
+        .map(|idx| match positions.len() {
+            1 => format!("{path_hash}{modified}.jpg"),
+            _ => format!("{path_hash}{modified}-{idx}.jpg"),
+        })
+        .map(|name| thumbnail_dir.join(name))
+        .collect();
+    if frames.iter().all(|frame| frame.exists()) {
+        log::debug!("using existing thumbnails of {}", path.as_ref().display());
     } else {
-        log::debug!("generating thumbnail {}", thumbnail.display());
-        let mut cmd = std::process::Command::new("ffmpeg");
-        cmd.arg("-ss")
-            .arg("00:00:10")
-            .arg("-y")
-            .arg("-i")
-            .arg(path.as_ref())
-            .arg("-vframes")
-            .arg("1")
-            .arg("-q:v")
-            .arg("2")
-            .arg("-vf")
-            .arg("scale=120:-1")
-            .arg(&thumbnail);
-        cmd.stdin(Stdio::null());
-        cmd.stdout(Stdio::piped());
-        cmd.stderr(Stdio::piped());
-        let _out = cmd.spawn()?.wait()?;
-        Ok(image_preview(
-            thumbnail,
-            mediainfo(path).unwrap_or_default(),
-        ))
+        log::debug!("generating {} thumbnails", frames.len());
+        let job = jobs::start(
+            JobKind::Preview,
+            format!("thumbnail of {}", path.as_ref().display()),
+        );
+        for (position, frame) in positions.iter().zip(frames.iter()) {
+            if frame.exists() {
+                continue;
+            }
+            let _internal = internal_operation(frame);
+            let mut cmd = std::process::Command::new("ffmpeg");
+            cmd.arg("-ss")
+                .arg(format!("{position:.2}"))
+                .arg("-y")
+                .arg("-i")
+                .arg(path.as_ref())
+                .arg("-vframes")
+                .arg("1")
+                .arg("-q:v")
+                .arg("2")
+                .arg("-vf")
+                .arg("scale=120:-1")
+                .arg(frame);
+            // The output is not read, so it must not be piped (ffmpeg would block once the pipe is full)
+            cmd.stdin(Stdio::null());
+            cmd.stdout(Stdio::null());
+            cmd.stderr(Stdio::null());
+            let mut child = cmd.spawn()?;
+            while child.try_wait()?.is_none() {
+                if job.is_cancelled() {
+                    let _ = child.kill();
+                    let _ = child.wait();
+                    // Don't leave a half-written thumbnail behind
+                    let _ = std::fs::remove_file(frame);
+                    anyhow::bail!("thumbnail generation was cancelled");
+                }
+                std::thread::sleep(Duration::from_millis(20));
+            }
+        }
+    }
+    // Positions behind the end of the video give no frame
+    let mut existing: Vec<PathBuf> = frames.iter().filter(|f| f.exists()).cloned().collect();
+    if existing.is_empty() {
+        existing = frames;
+    }
+    Ok(frames_preview(
+        &existing,
+        mediainfo(path).unwrap_or_default(),
+    ))
+}
+
+/// Duration of the video in seconds (read by `ffprobe`)
+fn video_duration(path: &Path) -> Option<f64> {
+    let output = std::process::Command::new("ffprobe")
+        .args(["-v", "error", "-show_entries", "format=duration"])
+        .args(["-of", "default=noprint_wrappers=1:nokey=1"])
+        .arg(path)
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    let duration: f64 = String::from_utf8_lossy(&output.stdout)
+        .trim()
+        .parse()
+        .ok()?;
+    (duration.is_finite() && duration > 0.0).then_some(duration)
+}
+
+/// Like [`video_duration`], but the result is kept in `file` (next to the thumbnails),
+/// so that `ffprobe` only runs the first time that the video is previewed.
+///
+/// An unknown duration is kept as an empty file.
+fn cached_video_duration(path: &Path, file: &Path) -> Option<f64> {
+    if let Ok(content) = std::fs::read_to_string(file) {
+        return content.trim().parse().ok();
+    }
+    let duration = video_duration(path);
+    let _internal = internal_operation(file);
+    let content = duration.map(|d| d.to_string()).unwrap_or_default();
+    if let Err(e) = write_atomic(file, |writer| writer.write_all(content.as_bytes())) {
+        log::debug!("cannot cache the duration of {}: {e}", path.display());
+    }
+    duration
+}
+
+/// Positions (in seconds) of the frames of a video: the middle of each of the `n` parts of the video,
+/// or just 10 seconds into it, if the duration is unknown.
+fn frame_positions(duration: Option<f64>, n: usize) -> Vec<f64> {
+    match duration {
+        Some(duration) => (0..n)
+            .map(|idx| (idx as f64 + 0.5) * duration / n as f64)
+            .collect(),
+        None => vec![10.0],
     }
 }
 
@@ -321,12 +748,113 @@ fn mediainfo(path: impl AsRef<Path>) -> io::Result<Vec<String>> {
         .and_then(|o| o.stdout.lines().take(128).collect())
 }
 
-fn bat_preview<P: AsRef<Path>>(path: P, binary: bool) -> Preview {
+/// Generates the preview of a text (or binary) file.
+///
+/// Files with null bytes are shown as a hexdump and large files are cut off,
+/// everything else is given to `bat`. Of remote files only the beginning is read.
+fn text_preview(path: &Path, binary: bool, config: &PreviewConfig) -> Preview {
+    let head = vfs::open(path).and_then(|file| {
+        let mut head = Vec::new();
+        file.take(HEAD_SIZE).read_to_end(&mut head)?;
+        Ok(head)
+    });
+    let head = match head {
+        Ok(head) => head,
+        Err(e) => {
+            return Preview::Text {
+                lines: vec![
+                    format!("Failed to open '{}'", path.display()),
+                    "".to_string(),
+                    format!("{}", e),
+                ],
+            }
+        }
+    };
+    if head[..head.len().min(SNIFF_SIZE)].contains(&0) {
+        return Preview::Text {
+            lines: hexdump(&head, config.max_lines),
+        };
+    }
+    if vfs::is_remote(path) {
+        return Preview::Text {
+            lines: String::from_utf8_lossy(&head)
+                .lines()
+                .take(config.max_lines)
+                .map(String::from)
+                .collect(),
+        };
+    }
+    let extension = path
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    let (mode, max_lines) = match config.extensions.get(&extension) {
+        Some(custom) => (custom.mode, custom.max_lines.unwrap_or(config.max_lines)),
+        None => (PreviewMode::Head, config.max_lines),
+    };
+    if mode == PreviewMode::Tail {
+        return match tail_lines(path, max_lines) {
+            Ok(lines) => Preview::Tail { lines },
+            Err(e) => Preview::Text {
+                lines: vec![format!("Failed to read '{}': {e}", path.display())],
+            },
+        };
+    }
+    let size = path.metadata().map(|m| m.len()).unwrap_or_default();
+    if size > config.max_file_size {
+        // Don't let bat (or us) read the whole file - the beginning is enough
+        let mut lines = vec![format!(
+            "\x1b[2m(showing the beginning of a {} file)\x1b[0m",
+            file_size_str(size)
+        )];
+        lines.extend(
+            String::from_utf8_lossy(&head)
+                .lines()
+                .take(max_lines.saturating_sub(1))
+                .map(String::from),
+        );
+        return Preview::Text { lines };
+    }
+    bat_preview(path, binary, max_lines)
+}
+
+/// Reads the last `n` lines of the file.
+///
+/// The file is read backwards in chunks, until enough lines are found,
+/// so that the end of a huge log file is as quick to preview as a small one.
+fn tail_lines(path: &Path, n: usize) -> io::Result<Vec<String>> {
+    let mut file = File::open(path)?;
+    let end = file.seek(SeekFrom::End(0))?;
+    let mut pos = end;
+    let mut data = Vec::new();
+    while pos > 0 && end - pos < TAIL_SIZE {
+        let len = pos.min(TAIL_CHUNK);
+        pos -= len;
+        file.seek(SeekFrom::Start(pos))?;
+        let mut chunk = vec![0; len as usize];
+        file.read_exact(&mut chunk)?;
+        chunk.append(&mut data);
+        data = chunk;
+        // One more newline than lines, because the first line may be cut off
+        if data.iter().filter(|&&byte| byte == b'\n').count() > n {
+            break;
+        }
+    }
+    let text = String::from_utf8_lossy(&data);
+    let lines: Vec<&str> = text.lines().collect();
+    let first = lines.len().saturating_sub(n);
+    Ok(lines[first..]
+        .iter()
+        .map(|line| line.replace('\r', ""))
+        .collect())
+}
+
+fn bat_preview<P: AsRef<Path>>(path: P, binary: bool, max_lines: usize) -> Preview {
     // Use bat for preview generation (if present)
     let mut cmd = std::process::Command::new("bat");
     cmd.arg("--color=always")
         .arg("--style=plain")
-        .arg("--line-range=0:128");
+        .arg(format!("--line-range=0:{max_lines}"));
 
     // If binary, use --show-all
     if binary {
@@ -337,7 +865,7 @@ fn bat_preview<P: AsRef<Path>>(path: P, binary: bool) -> Preview {
         Ok(output) => output
             .stdout
             .lines()
-            .take(128)
+            .take(max_lines)
             .flatten()
             .map(|l| l.replace(['\r', '\n'], ""))
             .collect(),
@@ -346,7 +874,7 @@ fn bat_preview<P: AsRef<Path>>(path: P, binary: bool) -> Preview {
             match File::open(&path) {
                 Ok(file) => io::BufReader::new(file)
                     .lines()
-                    .take(128)
+                    .take(max_lines)
                     .flatten()
                     .collect(),
                 Err(e) => vec![
@@ -360,6 +888,155 @@ fn bat_preview<P: AsRef<Path>>(path: P, binary: bool) -> Preview {
     Preview::Text { lines }
 }
 
+/// Formats the data like `hexdump -C`: offset, 16 bytes in hex and as ascii.
+fn hexdump(data: &[u8], max_lines: usize) -> Vec<String> {
+    data.chunks(16)
+        .take(max_lines)
+        .enumerate()
+        .map(|(idx, chunk)| {
+            let hex: Vec<String> = chunk.iter().map(|b| format!("{b:02x}")).collect();
+            let ascii: String = chunk
+                .iter()
+                .map(|&b| {
+                    if b.is_ascii_graphic() || b == b' ' {
+                        b as char
+                    } else {
+                        '.'
+                    }
+                })
+                .collect();
+            format!(
+                "\x1b[2m{:08x}\x1b[0m  {:<47}  |{ascii}|",
+                idx * 16,
+                hex.join(" ")
+            )
+        })
+        .collect()
+}
+
+/// Reads the content of `dir` into `entries` (directories first) and descends into
+/// the subdirectories until [`TREE_DEPTH`] is reached.
+///
+/// At most `max_entries` entries are listed per directory, the rest is summarized in one line.
+fn read_tree(
+    dir: &Path,
+    depth: usize,
+    parent_hidden: bool,
+    max_entries: usize,
+    entries: &mut Vec<TreeEntry>,
+) {
+    let Ok(read_dir) = std::fs::read_dir(dir) else {
+        return;
+    };
+    let mut children: Vec<(bool, String, PathBuf)> = read_dir
+        .flatten()
+        .map(|entry| {
+            let path = entry.path();
+            (
+                !path.is_dir(),
+                entry.file_name().to_string_lossy().to_lowercase(),
+                path,
+            )
+        })
+        .collect();
+    children.sort();
+    let n_children = children.len();
+    for (is_file, _, path) in children.into_iter().take(max_entries) {
+        let elem = DirElem::from(&path);
+        let is_hidden = parent_hidden || elem.is_hidden();
+        entries.push(TreeEntry {
+            depth,
+            name: elem.name().to_string(),
+            is_dir: !is_file,
+            is_hidden,
+        });
+        if !is_file && depth < TREE_DEPTH {
+            read_tree(&path, depth + 1, is_hidden, max_entries, entries);
+        }
+    }
+    if n_children > max_entries {
+        entries.push(TreeEntry {
+            depth,
+            name: format!("… {} more", n_children - max_entries),
+            is_dir: false,
+            is_hidden: parent_hidden,
+        });
+    }
+}
+
+/// Formats the tree preview like `tree` does.
+fn tree_lines(entries: &[TreeEntry], show_hidden: bool, dir_color: style::Color) -> Vec<String> {
+    let visible: Vec<&TreeEntry> = entries
+        .iter()
+        .filter(|entry| show_hidden || !entry.is_hidden)
+        .collect();
+    // For every level: true if the last entry on that level was the last one of its directory
+    let mut last_on_level = [false; TREE_DEPTH + 1];
+    let mut lines = Vec::with_capacity(visible.len());
+    for (idx, entry) in visible.iter().enumerate() {
+        let is_last = visible[idx + 1..]
+            .iter()
+            .find(|next| next.depth <= entry.depth)
+            .is_none_or(|next| next.depth < entry.depth);
+        last_on_level[entry.depth] = is_last;
+        let mut prefix: String = last_on_level[1..entry.depth]
+            .iter()
+            .map(|&last| if last { "    " } else { "│   " })
+            .collect();
+        prefix.push_str(if is_last { "└── " } else { "├── " });
+        let name = if entry.is_dir {
+            format!("{}/", entry.name).with(dir_color).bold()
+        } else {
+            entry.name.clone().grey()
+        };
+        lines.push(format!("{}{name}", prefix.dark_grey()));
+    }
+    lines
+}
+
+fn text_around_line(path: &Path, line: usize) -> Preview {
+    let first = line.saturating_sub(LINE_CONTEXT).max(1);
+    let max_lines = preview_config().max_lines;
+    let output = std::process::Command::new("bat")
+        .arg("--color=always")
+        .arg("--style=numbers")
+        .arg(format!("--line-range={first}:{}", first + max_lines - 1))
+        .arg(format!("--highlight-line={line}"))
+        .arg(path)
+        .output();
+    let lines = match output {
+        Ok(output) if output.status.success() => output
+            .stdout
+            .lines()
+            .map_while(io::Result::ok)
+            .map(|l| l.replace(['\r', '\n'], ""))
+            .collect(),
+        _ => match File::open(path) {
+            // Without bat, we print the line numbers ourselves and highlight the line in reverse video
+            Ok(file) => io::BufReader::new(file)
+                .lines()
+                .map_while(io::Result::ok)
+                .enumerate()
+                .skip(first - 1)
+                .take(max_lines)
+                .map(|(idx, l)| {
+                    if idx + 1 == line {
+                        format!("\x1b[7m{:>4} {l}\x1b[0m", idx + 1)
+                    } else {
+                        format!("{:>4} {l}", idx + 1)
+                    }
+                })
+                .collect(),
+            Err(e) => vec![
+                format!("Failed to open '{}'", path.display()),
+                "".to_string(),
+                format!("{}", e),
+            ],
+        },
+    };
+    Preview::Text { lines }
+}
+
 fn cmd_to_preview(cmd_name: &'static str, result: std::io::Result<Vec<String>>) -> Preview {
     let lines = match result {
         Ok(l) => l,
@@ -402,9 +1079,43 @@ impl PanelContent for FilePreview {
         self.modified
     }
 
-    fn update_content(&mut self, content: Self) {
+    fn update_content(&mut self, mut content: Self) {
+        // Keep the zoom level, if the same image is reloaded
+        if self.path == content.path {
+            if let (Some(view), Some(new_view)) =
+                (self.image_view_mut().copied(), content.image_view_mut())
+            {
+                *new_view = view;
+            }
+        }
         *self = content
     }
+
+    fn mem_size(&self) -> usize {
+        let content = match &self.preview {
+            Preview::Image {
+                img,
+                header,
+                info,
+                frames,
+                ..
+            } => {
+                img.iter()
+                    .chain(frames.iter())
+                    .map(|img| img.as_bytes().len())
+                    .sum::<usize>()
+                    + header.iter().chain(info).map(String::len).sum::<usize>()
+            }
+            Preview::Text { lines } | Preview::Tail { lines } => {
+                lines.iter().map(String::len).sum()
+            }
+            Preview::Tree { entries, .. } => entries
+                .iter()
+                .map(|entry| std::mem::size_of::<TreeEntry>() + entry.name.len())
+                .sum(),
+        };
+        std::mem::size_of::<Self>() + self.path.as_os_str().len() + content
+    }
 }
 #[derive(Debug, Clone)]
 pub enum PreviewPanel {
@@ -444,6 +1155,186 @@ impl Draw for PreviewPanel {
     }
 }
 
+/// Overview of the marked items, that is shown instead of the preview while more than one item is marked
+#[derive(Debug)]
+pub struct MarkedSummary {
+    paths: Vec<PathBuf>,
+    /// Counts and size of the items, once they are read
+    stats: Option<MarkedStats>,
+    /// Reads the stats in the background, so that marking items does not block the drawing
+    pending: Option<oneshot::Receiver<MarkedStats>>,
+}
+
+#[derive(Debug)]
+struct MarkedStats {
+    dirs: usize,
+    files: usize,
+    links: usize,
+    /// Number of files per (lowercase) extension, the most common one first
+    extensions: Vec<(String, usize)>,
+    /// Total size of the items, including the content of directories
+    size: u64,
+    /// False if the directories have too many entries to count all of them
+    complete: bool,
+}
+
+impl MarkedSummary {
+    pub fn new(paths: Vec<PathBuf>) -> Self {
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            let stats = MarkedStats::read(&paths);
+            return MarkedSummary {
+                paths,
+                stats: Some(stats),
+                pending: None,
+            };
+        };
+        let (tx, rx) = oneshot::channel();
+        let marked = paths.clone();
+        runtime.spawn_blocking(move || {
+            let _ = tx.send(MarkedStats::read(&marked));
+        });
+        MarkedSummary {
+            paths,
+            stats: None,
+            pending: Some(rx),
+        }
+    }
+
+    pub fn paths(&self) -> &[PathBuf] {
+        &self.paths
+    }
+
+    /// Takes the stats that were read in the background, returns true if they have arrived.
+    pub fn poll(&mut self) -> bool {
+        let Some(rx) = &mut self.pending else {
+            return false;
+        };
+        match rx.try_recv() {
+            Ok(stats) => self.stats = Some(stats),
+            Err(TryRecvError::Empty) => return false,
+            Err(TryRecvError::Closed) => (),
+        }
+        self.pending = None;
+        true
+    }
+
+    /// Lines above the list of items, e.g. "2 dirs, 5 files, 0 links" and "3 .png, 2 .txt"
+    fn summary(&self) -> Vec<String> {
+        let mut lines = vec![format!("{} items marked", self.paths.len())];
+        let Some(stats) = &self.stats else {
+            lines.push("…".to_string());
+            return lines;
+        };
+        let more = if stats.complete { "" } else { "+" };
+        lines.push(format!(
+            "{} dirs, {} files, {} links",
+            stats.dirs, stats.files, stats.links
+        ));
+        lines.push(format!("{}{more} in total", file_size_str(stats.size)));
+        if !stats.extensions.is_empty() {
+            let extensions: Vec<String> = stats
+                .extensions
+                .iter()
+                .map(|(ext, n)| match ext.as_str() {
+                    "" => format!("{n} without extension"),
+                    ext => format!("{n} .{ext}"),
+                })
+                .collect();
+            lines.push(extensions.join(", "));
+        }
+        lines
+    }
+}
+
+impl MarkedStats {
+    fn read(paths: &[PathBuf]) -> Self {
+        let (mut dirs, mut files, mut links) = (0, 0, 0);
+        let mut extensions: HashMap<String, usize> = HashMap::new();
+        for path in paths.iter() {
+            match path.symlink_metadata() {
+                Ok(metadata) if metadata.is_symlink() => links += 1,
+                Ok(metadata) if metadata.is_dir() => dirs += 1,
+                Ok(_) => {
+                    files += 1;
+                    let extension = path
+                        .extension()
+                        .map(|ext| ext.to_string_lossy().to_lowercase())
+                        .unwrap_or_default();
+                    *extensions.entry(extension).or_default() += 1;
+                }
+                Err(_) => (),
+            }
+        }
+        let mut extensions: Vec<(String, usize)> = extensions.into_iter().collect();
+        extensions.sort_by(|(a, n), (b, m)| m.cmp(n).then_with(|| a.cmp(b)));
+        let (size, complete) = total_size(paths, MARKED_SIZE_LIMIT);
+        MarkedStats {
+            dirs,
+            files,
+            links,
+            extensions,
+            size,
+            complete,
+        }
+    }
+}
+
+impl Draw for MarkedSummary {
+    fn draw(
+        &mut self,
+        stdout: &mut Stdout,
+        x_range: Range<u16>,
+        y_range: Range<u16>,
+    ) -> Result<()> {
+        let width = x_range.end.saturating_sub(x_range.start + 1) as usize;
+        let summary = self.summary();
+        let n_summary = summary.len();
+        let rows = y_range.len().saturating_sub(n_summary + 1);
+        let mut names: Vec<String> = self
+            .paths
+            .iter()
+            .map(|path| {
+                format!(
+                    " {}",
+                    path.file_name().unwrap_or_default().to_string_lossy()
+                )
+            })
+            .collect();
+        if names.len() > rows {
+            let hidden = names.len() - rows.saturating_sub(1);
+            names.truncate(rows.saturating_sub(1));
+            names.push(format!(" ... and {hidden} more"));
+        }
+        let mut lines = summary.into_iter().enumerate().map(|(idx, line)| {
+            let line = format!(" {line}").exact_width(width);
+            match idx {
+                0 => line.with(color_main()).bold(),
+                _ => line.dark_grey(),
+            }
+        });
+        let mut names = names
+            .into_iter()
+            .map(|name| name.exact_width(width).stylize());
+        for (n, y) in y_range.enumerate() {
+            let line = match lines.next() {
+                Some(line) => line,
+                // Empty line between the summary and the items
+                None if n == n_summary => "".exact_width(width).stylize(),
+                None => names
+                    .next()
+                    .unwrap_or_else(|| "".exact_width(width).stylize()),
+            };
+            queue!(
+                stdout,
+                cursor::MoveTo(x_range.start, y),
+                print_vertical_bar(),
+                PrintStyledContent(line),
+            )?;
+        }
+        Ok(())
+    }
+}
+
 impl PanelContent for PreviewPanel {
     fn path(&self) -> &Path {
         match self {
@@ -472,6 +1363,22 @@ impl PanelContent for PreviewPanel {
         }
         *self = content;
     }
+
+    fn mem_size(&self) -> usize {
+        match self {
+            PreviewPanel::Dir(panel) => panel.mem_size(),
+            PreviewPanel::File(preview) => preview.mem_size(),
+            PreviewPanel::Empty => std::mem::size_of::<Self>(),
+        }
+    }
+
+    fn is_complete(&self) -> bool {
+        // Directories that were cached by the directory manager have no statistics yet
+        match self {
+            PreviewPanel::Dir(panel) => panel.stats().is_some(),
+            _ => true,
+        }
+    }
 }
 
 impl BasePanel for PreviewPanel {
@@ -483,10 +1390,18 @@ impl BasePanel for PreviewPanel {
         PreviewPanel::Dir(DirPanel::loading(path))
     }
 
+    fn is_loading(&self) -> bool {
+        matches!(self, PreviewPanel::Dir(panel) if panel.is_loading())
+    }
+
     fn from_path(path: PathBuf) -> Self {
-        if path.is_dir() {
+        if !previews_enabled() {
+            PreviewPanel::Empty
+        } else if vfs::is_dir(&path) && tree_preview() && !vfs::is_remote(&path) {
+            PreviewPanel::File(FilePreview::tree(path))
+        } else if vfs::is_dir(&path) {
             PreviewPanel::Dir(DirPanel::from_path(path))
-        } else if path.is_file() {
+        } else if path.is_file() || vfs::metadata(&path).is_ok() {
             PreviewPanel::File(FilePreview::new(path))
         } else {
             PreviewPanel::Empty
@@ -503,6 +1418,15 @@ impl PreviewPanel {
         }
     }
 
+    /// Shows or hides hidden files in directory previews.
+    pub fn set_hidden(&mut self, show_hidden: bool) {
+        match self {
+            PreviewPanel::Dir(panel) => panel.set_hidden(show_hidden),
+            PreviewPanel::File(preview) => preview.set_hidden(show_hidden),
+            PreviewPanel::Empty => (),
+        }
+    }
+
     pub fn select_path(&mut self, selection: &Path) {
         if let PreviewPanel::Dir(panel) = self {
             log::debug!("preview-panel: selecting {}", selection.display());
@@ -510,3 +1434,213 @@ impl PreviewPanel {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn video_frames() {
+        assert_eq!(frame_positions(Some(100.0), 4), [12.5, 37.5, 62.5, 87.5]);
+        assert_eq!(frame_positions(None, 10), [10.0]);
+
+        // Cached durations are used without asking ffprobe
+        let dir = tempfile::tempdir().unwrap();
+        let cached = dir.path().join("video.duration");
+        std::fs::write(&cached, "42.5").unwrap();
+        assert_eq!(
+            cached_video_duration(Path::new("missing.mp4"), &cached),
+            Some(42.5)
+        );
+        std::fs::write(&cached, "").unwrap();
+        assert_eq!(
+            cached_video_duration(Path::new("missing.mp4"), &cached),
+            None
+        );
+
+        let frame = |width| DynamicImage::new_rgb8(width, 1);
+        let mut preview = FilePreview {
+            path: PathBuf::from("video.mp4"),
+            modified: UNIX_EPOCH,
+            preview: Preview::Image {
+                img: Some(frame(1)),
+                header: Vec::new(),
+                info: Vec::new(),
+                view: ImageView::default(),
+                frames: vec![frame(2), frame(3)],
+            },
+        };
+        let width = |preview: &FilePreview| match &preview.preview {
+            Preview::Image { img: Some(img), .. } => img.width(),
+            _ => 0,
+        };
+        let widths: Vec<u32> = (0..4)
+            .map(|_| {
+                assert!(preview.next_frame());
+                width(&preview)
+            })
+            .collect();
+        assert_eq!(widths, [2, 3, 1, 2]);
+    }
+
+    #[test]
+    fn summary_of_marked_items() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = |name: &str| dir.path().join(name);
+        std::fs::create_dir(path("dir")).unwrap();
+        std::fs::write(path("dir/inner"), "1234").unwrap();
+        for name in ["a.txt", "b.TXT", "c.png", "Makefile"] {
+            std::fs::write(path(name), "12").unwrap();
+        }
+        std::os::unix::fs::symlink(path("a.txt"), path("link")).unwrap();
+        let names = ["dir", "a.txt", "b.TXT", "c.png", "Makefile", "link"];
+        let summary = MarkedSummary::new(names.map(path).to_vec());
+        let stats = summary.stats.as_ref().unwrap();
+        assert_eq!((stats.dirs, stats.files, stats.links), (1, 4, 1));
+        assert_eq!(stats.size, 12);
+        assert_eq!(summary.summary()[3], "2 .txt, 1 without extension, 1 .png");
+    }
+
+    #[test]
+    fn image_view_zoom_and_pan() {
+        let img = DynamicImage::new_rgb8(200, 100);
+        let mut view = ImageView::default();
+        assert_eq!(view.render(&img, 100, 100, 1.0).width(), 100);
+
+        // 1:1 shows the upper left 100x100 pixels after panning to the left border
+        view.cycle_fit();
+        view.cycle_fit();
+        view.cycle_fit();
+        assert_eq!(view.fit, ImageFit::Original);
+        let rendered = view.render(&img, 100, 100, 1.0);
+        assert_eq!((rendered.width(), rendered.height()), (100, 100));
+        assert_eq!(view.visible, (0.5, 1.0));
+        for _ in 0..10 {
+            view.pan(-1.0, 0.0);
+        }
+        view.render(&img, 100, 100, 1.0);
+        assert_eq!(view.center, (0.25, 0.5));
+
+        // Zooming out shows the whole image, centered
+        view.zoom(0.25);
+        let rendered = view.render(&img, 100, 100, 1.0);
+        assert_eq!((rendered.width(), rendered.height()), (50, 25));
+        assert_eq!(view.center, (0.5, 0.5));
+    }
+
+    #[test]
+    fn image_aspect_ratio() {
+        let img = DynamicImage::new_rgb8(200, 100);
+        let mut view = ImageView::default();
+        // Square pixels: limited by the width
+        let rendered = view.render(&img, 100, 100, 1.0);
+        assert_eq!((rendered.width(), rendered.height()), (100, 50));
+        // Pixels are twice as high as wide, so only half as many rows are needed
+        let rendered = view.render(&img, 100, 100, 2.0);
+        assert_eq!((rendered.width(), rendered.height()), (100, 25));
+        // Wide pixels: limited by the height
+        let rendered = view.render(&img, 100, 20, 0.5);
+        assert_eq!((rendered.width(), rendered.height()), (20, 20));
+
+        view.cycle_fit();
+        view.cycle_fit();
+        assert_eq!(view.fit, ImageFit::Height);
+        let rendered = view.render(&img, 100, 50, 2.0);
+        assert_eq!((rendered.width(), rendered.height()), (100, 50));
+        assert_eq!(view.visible, (0.5, 1.0));
+    }
+
+    #[test]
+    fn binary_and_large_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = PreviewConfig {
+            max_file_size: 100,
+            max_lines: 4,
+            ..Default::default()
+        };
+        let lines = |preview| match preview {
+            Preview::Text { lines } => lines,
+            _ => panic!("expected a text preview"),
+        };
+
+        let binary = dir.path().join("binary.txt");
+        std::fs::write(
+            &binary,
+            b"\x7fELF\0\x01 binary data, that is long enough for two lines",
+        )
+        .unwrap();
+        let preview = lines(text_preview(&binary, false, &config));
+        assert_eq!(preview.len(), 4);
+        assert!(preview[0]
+            .ends_with("7f 45 4c 46 00 01 20 62 69 6e 61 72 79 20 64 61  |.ELF.. binary da|"));
+        assert!(preview[1].contains("00000010"));
+
+        let large = dir.path().join("large.txt");
+        std::fs::write(&large, "line\n".repeat(100)).unwrap();
+        let preview = lines(text_preview(&large, false, &config));
+        assert_eq!(preview.len(), 4);
+        assert!(preview[0].contains("500 B"));
+        assert_eq!(preview[3], "line");
+    }
+
+    #[test]
+    fn tail_of_log_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let log = dir.path().join("server.LOG");
+        let content: String = (0..20_000).map(|idx| format!("line {idx}\r\n")).collect();
+        std::fs::write(&log, content).unwrap();
+        let mut config = PreviewConfig::default();
+        config.extensions.insert(
+            "log".to_string(),
+            crate::config::ExtensionPreview {
+                mode: PreviewMode::Tail,
+                max_lines: Some(3),
+            },
+        );
+        match text_preview(&log, false, &config) {
+            Preview::Tail { lines } => {
+                assert_eq!(lines, ["line 19997", "line 19998", "line 19999"])
+            }
+            _ => panic!("expected the end of the file"),
+        }
+        // Everything is shown, if the file has fewer lines
+        std::fs::write(&log, "only line").unwrap();
+        assert_eq!(tail_lines(&log, 3).unwrap(), ["only line"]);
+        std::fs::write(&log, "").unwrap();
+        assert!(tail_lines(&log, 3).unwrap().is_empty());
+    }
+
+    #[test]
+    fn directory_tree() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("src/deep/deeper")).unwrap();
+        std::fs::create_dir(dir.path().join(".git")).unwrap();
+        for file in ["README.md", "src/a.rs", "src/b.rs", "src/c.rs", ".git/HEAD"] {
+            std::fs::write(dir.path().join(file), "").unwrap();
+        }
+        let mut entries = Vec::new();
+        read_tree(dir.path(), 1, false, 3, &mut entries);
+        let names: Vec<(usize, &str)> =
+            entries.iter().map(|e| (e.depth, e.name.as_str())).collect();
+        assert_eq!(
+            names,
+            [
+                (1, ".git"),
+                (2, "HEAD"),
+                (1, "src"),
+                (2, "deep"),
+                (2, "a.rs"),
+                (2, "b.rs"),
+                (2, "… 1 more"),
+                (1, "README.md"),
+            ]
+        );
+        assert!(entries[1].is_hidden);
+        assert_eq!(tree_lines(&entries, true, style::Color::Blue).len(), 8);
+        let lines = tree_lines(&entries, false, style::Color::Blue);
+        assert_eq!(lines.len(), 6);
+        assert!(lines[0].contains("├── ") && lines[0].contains("src/"));
+        assert!(lines[4].contains("│   └── "));
+        assert!(lines[5].contains("└── ") && lines[5].contains("README.md"));
+    }
+}
@@ -0,0 +1,231 @@
+//! Templates for the content of the header, the footer and the terminal title.
+//!
+//! A template is a plain string with placeholders like `{user}@{host} {path}`.
+//! Literal braces are written as `{{` and `}}`.
+use std::str::FromStr;
+
+use anyhow::{anyhow, Result};
+use crossterm::style::StyledContent;
+use log::warn;
+use once_cell::sync::OnceCell;
+
+use crate::config::StatusLineConfig;
+
+static STATUS_LINE: OnceCell<StatusLine> = OnceCell::new();
+
+const DEFAULT_TITLE: &str = "rfm: {short_dir}";
+
+/// Values that can be inserted into a template
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Field {
+    /// Name of the current user
+    User,
+    /// Hostname
+    Host,
+    /// Absolute path of the selected item
+    Path,
+    /// Current directory
+    Dir,
+    /// Current directory, with the home directory shortened to `~`
+    ShortDir,
+    /// Name of the selected item
+    Name,
+    Perm,
+    Owner,
+    Group,
+    Size,
+    Modified,
+    Mime,
+    /// Index of the selected item
+    Index,
+    /// Number of items in the current directory
+    Total,
+    /// Git branch of the current directory
+    GitBranch,
+    /// Free and total space of the filesystem of the current directory
+    Disk,
+}
+
+impl FromStr for Field {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let field = match s {
+            "user" => Field::User,
+            "host" => Field::Host,
+            "path" => Field::Path,
+            "dir" => Field::Dir,
+            "short_dir" => Field::ShortDir,
+            "name" => Field::Name,
+            "perm" => Field::Perm,
+            "owner" => Field::Owner,
+            "group" => Field::Group,
+            "size" => Field::Size,
+            "modified" => Field::Modified,
+            "mime" => Field::Mime,
+            "index" => Field::Index,
+            "total" => Field::Total,
+            "git_branch" => Field::GitBranch,
+            "disk" => Field::Disk,
+            other => return Err(anyhow!("unknown placeholder '{{{other}}}'")),
+        };
+        Ok(field)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Part {
+    Text(String),
+    Field(Field),
+}
+
+/// Parsed template string
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Template {
+    parts: Vec<Part>,
+}
+
+impl FromStr for Template {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut parts = Vec::new();
+        let mut text = String::new();
+        let mut chars = s.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    text.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    text.push('}');
+                }
+                '{' => {
+                    let mut name = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => name.push(c),
+                            None => return Err(anyhow!("unclosed placeholder '{{{name}'")),
+                        }
+                    }
+                    if !text.is_empty() {
+                        parts.push(Part::Text(std::mem::take(&mut text)));
+                    }
+                    parts.push(Part::Field(name.trim().parse()?));
+                }
+                '}' => return Err(anyhow!("unmatched '}}' (use '}}}}' for a literal brace)")),
+                c => text.push(c),
+            }
+        }
+        if !text.is_empty() {
+            parts.push(Part::Text(text));
+        }
+        Ok(Template { parts })
+    }
+}
+
+impl Template {
+    /// Returns true if the template contains the given field.
+    pub fn contains(&self, field: Field) -> bool {
+        self.parts.contains(&Part::Field(field))
+    }
+
+    /// Replaces all placeholders with the output of `value`.
+    pub fn render<F>(&self, mut value: F) -> Vec<StyledContent<String>>
+    where
+        F: FnMut(Field) -> Vec<StyledContent<String>>,
+    {
+        let mut output = Vec::new();
+        for part in self.parts.iter() {
+            match part {
+                Part::Text(text) => {
+                    output.push(StyledContent::new(Default::default(), text.clone()))
+                }
+                Part::Field(field) => output.extend(value(*field)),
+            }
+        }
+        output
+    }
+}
+
+/// Custom templates for the header and footer.
+///
+/// If a template is `None`, the built-in layout is used.
+#[derive(Debug, Default)]
+pub struct StatusLine {
+    pub header: Option<Template>,
+    /// Left side of the footer
+    pub footer: Option<Template>,
+    /// Right side of the footer
+    pub footer_right: Option<Template>,
+    /// Title of the terminal (`None` if rfm does not set it)
+    pub title: Option<Template>,
+}
+
+impl StatusLine {
+    pub fn contains(&self, field: Field) -> bool {
+        [&self.header, &self.footer, &self.footer_right, &self.title]
+            .into_iter()
+            .flatten()
+            .any(|t| t.contains(field))
+    }
+}
+
+fn parse_template(name: &str, template: Option<String>) -> Option<Template> {
+    match template?.parse() {
+        Ok(template) => Some(template),
+        Err(e) => {
+            warn!("Invalid {name} template: {e}. Using default {name}");
+            None
+        }
+    }
+}
+
+/// Parses the templates from the config. Must be called before the first draw.
+pub fn set_status_line(config: StatusLineConfig) {
+    let status_line = StatusLine {
+        header: parse_template("header", config.header),
+        footer: parse_template("footer", config.footer),
+        footer_right: parse_template("footer_right", config.footer_right),
+        title: match config.set_title {
+            Some(false) => None,
+            _ => parse_template("title", config.title).or_else(|| DEFAULT_TITLE.parse().ok()),
+        },
+    };
+    if STATUS_LINE.set(status_line).is_err() {
+        warn!("status line templates are already set");
+    }
+}
+
+pub fn status_line() -> &'static StatusLine {
+    STATUS_LINE.get_or_init(StatusLine::default)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_templates() {
+        let template: Template = "{user}@{host} {{{path}}}".parse().unwrap();
+        assert!(template.contains(Field::Path));
+        assert!(!template.contains(Field::GitBranch));
+        let rendered: String = template
+            .render(|field| vec![StyledContent::new(Default::default(), format!("{field:?}"))])
+            .into_iter()
+            .map(|s| s.content().clone())
+            .collect();
+        assert_eq!(rendered, "User@Host {Path}");
+
+        assert!(DEFAULT_TITLE
+            .parse::<Template>()
+            .unwrap()
+            .contains(Field::ShortDir));
+        assert!("{unknown}".parse::<Template>().is_err());
+        assert!("a } b".parse::<Template>().is_err());
+        assert!("{path".parse::<Template>().is_err());
+    }
+}
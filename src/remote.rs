@@ -0,0 +1,328 @@
+//! Browsing of remote hosts.
+//!
+//! Remote directories are read via SFTP (see [`SftpBackend`]) and mounted into the [`vfs`],
+//! so that panels and previews work on them like on local directories.
+//! Remote hosts can only be browsed: their files can be copied to local directories
+//! and opened (from a local copy), but nothing on the host is changed.
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    io::{self, Read},
+    net::{TcpStream, ToSocketAddrs},
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{anyhow, bail, Context, Result};
+use log::info;
+use parking_lot::Mutex;
+use ssh2::{CheckResult, KnownHostFileKind, Session, Sftp};
+
+use crate::vfs::{self, Entries, Metadata, VfsBackend};
+
+/// Port of the SSH server
+const SSH_PORT: u16 = 22;
+
+/// Timeout for connecting and for every request to the host
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Private keys in `~/.ssh`, that are tried if the `ssh-agent` cannot authenticate us
+const KEY_FILES: [&str; 3] = ["id_ed25519", "id_ecdsa", "id_rsa"];
+
+/// Location on a remote host in the form `[user@]host:[path]`.
+///
+/// An empty path refers to the home directory of the user.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RemoteLocation {
+    pub user: Option<String>,
+    pub host: String,
+    pub path: String,
+}
+
+impl FromStr for RemoteLocation {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (login, path) = s
+            .trim()
+            .split_once(':')
+            .ok_or_else(|| anyhow!("expected [user@]host:[path]"))?;
+        let (user, host) = match login.split_once('@') {
+            Some((user, host)) => (Some(user.to_string()), host),
+            None => (None, login),
+        };
+        if host.is_empty() || user.as_deref() == Some("") {
+            return Err(anyhow!("expected [user@]host:[path]"));
+        }
+        // Would be read as an option, if the location is passed on to ssh or scp
+        if host.starts_with('-') || user.as_deref().is_some_and(|u| u.starts_with('-')) {
+            return Err(anyhow!("invalid host {host}"));
+        }
+        Ok(RemoteLocation {
+            user,
+            host: host.to_string(),
+            path: path.to_string(),
+        })
+    }
+}
+
+impl Display for RemoteLocation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(user) = &self.user {
+            write!(f, "{user}@")?;
+        }
+        write!(f, "{}:{}", self.host, self.path)
+    }
+}
+
+impl RemoteLocation {
+    /// Local directory that the location is mounted to
+    fn mountpoint(&self) -> PathBuf {
+        let base = std::env::var_os("XDG_RUNTIME_DIR")
+            .map(PathBuf::from)
+            .unwrap_or_else(std::env::temp_dir);
+        base.join("rfm-remote")
+            .join(self.to_string().replace('/', "_"))
+    }
+}
+
+/// Connection to a remote host, that reads the directories below `root` via SFTP.
+pub struct SftpBackend {
+    /// Local directory that the remote directory is mounted to
+    root: PathBuf,
+    /// Remote directory
+    base: PathBuf,
+    sftp: Sftp,
+    /// Metadata of the entries that were listed last, so that sorting and drawing them
+    /// does not need one request per entry
+    attributes: Mutex<HashMap<PathBuf, Metadata>>,
+    /// Keeps the connection alive
+    _session: Session,
+}
+
+impl SftpBackend {
+    fn remote_path(&self, path: &Path) -> PathBuf {
+        match path.strip_prefix(&self.root) {
+            Ok(relative) if relative.as_os_str().is_empty() => self.base.clone(),
+            Ok(relative) => self.base.join(relative),
+            Err(_) => path.to_path_buf(),
+        }
+    }
+
+    fn stat(&self, path: &Path) -> io::Result<Metadata> {
+        let stat = self.sftp.stat(&self.remote_path(path))?;
+        Ok(Metadata {
+            is_dir: stat.is_dir(),
+            len: stat.size.unwrap_or_default(),
+            mode: stat.perm.unwrap_or_default(),
+            modified: stat
+                .mtime
+                .map(|secs| UNIX_EPOCH + Duration::from_secs(secs)),
+        })
+    }
+}
+
+impl VfsBackend for SftpBackend {
+    fn read_dir(&self, path: &Path) -> io::Result<Entries> {
+        let entries = self.sftp.readdir(self.remote_path(path))?;
+        let mut attributes = self.attributes.lock();
+        attributes.retain(|cached, _| cached.parent() != Some(path));
+        let mut paths = Vec::with_capacity(entries.len());
+        for (remote, stat) in entries {
+            let Some(name) = remote.file_name() else {
+                continue;
+            };
+            let local = path.join(name);
+            // Symlinks are resolved later (with one request each)
+            if !stat.file_type().is_symlink() {
+                let metadata = Metadata {
+                    is_dir: stat.is_dir(),
+                    len: stat.size.unwrap_or_default(),
+                    mode: stat.perm.unwrap_or_default(),
+                    modified: stat
+                        .mtime
+                        .map(|secs| UNIX_EPOCH + Duration::from_secs(secs)),
+                };
+                attributes.insert(local.clone(), metadata);
+            }
+            paths.push(Ok(local));
+        }
+        Ok(Box::new(paths.into_iter()))
+    }
+
+    fn metadata(&self, path: &Path) -> io::Result<Metadata> {
+        if let Some(metadata) = self.attributes.lock().get(path) {
+            return Ok(*metadata);
+        }
+        let metadata = self.stat(path)?;
+        self.attributes.lock().insert(path.to_path_buf(), metadata);
+        Ok(metadata)
+    }
+
+    fn open(&self, path: &Path) -> io::Result<Box<dyn Read + Send>> {
+        Ok(Box::new(self.sftp.open(self.remote_path(path))?))
+    }
+
+    fn copy(&self, from: &Path, to: &Path) -> io::Result<u64> {
+        let mut file = self.sftp.open(self.remote_path(from))?;
+        io::copy(&mut file, &mut std::fs::File::create(to)?)
+    }
+}
+
+/// Connects to the remote location and mounts it, returns the local mountpoint.
+///
+/// Only key based authentication is supported (via `ssh-agent` or an unencrypted key in `~/.ssh`),
+/// because we cannot prompt for a password while the terminal is in raw mode.
+/// The host must be listed in `~/.ssh/known_hosts`.
+///
+/// This is a blocking operation, so it should be run inside of `spawn_blocking`.
+pub fn mount(location: &RemoteLocation) -> Result<PathBuf> {
+    let root = location.mountpoint();
+    if vfs::is_mounted(&root) {
+        return Ok(root);
+    }
+    let session = connect(location)?;
+    let sftp = session.sftp().context("cannot start sftp")?;
+    let base = sftp
+        .realpath(Path::new(if location.path.is_empty() {
+            "."
+        } else {
+            &location.path
+        }))
+        .context(format!("cannot find {}", location.path))?;
+    if !sftp.stat(&base)?.is_dir() {
+        bail!("{} is not a directory", base.display());
+    }
+    // The panel of the parent directory shows the mountpoint like a local directory
+    std::fs::create_dir_all(&root).context(format!("failed to create {}", root.display()))?;
+    info!("connected to {location}, mounted to {}", root.display());
+    let backend = SftpBackend {
+        root: root.clone(),
+        base,
+        sftp,
+        attributes: Mutex::new(HashMap::new()),
+        _session: session,
+    };
+    vfs::mount(root.clone(), Arc::new(backend));
+    Ok(root)
+}
+
+/// Opens an authenticated session with the host.
+fn connect(location: &RemoteLocation) -> Result<Session> {
+    let address = (location.host.as_str(), SSH_PORT)
+        .to_socket_addrs()
+        .context(format!("cannot resolve {}", location.host))?
+        .next()
+        .ok_or_else(|| anyhow!("cannot resolve {}", location.host))?;
+    let tcp = TcpStream::connect_timeout(&address, TIMEOUT)
+        .context(format!("cannot connect to {}", location.host))?;
+    let mut session = Session::new()?;
+    session.set_tcp_stream(tcp);
+    session.set_timeout(TIMEOUT.as_millis() as u32);
+    session.handshake().context("ssh handshake failed")?;
+    verify_host_key(&session, &location.host)?;
+
+    let user = location.user.clone().unwrap_or_else(whoami::username);
+    if session.userauth_agent(&user).is_err() {
+        let ssh_dir = home_dir().join(".ssh");
+        for key in KEY_FILES.iter().map(|name| ssh_dir.join(name)) {
+            if key.exists()
+                && session
+                    .userauth_pubkey_file(&user, None, &key, None)
+                    .is_ok()
+            {
+                break;
+            }
+        }
+    }
+    if !session.authenticated() {
+        bail!("authentication as {user} failed (only keys of the ssh-agent or unencrypted keys in ~/.ssh are supported)");
+    }
+    Ok(session)
+}
+
+/// Refuses hosts, whose key is not in `~/.ssh/known_hosts` (like `ssh -o BatchMode=yes` does).
+fn verify_host_key(session: &Session, host: &str) -> Result<()> {
+    let (key, _) = session
+        .host_key()
+        .ok_or_else(|| anyhow!("{host} sent no host key"))?;
+    let mut known_hosts = session.known_hosts()?;
+    let file = home_dir().join(".ssh").join("known_hosts");
+    if file.exists() {
+        known_hosts
+            .read_file(&file, KnownHostFileKind::OpenSSH)
+            .context(format!("cannot read {}", file.display()))?;
+    }
+    match known_hosts.check_port(host, SSH_PORT, key) {
+        CheckResult::Match => Ok(()),
+        CheckResult::NotFound => {
+            bail!("{host} is not a known host, connect once with ssh to verify its key")
+        }
+        CheckResult::Mismatch => {
+            bail!("the host key of {host} does not match the one in known_hosts")
+        }
+        CheckResult::Failure => bail!("cannot check the host key of {host}"),
+    }
+}
+
+fn home_dir() -> PathBuf {
+    std::env::var_os("HOME")
+        .map(PathBuf::from)
+        .unwrap_or_default()
+}
+
+/// Local directory for the files that are opened from remote hosts
+fn downloads() -> PathBuf {
+    std::env::temp_dir().join(format!("rfm-remote-{}", std::process::id()))
+}
+
+/// Copies a remote file into a local temporary directory, so that it can be opened by applications.
+///
+/// Changes of the copy are not written back to the host.
+pub fn fetch(path: &Path) -> Result<PathBuf> {
+    let name = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|t| t.as_nanos())
+        .unwrap_or_default();
+    let dir = downloads().join(name.to_string());
+    std::fs::create_dir_all(&dir)?;
+    let copy = dir.join(path.file_name().unwrap_or_default());
+    vfs::download(path, &copy).context(format!("cannot download {}", path.display()))?;
+    Ok(copy)
+}
+
+/// Disconnects from all remote hosts and removes the local copies of their files.
+pub fn unmount_all() {
+    for root in vfs::unmount_all() {
+        let _ = std::fs::remove_dir(&root);
+    }
+    let _ = std::fs::remove_dir_all(downloads());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_remote_location() {
+        let location: RemoteLocation = "alice@example.org:/var/log".parse().unwrap();
+        assert_eq!(location.user.as_deref(), Some("alice"));
+        assert_eq!(location.host, "example.org");
+        assert_eq!(location.path, "/var/log");
+        assert_eq!(location.to_string(), "alice@example.org:/var/log");
+
+        let location: RemoteLocation = "server:".parse().unwrap();
+        assert_eq!(location.user, None);
+        assert_eq!(location.path, "");
+
+        assert!("server".parse::<RemoteLocation>().is_err());
+        assert!(":/path".parse::<RemoteLocation>().is_err());
+        assert!("@server:/path".parse::<RemoteLocation>().is_err());
+        assert!("-oProxyCommand=touch /tmp/x:"
+            .parse::<RemoteLocation>()
+            .is_err());
+        assert!("-l@server:".parse::<RemoteLocation>().is_err());
+    }
+}
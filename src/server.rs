@@ -0,0 +1,257 @@
+//! Control server for external tools.
+//!
+//! If enabled, rfm listens on a unix socket for commands from editor plugins or scripts
+//! (see `rfm --send`). Every line is one request, which is answered with `ok` or `error: <reason>`.
+//! Besides the commands of scripts (see [`parse_script`]), the server understands
+//! `jump <dir>`, `select <name>` and `reveal <path>`.
+use std::{
+    io::{BufRead, BufReader, Write},
+    os::unix::{
+        fs::{DirBuilderExt, PermissionsExt},
+        net::UnixStream,
+    },
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, bail, Context, Result};
+use log::{debug, info, warn};
+use once_cell::sync::OnceCell;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt},
+    net::UnixListener,
+    sync::mpsc,
+};
+
+use crate::engine::{
+    commands::{Command, ExpandedPath, Move},
+    script::{parse_script, ScriptStep},
+};
+
+/// Environment variable with the socket of the running instance (set for all child processes)
+pub const SOCKET_ENV: &str = "RFM_SOCKET";
+
+/// Socket of the control server of this instance
+static SOCKET: OnceCell<PathBuf> = OnceCell::new();
+
+/// Passes the socket to a program that is started from rfm (see [`SOCKET_ENV`]).
+///
+/// The variable is set per child process, because changing the environment of rfm itself
+/// would race with the threads that read it.
+pub fn announce(command: &mut std::process::Command) -> &mut std::process::Command {
+    if let Some(socket) = SOCKET.get() {
+        command.env(SOCKET_ENV, socket);
+    }
+    command
+}
+
+/// Request of an external tool
+#[derive(Debug, Clone)]
+pub enum Request {
+    /// Executes a command like a step of a script
+    Step(ScriptStep),
+    /// Selects the item with the given name in the current directory
+    Select(String),
+    /// Jumps into the directory of the given path and selects it
+    Reveal(PathBuf),
+}
+
+/// Parses a single line like `jump /tmp` or `toggle_hidden`.
+pub fn parse_request(line: &str) -> Result<Request> {
+    let line = line.trim();
+    let (name, argument) = match line.split_once(char::is_whitespace) {
+        Some((name, argument)) => (name, Some(argument.trim())),
+        None => (line, None),
+    };
+    let argument = || argument.ok_or_else(|| anyhow!("'{name}' requires an argument"));
+    let request = match name {
+        "jump" => Request::Step(ScriptStep {
+            command: Command::Move(Move::JumpTo(argument()?.into())),
+            input: None,
+        }),
+        "select" => Request::Select(argument()?.to_string()),
+        "reveal" => Request::Reveal(ExpandedPath::from(argument()?).into()),
+        _ => {
+            let mut steps = parse_script(line)?;
+            if steps.len() != 1 {
+                bail!("expected exactly one command");
+            }
+            Request::Step(steps.remove(0))
+        }
+    };
+    Ok(request)
+}
+
+/// Default location of the socket
+pub fn default_socket() -> Option<PathBuf> {
+    std::env::var_os("XDG_RUNTIME_DIR")
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_absolute())
+        .or_else(|| crate::util::xdg_data_home().ok())
+        .map(|dir| dir.join("rfm").join("rfm.sock"))
+}
+
+/// Running control server, the socket is removed when it is dropped.
+pub struct Server {
+    path: PathBuf,
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Listens on the socket and forwards all valid requests to `tx`.
+///
+/// Requests can execute any command (including deleting files), so only the owner can connect
+/// to the socket, and connections of other users are refused.
+/// Fails if another instance is already listening on the same socket.
+pub fn start(path: PathBuf, tx: mpsc::UnboundedSender<Request>) -> Result<Server> {
+    if path.exists() {
+        if UnixStream::connect(&path).is_ok() {
+            bail!("another instance is listening on {}", path.display());
+        }
+        // Left over from an instance that did not shut down properly
+        std::fs::remove_file(&path)?;
+    }
+    if let Some(parent) = path.parent() {
+        std::fs::DirBuilder::new()
+            .recursive(true)
+            .mode(0o700)
+            .create(parent)?;
+    }
+    let listener =
+        UnixListener::bind(&path).context(format!("cannot listen on {}", path.display()))?;
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600))?;
+    info!("listening for commands on {}", path.display());
+    let _ = SOCKET.set(path.clone());
+    // SAFETY: getuid cannot fail
+    let uid = unsafe { libc::getuid() };
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            match stream.peer_cred() {
+                Ok(cred) if cred.uid() == uid => (),
+                Ok(cred) => {
+                    warn!("refused a connection of user {}", cred.uid());
+                    continue;
+                }
+                Err(e) => {
+                    warn!("refused a connection with unknown user: {e}");
+                    continue;
+                }
+            }
+            let tx = tx.clone();
+            tokio::spawn(async move {
+                let (reader, mut writer) = stream.into_split();
+                let mut lines = tokio::io::BufReader::new(reader).lines();
+                while let Ok(Some(line)) = lines.next_line().await {
+                    if line.trim().is_empty() {
+                        continue;
+                    }
+                    let answer = match parse_request(&line) {
+                        Ok(request) => {
+                            debug!("request: {line}");
+                            match tx.send(request) {
+                                Ok(()) => "ok".to_string(),
+                                Err(_) => "error: rfm is shutting down".to_string(),
+                            }
+                        }
+                        Err(e) => format!("error: {e}"),
+                    };
+                    if writer
+                        .write_all(format!("{answer}\n").as_bytes())
+                        .await
+                        .is_err()
+                    {
+                        break;
+                    }
+                }
+            });
+        }
+        warn!("control server stopped");
+    });
+    Ok(Server { path })
+}
+
+/// Makes a relative path of `jump` and `reveal` absolute, because the server
+/// does not know the working directory `cwd` of the client.
+fn absolute_arguments(command: &str, cwd: &Path) -> String {
+    let command = command.trim();
+    match command.split_once(char::is_whitespace) {
+        Some((name @ ("jump" | "reveal"), path)) if Path::new(path.trim()).is_relative() => {
+            let absolute = cwd.join(path.trim());
+            if absolute.exists() {
+                format!("{name} {}", absolute.display())
+            } else {
+                command.to_string()
+            }
+        }
+        _ => command.to_string(),
+    }
+}
+
+/// Sends a command to a running instance and returns its answer.
+pub fn send(socket: &Path, command: &str) -> Result<String> {
+    let mut stream = UnixStream::connect(socket)
+        .context(format!("no instance is listening on {}", socket.display()))?;
+    let cwd = std::env::current_dir().unwrap_or_default();
+    stream.write_all(format!("{}\n", absolute_arguments(command, &cwd)).as_bytes())?;
+    let mut answer = String::new();
+    BufReader::new(stream).read_line(&mut answer)?;
+    Ok(answer.trim_end().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_requests() {
+        let request = parse_request("jump /tmp").unwrap();
+        assert!(matches!(
+            request,
+            Request::Step(ScriptStep {
+                command: Command::Move(Move::JumpTo(_)),
+                ..
+            })
+        ));
+        assert!(matches!(
+            parse_request("select notes.txt").unwrap(),
+            Request::Select(name) if name == "notes.txt"
+        ));
+        assert!(matches!(
+            parse_request("  toggle_hidden ").unwrap(),
+            Request::Step(ScriptStep {
+                command: Command::ToggleHidden,
+                ..
+            })
+        ));
+        assert!(parse_request("reveal").is_err());
+        assert!(parse_request("fly-away").is_err());
+
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("sub")).unwrap();
+        let expected = format!("jump {}", dir.path().join("sub").display());
+        assert_eq!(absolute_arguments("jump sub", dir.path()), expected);
+        assert_eq!(absolute_arguments("jump ~/sub", dir.path()), "jump ~/sub");
+        assert_eq!(absolute_arguments("select sub", dir.path()), "select sub");
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn only_the_owner_can_connect() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("rfm").join("rfm.sock");
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let _server = start(path.clone(), tx).unwrap();
+        let mode = |path: &Path| std::fs::metadata(path).unwrap().permissions().mode() & 0o777;
+        assert_eq!(mode(&path), 0o600);
+        assert_eq!(mode(path.parent().unwrap()), 0o700);
+
+        let answer = tokio::task::spawn_blocking(move || send(&path, "toggle_hidden"))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(answer, "ok");
+        assert!(rx.recv().await.is_some());
+    }
+}
@@ -1,15 +1,24 @@
 use anyhow::anyhow;
+use crossterm::style::{StyledContent, Stylize};
 use fs_extra::dir::CopyOptions;
-use log::warn;
 use std::{
     cmp::Ordering,
-    os::unix::fs::{MetadataExt, PermissionsExt},
+    collections::HashSet,
+    fs::File,
+    io::Write,
+    os::unix::{
+        fs::{MetadataExt, PermissionsExt},
+        io::AsRawFd,
+    },
     path::{Path, PathBuf},
+    time::SystemTime,
 };
 use time::OffsetDateTime;
 use unicode_display_width::width as unicode_width;
 use users::{get_group_by_gid, get_user_by_uid};
 
+use crate::{engine::checksum::sha256, vfs};
+
 pub fn file_size_str(file_size: u64) -> String {
     match file_size {
         0..=1023 => format!("{file_size} B"),
@@ -207,42 +216,388 @@ where
     Ok(result)
 }
 
-pub fn move_item<P, Q>(source: P, destination: Q) -> anyhow::Result<()>
-where
-    P: AsRef<Path>,
-    Q: AsRef<Path>,
-{
-    let from = source.as_ref();
-    let dest_name = from
-        .file_name()
-        .and_then(|p| p.to_str())
-        .map(|s| s.to_string())
-        .unwrap_or_default();
-    // If destination is the directory of from, don't do anything
-    if from == destination.as_ref().join(dest_name) {
-        warn!("from and to are identical");
-        return Ok(());
+/// Source and destination of every item of a copy or move operation.
+#[derive(Debug, Clone)]
+pub struct TransferPlan {
+    pub items: Vec<(PathBuf, PathBuf)>,
+    pub cut: bool,
+}
+
+impl TransferPlan {
+    /// Calculates where the files end up, if they are copied or moved into 'destination'.
+    ///
+    /// Like in [`get_destination`], underscores are appended to names that already exist -
+    /// which includes the names that are taken by earlier items of the same operation.
+    /// Items that would be moved onto themselves keep their path.
+    pub fn new(files: &[PathBuf], destination: &Path, cut: bool) -> Result<Self, std::io::Error> {
+        if !destination.is_dir() {
+            return Err(std::io::Error::other(format!(
+                "{} is not a directory",
+                destination.display()
+            )));
+        }
+        let mut taken = HashSet::new();
+        let mut items = Vec::with_capacity(files.len());
+        for from in files {
+            let mut dest_name = from
+                .file_name()
+                .and_then(|p| p.to_str())
+                .map(|s| s.to_string())
+                .unwrap_or_default();
+            let mut to = destination.join(&dest_name);
+            if cut && &to == from {
+                items.push((from.clone(), to));
+                continue;
+            }
+            while to.exists() || taken.contains(&to) {
+                dest_name.push('_');
+                to = destination.join(&dest_name);
+            }
+            taken.insert(to.clone());
+            items.push((from.clone(), to));
+        }
+        Ok(TransferPlan { items, cut })
+    }
+
+    /// Number of items whose name has to be changed
+    pub fn renamed(&self) -> usize {
+        self.items
+            .iter()
+            .filter(|(from, to)| from.file_name() != to.file_name())
+            .count()
+    }
+}
+
+/// Copies or moves 'source' to exactly 'destination' (which must not exist).
+pub fn transfer_item(source: &Path, destination: &Path, cut: bool) -> anyhow::Result<()> {
+    if destination.exists() {
+        return Err(anyhow!("{} already exists", destination.display()));
+    }
+    if vfs::is_remote(source) {
+        // Remote hosts are read-only
+        if cut {
+            return Err(anyhow!(
+                "cannot move {} from a remote host",
+                source.display()
+            ));
+        }
+        vfs::download(source, destination)?;
+    } else if cut {
+        std::fs::rename(source, destination)?;
+    } else if source.is_dir() {
+        fs_extra::dir::copy(
+            source,
+            destination,
+            &CopyOptions::default().copy_inside(true),
+        )?;
+    } else {
+        std::fs::copy(source, destination)?;
     }
-    let to = get_destination(&source, destination)?;
-    std::fs::rename(from, to)?;
     Ok(())
 }
 
-pub fn copy_item<P, Q>(source: P, destination: Q) -> anyhow::Result<()>
+/// Result of [`verify_copy`]
+#[derive(Debug, Default)]
+pub struct Verification {
+    /// Number of files whose checksum matches
+    pub verified: usize,
+    /// Files in the destination whose content differs (or that are missing)
+    pub mismatches: Vec<PathBuf>,
+    /// Files in the source that could not be read, so their copy was not checked
+    pub unverified: Vec<PathBuf>,
+}
+
+/// Compares the sha256 checksums of 'source' and its copy 'destination' (recursively for directories).
+///
+/// The copied files are synced to disk and dropped from the page cache before they are read again,
+/// so that we compare what has actually been written to the disk.
+pub fn verify_copy<P, Q>(source: P, destination: Q) -> anyhow::Result<Verification>
 where
     P: AsRef<Path>,
     Q: AsRef<Path>,
 {
     let from = source.as_ref();
-    let to = get_destination(&source, destination)?;
-    if from.is_dir() {
-        fs_extra::dir::copy(from, to, &CopyOptions::default().copy_inside(true))?;
-    } else {
-        std::fs::copy(from, to)?;
+    let to = destination.as_ref();
+    if vfs::is_remote(from) {
+        return Err(anyhow!("cannot verify copies from a remote host"));
     }
+    let mut result = Verification::default();
+    for entry in walkdir::WalkDir::new(from).into_iter() {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(e) => {
+                result
+                    .unverified
+                    .push(e.path().unwrap_or(from).to_path_buf());
+                continue;
+            }
+        };
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        let original = match sha256(entry.path()) {
+            Ok(checksum) => checksum,
+            Err(_) => {
+                result.unverified.push(entry.path().to_path_buf());
+                continue;
+            }
+        };
+        let copy = to.join(entry.path().strip_prefix(from)?);
+        let copy_checksum = File::open(&copy)
+            .and_then(|f| {
+                f.sync_all()?;
+                drop_cached_pages(&f)
+            })
+            .and_then(|()| sha256(&copy));
+        match copy_checksum {
+            Ok(checksum) if checksum == original => result.verified += 1,
+            _ => result.mismatches.push(copy),
+        }
+    }
+    Ok(result)
+}
+
+/// Drops the cached pages of the file, so that it is read from the disk again (see `posix_fadvise(2)`).
+///
+/// Pages that were not written yet are kept, so the file has to be synced first.
+/// Does nothing on systems without `posix_fadvise`.
+#[cfg(any(target_os = "linux", target_os = "android", target_os = "freebsd"))]
+pub fn drop_cached_pages(file: &File) -> std::io::Result<()> {
+    use std::os::unix::io::AsRawFd;
+    // SAFETY: the file descriptor is valid as long as 'file' lives
+    let result = unsafe { libc::posix_fadvise(file.as_raw_fd(), 0, 0, libc::POSIX_FADV_DONTNEED) };
+    // The error is returned instead of being stored in errno
+    match result {
+        0 => Ok(()),
+        errno => Err(std::io::Error::from_raw_os_error(errno)),
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android", target_os = "freebsd")))]
+pub fn drop_cached_pages(_file: &File) -> std::io::Result<()> {
     Ok(())
 }
 
+/// Sums up the size of all files (recursively for directories).
+///
+/// Stops after `limit` entries, so that huge directories cannot block the caller.
+/// Returns the size and `false`, if the limit was reached before all entries were counted.
+pub fn total_size(paths: &[PathBuf], limit: usize) -> (u64, bool) {
+    let mut size = 0;
+    let mut entries = 0;
+    for path in paths {
+        for entry in walkdir::WalkDir::new(path).into_iter().flatten() {
+            entries += 1;
+            if entries > limit {
+                return (size, false);
+            }
+            if entry.file_type().is_file() {
+                size += entry.metadata().map(|m| m.len()).unwrap_or_default();
+            }
+        }
+    }
+    (size, true)
+}
+
+/// Checks if the name matches the glob pattern.
+///
+/// Supports `*` (any sequence of characters) and `?` (any single character).
+pub fn glob_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    let (mut p, mut n) = (0, 0);
+    // Position of the last '*' in the pattern and the matching position in the name
+    let mut backtrack = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, n));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match backtrack {
+                // Let the last '*' consume one more character
+                Some((star, consumed)) => {
+                    p = star + 1;
+                    n = consumed + 1;
+                    backtrack = Some((star, consumed + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// How [`link_item`] refers to the original item
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinkKind {
+    /// Symlink with the absolute path of the item
+    Absolute,
+    /// Symlink with the path of the item relative to the directory of the link
+    Relative,
+    /// Hard link (only possible for files on the same filesystem)
+    Hard,
+}
+
+/// Creates a link to 'source' in the directory 'destination' and returns the path of the link.
+///
+/// Like pasting, the link gets a new name if the name already exists.
+pub fn link_item(source: &Path, destination: &Path, kind: LinkKind) -> std::io::Result<PathBuf> {
+    let link = get_destination(source, destination)?;
+    match kind {
+        LinkKind::Hard => std::fs::hard_link(source, &link)?,
+        LinkKind::Absolute => std::os::unix::fs::symlink(std::path::absolute(source)?, &link)?,
+        LinkKind::Relative => {
+            // Relative links are resolved from the real location of the link
+            let base = destination.canonicalize()?;
+            let parent = source.parent().unwrap_or(Path::new("/")).canonicalize()?;
+            let target = parent.join(source.file_name().unwrap_or_default());
+            std::os::unix::fs::symlink(relative_path(&target, &base), &link)?
+        }
+    }
+    Ok(link)
+}
+
+/// Returns the path that leads from the directory 'base' to 'target' (both must be absolute).
+fn relative_path(target: &Path, base: &Path) -> PathBuf {
+    let target: Vec<_> = target.components().collect();
+    let base: Vec<_> = base.components().collect();
+    let common = target
+        .iter()
+        .zip(base.iter())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let mut path = PathBuf::new();
+    for _ in common..base.len() {
+        path.push("..");
+    }
+    for component in &target[common..] {
+        path.push(component);
+    }
+    path
+}
+
+/// Splits the path into the segments that are shown in the header,
+/// e.g. "/", "home/", "user/" and "file.txt", together with the path that each segment ends with.
+pub fn breadcrumbs(path: &Path) -> Vec<(String, PathBuf)> {
+    let mut crumbs: Vec<(String, PathBuf)> = path
+        .ancestors()
+        .map(|ancestor| {
+            let segment = match ancestor.file_name() {
+                Some(name) => format!("{}/", name.to_string_lossy()),
+                None => ancestor.display().to_string(),
+            };
+            (segment, ancestor.to_path_buf())
+        })
+        .collect();
+    crumbs.reverse();
+    // The last segment has no trailing slash (unless it is the root)
+    if let Some((segment, path)) = crumbs.last_mut() {
+        if path.file_name().is_some() {
+            segment.pop();
+        }
+    }
+    crumbs
+}
+
+/// Displays the path with the home directory replaced by `~`.
+pub fn tilde_path(path: &Path) -> String {
+    let home = std::env::var_os("HOME");
+    abbreviate_home(path, home.as_deref().map(Path::new))
+}
+
+fn abbreviate_home(path: &Path, home: Option<&Path>) -> String {
+    let rest = home
+        .filter(|home| home.parent().is_some())
+        .and_then(|home| path.strip_prefix(home).ok());
+    match rest {
+        Some(rest) if rest.as_os_str().is_empty() => "~".to_string(),
+        Some(rest) => format!("~/{}", rest.display()),
+        None => path.display().to_string(),
+    }
+}
+
+/// Parses an age like `30m`, `2h`, `7d` or `1w` (a plain number counts days).
+pub fn parse_age(input: &str) -> Option<std::time::Duration> {
+    let input = input.trim();
+    let split = input
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(input.len());
+    let (number, unit) = input.split_at(split);
+    let number: u64 = number.parse().ok()?;
+    let seconds = match unit.trim() {
+        "s" => 1,
+        "m" | "min" => 60,
+        "h" => 60 * 60,
+        "" | "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        _ => return None,
+    };
+    Some(std::time::Duration::from_secs(number.checked_mul(seconds)?))
+}
+
+/// Sort key that orders names like a human would, so that "file2" comes before "file10".
+///
+/// Every run of digits is replaced by the number of its digits (without leading zeros)
+/// followed by the digits, so that a plain string comparison compares the numbers by value.
+pub fn natural_key(name: &str) -> String {
+    let mut key = String::with_capacity(name.len() + 8);
+    let mut chars = name.chars().peekable();
+    while let Some(c) = chars.next() {
+        if !c.is_ascii_digit() {
+            key.push(c);
+            continue;
+        }
+        let mut digits = String::from(c);
+        while let Some(c) = chars.next_if(char::is_ascii_digit) {
+            digits.push(c);
+        }
+        let value = match digits.trim_start_matches('0') {
+            "" => "0",
+            value => value,
+        };
+        // Filenames are at most 255 bytes long, so three digits are enough for the length
+        key.push_str(&format!("0{:03}{value}", value.len()));
+    }
+    key
+}
+
+/// Checks if all characters of the pattern appear in the text in the same order (ignoring case).
+///
+/// Returns a score for the match (higher is better) or `None` if the text does not match.
+/// Consecutive characters and characters at the start of a word (after `/`, `_`, `-`, `.` or ` `)
+/// score higher, and so do matches in the last component of a path.
+pub fn fuzzy_score(pattern: &str, text: &str) -> Option<i64> {
+    let text: Vec<char> = text.chars().flat_map(char::to_lowercase).collect();
+    let last_component = text
+        .iter()
+        .rposition(|&c| c == '/')
+        .map_or(0, |idx| idx + 1);
+    let mut score = 0;
+    let mut t = 0;
+    let mut previous: Option<usize> = None;
+    for p in pattern.chars().flat_map(char::to_lowercase) {
+        let idx = t + text[t..].iter().position(|&c| c == p)?;
+        score += 1;
+        if previous.is_some_and(|prev| prev + 1 == idx) {
+            score += 4;
+        }
+        if idx == 0 || matches!(text[idx - 1], '/' | '_' | '-' | '.' | ' ') {
+            score += 3;
+        }
+        if idx >= last_component {
+            score += 2;
+        }
+        previous = Some(idx);
+        t = idx + 1;
+    }
+    Some(score)
+}
+
 /// Query the XDG Config Home (usually ~/.config) according to
 /// https://specifications.freedesktop.org/basedir-spec/basedir-spec-latest.html
 pub fn xdg_config_home() -> anyhow::Result<PathBuf> {
@@ -257,45 +612,544 @@ pub fn xdg_config_home() -> anyhow::Result<PathBuf> {
     }
 }
 
+/// Query the XDG Data Home (usually ~/.local/share) according to
+/// https://specifications.freedesktop.org/basedir-spec/basedir-spec-latest.html
+pub fn xdg_data_home() -> anyhow::Result<PathBuf> {
+    match std::env::var("XDG_DATA_HOME") {
+        Ok(xdg_data) => Ok(PathBuf::from(xdg_data)),
+        Err(_) => match std::env::var("HOME") {
+            Ok(home) => Ok(PathBuf::from(home).join(".local").join("share")),
+            Err(_) => Err(anyhow!(
+                "Neither the XDG_DATA_HOME nor the HOME environment variable was set."
+            ))?,
+        },
+    }
+}
+
+/// Replaces the content of the file, without ever leaving a half-written file behind.
+///
+/// The content is written to a temporary file next to it first, which is then renamed.
+/// The temporary file is named after our process id, so that two running instances
+/// never write into the same one.
+pub fn write_atomic(
+    file: &Path,
+    content: impl FnOnce(&mut std::io::BufWriter<File>) -> std::io::Result<()>,
+) -> std::io::Result<()> {
+    if let Some(parent) = file.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let tmp = file.with_extension(format!("{}.tmp", std::process::id()));
+    let result = File::create(&tmp)
+        .and_then(|tmp| {
+            let mut out = std::io::BufWriter::new(tmp);
+            content(&mut out)?;
+            out.flush()
+        })
+        .and_then(|()| std::fs::rename(&tmp, file));
+    if result.is_err() {
+        let _ = std::fs::remove_file(&tmp);
+    }
+    result
+}
+
+/// Locks the file against other instances, until the returned lock file is dropped.
+///
+/// The lock is taken on `<file>.lock`, so that the file itself can be replaced
+/// (see [`write_atomic`]). Uses `flock(2)` directly, because `File::lock` needs a very recent Rust version.
+pub fn lock_file(file: &Path, exclusive: bool) -> std::io::Result<File> {
+    if let Some(parent) = file.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let lock = File::options()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(file.with_extension("lock"))?;
+    let operation = if exclusive {
+        libc::LOCK_EX
+    } else {
+        libc::LOCK_SH
+    };
+    // SAFETY: the file descriptor is valid as long as 'lock' lives
+    if unsafe { libc::flock(lock.as_raw_fd(), operation) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(lock)
+}
+
 /// Returns the permissions and metadata for some selected path, if any.
 ///
 /// The output is ready to be printed in the footer of the filemanager.
 pub fn print_metadata(selected_path: Option<&Path>) -> (String, String) {
-    if let Some(path) = selected_path {
-        // TODO: Maybe we can put all of this into the DirElem and be done with it.
-        if let Ok(metadata) = path.metadata() {
-            let permissions = unix_mode::to_string(metadata.permissions().mode());
-            let modified = metadata
-                .modified()
-                .map(OffsetDateTime::from)
-                .map(|t| {
-                    format!(
-                        "{}-{:02}-{:02} {:02}:{:02}:{:02}",
-                        t.year(),
-                        u8::from(t.month()),
-                        t.day(),
-                        t.hour(),
-                        t.minute(),
-                        t.second()
-                    )
-                })
-                .unwrap_or_else(|_| String::from("cannot read timestamp"));
-            let user = get_user_by_uid(metadata.uid())
-                .and_then(|u| u.name().to_str().map(String::from))
-                .unwrap_or_default();
-            let group = get_group_by_gid(metadata.gid())
-                .and_then(|g| g.name().to_str().map(String::from))
-                .unwrap_or_default();
-            let size_str = file_size_str(metadata.size());
-            let mime_type = mime_guess::from_path(path).first_raw().unwrap_or_default();
-            let other = format!("{user} {group} {size_str} {modified} {mime_type}");
-            (permissions, other)
-        } else {
-            ("------------".to_string(), "".to_string())
+    let info = file_info(selected_path);
+    let other = if info.size.is_empty() {
+        String::new()
+    } else {
+        format!(
+            "{} {} {} {} {}",
+            info.owner, info.group, info.size, info.modified, info.mime
+        )
+    };
+    (info.permissions, other)
+}
+
+/// Metadata of a file, formatted for the footer.
+pub struct FileInfo {
+    pub permissions: String,
+    pub owner: String,
+    pub group: String,
+    pub size: String,
+    pub modified: String,
+    pub mime: String,
+}
+
+impl Default for FileInfo {
+    fn default() -> Self {
+        FileInfo {
+            permissions: "------------".to_string(),
+            owner: String::new(),
+            group: String::new(),
+            size: String::new(),
+            modified: String::new(),
+            mime: String::new(),
+        }
+    }
+}
+
+/// Formats a timestamp like "2024-03-17 09:41:05" (in UTC).
+pub fn timestamp_str(time: SystemTime) -> String {
+    let t = OffsetDateTime::from(time);
+    format!(
+        "{}-{:02}-{:02} {:02}:{:02}:{:02}",
+        t.year(),
+        u8::from(t.month()),
+        t.day(),
+        t.hour(),
+        t.minute(),
+        t.second()
+    )
+}
+
+pub fn file_info(selected_path: Option<&Path>) -> FileInfo {
+    // TODO: Maybe we can put all of this into the DirElem and be done with it.
+    let Some(path) = selected_path else {
+        return FileInfo::default();
+    };
+    let Ok(metadata) = path.metadata() else {
+        return FileInfo::default();
+    };
+    let modified = metadata
+        .modified()
+        .map(timestamp_str)
+        .unwrap_or_else(|_| String::from("cannot read timestamp"));
+    FileInfo {
+        permissions: unix_mode::to_string(metadata.permissions().mode()),
+        owner: get_user_by_uid(metadata.uid())
+            .and_then(|u| u.name().to_str().map(String::from))
+            .unwrap_or_default(),
+        group: get_group_by_gid(metadata.gid())
+            .and_then(|g| g.name().to_str().map(String::from))
+            .unwrap_or_default(),
+        size: file_size_str(metadata.size()),
+        modified,
+        mime: mime_guess::from_path(path)
+            .first_raw()
+            .unwrap_or_default()
+            .to_string(),
+    }
+}
+
+/// Returns the checked out branch of the git repository that contains 'dir'.
+///
+/// For a detached HEAD, the abbreviated commit hash is returned.
+pub fn git_branch(dir: &Path) -> Option<String> {
+    let dot_git = dir
+        .ancestors()
+        .map(|d| d.join(".git"))
+        .find(|d| d.exists())?;
+    // In worktrees and submodules, .git is a file that points to the actual git directory
+    let git_dir = if dot_git.is_file() {
+        let content = std::fs::read_to_string(&dot_git).ok()?;
+        let target = PathBuf::from(content.strip_prefix("gitdir:")?.trim());
+        dot_git.parent()?.join(target)
+    } else {
+        dot_git
+    };
+    let head = std::fs::read_to_string(git_dir.join("HEAD")).ok()?;
+    let head = head.trim();
+    match head.strip_prefix("ref: ") {
+        Some(reference) => Some(
+            reference
+                .strip_prefix("refs/heads/")
+                .unwrap_or(reference)
+                .to_string(),
+        ),
+        None => Some(head.chars().take(7).collect()),
+    }
+}
+
+/// Returns the type of the filesystem that 'path' is located on (e.g. `ext4` or `fuse.sshfs`).
+pub fn filesystem_type(path: &Path) -> Option<String> {
+    let mounts = std::fs::read_to_string("/proc/self/mounts").ok()?;
+    mount_fstype(&mounts, path)
+}
+
+/// Size and free space of a filesystem in bytes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DiskUsage {
+    pub total: u64,
+    pub used: u64,
+    /// Space that is available to unprivileged users
+    pub free: u64,
+}
+
+impl DiskUsage {
+    /// Used space in percent of the space that users can use (like `df` calculates it)
+    pub fn used_percent(&self) -> u64 {
+        let usable = self.used.saturating_add(self.free);
+        if usable == 0 {
+            return 0;
         }
+        self.used.saturating_mul(100).div_ceil(usable)
+    }
+}
+
+/// Returns the size and free space of the filesystem that 'path' is located on (see `statvfs(3)`).
+#[cfg(unix)]
+#[allow(clippy::unnecessary_cast)] // the types of the fields differ between the platforms
+pub fn disk_usage(path: &Path) -> Option<DiskUsage> {
+    use std::os::unix::ffi::OsStrExt;
+    let path = std::ffi::CString::new(path.as_os_str().as_bytes()).ok()?;
+    let mut stat = std::mem::MaybeUninit::<libc::statvfs>::uninit();
+    // SAFETY: 'path' is a valid C string and 'stat' is a `struct statvfs`
+    if unsafe { libc::statvfs(path.as_ptr(), stat.as_mut_ptr()) } != 0 {
+        return None;
+    }
+    // SAFETY: statvfs has filled 'stat'
+    let stat = unsafe { stat.assume_init() };
+    let (frsize, blocks) = (stat.f_frsize as u64, stat.f_blocks as u64);
+    Some(DiskUsage {
+        total: blocks.saturating_mul(frsize),
+        used: blocks
+            .saturating_sub(stat.f_bfree as u64)
+            .saturating_mul(frsize),
+        free: (stat.f_bavail as u64).saturating_mul(frsize),
+    })
+}
+
+#[cfg(not(unix))]
+pub fn disk_usage(_path: &Path) -> Option<DiskUsage> {
+    None
+}
+
+/// Returns the extended attributes of 'path' with their values (see `xattr(7)`).
+///
+/// Symlinks are not followed. Values that are not valid UTF-8 are replaced by their length.
+#[cfg(target_os = "linux")]
+pub fn extended_attributes(path: &Path) -> Vec<(String, String)> {
+    use std::{ffi::CString, os::unix::ffi::OsStrExt};
+    let Ok(c_path) = CString::new(path.as_os_str().as_bytes()) else {
+        return Vec::new();
+    };
+    // SAFETY: 'c_path' is a valid C string, a null buffer only queries the size
+    let size = unsafe { libc::llistxattr(c_path.as_ptr(), std::ptr::null_mut(), 0) };
+    if size <= 0 {
+        return Vec::new();
+    }
+    let mut names = vec![0u8; size as usize];
+    // SAFETY: 'names' has room for 'size' bytes
+    let size = unsafe { libc::llistxattr(c_path.as_ptr(), names.as_mut_ptr().cast(), names.len()) };
+    if size <= 0 {
+        return Vec::new();
+    }
+    names.truncate(size as usize);
+    names
+        .split(|b| *b == 0)
+        .filter(|name| !name.is_empty())
+        .filter_map(|name| {
+            let c_name = CString::new(name).ok()?;
+            // SAFETY: both strings are valid C strings, a null buffer only queries the size
+            let size = unsafe {
+                libc::lgetxattr(c_path.as_ptr(), c_name.as_ptr(), std::ptr::null_mut(), 0)
+            };
+            let mut value = vec![0u8; size.max(0) as usize];
+            // SAFETY: 'value' has room for 'value.len()' bytes
+            let size = unsafe {
+                libc::lgetxattr(
+                    c_path.as_ptr(),
+                    c_name.as_ptr(),
+                    value.as_mut_ptr().cast(),
+                    value.len(),
+                )
+            };
+            value.truncate(size.max(0) as usize);
+            // Most values are C strings
+            if value.last() == Some(&0) {
+                value.pop();
+            }
+            let value = match String::from_utf8(value) {
+                Ok(text) if !text.chars().any(char::is_control) => text,
+                Ok(text) => format!("({} bytes)", text.len()),
+                Err(e) => format!("({} bytes)", e.as_bytes().len()),
+            };
+            Some((String::from_utf8_lossy(name).into_owned(), value))
+        })
+        .collect()
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn extended_attributes(_path: &Path) -> Vec<(String, String)> {
+    Vec::new()
+}
+
+/// Returns the width and height of one terminal cell in pixels (see `TIOCGWINSZ`).
+///
+/// Many terminals do not report their size in pixels, which gives `None`.
+#[cfg(unix)]
+pub fn cell_size() -> Option<(u16, u16)> {
+    use std::os::unix::io::AsRawFd;
+    let mut size = libc::winsize {
+        ws_row: 0,
+        ws_col: 0,
+        ws_xpixel: 0,
+        ws_ypixel: 0,
+    };
+    // SAFETY: TIOCGWINSZ writes a `struct winsize`, which 'size' is
+    if unsafe { libc::ioctl(std::io::stdout().as_raw_fd(), libc::TIOCGWINSZ, &mut size) } != 0 {
+        return None;
+    }
+    if size.ws_row == 0 || size.ws_col == 0 || size.ws_xpixel == 0 || size.ws_ypixel == 0 {
+        return None;
+    }
+    Some((size.ws_xpixel / size.ws_col, size.ws_ypixel / size.ws_row))
+        .filter(|(w, h)| *w > 0 && *h > 0)
+}
+
+#[cfg(not(unix))]
+pub fn cell_size() -> Option<(u16, u16)> {
+    None
+}
+
+/// Returns true if we may list the directory and enter it (see `access(2)`).
+///
+/// Unlike the permission bits, this takes our user, groups and capabilities into account.
+#[cfg(unix)]
+pub fn is_traversable(path: &Path) -> bool {
+    use std::os::unix::ffi::OsStrExt;
+    let Ok(path) = std::ffi::CString::new(path.as_os_str().as_bytes()) else {
+        return false;
+    };
+    // SAFETY: 'path' is a valid C string
+    unsafe { libc::access(path.as_ptr(), libc::R_OK | libc::X_OK) == 0 }
+}
+
+#[cfg(not(unix))]
+pub fn is_traversable(_path: &Path) -> bool {
+    true
+}
+
+/// Waits until there is something to read from the file (see `poll(2)`).
+///
+/// Returns false, if nothing arrived within the timeout.
+#[cfg(unix)]
+pub fn wait_readable(file: &impl std::os::unix::io::AsRawFd, timeout: std::time::Duration) -> bool {
+    let mut fd = libc::pollfd {
+        fd: file.as_raw_fd(),
+        events: libc::POLLIN,
+        revents: 0,
+    };
+    let timeout = timeout.as_millis().min(libc::c_int::MAX as u128) as libc::c_int;
+    // SAFETY: 'fd' is a single, valid `struct pollfd`
+    unsafe { libc::poll(&mut fd, 1, timeout) > 0 && fd.revents & libc::POLLIN != 0 }
+}
+
+/// Finds the mount point that contains 'path' in the content of `/proc/mounts`
+/// and returns its filesystem type.
+fn mount_fstype(mounts: &str, path: &Path) -> Option<String> {
+    mounts
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace().skip(1);
+            // Spaces in mount points are escaped as "\040"
+            let mount_point = PathBuf::from(fields.next()?.replace("\\040", " "));
+            let fstype = fields.next()?;
+            path.starts_with(&mount_point)
+                .then(|| (mount_point.components().count(), fstype))
+        })
+        .max_by_key(|(depth, _)| *depth)
+        .map(|(_, fstype)| fstype.to_string())
+}
+
+/// Encodes the data as (padded) base64.
+fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let bytes = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let n = u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+/// Puts the text into the system clipboard.
+///
+/// Uses the OSC 52 escape sequence, which is supported by most terminals (also via ssh).
+pub fn copy_to_clipboard(text: &str) -> std::io::Result<()> {
+    let mut stdout = std::io::stdout();
+    write!(stdout, "\x1b]52;c;{}\x07", base64(text.as_bytes()))?;
+    stdout.flush()
+}
+
+/// Largest file that is copied to the clipboard (terminals limit the size of OSC 52 sequences)
+const MAX_CLIPBOARD_FILE_SIZE: u64 = 100 * 1024;
+
+/// Reads a small text file, whose content should be copied to the clipboard.
+pub fn read_text_file<P: AsRef<Path>>(path: P) -> std::io::Result<String> {
+    let path = path.as_ref();
+    let metadata = path.metadata()?;
+    if metadata.is_dir() {
+        return Err(std::io::Error::other("not a file"));
+    }
+    let size = metadata.len();
+    if size > MAX_CLIPBOARD_FILE_SIZE {
+        return Err(std::io::Error::other(format!(
+            "file is too large ({}, the limit is {})",
+            file_size_str(size),
+            file_size_str(MAX_CLIPBOARD_FILE_SIZE)
+        )));
+    }
+    String::from_utf8(std::fs::read(path)?).map_err(|_| std::io::Error::other("not a text file"))
+}
+
+/// Programs that print the content of the system clipboard,
+/// together with the environment variable that their display server requires.
+const PASTE_PROGRAMS: [(&str, &[&str], Option<&str>); 4] = [
+    ("wl-paste", &["--no-newline"], Some("WAYLAND_DISPLAY")),
+    (
+        "xclip",
+        &["-selection", "clipboard", "-out"],
+        Some("DISPLAY"),
+    ),
+    ("xsel", &["--clipboard", "--output"], Some("DISPLAY")),
+    ("pbpaste", &[], None),
+];
+
+/// Returns the text of the system clipboard.
+///
+/// Terminals rarely allow to read the clipboard, so this requires one of the programs
+/// `wl-paste`, `xclip`, `xsel` or `pbpaste`.
+pub fn paste_from_clipboard() -> std::io::Result<String> {
+    let programs = PASTE_PROGRAMS
+        .iter()
+        .filter(|(_, _, env)| env.is_none_or(|var| std::env::var_os(var).is_some()));
+    for (program, args, _) in programs {
+        let output = match std::process::Command::new(program)
+            .args(*args)
+            .stdin(std::process::Stdio::null())
+            .output()
+        {
+            Ok(output) => output,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e),
+        };
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(std::io::Error::other(format!(
+                "{program} failed: {}",
+                stderr.trim()
+            )));
+        }
+        return String::from_utf8(output.stdout)
+            .map_err(|_| std::io::Error::other("the clipboard does not contain text"));
+    }
+    Err(std::io::Error::other(
+        "reading the clipboard requires wl-paste, xclip, xsel or pbpaste",
+    ))
+}
+
+/// Colorizes a permission string like `drwxr-xr-x`.
+///
+/// Read, write and execute bits get their own color, which is brightest for the user class
+/// and darkest for the other class, so that the three classes are easy to tell apart.
+pub fn styled_permissions(permissions: &str) -> Vec<StyledContent<char>> {
+    permissions
+        .chars()
+        .enumerate()
+        .map(|(idx, c)| match (idx, c) {
+            (_, '-') => c.dark_grey(),
+            (0, 'd') => c.blue().bold(),
+            (0, _) => c.cyan(),
+            (1..=3, 'r') => c.yellow().bold(),
+            (1..=3, 'w') => c.red().bold(),
+            (1..=3, _) => c.green().bold(),
+            (4..=6, 'r') => c.yellow(),
+            (4..=6, 'w') => c.red(),
+            (4..=6, _) => c.green(),
+            (_, 'r') => c.dark_yellow(),
+            (_, 'w') => c.dark_red(),
+            (_, _) => c.dark_green(),
+        })
+        .collect()
+}
+
+/// Toggles the executable bits of a file and returns whether it is executable afterwards.
+///
+/// Like `chmod +x`, the executable bit is set for every class that may read the file.
+/// If the owner can already execute the file, all executable bits are removed.
+pub fn toggle_executable<P: AsRef<Path>>(path: P) -> Result<bool, std::io::Error> {
+    let path = path.as_ref();
+    let metadata = path.metadata()?;
+    if metadata.is_dir() {
+        return Err(std::io::Error::other(format!(
+            "{} is a directory",
+            path.display()
+        )));
+    }
+    let mode = metadata.permissions().mode();
+    let executable = mode & 0o100 == 0;
+    let new_mode = if executable {
+        mode | ((mode & 0o444) >> 2)
     } else {
-        ("------------".to_string(), "".to_string())
+        mode & !0o111
+    };
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(new_mode))?;
+    Ok(executable)
+}
+
+/// Creates a new directory or an empty file, together with all missing parent directories
+/// (so `a/b/c` works, even if `a` does not exist yet).
+///
+/// An existing directory is an error, while an existing file is left untouched (like `touch`).
+pub fn create_item(path: &Path, is_dir: bool) -> Result<(), std::io::Error> {
+    if is_dir {
+        if path.exists() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::AlreadyExists,
+                format!("{} already exists", path.display()),
+            ));
+        }
+        return std::fs::create_dir_all(path);
+    }
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
     }
+    std::fs::OpenOptions::new()
+        .read(true)
+        .append(true)
+        .create(true)
+        .open(path)?;
+    Ok(())
 }
 
 // TODO: Use the device-id to check, if deletion actually just moves the file on the same disk.
@@ -320,3 +1174,284 @@ pub fn print_metadata(selected_path: Option<&Path>) -> (String, String) {
 //         self.volume_serial_number().unwrap_or(0)
 //     }
 // }
+
+#[test]
+fn verify_copied_directory() {
+    let source = tempfile::tempdir().unwrap();
+    let destination = tempfile::tempdir().unwrap();
+    std::fs::create_dir(source.path().join("sub")).unwrap();
+    std::fs::write(source.path().join("a.txt"), "content").unwrap();
+    std::fs::write(source.path().join("sub/b.txt"), "more content").unwrap();
+
+    let copy = destination.path().join("copy");
+    transfer_item(source.path(), &copy, false).unwrap();
+    let result = verify_copy(source.path(), &copy).unwrap();
+    assert_eq!(result.verified, 2);
+    assert!(result.mismatches.is_empty() && result.unverified.is_empty());
+
+    std::fs::write(copy.join("sub/b.txt"), "corrupted").unwrap();
+    std::fs::remove_file(copy.join("a.txt")).unwrap();
+    let result = verify_copy(source.path(), &copy).unwrap();
+    assert_eq!(result.verified, 0);
+    assert_eq!(result.mismatches.len(), 2);
+}
+
+#[test]
+fn total_size_of_items() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::create_dir(dir.path().join("sub")).unwrap();
+    std::fs::write(dir.path().join("sub/a"), [0; 100]).unwrap();
+    std::fs::write(dir.path().join("sub/b"), [0; 20]).unwrap();
+    std::fs::write(dir.path().join("c"), [0; 3]).unwrap();
+    let items = [dir.path().join("sub"), dir.path().join("c")];
+    assert_eq!(total_size(&items, 100), (123, true));
+    assert_eq!(total_size(&items, 1), (0, false));
+}
+
+#[test]
+fn glob_patterns() {
+    assert!(glob_match("*.png", "image.png"));
+    assert!(!glob_match("*.png", "image.png.txt"));
+    assert!(glob_match("a?c*", "abcdef"));
+    assert!(glob_match("*a*b*", "xxaxxbxx"));
+    assert!(!glob_match("*a*b", "xxaxxbxx"));
+    assert!(glob_match("*", ""));
+    assert!(!glob_match("?", ""));
+}
+
+#[test]
+fn links() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("data/file.txt");
+    let target = dir.path().join("links/deep");
+    std::fs::create_dir_all(file.parent().unwrap()).unwrap();
+    std::fs::create_dir_all(&target).unwrap();
+    std::fs::write(&file, "content").unwrap();
+
+    let relative = link_item(&file, &target, LinkKind::Relative).unwrap();
+    assert_eq!(
+        std::fs::read_link(&relative).unwrap(),
+        Path::new("../../data/file.txt")
+    );
+    let absolute = link_item(&file, &target, LinkKind::Absolute).unwrap();
+    assert_eq!(absolute, target.join("file.txt_"));
+    assert!(std::fs::read_link(&absolute).unwrap().is_absolute());
+    let hard = link_item(&file, &target, LinkKind::Hard).unwrap();
+    for link in [relative, absolute, hard] {
+        assert_eq!(std::fs::read_to_string(link).unwrap(), "content");
+    }
+    assert!(link_item(file.parent().unwrap(), &target, LinkKind::Hard).is_err());
+}
+
+#[test]
+fn path_segments() {
+    let segments: Vec<String> = breadcrumbs(Path::new("/home/user/file.txt"))
+        .into_iter()
+        .map(|(segment, _)| segment)
+        .collect();
+    assert_eq!(segments, ["/", "home/", "user/", "file.txt"]);
+    let (_, path) = &breadcrumbs(Path::new("/home/user"))[1];
+    assert_eq!(path, Path::new("/home"));
+    assert_eq!(breadcrumbs(Path::new("/")), [("/".into(), "/".into())]);
+}
+
+#[test]
+fn home_as_tilde() {
+    let home = Some(Path::new("/home/user"));
+    assert_eq!(abbreviate_home(Path::new("/home/user"), home), "~");
+    assert_eq!(abbreviate_home(Path::new("/home/user/a/b"), home), "~/a/b");
+    assert_eq!(
+        abbreviate_home(Path::new("/home/username"), home),
+        "/home/username"
+    );
+    assert_eq!(abbreviate_home(Path::new("/tmp"), None), "/tmp");
+    // A home directory of "/" would turn every path into "~/..."
+    assert_eq!(
+        abbreviate_home(Path::new("/tmp"), Some(Path::new("/"))),
+        "/tmp"
+    );
+}
+
+#[test]
+fn ages() {
+    use std::time::Duration;
+    assert_eq!(parse_age("90s"), Some(Duration::from_secs(90)));
+    assert_eq!(parse_age("2h"), Some(Duration::from_secs(7200)));
+    assert_eq!(parse_age(" 7d "), parse_age("7"));
+    assert_eq!(parse_age("1w"), Some(Duration::from_secs(604_800)));
+    assert_eq!(parse_age("d"), None);
+    assert_eq!(parse_age("3y"), None);
+    assert_eq!(parse_age("-1d"), None);
+}
+
+#[test]
+fn natural_order() {
+    let mut names = vec![
+        "file10", "file2", "file1", "a100b", "a20b", "file", "10", "9", "00",
+    ];
+    names.sort_by_cached_key(|name| natural_key(name));
+    assert_eq!(
+        names,
+        ["00", "9", "10", "a20b", "a100b", "file", "file1", "file2", "file10"]
+    );
+    assert_eq!(natural_key("file02"), natural_key("file2"));
+    // Huge numbers don't overflow
+    assert!(natural_key("v123456789012345678901234567890") > natural_key("v99"));
+}
+
+#[test]
+fn fuzzy_matching() {
+    assert!(fuzzy_score("rdm", "/home/user/README.md").is_some());
+    assert!(fuzzy_score("mdr", "/home/user/README.md").is_none());
+    assert_eq!(fuzzy_score("", "anything"), Some(0));
+    // Consecutive characters in the file name win
+    let good = fuzzy_score("main", "/src/main.rs").unwrap();
+    let bad = fuzzy_score("main", "/mail/inbox/n.txt").unwrap();
+    assert!(good > bad);
+}
+
+#[test]
+fn toggle_executable_bits() {
+    let dir = tempfile::tempdir().unwrap();
+    let script = dir.path().join("script.sh");
+    std::fs::write(&script, "#!/bin/sh").unwrap();
+    std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o640)).unwrap();
+
+    assert!(toggle_executable(&script).unwrap());
+    assert_eq!(
+        script.metadata().unwrap().permissions().mode() & 0o777,
+        0o750
+    );
+    assert!(!toggle_executable(&script).unwrap());
+    assert_eq!(
+        script.metadata().unwrap().permissions().mode() & 0o777,
+        0o640
+    );
+    assert!(toggle_executable(dir.path()).is_err());
+}
+
+#[test]
+fn transfer_plan_renames_collisions() {
+    let source = tempfile::tempdir().unwrap();
+    let destination = tempfile::tempdir().unwrap();
+    std::fs::create_dir(source.path().join("sub")).unwrap();
+    for file in ["a", "b", "sub/a"] {
+        std::fs::write(source.path().join(file), file).unwrap();
+    }
+    std::fs::write(destination.path().join("b"), "existing").unwrap();
+    let files: Vec<PathBuf> = ["a", "b", "sub/a"]
+        .iter()
+        .map(|f| source.path().join(f))
+        .collect();
+
+    let plan = TransferPlan::new(&files, destination.path(), false).unwrap();
+    let names: Vec<_> = plan
+        .items
+        .iter()
+        .map(|(_, to)| to.file_name().unwrap().to_str().unwrap())
+        .collect();
+    assert_eq!(names, ["a", "b_", "a_"]);
+    assert_eq!(plan.renamed(), 2);
+
+    // Moving items into their own directory does not change anything
+    let plan = TransferPlan::new(&files[..2], source.path(), true).unwrap();
+    assert!(plan.items.iter().all(|(from, to)| from == to));
+}
+
+#[test]
+fn git_branch_from_head() {
+    let repo = tempfile::tempdir().unwrap();
+    let sub_dir = repo.path().join("src/deeper");
+    std::fs::create_dir_all(&sub_dir).unwrap();
+    assert_eq!(git_branch(&sub_dir), None);
+
+    std::fs::create_dir(repo.path().join(".git")).unwrap();
+    std::fs::write(repo.path().join(".git/HEAD"), "ref: refs/heads/feature/x\n").unwrap();
+    assert_eq!(git_branch(&sub_dir).as_deref(), Some("feature/x"));
+
+    std::fs::write(repo.path().join(".git/HEAD"), "0123456789abcdef\n").unwrap();
+    assert_eq!(git_branch(&sub_dir).as_deref(), Some("0123456"));
+}
+
+#[test]
+fn disk_usage_of_tempdir() {
+    let dir = tempfile::tempdir().unwrap();
+    let usage = disk_usage(dir.path()).unwrap();
+    assert!(usage.total > 0 && usage.free <= usage.total);
+    assert!(usage.used_percent() <= 100);
+    assert_eq!(disk_usage(&dir.path().join("missing")), None);
+    // Like 'df', the space that is reserved for root is not counted
+    let usage = DiskUsage {
+        total: 220,
+        used: 100,
+        free: 100,
+    };
+    assert_eq!(usage.used_percent(), 50);
+}
+
+#[test]
+fn filesystem_of_mount_point() {
+    let mounts = "\
+/dev/sda1 / ext4 rw,relatime 0 0
+server:/export /mnt/nfs nfs4 rw 0 0
+user@host:/srv /home/user/remote\\040dir fuse.sshfs rw 0 0
+";
+    let fstype = |path: &str| mount_fstype(mounts, Path::new(path));
+    assert_eq!(fstype("/home/user").as_deref(), Some("ext4"));
+    assert_eq!(fstype("/mnt/nfs/some/dir").as_deref(), Some("nfs4"));
+    assert_eq!(fstype("/mnt/nfsx").as_deref(), Some("ext4"));
+    assert_eq!(
+        fstype("/home/user/remote dir/a").as_deref(),
+        Some("fuse.sshfs")
+    );
+}
+
+#[test]
+fn base64_encoding() {
+    assert_eq!(base64(b""), "");
+    assert_eq!(base64(b"f"), "Zg==");
+    assert_eq!(base64(b"fo"), "Zm8=");
+    assert_eq!(base64(b"foo"), "Zm9v");
+    assert_eq!(base64(b"/home/user"), "L2hvbWUvdXNlcg==");
+}
+
+#[test]
+fn timestamps() {
+    use std::time::{Duration, UNIX_EPOCH};
+    assert_eq!(timestamp_str(UNIX_EPOCH), "1970-01-01 00:00:00");
+    let time = UNIX_EPOCH + Duration::from_secs(1_710_668_465);
+    assert_eq!(timestamp_str(time), "2024-03-17 09:41:05");
+}
+
+#[test]
+fn create_nested_items() {
+    let dir = tempfile::tempdir().unwrap();
+    create_item(&dir.path().join("a/b/c"), true).unwrap();
+    assert!(dir.path().join("a/b/c").is_dir());
+    assert!(create_item(&dir.path().join("a/b"), true).is_err());
+
+    let file = dir.path().join("x/y/notes.txt");
+    create_item(&file, false).unwrap();
+    assert!(file.is_file());
+    std::fs::write(&file, "content").unwrap();
+    // Touching an existing file keeps its content
+    create_item(&file, false).unwrap();
+    assert_eq!(std::fs::read_to_string(&file).unwrap(), "content");
+}
+
+#[test]
+fn write_files_atomically() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("data").join("history.txt");
+    write_atomic(&file, |out| writeln!(out, "first")).unwrap();
+    write_atomic(&file, |out| writeln!(out, "second")).unwrap();
+    assert_eq!(std::fs::read_to_string(&file).unwrap(), "second\n");
+    // A failed write keeps the old content and removes its temporary file
+    let err = write_atomic(&file, |_| Err(std::io::Error::other("failed")));
+    assert!(err.is_err());
+    assert_eq!(std::fs::read_to_string(&file).unwrap(), "second\n");
+    assert_eq!(
+        std::fs::read_dir(file.parent().unwrap()).unwrap().count(),
+        1
+    );
+}
@@ -0,0 +1,238 @@
+//! Filesystems that directories are read from.
+//!
+//! Local paths are read by the [`LocalBackend`]. Remote hosts (see [`crate::remote`]) are mounted
+//! below a local root directory, and all paths below it are read by their backend instead.
+//! Panels, previews and pasting go through the functions of this module, so they work on both.
+use std::{
+    fs::File,
+    io::{self, Read},
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::SystemTime,
+};
+
+use once_cell::sync::Lazy;
+use parking_lot::RwLock;
+
+/// Metadata of a file or directory (symlinks are followed)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Metadata {
+    pub is_dir: bool,
+    pub len: u64,
+    /// Permissions and file type like in `st_mode`
+    pub mode: u32,
+    pub modified: Option<SystemTime>,
+}
+
+impl From<std::fs::Metadata> for Metadata {
+    fn from(metadata: std::fs::Metadata) -> Self {
+        Metadata {
+            is_dir: metadata.is_dir(),
+            len: metadata.len(),
+            mode: metadata.permissions().mode(),
+            modified: metadata.modified().ok(),
+        }
+    }
+}
+
+/// Paths of the entries of a directory
+pub type Entries = Box<dyn Iterator<Item = io::Result<PathBuf>> + Send>;
+
+/// Access to a filesystem.
+///
+/// All paths are local paths - a remote backend translates them to the paths on its host.
+/// All functions are blocking, so they should be run inside of `spawn_blocking`.
+pub trait VfsBackend: Send + Sync {
+    /// Lists the entries of the directory.
+    fn read_dir(&self, path: &Path) -> io::Result<Entries>;
+
+    fn metadata(&self, path: &Path) -> io::Result<Metadata>;
+
+    /// Opens the file for reading.
+    fn open(&self, path: &Path) -> io::Result<Box<dyn Read + Send>>;
+
+    /// Copies the file to the local path `to`, returns the number of copied bytes.
+    fn copy(&self, from: &Path, to: &Path) -> io::Result<u64>;
+}
+
+/// The local filesystem
+pub struct LocalBackend;
+
+impl VfsBackend for LocalBackend {
+    fn read_dir(&self, path: &Path) -> io::Result<Entries> {
+        Ok(Box::new(
+            std::fs::read_dir(path)?.map(|entry| entry.map(|e| e.path())),
+        ))
+    }
+
+    fn metadata(&self, path: &Path) -> io::Result<Metadata> {
+        path.metadata().map(Metadata::from)
+    }
+
+    fn open(&self, path: &Path) -> io::Result<Box<dyn Read + Send>> {
+        Ok(Box::new(File::open(path)?))
+    }
+
+    fn copy(&self, from: &Path, to: &Path) -> io::Result<u64> {
+        std::fs::copy(from, to)
+    }
+}
+
+static LOCAL: Lazy<Arc<dyn VfsBackend>> = Lazy::new(|| Arc::new(LocalBackend));
+
+/// Root directories of the mounted backends
+static MOUNTS: RwLock<Vec<(PathBuf, Arc<dyn VfsBackend>)>> = RwLock::new(Vec::new());
+
+/// Reads all paths below `root` with the backend (instead of the local filesystem).
+pub fn mount(root: PathBuf, backend: Arc<dyn VfsBackend>) {
+    let mut mounts = MOUNTS.write();
+    mounts.retain(|(mounted, _)| *mounted != root);
+    mounts.push((root, backend));
+}
+
+/// Unmounts all backends and returns their root directories.
+pub fn unmount_all() -> Vec<PathBuf> {
+    MOUNTS.write().drain(..).map(|(root, _)| root).collect()
+}
+
+/// Returns true if the backend is mounted at exactly this root.
+pub fn is_mounted(root: &Path) -> bool {
+    MOUNTS.read().iter().any(|(mounted, _)| mounted == root)
+}
+
+/// Returns the backend, that the path belongs to.
+pub fn backend(path: &Path) -> Arc<dyn VfsBackend> {
+    MOUNTS
+        .read()
+        .iter()
+        .find(|(root, _)| path.starts_with(root))
+        .map(|(_, backend)| backend.clone())
+        .unwrap_or_else(|| LOCAL.clone())
+}
+
+/// Returns true if the path does not belong to the local filesystem.
+pub fn is_remote(path: &Path) -> bool {
+    MOUNTS.read().iter().any(|(root, _)| path.starts_with(root))
+}
+
+pub fn read_dir(path: &Path) -> io::Result<Entries> {
+    backend(path).read_dir(path)
+}
+
+pub fn metadata(path: &Path) -> io::Result<Metadata> {
+    backend(path).metadata(path)
+}
+
+/// Like [`Path::is_dir`] for all backends
+pub fn is_dir(path: &Path) -> bool {
+    if is_remote(path) {
+        metadata(path).is_ok_and(|m| m.is_dir)
+    } else {
+        path.is_dir()
+    }
+}
+
+pub fn open(path: &Path) -> io::Result<Box<dyn Read + Send>> {
+    backend(path).open(path)
+}
+
+/// Copies the file or directory (recursively) to the local path `to`, which must not exist.
+pub fn download(from: &Path, to: &Path) -> io::Result<()> {
+    let backend = backend(from);
+    if backend.metadata(from)?.is_dir {
+        std::fs::create_dir(to)?;
+        for entry in backend.read_dir(from)? {
+            let entry = entry?;
+            if let Some(name) = entry.file_name() {
+                download(&entry, &to.join(name))?;
+            }
+        }
+    } else {
+        backend.copy(from, to)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    /// Backend with some files in memory
+    struct MemoryBackend {
+        root: PathBuf,
+        files: HashMap<PathBuf, &'static str>,
+    }
+
+    impl VfsBackend for MemoryBackend {
+        fn read_dir(&self, path: &Path) -> io::Result<Entries> {
+            let entries: Vec<io::Result<PathBuf>> = self
+                .files
+                .keys()
+                .filter(|file| file.parent() == Some(path))
+                .map(|file| Ok(file.clone()))
+                .collect();
+            Ok(Box::new(entries.into_iter()))
+        }
+
+        fn metadata(&self, path: &Path) -> io::Result<Metadata> {
+            let content = if path == self.root {
+                None
+            } else {
+                Some(self.files.get(path).ok_or(io::ErrorKind::NotFound)?)
+            };
+            Ok(Metadata {
+                is_dir: content.is_none(),
+                len: content.map(|c| c.len() as u64).unwrap_or_default(),
+                mode: 0o644,
+                modified: None,
+            })
+        }
+
+        fn open(&self, path: &Path) -> io::Result<Box<dyn Read + Send>> {
+            let content = self.files.get(path).ok_or(io::ErrorKind::NotFound)?;
+            Ok(Box::new(content.as_bytes()))
+        }
+
+        fn copy(&self, from: &Path, to: &Path) -> io::Result<u64> {
+            let mut content = self.open(from)?;
+            io::copy(&mut content, &mut File::create(to)?)
+        }
+    }
+
+    #[test]
+    fn route_paths_to_their_backend() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("remote");
+        let file = root.join("notes.txt");
+        std::fs::create_dir(&root).unwrap();
+        std::fs::write(dir.path().join("local.txt"), "local").unwrap();
+
+        mount(
+            root.clone(),
+            Arc::new(MemoryBackend {
+                root: root.clone(),
+                files: HashMap::from([(file.clone(), "remote notes")]),
+            }),
+        );
+        assert!(is_mounted(&root) && is_remote(&file));
+        assert!(!is_remote(dir.path()));
+        assert!(is_dir(&root) && !is_dir(&file));
+        assert_eq!(metadata(&file).unwrap().len, 12);
+        let entries: Vec<PathBuf> = read_dir(&root).unwrap().flatten().collect();
+        assert_eq!(entries, vec![file.clone()]);
+        let local: Vec<PathBuf> = read_dir(dir.path()).unwrap().flatten().collect();
+        assert_eq!(local.len(), 2);
+
+        let copy = dir.path().join("copy");
+        download(&root, &copy).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(copy.join("notes.txt")).unwrap(),
+            "remote notes"
+        );
+        assert!(unmount_all().contains(&root));
+        assert!(!is_remote(&file));
+    }
+}